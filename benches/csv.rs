@@ -0,0 +1,113 @@
+//! Compare writing CSV rows of line protocol by `CsvRowWriter` with serializing rows of
+//! owned values.
+//!
+//! Run by `cargo bench --bench csv`. Lines are generated by the `devops` use case, which
+//! has string tags, integer and float fields.
+
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use tsdb_tools::convert::{
+    Columns, CsvDialect, CsvRowWriter, LineProtocolReader, ParsedLine, TimeFormat, Value,
+};
+use tsdb_tools::generate::GenerateCommand;
+
+fn generate() -> String {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("devops.lp");
+    GenerateCommand::try_parse_from([
+        "generate",
+        "--use-case",
+        "devops",
+        "--scale",
+        "20",
+        "--end",
+        "2016-01-01T00:10:00Z",
+        "-o",
+        path.to_str().unwrap(),
+    ])
+    .unwrap()
+    .run()
+    .unwrap();
+    fs::read_to_string(path).unwrap()
+}
+
+/// Writes rows like `CsvRowWriter` did before, by a row of owned values.
+struct ValueRowWriter {
+    index: HashMap<String, usize>,
+    row: Vec<Option<Value>>,
+    writer: csv::Writer<io::Sink>,
+}
+
+impl ValueRowWriter {
+    fn new(columns: &Columns) -> ValueRowWriter {
+        let names = columns
+            .tags()
+            .iter()
+            .map(|name| name.as_str())
+            .chain(columns.fields().map(|(name, _)| name));
+        ValueRowWriter {
+            index: names
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), 1 + i))
+                .collect(),
+            row: Vec::new(),
+            writer: csv::Writer::from_writer(io::sink()),
+        }
+    }
+
+    fn write_line(&mut self, line: ParsedLine<'_>) {
+        self.row.clear();
+        self.row.resize_with(self.index.len() + 2, || None);
+        self.row[0] = Some(Value::from(line.series.measurement));
+        for (tagk, tagv) in line.series.tag_set.into_iter().flatten() {
+            self.row[self.index[tagk.as_str()]] = Some(Value::from(tagv));
+        }
+        for (fieldk, fieldv) in line.field_set {
+            self.row[self.index[fieldk.as_str()]] = Some(Value::from(fieldv));
+        }
+        if let Some(ts) = line.timestamp {
+            let ts = chrono::DateTime::from_timestamp_nanos(ts)
+                .to_rfc3339_opts(chrono::SecondsFormat::Nanos, false);
+            *self.row.last_mut().unwrap() = Some(Value::String(ts));
+        }
+        self.writer.serialize(&self.row).unwrap();
+    }
+}
+
+fn bench_csv(c: &mut Criterion) {
+    let input = generate();
+    let columns = LineProtocolReader::new(input.as_bytes()).columns().unwrap();
+
+    let mut group = c.benchmark_group("to-csv");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.bench_function("row-writer", |b| {
+        b.iter(|| {
+            let mut writer = CsvRowWriter::new(io::sink(), columns.clone(), CsvDialect::default())
+                .unwrap()
+                .time_format(TimeFormat::Rfc3339);
+            LineProtocolReader::new(input.as_bytes())
+                .for_each(|line| writer.write_line(line))
+                .unwrap();
+            writer.into_inner().unwrap()
+        })
+    });
+    group.bench_function("owned-values", |b| {
+        b.iter(|| {
+            let mut writer = ValueRowWriter::new(&columns);
+            LineProtocolReader::new(input.as_bytes())
+                .for_each(|line| {
+                    writer.write_line(line);
+                    Ok(())
+                })
+                .unwrap();
+            writer.writer.into_inner().unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_csv);
+criterion_main!(benches);
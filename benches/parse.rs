@@ -0,0 +1,78 @@
+//! Compare parsing and converting line protocol by the buffered reader and by the memory
+//! map.
+//!
+//! Run by `cargo bench --bench parse`. Lines are generated by the `cpu-only` use case,
+//! `TSDB_TOOLS_BENCH_SCALE` sets the number of hosts, which defaults to 10.
+
+use clap::Parser;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::fs::{self, File};
+use std::io;
+use tsdb_tools::convert::{
+    line_protocol_to_csv, CsvDialect, LineProtocolReader, MappedLineProtocol, TimeFormat,
+};
+use tsdb_tools::generate::GenerateCommand;
+
+fn bench_parse(c: &mut Criterion) {
+    let scale = std::env::var("TSDB_TOOLS_BENCH_SCALE").unwrap_or_else(|_| "10".to_string());
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cpu.lp");
+    GenerateCommand::try_parse_from(["generate", "--scale", &scale, "-o", path.to_str().unwrap()])
+        .unwrap()
+        .run()
+        .unwrap();
+    let bytes = fs::metadata(&path).unwrap().len();
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("buffered", |b| {
+        b.iter(|| {
+            LineProtocolReader::new(File::open(&path).unwrap())
+                .for_each(|_| Ok(()))
+                .unwrap()
+        })
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            MappedLineProtocol::open(&path)
+                .unwrap()
+                .for_each(|_| Ok(()))
+                .unwrap()
+        })
+    });
+    group.finish();
+
+    let columns = MappedLineProtocol::open(&path).unwrap().columns().unwrap();
+    let mut group = c.benchmark_group("to-csv");
+    group.throughput(Throughput::Bytes(bytes));
+    group.bench_function("buffered", |b| {
+        b.iter(|| {
+            line_protocol_to_csv(
+                File::open(&path).unwrap(),
+                io::sink(),
+                &columns,
+                CsvDialect::default(),
+                TimeFormat::Rfc3339,
+                1,
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("mmap", |b| {
+        b.iter(|| {
+            MappedLineProtocol::open(&path)
+                .unwrap()
+                .to_csv(
+                    io::sink(),
+                    &columns,
+                    CsvDialect::default(),
+                    TimeFormat::Rfc3339,
+                )
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
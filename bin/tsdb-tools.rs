@@ -1,12 +1,59 @@
 //! TSDB utilities.
 
-use clap::Parser;
+use clap::{ArgAction, CommandFactory, Parser};
+use std::env;
+use std::error::Error;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process;
+use tracing::{debug, Level};
+use tsdb_tools::clickhouse::ClickhouseCommand;
+use tsdb_tools::config::{self, ConfigCommand};
+use tsdb_tools::csv_tools::CsvCommand;
+use tsdb_tools::datadog::DatadogCommand;
+#[cfg(feature = "flight")]
+use tsdb_tools::flight::FlightCommand;
+use tsdb_tools::generate::GenerateCommand;
+use tsdb_tools::graphite::GraphiteCommand;
+#[cfg(feature = "greptime")]
+use tsdb_tools::greptime::GreptimeCommand;
 use tsdb_tools::influx::InfluxCommand;
+#[cfg(feature = "kafka")]
+use tsdb_tools::kafka::KafkaCommand;
+use tsdb_tools::opentsdb::OpenTsdbCommand;
+use tsdb_tools::otel::OtelCommand;
+use tsdb_tools::progress;
+use tsdb_tools::prom::PromCommand;
+use tsdb_tools::questdb::QuestdbCommand;
+use tsdb_tools::reject::{self, OnError};
+use tsdb_tools::timescale::TimescaleCommand;
+use tsdb_tools::vm::VmCommand;
+use tsdb_tools::wavefront::WavefrontCommand;
 
 /// TSDB utilities.
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 struct Command {
+    /// Show a progress bar on stderr and print a summary at the end.
+    #[arg(long, global = true)]
+    progress: bool,
+    /// Print debug logs, `-vv` prints trace logs.
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Print errors only.
+    #[arg(long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// What to do with invalid lines.
+    #[arg(long, global = true, value_enum, default_value_t = OnError::Abort)]
+    on_error: OnError,
+    /// File to write rejected lines to, each line follows a comment of its error.
+    #[arg(long, global = true)]
+    errors: Option<PathBuf>,
+    /// TOML file of the command and its options, options on the command line override it.
+    ///
+    /// Config files are written by `tsdb-tools config init`.
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
     #[clap(subcommand)]
     subcmd: Subcommand,
 }
@@ -16,12 +63,118 @@ struct Command {
 enum Subcommand {
     /// Subcommand for InfluxDB target.
     Influx(InfluxCommand),
+    /// Subcommand for Prometheus target.
+    Prom(PromCommand),
+    /// Subcommand for OpenTSDB target.
+    #[command(name = "opentsdb")]
+    OpenTsdb(OpenTsdbCommand),
+    /// Subcommand for Graphite target.
+    Graphite(GraphiteCommand),
+    /// Subcommand for VictoriaMetrics target.
+    Vm(VmCommand),
+    /// Subcommand for OpenTelemetry target.
+    Otel(OtelCommand),
+    /// Subcommand for TimescaleDB target.
+    Timescale(TimescaleCommand),
+    /// Subcommand for ClickHouse target.
+    Clickhouse(ClickhouseCommand),
+    /// Subcommand for QuestDB target.
+    Questdb(QuestdbCommand),
+    /// Subcommand for Datadog metrics.
+    Datadog(DatadogCommand),
+    /// Subcommand for Wavefront data format.
+    Wavefront(WavefrontCommand),
+    /// Subcommand for Arrow Flight endpoints.
+    #[cfg(feature = "flight")]
+    Flight(FlightCommand),
+    /// Subcommand for GreptimeDB target.
+    #[cfg(feature = "greptime")]
+    Greptime(GreptimeCommand),
+    /// Subcommand for Kafka.
+    #[cfg(feature = "kafka")]
+    Kafka(KafkaCommand),
+    /// Generate synthetic time series for benchmarks.
+    Generate(GenerateCommand),
+    /// Tools for CSV files.
+    Csv(CsvCommand),
+    /// Config files of commands.
+    Config(ConfigCommand),
+}
+
+impl Subcommand {
+    /// Run the subcommand.
+    fn run(self) -> tsdb_tools::Result<()> {
+        match self {
+            Subcommand::Influx(influx) => influx.run(),
+            Subcommand::Prom(prom) => prom.run(),
+            Subcommand::OpenTsdb(opentsdb) => opentsdb.run(),
+            Subcommand::Graphite(graphite) => graphite.run(),
+            Subcommand::Vm(vm) => vm.run(),
+            Subcommand::Otel(otel) => otel.run(),
+            Subcommand::Timescale(timescale) => timescale.run(),
+            Subcommand::Clickhouse(clickhouse) => clickhouse.run(),
+            Subcommand::Questdb(questdb) => questdb.run(),
+            Subcommand::Datadog(datadog) => datadog.run(),
+            Subcommand::Wavefront(wavefront) => wavefront.run(),
+            #[cfg(feature = "flight")]
+            Subcommand::Flight(flight) => flight.run(),
+            #[cfg(feature = "greptime")]
+            Subcommand::Greptime(greptime) => greptime.run(),
+            #[cfg(feature = "kafka")]
+            Subcommand::Kafka(kafka) => kafka.run(),
+            Subcommand::Generate(generate) => generate.run(),
+            Subcommand::Csv(csv) => csv.run(),
+            Subcommand::Config(config) => config.run(&Command::command()),
+        }
+    }
 }
 
 fn main() {
-    let cmd = Command::parse();
+    let args = config::expand_args(&Command::command(), env::args_os().collect())
+        .unwrap_or_else(|e| exit(e));
+    let cmd = Command::parse_from(args);
+    init_logging(cmd.verbose, cmd.quiet);
+    if let Some(path) = &cmd.config {
+        debug!("Loaded config {}", path.display());
+    }
+    if cmd.progress {
+        progress::enable();
+    }
+
+    let result = reject::init(cmd.on_error, cmd.errors.as_deref())
+        .and_then(|()| cmd.subcmd.run())
+        .and(reject::finish());
+    progress::finish();
 
-    match cmd.subcmd {
-        Subcommand::Influx(influx) => influx.run(),
+    if let Err(e) = result {
+        exit(e);
     }
 }
+
+/// Print the error with all its causes and exit.
+fn exit(e: tsdb_tools::Error) -> ! {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    eprintln!("Error: {message}");
+    process::exit(1);
+}
+
+/// Print logs of the verbosity to stderr.
+fn init_logging(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(progress::LogWriter::default)
+        .init();
+}
@@ -0,0 +1,581 @@
+//! Tools for ClickHouse target.
+
+use crate::error::{
+    CreateDirSnafu, FieldTypeConflictSnafu, RequestStatusSnafu, Result, SendRequestSnafu,
+    WriteOutputSnafu,
+};
+use crate::influx::read_lines;
+use crate::influx::schema::{Dialect, FieldType, Schema};
+use crate::influx::split::file_name;
+use crate::io::{create_output, Compression, Output, ReplayableInput};
+use crate::reject;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::{ensure, ResultExt};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use tracing::info;
+
+/// ClickHouse command.
+#[derive(Debug, Parser)]
+pub struct ClickhouseCommand {
+    #[clap(subcommand)]
+    subcmd: ClickhouseSubcommand,
+}
+
+impl ClickhouseCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            ClickhouseSubcommand::Convert(c) => c.run(),
+            ClickhouseSubcommand::Insert(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for ClickHouse.
+#[derive(Debug, Parser)]
+enum ClickhouseSubcommand {
+    /// Line protocol to files of tables and DDL.
+    Convert(Convert),
+    /// Insert line protocol into ClickHouse by the HTTP interface.
+    Insert(Insert),
+}
+
+/// Input format of ClickHouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// `TabSeparatedWithNames`, with a header of column names.
+    Tsv,
+    /// `RowBinary`, values in the binary format of the column types.
+    RowBinary,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Tsv => "TabSeparatedWithNames",
+            Format::RowBinary => "RowBinary",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Tsv => "tsv",
+            Format::RowBinary => "rowbinary",
+        }
+    }
+}
+
+/// Convert line protocol file to a file of each measurement in a ClickHouse format and
+/// the statements to create the MergeTree tables.
+///
+/// The output directory has `schema.sql` and files named by the measurements, e.g.
+/// `cpu.tsv`. Columns are tags, fields and the timestamp in the order of the table,
+/// missing tags are empty strings. The input is read twice, first to infer the schemas.
+#[derive(Debug, Parser)]
+struct Convert {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Format of the files.
+    #[arg(long, value_enum, default_value_t = Format::Tsv)]
+    format: Format,
+    /// Timestamp column name.
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+    /// Compression of the output files, which adds the extension to file names.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    output_compression: Compression,
+}
+
+impl Convert {
+    fn run(self) -> Result<()> {
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let tables = Tables::infer(&input, &self.timestamp)?;
+
+        let mut output = create_output(self.output_dir.join("schema.sql"), Compression::None)?;
+        for (measurement, schema) in &tables.schemas {
+            let sql = schema.create_table(measurement, &self.timestamp, Dialect::Clickhouse);
+            writeln!(output, "{sql}").context(WriteOutputSnafu)?;
+        }
+        output.finish().context(WriteOutputSnafu)?;
+
+        let extension = self.output_compression.extension();
+        let mut outputs = Vec::with_capacity(tables.schemas.len());
+        let mut row = Vec::new();
+        for (i, (measurement, _)) in tables.schemas.iter().enumerate() {
+            let name = file_name(measurement);
+            let path = self
+                .output_dir
+                .join(format!("{name}.{}{extension}", self.format.extension()));
+            let mut output = BufWriter::new(create_output(path, self.output_compression)?);
+            output
+                .write_all(&tables.header(i, self.format))
+                .context(WriteOutputSnafu)?;
+            outputs.push(output);
+        }
+        read_lines(input.reader()?, |line| {
+            row.clear();
+            let idx = tables.table_index(&line);
+            tables.push_row(idx, &line, self.format, &mut row)?;
+            outputs[idx].write_all(&row).context(WriteOutputSnafu)
+        })?;
+        for output in outputs {
+            let mut output = output
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context(WriteOutputSnafu)?;
+            output.finish().context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+/// Insert line protocol file into ClickHouse by `INSERT` queries of the HTTP interface.
+///
+/// Tables are created if they don't exist, then rows of each table are inserted in
+/// batches. The input is read twice, first to infer the schemas.
+#[derive(Debug, Parser)]
+struct Insert {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// URL of the HTTP interface, e.g. http://localhost:8123.
+    #[arg(long)]
+    url: String,
+    /// Database of the tables.
+    #[arg(long, default_value = "default")]
+    database: String,
+    /// User name.
+    #[arg(long)]
+    user: Option<String>,
+    /// Password of the user.
+    #[arg(long)]
+    password: Option<String>,
+    /// Format of the inserted data.
+    #[arg(long, value_enum, default_value_t = Format::RowBinary)]
+    format: Format,
+    /// Timestamp column name.
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+    /// Max rows of a table in an insert.
+    #[arg(long, default_value_t = 100000, value_parser = clap::value_parser!(u64).range(1..))]
+    batch_size: u64,
+    /// Don't create the tables.
+    #[arg(long)]
+    no_create: bool,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Insert {
+    fn run(self) -> Result<()> {
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let tables = Tables::infer(&input, &self.timestamp)?;
+        if !self.no_create {
+            for (measurement, schema) in &tables.schemas {
+                let sql = schema.create_table(measurement, &self.timestamp, Dialect::Clickhouse);
+                self.query(&sql, &[])?;
+            }
+        }
+
+        let mut batches: Vec<_> = (0..tables.schemas.len())
+            .map(|i| Batch {
+                insert: tables.insert_statement(i, &self.database, self.format),
+                header: tables.header(i, self.format),
+                data: Vec::new(),
+                rows: 0,
+            })
+            .collect();
+        let mut total_rows = 0;
+        read_lines(input.reader()?, |line| {
+            let idx = tables.table_index(&line);
+            let batch = &mut batches[idx];
+            tables.push_row(idx, &line, self.format, &mut batch.data)?;
+            batch.rows += 1;
+            total_rows += 1;
+            if batch.rows >= self.batch_size {
+                self.insert(batch)?;
+            }
+            Ok(())
+        })?;
+        for batch in &mut batches {
+            self.insert(batch)?;
+        }
+
+        info!("Inserted {total_rows} rows into {} tables", batches.len());
+        Ok(())
+    }
+
+    fn insert(&self, batch: &mut Batch) -> Result<()> {
+        if batch.rows > 0 {
+            let mut body = batch.header.clone();
+            body.extend(&batch.data);
+            self.query(&batch.insert, &body)?;
+        }
+        batch.data.clear();
+        batch.rows = 0;
+        Ok(())
+    }
+
+    /// Send the query with the data of `INSERT` in the body.
+    fn query(&self, sql: &str, data: &[u8]) -> Result<()> {
+        let url = self.url.trim_end_matches('/');
+        let mut request = ureq::post(url)
+            .query("query", sql)
+            .query("database", &self.database);
+        if let Some(user) = &self.user {
+            request = request.set("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.set("X-ClickHouse-Key", password);
+        }
+        match request.send_bytes(data) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => RequestStatusSnafu {
+                url,
+                status,
+                body: response.into_string().unwrap_or_default(),
+            }
+            .fail(),
+            Err(ureq::Error::Transport(e)) => Err(Box::new(e)).context(SendRequestSnafu { url }),
+        }
+    }
+}
+
+/// Rows of a table to insert.
+struct Batch {
+    insert: String,
+    /// Header of the format before rows.
+    header: Vec<u8>,
+    data: Vec<u8>,
+    rows: u64,
+}
+
+/// Schemas of measurements as tables.
+struct Tables {
+    schemas: Vec<(String, Schema)>,
+    /// Index of schemas by the measurement.
+    index: HashMap<String, usize>,
+    timestamp: String,
+    now: i64,
+}
+
+impl Tables {
+    fn infer(input: &ReplayableInput, timestamp: &str) -> Result<Tables> {
+        let schemas = reject::silently(|| Schema::infer_measurements(input.reader()?))?;
+        let index = schemas
+            .iter()
+            .enumerate()
+            .map(|(i, (measurement, _))| (measurement.clone(), i))
+            .collect();
+        Ok(Tables {
+            schemas,
+            index,
+            timestamp: timestamp.to_string(),
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        })
+    }
+
+    /// Returns the index of the table of the line.
+    fn table_index(&self, line: &ParsedLine<'_>) -> usize {
+        // Schemas have all measurements of the input.
+        self.index[line.series.measurement.as_str()]
+    }
+
+    /// Returns the columns of the table in order.
+    fn columns(&self, idx: usize) -> impl Iterator<Item = &str> {
+        let schema = &self.schemas[idx].1;
+        schema
+            .tags
+            .iter()
+            .map(|tag| tag.as_str())
+            .chain(schema.fields.iter().map(|(field, _)| field.as_str()))
+            .chain([self.timestamp.as_str()])
+    }
+
+    fn insert_statement(&self, idx: usize, database: &str, format: Format) -> String {
+        let quote = |ident: &str| Dialect::Clickhouse.quote(ident);
+        let columns: Vec<_> = self.columns(idx).map(quote).collect();
+        format!(
+            "INSERT INTO {}.{} ({}) FORMAT {}",
+            quote(database),
+            quote(&self.schemas[idx].0),
+            columns.join(", "),
+            format.name()
+        )
+    }
+
+    /// Returns the header before rows of the table in the format, which has column names
+    /// in `TabSeparatedWithNames`.
+    fn header(&self, idx: usize, format: Format) -> Vec<u8> {
+        let mut header = Vec::new();
+        if format == Format::Tsv {
+            for (i, column) in self.columns(idx).enumerate() {
+                if i > 0 {
+                    header.push(b'\t');
+                }
+                push_tsv_text(&mut header, column);
+            }
+            header.push(b'\n');
+        }
+        header
+    }
+
+    /// Append the line as a row of the table in the format.
+    ///
+    /// Timestamps are written as Unix timestamps, and lines without timestamp have the
+    /// current time. Returns an error without appending anything if a value doesn't match
+    /// the type of its column, e.g. the line is skipped by the inference pass.
+    fn push_row(
+        &self,
+        idx: usize,
+        line: &ParsedLine<'_>,
+        format: Format,
+        buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        let schema = &self.schemas[idx].1;
+        let mut tags = vec![""; schema.tags.len()];
+        for (key, value) in line.series.tag_set.iter().flatten() {
+            if let Some(i) = schema.tag_index(key.as_str()) {
+                tags[i] = value.as_str();
+            }
+        }
+        let mut fields = vec![None; schema.fields.len()];
+        for (key, value) in &line.field_set {
+            if let Some(i) = schema.field_index(key.as_str()) {
+                let field_type = schema.fields[i].1;
+                ensure!(
+                    field_type.holds(FieldType::of(value)),
+                    FieldTypeConflictSnafu {
+                        field: key.as_str(),
+                        current: field_type.to_string(),
+                        other: FieldType::of(value).to_string(),
+                    }
+                );
+                fields[i] = Some(value);
+            }
+        }
+        let ts = line.timestamp.unwrap_or(self.now);
+
+        match format {
+            Format::Tsv => {
+                for tag in tags {
+                    push_tsv_text(buf, tag);
+                    buf.push(b'\t');
+                }
+                for field in fields {
+                    push_tsv_value(buf, field);
+                    buf.push(b'\t');
+                }
+                let (secs, nanos) = (ts.div_euclid(1_000_000_000), ts.rem_euclid(1_000_000_000));
+                writeln!(buf, "{secs}.{nanos:09}").unwrap();
+            }
+            Format::RowBinary => {
+                for tag in tags {
+                    push_binary_string(buf, tag);
+                }
+                for ((_, field_type), field) in schema.fields.iter().zip(fields) {
+                    push_binary_value(buf, *field_type, field);
+                }
+                // DateTime64(9) is the number of nanoseconds.
+                buf.extend(ts.to_le_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Append the text escaped for `TabSeparated`.
+fn push_tsv_text(buf: &mut Vec<u8>, text: &str) {
+    for b in text.bytes() {
+        match b {
+            b'\\' => buf.extend(b"\\\\"),
+            b'\t' => buf.extend(b"\\t"),
+            b'\n' => buf.extend(b"\\n"),
+            b'\r' => buf.extend(b"\\r"),
+            b'\0' => buf.extend(b"\\0"),
+            b => buf.push(b),
+        }
+    }
+}
+
+fn push_tsv_value(buf: &mut Vec<u8>, value: Option<&FieldValue<'_>>) {
+    match value {
+        None => buf.extend(b"\\N"),
+        Some(FieldValue::String(v)) => push_tsv_text(buf, v.as_str()),
+        Some(FieldValue::Boolean(v)) => write!(buf, "{v}").unwrap(),
+        Some(FieldValue::F64(v)) if v.is_nan() => buf.extend(b"nan"),
+        Some(FieldValue::F64(v)) => write!(buf, "{v}").unwrap(),
+        Some(FieldValue::I64(v)) => write!(buf, "{v}").unwrap(),
+        Some(FieldValue::U64(v)) => write!(buf, "{v}").unwrap(),
+    }
+}
+
+/// Append the string with its LEB128 length.
+fn push_binary_string(buf: &mut Vec<u8>, s: &str) {
+    let mut len = s.len() as u64;
+    while len >= 0x80 {
+        buf.push((len as u8) | 0x80);
+        len >>= 7;
+    }
+    buf.push(len as u8);
+    buf.extend(s.as_bytes());
+}
+
+/// Append the value of a nullable column of the type, which holds the value, integers of
+/// float columns are converted.
+fn push_binary_value(buf: &mut Vec<u8>, field_type: FieldType, value: Option<&FieldValue<'_>>) {
+    let Some(value) = value else {
+        buf.push(1);
+        return;
+    };
+    buf.push(0);
+    match (field_type, value) {
+        (FieldType::Float64, FieldValue::I64(v)) => buf.extend((*v as f64).to_le_bytes()),
+        (FieldType::Float64, FieldValue::U64(v)) => buf.extend((*v as f64).to_le_bytes()),
+        (_, FieldValue::I64(v)) => buf.extend(v.to_le_bytes()),
+        (_, FieldValue::U64(v)) => buf.extend(v.to_le_bytes()),
+        (_, FieldValue::F64(v)) => buf.extend(v.to_le_bytes()),
+        (_, FieldValue::Boolean(v)) => buf.push(u8::from(*v)),
+        (_, FieldValue::String(v)) => push_binary_string(buf, v.as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve_statuses;
+
+    #[test]
+    fn test_convert() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.lp");
+        fs::write(
+            &input,
+            "cpu,host=a usage=1.5 1700000000123456789
+cpu usage=2i,up=true,state=\"a\tb\" 1700000001000000000
+",
+        )
+        .unwrap();
+        let output_dir = dir.path().join("out");
+        let convert = |format| {
+            let args = format!(
+                "convert -i {} -o {} --format {format}",
+                input.display(),
+                output_dir.display()
+            );
+            Convert::try_parse_from(args.split(' '))
+                .unwrap()
+                .run()
+                .unwrap();
+        };
+
+        convert("tsv");
+        let schema = fs::read_to_string(output_dir.join("schema.sql")).unwrap();
+        assert!(
+            schema.contains("ORDER BY (`host`, `timestamp`);"),
+            "{schema}"
+        );
+        assert_eq!(
+            "host\tusage\tup\tstate\ttimestamp
+a\t1.5\t\\N\t\\N\t1700000000.123456789
+\t2\ttrue\ta\\tb\t1700000001.000000000
+",
+            fs::read_to_string(output_dir.join("cpu.tsv")).unwrap()
+        );
+
+        convert("row-binary");
+        let mut expected = vec![1, b'a', 0];
+        expected.extend(1.5f64.to_le_bytes());
+        expected.extend([1, 1]);
+        expected.extend(1700000000123456789i64.to_le_bytes());
+        // Integers of float columns are converted.
+        expected.extend([0, 0]);
+        expected.extend(2f64.to_le_bytes());
+        expected.extend([0, 1, 0, 3, b'a', b'\t', b'b']);
+        expected.extend(1700000001000000000i64.to_le_bytes());
+        assert_eq!(
+            expected,
+            fs::read(output_dir.join("cpu.rowbinary")).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_push_row_type_conflict() {
+        let schemas = Schema::infer_measurements("cpu v=1i 1\n".as_bytes()).unwrap();
+        let tables = Tables {
+            schemas,
+            index: HashMap::from([("cpu".to_string(), 0)]),
+            timestamp: "timestamp".to_string(),
+            now: 0,
+        };
+        let line = influxdb_line_protocol::parse_lines("cpu v=\"x\" 2")
+            .next()
+            .unwrap()
+            .unwrap();
+        let mut buf = Vec::new();
+        let err = tables
+            .push_row(0, &line, Format::RowBinary, &mut buf)
+            .unwrap_err();
+        assert_eq!(
+            "Field v has conflicting types integer and string",
+            err.to_string()
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_insert() {
+        let (url, handle) = serve_statuses(vec![200, 200]);
+
+        let input = tempfile::NamedTempFile::new().unwrap();
+        fs::write(
+            input.path(),
+            "cpu usage=1 1\ncpu usage=2 2\ncpu usage=3 3\n",
+        )
+        .unwrap();
+        let args = format!(
+            "insert -i {} --url {url} --format tsv --batch-size 2 --no-create",
+            input.path().display()
+        );
+        Insert::try_parse_from(args.split(' '))
+            .unwrap()
+            .run()
+            .unwrap();
+
+        let requests = handle.join().unwrap();
+        assert!(
+            requests[0]
+                .0
+                .starts_with("POST /?query=INSERT+INTO+%60default%60.%60cpu%60+"),
+            "{}",
+            requests[0].0
+        );
+        let bodies: Vec<_> = requests
+            .iter()
+            .map(|(_, body)| std::str::from_utf8(body).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "usage\ttimestamp\n1\t0.000000001\n2\t0.000000002\n",
+                "usage\ttimestamp\n3\t0.000000003\n"
+            ],
+            bodies
+        );
+    }
+}
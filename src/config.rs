@@ -0,0 +1,431 @@
+//! Config files of commands.
+//!
+//! A config file is a TOML file of the subcommand to run and its options, keyed by long
+//! names of options, e.g.
+//!
+//! ```toml
+//! command = "influx from-csv"
+//! input = "data/*.csv"
+//! tag = ["host", "region"]
+//! infer-types = true
+//! ```
+
+use crate::error::{
+    InvalidConfigSnafu, ParseConfigSnafu, ReadFileSnafu, Result, UnknownCommandSnafu,
+    WriteOutputSnafu,
+};
+use crate::io::{create_output, Compression, Output};
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Parser};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use toml::{Table, Value};
+
+/// Config file command.
+#[derive(Debug, Parser)]
+pub struct ConfigCommand {
+    #[clap(subcommand)]
+    subcmd: ConfigSubcommand,
+}
+
+impl ConfigCommand {
+    /// Run this command, options are looked up in the root command.
+    pub fn run(self, root: &clap::Command) -> Result<()> {
+        match self.subcmd {
+            ConfigSubcommand::Init(c) => c.run(root),
+        }
+    }
+}
+
+/// Subcommands for config files.
+#[derive(Debug, Parser)]
+enum ConfigSubcommand {
+    /// Write a config file of a command.
+    Init(Init),
+}
+
+/// Write a config file of the command with the options given after it, other options are
+/// commented out with their defaults.
+///
+/// e.g. `tsdb-tools config init -o job.toml influx from-csv -i data.csv --tag host`.
+#[derive(Debug, Parser)]
+struct Init {
+    /// Output config file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// The command and its options.
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+impl Init {
+    fn run(self, root: &clap::Command) -> Result<()> {
+        let config = init_config(root, &self.command)?;
+        let mut output = create_output(&self.output, Compression::None)?;
+        output
+            .write_all(config.as_bytes())
+            .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Returns the config of the command words and options in the arguments.
+fn init_config(root: &clap::Command, args: &[String]) -> Result<String> {
+    let (words, leaf) = find_command(root, args.iter().map(String::as_str))?;
+    let options = &args[words.len()..];
+    let globals = global_args(root);
+    let parser = leaf
+        .clone()
+        .no_binary_name(true)
+        .ignore_errors(true)
+        .args(globals.iter().cloned());
+    let matches = parser.try_get_matches_from(options).unwrap_or_default();
+
+    let command = words.join(" ");
+    let mut config = format!(
+        "# Config of `tsdb-tools {command}`, run by `tsdb-tools --config <file>`.\n\
+         # Options on the command line override values in this file.\n\
+         command = {}\n",
+        Value::from(command.as_str())
+    );
+    let options = leaf.get_arguments().chain(&globals);
+    for arg in options.filter(|arg| is_option(arg)) {
+        let id = arg.get_id().as_str();
+        let key = arg.get_long().unwrap_or(id);
+        config.push('\n');
+        if let Some(help) = arg.get_help() {
+            let help = help.to_string();
+            writeln!(config, "# {}", help.lines().next().unwrap_or_default()).unwrap();
+        }
+        if matches.value_source(id) == Some(ValueSource::CommandLine) {
+            let value = match arg.get_action() {
+                ArgAction::SetTrue | ArgAction::SetFalse => Value::from(matches.get_flag(id)),
+                ArgAction::Count => Value::from(i64::from(matches.get_count(id))),
+                action => {
+                    let values = matches.get_raw(id).into_iter().flatten();
+                    let mut values = values.map(|v| toml_value(&v.to_string_lossy()));
+                    match action {
+                        ArgAction::Append => Value::Array(values.collect()),
+                        _ => values.next().unwrap_or_else(|| Value::from("")),
+                    }
+                }
+            };
+            writeln!(config, "{key} = {value}").unwrap();
+        } else {
+            let defaults = arg.get_default_values().iter();
+            let mut defaults = defaults.map(|v| toml_value(&v.to_string_lossy()));
+            let value = match arg.get_action() {
+                ArgAction::SetTrue => Value::from(false),
+                ArgAction::SetFalse => Value::from(true),
+                ArgAction::Count => Value::from(0),
+                ArgAction::Append => Value::Array(defaults.collect()),
+                _ => defaults.next().unwrap_or_else(|| Value::from("")),
+            };
+            writeln!(config, "# {key} = {value}").unwrap();
+        }
+    }
+    Ok(config)
+}
+
+/// Returns the arguments with the `--config` file expanded.
+///
+/// Options of the file are inserted after the command words of it if they are not on
+/// the command line, so options on the command line override the file. Arguments
+/// without `--config` are returned as is.
+pub fn expand_args(root: &clap::Command, args: Vec<OsString>) -> Result<Vec<OsString>> {
+    let mut path = None;
+    let mut rest = Vec::with_capacity(args.len());
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            rest.push(arg);
+            rest.extend(iter.by_ref());
+        } else if arg == "--config" {
+            rest.push(arg);
+            if let Some(value) = iter.next() {
+                path = Some(value.clone());
+                rest.push(value);
+            }
+        } else {
+            if let Some(value) = arg.to_str().and_then(|a| a.strip_prefix("--config=")) {
+                path = Some(OsString::from(value));
+            }
+            rest.push(arg);
+        }
+    }
+    let Some(path) = path else {
+        return Ok(rest);
+    };
+    let path = Path::new(&path);
+    let content = fs::read_to_string(path).context(ReadFileSnafu { path })?;
+    let mut table: Table = toml::from_str(&content)
+        .map_err(Box::new)
+        .context(ParseConfigSnafu { path })?;
+
+    let command = match table.remove("command") {
+        Some(Value::String(command)) => command,
+        _ => {
+            return InvalidConfigSnafu {
+                path,
+                reason: "command is required",
+            }
+            .fail()
+        }
+    };
+    let (words, leaf) = find_command(root, command.split_whitespace())?;
+    let globals = global_args(root);
+    let options: Vec<_> = leaf.get_arguments().chain(&globals).collect();
+    // The command is the one in the file, remove the same command on the command line.
+    let mut cli = rest.split_off(1.min(rest.len()));
+    if let Some(pos) = cli.windows(words.len()).position(|w| w.iter().eq(&words)) {
+        cli.drain(pos..pos + words.len());
+    }
+
+    rest.extend(words.iter().map(OsString::from));
+    for (key, value) in table {
+        let name = key.replace('_', "-");
+        let arg = options
+            .iter()
+            .find(|arg| is_option(arg) && arg.get_long() == Some(&name))
+            .with_context(|| InvalidConfigSnafu {
+                path,
+                reason: format!("unknown option {key} of {command}"),
+            })?;
+        if !is_given(arg, &cli) {
+            rest.extend(option_args(arg, &value).context(InvalidConfigSnafu {
+                path,
+                reason: format!("invalid value of {key}"),
+            })?);
+        }
+    }
+    rest.extend(cli);
+    Ok(rest)
+}
+
+/// Returns the words of the subcommand in the arguments and the subcommand, which is a
+/// command without subcommands.
+fn find_command<'a, 'b>(
+    root: &'a clap::Command,
+    args: impl Iterator<Item = &'b str>,
+) -> Result<(Vec<&'b str>, &'a clap::Command)> {
+    let mut words = Vec::new();
+    let mut command = root;
+    for arg in args {
+        if !command.has_subcommands() {
+            break;
+        }
+        command = command
+            .find_subcommand(arg)
+            .with_context(|| UnknownCommandSnafu {
+                command: words
+                    .iter()
+                    .chain([&arg])
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            })?;
+        words.push(arg);
+    }
+    ensure!(
+        !command.has_subcommands(),
+        UnknownCommandSnafu {
+            command: words.join(" ")
+        }
+    );
+    Ok((words, command))
+}
+
+/// Returns global options of the root command that can be in config files.
+fn global_args(root: &clap::Command) -> Vec<Arg> {
+    root.get_arguments()
+        .filter(|arg| arg.is_global_set() && arg.get_id() != "config")
+        .cloned()
+        .collect()
+}
+
+/// Returns true if the argument is an option with a long name, except help and version.
+fn is_option(arg: &Arg) -> bool {
+    arg.get_long().is_some()
+        && !matches!(
+            arg.get_action(),
+            ArgAction::Help | ArgAction::HelpShort | ArgAction::HelpLong | ArgAction::Version
+        )
+}
+
+/// Returns true if the option is in the arguments of the command line.
+fn is_given(arg: &Arg, args: &[OsString]) -> bool {
+    let long = arg.get_long().map(|long| format!("--{long}"));
+    let short = arg.get_short();
+    args.iter()
+        .map_while(|arg| arg.to_str().filter(|&arg| arg != "--"))
+        .any(|token| {
+            let is_long = long.as_deref().is_some_and(|long| {
+                token == long || token.strip_prefix(long).is_some_and(|v| v.starts_with('='))
+            });
+            let is_short = short.is_some_and(|short| {
+                !token.starts_with("--")
+                    && token.strip_prefix('-').and_then(|v| v.chars().next()) == Some(short)
+            });
+            is_long || is_short
+        })
+}
+
+/// Returns the arguments of the option of the value, `None` if the value is invalid.
+fn option_args(arg: &Arg, value: &Value) -> Option<Vec<OsString>> {
+    let long = arg.get_long()?;
+    let values = match value {
+        Value::Array(values) => values.iter().collect(),
+        value => vec![value],
+    };
+    let mut args = Vec::new();
+    for value in values {
+        match (arg.get_action(), value) {
+            (ArgAction::SetTrue | ArgAction::SetFalse, Value::Boolean(flag)) => {
+                // The flag is set if the value is not the default one.
+                if *flag == matches!(arg.get_action(), ArgAction::SetTrue) {
+                    args.push(format!("--{long}").into());
+                }
+            }
+            (ArgAction::Count, Value::Integer(count)) => {
+                let count = usize::try_from(*count).ok()?;
+                args.extend((0..count).map(|_| format!("--{long}").into()));
+            }
+            (ArgAction::Set | ArgAction::Append, value) => {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Integer(_) | Value::Float(_) | Value::Boolean(_) => value.to_string(),
+                    Value::Datetime(dt) => dt.to_string(),
+                    Value::Array(_) | Value::Table(_) => return None,
+                };
+                // Values like `-` for stdin are kept in the same argument.
+                args.push(format!("--{long}={value}").into());
+            }
+            _ => return None,
+        }
+    }
+    Some(args)
+}
+
+/// Returns the TOML value of the argument value, numbers are kept as numbers.
+fn toml_value(value: &str) -> Value {
+    if let Some(v) = value.parse::<i64>().ok().filter(|v| v.to_string() == value) {
+        return Value::from(v);
+    }
+    if let Some(v) = value.parse::<f64>().ok().filter(|v| v.to_string() == value) {
+        return Value::from(v);
+    }
+    Value::from(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, Subcommand};
+    use std::path::PathBuf;
+
+    #[derive(Debug, Parser)]
+    struct Root {
+        #[arg(long, global = true)]
+        progress: bool,
+        #[arg(long, global = true)]
+        config: Option<PathBuf>,
+        #[clap(subcommand)]
+        subcmd: Group,
+    }
+
+    #[derive(Debug, Subcommand)]
+    enum Group {
+        #[command(subcommand)]
+        Influx(Leaf),
+    }
+
+    #[derive(Debug, Subcommand)]
+    enum Leaf {
+        FromCsv {
+            /// Input file path.
+            #[arg(short, long)]
+            input: String,
+            #[arg(short, long)]
+            output: String,
+            /// Tag names.
+            #[arg(long)]
+            tag: Vec<String>,
+            #[arg(long, default_value_t = 1)]
+            threads: usize,
+            #[arg(long)]
+            infer_types: bool,
+        },
+    }
+
+    #[test]
+    fn test_init_and_expand() {
+        let root = Root::command();
+        let args = "influx from-csv -i - --tag host --tag region --threads 4 --progress";
+        let args: Vec<_> = args.split(' ').map(String::from).collect();
+        let config = init_config(&root, &args).unwrap();
+        assert_eq!(
+            "# Config of `tsdb-tools influx from-csv`, run by `tsdb-tools --config <file>`.
+# Options on the command line override values in this file.
+command = \"influx from-csv\"
+
+# Input file path
+input = \"-\"
+
+# output = \"\"
+
+# Tag names
+tag = [\"host\", \"region\"]
+
+threads = 4
+
+# infer-types = false
+
+progress = true
+",
+            config
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("job.toml");
+        let config = config.replace("# output = \"\"", "output = \"a.lp\"");
+        fs::write(
+            &path,
+            config.replace("# infer-types = false", "infer_types = true"),
+        )
+        .unwrap();
+        let config_arg = format!("--config={}", path.display());
+        let args = [
+            "tsdb-tools",
+            &config_arg,
+            "influx",
+            "from-csv",
+            "-o",
+            "b.lp",
+            "--tag",
+            "dc",
+        ];
+        let args = expand_args(&root, args.iter().map(OsString::from).collect()).unwrap();
+        let root = Root::try_parse_from(args).unwrap();
+        assert!(root.progress);
+        let Group::Influx(Leaf::FromCsv {
+            input,
+            output,
+            tag,
+            threads,
+            infer_types,
+        }) = root.subcmd;
+        assert_eq!(
+            ("-", "b.lp", 4, true),
+            (&*input, &*output, threads, infer_types)
+        );
+        assert_eq!(vec!["dc"], tag);
+
+        fs::write(&path, "command = \"influx\"\n").unwrap();
+        let args = ["tsdb-tools", "--config", path.to_str().unwrap()];
+        assert!(expand_args(&Root::command(), args.iter().map(OsString::from).collect()).is_err());
+    }
+}
@@ -0,0 +1,649 @@
+//! Conversions between line protocol and CSV for other programs.
+//!
+//! The conversions of `influx to-csv` and `influx from-csv` work on any reader and
+//! writer, e.g. to convert line protocol in memory to CSV:
+//!
+//! ```
+//! use tsdb_tools::convert::{CsvDialect, CsvRowWriter, LineProtocolReader};
+//!
+//! let input = "cpu,host=a usage=1.5 1451606400000000000\n";
+//! // The first pass collects the columns of the CSV.
+//! let columns = LineProtocolReader::new(input.as_bytes()).columns()?;
+//! let mut writer = CsvRowWriter::new(Vec::new(), columns, CsvDialect::default())?;
+//! LineProtocolReader::new(input.as_bytes()).for_each(|line| writer.write_line(line))?;
+//!
+//! let csv = String::from_utf8(writer.into_inner()?).unwrap();
+//! assert_eq!(
+//!     "measurement,host,usage,timestamp\ncpu,a,1.5,2016-01-01T00:00:00.000000000+00:00\n",
+//!     csv
+//! );
+//! # Ok::<(), tsdb_tools::Error>(())
+//! ```
+//!
+//! [LineProtocolIter] yields owned points for other sinks:
+//!
+//! ```
+//! use tsdb_tools::convert::{LineProtocolIter, Value};
+//!
+//! let input = "# comment\ncpu,host=a usage=1.5,ok=true 1451606400000000000\n";
+//! let points = LineProtocolIter::new(input.as_bytes()).collect::<Result<Vec<_>, _>>()?;
+//! assert_eq!("cpu", points[0].measurement);
+//! assert_eq!(("ok".to_string(), Value::Boolean(true)), points[0].fields[1]);
+//! assert_eq!(
+//!     "cpu,host=a usage=1.5,ok=true 1451606400000000000",
+//!     points[0].to_string()
+//! );
+//! # Ok::<(), tsdb_tools::Error>(())
+//! ```
+//!
+//! [MappedLineProtocol] parses huge files by a memory map instead of a reader.
+
+use crate::error::{
+    LineSnafu, ParseLineSnafu, ReadInputSnafu, Result, WriteCsvSnafu, WriteOutputSnafu,
+};
+pub use crate::influx::schema::FieldType;
+use crate::influx::schema::Schema;
+use crate::influx::timestamp::TimestampParser;
+pub use crate::influx::timestamp::{Precision, TimeFormat};
+pub use crate::influx::CsvDialect;
+pub use crate::influx::Value;
+use crate::influx::{
+    self, csv_headers, mapped_text, push_fields, push_series, read_blocks_while, read_text_lines,
+    write_csv_row, CsvRow, LineWriter, DEFAULT_BLOCK_SIZE, DEFAULT_BUFFER_SIZE,
+};
+use crate::io::{Compression, Mmap};
+use crate::reject;
+use csv::Writer;
+pub use influxdb_line_protocol::ParsedLine;
+use influxdb_line_protocol::{EscapedStr, FieldValue};
+use snafu::ResultExt;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Tag and field columns of line protocol, in the order they first appear.
+#[derive(Debug, Clone, Default)]
+pub struct Columns {
+    schema: Schema,
+}
+
+impl Columns {
+    /// Returns names of tags.
+    pub fn tags(&self) -> &[String] {
+        &self.schema.tags
+    }
+
+    /// Returns names and types of fields.
+    pub fn fields(&self) -> impl Iterator<Item = (&str, FieldType)> {
+        self.schema
+            .fields
+            .iter()
+            .map(|(name, field_type)| (name.as_str(), *field_type))
+    }
+
+    /// Add columns of the line.
+    ///
+    /// Returns an error if a field has conflicting types.
+    pub fn update(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        self.schema.update(line)
+    }
+
+    /// Returns the header of CSV, which has the measurement, tags, fields and the
+    /// timestamp.
+    pub fn csv_header(&self) -> Vec<&str> {
+        csv_headers(&self.schema).collect()
+    }
+}
+
+/// Reader of line protocol, comments and empty lines are skipped.
+///
+/// The source is read in blocks of 1 MiB by default and lines are parsed from slices of
+/// each block.
+#[derive(Debug)]
+pub struct LineProtocolReader<R> {
+    source: R,
+    block_size: usize,
+}
+
+impl<R: Read> LineProtocolReader<R> {
+    /// Create the reader of line protocol from the source.
+    pub fn new(source: R) -> LineProtocolReader<R> {
+        LineProtocolReader {
+            source,
+            block_size: DEFAULT_BLOCK_SIZE,
+        }
+    }
+
+    /// Set the bytes of blocks read from the source, a line longer than the block is
+    /// read whole.
+    pub fn block_size(mut self, size: usize) -> Self {
+        self.block_size = size;
+        self
+    }
+
+    /// Call `f` for each parsed line, errors are reported with the line number.
+    pub fn for_each(self, mut f: impl FnMut(ParsedLine<'_>) -> Result<()>) -> Result<()> {
+        read_blocks_while(self.source, self.block_size, |_, line| {
+            f(line).map(|()| true)
+        })
+    }
+
+    /// Returns the columns of all lines.
+    pub fn columns(self) -> Result<Columns> {
+        let mut columns = Columns::default();
+        self.for_each(|line| columns.update(&line))?;
+        Ok(columns)
+    }
+}
+
+/// Line protocol of a memory-mapped file, lines are parsed from slices of the map without
+/// copying them.
+///
+/// The file must be uncompressed and must not be truncated while it is mapped.
+#[derive(Debug)]
+pub struct MappedLineProtocol {
+    map: Mmap,
+    path: PathBuf,
+}
+
+impl MappedLineProtocol {
+    /// Map the line protocol file.
+    pub fn open(path: impl AsRef<Path>) -> Result<MappedLineProtocol> {
+        let path = path.as_ref();
+        Ok(MappedLineProtocol {
+            map: Mmap::open(path, Compression::Auto)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Call `f` for each parsed line, errors are reported with the line number.
+    pub fn for_each(&self, mut f: impl FnMut(ParsedLine<'_>) -> Result<()>) -> Result<()> {
+        read_text_lines(self.text()?, |_, line| f(line))
+    }
+
+    /// Returns the columns of all lines.
+    pub fn columns(&self) -> Result<Columns> {
+        let mut columns = Columns::default();
+        self.for_each(|line| columns.update(&line))?;
+        Ok(columns)
+    }
+
+    /// Convert the lines to CSV of the columns like [line_protocol_to_csv].
+    pub fn to_csv<W: Write>(
+        &self,
+        dest: W,
+        columns: &Columns,
+        dialect: CsvDialect,
+        time_format: TimeFormat,
+    ) -> Result<W> {
+        influx::text_to_csv(&columns.schema, self.text()?, dest, &dialect, time_format)
+    }
+
+    fn text(&self) -> Result<&str> {
+        mapped_text(&self.map, &self.path)
+    }
+}
+
+/// A point of line protocol with owned values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point {
+    pub measurement: String,
+    /// Tags in the order of the line.
+    pub tags: Vec<(String, String)>,
+    /// Fields in the order of the line.
+    pub fields: Vec<(String, Value)>,
+    /// Timestamp in nanosecond.
+    pub timestamp: Option<i64>,
+}
+
+impl From<ParsedLine<'_>> for Point {
+    fn from(line: ParsedLine<'_>) -> Point {
+        Point {
+            measurement: line.series.measurement.to_string(),
+            tags: line
+                .series
+                .tag_set
+                .into_iter()
+                .flatten()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            fields: line
+                .field_set
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), Value::from(v)))
+                .collect(),
+            timestamp: line.timestamp,
+        }
+    }
+}
+
+/// Writes the point in line protocol without the newline.
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut line = String::new();
+        let tags = self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str()));
+        push_series(&mut line, &self.measurement, tags);
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(k, v)| {
+                let value = match v {
+                    Value::Int64(v) => FieldValue::I64(*v),
+                    Value::UInt64(v) => FieldValue::U64(*v),
+                    Value::Float64(v) => FieldValue::F64(*v),
+                    Value::String(v) => FieldValue::String(EscapedStr::from(v.as_str())),
+                    Value::Boolean(v) => FieldValue::Boolean(*v),
+                };
+                (k.as_str(), value)
+            })
+            .collect();
+        push_fields(&mut line, fields.iter().map(|(k, v)| (*k, v)));
+        f.write_str(&line)?;
+        match self.timestamp {
+            Some(ts) => write!(f, " {ts}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Iterator of points parsed from line protocol, comments and empty lines are skipped.
+///
+/// Invalid lines are errors with their line numbers, and the iterator goes on to the next
+/// line. The iterator ends after an error of reading the source.
+#[derive(Debug)]
+pub struct LineProtocolIter<R> {
+    source: R,
+    buffer: String,
+    line_number: usize,
+    /// Points of the last line read.
+    pending: VecDeque<Result<Point>>,
+    done: bool,
+}
+
+impl<R: BufRead> LineProtocolIter<R> {
+    /// Create the iterator of points from the source.
+    pub fn new(source: R) -> LineProtocolIter<R> {
+        LineProtocolIter {
+            source,
+            buffer: String::new(),
+            line_number: 0,
+            pending: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Read the next line into pending points, returns false at the end of the source.
+    fn read_line(&mut self) -> Result<bool> {
+        self.buffer.clear();
+        if self
+            .source
+            .read_line(&mut self.buffer)
+            .context(ReadInputSnafu)?
+            == 0
+        {
+            return Ok(false);
+        }
+        self.line_number += 1;
+        for line in influxdb_line_protocol::parse_lines(&self.buffer) {
+            let result = line
+                .context(ParseLineSnafu)
+                .map(Point::from)
+                .context(LineSnafu {
+                    line: self.line_number,
+                });
+            if let Some(result) = reject::check(result, || &self.buffer).transpose() {
+                self.pending.push_back(result);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: BufRead> Iterator for LineProtocolIter<R> {
+    type Item = Result<Point>;
+
+    fn next(&mut self) -> Option<Result<Point>> {
+        while self.pending.is_empty() && !self.done {
+            match self.read_line() {
+                Ok(true) => {}
+                Ok(false) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.pending.pop_front()
+    }
+}
+
+/// Writer of lines as CSV rows of the columns.
+///
+/// Each value is written into the column of its key and missing keys are left empty.
+/// Timestamps are in RFC3339 with nanoseconds by default.
+pub struct CsvRowWriter<W: Write> {
+    columns: Columns,
+    writer: Writer<W>,
+    time_format: TimeFormat,
+    /// Reusable buffers of a row.
+    row: CsvRow,
+}
+
+impl<W: Write> CsvRowWriter<W> {
+    /// Create the writer, which writes the header unless the dialect has no header.
+    pub fn new(dest: W, columns: Columns, dialect: CsvDialect) -> Result<CsvRowWriter<W>> {
+        let mut writer = dialect.writer_builder().from_writer(dest);
+        if !dialect.no_header {
+            writer
+                .write_record(columns.csv_header())
+                .context(WriteCsvSnafu)?;
+        }
+        Ok(CsvRowWriter {
+            columns,
+            writer,
+            time_format: TimeFormat::default(),
+            row: CsvRow::default(),
+        })
+    }
+
+    /// Set the format of timestamps.
+    pub fn time_format(mut self, format: TimeFormat) -> Self {
+        self.time_format = format;
+        self
+    }
+
+    /// Write the line as a row.
+    ///
+    /// Returns an error if a tag or field of the line is not in the columns.
+    pub fn write_line(&mut self, line: ParsedLine<'_>) -> Result<()> {
+        write_csv_row(
+            &self.columns.schema,
+            line,
+            self.time_format,
+            &mut self.row,
+            &mut self.writer,
+        )
+    }
+
+    /// Flush the rows and returns the underlying writer.
+    pub fn into_inner(self) -> Result<W> {
+        self.writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol from the source to CSV of the columns, lines are converted by a
+/// pool of threads if there are more than one thread.
+///
+/// Returns the writer of the CSV after all lines are written.
+pub fn line_protocol_to_csv<R: Read + Send, W: Write>(
+    source: R,
+    dest: W,
+    columns: &Columns,
+    dialect: CsvDialect,
+    time_format: TimeFormat,
+    threads: usize,
+) -> Result<W> {
+    influx::line_protocol_to_csv(
+        &columns.schema,
+        source,
+        dest,
+        &dialect,
+        time_format,
+        threads,
+        DEFAULT_BLOCK_SIZE,
+    )
+}
+
+/// Writer of CSV rows as line protocol.
+///
+/// By default, timestamps are epoch or RFC3339 in the `timestamp` column, the precision
+/// of epoch timestamps is detected by their magnitude,
+/// and other columns are fields, which are floats if they are numbers or strings
+/// otherwise. Rows without fields are skipped.
+pub struct LineProtocolWriter<W: Write> {
+    writer: LineWriter<W>,
+    precision: Precision,
+    timestamp_format: Option<String>,
+}
+
+impl<W: Write> LineProtocolWriter<W> {
+    /// Create the writer of line protocol to the destination.
+    pub fn new(dest: W) -> Result<LineProtocolWriter<W>> {
+        LineProtocolWriter::with_capacity(DEFAULT_BUFFER_SIZE, dest)
+    }
+
+    /// Create the writer of line protocol to the destination with a buffer of `capacity`
+    /// bytes.
+    pub fn with_capacity(capacity: usize, dest: W) -> Result<LineProtocolWriter<W>> {
+        let writer = LineWriter::with_capacity(
+            capacity,
+            dest,
+            "timestamp".to_string(),
+            TimestampParser::new(Precision::Auto, None),
+            HashSet::new(),
+            String::new(),
+        )?;
+        Ok(LineProtocolWriter {
+            writer,
+            precision: Precision::Auto,
+            timestamp_format: None,
+        })
+    }
+
+    /// Set the timestamp column and the precision of epoch timestamps in it.
+    pub fn timestamp(mut self, column: impl Into<String>, precision: Precision) -> Self {
+        self.writer.timestamp = column.into();
+        self.precision = precision;
+        self.writer.timestamp_parser =
+            TimestampParser::new(precision, self.timestamp_format.clone());
+        self
+    }
+
+    /// Set the strptime format of timestamps, e.g. "%Y-%m-%d %H:%M:%S".
+    pub fn timestamp_format(mut self, format: impl Into<String>) -> Self {
+        let format = format.into();
+        self.writer.timestamp_parser = TimestampParser::new(self.precision, Some(format.clone()));
+        self.timestamp_format = Some(format);
+        self
+    }
+
+    /// Set the tag columns.
+    pub fn tags<I: IntoIterator<Item = S>, S: Into<String>>(mut self, tags: I) -> Self {
+        self.writer.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the column of measurement names of rows, empty names fall back to the
+    /// measurement of [LineProtocolWriter::write_csv].
+    pub fn measurement_column(mut self, column: impl Into<String>) -> Self {
+        self.writer.measurement_column = Some(column.into());
+        self
+    }
+
+    /// Set the type of the field column.
+    pub fn field_type(mut self, column: impl Into<String>, field_type: FieldType) -> Self {
+        self.writer.types.insert(column.into(), field_type);
+        self
+    }
+
+    /// Set the dialect of CSV.
+    pub fn dialect(mut self, dialect: CsvDialect) -> Self {
+        self.writer.dialect = dialect;
+        self
+    }
+
+    /// Flush the destination after every `lines` lines.
+    pub fn flush_lines(mut self, lines: u64) -> Self {
+        self.writer.flush_lines = Some(lines);
+        self
+    }
+
+    /// Returns the number of lines written.
+    pub fn points_written(&self) -> u64 {
+        self.writer.points_written
+    }
+
+    /// Returns the number of bytes of lines written.
+    pub fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written
+    }
+
+    /// Write rows of the CSV from the source as lines of the measurement.
+    pub fn write_csv<R: Read>(&mut self, source: R, measurement: &str) -> Result<()> {
+        let reader = self.writer.dialect.reader(source);
+        self.writer
+            .convert_csv(reader, Path::new("-"), &[], measurement)
+    }
+
+    /// Flush the lines and returns the underlying writer.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.writer.flush()?;
+        self.writer
+            .writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_points() {
+        let input = "cpu,host=a\\ b usage=1i,name=\"a \\\"b\\\"\" 1\n\ncpu usage=\nmem used=2u\n";
+        let mut points = LineProtocolIter::new(input.as_bytes());
+        let point = points.next().unwrap().unwrap();
+        assert_eq!(
+            Point {
+                measurement: "cpu".to_string(),
+                tags: vec![("host".to_string(), "a b".to_string())],
+                fields: vec![
+                    ("usage".to_string(), Value::Int64(1)),
+                    ("name".to_string(), Value::String("a \"b\"".to_string())),
+                ],
+                timestamp: Some(1),
+            },
+            point
+        );
+        assert_eq!(input.lines().next().unwrap(), point.to_string());
+        let e = points.next().unwrap().unwrap_err();
+        assert!(e.to_string().contains("line 3"), "{e}");
+        assert_eq!("mem used=2u", points.next().unwrap().unwrap().to_string());
+        assert!(points.next().is_none());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let input = "cpu,host=a usage=1.5,count=3i 1451606400000000001
+cpu,host=b,region=eu usage=2 1451606410000123456
+";
+        let columns = LineProtocolReader::new(input.as_bytes()).columns().unwrap();
+        assert_eq!(vec!["host", "region"], columns.tags());
+        let csv = line_protocol_to_csv(
+            input.as_bytes(),
+            Vec::new(),
+            &columns,
+            CsvDialect::default(),
+            TimeFormat::Ns,
+            2,
+        )
+        .unwrap();
+
+        let mut writer = LineProtocolWriter::new(Vec::new())
+            .unwrap()
+            .measurement_column("measurement")
+            .tags(["host", "region"])
+            .field_type("count", FieldType::Int64);
+        writer.write_csv(&csv[..], "").unwrap();
+        let lines = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(input, lines);
+    }
+
+    #[test]
+    fn test_block_size() {
+        let input = "cpu,host=a usage=1.5 1\n\n# comment\ncpu,host=b usage=2.5 2\ncpu usage=\n";
+        let mut points = Vec::new();
+        let e = LineProtocolReader::new(input.as_bytes())
+            .block_size(8)
+            .for_each(|line| {
+                points.push(Point::from(line));
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(e.to_string().contains("line 5"), "{e}");
+        let points: Vec<_> = points.iter().map(|point| point.to_string()).collect();
+        assert_eq!(
+            vec!["cpu,host=a usage=1.5 1", "cpu,host=b usage=2.5 2"],
+            points
+        );
+    }
+
+    #[test]
+    fn test_mapped_line_protocol() {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "cpu,host=a usage=1.5 1\n\n# comment\ncpu usage=\n",
+        )
+        .unwrap();
+        let lines = MappedLineProtocol::open(file.path()).unwrap();
+        let mut points = Vec::new();
+        let e = lines
+            .for_each(|line| {
+                points.push(Point::from(line));
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(e.to_string().contains("line 4"), "{e}");
+        assert_eq!(1, points.len());
+        assert_eq!("cpu,host=a usage=1.5 1", points[0].to_string());
+
+        std::fs::write(file.path(), b"cpu,host=\xff usage=1 1\n").unwrap();
+        let e = MappedLineProtocol::open(file.path())
+            .unwrap()
+            .for_each(|_| Ok(()))
+            .unwrap_err();
+        assert!(e.to_string().ends_with("invalid UTF-8 at byte 9"), "{e}");
+    }
+
+    /// Destination of shared bytes, which counts flushes.
+    #[derive(Default)]
+    struct Flushes {
+        bytes: Vec<u8>,
+        flushes: Vec<usize>,
+    }
+
+    impl Write for &mut Flushes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.bytes.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushes.push(self.bytes.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush_lines() {
+        let mut dest = Flushes::default();
+        let mut writer = LineProtocolWriter::with_capacity(1 << 20, &mut dest)
+            .unwrap()
+            .flush_lines(2);
+        writer
+            .write_csv("usage,timestamp\n1,1\n2,2\n3,3\n".as_bytes(), "cpu")
+            .unwrap();
+        assert_eq!(3, writer.points_written());
+        assert_eq!(69, writer.bytes_written());
+        writer.into_inner().unwrap();
+        // Lines are flushed after the second line and at the end.
+        assert_eq!(vec![46, 69], dest.flushes);
+    }
+}
@@ -0,0 +1,244 @@
+//! Tools for CSV files.
+
+use crate::error::{MissingColumnSnafu, ReadCsvSnafu, Result, WriteCsvSnafu, WriteOutputSnafu};
+use crate::io::{create_output, open_input, CompressionArgs, Output, ReplayableInput};
+use clap::{Parser, ValueEnum};
+use csv::{Reader, StringRecord, Writer};
+use snafu::{OptionExt, ResultExt};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// CSV command.
+#[derive(Debug, Parser)]
+pub struct CsvCommand {
+    #[clap(subcommand)]
+    subcmd: CsvSubcommand,
+}
+
+impl CsvCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            CsvSubcommand::Pivot(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for CSV.
+#[derive(Debug, Parser)]
+enum CsvSubcommand {
+    /// Pivot CSV between wide and long formats.
+    Pivot(Pivot),
+}
+
+/// Format of CSV to pivot to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PivotTo {
+    /// A row of each field with its name and value.
+    Long,
+    /// A column of each field.
+    Wide,
+}
+
+/// Pivot CSV file between wide format (a column of each field) and long format (rows of
+/// names and values of fields).
+///
+/// Rows are grouped by the timestamp and tag columns. When pivoting to wide format,
+/// adjacent rows of the same group are merged into a row and other columns are skipped.
+#[derive(Debug, Parser)]
+struct Pivot {
+    /// Input CSV file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output CSV file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format to pivot to.
+    #[arg(long, value_enum)]
+    to: PivotTo,
+    /// Timestamp column name.
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+    /// Tag names.
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Column of field names in long format.
+    #[arg(long, default_value = "metric")]
+    name_column: String,
+    /// Column of field values in long format.
+    #[arg(long, default_value = "value")]
+    value_column: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Pivot {
+    fn run(self) -> Result<()> {
+        let path = Path::new(&self.input);
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = match self.to {
+            PivotTo::Long => {
+                let input = open_input(path, self.compression.input_compression)?;
+                self.wide_to_long(path, input, output)?
+            }
+            PivotTo::Wide => {
+                // The first pass collects names of fields for the header.
+                let input = ReplayableInput::open(path, self.compression.input_compression)?;
+                self.long_to_wide(path, input.reader()?, input.reader()?, output)?
+            }
+        };
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Returns the timestamp and tag columns.
+    fn keys(&self) -> impl Iterator<Item = &str> {
+        [self.timestamp.as_str()]
+            .into_iter()
+            .chain(self.tag.iter().map(String::as_str))
+    }
+
+    fn wide_to_long<R: Read, W: Write>(&self, path: &Path, source: R, dest: W) -> Result<W> {
+        let mut reader = Reader::from_reader(source);
+        let headers = reader.headers().context(ReadCsvSnafu { path })?.clone();
+        let keys = self
+            .keys()
+            .map(|column| position(path, &headers, column))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut writer = Writer::from_writer(dest);
+        let header = self
+            .keys()
+            .chain([self.name_column.as_str(), self.value_column.as_str()]);
+        writer.write_record(header).context(WriteCsvSnafu)?;
+        let mut row = StringRecord::new();
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            for (i, value) in record.iter().enumerate() {
+                // Skip keys and empty fields.
+                if keys.contains(&i) || value.is_empty() {
+                    continue;
+                }
+                row.clear();
+                for key in &keys {
+                    row.push_field(record.get(*key).unwrap_or_default());
+                }
+                row.push_field(headers.get(i).unwrap_or_default());
+                row.push_field(value);
+                writer.write_record(&row).context(WriteCsvSnafu)?;
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+
+    fn long_to_wide<R: Read, W: Write>(
+        &self,
+        path: &Path,
+        first: R,
+        second: R,
+        dest: W,
+    ) -> Result<W> {
+        // Names of fields in the order they first appear.
+        let mut names = Vec::new();
+        let mut indices = HashMap::new();
+        let mut reader = Reader::from_reader(first);
+        let headers = reader.headers().context(ReadCsvSnafu { path })?.clone();
+        let name_idx = position(path, &headers, &self.name_column)?;
+        let value_idx = position(path, &headers, &self.value_column)?;
+        let keys = self
+            .keys()
+            .map(|column| position(path, &headers, column))
+            .collect::<Result<Vec<_>>>()?;
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            let name = record.get(name_idx).unwrap_or_default();
+            if !indices.contains_key(name) {
+                indices.insert(name.to_string(), names.len());
+                names.push(name.to_string());
+            }
+        }
+
+        let mut writer = Writer::from_writer(dest);
+        let header = self.keys().chain(names.iter().map(String::as_str));
+        writer.write_record(header).context(WriteCsvSnafu)?;
+        let mut reader = Reader::from_reader(second);
+        let mut group: Option<(Vec<String>, Vec<String>)> = None;
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            let key: Vec<_> = keys
+                .iter()
+                .map(|i| record.get(*i).unwrap_or_default().to_string())
+                .collect();
+            if group.as_ref().is_none_or(|(prev, _)| *prev != key) {
+                if let Some((prev, values)) = group.take() {
+                    write_group(&mut writer, &prev, &values)?;
+                }
+                group = Some((key, vec![String::new(); names.len()]));
+            }
+            if let Some((_, values)) = &mut group {
+                let name = record.get(name_idx).unwrap_or_default();
+                values[indices[name]] = record.get(value_idx).unwrap_or_default().to_string();
+            }
+        }
+        if let Some((key, values)) = group {
+            write_group(&mut writer, &key, &values)?;
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+/// Returns the index of the column in headers.
+fn position(path: &Path, headers: &StringRecord, column: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|name| name == column)
+        .context(MissingColumnSnafu { path, column })
+}
+
+fn write_group<W: Write>(writer: &mut Writer<W>, key: &[String], values: &[String]) -> Result<()> {
+    writer
+        .write_record(key.iter().chain(values))
+        .context(WriteCsvSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pivot() {
+        let pivot = Pivot {
+            input: "-".to_string(),
+            output: "-".to_string(),
+            to: PivotTo::Long,
+            timestamp: "ts".to_string(),
+            tag: vec!["host".to_string()],
+            name_column: "metric".to_string(),
+            value_column: "value".to_string(),
+            compression: CompressionArgs::default(),
+        };
+        let wide = "host,usage,ts,count\na,1,1,\nb,2,1,3\n";
+        let long = "ts,host,metric,value\n1,a,usage,1\n1,b,usage,2\n1,b,count,3\n";
+        let path = Path::new("-");
+        let output = pivot
+            .wide_to_long(path, wide.as_bytes(), Vec::new())
+            .unwrap();
+        assert_eq!(long, String::from_utf8(output).unwrap());
+
+        let output = pivot
+            .long_to_wide(path, long.as_bytes(), long.as_bytes(), Vec::new())
+            .unwrap();
+        assert_eq!(
+            "ts,host,usage,count\n1,a,1,\n1,b,2,3\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
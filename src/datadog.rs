@@ -0,0 +1,330 @@
+//! Tools for Datadog metrics.
+
+use crate::error::{InvalidSampleSnafu, ParseJsonSnafu, Result, WriteJsonSnafu, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::prom::exposition::Sample;
+use crate::prom::samples_to_line_protocol;
+use chrono::Utc;
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use snafu::{OptionExt, ResultExt};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Datadog command.
+#[derive(Debug, Parser)]
+pub struct DatadogCommand {
+    #[clap(subcommand)]
+    subcmd: DatadogSubcommand,
+}
+
+impl DatadogCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            DatadogSubcommand::ToLp(c) => c.run(),
+            DatadogSubcommand::FromLp(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Datadog.
+#[derive(Debug, Parser)]
+enum DatadogSubcommand {
+    /// Metrics submission JSON to line protocol.
+    ToLp(ToLp),
+    /// Line protocol to metrics submission JSON.
+    FromLp(FromLp),
+}
+
+/// Name of the field of values.
+const VALUE_FIELD: &str = "value";
+
+/// A payload of `/api/v1/series` or `/api/v2/series`.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Payload {
+    series: Vec<Series>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Series {
+    metric: String,
+    points: Vec<Point>,
+    /// Type of the metric, which is a name in v1 and a number in v2.
+    #[serde(
+        rename = "type",
+        skip_deserializing,
+        skip_serializing_if = "Option::is_none"
+    )]
+    kind: Option<String>,
+    /// Tags in `key:value`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    device: Option<String>,
+    /// Resources of v2, like the host.
+    #[serde(default, skip_serializing)]
+    resources: Vec<Resource>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Resource {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// A point of a series.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Point {
+    /// `[timestamp, value]` of v1, the timestamp is in second.
+    V1(Number, Option<f64>),
+    /// `{"timestamp": timestamp, "value": value}` of v2, the timestamp is in second.
+    V2 { timestamp: i64, value: Option<f64> },
+}
+
+impl Series {
+    /// Append samples of the series, `null` values are skipped.
+    fn push_samples<'a>(&'a self, samples: &mut Vec<Sample<'a>>) -> Result<()> {
+        // The first value of duplicate tag keys wins.
+        let mut tags = BTreeMap::new();
+        for tag in &self.tags {
+            let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+            tags.entry(key).or_insert(value);
+        }
+        let resources = self.resources.iter().map(|r| (r.kind.as_str(), &r.name));
+        for (key, value) in [("host", &self.host), ("device", &self.device)]
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value.as_ref()?)))
+            .chain(resources)
+        {
+            tags.entry(key).or_insert(value);
+        }
+        let labels: Vec<_> = tags.into_iter().map(|(k, v)| (k, v.to_string())).collect();
+
+        for point in &self.points {
+            let (timestamp, value) = match point {
+                Point::V1(ts, value) => {
+                    let timestamp = match ts.as_i64() {
+                        Some(ts) => ts.checked_mul(1_000_000_000),
+                        None => ts
+                            .as_f64()
+                            .map(|ts| ts * 1e9)
+                            .filter(|nanos| nanos.abs() < i64::MAX as f64)
+                            .map(|nanos| nanos.round() as i64),
+                    };
+                    (timestamp, value)
+                }
+                Point::V2 { timestamp, value } => (timestamp.checked_mul(1_000_000_000), value),
+            };
+            let Some(value) = value else {
+                continue;
+            };
+            let timestamp = timestamp.context(InvalidSampleSnafu {
+                reason: format!("timestamp of {} is out of range", self.metric),
+            })?;
+            samples.push(Sample {
+                name: &self.metric,
+                labels: labels.clone(),
+                value: *value,
+                timestamp: Some(timestamp),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Convert Datadog metrics submission JSON to line protocol file.
+///
+/// The input is payloads of `/api/v1/series` or `/api/v2/series`, one after another like
+/// JSON lines. Each point is a line with the metric as the measurement, tags and the
+/// `host`, `device` and resources of the series as tags and the `value` field. Tags
+/// without a value are dropped as line protocol has no such tags.
+#[derive(Debug, Parser)]
+struct ToLp {
+    /// Input JSON file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = payloads_to_line_protocol(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol file to Datadog metrics submission JSON for `/api/v1/series`.
+///
+/// Each field is a gauge series named `{measurement}.{field}`, or the measurement for the
+/// `value` field, with tags as its tags. Integer and boolean fields are converted to float
+/// and string fields are skipped. A payload is written as a JSON line every
+/// `--batch-size` points.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output JSON lines file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Max number of points in a payload.
+    #[arg(long, default_value_t = 1000)]
+    batch_size: usize,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = line_protocol_to_payloads(input, output, self.batch_size)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn payloads_to_line_protocol<R: Read, W: Write>(source: R, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let stream = serde_json::Deserializer::from_reader(BufReader::new(source)).into_iter();
+    for payload in stream {
+        let payload: Payload = payload.context(ParseJsonSnafu)?;
+        let mut samples = Vec::new();
+        for series in &payload.series {
+            series.push_samples(&mut samples)?;
+        }
+        writer = samples_to_line_protocol(&samples, writer)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn line_protocol_to_payloads<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    batch_size: usize,
+) -> Result<W> {
+    let now = Utc::now().timestamp();
+    let mut writer = BufWriter::new(dest);
+    let mut payload = Payload::default();
+    // Index of each series in the payload by its metric and tags.
+    let mut series_index: HashMap<(String, Vec<String>), usize> = HashMap::new();
+    let mut points = 0;
+
+    read_lines(source, |line| {
+        let timestamp = line
+            .timestamp
+            .map(|ts| ts.div_euclid(1_000_000_000))
+            .unwrap_or(now);
+        let tags: Vec<_> = line
+            .series
+            .tag_set
+            .iter()
+            .flatten()
+            .map(|(k, v)| format!("{k}:{v}"))
+            .collect();
+        for (fieldk, fieldv) in &line.field_set {
+            let value = match fieldv {
+                FieldValue::I64(v) => *v as f64,
+                FieldValue::U64(v) => *v as f64,
+                FieldValue::F64(v) => *v,
+                FieldValue::Boolean(v) => f64::from(u8::from(*v)),
+                FieldValue::String(_) => continue,
+            };
+            let metric = if fieldk.as_str() == VALUE_FIELD {
+                line.series.measurement.to_string()
+            } else {
+                format!("{}.{}", line.series.measurement, fieldk)
+            };
+
+            let idx = *series_index
+                .entry((metric.clone(), tags.clone()))
+                .or_insert_with(|| {
+                    payload.series.push(Series {
+                        metric,
+                        points: Vec::new(),
+                        kind: Some("gauge".to_string()),
+                        tags: tags.clone(),
+                        host: None,
+                        device: None,
+                        resources: Vec::new(),
+                    });
+                    payload.series.len() - 1
+                });
+            payload.series[idx]
+                .points
+                .push(Point::V1(timestamp.into(), Some(value)));
+            points += 1;
+
+            if points >= batch_size {
+                write_payload(&mut writer, &payload)?;
+                payload.series.clear();
+                series_index.clear();
+                points = 0;
+            }
+        }
+        Ok(())
+    })?;
+    if !payload.series.is_empty() {
+        write_payload(&mut writer, &payload)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn write_payload<W: Write>(writer: &mut W, payload: &Payload) -> Result<()> {
+    serde_json::to_writer(&mut *writer, payload).context(WriteJsonSnafu)?;
+    writer.write_all(b"\n").context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_datadog_lp() {
+        let input = r#"{"series":[{"metric":"system.load.1","type":"gauge","points":[[1636629071,0.5],[1636629081.5,null]],"host":"a","tags":["env:prod","role","env:dev"]}]}
+{"series":[{"metric":"requests","type":1,"points":[{"timestamp":1636629071,"value":3}],"resources":[{"name":"b","type":"host"}]}]}
+"#;
+        let output = payloads_to_line_protocol(input.as_bytes(), Vec::new()).unwrap();
+        let lp = String::from_utf8(output).unwrap();
+        assert_eq!(
+            "system.load.1,env=prod,host=a value=0.5 1636629071000000000
+requests,host=b value=3 1636629071000000000
+",
+            lp
+        );
+
+        let input = format!("{lp}cpu,host=a usage=1i,idle=true,note=\"x\" 1636629072000000000\n");
+        let output = line_protocol_to_payloads(input.as_bytes(), Vec::new(), 3).unwrap();
+        assert_eq!(
+            r#"{"series":[{"metric":"system.load.1","points":[[1636629071,0.5]],"type":"gauge","tags":["env:prod","host:a"]},{"metric":"requests","points":[[1636629071,3.0]],"type":"gauge","tags":["host:b"]},{"metric":"cpu.usage","points":[[1636629072,1.0]],"type":"gauge","tags":["host:a"]}]}
+{"series":[{"metric":"cpu.idle","points":[[1636629072,1.0]],"type":"gauge","tags":["host:a"]}]}
+"#,
+            String::from_utf8(output).unwrap()
+        );
+
+        let input = r#"{"series":[{"metric":"up","points":[[1e300,1]]}]}"#;
+        let err = payloads_to_line_protocol(input.as_bytes(), Vec::new()).unwrap_err();
+        assert!(err.is_invalid_line(), "{err}");
+    }
+}
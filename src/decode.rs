@@ -0,0 +1,181 @@
+//! Decoders of the binary encodings shared by readers of TSDB files.
+
+/// Decoder of bytes.
+pub(crate) struct Decbuf<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decbuf<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Decbuf<'a> {
+        Decbuf { data, pos: 0 }
+    }
+
+    pub(crate) fn at(data: &'a [u8], pos: usize) -> Decbuf<'a> {
+        Decbuf { data, pos }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| format!("unexpected end at offset {}", self.pos))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn be_u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn be_u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn be_u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.bytes(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn uvarint(&mut self) -> Result<u64, String> {
+        uvarint(|| self.byte())
+    }
+
+    /// Returns the remaining bytes and moves to the end.
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = self.data.get(self.pos..).unwrap_or_default();
+        self.pos = self.data.len();
+        rest
+    }
+
+    /// Returns the offset of the next byte.
+    pub(crate) fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Returns the number of remaining bytes.
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<i64, String> {
+        Ok(zigzag(self.uvarint()?))
+    }
+}
+
+/// Reader of bits, from the most significant bit of each byte.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    /// Position in bits.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, pos: 0 }
+    }
+
+    pub(crate) fn bit(&mut self) -> Result<bool, String> {
+        let byte = self
+            .data
+            .get(self.pos / 8)
+            .ok_or("unexpected end of chunk")?;
+        let bit = byte & (0x80 >> (self.pos % 8)) != 0;
+        self.pos += 1;
+        Ok(bit)
+    }
+
+    pub(crate) fn bits(&mut self, n: usize) -> Result<u64, String> {
+        let mut bits = 0;
+        for _ in 0..n {
+            bits = (bits << 1) | u64::from(self.bit()?);
+        }
+        Ok(bits)
+    }
+
+    pub(crate) fn uvarint(&mut self) -> Result<u64, String> {
+        uvarint(|| Ok(self.bits(8)? as u8))
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<i64, String> {
+        Ok(zigzag(self.uvarint()?))
+    }
+
+    /// Read the value XOR encoded with the previous value.
+    pub(crate) fn xor(
+        &mut self,
+        prev: u64,
+        leading: &mut usize,
+        trailing: &mut usize,
+    ) -> Result<u64, String> {
+        if !self.bit()? {
+            return Ok(prev);
+        }
+        if self.bit()? {
+            *leading = self.bits(5)? as usize;
+            let significant = match self.bits(6)? as usize {
+                0 => 64,
+                n => n,
+            };
+            *trailing = 64usize
+                .checked_sub(*leading + significant)
+                .ok_or("invalid XOR value")?;
+        }
+        let significant = 64 - *leading - *trailing;
+        Ok(prev ^ (self.bits(significant)? << *trailing))
+    }
+}
+
+fn uvarint(mut next: impl FnMut() -> Result<u8, String>) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = next()?;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err("varint overflows".to_string())
+}
+
+pub(crate) fn zigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decbuf() {
+        let mut buf = Decbuf::at(&[0, 0, 0xAC, 0x02, 0x03, 0xFF], 2);
+        assert_eq!(300, buf.uvarint().unwrap());
+        assert_eq!(-2, buf.varint().unwrap());
+        assert_eq!(5, buf.position());
+        assert!(buf.bytes(usize::MAX).is_err());
+        assert_eq!(&[0xFF], buf.rest());
+        assert!(buf.is_empty());
+        assert!(buf.byte().is_err());
+
+        let mut buf = Decbuf::new(&[0xFF; 10]);
+        assert_eq!("varint overflows", buf.uvarint().unwrap_err());
+    }
+
+    #[test]
+    fn test_bit_reader() {
+        let mut reader = BitReader::new(&[0b1010_0000, 0xAC, 0x02]);
+        assert!(reader.bit().unwrap());
+        assert_eq!(0b010, reader.bits(3).unwrap());
+        assert_eq!(0, reader.bits(4).unwrap());
+        assert_eq!(300, reader.uvarint().unwrap());
+        assert!(reader.bit().is_err());
+    }
+}
@@ -0,0 +1,361 @@
+//! Error of TSDB tools.
+
+use snafu::Snafu;
+use std::io;
+use std::path::PathBuf;
+
+/// Error of TSDB tools.
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    #[snafu(display("Failed to open {}", path.display()))]
+    OpenFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to read {}", path.display()))]
+    ReadFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to map {}", path.display()))]
+    MapFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Cannot map {}: {reason}", path.display()))]
+    InvalidMappedInput { path: PathBuf, reason: String },
+
+    #[snafu(display("Cannot watch {}: {reason}", path.display()))]
+    InvalidWatchInput { path: PathBuf, reason: String },
+
+    #[snafu(display("Failed to create {}", path.display()))]
+    CreateFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to remove {}", path.display()))]
+    RemoveFile { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to create directory {}", path.display()))]
+    CreateDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Failed to read directory {}", path.display()))]
+    ReadDir { path: PathBuf, source: io::Error },
+
+    #[snafu(display("Invalid glob pattern {pattern}"))]
+    InvalidGlob {
+        pattern: String,
+        source: glob::PatternError,
+    },
+
+    #[snafu(display("Failed to match glob pattern"))]
+    Glob { source: glob::GlobError },
+
+    #[snafu(display("No file matches {pattern}"))]
+    NoMatchedFile { pattern: String },
+
+    #[snafu(display("Invalid file name {}", path.display()))]
+    InvalidFileName { path: PathBuf },
+
+    #[snafu(display("Measurement name is required when reading from stdin"))]
+    MissingMeasurement,
+
+    #[snafu(display("Failed to read input"))]
+    ReadInput { source: io::Error },
+
+    #[snafu(display("Failed to write output"))]
+    WriteOutput { source: io::Error },
+
+    #[snafu(display("Error at line {line}"))]
+    Line {
+        line: usize,
+        #[snafu(source(from(Error, Box::new)))]
+        source: Box<Error>,
+    },
+
+    #[snafu(display("Failed to parse line protocol"))]
+    ParseLine {
+        source: influxdb_line_protocol::Error,
+    },
+
+    #[snafu(display("Field {field} has conflicting types {current} and {other}"))]
+    FieldTypeConflict {
+        field: String,
+        current: String,
+        other: String,
+    },
+
+    #[snafu(display("Found {problems} problems in line protocol"))]
+    ValidationFailed { problems: usize },
+
+    #[snafu(display("Found {differences} different points"))]
+    InputsDiffer { differences: usize },
+
+    #[snafu(display("{files} files failed verification"))]
+    VerificationFailed { files: usize },
+
+    #[snafu(display("Cardinality {cardinality} of {name} exceeds {max}"))]
+    CardinalityExceeded {
+        name: String,
+        cardinality: u64,
+        max: u64,
+    },
+
+    #[snafu(display("Code {code} of field {field} is not in the dictionary"))]
+    UnknownCode { field: String, code: i64 },
+
+    #[snafu(display("Column {column} is not in the schema"))]
+    ColumnNotInSchema { column: String },
+
+    #[snafu(display("Failed to parse schema file {}", path.display()))]
+    ParseSchema {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[snafu(display("Column {column} is not in {}", path.display()))]
+    MissingColumn { path: PathBuf, column: String },
+
+    #[snafu(display("Columns are required to read CSV without header"))]
+    MissingColumns,
+
+    #[snafu(display("Failed to read CSV {}", path.display()))]
+    ReadCsv { path: PathBuf, source: csv::Error },
+
+    #[snafu(display("Failed to write CSV"))]
+    WriteCsv { source: csv::Error },
+
+    #[snafu(display(
+        "Invalid timestamp {value} in column {column} at line {line} of {}",
+        path.display()
+    ))]
+    InvalidTimestamp {
+        path: PathBuf,
+        line: u64,
+        column: String,
+        value: String,
+    },
+
+    #[snafu(display(
+        "Invalid {field_type} value {value} in column {column} at line {line} of {}",
+        path.display()
+    ))]
+    InvalidColumnValue {
+        path: PathBuf,
+        line: u64,
+        column: String,
+        value: String,
+        field_type: String,
+    },
+
+    #[snafu(display("Timestamp {timestamp} shifted by {offset} is out of range"))]
+    TimestampOutOfRange { timestamp: i64, offset: i64 },
+
+    #[snafu(display("Input {path} is not sorted by timestamp at line {line}"))]
+    UnsortedInput { path: String, line: usize },
+
+    #[snafu(display("Failed to spill to temporary file"))]
+    Spill { source: io::Error },
+
+    #[snafu(display("Failed to read Parquet {}", path.display()))]
+    ReadParquet {
+        path: PathBuf,
+        source: parquet::errors::ParquetError,
+    },
+
+    #[snafu(display("Failed to write Parquet"))]
+    WriteParquet {
+        source: parquet::errors::ParquetError,
+    },
+
+    #[snafu(display("Arrow error"))]
+    Arrow { source: arrow::error::ArrowError },
+
+    #[snafu(display(
+        "Unsupported type {data_type} of timestamp column {column} in {}",
+        path.display()
+    ))]
+    UnsupportedTimestampType {
+        path: PathBuf,
+        column: String,
+        data_type: String,
+    },
+
+    #[snafu(display("Invalid timestamp {value} in column {column} of {}", path.display()))]
+    InvalidColumnTimestamp {
+        path: PathBuf,
+        column: String,
+        value: String,
+    },
+
+    #[snafu(display("Failed to send request to {url}"))]
+    SendRequest {
+        url: String,
+        source: Box<ureq::Transport>,
+    },
+
+    #[snafu(display("Invalid connection URL: {reason}"))]
+    InvalidConnectionUrl { reason: String },
+
+    #[snafu(display("Failed to connect to {addr}"))]
+    Connect { addr: String, source: io::Error },
+
+    #[snafu(display("Failed to send to {addr}"))]
+    Send { addr: String, source: io::Error },
+
+    #[snafu(display("Failed to communicate with PostgreSQL"))]
+    PostgresIo { source: io::Error },
+
+    #[snafu(display("PostgreSQL error: {message}"))]
+    Postgres { message: String },
+
+    #[cfg(feature = "tokio")]
+    #[snafu(display("Failed to send request to {url}"))]
+    SendAsyncRequest { url: String, source: reqwest::Error },
+
+    #[snafu(display("Request to {url} failed with status {status}: {body}"))]
+    RequestStatus {
+        url: String,
+        status: u16,
+        body: String,
+    },
+
+    #[snafu(display("Failed to listen on {addr}"))]
+    Bind { addr: String, source: io::Error },
+
+    #[snafu(display("Failed to parse JSON"))]
+    ParseJson { source: serde_json::Error },
+
+    #[snafu(display("Failed to write JSON"))]
+    WriteJson { source: serde_json::Error },
+
+    #[snafu(display("Invalid point: {reason}"))]
+    InvalidPoint { reason: String },
+
+    #[snafu(display("Invalid sample: {reason}"))]
+    InvalidSample { reason: String },
+
+    #[snafu(display("Invalid TSDB block file {}: {reason}", path.display()))]
+    InvalidBlock { path: PathBuf, reason: String },
+
+    #[snafu(display("Invalid TSM file {}: {reason}", path.display()))]
+    InvalidTsm { path: PathBuf, reason: String },
+
+    #[snafu(display("Invalid InfluxDB 3 file {}: {reason}", path.display()))]
+    InvalidIoxFile { path: PathBuf, reason: String },
+
+    #[snafu(display("Invalid pickle: {reason}"))]
+    InvalidPickle { reason: String },
+
+    #[snafu(display("Failed to parse config file {}", path.display()))]
+    ParseConfig {
+        path: PathBuf,
+        source: Box<toml::de::Error>,
+    },
+
+    #[snafu(display("Invalid config file {}: {reason}", path.display()))]
+    InvalidConfig { path: PathBuf, reason: String },
+
+    #[snafu(display("Invalid checkpoint {}: {reason}", path.display()))]
+    InvalidCheckpoint { path: PathBuf, reason: String },
+
+    #[snafu(display("Unknown command {command}"))]
+    UnknownCommand { command: String },
+
+    #[snafu(display("Failed to compress with snappy"))]
+    SnappyCompress { source: snap::Error },
+
+    #[cfg(any(
+        feature = "flight",
+        feature = "greptime",
+        feature = "kafka",
+        feature = "object-store",
+        feature = "otel-grpc"
+    ))]
+    #[snafu(display("Failed to create async runtime"))]
+    CreateRuntime { source: io::Error },
+
+    #[cfg(feature = "greptime")]
+    #[snafu(display("GreptimeDB error"))]
+    Greptime { source: greptimedb_ingester::Error },
+
+    #[cfg(feature = "object-store")]
+    #[snafu(display("Invalid URL {url}"))]
+    InvalidUrl {
+        url: String,
+        source: url::ParseError,
+    },
+
+    #[cfg(feature = "object-store")]
+    #[snafu(display("Object store error of {url}"))]
+    ObjectStore {
+        url: String,
+        source: Box<object_store::Error>,
+    },
+
+    #[cfg(not(feature = "object-store"))]
+    #[snafu(display("Object store URL {url} requires building with the object-store feature"))]
+    ObjectStoreDisabled { url: String },
+
+    #[snafu(display("Invalid sink {url}: {reason}"))]
+    InvalidSink { url: String, reason: String },
+
+    #[cfg(not(all(feature = "greptime", feature = "kafka")))]
+    #[snafu(display("Sink {url} requires building with the {feature} feature"))]
+    SinkDisabled { url: String, feature: String },
+
+    #[cfg(feature = "kafka")]
+    #[snafu(display("Kafka error"))]
+    Kafka {
+        source: Box<rskafka::client::error::Error>,
+    },
+
+    #[cfg(any(feature = "flight", feature = "otel-grpc"))]
+    #[snafu(display("Failed to connect to {endpoint}"))]
+    ConnectGrpc {
+        endpoint: String,
+        source: Box<tonic::transport::Error>,
+    },
+
+    #[cfg(feature = "otel-grpc")]
+    #[snafu(display("gRPC request failed"))]
+    Grpc { source: Box<tonic::Status> },
+
+    #[cfg(feature = "otel-grpc")]
+    #[snafu(display("Failed to serve gRPC on {addr}"))]
+    ServeGrpc {
+        addr: String,
+        source: Box<tonic::transport::Error>,
+    },
+
+    #[cfg(not(feature = "otel-grpc"))]
+    #[snafu(display("gRPC requires building with the otel-grpc feature"))]
+    GrpcDisabled,
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Arrow Flight error"))]
+    Flight {
+        source: Box<arrow_flight::error::FlightError>,
+    },
+
+    #[cfg(feature = "flight")]
+    #[snafu(display("Non-nullable column {column} of table {table} is missing"))]
+    MissingTableColumn { table: String, column: String },
+}
+
+impl Error {
+    /// Returns true if the error is caused by invalid data of a line, instead of failures
+    /// of inputs and outputs.
+    pub(crate) fn is_invalid_line(&self) -> bool {
+        match self {
+            Error::Line { source, .. } => source.is_invalid_line(),
+            Error::ParseLine { .. }
+            | Error::ColumnNotInSchema { .. }
+            | Error::FieldTypeConflict { .. }
+            | Error::InvalidTimestamp { .. }
+            | Error::InvalidColumnValue { .. }
+            | Error::TimestampOutOfRange { .. }
+            | Error::ParseJson { .. }
+            | Error::InvalidPoint { .. }
+            | Error::InvalidSample { .. }
+            | Error::UnknownCode { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Result of TSDB tools.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
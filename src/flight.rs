@@ -0,0 +1,409 @@
+//! Tools for Arrow Flight endpoints.
+
+use crate::error::{
+    ArrowSnafu, ConnectGrpcSnafu, CreateRuntimeSnafu, FlightSnafu, MissingTableColumnSnafu, Result,
+};
+use crate::influx::read_lines;
+use crate::influx::schema::Schema;
+use crate::influx::to_parquet::BatchBuilder;
+use crate::io::{Compression, ReplayableInput};
+use crate::progress;
+use crate::rate_limit::RateLimit;
+use crate::reject;
+use arrow::array::new_null_array;
+use arrow::compute::cast;
+use arrow::datatypes::{Schema as ArrowSchema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::sql::client::FlightSqlServiceClient;
+use arrow_flight::sql::{
+    CommandStatementIngest, TableDefinitionOptions, TableExistsOption, TableNotExistOption,
+};
+use arrow_flight::{FlightClient, FlightDescriptor, PutResult};
+use clap::{Parser, ValueEnum};
+use futures::{stream, TryStreamExt};
+use snafu::{ensure, ResultExt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::Runtime;
+use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
+use tracing::debug;
+
+/// Arrow Flight command.
+#[derive(Debug, Parser)]
+pub struct FlightCommand {
+    #[clap(subcommand)]
+    subcmd: FlightSubcommand,
+}
+
+impl FlightCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            FlightSubcommand::Put(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Arrow Flight.
+#[derive(Debug, Parser)]
+enum FlightSubcommand {
+    /// Put line protocol to a Flight or Flight SQL endpoint.
+    Put(Put),
+}
+
+/// Protocol of the endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Protocol {
+    /// `DoPut` with the path of the table as the descriptor.
+    Flight,
+    /// Bulk ingest statements of Flight SQL.
+    Sql,
+}
+
+/// Put line protocol file to an Arrow Flight or Flight SQL endpoint, like InfluxDB 3.0
+/// and GreptimeDB.
+///
+/// Each measurement is a table with tags, fields and the timestamp as columns. Rows of a
+/// table are sent by a `DoPut` request every `--batch-size` rows. With the `flight`
+/// protocol, the schema of an existing table is fetched by `GetSchema` and columns are
+/// cast to the types of the table, new columns are appended after columns of the table.
+/// With the `sql` protocol, tables are created if they don't exist.
+#[derive(Debug, Parser)]
+struct Put {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Flight endpoint, e.g. `http://localhost:4001`.
+    #[arg(long)]
+    endpoint: String,
+    /// Protocol of the endpoint.
+    #[arg(long, value_enum, default_value_t = Protocol::Flight)]
+    protocol: Protocol,
+    /// Header of requests in `key:value`, e.g. `x-greptime-db-name:public`, can be
+    /// repeated.
+    #[arg(long = "header", value_parser = parse_header)]
+    headers: Vec<(String, String)>,
+    /// Bearer token of requests.
+    #[arg(long)]
+    token: Option<String>,
+    /// Catalog of tables of the `sql` protocol.
+    #[arg(long)]
+    catalog: Option<String>,
+    /// Schema of tables of the `sql` protocol.
+    #[arg(long)]
+    db_schema: Option<String>,
+    /// Name of the timestamp column.
+    #[arg(long, default_value = "time")]
+    timestamp: String,
+    /// Number of rows in each record batch.
+    #[arg(long, default_value_t = 8192)]
+    batch_size: usize,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    match s.split_once(':') {
+        Some((key, value)) if !key.is_empty() => {
+            Ok((key.trim().to_lowercase(), value.trim().to_string()))
+        }
+        _ => Err(format!("{s} is not in the form of key:value")),
+    }
+}
+
+impl Put {
+    fn run(self) -> Result<()> {
+        // The first pass collects the schema of each measurement, the second pass puts the rows.
+        let start = Instant::now();
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let schemas = reject::silently(|| Schema::infer_measurements(input.reader()?))?;
+        debug!(
+            "Inferred schemas of {} measurements in {:?}",
+            schemas.len(),
+            start.elapsed()
+        );
+
+        let mut writer = TableWriter::connect(&self)?;
+        let mut index = HashMap::with_capacity(schemas.len());
+        let mut builders = Vec::with_capacity(schemas.len());
+        for (measurement, schema) in &schemas {
+            index.insert(measurement.as_str(), builders.len());
+            builders.push(BatchBuilder::new(schema));
+        }
+
+        read_lines(input.reader()?, |line| {
+            // All measurements are collected by the first pass.
+            let idx = index[line.series.measurement.as_str()];
+            let (measurement, schema) = &schemas[idx];
+            builders[idx].append_line(schema, &line)?;
+            if builders[idx].len >= self.batch_size {
+                writer.put(measurement, builders[idx].finish()?)?;
+            }
+            Ok(())
+        })?;
+
+        for ((measurement, _), builder) in schemas.iter().zip(&mut builders) {
+            if builder.len > 0 {
+                writer.put(measurement, builder.finish()?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Client of the protocol.
+enum Client {
+    Flight(FlightClient),
+    Sql(FlightSqlServiceClient<Channel>),
+}
+
+/// Writer of record batches to tables of the endpoint.
+struct TableWriter<'a> {
+    put: &'a Put,
+    runtime: Runtime,
+    client: Client,
+    /// Schema of each table by `GetSchema`, `None` if the endpoint doesn't know the table.
+    table_schemas: HashMap<String, Option<SchemaRef>>,
+}
+
+impl TableWriter<'_> {
+    fn connect(put: &Put) -> Result<TableWriter<'_>> {
+        let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+        let channel = runtime.block_on(async {
+            async { Endpoint::from_shared(put.endpoint.clone())?.connect().await }
+                .await
+                .map_err(Box::new)
+                .context(ConnectGrpcSnafu {
+                    endpoint: &put.endpoint,
+                })
+        })?;
+        let client = match put.protocol {
+            Protocol::Flight => {
+                let mut client = FlightClient::new(channel);
+                for (key, value) in &put.headers {
+                    client
+                        .add_header(key, value)
+                        .map_err(Box::new)
+                        .context(FlightSnafu)?;
+                }
+                if let Some(token) = &put.token {
+                    client
+                        .add_header("authorization", &format!("Bearer {token}"))
+                        .map_err(Box::new)
+                        .context(FlightSnafu)?;
+                }
+                Client::Flight(client)
+            }
+            Protocol::Sql => {
+                let mut client = FlightSqlServiceClient::new(channel);
+                for (key, value) in &put.headers {
+                    client.set_header(key, value);
+                }
+                if let Some(token) = &put.token {
+                    client.set_token(token.clone());
+                }
+                Client::Sql(client)
+            }
+        };
+        Ok(TableWriter {
+            put,
+            runtime,
+            client,
+            table_schemas: HashMap::new(),
+        })
+    }
+
+    /// Put the batch of the builder to the table after waiting for the rate limit.
+    fn put(&mut self, table: &str, batch: RecordBatch) -> Result<()> {
+        let num_rows = batch.num_rows();
+        let mut batch = table_batch(batch, &self.put.timestamp)?;
+        if let Some(table_schema) = self.table_schema(table)? {
+            batch = align_batch(&batch, table, &table_schema)?;
+        }
+        self.put
+            .rate_limit
+            .acquire(num_rows, batch.get_array_memory_size());
+
+        let batches = stream::iter([Ok(batch)]);
+        match &mut self.client {
+            Client::Flight(client) => {
+                let descriptor = FlightDescriptor::new_path(vec![table.to_string()]);
+                let data = FlightDataEncoderBuilder::new()
+                    .with_flight_descriptor(Some(descriptor))
+                    .build(batches);
+                self.runtime
+                    .block_on(async {
+                        client
+                            .do_put(data)
+                            .await?
+                            .try_collect::<Vec<PutResult>>()
+                            .await
+                    })
+                    .map(|_| ())
+            }
+            Client::Sql(client) => {
+                let command = CommandStatementIngest {
+                    table_definition_options: Some(TableDefinitionOptions {
+                        if_not_exist: TableNotExistOption::Create.into(),
+                        if_exists: TableExistsOption::Append.into(),
+                    }),
+                    table: table.to_string(),
+                    schema: self.put.db_schema.clone(),
+                    catalog: self.put.catalog.clone(),
+                    ..Default::default()
+                };
+                self.runtime
+                    .block_on(client.execute_ingest(command, batches))
+                    .map(|_| ())
+            }
+        }
+        .map_err(Box::new)
+        .context(FlightSnafu)?;
+        progress::add_lines_written(num_rows);
+        Ok(())
+    }
+
+    /// Returns the schema of the table known by the endpoint, only the `flight` protocol
+    /// fetches schemas.
+    fn table_schema(&mut self, table: &str) -> Result<Option<SchemaRef>> {
+        let Client::Flight(client) = &mut self.client else {
+            return Ok(None);
+        };
+        if let Some(schema) = self.table_schemas.get(table) {
+            return Ok(schema.clone());
+        }
+
+        let descriptor = FlightDescriptor::new_path(vec![table.to_string()]);
+        let schema = match self.runtime.block_on(client.get_schema(descriptor)) {
+            Ok(schema) => Some(Arc::new(schema)),
+            Err(FlightError::Tonic(status))
+                if matches!(status.code(), Code::NotFound | Code::Unimplemented) =>
+            {
+                debug!("No schema of table {table}: {}", status.message());
+                None
+            }
+            Err(e) => return Err(Box::new(e)).context(FlightSnafu),
+        };
+        self.table_schemas.insert(table.to_string(), schema.clone());
+        Ok(schema)
+    }
+}
+
+/// Returns the batch of a table from the batch of the builder, without the measurement
+/// column and with the timestamp column renamed.
+fn table_batch(batch: RecordBatch, timestamp: &str) -> Result<RecordBatch> {
+    let schema = batch.schema();
+    let mut fields = schema.fields()[1..].to_vec();
+    if let Some(last) = fields.last_mut() {
+        *last = Arc::new(last.as_ref().clone().with_name(timestamp));
+    }
+    let columns = batch.columns()[1..].to_vec();
+    RecordBatch::try_new(Arc::new(ArrowSchema::new(fields)), columns).context(ArrowSnafu)
+}
+
+/// Cast columns of the batch to types of the table by names, missing columns of the table
+/// are nulls and columns not in the table are kept after columns of the table.
+fn align_batch(
+    batch: &RecordBatch,
+    table: &str,
+    table_schema: &ArrowSchema,
+) -> Result<RecordBatch> {
+    let mut fields = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for field in table_schema.fields() {
+        let column = match batch.column_by_name(field.name()) {
+            Some(column) => cast(column, field.data_type()).context(ArrowSnafu)?,
+            None => {
+                ensure!(
+                    field.is_nullable(),
+                    MissingTableColumnSnafu {
+                        table,
+                        column: field.name(),
+                    }
+                );
+                new_null_array(field.data_type(), batch.num_rows())
+            }
+        };
+        fields.push(field.clone());
+        columns.push(column);
+    }
+    let schema = batch.schema();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if table_schema.field_with_name(field.name()).is_err() {
+            fields.push(field.clone());
+            columns.push(column.clone());
+        }
+    }
+
+    RecordBatch::try_new(Arc::new(ArrowSchema::new(fields)), columns).context(ArrowSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::{DataType, Field, Float64Type, TimeUnit};
+
+    #[test]
+    fn test_table_batch() {
+        let input = "cpu,host=a usage=1i,idle=0.5 1\ncpu,host=b usage=2i 2\n";
+        let schemas = Schema::infer_measurements(input.as_bytes()).unwrap();
+        let mut builder = BatchBuilder::new(&schemas[0].1);
+        read_lines(input.as_bytes(), |line| {
+            builder.append_line(&schemas[0].1, &line)
+        })
+        .unwrap();
+        let batch = table_batch(builder.finish().unwrap(), "time").unwrap();
+        let names: Vec<_> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(["host", "usage", "idle", "time"], names.as_slice());
+
+        let table_schema = ArrowSchema::new(vec![
+            Field::new(
+                "time",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("host", DataType::Utf8, true),
+            Field::new("usage", DataType::Float64, true),
+            Field::new("region", DataType::Utf8, true),
+        ]);
+        let aligned = align_batch(&batch, "cpu", &table_schema).unwrap();
+        let names: Vec<_> = aligned
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect();
+        assert_eq!(
+            ["time", "host", "usage", "region", "idle"],
+            names.as_slice()
+        );
+        assert_eq!(
+            [1.0, 2.0],
+            aligned.column(2).as_primitive::<Float64Type>().values()[..]
+        );
+        assert_eq!("b", aligned.column(1).as_string::<i32>().value(1));
+        assert_eq!(2, aligned.column(3).null_count());
+
+        let table_schema = ArrowSchema::new(vec![Field::new("region", DataType::Utf8, false)]);
+        assert!(align_batch(&batch, "cpu", &table_schema).is_err());
+
+        assert_eq!(
+            Ok(("x-greptime-db-name".to_string(), "public".to_string())),
+            parse_header("X-Greptime-DB-Name: public")
+        );
+        assert!(parse_header("public").is_err());
+    }
+}
@@ -0,0 +1,533 @@
+//! Generate synthetic time series for benchmarks.
+
+use crate::error::{InvalidSinkSnafu, Result, WriteCsvSnafu, WriteOutputSnafu};
+use crate::influx::timestamp::{parse_duration, parse_rfc3339};
+use crate::io::{create_output, Compression, Output};
+use crate::rate_limit::RateLimit;
+use chrono::{TimeZone, Utc};
+use clap::{Parser, ValueEnum};
+use csv::Writer;
+use sink::Sink;
+use snafu::{ensure, ResultExt};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+
+mod devops;
+mod finance;
+mod iot;
+mod sink;
+
+/// Use case of the generated data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum UseCase {
+    /// CPU usage of hosts, like the `cpu-only` use case of TSBS.
+    CpuOnly,
+    /// CPU, memory, disk and network metrics of hosts.
+    Devops,
+    /// Readings of sensors at irregular intervals, with dropped readings, offline sensors
+    /// and missing fields.
+    Iot,
+    /// Trades and quotes of symbols, with bursts of ticks.
+    Finance,
+}
+
+/// Format of the generated data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Format {
+    /// Line protocol.
+    Lp,
+    /// CSV with the same columns as `influx to-csv`.
+    Csv,
+}
+
+/// Generate synthetic time series like TSBS.
+#[derive(Debug, Parser)]
+pub struct GenerateCommand {
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Sink to write lines to instead of the output, e.g.
+    /// `influx://localhost:8086/bucket?org=org&token=token`.
+    ///
+    /// Supported schemes are `file://`, `gzip://`, `influx://`, `influxs://`,
+    /// `greptime://` and `kafka://`.
+    #[arg(long, conflicts_with = "output")]
+    sink: Option<String>,
+    /// Number of lines in each request to the sink.
+    #[arg(long, default_value_t = 5000)]
+    batch_size: usize,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Use case of the data.
+    #[arg(long, value_enum, default_value_t = UseCase::CpuOnly)]
+    use_case: UseCase,
+    /// Number of hosts, sensors or symbols of the use case.
+    #[arg(long, default_value_t = 1)]
+    scale: usize,
+    /// Start time of the data in RFC3339, inclusive.
+    #[arg(long, default_value = "2016-01-01T00:00:00Z", value_parser = parse_rfc3339)]
+    start: i64,
+    /// End time of the data in RFC3339, exclusive.
+    #[arg(long, default_value = "2016-01-02T00:00:00Z", value_parser = parse_rfc3339)]
+    end: i64,
+    /// Interval between points of a host or a sensor, and ticks of a symbol are in each
+    /// interval, e.g. 10s.
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    interval: i64,
+    /// Seed of the random generator, the same seed generates the same data.
+    #[arg(long, default_value_t = 123)]
+    seed: u64,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Lp)]
+    format: Format,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl GenerateCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        let mut generator = Generator::new(self.use_case, self.scale, self.seed);
+        if let Some(url) = &self.sink {
+            ensure!(
+                self.format == Format::Lp,
+                InvalidSinkSnafu {
+                    url,
+                    reason: "only line protocol can be written to sinks",
+                }
+            );
+            let mut writer = Sink::parse(url)?.connect(self.batch_size, self.rate_limit)?;
+            generator.for_each_line(self.start, self.end, self.interval, |line| {
+                writer.write_line(line)
+            })?;
+            return writer.finish();
+        }
+
+        let output = create_output(&self.output, self.output_compression)?;
+        let mut output = match self.format {
+            Format::Lp => generator.write_lines(self.start, self.end, self.interval, output)?,
+            Format::Csv => generator.write_csv(self.start, self.end, self.interval, output)?,
+        };
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Deterministic random generator (SplitMix64).
+#[derive(Debug)]
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number in `[0, n)`.
+    pub(crate) fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn choose<T: Copy>(&mut self, values: &[T]) -> T {
+        values[self.below(values.len() as u64) as usize]
+    }
+
+    /// Returns a random number in `[0, 1)`.
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a random number of the standard normal distribution (Box-Muller).
+    pub(crate) fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+const REGIONS: &[(&str, &[&str])] = &[
+    (
+        "us-east-1",
+        &["us-east-1a", "us-east-1b", "us-east-1c", "us-east-1e"],
+    ),
+    ("us-west-1", &["us-west-1a", "us-west-1b"]),
+    ("us-west-2", &["us-west-2a", "us-west-2b", "us-west-2c"]),
+    ("eu-west-1", &["eu-west-1a", "eu-west-1b", "eu-west-1c"]),
+    ("eu-central-1", &["eu-central-1a", "eu-central-1b"]),
+    ("ap-southeast-1", &["ap-southeast-1a", "ap-southeast-1b"]),
+    ("ap-southeast-2", &["ap-southeast-2a", "ap-southeast-2b"]),
+    ("ap-northeast-1", &["ap-northeast-1a", "ap-northeast-1c"]),
+    ("sa-east-1", &["sa-east-1a", "sa-east-1b", "sa-east-1c"]),
+];
+const OS: &[&str] = &["Ubuntu16.10", "Ubuntu16.04LTS", "Ubuntu15.10"];
+const ARCH: &[&str] = &["x64", "x86"];
+const TEAM: &[&str] = &["SF", "NYC", "LON", "CHI"];
+const ENVIRONMENT: &[&str] = &["production", "staging", "test"];
+
+const HOST_TAGS: &[&str] = &[
+    "hostname",
+    "region",
+    "datacenter",
+    "rack",
+    "os",
+    "arch",
+    "team",
+    "service",
+    "service_version",
+    "service_environment",
+];
+const CPU_FIELDS: &[&str] = &[
+    "usage_user",
+    "usage_system",
+    "usage_idle",
+    "usage_nice",
+    "usage_iowait",
+    "usage_irq",
+    "usage_softirq",
+    "usage_steal",
+    "usage_guest",
+    "usage_guest_nice",
+];
+
+/// A measurement of generated rows.
+#[derive(Debug)]
+pub(crate) struct Measurement {
+    pub(crate) name: &'static str,
+    pub(crate) tags: &'static [&'static str],
+    pub(crate) fields: &'static [&'static str],
+}
+
+/// Value of a generated field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Value {
+    Int(i64),
+    Float(f64),
+}
+
+impl Value {
+    /// Returns the float value rounded to 2 decimal places.
+    fn rounded(v: f64) -> Value {
+        Value::Float((v * 100.0).round() / 100.0)
+    }
+}
+
+/// A generated row, tags and fields are in the order of its measurement.
+#[derive(Debug)]
+pub(crate) struct Row<'a> {
+    /// Index of the measurement in the measurements of the profile.
+    pub(crate) measurement: usize,
+    pub(crate) tags: &'a [String],
+    /// Values of fields, `None` for missing fields.
+    pub(crate) fields: &'a [Option<Value>],
+    pub(crate) timestamp: i64,
+}
+
+/// Profile of a use case, which simulates entities like hosts and generates their rows.
+pub(crate) trait Profile: Send {
+    /// Measurements of generated rows.
+    fn measurements(&self) -> &'static [Measurement];
+
+    /// Call `f` with rows of all entities in `[ts, ts + interval)`, then advance the
+    /// simulation to the next interval.
+    fn step(
+        &mut self,
+        ts: i64,
+        interval: i64,
+        f: &mut dyn FnMut(Row<'_>) -> Result<()>,
+    ) -> Result<()>;
+}
+
+/// Generator of rows of a profile in line protocol or CSV.
+pub(crate) struct Generator {
+    profile: Box<dyn Profile>,
+}
+
+impl Generator {
+    /// Returns the generator of `scale` entities of the use case.
+    pub(crate) fn new(use_case: UseCase, scale: usize, seed: u64) -> Generator {
+        let profile: Box<dyn Profile> = match use_case {
+            UseCase::CpuOnly => Box::new(CpuOnly::new(scale, seed)),
+            UseCase::Devops => Box::new(devops::Devops::new(scale, seed)),
+            UseCase::Iot => Box::new(iot::Iot::new(scale, seed)),
+            UseCase::Finance => Box::new(finance::Finance::new(scale, seed)),
+        };
+        Generator { profile }
+    }
+
+    /// Write lines of all intervals in `[start, end)`, rows without fields are skipped.
+    pub(crate) fn write_lines<W: Write>(
+        &mut self,
+        start: i64,
+        end: i64,
+        interval: i64,
+        dest: W,
+    ) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        self.for_each_line(start, end, interval, |line| {
+            writer.write_all(line.as_bytes()).context(WriteOutputSnafu)
+        })?;
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+
+    /// Call `f` with each line of all intervals in `[start, end)`, with the trailing
+    /// newline. Rows without fields are skipped.
+    pub(crate) fn for_each_line(
+        &mut self,
+        start: i64,
+        end: i64,
+        interval: i64,
+        mut f: impl FnMut(&str) -> Result<()>,
+    ) -> Result<()> {
+        let measurements = self.profile.measurements();
+        let mut line = String::new();
+        let mut ts = start;
+        while ts < end {
+            self.profile.step(ts, interval, &mut |row| {
+                let measurement = &measurements[row.measurement];
+                line.clear();
+                line.push_str(measurement.name);
+                for (name, value) in measurement.tags.iter().zip(row.tags) {
+                    write!(line, ",{name}={value}").unwrap();
+                }
+                let mut sep = ' ';
+                for (name, value) in measurement.fields.iter().zip(row.fields) {
+                    match value {
+                        Some(Value::Int(v)) => write!(line, "{sep}{name}={v}i").unwrap(),
+                        Some(Value::Float(v)) => write!(line, "{sep}{name}={v}").unwrap(),
+                        None => continue,
+                    }
+                    sep = ',';
+                }
+                if sep == ' ' {
+                    return Ok(());
+                }
+                writeln!(line, " {}", row.timestamp).unwrap();
+                f(&line)
+            })?;
+            ts = ts.saturating_add(interval);
+        }
+        Ok(())
+    }
+
+    /// Write rows of all intervals in `[start, end)` as CSV.
+    ///
+    /// Columns are tags and fields of all measurements, values of other measurements are
+    /// empty.
+    fn write_csv<W: Write>(&mut self, start: i64, end: i64, interval: i64, dest: W) -> Result<W> {
+        let measurements = self.profile.measurements();
+        let mut tags: Vec<&str> = Vec::new();
+        let mut fields: Vec<&str> = Vec::new();
+        // Indices of columns of tags and fields of each measurement.
+        let columns: Vec<(Vec<usize>, Vec<usize>)> = measurements
+            .iter()
+            .map(|measurement| {
+                let tag_columns = measurement
+                    .tags
+                    .iter()
+                    .map(|name| column_index(&mut tags, name))
+                    .collect();
+                let field_columns = measurement
+                    .fields
+                    .iter()
+                    .map(|name| column_index(&mut fields, name))
+                    .collect();
+                (tag_columns, field_columns)
+            })
+            .collect();
+
+        let mut writer = Writer::from_writer(dest);
+        let headers = ["measurement"]
+            .iter()
+            .chain(&tags)
+            .chain(&fields)
+            .chain(&["timestamp"]);
+        writer.write_record(headers).context(WriteCsvSnafu)?;
+
+        let mut record = vec![String::new(); tags.len() + fields.len() + 2];
+        let mut ts = start;
+        while ts < end {
+            self.profile.step(ts, interval, &mut |row| {
+                let (tag_columns, field_columns) = &columns[row.measurement];
+                record.iter_mut().for_each(String::clear);
+                record[0].push_str(measurements[row.measurement].name);
+                for (i, value) in tag_columns.iter().zip(row.tags) {
+                    record[1 + i].push_str(value);
+                }
+                for (i, value) in field_columns.iter().zip(row.fields) {
+                    let column = &mut record[1 + tags.len() + i];
+                    match value {
+                        Some(Value::Int(v)) => write!(column, "{v}").unwrap(),
+                        Some(Value::Float(v)) => write!(column, "{v}").unwrap(),
+                        None => {}
+                    }
+                }
+                let timestamp = Utc.timestamp_nanos(row.timestamp).to_rfc3339();
+                *record.last_mut().unwrap() = timestamp;
+                writer.write_record(&record).context(WriteCsvSnafu)
+            })?;
+            ts = ts.saturating_add(interval);
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+/// Returns the index of the name in the columns, the name is appended if it is missing.
+fn column_index<'a>(columns: &mut Vec<&'a str>, name: &'a str) -> usize {
+    match columns.iter().position(|column| *column == name) {
+        Some(i) => i,
+        None => {
+            columns.push(name);
+            columns.len() - 1
+        }
+    }
+}
+
+/// Returns random tags of the host in the order of `HOST_TAGS`.
+fn host_tags(rng: &mut Rng, i: usize) -> Vec<String> {
+    let (region, datacenters) = REGIONS[rng.below(REGIONS.len() as u64) as usize];
+    vec![
+        format!("host_{i}"),
+        region.to_string(),
+        rng.choose(datacenters).to_string(),
+        rng.below(100).to_string(),
+        rng.choose(OS).to_string(),
+        rng.choose(ARCH).to_string(),
+        rng.choose(TEAM).to_string(),
+        rng.below(20).to_string(),
+        rng.below(2).to_string(),
+        rng.choose(ENVIRONMENT).to_string(),
+    ]
+}
+
+/// Advance CPU usages by a bounded random walk.
+fn walk_usages(rng: &mut Rng, usages: &mut [i64]) {
+    for usage in usages {
+        let delta = rng.below(11) as i64 - 5;
+        *usage = (*usage + delta).clamp(0, 100);
+    }
+}
+
+const CPU_MEASUREMENTS: &[Measurement] = &[Measurement {
+    name: "cpu",
+    tags: HOST_TAGS,
+    fields: CPU_FIELDS,
+}];
+
+/// Simulated host with its tags and current usages.
+#[derive(Debug)]
+struct Host {
+    tags: Vec<String>,
+    usages: [i64; CPU_FIELDS.len()],
+}
+
+/// Profile of the `cpu` measurement of hosts.
+#[derive(Debug)]
+struct CpuOnly {
+    rng: Rng,
+    hosts: Vec<Host>,
+}
+
+impl CpuOnly {
+    fn new(scale: usize, seed: u64) -> CpuOnly {
+        let mut rng = Rng(seed);
+        let hosts = (0..scale)
+            .map(|i| {
+                let tags = host_tags(&mut rng, i);
+                let usages = std::array::from_fn(|_| rng.below(101) as i64);
+                Host { tags, usages }
+            })
+            .collect();
+
+        CpuOnly { rng, hosts }
+    }
+}
+
+impl Profile for CpuOnly {
+    fn measurements(&self) -> &'static [Measurement] {
+        CPU_MEASUREMENTS
+    }
+
+    fn step(
+        &mut self,
+        ts: i64,
+        _interval: i64,
+        f: &mut dyn FnMut(Row<'_>) -> Result<()>,
+    ) -> Result<()> {
+        for host in &self.hosts {
+            let fields = host.usages.map(|usage| Some(Value::Int(usage)));
+            f(Row {
+                measurement: 0,
+                tags: &host.tags,
+                fields: &fields,
+                timestamp: ts,
+            })?;
+        }
+        for host in &mut self.hosts {
+            walk_usages(&mut self.rng, &mut host.usages);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::influx::read_lines;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_cpu_only() {
+        let start = 1451606400000000000;
+        let end = start + 30_000_000_000;
+        let interval = 10_000_000_000;
+        let lines = Generator::new(UseCase::CpuOnly, 2, 1)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        let again = Generator::new(UseCase::CpuOnly, 2, 1)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        assert_eq!(lines, again);
+
+        let mut timestamps = Vec::new();
+        read_lines(Cursor::new(&lines), |line| {
+            assert_eq!("cpu", line.series.measurement.as_str());
+            assert_eq!(HOST_TAGS.len(), line.series.tag_set.unwrap().len());
+            assert_eq!(CPU_FIELDS.len(), line.field_set.len());
+            timestamps.push(line.timestamp.unwrap());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            vec![
+                start,
+                start,
+                start + interval,
+                start + interval,
+                start + 2 * interval,
+                start + 2 * interval
+            ],
+            timestamps
+        );
+        assert!(String::from_utf8(lines)
+            .unwrap()
+            .starts_with("cpu,hostname=host_0,"));
+
+        let csv = Generator::new(UseCase::CpuOnly, 2, 1)
+            .write_csv(start, end, interval, Vec::new())
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(7, csv.lines().count());
+        assert!(csv.starts_with("measurement,hostname,region,"));
+    }
+}
@@ -0,0 +1,304 @@
+//! Profile of CPU, memory, disk and network metrics of hosts, like the `devops` use case
+//! of TSBS.
+
+use crate::error::Result;
+use crate::generate::{
+    host_tags, walk_usages, Measurement, Profile, Rng, Row, Value, CPU_FIELDS, HOST_TAGS,
+};
+
+const DISK_TAGS: &[&str] = &[
+    "hostname",
+    "region",
+    "datacenter",
+    "rack",
+    "os",
+    "arch",
+    "team",
+    "service",
+    "service_version",
+    "service_environment",
+    "path",
+    "fstype",
+];
+const NET_TAGS: &[&str] = &[
+    "hostname",
+    "region",
+    "datacenter",
+    "rack",
+    "os",
+    "arch",
+    "team",
+    "service",
+    "service_version",
+    "service_environment",
+    "interface",
+];
+const MEM_FIELDS: &[&str] = &[
+    "total",
+    "available",
+    "used",
+    "free",
+    "cached",
+    "buffered",
+    "used_percent",
+    "available_percent",
+];
+const DISK_FIELDS: &[&str] = &[
+    "total",
+    "free",
+    "used",
+    "used_percent",
+    "inodes_total",
+    "inodes_free",
+    "inodes_used",
+];
+const NET_FIELDS: &[&str] = &[
+    "bytes_sent",
+    "bytes_recv",
+    "packets_sent",
+    "packets_recv",
+    "err_in",
+    "err_out",
+    "drop_in",
+    "drop_out",
+];
+
+const MEASUREMENTS: &[Measurement] = &[
+    Measurement {
+        name: "cpu",
+        tags: HOST_TAGS,
+        fields: CPU_FIELDS,
+    },
+    Measurement {
+        name: "mem",
+        tags: HOST_TAGS,
+        fields: MEM_FIELDS,
+    },
+    Measurement {
+        name: "disk",
+        tags: DISK_TAGS,
+        fields: DISK_FIELDS,
+    },
+    Measurement {
+        name: "net",
+        tags: NET_TAGS,
+        fields: NET_FIELDS,
+    },
+];
+
+/// Memory sizes of hosts in GiB.
+const MEM_GIB: &[i64] = &[8, 16, 32, 64, 128];
+/// Disk sizes of hosts in GiB.
+const DISK_GIB: &[i64] = &[100, 250, 500, 1000];
+const GIB: i64 = 1 << 30;
+/// Bytes per inode of file systems.
+const INODE_BYTES: i64 = 16 << 10;
+/// Disk usage is cleaned up to half of the disk once it reaches this ratio.
+const DISK_FULL_RATIO: f64 = 0.95;
+
+/// Simulated host with its tags and current metrics.
+#[derive(Debug)]
+struct Host {
+    tags: Vec<String>,
+    disk_tags: Vec<String>,
+    net_tags: Vec<String>,
+    usages: [i64; CPU_FIELDS.len()],
+    mem_total: i64,
+    /// Ratios of used and cached memory.
+    mem_used: f64,
+    mem_cached: f64,
+    disk_total: i64,
+    disk_used: i64,
+    /// Counters of `NET_FIELDS`.
+    net: [i64; NET_FIELDS.len()],
+}
+
+/// Profile of the `cpu`, `mem`, `disk` and `net` measurements of hosts.
+#[derive(Debug)]
+pub(super) struct Devops {
+    rng: Rng,
+    hosts: Vec<Host>,
+}
+
+impl Devops {
+    pub(super) fn new(scale: usize, seed: u64) -> Devops {
+        let mut rng = Rng(seed);
+        let hosts = (0..scale)
+            .map(|i| {
+                let tags = host_tags(&mut rng, i);
+                let mut disk_tags = tags.clone();
+                disk_tags.extend(["/".to_string(), "ext4".to_string()]);
+                let mut net_tags = tags.clone();
+                net_tags.push("eth0".to_string());
+                let disk_total = rng.choose(DISK_GIB) * GIB;
+                Host {
+                    tags,
+                    disk_tags,
+                    net_tags,
+                    usages: std::array::from_fn(|_| rng.below(101) as i64),
+                    mem_total: rng.choose(MEM_GIB) * GIB,
+                    mem_used: 0.2 + rng.next_f64() * 0.6,
+                    mem_cached: rng.next_f64() * 0.05,
+                    disk_total,
+                    disk_used: (disk_total as f64 * (0.1 + rng.next_f64() * 0.5)) as i64,
+                    net: [0; NET_FIELDS.len()],
+                }
+            })
+            .collect();
+
+        Devops { rng, hosts }
+    }
+}
+
+impl Profile for Devops {
+    fn measurements(&self) -> &'static [Measurement] {
+        MEASUREMENTS
+    }
+
+    fn step(
+        &mut self,
+        ts: i64,
+        _interval: i64,
+        f: &mut dyn FnMut(Row<'_>) -> Result<()>,
+    ) -> Result<()> {
+        for host in &self.hosts {
+            let cpu = host.usages.map(|usage| Some(Value::Int(usage)));
+            f(Row {
+                measurement: 0,
+                tags: &host.tags,
+                fields: &cpu,
+                timestamp: ts,
+            })?;
+
+            let total = host.mem_total as f64;
+            let used = (total * host.mem_used) as i64;
+            let cached = (total * host.mem_cached) as i64;
+            let buffered = host.mem_total / 50;
+            let free = (host.mem_total - used - cached - buffered).max(0);
+            let available = free + cached + buffered;
+            let mem = [
+                Value::Int(host.mem_total),
+                Value::Int(available),
+                Value::Int(used),
+                Value::Int(free),
+                Value::Int(cached),
+                Value::Int(buffered),
+                Value::rounded(used as f64 / total * 100.0),
+                Value::rounded(available as f64 / total * 100.0),
+            ]
+            .map(Some);
+            f(Row {
+                measurement: 1,
+                tags: &host.tags,
+                fields: &mem,
+                timestamp: ts,
+            })?;
+
+            let inodes_total = host.disk_total / INODE_BYTES;
+            let inodes_used = host.disk_used / INODE_BYTES;
+            let disk = [
+                Value::Int(host.disk_total),
+                Value::Int(host.disk_total - host.disk_used),
+                Value::Int(host.disk_used),
+                Value::rounded(host.disk_used as f64 / host.disk_total as f64 * 100.0),
+                Value::Int(inodes_total),
+                Value::Int(inodes_total - inodes_used),
+                Value::Int(inodes_used),
+            ]
+            .map(Some);
+            f(Row {
+                measurement: 2,
+                tags: &host.disk_tags,
+                fields: &disk,
+                timestamp: ts,
+            })?;
+
+            let net = host.net.map(|v| Some(Value::Int(v)));
+            f(Row {
+                measurement: 3,
+                tags: &host.net_tags,
+                fields: &net,
+                timestamp: ts,
+            })?;
+        }
+
+        let rng = &mut self.rng;
+        for host in &mut self.hosts {
+            walk_usages(rng, &mut host.usages);
+            host.mem_used = (host.mem_used + rng.gaussian() * 0.01).clamp(0.05, 0.9);
+            host.mem_cached = (host.mem_cached + rng.gaussian() * 0.005).clamp(0.0, 0.05);
+            host.disk_used += rng.below(64 << 20) as i64;
+            if host.disk_used as f64 > host.disk_total as f64 * DISK_FULL_RATIO {
+                host.disk_used = host.disk_total / 2;
+            }
+            let [bytes_sent, bytes_recv, packets_sent, packets_recv, err_in, err_out, drop_in, drop_out] =
+                &mut host.net;
+            *bytes_sent += rng.below(16 << 20) as i64;
+            *bytes_recv += rng.below(16 << 20) as i64;
+            *packets_sent += rng.below(10_000) as i64;
+            *packets_recv += rng.below(10_000) as i64;
+            // Errors and drops are rare.
+            for counter in [err_in, err_out, drop_in, drop_out] {
+                *counter += i64::from(rng.below(100) == 0);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{Generator, UseCase};
+    use crate::influx::read_lines;
+    use influxdb_line_protocol::FieldValue;
+
+    #[test]
+    fn test_devops() {
+        let start = 1451606400000000000;
+        let interval = 10_000_000_000;
+        let end = start + 3 * interval;
+        let lines = Generator::new(UseCase::Devops, 2, 7)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        let again = Generator::new(UseCase::Devops, 2, 7)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        assert_eq!(lines, again);
+
+        let mut measurements = Vec::new();
+        let mut last_bytes_sent = 0;
+        read_lines(lines.as_slice(), |line| {
+            let measurement = line.series.measurement.as_str();
+            measurements.push(measurement.to_string());
+            let tags = line.series.tag_set.as_ref().unwrap();
+            if measurement == "net" {
+                assert_eq!(NET_TAGS.len(), tags.len());
+                assert_eq!(NET_FIELDS.len(), line.field_set.len());
+                if tags[0].1.as_str() == "host_0" {
+                    let FieldValue::I64(bytes_sent) = line.field_set[0].1 else {
+                        panic!("unexpected value {:?}", line.field_set[0].1);
+                    };
+                    assert!(bytes_sent >= last_bytes_sent);
+                    last_bytes_sent = bytes_sent;
+                }
+            }
+            if measurement == "disk" {
+                assert_eq!(DISK_TAGS.len(), tags.len());
+                assert_eq!("/", tags[10].1.as_str());
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(3 * 2 * MEASUREMENTS.len(), measurements.len());
+        assert_eq!(["cpu", "mem", "disk", "net"], measurements[..4]);
+        assert!(last_bytes_sent > 0);
+
+        let csv = Generator::new(UseCase::Devops, 1, 7)
+            .write_csv(start, end, interval, Vec::new())
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(1 + 3 * MEASUREMENTS.len(), csv.lines().count());
+        assert!(csv.starts_with("measurement,hostname,region,"));
+    }
+}
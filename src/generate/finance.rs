@@ -0,0 +1,186 @@
+//! Profile of tick data of symbols, with bursts of ticks.
+
+use crate::error::Result;
+use crate::generate::{Measurement, Profile, Rng, Row, Value};
+
+const TAGS: &[&str] = &["symbol", "exchange"];
+const MEASUREMENTS: &[Measurement] = &[
+    Measurement {
+        name: "trades",
+        tags: TAGS,
+        fields: &["price", "size"],
+    },
+    Measurement {
+        name: "quotes",
+        tags: TAGS,
+        fields: &["bid", "ask", "bid_size", "ask_size"],
+    },
+];
+
+const EXCHANGES: &[&str] = &["NASDAQ", "NYSE", "ARCA", "BATS"];
+
+/// Mean number of ticks of a symbol in an interval.
+const MEAN_TICKS: u64 = 4;
+/// Probability that a symbol starts a burst in an interval.
+const BURST_PROBABILITY: f64 = 0.02;
+/// Max number of intervals of a burst.
+const MAX_BURST_INTERVALS: u64 = 10;
+/// Ticks and volatility are multiplied in bursts.
+const BURST_TICKS: u64 = 20;
+const BURST_VOLATILITY: f64 = 4.0;
+/// Standard deviation of the log return of a tick.
+const VOLATILITY: f64 = 0.0005;
+/// Probability that a tick is a trade instead of a quote.
+const TRADE_PROBABILITY: f64 = 0.4;
+
+/// Simulated symbol with its tags and current price.
+#[derive(Debug)]
+struct Symbol {
+    tags: Vec<String>,
+    price: f64,
+    /// Number of intervals until the burst ends.
+    burst: u64,
+}
+
+/// Profile of the `trades` and `quotes` measurements of symbols.
+///
+/// Ticks of a symbol are at random times of an interval, and a symbol may burst with many
+/// more ticks and larger price moves for a few intervals.
+#[derive(Debug)]
+pub(super) struct Finance {
+    rng: Rng,
+    symbols: Vec<Symbol>,
+    /// Buffer of timestamps of ticks.
+    ticks: Vec<i64>,
+}
+
+impl Finance {
+    pub(super) fn new(scale: usize, seed: u64) -> Finance {
+        let mut rng = Rng(seed);
+        let symbols = (0..scale)
+            .map(|i| Symbol {
+                tags: vec![format!("SYM{i:04}"), rng.choose(EXCHANGES).to_string()],
+                price: 10.0 + rng.next_f64() * 490.0,
+                burst: 0,
+            })
+            .collect();
+
+        Finance {
+            rng,
+            symbols,
+            ticks: Vec::new(),
+        }
+    }
+}
+
+impl Profile for Finance {
+    fn measurements(&self) -> &'static [Measurement] {
+        MEASUREMENTS
+    }
+
+    fn step(
+        &mut self,
+        ts: i64,
+        interval: i64,
+        f: &mut dyn FnMut(Row<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let rng = &mut self.rng;
+        for symbol in &mut self.symbols {
+            if symbol.burst > 0 {
+                symbol.burst -= 1;
+            } else if rng.next_f64() < BURST_PROBABILITY {
+                symbol.burst = 1 + rng.below(MAX_BURST_INTERVALS);
+            }
+            let (num_ticks, volatility) = if symbol.burst > 0 {
+                (
+                    rng.below(2 * MEAN_TICKS * BURST_TICKS + 1),
+                    VOLATILITY * BURST_VOLATILITY,
+                )
+            } else {
+                (rng.below(2 * MEAN_TICKS + 1), VOLATILITY)
+            };
+
+            self.ticks.clear();
+            self.ticks.extend(
+                (0..num_ticks).map(|_| ts.saturating_add(rng.below(interval.max(1) as u64) as i64)),
+            );
+            self.ticks.sort_unstable();
+            for &timestamp in &self.ticks {
+                symbol.price = (symbol.price * (rng.gaussian() * volatility).exp()).max(0.01);
+                let price = (symbol.price * 100.0).round() / 100.0;
+                if rng.next_f64() < TRADE_PROBABILITY {
+                    let size = 100 * (1 + rng.below(100) as i64);
+                    f(Row {
+                        measurement: 0,
+                        tags: &symbol.tags,
+                        fields: &[Some(Value::Float(price)), Some(Value::Int(size))],
+                        timestamp,
+                    })?;
+                } else {
+                    let spread = (price * 0.0002).max(0.01);
+                    let bid = price - spread / 2.0;
+                    let fields = [
+                        Value::rounded(bid),
+                        Value::rounded(bid + spread),
+                        Value::Int(100 * (1 + rng.below(50) as i64)),
+                        Value::Int(100 * (1 + rng.below(50) as i64)),
+                    ]
+                    .map(Some);
+                    f(Row {
+                        measurement: 1,
+                        tags: &symbol.tags,
+                        fields: &fields,
+                        timestamp,
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{Generator, UseCase};
+    use crate::influx::read_lines;
+    use influxdb_line_protocol::FieldValue;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_finance() {
+        let start = 1451606400000000000;
+        let interval = 1_000_000_000;
+        let steps = 500;
+        let end = start + steps * interval;
+        let lines = Generator::new(UseCase::Finance, 2, 5)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        let again = Generator::new(UseCase::Finance, 2, 5)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        assert_eq!(lines, again);
+
+        // Number of ticks of each symbol in each interval.
+        let mut ticks: BTreeMap<(String, i64), u64> = BTreeMap::new();
+        let mut last_ts: BTreeMap<String, i64> = BTreeMap::new();
+        read_lines(lines.as_slice(), |line| {
+            let symbol = line.series.tag_set.as_ref().unwrap()[0].1.to_string();
+            let ts = line.timestamp.unwrap();
+            // Ticks of a symbol are in time order.
+            assert!(ts >= *last_ts.get(&symbol).unwrap_or(&start));
+            last_ts.insert(symbol.clone(), ts);
+            *ticks.entry((symbol, (ts - start) / interval)).or_default() += 1;
+            match line.series.measurement.as_str() {
+                "trades" => assert!(matches!(line.field_set[0].1, FieldValue::F64(p) if p > 0.0)),
+                "quotes" => assert_eq!(4, line.field_set.len()),
+                other => panic!("unexpected measurement {other}"),
+            }
+            Ok(())
+        })
+        .unwrap();
+        // Bursts have much more ticks than usual intervals.
+        let max_ticks = ticks.values().copied().max().unwrap();
+        assert!(max_ticks > 2 * MEAN_TICKS, "{max_ticks}");
+    }
+}
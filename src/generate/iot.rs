@@ -0,0 +1,172 @@
+//! Profile of readings of sensors, which are irregular and incomplete.
+
+use crate::error::Result;
+use crate::generate::{Measurement, Profile, Rng, Row, Value};
+
+const MEASUREMENTS: &[Measurement] = &[Measurement {
+    name: "sensor",
+    tags: &["sensor_id", "site", "model", "firmware"],
+    fields: &["temperature", "humidity", "pressure", "battery", "rssi"],
+}];
+
+const SITES: &[&str] = &[
+    "factory_a",
+    "factory_b",
+    "warehouse",
+    "greenhouse",
+    "office",
+];
+const MODELS: &[&str] = &["TH-100", "TH-200", "ENV-3"];
+const FIRMWARES: &[&str] = &["1.0.3", "1.1.0", "2.0.1"];
+
+/// Probability that a reading of an online sensor is dropped.
+const DROP_PROBABILITY: f64 = 0.05;
+/// Probability that an online sensor goes offline.
+const OFFLINE_PROBABILITY: f64 = 0.01;
+/// Max number of intervals a sensor stays offline.
+const MAX_OFFLINE_INTERVALS: u64 = 30;
+/// Probability that a field of a reading is missing.
+const MISSING_FIELD_PROBABILITY: f64 = 0.02;
+
+/// Simulated sensor with its tags and current readings.
+#[derive(Debug)]
+struct Sensor {
+    tags: Vec<String>,
+    temperature: f64,
+    humidity: f64,
+    pressure: f64,
+    battery: f64,
+    /// Number of intervals until the sensor is online again.
+    offline: u64,
+}
+
+/// Profile of the `sensor` measurement of sensors.
+///
+/// A sensor reads once in an interval at a random time, so readings of an interval are
+/// not in time order. Readings may be dropped, sensors may be offline for a while and
+/// fields of a reading may be missing.
+#[derive(Debug)]
+pub(super) struct Iot {
+    rng: Rng,
+    sensors: Vec<Sensor>,
+}
+
+impl Iot {
+    pub(super) fn new(scale: usize, seed: u64) -> Iot {
+        let mut rng = Rng(seed);
+        let sensors = (0..scale)
+            .map(|i| Sensor {
+                tags: vec![
+                    format!("sensor_{i}"),
+                    rng.choose(SITES).to_string(),
+                    rng.choose(MODELS).to_string(),
+                    rng.choose(FIRMWARES).to_string(),
+                ],
+                temperature: 15.0 + rng.next_f64() * 15.0,
+                humidity: 30.0 + rng.next_f64() * 40.0,
+                pressure: 1000.0 + rng.next_f64() * 25.0,
+                battery: 50.0 + rng.next_f64() * 50.0,
+                offline: 0,
+            })
+            .collect();
+
+        Iot { rng, sensors }
+    }
+}
+
+impl Profile for Iot {
+    fn measurements(&self) -> &'static [Measurement] {
+        MEASUREMENTS
+    }
+
+    fn step(
+        &mut self,
+        ts: i64,
+        interval: i64,
+        f: &mut dyn FnMut(Row<'_>) -> Result<()>,
+    ) -> Result<()> {
+        let rng = &mut self.rng;
+        for sensor in &mut self.sensors {
+            if sensor.offline > 0 {
+                sensor.offline -= 1;
+            } else if rng.next_f64() < OFFLINE_PROBABILITY {
+                sensor.offline = 1 + rng.below(MAX_OFFLINE_INTERVALS);
+            } else if rng.next_f64() >= DROP_PROBABILITY {
+                let timestamp = ts.saturating_add(rng.below(interval.max(1) as u64) as i64);
+                let rssi = -40 - rng.below(60) as i64;
+                let mut fields = [
+                    Value::rounded(sensor.temperature),
+                    Value::rounded(sensor.humidity),
+                    Value::rounded(sensor.pressure),
+                    Value::rounded(sensor.battery),
+                    Value::Int(rssi),
+                ]
+                .map(Some);
+                for field in &mut fields {
+                    if rng.next_f64() < MISSING_FIELD_PROBABILITY {
+                        *field = None;
+                    }
+                }
+                f(Row {
+                    measurement: 0,
+                    tags: &sensor.tags,
+                    fields: &fields,
+                    timestamp,
+                })?;
+            }
+
+            sensor.temperature = (sensor.temperature + rng.gaussian() * 0.1).clamp(-20.0, 50.0);
+            sensor.humidity = (sensor.humidity + rng.gaussian() * 0.5).clamp(0.0, 100.0);
+            sensor.pressure = (sensor.pressure + rng.gaussian() * 0.2).clamp(950.0, 1060.0);
+            // Batteries drain slowly and are replaced when empty.
+            sensor.battery -= rng.next_f64() * 0.01;
+            if sensor.battery <= 0.0 {
+                sensor.battery = 100.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{Generator, UseCase};
+    use crate::influx::read_lines;
+
+    #[test]
+    fn test_iot() {
+        let start = 1451606400000000000;
+        let interval = 10_000_000_000;
+        let steps = 200;
+        let scale = 10;
+        let end = start + steps * interval;
+        let lines = Generator::new(UseCase::Iot, scale, 3)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        let again = Generator::new(UseCase::Iot, scale, 3)
+            .write_lines(start, end, interval, Vec::new())
+            .unwrap();
+        assert_eq!(lines, again);
+
+        let mut readings = 0;
+        let mut missing_fields = 0;
+        let mut unaligned = 0;
+        read_lines(lines.as_slice(), |line| {
+            readings += 1;
+            missing_fields += MEASUREMENTS[0].fields.len() - line.field_set.len();
+            let ts = line.timestamp.unwrap();
+            assert!((start..end).contains(&ts));
+            if (ts - start) % interval != 0 {
+                unaligned += 1;
+            }
+            Ok(())
+        })
+        .unwrap();
+        // Some readings are dropped or offline, and some fields are missing.
+        assert!(readings < steps as usize * scale, "{readings}");
+        assert!(readings > steps as usize * scale / 2, "{readings}");
+        assert!(missing_fields > 0);
+        assert!(unaligned > readings / 2);
+    }
+}
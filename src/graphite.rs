@@ -0,0 +1,629 @@
+//! Tools for Graphite target.
+
+mod pickle;
+
+use crate::error::{
+    InvalidPickleSnafu, InvalidPointSnafu, LineSnafu, ReadInputSnafu, Result, WriteOutputSnafu,
+};
+use crate::influx::{push_fields, push_series, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::reject;
+use chrono::Utc;
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+/// Graphite command.
+#[derive(Debug, Parser)]
+pub struct GraphiteCommand {
+    #[clap(subcommand)]
+    subcmd: GraphiteSubcommand,
+}
+
+impl GraphiteCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            GraphiteSubcommand::ToLp(c) => c.run(),
+            GraphiteSubcommand::FromLp(c) => c.run(),
+            GraphiteSubcommand::ReadPickle(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Graphite.
+#[derive(Debug, Parser)]
+enum GraphiteSubcommand {
+    /// Plaintext protocol to line protocol.
+    ToLp(ToLp),
+    /// Line protocol to plaintext protocol.
+    FromLp(FromLp),
+    /// Pickle protocol batches to line protocol.
+    ReadPickle(ReadPickle),
+}
+
+/// Meaning of a segment of metric paths.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Measurement,
+    Field,
+    Tag(String),
+    /// The segment is ignored.
+    Skip,
+}
+
+/// Template mapping segments of metric paths to the measurement, field and tags.
+///
+/// Segments are separated by `.`, e.g. `region.host.measurement.field`. `measurement` and
+/// `field` may appear multiple times and their segments are joined by `.`, a trailing `*`
+/// like `measurement*` takes all remaining segments. Empty segments are ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Template {
+    segments: Vec<Segment>,
+    /// Index of the segment taking remaining segments.
+    greedy: Option<usize>,
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Template, String> {
+        let mut segments = Vec::new();
+        let mut greedy = None;
+        for (i, part) in s.split('.').enumerate() {
+            let name = match part.strip_suffix('*') {
+                Some(name) => {
+                    if greedy.is_some() {
+                        return Err(format!("template {s:?} has multiple `*`"));
+                    }
+                    greedy = Some(i);
+                    name
+                }
+                None => part,
+            };
+            let segment = match name {
+                "measurement" => Segment::Measurement,
+                "field" => Segment::Field,
+                "" => Segment::Skip,
+                tag => Segment::Tag(tag.to_string()),
+            };
+            if greedy == Some(i) && !matches!(segment, Segment::Measurement | Segment::Field) {
+                return Err(format!(
+                    "only measurement and field may end with `*` in {s:?}"
+                ));
+            }
+            segments.push(segment);
+        }
+        if !segments.contains(&Segment::Measurement) {
+            return Err(format!("template {s:?} has no measurement"));
+        }
+        Ok(Template { segments, greedy })
+    }
+}
+
+impl Template {
+    /// Returns the segment of each part of the path, extra parts are ignored if the
+    /// template has no `*`.
+    fn segments_of(&self, num_parts: usize) -> impl Iterator<Item = &Segment> + '_ {
+        let num_segments = self.segments.len();
+        (0..num_parts).filter_map(move |i| match self.greedy {
+            Some(g) if i >= g => {
+                // Parts after the greedy part are mapped to the segments after it.
+                let tail = num_segments - g - 1;
+                let j = if num_parts - i <= tail {
+                    num_segments - (num_parts - i)
+                } else {
+                    g
+                };
+                self.segments.get(j)
+            }
+            _ => self.segments.get(i),
+        })
+    }
+
+    /// Returns the measurement, field and tags of the path.
+    fn parse<'a>(&'a self, path: &'a str) -> (String, String, Vec<(&'a str, &'a str)>) {
+        let parts: Vec<_> = path.split('.').collect();
+        let mut measurement = Vec::new();
+        let mut field = Vec::new();
+        let mut tags = Vec::new();
+        for (part, segment) in parts.iter().zip(self.segments_of(parts.len())) {
+            match segment {
+                Segment::Measurement => measurement.push(*part),
+                Segment::Field => field.push(*part),
+                Segment::Tag(name) => tags.push((name.as_str(), *part)),
+                Segment::Skip => (),
+            }
+        }
+        let field = if field.is_empty() {
+            "value".to_string()
+        } else {
+            field.join(".")
+        };
+        (measurement.join("."), field, tags)
+    }
+
+    /// Push the path of the field of the line, tags not in the template are returned.
+    fn push_path<'a>(
+        &self,
+        path: &mut String,
+        line: &'a ParsedLine<'_>,
+        field: &str,
+    ) -> Vec<(&'a str, &'a str)> {
+        let mut tags: Vec<_> = line
+            .series
+            .tag_set
+            .iter()
+            .flatten()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        let mut parts = Vec::with_capacity(self.segments.len());
+        for segment in &self.segments {
+            match segment {
+                Segment::Measurement => parts.push(line.series.measurement.as_str()),
+                Segment::Field => parts.push(field),
+                Segment::Tag(name) => {
+                    if let Some(i) = tags.iter().position(|(k, _)| k == name) {
+                        parts.push(tags.remove(i).1);
+                    }
+                }
+                Segment::Skip => (),
+            }
+        }
+        if !self.segments.contains(&Segment::Field) {
+            parts.push(field);
+        }
+
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                path.push('.');
+            }
+            push_sanitized(path, part, &['.']);
+        }
+        tags
+    }
+}
+
+/// Push the value, whitespaces, `;` and the chars are replaced by `_`.
+fn push_sanitized(path: &mut String, value: &str, chars: &[char]) {
+    path.extend(value.chars().map(|c| {
+        if c.is_whitespace() || c == ';' || chars.contains(&c) {
+            '_'
+        } else {
+            c
+        }
+    }));
+}
+
+/// Convert Graphite plaintext protocol file to line protocol file.
+///
+/// Each line is `<path>[;tag=value...] <value> <timestamp>` with the timestamp in second,
+/// the path is mapped by the template and Graphite tags are kept as tags.
+#[derive(Debug, Parser)]
+struct ToLp {
+    /// Input plaintext protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Template of metric paths, e.g. `host.measurement.field`, the whole path is the
+    /// measurement with the `value` field by default.
+    #[arg(long, default_value = "measurement*")]
+    template: Template,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = graphite_to_line_protocol(input, output, &self.template, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol file to Graphite plaintext protocol file.
+///
+/// Each numeric field is a metric, booleans are converted to 0 and 1 and string fields are
+/// skipped. Tags not in the template are written as Graphite tags.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output plaintext protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Template of metric paths, e.g. `host.measurement.field`, the field is appended if
+    /// the template has no field.
+    #[arg(long, default_value = "measurement.field")]
+    template: Template,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = line_protocol_to_graphite(input, output, &self.template, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert batches of Graphite pickle protocol to line protocol file, e.g. spool files
+/// of relays.
+///
+/// Each batch is a pickled list of `(path, (timestamp, value))`, optionally prefixed by
+/// its length in a 4-byte big endian integer like batches sent to the pickle receiver.
+/// Paths are mapped by the template like `to-lp`.
+#[derive(Debug, Parser)]
+struct ReadPickle {
+    /// Input pickle file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Template of metric paths, e.g. `host.measurement.field`, the whole path is the
+    /// measurement with the `value` field by default.
+    #[arg(long, default_value = "measurement*")]
+    template: Template,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ReadPickle {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = pickle_to_line_protocol(input, output, &self.template, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Push the Graphite line as a line of line protocol.
+///
+/// Non-finite values are skipped as line protocol doesn't support them, timestamps of
+/// `-1` or missing timestamps use `now`.
+fn push_graphite_line(line: &mut String, text: &str, template: &Template, now: i64) -> Result<()> {
+    let invalid = || InvalidPointSnafu {
+        reason: format!("invalid plaintext line {text:?}"),
+    };
+    let mut parts = text.split_ascii_whitespace();
+    let metric = parts.next().with_context(invalid)?;
+    let value: f64 = parts
+        .next()
+        .and_then(|v| v.parse().ok())
+        .with_context(invalid)?;
+    let timestamp = match parts.next() {
+        None | Some("-1") => now,
+        Some(ts) => ts
+            .parse::<f64>()
+            .ok()
+            .map(|ts| (ts * 1e9) as i64)
+            .with_context(invalid)?,
+    };
+    push_point(line, metric, value, timestamp, template)
+}
+
+/// Push the value of the metric as a line of line protocol, non-finite values are
+/// skipped.
+fn push_point(
+    line: &mut String,
+    metric: &str,
+    value: f64,
+    timestamp: i64,
+    template: &Template,
+) -> Result<()> {
+    if !value.is_finite() {
+        return Ok(());
+    }
+
+    let mut split = metric.split(';');
+    let path = split.next().unwrap_or_default();
+    let (measurement, field, path_tags) = template.parse(path);
+    ensure!(
+        !measurement.is_empty(),
+        InvalidPointSnafu {
+            reason: format!("path {path} has no measurement"),
+        }
+    );
+    let mut tags: BTreeMap<&str, &str> = path_tags.into_iter().collect();
+    for pair in split {
+        let (k, v) = pair.split_once('=').context(InvalidPointSnafu {
+            reason: format!("invalid tag {pair:?} of {metric}"),
+        })?;
+        tags.insert(k, v);
+    }
+
+    push_series(line, &measurement, tags);
+    push_fields(line, [(field.as_str(), &FieldValue::F64(value))]);
+    writeln!(line, " {timestamp}").unwrap();
+    Ok(())
+}
+
+fn graphite_to_line_protocol<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    template: &Template,
+    now: i64,
+) -> Result<W> {
+    let reader = BufReader::new(source);
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    for (i, text) in reader.lines().enumerate() {
+        let text = text.context(ReadInputSnafu)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        line.clear();
+        let result =
+            push_graphite_line(&mut line, &text, template, now).context(LineSnafu { line: i + 1 });
+        if reject::check(result, || &text)?.is_none() {
+            continue;
+        }
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Push the `(path, (timestamp, value))` datapoint as a line of line protocol.
+fn push_pickle_point(
+    line: &mut String,
+    point: &pickle::Value,
+    template: &Template,
+    now: i64,
+) -> Result<()> {
+    use pickle::Value;
+
+    let invalid = || InvalidPointSnafu {
+        reason: format!("invalid datapoint {point:?}"),
+    };
+    let (Value::Tuple(point) | Value::List(point)) = point else {
+        return invalid().fail();
+    };
+    let [Value::Str(metric), Value::Tuple(sample) | Value::List(sample)] = &point[..] else {
+        return invalid().fail();
+    };
+    let [timestamp, value] = &sample[..] else {
+        return invalid().fail();
+    };
+    let value = match value {
+        Value::Int(v) => *v as f64,
+        Value::Float(v) => *v,
+        Value::Str(v) => v.parse().ok().with_context(invalid)?,
+        _ => return invalid().fail(),
+    };
+    let timestamp = match timestamp {
+        Value::Int(-1) => now,
+        Value::Int(ts) => ts.checked_mul(1_000_000_000).with_context(invalid)?,
+        Value::Float(ts) => (ts * 1e9) as i64,
+        _ => return invalid().fail(),
+    };
+    push_point(line, metric, value, timestamp, template)
+}
+
+fn pickle_to_line_protocol<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    template: &Template,
+    now: i64,
+) -> Result<W> {
+    let mut reader = BufReader::new(source);
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    let mut data = Vec::new();
+    loop {
+        // Pickles never start with 0, which is the first byte of lengths of batches
+        // smaller than 16 MiB.
+        let buf = reader.fill_buf().context(ReadInputSnafu)?;
+        let Some(first) = buf.first() else {
+            break;
+        };
+        data.clear();
+        if *first == 0 {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len).context(ReadInputSnafu)?;
+            let len = u32::from_be_bytes(len) as u64;
+            reader
+                .by_ref()
+                .take(len)
+                .read_to_end(&mut data)
+                .context(ReadInputSnafu)?;
+            ensure!(
+                data.len() as u64 == len,
+                InvalidPickleSnafu {
+                    reason: format!("batch of {len} bytes is truncated"),
+                }
+            );
+        } else {
+            reader.read_to_end(&mut data).context(ReadInputSnafu)?;
+        }
+
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let (batch, len) = pickle::load(rest)?;
+            rest = &rest[len..];
+            let pickle::Value::List(points) = batch else {
+                return InvalidPickleSnafu {
+                    reason: "batch is not a list",
+                }
+                .fail();
+            };
+            for point in &points {
+                line.clear();
+                let result = push_pickle_point(&mut line, point, template, now);
+                if reject::check(result, || format!("{point:?}"))?.is_none() {
+                    continue;
+                }
+                writer
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn line_protocol_to_graphite<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    template: &Template,
+    now: i64,
+) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    read_lines(source, |parsed| {
+        let timestamp = parsed.timestamp.unwrap_or(now).div_euclid(1_000_000_000);
+        for (key, value) in &parsed.field_set {
+            let value = match value {
+                FieldValue::I64(v) => *v as f64,
+                FieldValue::U64(v) => *v as f64,
+                FieldValue::F64(v) => *v,
+                FieldValue::Boolean(v) => f64::from(u8::from(*v)),
+                FieldValue::String(_) => continue,
+            };
+
+            line.clear();
+            let tags = template.push_path(&mut line, &parsed, key.as_str());
+            for (k, v) in tags {
+                line.push(';');
+                push_sanitized(&mut line, k, &['=']);
+                line.push('=');
+                push_sanitized(&mut line, v, &[]);
+            }
+            writeln!(line, " {value} {timestamp}").unwrap();
+            writer
+                .write_all(line.as_bytes())
+                .context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_lp(input: &str, template: &str) -> String {
+        let template = template.parse().unwrap();
+        let output = graphite_to_line_protocol(input.as_bytes(), Vec::new(), &template, 0).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_graphite_to_line_protocol() {
+        let input = "servers.host_0.cpu.usage_user 58 1451606400
+servers.host_0.cpu.usage_user;region=eu 58.5 1451606400
+servers.host_0.disk.sda.read NaN 1451606400
+";
+        assert_eq!(
+            "servers.host_0.cpu.usage_user value=58 1451606400000000000
+servers.host_0.cpu.usage_user,region=eu value=58.5 1451606400000000000
+",
+            to_lp(input, "measurement*")
+        );
+        assert_eq!(
+            "cpu,host=host_0 usage_user=58 1451606400000000000
+cpu,host=host_0,region=eu usage_user=58.5 1451606400000000000
+",
+            to_lp(input, ".host.measurement.field*")
+        );
+        assert_eq!(
+            "cpu,host=host_0 value=58 0\n",
+            to_lp("servers.host_0.cpu.usage_user 58 -1", ".host.measurement*.")
+        );
+
+        assert!("host.field".parse::<Template>().is_err());
+        assert!("measurement.host*".parse::<Template>().is_err());
+        let template = "measurement".parse().unwrap();
+        let err =
+            graphite_to_line_protocol("cpu x 1".as_bytes(), Vec::new(), &template, 0).unwrap_err();
+        assert_eq!("Error at line 1", err.to_string());
+    }
+
+    #[test]
+    fn test_pickle_to_line_protocol() {
+        let to_lp = |input: &[u8], template: &str| {
+            let template = template.parse().unwrap();
+            pickle_to_line_protocol(input, Vec::new(), &template, 0)
+                .map(|output| String::from_utf8(output).unwrap())
+        };
+        // Batches with lengths, the last datapoint has an invalid timestamp.
+        let input = b"\x00\x00\x00\x7f\x80\x02]q\x00(X\x1d\x00\x00\x00servers.host_0.cpu.usage_userq\x01J\x80\xc1\x85VK:\x86q\x02\x86q\x03X'\x00\x00\x00servers.host_0.cpu.usage_user;region=euq\x04GA\xd5\xa1p` \x00\x00G@M@\x00\x00\x00\x00\x00\x86q\x05\x86q\x06e.\x00\x00\x00o\x80\x02]q\x00(X\x18\x00\x00\x00servers.host_0.disk.readq\x01J\x8a\xc1\x85VG\x7f\xf8\x00\x00\x00\x00\x00\x00\x86q\x02\x86q\x03X\x1d\x00\x00\x00servers.host_0.cpu.usage_userq\x04X\x01\x00\x00\x00xq\x05K\x01\x86q\x06\x86q\x07e.";
+        assert_eq!(
+            "cpu,host=host_0 usage_user=58 1451606400000000000
+cpu,host=host_0,region=eu usage_user=58.5 1451606400500000000
+",
+            to_lp(&input[..0x83], ".host.measurement.field*").unwrap()
+        );
+        let err = to_lp(input, "measurement*").unwrap_err();
+        assert!(err.is_invalid_line(), "{err}");
+        assert!(to_lp(&input[..0x82], "measurement*").is_err());
+
+        // Batches of protocol 0 and 4 without lengths.
+        let mut input = b"(lp0\n(Vservers.host_0.cpu.usage_user\np1\n(I1451606400\nI58\ntp2\ntp3\na(Vservers.host_0.disk.read\np4\n(I1451606410\nFnan\ntp5\ntp6\na.".to_vec();
+        input.extend_from_slice(b"\x80\x04\x95\x1b\x00\x00\x00\x00\x00\x00\x00]\x94\x8c\tcpu.usage\x94J\xff\xff\xff\xffK\x01\x86\x94\x86\x94a.");
+        assert_eq!(
+            "servers.host_0.cpu.usage_user value=58 1451606400000000000
+cpu.usage value=1 0
+",
+            to_lp(&input, "measurement*").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_line_protocol_to_graphite() {
+        let input =
+            "cpu,host=host.0,region=eu\\ 1 usage=1.5,ok=true,note=\"x\" 1451606400123456789\n";
+        let template = "measurement.field".parse().unwrap();
+        let output = line_protocol_to_graphite(input.as_bytes(), Vec::new(), &template, 0).unwrap();
+        assert_eq!(
+            "cpu.usage;host=host.0;region=eu_1 1.5 1451606400
+cpu.ok;host=host.0;region=eu_1 1 1451606400
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let template = "host.measurement".parse().unwrap();
+        let output = line_protocol_to_graphite(input.as_bytes(), Vec::new(), &template, 0).unwrap();
+        let graphite = String::from_utf8(output).unwrap();
+        assert_eq!(
+            "host_0.cpu.usage;region=eu_1 1.5 1451606400
+host_0.cpu.ok;region=eu_1 1 1451606400
+",
+            graphite
+        );
+        assert_eq!(
+            "cpu,host=host_0,region=eu_1 usage=1.5 1451606400000000000
+cpu,host=host_0,region=eu_1 ok=1 1451606400000000000
+",
+            to_lp(&graphite, "host.measurement.field")
+        );
+    }
+}
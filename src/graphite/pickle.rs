@@ -0,0 +1,443 @@
+//! Decoder of Python pickles sent by the Carbon pickle protocol.
+//!
+//! Only opcodes of builtin values are supported, pickles of lists of
+//! `(path, (timestamp, value))` tuples don't use others.
+
+use crate::error::{InvalidPickleSnafu, Result};
+use snafu::{ensure, OptionExt};
+use std::collections::HashMap;
+
+/// A Python value.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Value {
+    None,
+    Bool(bool),
+    /// An integer, integers out of the range of `i64` are floats.
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+}
+
+/// An item of the stack of the unpickler.
+enum Item {
+    Value(Value),
+    Mark,
+}
+
+type Stack = Vec<Item>;
+
+fn invalid(reason: impl Into<String>) -> InvalidPickleSnafu<String> {
+    InvalidPickleSnafu {
+        reason: reason.into(),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .with_context(|| invalid("unexpected end of pickle"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    /// Returns bytes of the length in a little endian `u32`.
+    fn take_u32_len(&mut self) -> Result<&'a [u8]> {
+        let len = u32::from_le_bytes(self.array()?);
+        self.take(len as usize)
+    }
+
+    fn take_u64_len(&mut self) -> Result<&'a [u8]> {
+        let len = u64::from_le_bytes(self.array()?);
+        let len = usize::try_from(len)
+            .ok()
+            .with_context(|| invalid("too long"))?;
+        self.take(len)
+    }
+
+    /// Returns the bytes until `\n` of text opcodes.
+    fn line_bytes(&mut self) -> Result<&'a [u8]> {
+        let rest = &self.data[self.pos..];
+        let len = rest
+            .iter()
+            .position(|b| *b == b'\n')
+            .with_context(|| invalid("unexpected end of pickle"))?;
+        self.pos += len + 1;
+        Ok(&rest[..len])
+    }
+
+    fn line(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.line_bytes()?)
+            .ok()
+            .with_context(|| invalid("invalid text argument"))
+    }
+}
+
+fn pop(stack: &mut Stack) -> Result<Value> {
+    match stack.pop() {
+        Some(Item::Value(value)) => Ok(value),
+        _ => invalid("pop from empty stack or mark").fail(),
+    }
+}
+
+/// Pop values until the top mark.
+fn pop_mark(stack: &mut Stack) -> Result<Vec<Value>> {
+    let mark = stack
+        .iter()
+        .rposition(|item| matches!(item, Item::Mark))
+        .with_context(|| invalid("missing mark"))?;
+    let values = stack
+        .drain(mark + 1..)
+        .map(|item| match item {
+            Item::Value(value) => value,
+            Item::Mark => unreachable!(),
+        })
+        .collect();
+    stack.pop();
+    Ok(values)
+}
+
+fn top(stack: &mut Stack) -> Result<&mut Value> {
+    match stack.last_mut() {
+        Some(Item::Value(value)) => Ok(value),
+        _ => invalid("empty stack").fail(),
+    }
+}
+
+fn extend_list(stack: &mut Stack, values: Vec<Value>) -> Result<()> {
+    match top(stack)? {
+        Value::List(list) => {
+            list.extend(values);
+            Ok(())
+        }
+        _ => invalid("append to non-list").fail(),
+    }
+}
+
+/// Returns the little endian two's complement integer.
+fn long(bytes: &[u8]) -> Value {
+    if bytes.len() <= 8 {
+        let fill = if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+            0xff
+        } else {
+            0
+        };
+        let mut buf = [fill; 8];
+        buf[..bytes.len()].copy_from_slice(bytes);
+        return Value::Int(i64::from_le_bytes(buf));
+    }
+    let negative = bytes.last().is_some_and(|b| b & 0x80 != 0);
+    let value = bytes.iter().rev().fold(0.0, |acc, b| {
+        let b = if negative { !b } else { *b };
+        acc * 256.0 + f64::from(b)
+    });
+    Value::Float(if negative { -value - 1.0 } else { value })
+}
+
+/// Returns the integer in the text, which is a float if it is out of range.
+fn parse_int(text: &str) -> Result<Value> {
+    if let Ok(v) = text.parse() {
+        return Ok(Value::Int(v));
+    }
+    text.parse()
+        .map(Value::Float)
+        .ok()
+        .with_context(|| invalid(format!("invalid integer {text}")))
+}
+
+/// Decode the text of `raw-unicode-escape`, bytes are Latin-1 chars.
+fn raw_unicode(bytes: &[u8]) -> Result<String> {
+    let mut value = String::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let width = match &bytes[i..] {
+            [b'\\', b'u', ..] => 4,
+            [b'\\', b'U', ..] => 8,
+            _ => {
+                value.push(char::from(bytes[i]));
+                i += 1;
+                continue;
+            }
+        };
+        let c = bytes
+            .get(i + 2..i + 2 + width)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+            .with_context(|| invalid("invalid unicode escape"))?;
+        value.push(c);
+        i += 2 + width;
+    }
+    Ok(value)
+}
+
+/// Decode the quoted repr of a string of `STRING`.
+fn quoted(text: &str) -> Result<String> {
+    let inner = [b'\'', b'"']
+        .iter()
+        .find_map(|q| {
+            let q = char::from(*q);
+            text.strip_prefix(q)?.strip_suffix(q)
+        })
+        .with_context(|| invalid(format!("invalid string {text}")))?;
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let b = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .with_context(|| invalid(format!("invalid string {text}")))?;
+                value.push(char::from(b));
+            }
+            Some(c) => value.push(c),
+            None => return invalid(format!("invalid string {text}")).fail(),
+        }
+    }
+    Ok(value)
+}
+
+fn string(bytes: &[u8]) -> Result<Value> {
+    String::from_utf8(bytes.to_vec())
+        .map(Value::Str)
+        .ok()
+        .with_context(|| invalid("invalid UTF-8 string"))
+}
+
+/// Decode the pickle at the start of the data, returns the value and the length of the
+/// pickle.
+pub(super) fn load(data: &[u8]) -> Result<(Value, usize)> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut stack = Stack::new();
+    let mut memo: HashMap<u32, Value> = HashMap::new();
+    loop {
+        let opcode = reader.byte()?;
+        let value = match opcode {
+            // PROTO
+            0x80 => {
+                let protocol = reader.byte()?;
+                ensure!(
+                    protocol <= 5,
+                    invalid(format!("unsupported protocol {protocol}"))
+                );
+                continue;
+            }
+            // FRAME
+            0x95 => {
+                reader.take(8)?;
+                continue;
+            }
+            // STOP
+            b'.' => {
+                let value = pop(&mut stack)?;
+                return Ok((value, reader.pos));
+            }
+            // MARK
+            b'(' => {
+                stack.push(Item::Mark);
+                continue;
+            }
+            // POP
+            b'0' => {
+                stack
+                    .pop()
+                    .with_context(|| invalid("pop from empty stack"))?;
+                continue;
+            }
+            // POP_MARK
+            b'1' => {
+                pop_mark(&mut stack)?;
+                continue;
+            }
+            // DUP
+            b'2' => top(&mut stack)?.clone(),
+            // APPEND
+            b'a' => {
+                let value = pop(&mut stack)?;
+                extend_list(&mut stack, vec![value])?;
+                continue;
+            }
+            // APPENDS
+            b'e' => {
+                let values = pop_mark(&mut stack)?;
+                extend_list(&mut stack, values)?;
+                continue;
+            }
+            // MEMOIZE
+            0x94 => {
+                let value = top(&mut stack)?.clone();
+                memo.insert(memo.len() as u32, value);
+                continue;
+            }
+            // PUT, BINPUT and LONG_BINPUT
+            b'p' | b'q' | b'r' => {
+                let index = match opcode {
+                    b'p' => reader
+                        .line()?
+                        .parse()
+                        .ok()
+                        .with_context(|| invalid("invalid memo index"))?,
+                    b'q' => u32::from(reader.byte()?),
+                    _ => u32::from_le_bytes(reader.array()?),
+                };
+                let value = top(&mut stack)?.clone();
+                memo.insert(index, value);
+                continue;
+            }
+            // GET, BINGET and LONG_BINGET
+            b'g' | b'h' | b'j' => {
+                let index: u32 = match opcode {
+                    b'g' => reader
+                        .line()?
+                        .parse()
+                        .ok()
+                        .with_context(|| invalid("invalid memo index"))?,
+                    b'h' => u32::from(reader.byte()?),
+                    _ => u32::from_le_bytes(reader.array()?),
+                };
+                memo.get(&index)
+                    .cloned()
+                    .with_context(|| invalid(format!("missing memo {index}")))?
+            }
+            // NONE, NEWTRUE and NEWFALSE
+            b'N' => Value::None,
+            0x88 => Value::Bool(true),
+            0x89 => Value::Bool(false),
+            // INT, where `01` and `00` are booleans
+            b'I' => match reader.line()? {
+                "01" => Value::Bool(true),
+                "00" => Value::Bool(false),
+                text => parse_int(text)?,
+            },
+            // BININT, BININT1 and BININT2
+            b'J' => Value::Int(i32::from_le_bytes(reader.array()?).into()),
+            b'K' => Value::Int(reader.byte()?.into()),
+            b'M' => Value::Int(u16::from_le_bytes(reader.array()?).into()),
+            // LONG, LONG1 and LONG4
+            b'L' => {
+                let text = reader.line()?;
+                parse_int(text.strip_suffix('L').unwrap_or(text))?
+            }
+            0x8a => {
+                let len = reader.byte()?;
+                long(reader.take(len.into())?)
+            }
+            0x8b => long(reader.take_u32_len()?),
+            // FLOAT and BINFLOAT
+            b'F' => {
+                let text = reader.line()?;
+                text.parse()
+                    .map(Value::Float)
+                    .ok()
+                    .with_context(|| invalid(format!("invalid float {text}")))?
+            }
+            b'G' => Value::Float(f64::from_be_bytes(reader.array()?)),
+            // UNICODE, SHORT_BINUNICODE, BINUNICODE and BINUNICODE8
+            b'V' => Value::Str(raw_unicode(reader.line_bytes()?)?),
+            0x8c => {
+                let len = reader.byte()?;
+                string(reader.take(len.into())?)?
+            }
+            b'X' => string(reader.take_u32_len()?)?,
+            0x8d => string(reader.take_u64_len()?)?,
+            // STRING, SHORT_BINSTRING and BINSTRING of Python 2
+            b'S' => Value::Str(quoted(reader.line()?)?),
+            b'U' => {
+                let len = reader.byte()?;
+                Value::Str(String::from_utf8_lossy(reader.take(len.into())?).into_owned())
+            }
+            b'T' => Value::Str(String::from_utf8_lossy(reader.take_u32_len()?).into_owned()),
+            // SHORT_BINBYTES, BINBYTES and BINBYTES8
+            b'C' => {
+                let len = reader.byte()?;
+                Value::Bytes(reader.take(len.into())?.to_vec())
+            }
+            b'B' => Value::Bytes(reader.take_u32_len()?.to_vec()),
+            0x8e => Value::Bytes(reader.take_u64_len()?.to_vec()),
+            // EMPTY_LIST, LIST, EMPTY_TUPLE, TUPLE, TUPLE1, TUPLE2 and TUPLE3
+            b']' => Value::List(Vec::new()),
+            b'l' => Value::List(pop_mark(&mut stack)?),
+            b')' => Value::Tuple(Vec::new()),
+            b't' => Value::Tuple(pop_mark(&mut stack)?),
+            0x85..=0x87 => {
+                let len = usize::from(opcode - 0x84);
+                ensure!(stack.len() >= len, invalid("pop from empty stack"));
+                let mut values = Vec::with_capacity(len);
+                for _ in 0..len {
+                    values.push(pop(&mut stack)?);
+                }
+                values.reverse();
+                Value::Tuple(values)
+            }
+            _ => return invalid(format!("unsupported opcode 0x{opcode:02x}")).fail(),
+        };
+        stack.push(Item::Value(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let expect = Value::List(vec![
+            Value::Tuple(vec![
+                Value::Str("a".to_string()),
+                Value::List(vec![Value::Int(1), Value::Int(1 << 40)]),
+            ]),
+            Value::Tuple(vec![
+                Value::Str("b".to_string()),
+                Value::Tuple(vec![
+                    Value::Bool(true),
+                    Value::None,
+                    Value::Int(-300),
+                    Value::Float(2f64.powi(70)),
+                    Value::Str("ü".to_string()),
+                ]),
+            ]),
+        ]);
+        // Pickles of `[('a', [1, 2**40]), ('b', (True, None, -300, 2**70, 'ü'))]`.
+        let protocol_0 = b"(lp0\n(Va\np1\n(lp2\nI1\naL1099511627776L\natp3\na(Vb\np4\n(I01\nNI-300\nL1180591620717411303424L\nV\xfc\np5\ntp6\ntp7\na.";
+        let protocol_2 = b"\x80\x02]q\x00(X\x01\x00\x00\x00aq\x01]q\x02(K\x01\x8a\x06\x00\x00\x00\x00\x00\x01e\x86q\x03X\x01\x00\x00\x00bq\x04(\x88NJ\xd4\xfe\xff\xff\x8a\t\x00\x00\x00\x00\x00\x00\x00\x00@X\x02\x00\x00\x00\xc3\xbcq\x05tq\x06\x86q\x07e.";
+        for pickle in [&protocol_0[..], &protocol_2[..]] {
+            let mut data = pickle.to_vec();
+            data.extend_from_slice(b"rest");
+            assert_eq!((expect.clone(), pickle.len()), load(&data).unwrap());
+        }
+
+        assert!(load(&protocol_2[..protocol_2.len() - 1]).is_err());
+        assert!(load(b"\x80\x02cos\nsystem\n.").is_err());
+        assert_eq!(Value::Int(-2), long(&[0xfe]));
+        assert_eq!(
+            Value::Float(-(2f64.powi(64))),
+            long(&[0, 0, 0, 0, 0, 0, 0, 0, 0xff])
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Tools for GreptimeDB target.
+
+use crate::error::{CreateRuntimeSnafu, FieldTypeConflictSnafu, GreptimeSnafu, Result};
+use crate::influx::read_lines;
+use crate::io::{open_input, Compression};
+use crate::progress;
+use crate::rate_limit::RateLimit;
+use chrono::Utc;
+use clap::Parser;
+use greptimedb_ingester::api::v1::auth_header::AuthScheme;
+use greptimedb_ingester::api::v1::{
+    Basic, ColumnSchema, Row, RowInsertRequest, RowInsertRequests, Rows, Value,
+};
+use greptimedb_ingester::client::Client;
+use greptimedb_ingester::database::Database;
+use greptimedb_ingester::helpers::schema::{field, tag, timestamp};
+use greptimedb_ingester::helpers::values::{
+    bool_value, f64_value, i64_value, none_value, string_value, timestamp_nanosecond_value,
+    u64_value,
+};
+use greptimedb_ingester::ColumnDataType;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use prost::Message;
+use snafu::{ensure, ResultExt};
+use std::collections::HashMap;
+use tokio::runtime::Runtime;
+
+/// GreptimeDB command.
+#[derive(Debug, Parser)]
+pub struct GreptimeCommand {
+    #[clap(subcommand)]
+    subcmd: GreptimeSubcommand,
+}
+
+impl GreptimeCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            GreptimeSubcommand::Write(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for GreptimeDB.
+#[derive(Debug, Parser)]
+enum GreptimeSubcommand {
+    /// Write line protocol to GreptimeDB.
+    Write(Write),
+}
+
+/// Write line protocol file to GreptimeDB by the gRPC insert API.
+#[derive(Debug, Parser)]
+struct Write {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// gRPC endpoint of GreptimeDB.
+    #[arg(long, default_value = "localhost:4001")]
+    endpoint: String,
+    /// Database name.
+    #[arg(long, default_value = "public")]
+    database: String,
+    /// Username of the basic auth.
+    #[arg(long, requires = "password")]
+    username: Option<String>,
+    /// Password of the basic auth.
+    #[arg(long, requires = "username")]
+    password: Option<String>,
+    /// Number of lines in each insert request.
+    #[arg(long, default_value_t = 1000)]
+    batch_size: usize,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Write {
+    fn run(self) -> Result<()> {
+        let auth = self.username.clone().zip(self.password.clone());
+        let inserter = Inserter::connect(&self.endpoint, &self.database, auth)?;
+
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut builder = InsertBuilder::default();
+        read_lines(input, |line| {
+            builder.append_line(&line)?;
+            if builder.num_rows >= self.batch_size {
+                self.insert(&inserter, &mut builder)?;
+            }
+            Ok(())
+        })?;
+        if builder.num_rows > 0 {
+            self.insert(&inserter, &mut builder)?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert rows in the builder after waiting for the rate limit.
+    fn insert(&self, inserter: &Inserter, builder: &mut InsertBuilder) -> Result<()> {
+        let num_rows = builder.num_rows;
+        let requests = builder.finish();
+        self.rate_limit.acquire(num_rows, requests.encoded_len());
+        inserter.insert(requests, num_rows)
+    }
+}
+
+/// Client of a GreptimeDB database to insert lines.
+pub(crate) struct Inserter {
+    runtime: Runtime,
+    database: Database,
+}
+
+impl Inserter {
+    /// Returns the client of the database, with the username and password of the basic
+    /// auth if present.
+    pub(crate) fn connect(
+        endpoint: &str,
+        database: &str,
+        auth: Option<(String, String)>,
+    ) -> Result<Inserter> {
+        let client = Client::with_urls([endpoint]);
+        let mut database = Database::new_with_dbname(database, client);
+        if let Some((username, password)) = auth {
+            database.set_auth(AuthScheme::Basic(Basic { username, password }));
+        }
+        let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+        Ok(Inserter { runtime, database })
+    }
+
+    /// Insert the lines in one request.
+    pub(crate) fn insert_lines(&self, lines: &str) -> Result<()> {
+        let mut builder = InsertBuilder::default();
+        read_lines(lines.as_bytes(), |line| builder.append_line(&line))?;
+        let num_rows = builder.num_rows;
+        self.insert(builder.finish(), num_rows)
+    }
+
+    fn insert(&self, requests: RowInsertRequests, num_rows: usize) -> Result<()> {
+        self.runtime
+            .block_on(self.database.insert(requests))
+            .context(GreptimeSnafu)?;
+        progress::add_lines_written(num_rows);
+        Ok(())
+    }
+}
+
+/// Name of the time index column.
+const TIMESTAMP_COLUMN: &str = "greptime_timestamp";
+
+/// Builder of the insert requests of lines.
+///
+/// Each measurement is a table, tags are tag columns, fields are field columns and the
+/// timestamp is the time index in nanosecond.
+#[derive(Debug, Default)]
+struct InsertBuilder {
+    tables: Vec<TableRows>,
+    table_index: HashMap<String, usize>,
+    num_rows: usize,
+}
+
+#[derive(Debug)]
+struct TableRows {
+    name: String,
+    schema: Vec<ColumnSchema>,
+    column_index: HashMap<String, usize>,
+    rows: Vec<Row>,
+}
+
+impl TableRows {
+    fn new(name: &str) -> TableRows {
+        TableRows {
+            name: name.to_string(),
+            schema: vec![timestamp(
+                TIMESTAMP_COLUMN,
+                ColumnDataType::TimestampNanosecond,
+            )],
+            column_index: HashMap::from([(TIMESTAMP_COLUMN.to_string(), 0)]),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Returns the index of the column, the column is added if it is new.
+    fn column(&mut self, name: &str, schema: impl FnOnce() -> ColumnSchema) -> Result<usize> {
+        if let Some(idx) = self.column_index.get(name) {
+            let expect = schema();
+            let current = &self.schema[*idx];
+            ensure!(
+                current.datatype == expect.datatype
+                    && current.semantic_type == expect.semantic_type,
+                FieldTypeConflictSnafu {
+                    field: name,
+                    current: current.datatype().as_str_name(),
+                    other: expect.datatype().as_str_name(),
+                }
+            );
+            return Ok(*idx);
+        }
+
+        self.schema.push(schema());
+        self.column_index
+            .insert(name.to_string(), self.schema.len() - 1);
+        Ok(self.schema.len() - 1)
+    }
+}
+
+impl InsertBuilder {
+    fn append_line(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        let measurement = line.series.measurement.as_str();
+        let idx = match self.table_index.get(measurement) {
+            Some(idx) => *idx,
+            None => {
+                self.tables.push(TableRows::new(measurement));
+                self.table_index
+                    .insert(measurement.to_string(), self.tables.len() - 1);
+                self.tables.len() - 1
+            }
+        };
+        let table = &mut self.tables[idx];
+
+        let mut values = vec![none_value(); table.schema.len()];
+        let ts = line
+            .timestamp
+            .unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        values[0] = timestamp_nanosecond_value(ts);
+        for (tagk, tagv) in line.series.tag_set.iter().flatten() {
+            let idx = table.column(tagk.as_str(), || tag(tagk.as_str(), ColumnDataType::String))?;
+            set_value(&mut values, idx, string_value(tagv.to_string()));
+        }
+        for (fieldk, fieldv) in &line.field_set {
+            let (datatype, value) = match fieldv {
+                FieldValue::I64(v) => (ColumnDataType::Int64, i64_value(*v)),
+                FieldValue::U64(v) => (ColumnDataType::Uint64, u64_value(*v)),
+                FieldValue::F64(v) => (ColumnDataType::Float64, f64_value(*v)),
+                FieldValue::String(v) => (ColumnDataType::String, string_value(v.to_string())),
+                FieldValue::Boolean(v) => (ColumnDataType::Boolean, bool_value(*v)),
+            };
+            let idx = table.column(fieldk.as_str(), || field(fieldk.as_str(), datatype))?;
+            set_value(&mut values, idx, value);
+        }
+
+        table.rows.push(Row { values });
+        self.num_rows += 1;
+        Ok(())
+    }
+
+    /// Returns the requests of appended lines and resets the builder.
+    fn finish(&mut self) -> RowInsertRequests {
+        self.table_index.clear();
+        self.num_rows = 0;
+        let inserts = self
+            .tables
+            .drain(..)
+            .map(|mut table| {
+                // Rows before new columns are added are shorter than the schema.
+                for row in &mut table.rows {
+                    row.values.resize(table.schema.len(), none_value());
+                }
+                RowInsertRequest {
+                    table_name: table.name,
+                    rows: Some(Rows {
+                        schema: table.schema,
+                        rows: table.rows,
+                    }),
+                }
+            })
+            .collect();
+
+        RowInsertRequests { inserts }
+    }
+}
+
+fn set_value(values: &mut Vec<Value>, idx: usize, value: Value) {
+    if values.len() <= idx {
+        values.resize(idx + 1, none_value());
+    }
+    values[idx] = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use greptimedb_ingester::api::v1::value::ValueData;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_insert_builder() {
+        let input = "cpu,host=host_0 usage_user=58i 1451606400000000000
+mem,host=host_0 used=1.5 1451606400000000000
+cpu,host=host_1,region=eu usage_user=60i,ok=true 1451606410000000000
+";
+        let mut builder = InsertBuilder::default();
+        read_lines(Cursor::new(input), |line| builder.append_line(&line)).unwrap();
+        assert_eq!(3, builder.num_rows);
+
+        let requests = builder.finish();
+        assert_eq!(0, builder.num_rows);
+        assert_eq!(2, requests.inserts.len());
+        let cpu = &requests.inserts[0];
+        assert_eq!("cpu", cpu.table_name);
+        let rows = cpu.rows.as_ref().unwrap();
+        let names: Vec<_> = rows.schema.iter().map(|c| c.column_name.as_str()).collect();
+        assert_eq!(
+            vec![TIMESTAMP_COLUMN, "host", "usage_user", "region", "ok"],
+            names
+        );
+        assert_eq!(2, rows.rows.len());
+        assert!(rows.rows.iter().all(|row| row.values.len() == 5));
+        assert_eq!(None, rows.rows[0].values[3].value_data);
+        assert_eq!(
+            Some(ValueData::I64Value(60)),
+            rows.rows[1].values[2].value_data
+        );
+
+        let mut builder = InsertBuilder::default();
+        let err = read_lines(Cursor::new("cpu v=1i\ncpu v=1.5\n"), |line| {
+            builder.append_line(&line)
+        })
+        .unwrap_err();
+        assert_eq!("Error at line 2", err.to_string());
+    }
+}
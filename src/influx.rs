@@ -1,15 +1,118 @@
 //! Tools for InfluxDB target.
 
-use chrono::{TimeZone, Utc};
-use clap::Parser;
-use csv::{Reader, Writer};
-use influxdb_line_protocol::{self, EscapedStr, FieldValue};
+mod annotated_csv;
+mod anonymize;
+mod bench;
+mod binary;
+mod capture;
+mod cardinality;
+mod checkpoint;
+mod clip;
+mod csv_schema;
+mod dedup;
+mod describe;
+mod dictionary;
+mod diff;
+mod downsample;
+mod filter;
+mod from_parquet;
+mod gaps;
+mod jsonl;
+mod manifest;
+mod merge;
+mod noise;
+mod partition;
+mod query;
+mod rate;
+mod read_iox;
+mod read_tsm;
+mod replay;
+mod resample;
+mod rollup;
+mod sample;
+mod scale;
+pub(crate) mod schema;
+mod shift;
+mod sort;
+pub(crate) mod split;
+mod stats;
+pub(crate) mod timestamp;
+mod to_arrow;
+mod to_avro;
+pub(crate) mod to_parquet;
+mod top;
+mod validate;
+pub(crate) mod write;
+
+use crate::error::{
+    ColumnNotInSchemaSnafu, CreateDirSnafu, Error, InvalidCheckpointSnafu, InvalidColumnValueSnafu,
+    InvalidFileNameSnafu, InvalidMappedInputSnafu, InvalidPointSnafu, InvalidTimestampSnafu,
+    LineSnafu, MissingColumnSnafu, MissingColumnsSnafu, MissingMeasurementSnafu, OpenFileSnafu,
+    ParseLineSnafu, ReadCsvSnafu, ReadDirSnafu, Result, WriteCsvSnafu, WriteOutputSnafu,
+};
+use crate::io::{
+    create_output, expand_glob, is_glob, is_remote, is_stdio, open_input, open_input_at,
+    ChunkReader, Compression, CompressionArgs, DirWatcher, Mmap, Output, ReplayableInput,
+    WatchArgs,
+};
+use crate::pipeline;
+use crate::progress;
+use crate::reject;
+use annotated_csv::{FromAnnotatedCsv, ToAnnotatedCsv};
+use anonymize::Anonymize;
+use bench::Bench;
+use binary::{ToCbor, ToMsgpack};
+use capture::Capture;
+use cardinality::Cardinality;
+use checkpoint::{Checkpoint, CheckpointOptions, DEFAULT_CHECKPOINT_INTERVAL};
+use clap::{Args, Parser, ValueEnum};
+use clip::Clip;
+use csv::{Reader, ReaderBuilder, StringRecord, Writer, WriterBuilder};
+use csv_schema::{CsvSchema, MeasurementSchema};
+use dedup::Dedup;
+use describe::Describe;
+use dictionary::{DictDecode, DictEncode};
+use diff::Diff;
+use downsample::Downsample;
+use filter::Filter;
+use from_parquet::FromParquet;
+use gaps::Gaps;
+use influxdb_line_protocol::{self, EscapedStr, FieldValue, ParsedLine};
+use jsonl::{FromJsonl, ToJsonl};
+use manifest::{Manifest, Verify};
+use merge::Merge;
+use noise::Noise;
+use partition::Partition;
+use query::Query;
+use rate::Rate;
+use read_iox::ReadIox;
+use read_tsm::ReadTsm;
+use replay::Replay;
+use resample::Resample;
+use rollup::Rollup;
+use sample::Sample;
+use scale::Scale;
+use schema::{FieldType, Schema, SchemaDdl};
 use serde::Serialize;
-use std::collections::HashSet;
-use std::fmt::Write as FmtWrite;
-use std::fs::{self, File};
-use std::io::{BufRead, BufReader, BufWriter, Read, Write};
-use std::path::Path;
+use shift::Shift;
+use snafu::{ensure, OptionExt, ResultExt};
+use sort::Sort;
+use split::{file_name, Split};
+use stats::{ShowStats, Stats, StatsWriter};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as FmtWrite};
+use std::fs;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use timestamp::{Precision, TimeFormat, TimestampParser};
+use to_arrow::ToArrow;
+use to_avro::ToAvro;
+use to_parquet::ToParquet;
+use top::Top;
+use tracing::{debug, info, warn};
+use validate::Validate;
 
 /// InfluxDB command.
 #[derive(Debug, Parser)]
@@ -20,10 +123,55 @@ pub struct InfluxCommand {
 
 impl InfluxCommand {
     /// Run this command.
-    pub fn run(self) {
+    pub fn run(self) -> Result<()> {
         match self.subcmd {
             InfluxSubcommand::ToCsv(c) => c.run(),
             InfluxSubcommand::FromCsv(c) => c.run(),
+            InfluxSubcommand::ToParquet(c) => c.run(),
+            InfluxSubcommand::FromParquet(c) => c.run(),
+            InfluxSubcommand::ToArrow(c) => c.run(),
+            InfluxSubcommand::ToAvro(c) => c.run(),
+            InfluxSubcommand::ToMsgpack(c) => c.run(),
+            InfluxSubcommand::ToCbor(c) => c.run(),
+            InfluxSubcommand::ToJsonl(c) => c.run(),
+            InfluxSubcommand::FromJsonl(c) => c.run(),
+            InfluxSubcommand::ToAnnotatedCsv(c) => c.run(),
+            InfluxSubcommand::FromAnnotatedCsv(c) => c.run(),
+            InfluxSubcommand::Write(c) => c.run(),
+            InfluxSubcommand::Query(c) => c.run(),
+            InfluxSubcommand::Schema(c) => c.run(),
+            InfluxSubcommand::Stats(c) => c.run(),
+            InfluxSubcommand::Validate(c) => c.run(),
+            InfluxSubcommand::Filter(c) => c.run(),
+            InfluxSubcommand::Downsample(c) => c.run(),
+            InfluxSubcommand::Shift(c) => c.run(),
+            InfluxSubcommand::Scale(c) => c.run(),
+            InfluxSubcommand::Anonymize(c) => c.run(),
+            InfluxSubcommand::Noise(c) => c.run(),
+            InfluxSubcommand::Dedup(c) => c.run(),
+            InfluxSubcommand::Diff(c) => c.run(),
+            InfluxSubcommand::Manifest(c) => c.run(),
+            InfluxSubcommand::Verify(c) => c.run(),
+            InfluxSubcommand::Gaps(c) => c.run(),
+            InfluxSubcommand::Cardinality(c) => c.run(),
+            InfluxSubcommand::Describe(c) => c.run(),
+            InfluxSubcommand::Top(c) => c.run(),
+            InfluxSubcommand::Sample(c) => c.run(),
+            InfluxSubcommand::Clip(c) => c.run(),
+            InfluxSubcommand::Resample(c) => c.run(),
+            InfluxSubcommand::Rollup(c) => c.run(),
+            InfluxSubcommand::Rate(c) => c.run(),
+            InfluxSubcommand::DictEncode(c) => c.run(),
+            InfluxSubcommand::DictDecode(c) => c.run(),
+            InfluxSubcommand::Sort(c) => c.run(),
+            InfluxSubcommand::Merge(c) => c.run(),
+            InfluxSubcommand::Split(c) => c.run(),
+            InfluxSubcommand::Partition(c) => c.run(),
+            InfluxSubcommand::ReadTsm(c) => c.run(),
+            InfluxSubcommand::ReadIox(c) => c.run(),
+            InfluxSubcommand::Capture(c) => c.run(),
+            InfluxSubcommand::Replay(c) => c.run(),
+            InfluxSubcommand::Bench(c) => c.run(),
         }
     }
 }
@@ -34,236 +182,2391 @@ enum InfluxSubcommand {
     /// Line protocol to CSV.
     ToCsv(ToCsv),
     /// CSV to line protocol.
-    FromCsv(FromCsv),
+    FromCsv(Box<FromCsv>),
+    /// Line protocol to Parquet.
+    ToParquet(ToParquet),
+    /// Parquet to line protocol.
+    FromParquet(FromParquet),
+    /// Line protocol to Arrow IPC files of each measurement.
+    ToArrow(ToArrow),
+    /// Line protocol to Avro object container files of each measurement.
+    ToAvro(ToAvro),
+    /// Line protocol to a stream of MessagePack points.
+    ToMsgpack(ToMsgpack),
+    /// Line protocol to a CBOR sequence of points.
+    ToCbor(ToCbor),
+    /// Line protocol to JSON Lines.
+    ToJsonl(ToJsonl),
+    /// JSON Lines to line protocol.
+    FromJsonl(FromJsonl),
+    /// Line protocol to InfluxDB annotated CSV.
+    ToAnnotatedCsv(ToAnnotatedCsv),
+    /// InfluxDB annotated CSV to line protocol.
+    FromAnnotatedCsv(FromAnnotatedCsv),
+    /// Write line protocol to InfluxDB.
+    Write(write::Write),
+    /// Query InfluxDB to CSV or line protocol.
+    Query(Query),
+    /// Line protocol schema to SQL DDL.
+    Schema(SchemaDdl),
+    /// Statistics of line protocol.
+    Stats(ShowStats),
+    /// Validate line protocol.
+    Validate(Validate),
+    /// Filter line protocol by measurement, tags and time range.
+    Filter(Filter),
+    /// Downsample line protocol by aggregating points in time buckets.
+    Downsample(Downsample),
+    /// Shift timestamps of line protocol.
+    Shift(Shift),
+    /// Multiply series of line protocol.
+    Scale(Scale),
+    /// Anonymize tag values of line protocol.
+    Anonymize(Anonymize),
+    /// Add random noise to field values and timestamps of line protocol.
+    Noise(Noise),
+    /// Remove duplicate points of line protocol.
+    Dedup(Dedup),
+    /// Compare two line protocol files point by point.
+    Diff(Diff),
+    /// Record line counts, time ranges and checksums of line protocol files.
+    Manifest(Manifest),
+    /// Verify line protocol files against a manifest.
+    Verify(Verify),
+    /// Report gaps of series of line protocol.
+    Gaps(Gaps),
+    /// Report series cardinality of line protocol.
+    Cardinality(Cardinality),
+    /// Report summary statistics of field values of line protocol.
+    Describe(Describe),
+    /// Report the heaviest series of line protocol.
+    Top(Top),
+    /// Sample lines of line protocol.
+    Sample(Sample),
+    /// Clip line protocol to a time range.
+    Clip(Clip),
+    /// Resample series of line protocol to a fixed interval.
+    Resample(Resample),
+    /// Downsample line protocol to multiple resolutions in one pass.
+    Rollup(Rollup),
+    /// Convert counters of line protocol to rates.
+    Rate(Rate),
+    /// Replace string field values of line protocol by integer codes of a dictionary.
+    DictEncode(DictEncode),
+    /// Replace integer codes of line protocol by string values of a dictionary.
+    DictDecode(DictDecode),
+    /// Sort line protocol by timestamp.
+    Sort(Sort),
+    /// Merge sorted line protocol files.
+    Merge(Merge),
+    /// Split line protocol into files.
+    Split(Split),
+    /// Partition line protocol into files by the hash of series.
+    Partition(Partition),
+    /// TSM files of InfluxDB to line protocol.
+    ReadTsm(ReadTsm),
+    /// Parquet files of InfluxDB 3.x and IOx to line protocol.
+    ReadIox(ReadIox),
+    /// Capture line protocol written to an emulated InfluxDB.
+    Capture(Capture),
+    /// Replay line protocol to InfluxDB with the original pacing.
+    Replay(Replay),
+    /// Benchmark writing line protocol to InfluxDB.
+    Bench(Bench),
+}
+
+/// Dialect of CSV files.
+#[derive(Debug, Clone, Copy, Args)]
+pub struct CsvDialect {
+    /// Delimiter of CSV, e.g. `;` or `\t` for TSV.
+    #[arg(long, default_value = ",", value_parser = parse_csv_char)]
+    pub delimiter: u8,
+    /// Quote character of CSV.
+    #[arg(long, default_value = "\"", value_parser = parse_csv_char)]
+    pub quote: u8,
+    /// CSV files have no header row.
+    #[arg(long)]
+    pub no_header: bool,
+}
+
+impl Default for CsvDialect {
+    fn default() -> CsvDialect {
+        CsvDialect {
+            delimiter: b',',
+            quote: b'"',
+            no_header: false,
+        }
+    }
+}
+
+impl CsvDialect {
+    pub(crate) fn reader<R: Read>(&self, source: R) -> Reader<R> {
+        ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .has_headers(!self.no_header)
+            .from_reader(source)
+    }
+
+    /// Returns the builder of writers, which never writes headers by serializing.
+    pub(crate) fn writer_builder(&self) -> WriterBuilder {
+        let mut builder = WriterBuilder::new();
+        builder
+            .delimiter(self.delimiter)
+            .quote(self.quote)
+            .has_headers(false);
+        builder
+    }
+}
+
+/// Parse a single byte char of CSV, `\t` is a tab.
+fn parse_csv_char(s: &str) -> Result<u8, String> {
+    match s {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ if s.len() == 1 && s.is_ascii() => Ok(s.as_bytes()[0]),
+        _ => Err(format!("{s} is not a single ASCII char")),
+    }
+}
+
+/// Selection and renaming of tag and field columns.
+#[derive(Debug, Clone, Default, Args)]
+struct ColumnSelection {
+    /// Tag and field columns to keep, e.g. `host,usage_user`, other tags and fields are
+    /// dropped.
+    #[arg(long, value_delimiter = ',')]
+    select: Vec<String>,
+    /// Rename a tag or field column, e.g. `hostname=host`.
+    ///
+    /// Other options refer to columns by their names before renaming.
+    #[arg(long, value_parser = parse_rename)]
+    rename: Vec<(String, String)>,
+}
+
+impl ColumnSelection {
+    /// Select and rename columns of the schema.
+    fn apply(&self, schema: &mut Schema) {
+        if !self.select.is_empty() {
+            schema.select(&HashSet::from_iter(self.select.iter().cloned()));
+        }
+        for (key, name) in &self.rename {
+            schema.rename(key, name);
+        }
+    }
+}
+
+/// Parse a rename like `old=new`.
+fn parse_rename(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+            Ok((old.to_string(), new.to_string()))
+        }
+        _ => Err(format!("{s} is not in the form of <OLD>=<NEW>")),
+    }
 }
 
 /// Convert line protocol file to CSV file.
+///
+/// If the input is a directory, each line protocol file (`.lp` or `.txt`) in it is
+/// converted to a CSV file in the output directory with the same relative path.
+/// With `--output-dir`, each measurement is converted to a CSV file of its own columns.
 #[derive(Debug, Parser)]
 struct ToCsv {
-    /// Input line protocol file path.
+    /// Input line protocol file path (file or directory), `-` for stdin.
     #[arg(short, long)]
     input: String,
-    /// Output CSV file path.
-    #[arg(short, long)]
-    output: String,
+    /// Output CSV file path, or the directory if the input is a directory, `-` for stdout.
+    #[arg(short, long, required_unless_present = "output_dir")]
+    output: Option<String>,
+    /// Output directory of a CSV file of each measurement, e.g. `cpu.csv`.
+    ///
+    /// If the input is a directory, files of each input file are in the directory of
+    /// its relative path without extension, e.g. `sub/cpu.lp` to `sub/cpu/cpu.csv`.
+    #[arg(long, conflicts_with = "output")]
+    output_dir: Option<PathBuf>,
+    /// Search files in subdirectories if the input is a directory.
+    #[arg(long)]
+    recursive: bool,
+    /// Number of threads to convert lines, or files if the input is a directory.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    #[command(flatten)]
+    dialect: CsvDialect,
+    /// Format of the timestamp column.
+    #[arg(long, value_enum, default_value_t = TimeFormat::Rfc3339)]
+    time_format: TimeFormat,
+    /// Tag columns in order, which skips the first pass collecting columns of the input.
+    ///
+    /// Lines with tags or fields not in `--tag` and `--field` are invalid.
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Field columns in order, which skips the first pass collecting columns of the input.
+    #[arg(long)]
+    field: Vec<String>,
+    #[command(flatten)]
+    selection: ColumnSelection,
+    #[command(flatten)]
+    compression: CompressionArgs,
+    /// Map uncompressed input files into memory and parse lines without copying them,
+    /// which is faster for huge files. Lines of a file are converted by one thread.
+    #[arg(long, conflicts_with = "output_dir")]
+    mmap: bool,
+    /// Bytes of blocks of lines read from the input, lines are parsed from each block
+    /// instead of being read one by one. Blocks are also the unit of work of `--threads`.
+    #[arg(long, default_value_t = DEFAULT_BLOCK_SIZE)]
+    block_size: usize,
+    /// Save the progress to the checkpoint file periodically, so an interrupted
+    /// conversion is continued by `--resume`. Lines are converted by one thread and the
+    /// output must be an uncompressed local file. The file is removed after the
+    /// conversion.
+    #[arg(long, conflicts_with_all = ["output_dir", "mmap"])]
+    checkpoint: Option<PathBuf>,
+    /// Resume the conversion from the checkpoint file without converting the input
+    /// before it again, or start it if the checkpoint file doesn't exist.
+    #[arg(long, requires = "checkpoint")]
+    resume: bool,
+    /// Bytes of the input converted between checkpoints.
+    #[arg(long, default_value_t = DEFAULT_CHECKPOINT_INTERVAL)]
+    checkpoint_interval: u64,
+    /// Parse the input and print its statistics and the CSV header without writing the
+    /// output.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 impl ToCsv {
-    fn run(self) {
-        let input_file = File::open(&self.input).expect("Open line protocol file");
-        let output_file = File::create(&self.output).expect("Open CSV file");
+    fn run(self) -> Result<()> {
+        let input = Path::new(&self.input);
+        let is_dir = !is_stdio(input)
+            && !is_glob(input)
+            && !is_remote(input)
+            && fs::metadata(input)
+                .context(OpenFileSnafu { path: input })?
+                .is_dir();
+        if self.dry_run {
+            let files = if is_dir {
+                line_protocol_files(input, self.recursive)?
+            } else {
+                vec![input.to_path_buf()]
+            };
+            let mut output = io::stdout().lock();
+            for path in files {
+                if is_dir {
+                    writeln!(output, "File: {}\n", path.display()).context(WriteOutputSnafu)?;
+                }
+                let summary = self.summarize(&path)?;
+                writeln!(output, "{summary}").context(WriteOutputSnafu)?;
+            }
+            return Ok(());
+        }
+        if is_dir {
+            if let Some(path) = &self.checkpoint {
+                return InvalidCheckpointSnafu {
+                    path,
+                    reason: "directories can't be checkpointed",
+                }
+                .fail();
+            }
+            return self.convert_dir(input);
+        }
+
+        match (&self.output_dir, &self.output, &self.checkpoint) {
+            (Some(output_dir), _, _) => self.convert_measurements(input, output_dir),
+            (None, Some(output), Some(path)) => {
+                self.convert_checkpointed(input, Path::new(output), path)
+            }
+            (None, Some(output), None) => self.convert_file(input, Path::new(output), self.threads),
+            (None, None, _) => unreachable!("output is required without output dir"),
+        }
+    }
+
+    /// Returns the statistics of the line protocol file and the header of its CSV file, or
+    /// the header of the CSV file of each measurement if the output is a directory.
+    ///
+    /// The header has the columns if they are set by `--tag` and `--field`, or the columns
+    /// collected from the file.
+    fn summarize(&self, input: &Path) -> Result<String> {
+        let by_measurement = self.output_dir.is_some();
+        let columns = self.columns();
+        let mut stats = Stats::default();
+        let mut schema = Schema::default();
+        let mut schemas: Vec<(String, Schema)> = Vec::new();
+        read_lines(
+            open_input(input, self.compression.input_compression)?,
+            |line| {
+                let measurement = line.series.measurement.as_str();
+                if by_measurement {
+                    let idx = match schemas.iter().position(|(name, _)| name == measurement) {
+                        Some(idx) => idx,
+                        None => {
+                            schemas.push((measurement.to_string(), Schema::default()));
+                            schemas.len() - 1
+                        }
+                    };
+                    schemas[idx].1.add_columns(&line);
+                } else {
+                    schema.add_columns(&line);
+                }
+                stats.update(&line)
+            },
+        )?;
+
+        let header = |schema: &mut Schema| -> Result<String> {
+            self.selection.apply(schema);
+            let mut writer = self.dialect.writer_builder().from_writer(Vec::new());
+            writer
+                .write_record(csv_headers(columns.as_ref().unwrap_or(schema)))
+                .context(WriteCsvSnafu)?;
+            let header = writer
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context(WriteOutputSnafu)?;
+            Ok(String::from_utf8_lossy(&header).trim_end().to_string())
+        };
+        let mut summary = stats.to_string();
+        if !by_measurement {
+            summary.push_str(&format!("\nCSV header: {}", header(&mut schema)?));
+        }
+        for (measurement, schema) in &mut schemas {
+            let name = file_name(measurement);
+            summary.push_str(&format!("\nCSV header of {name}.csv: {}", header(schema)?));
+        }
+        Ok(summary)
+    }
+
+    /// Returns the columns set by `--tag` and `--field`, which are selected and renamed.
+    fn columns(&self) -> Option<Schema> {
+        (!self.tag.is_empty() || !self.field.is_empty()).then(|| {
+            let mut schema = Schema::with_columns(&self.tag, &self.field);
+            self.selection.apply(&mut schema);
+            schema
+        })
+    }
+
+    fn convert_dir(&self, dir: &Path) -> Result<()> {
+        let files = line_protocol_files(dir, self.recursive)?;
+        let output_dir = match &self.output_dir {
+            Some(output_dir) => output_dir.as_path(),
+            None => Path::new(self.output.as_deref().unwrap_or_default()),
+        };
+        let extension = self.compression.output_compression.extension();
+        pipeline::for_each(files, self.threads, |path| {
+            let relative = path.strip_prefix(dir).unwrap_or(&path);
+            let name = table_name(&path, None)?;
+            if self.output_dir.is_some() {
+                let output = output_dir.join(relative).with_file_name(name);
+                return self.convert_measurements(&path, &output);
+            }
+
+            let output = output_dir
+                .join(relative)
+                .with_file_name(format!("{name}.csv{extension}"));
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent).context(CreateDirSnafu { path: parent })?;
+            }
+            self.convert_file(&path, &output, 1)
+        })
+    }
+
+    /// Convert the line protocol file to a CSV file of each measurement in the directory.
+    fn convert_measurements(&self, input: &Path, dir: &Path) -> Result<()> {
+        let compression = self.compression;
+        let columns = self.columns();
+        let (schemas, source) = match &columns {
+            Some(_) => (
+                Vec::new(),
+                open_input(input, compression.input_compression)?,
+            ),
+            None => {
+                // The first pass collects the columns of each measurement.
+                let start = Instant::now();
+                let source = ReplayableInput::open(input, compression.input_compression)?;
+                let mut schemas =
+                    reject::silently(|| Schema::collect_measurement_columns(source.reader()?))?;
+                debug!(
+                    "Collected columns of {} measurements of {} in {:?}",
+                    schemas.len(),
+                    input.display(),
+                    start.elapsed()
+                );
+                for (_, schema) in &mut schemas {
+                    self.selection.apply(schema);
+                }
+                (schemas, source.reader()?)
+            }
+        };
+        let schemas: HashMap<_, _> = schemas.into_iter().collect();
+        fs::create_dir_all(dir).context(CreateDirSnafu { path: dir })?;
+
+        let start = Instant::now();
+        let extension = compression.output_compression.extension();
+        let mut writers: HashMap<String, Writer<Box<dyn Output + Send>>> = HashMap::new();
+        let mut row = CsvRow::default();
+        read_lines(source, |line| {
+            let measurement = line.series.measurement.as_str();
+            let schema = match &columns {
+                Some(schema) => schema,
+                None => schemas
+                    .get(measurement)
+                    .expect("measurements are collected by the first pass"),
+            };
+            let name = file_name(measurement);
+            let writer = match writers.get_mut(&name) {
+                Some(writer) => writer,
+                None => {
+                    let path = dir.join(format!("{name}.csv{extension}"));
+                    let output = create_output(path, compression.output_compression)?;
+                    let mut writer = self.dialect.writer_builder().from_writer(output);
+                    if !self.dialect.no_header {
+                        writer
+                            .write_record(csv_headers(schema))
+                            .context(WriteCsvSnafu)?;
+                    }
+                    writers.entry(name).or_insert(writer)
+                }
+            };
+            write_csv_row(schema, line, self.time_format, &mut row, writer)
+        })?;
+
+        for (_, writer) in writers {
+            let mut output = writer
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context(WriteOutputSnafu)?;
+            output.finish().context(WriteOutputSnafu)?;
+        }
+        debug!("Converted {} in {:?}", input.display(), start.elapsed());
+        Ok(())
+    }
+
+    /// Convert the line protocol file to the CSV file.
+    fn convert_file(&self, input: &Path, output: &Path, threads: usize) -> Result<()> {
+        if self.mmap {
+            return self.convert_mapped(input, output);
+        }
+        let compression = self.compression;
+        let (schema, source) = match self.columns() {
+            Some(schema) => (schema, open_input(input, compression.input_compression)?),
+            None => {
+                // The first pass collects the columns, the second pass writes the rows.
+                let start = Instant::now();
+                let source = ReplayableInput::open(input, compression.input_compression)?;
+                let mut schema = reject::silently(|| Schema::collect_columns(source.reader()?))?;
+                debug!(
+                    "Collected {} tags and {} fields of {} in {:?}",
+                    schema.tags.len(),
+                    schema.fields.len(),
+                    input.display(),
+                    start.elapsed()
+                );
+                self.selection.apply(&mut schema);
+                (schema, source.reader()?)
+            }
+        };
+
+        let start = Instant::now();
+        let output = create_output(output, compression.output_compression)?;
+        let mut output = line_protocol_to_csv(
+            &schema,
+            source,
+            output,
+            &self.dialect,
+            self.time_format,
+            threads,
+            self.block_size,
+        )?;
+        output.finish().context(WriteOutputSnafu)?;
+        debug!("Converted {} in {:?}", input.display(), start.elapsed());
+        Ok(())
+    }
+
+    /// Convert the line protocol file to the CSV file like `convert_file`, the progress is
+    /// saved to the checkpoint file to resume the conversion.
+    fn convert_checkpointed(&self, input: &Path, output: &Path, path: &Path) -> Result<()> {
+        let compression = self.compression;
+        ensure!(
+            !is_stdio(input),
+            InvalidCheckpointSnafu {
+                path,
+                reason: "stdin can't be resumed",
+            }
+        );
+        ensure!(
+            !is_stdio(output)
+                && !is_remote(output)
+                && compression.output_compression.of(output) == Compression::None,
+            InvalidCheckpointSnafu {
+                path,
+                reason: "the output must be an uncompressed local file",
+            }
+        );
+
+        let (mut checkpoint, source) = match Checkpoint::load(path)? {
+            Some(checkpoint) => {
+                ensure!(
+                    self.resume,
+                    InvalidCheckpointSnafu {
+                        path,
+                        reason: "it exists, continue by --resume or remove it",
+                    }
+                );
+                ensure!(
+                    checkpoint.input == input && checkpoint.output == output,
+                    InvalidCheckpointSnafu {
+                        path,
+                        reason: format!(
+                            "it converts {} to {}",
+                            checkpoint.input.display(),
+                            checkpoint.output.display()
+                        ),
+                    }
+                );
+                info!(
+                    "Resuming from line {} of {}",
+                    checkpoint.lines + 1,
+                    input.display()
+                );
+                let source = open_input_at(
+                    input,
+                    compression.input_compression,
+                    checkpoint.input_offset,
+                )?;
+                (checkpoint, source)
+            }
+            None if !self.tag.is_empty() || !self.field.is_empty() => {
+                let schema = Schema::with_columns(&self.tag, &self.field);
+                let source = open_input(input, compression.input_compression)?;
+                (Checkpoint::new(input, output, &schema), source)
+            }
+            None => {
+                let source = ReplayableInput::open(input, compression.input_compression)?;
+                let schema = reject::silently(|| Schema::collect_columns(source.reader()?))?;
+                (Checkpoint::new(input, output, &schema), source.reader()?)
+            }
+        };
+        // The selection is applied again, so the checkpoint has the columns before it.
+        let mut schema = checkpoint.schema();
+        self.selection.apply(&mut schema);
+
+        let options = CheckpointOptions {
+            path,
+            dialect: &self.dialect,
+            time_format: self.time_format,
+            block_size: self.block_size,
+            interval: self.checkpoint_interval,
+        };
+        checkpoint::convert(source, &mut checkpoint, &schema, &options)
+    }
+
+    /// Convert the mapped line protocol file to the CSV file.
+    fn convert_mapped(&self, input: &Path, output: &Path) -> Result<()> {
+        let compression = self.compression;
+        let map = Mmap::open(input, compression.input_compression)?;
+        let text = mapped_text(&map, input)?;
+        let schema = match self.columns() {
+            Some(schema) => schema,
+            None => {
+                let start = Instant::now();
+                let mut schema = Schema::default();
+                reject::silently(|| {
+                    read_text_lines(text, |_, line| {
+                        schema.add_columns(&line);
+                        Ok(())
+                    })
+                })?;
+                debug!(
+                    "Collected {} tags and {} fields of {} in {:?}",
+                    schema.tags.len(),
+                    schema.fields.len(),
+                    input.display(),
+                    start.elapsed()
+                );
+                self.selection.apply(&mut schema);
+                schema
+            }
+        };
+
+        let start = Instant::now();
+        let output = create_output(output, compression.output_compression)?;
+        let mut output = text_to_csv(&schema, text, output, &self.dialect, self.time_format)?;
+        output.finish().context(WriteOutputSnafu)?;
+        debug!("Converted {} in {:?}", input.display(), start.elapsed());
+        Ok(())
+    }
+}
+
+/// Returns line protocol files, which are `.lp` or `.txt` files, in the directory in sorted
+/// order.
+fn line_protocol_files(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in dir.read_dir().context(ReadDirSnafu { path: &dir })? {
+            let path = entry.context(ReadDirSnafu { path: &dir })?.path();
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+                continue;
+            }
 
-        line_protocol_to_csv(input_file, output_file);
+            let name = path.file_name().and_then(|name| name.to_str());
+            let name = match Compression::Auto.of(&path) {
+                Compression::None => name,
+                _ => name
+                    .and_then(|name| name.rsplit_once('.'))
+                    .map(|(name, _)| name),
+            };
+            if name.is_some_and(|name| name.ends_with(".lp") || name.ends_with(".txt")) {
+                paths.push(path);
+            }
+        }
     }
+    paths.sort_unstable();
+    Ok(paths)
 }
 
 /// Convert CSV file to line protocol file.
 #[derive(Debug, Parser)]
 struct FromCsv {
-    /// Input CSV file path (file or directory).
+    /// Input CSV file path (file, directory or glob pattern), `-` for stdin.
     #[arg(short, long)]
     input: String,
-    /// Output line protocol file path.
+    /// Output line protocol file path, `-` for stdout.
     #[arg(short, long)]
     output: String,
+    /// Measurement name, defaults to the file name without extension.
+    ///
+    /// Required when reading from stdin without the measurement column.
+    #[arg(long)]
+    measurement: Option<String>,
+    /// Column of measurement names of rows, empty names fall back to the measurement.
+    #[arg(long)]
+    measurement_column: Option<String>,
     /// Timestamp column name.
     #[arg(long, default_value = "timestamp")]
     timestamp: String,
+    /// Precision of the timestamp column if timestamps are epoch, `auto` detects the
+    /// precision of each timestamp by its magnitude.
+    #[arg(long, value_enum, default_value_t = Precision::Ms)]
+    precision: Precision,
+    /// strptime format of the timestamp column, e.g. "%Y-%m-%d %H:%M:%S".
+    ///
+    /// Timestamps are epoch or RFC3339 if not set.
+    #[arg(long)]
+    timestamp_format: Option<String>,
     /// Tag names.
     #[arg(long)]
     tag: Vec<String>,
+    /// Types of field columns, e.g. `usage_user=i64,enabled=bool`.
+    ///
+    /// Types are `i64`, `u64`, `f64`, `bool` and `string`. Untyped fields are floats if
+    /// they are numbers, or strings otherwise.
+    #[arg(long, value_delimiter = ',', value_parser = parse_column_type)]
+    types: Vec<(String, FieldType)>,
+    /// Infer types of untyped field columns from their values, which reads each file twice.
+    #[arg(long)]
+    infer_types: bool,
+    /// How to write empty field values, rows without any field are always skipped.
+    #[arg(long, value_enum, default_value_t = NullPolicy::SkipField)]
+    null_policy: NullPolicy,
+    #[command(flatten)]
+    selection: ColumnSelection,
+    /// Read CSV files in long format, in which each row has the name and value of a field.
+    #[arg(long)]
+    long_format: bool,
+    /// Column of field names in long format.
+    #[arg(long, default_value = "metric")]
+    name_column: String,
+    /// Column of field values in long format.
+    #[arg(long, default_value = "value")]
+    value_column: String,
+    /// Names and kinds of columns in order if CSV files have no header, e.g.
+    /// `host:tag,usage:field,timestamp:time`.
+    #[arg(long, value_delimiter = ',', value_parser = parse_csv_column, requires = "no_header")]
+    columns: Vec<(String, ColumnKind)>,
+    #[command(flatten)]
+    dialect: CsvDialect,
+    /// TOML schema file of measurements, which overrides the options above for the tables
+    /// in it.
+    #[arg(long)]
+    schema: Option<PathBuf>,
     /// Table name prefix.
     #[arg(long, default_value = "")]
     table_prefix: String,
+    /// Bytes of the buffer of the output.
+    #[arg(long, default_value_t = DEFAULT_BUFFER_SIZE)]
+    buffer_size: usize,
+    /// Flush the output after this number of lines, so readers of the output see lines
+    /// without waiting for the buffer to fill up.
+    #[arg(long)]
+    flush_lines: Option<u64>,
+    #[command(flatten)]
+    compression: CompressionArgs,
+    /// With `--watch`, the input is a directory and each new file in it is converted once
+    /// it stops growing, the output is flushed after each batch of files.
+    #[command(flatten)]
+    watch: WatchArgs,
+    /// Parse the input and print the mapping of columns of each file and statistics of
+    /// lines without writing the output.
+    #[arg(long, conflicts_with = "watch")]
+    dry_run: bool,
 }
 
 impl FromCsv {
-    fn run(self) {
-        let file = File::create(&self.output).unwrap();
-        let mut writer = LineWriter {
-            writer: BufWriter::new(file),
-            timestamp: self.timestamp.clone(),
-            tags: HashSet::from_iter(self.tag.into_iter()),
-            table_prefix: self.table_prefix,
+    fn run(self) -> Result<()> {
+        ensure!(
+            !self.dialect.no_header || !self.columns.is_empty(),
+            MissingColumnsSnafu
+        );
+        let schema = match &self.schema {
+            Some(path) => CsvSchema::load(path)?,
+            None => CsvSchema::default(),
         };
-
-        let input_path = Path::new(&self.input);
-        let metadata = fs::metadata(input_path).unwrap();
-        if metadata.is_dir() {
-            writer.from_csv_dir(input_path);
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let output: Box<dyn Output + Send> = if self.dry_run {
+            Box::new(StatsWriter::new(stats.clone()))
         } else {
-            writer.from_csv_file(input_path);
+            create_output(&self.output, self.compression.output_compression)?
+        };
+        let mut writer = self.line_writer(output)?;
+
+        if self.watch.watch {
+            let mut watcher = DirWatcher::new(Path::new(&self.input), self.watch.interval())?;
+            loop {
+                let files = watcher.next_files()?;
+                self.convert_files(&mut writer, &schema, &files)?;
+            }
+        }
+        let files = input_files(Path::new(&self.input))?;
+        self.convert_files(&mut writer, &schema, &files)?;
+        writer.finish()?;
+        debug!(
+            "Wrote {} points in {} bytes",
+            writer.points_written, writer.bytes_written
+        );
+
+        if writer.skipped_rows > 0 {
+            warn!("Skipped {} rows without fields", writer.skipped_rows);
         }
+        if let Some(mappings) = &writer.mappings {
+            let mut output = io::stdout().lock();
+            for mapping in mappings {
+                writeln!(output, "{mapping}").context(WriteOutputSnafu)?;
+            }
+            let stats = stats.lock().unwrap();
+            writeln!(output, "{stats}\nSkipped rows: {}", writer.skipped_rows)
+                .context(WriteOutputSnafu)?;
+        }
+        Ok(())
     }
-}
 
-#[derive(Debug)]
-struct LineWriter {
-    writer: BufWriter<File>,
-    timestamp: String,
-    tags: HashSet<String>,
-    table_prefix: String,
-}
+    /// Returns the writer of lines to the output by the options.
+    fn line_writer(&self, output: Box<dyn Output + Send>) -> Result<LineWriter> {
+        let mut writer = LineWriter::with_capacity(
+            self.buffer_size,
+            output,
+            String::new(),
+            TimestampParser::new(self.precision, None),
+            HashSet::new(),
+            self.table_prefix.clone(),
+        )?;
+        writer.flush_lines = self.flush_lines;
+        writer.infer_types = self.infer_types;
+        writer.null_policy = self.null_policy;
+        writer.dialect = self.dialect;
+        writer.columns = self.columns.iter().map(|(name, _)| name.clone()).collect();
+        writer.mappings = self.dry_run.then(Vec::new);
 
-impl LineWriter {
-    fn from_csv_file(&mut self, path: &Path) {
-        let table_name = path.file_stem().unwrap().to_str().unwrap();
-        let input_file = File::open(path).unwrap();
-        let mut reader = Reader::from_reader(input_file);
-        let headers: Vec<_> = reader
-            .headers()
-            .unwrap()
-            .iter()
-            .map(|v| v.to_string())
-            .collect();
+        writer.measurement_column = self.measurement_column.clone();
+        if self.long_format {
+            writer.long_format = Some((self.name_column.clone(), self.value_column.clone()));
+        }
+        Ok(writer)
+    }
 
-        let mut line = String::new();
-        for result in reader.records() {
-            line.clear();
-            let record = result.unwrap();
-            // Push measurement.
-            write!(line, "{}{}", self.table_prefix, table_name).unwrap();
-            if !self.tags.is_empty() {
-                // Push tags.
-                for (name, value) in headers.iter().zip(record.iter()) {
-                    if self.tags.contains(name) {
-                        write!(line, ",{name}={value}").unwrap();
-                    }
+    /// Convert the CSV files and flush the output.
+    fn convert_files(
+        &self,
+        writer: &mut LineWriter,
+        schema: &CsvSchema,
+        paths: &[PathBuf],
+    ) -> Result<()> {
+        for path in paths {
+            self.convert_file(writer, schema, path)?;
+        }
+        writer.flush()
+    }
+
+    /// Convert the CSV file with the schema of its measurement.
+    fn convert_file(&self, writer: &mut LineWriter, schema: &CsvSchema, path: &Path) -> Result<()> {
+        let table_name = match table_name(path, self.measurement.as_deref()) {
+            // Rows have their measurements in the column.
+            Err(Error::MissingMeasurement) if self.measurement_column.is_some() => "",
+            result => result?,
+        };
+        let measurement = schema.measurements.get(table_name);
+        self.configure(writer, measurement);
+        let name = measurement
+            .and_then(|m| m.name.as_deref())
+            .unwrap_or(table_name);
+        let start = Instant::now();
+        writer.convert_csv_file(path, self.compression.input_compression, name)?;
+        debug!("Converted {} in {:?}", path.display(), start.elapsed());
+        Ok(())
+    }
+
+    /// Configure the writer by the schema of the measurement and the options.
+    fn configure(&self, writer: &mut LineWriter, schema: Option<&MeasurementSchema>) {
+        let default = MeasurementSchema::default();
+        let schema = schema.unwrap_or(&default);
+        writer.timestamp = schema.timestamp.as_ref().unwrap_or(&self.timestamp).clone();
+        writer.timestamp_parser = TimestampParser::new(
+            schema.precision.unwrap_or(self.precision),
+            schema
+                .timestamp_format
+                .as_ref()
+                .or(self.timestamp_format.as_ref())
+                .cloned(),
+        );
+        let tags = schema.tags.as_ref().unwrap_or(&self.tag);
+        writer.tags = HashSet::from_iter(tags.iter().cloned());
+        writer.types = HashMap::from_iter(self.types.iter().cloned());
+        writer.fields = None;
+        if let Some(fields) = &schema.fields {
+            writer.types.extend(fields.clone());
+            writer.fields = Some(fields.keys().cloned().collect());
+        }
+        writer.renames = schema.rename.clone();
+
+        for (name, kind) in &self.columns {
+            match kind {
+                ColumnKind::Tag => {
+                    writer.tags.insert(name.clone());
                 }
+                ColumnKind::Field => (),
+                ColumnKind::Time => writer.timestamp = name.clone(),
             }
-            // Push fields.
-            let mut first_field = true;
-            for (name, value) in headers.iter().zip(record.iter()) {
-                // Skip empty field.
-                if value.is_empty() {
-                    continue;
-                }
-                // Is not tag or timestamp.
-                if !self.tags.contains(name) && *name != self.timestamp {
-                    if first_field {
-                        line += " ";
-                        first_field = false;
-                    } else {
-                        line += ","
-                    }
-                    if let Ok(field) = value.parse::<f64>() {
-                        write!(line, "{name}={field}").unwrap();
-                    } else {
-                        write!(line, "{name}=\"{value}\"").unwrap();
-                    }
-                }
+        }
+
+        let selection = &self.selection;
+        if !selection.select.is_empty() {
+            let select = HashSet::from_iter(selection.select.iter().cloned());
+            writer.tags.retain(|tag| select.contains(tag));
+            writer.fields = Some(match writer.fields.take() {
+                Some(fields) => fields.intersection(&select).cloned().collect(),
+                None => select,
+            });
+        }
+        writer.renames.extend(selection.rename.iter().cloned());
+    }
+}
+
+/// Kind of a column in CSV without header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnKind {
+    Tag,
+    Field,
+    Time,
+}
+
+/// Parse a column of CSV without header like `host:tag`.
+fn parse_csv_column(s: &str) -> Result<(String, ColumnKind), String> {
+    let (name, kind) = s
+        .rsplit_once(':')
+        .ok_or_else(|| format!("{s} is not in the form of <COLUMN>:<KIND>"))?;
+    let kind = match kind {
+        "tag" => ColumnKind::Tag,
+        "field" => ColumnKind::Field,
+        "time" | "timestamp" => ColumnKind::Time,
+        _ => return Err(format!("unknown kind {kind}, expect tag, field or time")),
+    };
+    Ok((name.to_string(), kind))
+}
+
+/// Parse a type of column like `usage_user=i64`.
+fn parse_column_type(s: &str) -> Result<(String, FieldType), String> {
+    let (name, field_type) = s
+        .split_once('=')
+        .ok_or_else(|| format!("{s} is not in the form of <COLUMN>=<TYPE>"))?;
+    Ok((name.to_string(), field_type.parse()?))
+}
+
+/// Parse a boolean like line protocol does, e.g. `true`, `T` or `false`.
+fn parse_bool(text: &str) -> Option<bool> {
+    match text {
+        "t" | "T" | "true" | "True" | "TRUE" => Some(true),
+        "f" | "F" | "false" | "False" | "FALSE" => Some(false),
+        _ => None,
+    }
+}
+
+/// Infer the type of each column from all values in the CSV, `None` if a column has no value.
+///
+/// Integers are `i64`, or `u64` if they only fit in `u64`. Conflicting numeric types are
+/// widened to float, other conflicts become strings.
+fn infer_column_types<R: Read>(
+    mut reader: Reader<R>,
+    path: &Path,
+) -> Result<Vec<Option<FieldType>>> {
+    let mut types: Vec<Option<FieldType>> = Vec::new();
+    for result in reader.records() {
+        let record = result.context(ReadCsvSnafu { path })?;
+        types.resize(types.len().max(record.len()), None);
+        for (value, inferred) in record.iter().zip(&mut types) {
+            if value.is_empty() {
+                continue;
             }
-            // Push timestamp.
-            for (name, value) in headers.iter().zip(record.iter()) {
-                if *name == self.timestamp {
-                    // Now we assume timestamp is in millisecond.
-                    let ts = value.parse::<i64>().unwrap();
-                    // Convert millisecond to nanosecond.
-                    let ts = ts * 1000 * 1000;
+            let field_type = if value.parse::<i64>().is_ok() {
+                FieldType::Int64
+            } else if value.parse::<u64>().is_ok() {
+                FieldType::UInt64
+            } else if value.parse::<f64>().is_ok() {
+                FieldType::Float64
+            } else if parse_bool(value).is_some() && value.len() > 1 {
+                // Single letters like `t` are more likely to be strings.
+                FieldType::Boolean
+            } else {
+                FieldType::String
+            };
+            *inferred = Some(match *inferred {
+                Some(prev) => prev.merge(field_type).unwrap_or(FieldType::String),
+                None => field_type,
+            });
+        }
+    }
+    Ok(types)
+}
 
-                    write!(line, " {ts}").unwrap();
-                    break;
-                }
+/// Returns files to read from the input path.
+///
+/// Returns all files under the path in sorted order if the path is a directory, or
+/// files matching the path in sorted order if the path is a glob pattern.
+pub(crate) fn input_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if is_stdio(path) || is_remote(path) {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if is_glob(path) {
+        return expand_glob(path);
+    }
+
+    let metadata = fs::metadata(path).context(OpenFileSnafu { path })?;
+    if !metadata.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut paths = Vec::new();
+    for entry in path.read_dir().context(ReadDirSnafu { path })? {
+        let entry = entry.context(ReadDirSnafu { path })?;
+        paths.push(entry.path());
+    }
+    paths.sort_unstable();
+    Ok(paths)
+}
+
+/// Returns the table name of the file.
+///
+/// The table name is the measurement if it is set, or the file name without extension.
+/// The compression extension is also removed, e.g. `cpu.csv.gz` is `cpu`.
+fn table_name<'a>(path: &'a Path, measurement: Option<&'a str>) -> Result<&'a str> {
+    if let Some(measurement) = measurement {
+        return Ok(measurement);
+    }
+    ensure!(!is_stdio(path), MissingMeasurementSnafu);
+
+    let stem = match Compression::Auto.of(path) {
+        Compression::None => path.file_stem(),
+        _ => path
+            .file_stem()
+            .and_then(|stem| Path::new(stem).file_stem()),
+    };
+    stem.and_then(|stem| stem.to_str())
+        .context(InvalidFileNameSnafu { path })
+}
+
+/// Read line protocol from the source and call `f` for each parsed line.
+///
+/// Errors are reported with the line number.
+pub(crate) fn read_lines<R: Read>(
+    source: R,
+    mut f: impl FnMut(ParsedLine<'_>) -> Result<()>,
+) -> Result<()> {
+    read_lines_with_text(source, |_, line| f(line))
+}
+
+/// Read line protocol like `read_lines`, `f` is also called with the text of the line
+/// including the newline.
+pub(crate) fn read_lines_with_text<R: Read>(
+    source: R,
+    mut f: impl FnMut(&str, ParsedLine<'_>) -> Result<()>,
+) -> Result<()> {
+    read_lines_while(source, |text, line| f(text, line).map(|_| true))
+}
+
+/// Read line protocol like `read_lines_with_text` until `f` returns false.
+pub(crate) fn read_lines_while<R: Read>(
+    source: R,
+    f: impl FnMut(&str, ParsedLine<'_>) -> Result<bool>,
+) -> Result<()> {
+    read_blocks_while(source, DEFAULT_BLOCK_SIZE, f)
+}
+
+/// Read line protocol like `read_lines_while`, the source is read in blocks of about
+/// `block_size` bytes and lines are parsed from slices of each block.
+pub(crate) fn read_blocks_while<R: Read>(
+    source: R,
+    block_size: usize,
+    mut f: impl FnMut(&str, ParsedLine<'_>) -> Result<bool>,
+) -> Result<()> {
+    let mut reader = ChunkReader::new(source, block_size);
+    let mut line_number: usize = 0;
+
+    while let Some(block) = reader.next_chunk()? {
+        if !read_block_lines(block, &mut line_number, &mut f)? {
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse lines of the block and call `f` for each line until `f` returns false, returns
+/// false if `f` does. `line_number` is the number of lines before the block, which is
+/// advanced by lines of the block.
+pub(crate) fn read_block_lines(
+    block: &str,
+    line_number: &mut usize,
+    mut f: impl FnMut(&str, ParsedLine<'_>) -> Result<bool>,
+) -> Result<bool> {
+    for text in block.split_inclusive('\n') {
+        *line_number += 1;
+        for line in influxdb_line_protocol::parse_lines(text) {
+            let result = line
+                .context(ParseLineSnafu)
+                .and_then(|line| f(text, line))
+                .context(LineSnafu { line: *line_number });
+            if reject::check(result, || text)? == Some(false) {
+                return Ok(false);
             }
-            line += "\n";
+        }
+    }
+    Ok(true)
+}
 
-            // Write line.
-            self.writer.write(line.as_bytes()).unwrap();
+/// Number of lines between progress updates of `read_text_lines`.
+const PROGRESS_LINES: usize = 1 << 16;
+
+/// Read line protocol of the text like `read_lines_with_text`, lines are parsed from
+/// slices of the text without copying them, e.g. the text of a mapped file.
+pub(crate) fn read_text_lines(
+    text: &str,
+    mut f: impl FnMut(&str, ParsedLine<'_>) -> Result<()>,
+) -> Result<()> {
+    let (mut bytes, mut lines) = (0, 0);
+    for (i, text) in text.split_inclusive('\n').enumerate() {
+        for line in influxdb_line_protocol::parse_lines(text) {
+            let result = line
+                .context(ParseLineSnafu)
+                .and_then(|line| f(text, line))
+                .context(LineSnafu { line: i + 1 });
+            reject::check(result, || text)?;
+        }
+        bytes += text.len();
+        lines += 1;
+        if lines == PROGRESS_LINES {
+            progress::add_read(bytes, lines);
+            (bytes, lines) = (0, 0);
         }
     }
+    progress::add_read(bytes, lines);
 
-    fn from_csv_dir(&mut self, path: &Path) {
-        let mut paths = Vec::new();
-        for entry in path.read_dir().unwrap() {
-            let entry = entry.unwrap();
-            paths.push(entry.path().to_str().unwrap().to_string());
+    Ok(())
+}
+
+/// Returns the text of the mapped file, which must be valid UTF-8.
+pub(crate) fn mapped_text<'a>(map: &'a Mmap, path: &Path) -> Result<&'a str> {
+    std::str::from_utf8(map).map_err(|e| {
+        InvalidMappedInputSnafu {
+            path,
+            reason: format!("invalid UTF-8 at byte {}", e.valid_up_to()),
         }
-        paths.sort_unstable();
-        for path in paths {
-            let path = Path::new(&path);
-            self.from_csv_file(&path);
+        .build()
+    })
+}
+
+/// Chars to escape in measurements.
+pub(crate) const MEASUREMENT_ESCAPES: &[char] = &[',', ' '];
+/// Chars to escape in tag keys, tag values and field keys.
+pub(crate) const KEY_ESCAPES: &[char] = &[',', '=', ' '];
+
+/// Push the value with the chars escaped by `\\`.
+pub(crate) fn escape(line: &mut String, value: &str, chars: &[char]) {
+    for c in value.chars() {
+        if c == '\\' || chars.contains(&c) {
+            line.push('\\');
         }
+        line.push(c);
     }
 }
 
-#[derive(Debug, Serialize)]
-enum Value {
-    Int64(i64),
-    UInt64(u64),
-    Float64(f64),
-    String(String),
-    Boolean(bool),
+/// Push the measurement and tags of a line.
+pub(crate) fn push_series<'a>(
+    line: &mut String,
+    measurement: &str,
+    tags: impl IntoIterator<Item = (&'a str, &'a str)>,
+) {
+    escape(line, measurement, MEASUREMENT_ESCAPES);
+    for (key, value) in tags {
+        line.push(',');
+        escape(line, key, KEY_ESCAPES);
+        line.push('=');
+        escape(line, value, KEY_ESCAPES);
+    }
 }
 
-impl From<EscapedStr<'_>> for Value {
-    fn from(value: EscapedStr) -> Value {
-        Value::String(value.into())
+/// Returns the measurement and sorted tags of the line in line protocol.
+///
+/// Lines of the same series have the same key regardless of the order of tags.
+pub(crate) fn series_key(line: &ParsedLine<'_>) -> String {
+    let mut tags: Vec<_> = line
+        .series
+        .tag_set
+        .iter()
+        .flatten()
+        .map(|(k, v)| (k.as_str(), v.as_str()))
+        .collect();
+    tags.sort_unstable_by_key(|(k, _)| *k);
+    let mut key = String::new();
+    push_series(&mut key, line.series.measurement.as_str(), tags);
+    key
+}
+
+/// Returns the 64-bit FNV-1a hash of the parts, which is stable across runs and builds.
+pub(crate) fn fnv1a(parts: &[&str]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in part.bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
     }
+    hash
 }
 
-impl From<FieldValue<'_>> for Value {
-    fn from(value: FieldValue) -> Value {
+/// Push the value as a quoted string field value.
+fn push_string(line: &mut String, value: &str) {
+    line.push('"');
+    escape(line, value, &['"']);
+    line.push('"');
+}
+
+/// Push fields of a line, including the leading space.
+pub(crate) fn push_fields<'a>(
+    line: &mut String,
+    fields: impl IntoIterator<Item = (&'a str, &'a FieldValue<'a>)>,
+) {
+    for (i, (key, value)) in fields.into_iter().enumerate() {
+        line.push(if i == 0 { ' ' } else { ',' });
+        escape(line, key, KEY_ESCAPES);
+        line.push('=');
         match value {
-            FieldValue::I64(v) => Value::Int64(v),
-            FieldValue::U64(v) => Value::UInt64(v),
-            FieldValue::F64(v) => Value::Float64(v),
-            FieldValue::String(v) => Value::String(v.into()),
-            FieldValue::Boolean(v) => Value::Boolean(v),
+            FieldValue::String(v) => push_string(line, v.as_str()),
+            v => write!(line, "{v}").unwrap(),
         }
     }
 }
 
-fn line_protocol_to_csv<R: Read, W: Write>(source: R, dest: W) -> W {
-    let mut reader = BufReader::new(source);
-    let mut buffer = String::new();
-    let mut writer = Writer::from_writer(dest);
-    let mut row = Vec::new();
+/// Push the parsed line with a newline.
+///
+/// Unlike the `Display` of `ParsedLine`, string fields are quoted.
+pub(crate) fn push_line(line: &mut String, parsed: &ParsedLine<'_>) {
+    let tags = parsed.series.tag_set.iter().flatten();
+    push_series(
+        line,
+        parsed.series.measurement.as_str(),
+        tags.map(|(k, v)| (k.as_str(), v.as_str())),
+    );
+    push_fields(line, parsed.field_set.iter().map(|(k, v)| (k.as_str(), v)));
+    if let Some(ts) = parsed.timestamp {
+        write!(line, " {ts}").unwrap();
+    }
+    line.push('\n');
+}
 
-    while reader.read_line(&mut buffer).unwrap() > 0 {
-        let parsed_lines = influxdb_line_protocol::parse_lines(&buffer);
-        for line in parsed_lines {
-            let line = line.unwrap();
+/// How to write empty field values of CSV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum NullPolicy {
+    /// Skip the field.
+    SkipField,
+    /// Skip the row.
+    SkipRow,
+    /// Write an empty string.
+    EmptyString,
+    /// Write the zero value of the field type, e.g. `0`, `0i` or `false`.
+    Zero,
+}
 
-            if let Some(tag_set) = line.series.tag_set {
-                for (_tagk, tagv) in tag_set {
-                    row.push(Value::from(tagv));
-                }
-            }
-            for (_fieldk, fieldv) in line.field_set {
-                row.push(Value::from(fieldv));
+/// Value of a column to write into a line.
+#[derive(Debug)]
+enum ColumnValue<'a> {
+    /// Untyped text, written as a float field if it is a number.
+    Text(&'a str),
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    String(&'a str),
+    Boolean(bool),
+    Null,
+}
+
+impl ColumnValue<'_> {
+    /// Parse the text as a value of the type, empty text is null.
+    fn parse(text: &str, field_type: FieldType) -> Option<ColumnValue<'_>> {
+        if text.is_empty() {
+            return Some(ColumnValue::Null);
+        }
+        let value = match field_type {
+            FieldType::Int64 => ColumnValue::Int64(text.parse().ok()?),
+            FieldType::UInt64 => ColumnValue::UInt64(text.parse().ok()?),
+            // Line protocol has no NaN or infinity.
+            FieldType::Float64 => {
+                ColumnValue::Float64(text.parse().ok().filter(|v: &f64| v.is_finite())?)
             }
-            if let Some(timestamp) = line.timestamp {
-                let dt = Utc.timestamp_nanos(timestamp);
-                row.push(Value::String(dt.to_rfc3339()));
+            FieldType::String => ColumnValue::String(text),
+            FieldType::Boolean => ColumnValue::Boolean(parse_bool(text)?),
+        };
+        Some(value)
+    }
+
+    /// Write the value as an escaped tag value.
+    fn write_tag(&self, line: &mut String) {
+        match self {
+            ColumnValue::Text(v) | ColumnValue::String(v) => escape(line, v, KEY_ESCAPES),
+            ColumnValue::Int64(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::UInt64(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::Float64(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::Boolean(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::Null => (),
+        }
+    }
+
+    /// Write the value as a field value, strings are quoted and escaped.
+    fn write_field(&self, line: &mut String) {
+        match self {
+            ColumnValue::Text(v) => {
+                // Line protocol has no NaN or infinity, so they are strings.
+                match v.parse::<f64>().ok().filter(|field| field.is_finite()) {
+                    Some(field) => write!(line, "{field}").unwrap(),
+                    None => push_string(line, v),
+                }
             }
+            ColumnValue::Int64(v) => write!(line, "{v}i").unwrap(),
+            ColumnValue::UInt64(v) => write!(line, "{v}u").unwrap(),
+            ColumnValue::Float64(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::String(v) => push_string(line, v),
+            ColumnValue::Boolean(v) => write!(line, "{v}").unwrap(),
+            ColumnValue::Null => (),
         }
+    }
 
-        writer.serialize(&row).unwrap();
+    /// Returns the zero value of the type, a float if the type is unknown.
+    fn zero(field_type: Option<FieldType>) -> ColumnValue<'static> {
+        match field_type {
+            Some(FieldType::Int64) => ColumnValue::Int64(0),
+            Some(FieldType::UInt64) => ColumnValue::UInt64(0),
+            Some(FieldType::Float64) | None => ColumnValue::Float64(0.0),
+            Some(FieldType::String) => ColumnValue::String(""),
+            Some(FieldType::Boolean) => ColumnValue::Boolean(false),
+        }
+    }
 
-        buffer.clear();
-        row.clear();
+    /// Returns true if the value should be skipped when writing fields.
+    fn is_empty(&self) -> bool {
+        matches!(self, ColumnValue::Text("") | ColumnValue::Null)
     }
+}
+
+/// Default bytes of the buffer of [LineWriter], which is the default of [BufWriter].
+pub(crate) const DEFAULT_BUFFER_SIZE: usize = 8 << 10;
 
-    writer.into_inner().unwrap()
+/// Default bytes of blocks of line protocol read by [read_blocks_while].
+pub(crate) const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
+
+/// Writer of CSV rows and other records as line protocol.
+pub(crate) struct LineWriter<W: Write = Box<dyn Output + Send>> {
+    pub(crate) writer: BufWriter<W>,
+    /// Flush the output after this number of lines if set.
+    pub(crate) flush_lines: Option<u64>,
+    /// Number of lines and bytes written.
+    pub(crate) points_written: u64,
+    pub(crate) bytes_written: u64,
+    pub(crate) timestamp: String,
+    pub(crate) timestamp_parser: TimestampParser,
+    pub(crate) tags: HashSet<String>,
+    table_prefix: String,
+    /// Types of field columns.
+    pub(crate) types: HashMap<String, FieldType>,
+    /// Whether to infer types of untyped field columns.
+    infer_types: bool,
+    /// Field columns, all columns except tags and the timestamp are fields if not set.
+    fields: Option<HashSet<String>>,
+    /// New names of tag and field columns.
+    renames: HashMap<String, String>,
+    /// How to write empty field values of CSV.
+    null_policy: NullPolicy,
+    /// Number of rows skipped for empty values or having no fields.
+    skipped_rows: u64,
+    /// Dialect of CSV files.
+    pub(crate) dialect: CsvDialect,
+    /// Column names of CSV files without header.
+    columns: Vec<String>,
+    /// Column of measurement names.
+    pub(crate) measurement_column: Option<String>,
+    /// Columns of field names and values if CSV files are in long format.
+    long_format: Option<(String, String)>,
+    /// Mappings of columns of converted files, collected by dry runs.
+    mappings: Option<Vec<FileMapping>>,
+    /// Buffer to build the line.
+    line: String,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::io::Cursor;
-    use tempfile::{Builder, NamedTempFile};
+impl<W: Output> LineWriter<W> {
+    /// Flush buffered lines and finish the output.
+    pub(crate) fn finish(&mut self) -> Result<()> {
+        self.writer.finish().context(WriteOutputSnafu)
+    }
+}
 
-    #[test]
-    fn test_line_protocol_to_csv() {
-        let input = "cpu,hostname=host_0,region=eu-central-1,datacenter=eu-central-1a,rack=6,os=Ubuntu15.10,arch=x86,team=SF,service=19,service_version=1,service_environment=test usage_user=58i,usage_system=2i,usage_idle=24i,usage_nice=61i,usage_iowait=22i,usage_irq=63i,usage_softirq=6i,usage_steal=44i,usage_guest=80i,usage_guest_nice=38i 1451606400000000000
-cpu,hostname=host_1,region=us-west-1,datacenter=us-west-1a,rack=41,os=Ubuntu15.10,arch=x64,team=NYC,service=9,service_version=1,service_environment=staging usage_user=84i,usage_system=11i,usage_idle=53i,usage_nice=87i,usage_iowait=29i,usage_irq=20i,usage_softirq=54i,usage_steal=77i,usage_guest=53i,usage_guest_nice=74i 1451606400000000000
-cpu,hostname=host_2,region=sa-east-1,datacenter=sa-east-1a,rack=89,os=Ubuntu16.04LTS,arch=x86,team=LON,service=13,service_version=0,service_environment=staging usage_user=29i,usage_system=48i,usage_idle=5i,usage_nice=63i,usage_iowait=17i,usage_irq=52i,usage_softirq=60i,usage_steal=49i,usage_guest=93i,usage_guest_nice=1i 1451606400000000000";
-        let expect = "host_0,eu-central-1,eu-central-1a,6,Ubuntu15.10,x86,SF,19,1,test,58,2,24,61,22,63,6,44,80,38,2016-01-01T00:00:00+00:00
-host_1,us-west-1,us-west-1a,41,Ubuntu15.10,x64,NYC,9,1,staging,84,11,53,87,29,20,54,77,53,74,2016-01-01T00:00:00+00:00
-host_2,sa-east-1,sa-east-1a,89,Ubuntu16.04LTS,x86,LON,13,0,staging,29,48,5,63,17,52,60,49,93,1,2016-01-01T00:00:00+00:00
-";
-        let output = line_protocol_to_csv(Cursor::new(input), Vec::new());
-        assert_eq!(expect, String::from_utf8(output).unwrap());
+impl<W: Write> LineWriter<W> {
+    pub(crate) fn new(
+        output: W,
+        timestamp: String,
+        timestamp_parser: TimestampParser,
+        tags: HashSet<String>,
+        table_prefix: String,
+    ) -> Result<LineWriter<W>> {
+        LineWriter::with_capacity(
+            DEFAULT_BUFFER_SIZE,
+            output,
+            timestamp,
+            timestamp_parser,
+            tags,
+            table_prefix,
+        )
+    }
+
+    /// Returns the writer with a buffer of `capacity` bytes.
+    pub(crate) fn with_capacity(
+        capacity: usize,
+        output: W,
+        timestamp: String,
+        timestamp_parser: TimestampParser,
+        tags: HashSet<String>,
+        table_prefix: String,
+    ) -> Result<LineWriter<W>> {
+        Ok(LineWriter {
+            writer: BufWriter::with_capacity(capacity, output),
+            flush_lines: None,
+            points_written: 0,
+            bytes_written: 0,
+            timestamp,
+            timestamp_parser,
+            tags,
+            table_prefix,
+            types: HashMap::new(),
+            infer_types: false,
+            fields: None,
+            renames: HashMap::new(),
+            null_policy: NullPolicy::SkipField,
+            skipped_rows: 0,
+            dialect: CsvDialect::default(),
+            columns: Vec::new(),
+            measurement_column: None,
+            long_format: None,
+            mappings: None,
+            line: String::new(),
+        })
+    }
+
+    /// Flush buffered lines to the output.
+    pub(crate) fn flush(&mut self) -> Result<()> {
+        self.writer.flush().context(WriteOutputSnafu)
+    }
+
+    /// Count a skipped row.
+    fn skip_row(&mut self) {
+        self.skipped_rows += 1;
+        progress::add_lines_skipped(1);
+    }
+
+    /// Write a row as a line.
+    ///
+    /// The timestamp column is skipped, the timestamp should be in nanosecond. A row with
+    /// a float field that is NaN or infinite is invalid, as line protocol has no such values.
+    fn write_row(
+        &mut self,
+        table_name: &str,
+        columns: &[(&str, ColumnValue)],
+        timestamp: Option<i64>,
+    ) -> Result<()> {
+        for (name, value) in columns {
+            if let ColumnValue::Float64(v) = value {
+                ensure!(
+                    v.is_finite() || !self.is_field(name),
+                    InvalidPointSnafu {
+                        reason: format!("field {name} is {v}"),
+                    }
+                );
+            }
+        }
+        // Take the buffer to check columns while building the line.
+        let mut line = std::mem::take(&mut self.line);
+        line.clear();
+        // Push measurement.
+        escape(&mut line, &self.table_prefix, MEASUREMENT_ESCAPES);
+        escape(&mut line, table_name, MEASUREMENT_ESCAPES);
+        if !self.tags.is_empty() {
+            // Push tags, empty tag values are invalid.
+            for (name, value) in columns {
+                if self.tags.contains(*name)
+                    && !matches!(
+                        value,
+                        ColumnValue::Null | ColumnValue::Text("") | ColumnValue::String("")
+                    )
+                {
+                    let name = self.renames.get(*name).map_or(*name, String::as_str);
+                    line.push(',');
+                    escape(&mut line, name, KEY_ESCAPES);
+                    line.push('=');
+                    value.write_tag(&mut line);
+                }
+            }
+        }
+        // Push fields.
+        let mut first_field = true;
+        for (name, value) in columns {
+            // Skip empty field.
+            if value.is_empty() {
+                continue;
+            }
+            if self.is_field(name) {
+                if first_field {
+                    line += " ";
+                    first_field = false;
+                } else {
+                    line += ","
+                }
+                let name = self.renames.get(*name).map_or(*name, String::as_str);
+                escape(&mut line, name, KEY_ESCAPES);
+                line.push('=');
+                value.write_field(&mut line);
+            }
+        }
+        // A line without fields is invalid.
+        if first_field {
+            debug!("Skipped row without fields of {table_name}");
+            self.skip_row();
+            self.line = line;
+            return Ok(());
+        }
+        // Push timestamp.
+        if let Some(ts) = timestamp {
+            write!(line, " {ts}").unwrap();
+        }
+        line += "\n";
+
+        // Write line.
+        let result = self.write_line(&line);
+        self.line = line;
+        result
+    }
+
+    /// Write the line with the trailing newline, and flush the output periodically.
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        self.writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+        self.points_written += 1;
+        self.bytes_written += line.len() as u64;
+        match self.flush_lines {
+            Some(n) if self.points_written.is_multiple_of(n) => self.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the column is a field, which is not a tag or the timestamp.
+    fn is_field(&self, name: &str) -> bool {
+        !self.tags.contains(name)
+            && name != self.timestamp
+            && self.measurement_column.as_deref() != Some(name)
+            && self
+                .fields
+                .as_ref()
+                .is_none_or(|fields| fields.contains(name))
+    }
+
+    fn convert_csv_file(
+        &mut self,
+        path: &Path,
+        compression: Compression,
+        table_name: &str,
+    ) -> Result<()> {
+        if self.infer_types {
+            let input = ReplayableInput::open(path, compression)?;
+            let inferred = infer_column_types(self.dialect.reader(input.reader()?), path)?;
+            let reader = self.dialect.reader(input.reader()?);
+            self.convert_csv(reader, path, &inferred, table_name)
+        } else {
+            let reader = self.dialect.reader(open_input(path, compression)?);
+            self.convert_csv(reader, path, &[], table_name)
+        }
+    }
+
+    /// Convert records of the CSV reader, the path is the input in errors.
+    pub(crate) fn convert_csv<R: Read>(
+        &mut self,
+        mut reader: Reader<R>,
+        path: &Path,
+        inferred: &[Option<FieldType>],
+        table_name: &str,
+    ) -> Result<()> {
+        let headers: Vec<_> = if self.dialect.no_header {
+            self.columns.clone()
+        } else {
+            reader
+                .headers()
+                .context(ReadCsvSnafu { path })?
+                .iter()
+                .map(|v| v.to_string())
+                .collect()
+        };
+        let measurement_idx = match &self.measurement_column {
+            Some(column) => Some(
+                headers
+                    .iter()
+                    .position(|name| name == column)
+                    .context(MissingColumnSnafu { path, column })?,
+            ),
+            None => None,
+        };
+        // Explicit types take precedence over inferred types.
+        let types: Vec<_> = headers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                self.types
+                    .get(name)
+                    .copied()
+                    .or_else(|| inferred.get(i).copied().flatten())
+            })
+            .collect();
+        let mapping = self.mapping(path, table_name, &headers, &types);
+        if let Some(mappings) = &mut self.mappings {
+            mappings.push(mapping);
+        }
+
+        if let Some((name_column, value_column)) = &self.long_format {
+            let position = |column: &String| {
+                headers
+                    .iter()
+                    .position(|name| name == column)
+                    .context(MissingColumnSnafu { path, column })
+            };
+            let columns = LongColumns {
+                name: position(name_column)?,
+                value: position(value_column)?,
+                measurement: measurement_idx,
+            };
+            let value_type = types[columns.value];
+            return self
+                .convert_long_records(reader, path, &headers, table_name, columns, value_type);
+        }
+
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            let parsed = self.parse_record(path, &record, &headers, &types);
+            let Some(row) = reject::check(parsed, || self.record_text(&record))? else {
+                continue;
+            };
+            if row.skip {
+                debug!(
+                    "Skipped row with empty fields at {}:{}",
+                    path.display(),
+                    row.line
+                );
+                self.skip_row();
+                continue;
+            }
+            let measurement = match measurement_idx.and_then(|i| record.get(i)) {
+                Some(measurement) if !measurement.is_empty() => measurement,
+                _ => table_name,
+            };
+            ensure!(!measurement.is_empty(), MissingMeasurementSnafu);
+            self.write_row(measurement, &row.columns, row.timestamp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the mapping of columns of the file.
+    fn mapping(
+        &self,
+        path: &Path,
+        table_name: &str,
+        headers: &[String],
+        types: &[Option<FieldType>],
+    ) -> FileMapping {
+        let measurement = match (&self.measurement_column, table_name) {
+            (Some(column), "") => format!("column {column}"),
+            (Some(column), name) => format!("column {column}, defaults to {name}"),
+            (None, name) => name.to_string(),
+        };
+        let type_name = |field_type: Option<FieldType>| {
+            field_type.map_or("auto".to_string(), |t| t.to_string())
+        };
+        let fields = match &self.long_format {
+            Some((name_column, value_column)) => {
+                let value_type = headers.iter().position(|name| name == value_column);
+                vec![format!(
+                    "names in column {name_column}, values in column {value_column} ({})",
+                    type_name(value_type.and_then(|i| types[i]))
+                )]
+            }
+            None => headers
+                .iter()
+                .zip(types)
+                .filter(|(name, _)| self.is_field(name))
+                .map(|(name, field_type)| format!("{name} ({})", type_name(*field_type)))
+                .collect(),
+        };
+        let mut missing_tags: Vec<_> = self
+            .tags
+            .iter()
+            .filter(|tag| !headers.contains(tag))
+            .cloned()
+            .collect();
+        missing_tags.sort_unstable();
+        FileMapping {
+            path: path.to_path_buf(),
+            measurement,
+            timestamp: headers
+                .contains(&self.timestamp)
+                .then(|| self.timestamp.clone()),
+            tags: headers
+                .iter()
+                .filter(|name| self.tags.contains(*name))
+                .cloned()
+                .collect(),
+            missing_tags,
+            fields,
+        }
+    }
+
+    /// Parse the timestamp and values of columns of a record.
+    fn parse_record<'a>(
+        &self,
+        path: &Path,
+        record: &'a StringRecord,
+        headers: &'a [String],
+        types: &[Option<FieldType>],
+    ) -> Result<Row<'a>> {
+        let mut row = Row {
+            line: record.position().map(|p| p.line()).unwrap_or_default(),
+            timestamp: None,
+            columns: Vec::with_capacity(headers.len()),
+            skip: false,
+        };
+        for ((name, value), field_type) in headers.iter().zip(record.iter()).zip(types) {
+            if row.timestamp.is_none() && *name == self.timestamp {
+                row.timestamp = Some(self.parse_timestamp(path, row.line, name, value)?);
+            }
+            let value = if self.is_field(name) {
+                row.skip |= value.is_empty() && self.null_policy == NullPolicy::SkipRow;
+                self.field_value(path, row.line, name, value, *field_type)?
+            } else {
+                // Tags and the timestamp keep their text.
+                ColumnValue::Text(value)
+            };
+            row.columns.push((name.as_str(), value));
+        }
+        Ok(row)
+    }
+
+    /// Returns the text of the record in the dialect of inputs.
+    fn record_text(&self, record: &StringRecord) -> String {
+        let mut writer = self.dialect.writer_builder().from_writer(Vec::new());
+        let _ = writer.write_record(record);
+        writer
+            .into_inner()
+            .map(|text| String::from_utf8_lossy(&text).into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Convert records of long format CSV, in which each row is a field of a point.
+    ///
+    /// Adjacent rows of the same measurement, tags and timestamp are merged into a line.
+    fn convert_long_records<R: Read>(
+        &mut self,
+        mut reader: Reader<R>,
+        path: &Path,
+        headers: &[String],
+        table_name: &str,
+        columns: LongColumns,
+        value_type: Option<FieldType>,
+    ) -> Result<()> {
+        let mut point: Option<LongPoint> = None;
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let measurement = match columns.measurement.and_then(|i| record.get(i)) {
+                Some(measurement) if !measurement.is_empty() => measurement,
+                _ => table_name,
+            };
+            ensure!(!measurement.is_empty(), MissingMeasurementSnafu);
+
+            let parsed = self.parse_point_key(path, &record, headers, measurement, line);
+            let Some(key) = reject::check(parsed, || self.record_text(&record))? else {
+                continue;
+            };
+            let field = (
+                record.get(columns.name).unwrap_or_default().to_string(),
+                record.get(columns.value).unwrap_or_default().to_string(),
+            );
+
+            match &mut point {
+                Some(point) if point.key == key => point.fields.push(field),
+                _ => {
+                    let next = LongPoint {
+                        key,
+                        fields: vec![field],
+                        line,
+                    };
+                    if let Some(point) = point.replace(next) {
+                        self.write_long_point(path, &point, value_type)?;
+                    }
+                }
+            }
+        }
+        if let Some(point) = point {
+            self.write_long_point(path, &point, value_type)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the key of the point a long format record belongs to.
+    fn parse_point_key(
+        &self,
+        path: &Path,
+        record: &StringRecord,
+        headers: &[String],
+        measurement: &str,
+        line: u64,
+    ) -> Result<PointKey> {
+        let mut key = PointKey {
+            measurement: measurement.to_string(),
+            tags: Vec::new(),
+            timestamp: None,
+        };
+        for (name, value) in headers.iter().zip(record.iter()) {
+            if key.timestamp.is_none() && *name == self.timestamp {
+                key.timestamp = Some(self.parse_timestamp(path, line, name, value)?);
+            } else if self.tags.contains(name) {
+                key.tags.push((name.clone(), value.to_string()));
+            }
+        }
+        Ok(key)
+    }
+
+    /// Write a point of long format CSV, an invalid point is rejected without its text.
+    fn write_long_point(
+        &mut self,
+        path: &Path,
+        point: &LongPoint,
+        value_type: Option<FieldType>,
+    ) -> Result<()> {
+        let result = self.write_point(path, point, value_type);
+        reject::check(result, String::new).map(|_| ())
+    }
+
+    /// Write a point of long format CSV, fields are typed by their names.
+    fn write_point(
+        &mut self,
+        path: &Path,
+        point: &LongPoint,
+        value_type: Option<FieldType>,
+    ) -> Result<()> {
+        let mut columns = Vec::with_capacity(point.key.tags.len() + point.fields.len());
+        for (name, value) in &point.key.tags {
+            columns.push((name.as_str(), ColumnValue::Text(value)));
+        }
+        for (name, value) in point.fields.iter().filter(|(name, _)| !name.is_empty()) {
+            if value.is_empty() && self.null_policy == NullPolicy::SkipRow {
+                debug!(
+                    "Skipped point with empty fields at {}:{}",
+                    path.display(),
+                    point.line
+                );
+                self.skip_row();
+                return Ok(());
+            }
+            let field_type = self.types.get(name).copied().or(value_type);
+            let value = self.field_value(path, point.line, name, value, field_type)?;
+            columns.push((name.as_str(), value));
+        }
+        self.write_row(&point.key.measurement, &columns, point.key.timestamp)
+    }
+
+    fn parse_timestamp(&self, path: &Path, line: u64, column: &str, value: &str) -> Result<i64> {
+        self.timestamp_parser
+            .parse(value)
+            .with_context(|| InvalidTimestampSnafu {
+                path,
+                line,
+                column,
+                value,
+            })
+    }
+
+    /// Returns the value of a field, empty values are written by the null policy.
+    fn field_value<'a>(
+        &self,
+        path: &Path,
+        line: u64,
+        column: &str,
+        value: &'a str,
+        field_type: Option<FieldType>,
+    ) -> Result<ColumnValue<'a>> {
+        if value.is_empty() {
+            return Ok(match self.null_policy {
+                NullPolicy::SkipField | NullPolicy::SkipRow => ColumnValue::Null,
+                NullPolicy::EmptyString => ColumnValue::String(""),
+                NullPolicy::Zero => ColumnValue::zero(field_type),
+            });
+        }
+        let Some(field_type) = field_type else {
+            return Ok(ColumnValue::Text(value));
+        };
+        ColumnValue::parse(value, field_type).with_context(|| InvalidColumnValueSnafu {
+            path,
+            line,
+            column,
+            value,
+            field_type: field_type.to_string(),
+        })
+    }
+}
+
+/// Parsed columns of a CSV row.
+#[derive(Debug)]
+struct Row<'a> {
+    line: u64,
+    timestamp: Option<i64>,
+    columns: Vec<(&'a str, ColumnValue<'a>)>,
+    /// Whether the row is skipped by the null policy.
+    skip: bool,
+}
+
+/// Mapping of columns of a CSV file to line protocol, reported by dry runs.
+#[derive(Debug)]
+struct FileMapping {
+    path: PathBuf,
+    measurement: String,
+    /// Timestamp column, `None` if it is missing.
+    timestamp: Option<String>,
+    tags: Vec<String>,
+    /// Tags that are not columns of the file.
+    missing_tags: Vec<String>,
+    /// Fields with their types.
+    fields: Vec<String>,
+}
+
+impl fmt::Display for FileMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "File: {}", self.path.display())?;
+        writeln!(f, "  Measurement: {}", self.measurement)?;
+        match &self.timestamp {
+            Some(column) => writeln!(f, "  Timestamp: {column}")?,
+            None => writeln!(f, "  Timestamp: missing, lines have no timestamps")?,
+        }
+        writeln!(f, "  Tags: {}", self.tags.join(", "))?;
+        if !self.missing_tags.is_empty() {
+            writeln!(f, "  Missing tags: {}", self.missing_tags.join(", "))?;
+        }
+        writeln!(f, "  Fields: {}", self.fields.join(", "))
+    }
+}
+
+/// Indices of columns of long format CSV.
+#[derive(Debug, Clone, Copy)]
+struct LongColumns {
+    /// Column of field names.
+    name: usize,
+    /// Column of field values.
+    value: usize,
+    /// Column of measurement names.
+    measurement: Option<usize>,
+}
+
+/// Key of a point of long format CSV.
+#[derive(Debug, PartialEq, Eq)]
+struct PointKey {
+    measurement: String,
+    tags: Vec<(String, String)>,
+    timestamp: Option<i64>,
+}
+
+/// A point of long format CSV with names and values of fields.
+#[derive(Debug)]
+struct LongPoint {
+    key: PointKey,
+    fields: Vec<(String, String)>,
+    /// Line of the first row for errors.
+    line: u64,
+}
+
+/// Value of a field or a tag.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum Value {
+    Int64(i64),
+    UInt64(u64),
+    Float64(f64),
+    String(String),
+    Boolean(bool),
+}
+
+impl From<EscapedStr<'_>> for Value {
+    fn from(value: EscapedStr) -> Value {
+        Value::String(value.into())
+    }
+}
+
+impl From<FieldValue<'_>> for Value {
+    fn from(value: FieldValue) -> Value {
+        match value {
+            FieldValue::I64(v) => Value::Int64(v),
+            FieldValue::U64(v) => Value::UInt64(v),
+            FieldValue::F64(v) => Value::Float64(v),
+            FieldValue::String(v) => Value::String(v.into()),
+            FieldValue::Boolean(v) => Value::Boolean(v),
+        }
+    }
+}
+
+/// Name of the measurement column in CSV output.
+const CSV_MEASUREMENT_COLUMN: &str = "measurement";
+/// Name of the timestamp column in CSV output.
+const CSV_TIMESTAMP_COLUMN: &str = "timestamp";
+
+/// Convert line protocol to CSV.
+///
+/// The header contains the measurement, tags and fields in the schema, followed by
+/// the timestamp.
+/// Each value is written into the column of its key, missing keys are left empty.
+/// Lines are converted by a pool of threads if there are more than one thread.
+pub(crate) fn line_protocol_to_csv<R: Read + Send, W: Write>(
+    schema: &Schema,
+    source: R,
+    dest: W,
+    dialect: &CsvDialect,
+    time_format: TimeFormat,
+    threads: usize,
+    block_size: usize,
+) -> Result<W> {
+    let mut writer = csv_writer(schema, dest, dialect)?;
+    if threads > 1 {
+        let dest = writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)?;
+        return pipeline::convert_lines(source, dest, threads, block_size, |lines, buffer| {
+            let mut writer = dialect.writer_builder().from_writer(buffer);
+            let mut row = CsvRow::default();
+            lines
+                .for_each(|line| write_csv_row(schema, line, time_format, &mut row, &mut writer))?;
+            writer.flush().context(WriteOutputSnafu)
+        });
+    }
+
+    let mut row = CsvRow::default();
+    read_blocks_while(source, block_size, |_, line| {
+        write_csv_row(schema, line, time_format, &mut row, &mut writer).map(|()| true)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Convert line protocol of the text to CSV of the columns like `line_protocol_to_csv`,
+/// lines are parsed from slices of the text.
+pub(crate) fn text_to_csv<W: Write>(
+    schema: &Schema,
+    text: &str,
+    dest: W,
+    dialect: &CsvDialect,
+    time_format: TimeFormat,
+) -> Result<W> {
+    let mut writer = csv_writer(schema, dest, dialect)?;
+    let mut row = CsvRow::default();
+    read_text_lines(text, |_, line| {
+        write_csv_row(schema, line, time_format, &mut row, &mut writer)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Returns the CSV writer of the dialect, which has written the header of the schema
+/// unless the dialect has no header.
+fn csv_writer<W: Write>(schema: &Schema, dest: W, dialect: &CsvDialect) -> Result<Writer<W>> {
+    let mut writer = dialect.writer_builder().from_writer(dest);
+    if !dialect.no_header {
+        writer
+            .write_record(csv_headers(schema))
+            .context(WriteCsvSnafu)?;
+    }
+    Ok(writer)
+}
+
+/// Returns the header of CSV converted from line protocol of the schema.
+pub(crate) fn csv_headers(schema: &Schema) -> impl Iterator<Item = &str> {
+    [CSV_MEASUREMENT_COLUMN]
+        .into_iter()
+        .chain(schema.tags.iter().map(|name| name.as_str()))
+        .chain(schema.fields.iter().map(|(name, _)| name.as_str()))
+        .chain([CSV_TIMESTAMP_COLUMN])
+}
+
+/// Reusable buffers of CSV rows converted from line protocol.
+///
+/// Values are written from the borrowed line into the record of the CSV writer, so no
+/// value is allocated for each row.
+#[derive(Debug, Default)]
+pub(crate) struct CsvRow {
+    /// Tag or field of the line in each tag and field column.
+    slots: Vec<Slot>,
+    /// Buffer to format timestamps.
+    buffer: String,
+}
+
+/// Slot of a tag or field column of a row, which indexes into the line.
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Empty,
+    Tag(usize),
+    Field(usize),
+}
+
+/// Write the line as a CSV row.
+///
+/// Tags and fields dropped by the schema are skipped.
+pub(crate) fn write_csv_row<W: Write>(
+    schema: &Schema,
+    line: ParsedLine<'_>,
+    time_format: TimeFormat,
+    row: &mut CsvRow,
+    writer: &mut Writer<W>,
+) -> Result<()> {
+    row.slots.clear();
+    row.slots
+        .resize(schema.tags.len() + schema.fields.len(), Slot::Empty);
+
+    let tag_set = line.series.tag_set.as_deref().unwrap_or_default();
+    for (i, (tagk, _)) in tag_set.iter().enumerate() {
+        if schema.is_dropped(tagk.as_str()) {
+            continue;
+        }
+        let idx = schema
+            .tag_index(tagk.as_str())
+            .context(ColumnNotInSchemaSnafu {
+                column: tagk.as_str(),
+            })?;
+        row.slots[idx] = Slot::Tag(i);
+    }
+    for (i, (fieldk, _)) in line.field_set.iter().enumerate() {
+        if schema.is_dropped(fieldk.as_str()) {
+            continue;
+        }
+        let idx = schema
+            .field_index(fieldk.as_str())
+            .context(ColumnNotInSchemaSnafu {
+                column: fieldk.as_str(),
+            })?;
+        row.slots[schema.tags.len() + idx] = Slot::Field(i);
+    }
+
+    writer
+        .write_field(line.series.measurement.as_str())
+        .context(WriteCsvSnafu)?;
+    for slot in &row.slots {
+        match *slot {
+            Slot::Empty => writer.write_field(""),
+            Slot::Tag(i) => writer.write_field(tag_set[i].1.as_str()),
+            Slot::Field(i) => write_csv_field(&line.field_set[i].1, writer),
+        }
+        .context(WriteCsvSnafu)?;
+    }
+    row.buffer.clear();
+    if let Some(timestamp) = line.timestamp {
+        time_format.write(timestamp, &mut row.buffer);
+    }
+    writer
+        .write_field(&row.buffer)
+        .and_then(|()| writer.write_record(None::<&[u8]>))
+        .context(WriteCsvSnafu)
+}
+
+/// Write the field value like serializing a [Value], numbers are formatted on the stack.
+fn write_csv_field<W: Write>(value: &FieldValue<'_>, writer: &mut Writer<W>) -> csv::Result<()> {
+    match value {
+        FieldValue::I64(v) => writer.write_field(itoa::Buffer::new().format(*v)),
+        FieldValue::U64(v) => writer.write_field(itoa::Buffer::new().format(*v)),
+        FieldValue::F64(v) => writer.write_field(ryu::Buffer::new().format(*v)),
+        FieldValue::String(v) => writer.write_field(v.as_str()),
+        FieldValue::Boolean(v) => writer.write_field(if *v { "true" } else { "false" }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Cursor;
+    use tempfile::{Builder, NamedTempFile};
+
+    #[test]
+    fn test_line_protocol_to_csv() {
+        let input = "cpu,hostname=host_0,region=eu-central-1,datacenter=eu-central-1a,rack=6,os=Ubuntu15.10,arch=x86,team=SF,service=19,service_version=1,service_environment=test usage_user=58i,usage_system=2i,usage_idle=24i,usage_nice=61i,usage_iowait=22i,usage_irq=63i,usage_softirq=6i,usage_steal=44i,usage_guest=80i,usage_guest_nice=38i 1451606400000000000
+cpu,hostname=host_1,region=us-west-1,datacenter=us-west-1a,rack=41,os=Ubuntu15.10,arch=x64,team=NYC,service=9,service_version=1,service_environment=staging usage_user=84i,usage_system=11i,usage_idle=53i,usage_nice=87i,usage_iowait=29i,usage_irq=20i,usage_softirq=54i,usage_steal=77i,usage_guest=53i,usage_guest_nice=74i 1451606400000000000
+cpu,hostname=host_2,region=sa-east-1,datacenter=sa-east-1a,rack=89,os=Ubuntu16.04LTS,arch=x86,team=LON,service=13,service_version=0,service_environment=staging usage_user=29i,usage_system=48i,usage_idle=5i,usage_nice=63i,usage_iowait=17i,usage_irq=52i,usage_softirq=60i,usage_steal=49i,usage_guest=93i,usage_guest_nice=1i 1451606400000000000";
+        let expect = "measurement,hostname,region,datacenter,rack,os,arch,team,service,service_version,service_environment,usage_user,usage_system,usage_idle,usage_nice,usage_iowait,usage_irq,usage_softirq,usage_steal,usage_guest,usage_guest_nice,timestamp
+cpu,host_0,eu-central-1,eu-central-1a,6,Ubuntu15.10,x86,SF,19,1,test,58,2,24,61,22,63,6,44,80,38,2016-01-01T00:00:00.000000000+00:00
+cpu,host_1,us-west-1,us-west-1a,41,Ubuntu15.10,x64,NYC,9,1,staging,84,11,53,87,29,20,54,77,53,74,2016-01-01T00:00:00.000000000+00:00
+cpu,host_2,sa-east-1,sa-east-1a,89,Ubuntu16.04LTS,x86,LON,13,0,staging,29,48,5,63,17,52,60,49,93,1,2016-01-01T00:00:00.000000000+00:00
+";
+        let schema = Schema::infer(Cursor::new(input)).unwrap();
+        let output = line_protocol_to_csv(
+            &schema,
+            Cursor::new(input),
+            Vec::new(),
+            &CsvDialect::default(),
+            TimeFormat::Rfc3339,
+            1,
+            DEFAULT_BLOCK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+        let output = line_protocol_to_csv(
+            &schema,
+            Cursor::new(input),
+            Vec::new(),
+            &CsvDialect::default(),
+            TimeFormat::Rfc3339,
+            4,
+            DEFAULT_BLOCK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+        let output = text_to_csv(
+            &schema,
+            input,
+            Vec::new(),
+            &CsvDialect::default(),
+            TimeFormat::Rfc3339,
+        )
+        .unwrap();
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn test_line_protocol_to_csv_new_keys() {
+        let input = "cpu,hostname=host_0 usage_user=58i 1451606400000000000
+
+cpu,region=us-west-1,hostname=host_1 usage_user=84i,usage_idle=0.5
+mem,hostname=host_2 usage_idle=1.5,up=true 1451606400000000000
+";
+        let expect = "measurement,hostname,region,usage_user,usage_idle,up,timestamp
+cpu,host_0,,58,,,2016-01-01T00:00:00.000000000+00:00
+cpu,host_1,us-west-1,84,0.5,,
+mem,host_2,,,1.5,true,2016-01-01T00:00:00.000000000+00:00
+";
+        let schema = Schema::infer(Cursor::new(input)).unwrap();
+        let output = line_protocol_to_csv(
+            &schema,
+            Cursor::new(input),
+            Vec::new(),
+            &CsvDialect::default(),
+            TimeFormat::Rfc3339,
+            1,
+            DEFAULT_BLOCK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn test_to_csv_dir() {
+        let input_dir = Builder::new().tempdir().unwrap();
+        let output_dir = Builder::new().tempdir().unwrap();
+        fs::create_dir(input_dir.path().join("sub")).unwrap();
+        for name in ["cpu.lp", "sub/mem.txt", "sub/disk.lp.gz", "notes.md"] {
+            let output = create_output(input_dir.path().join(name), Compression::Auto).unwrap();
+            let mut output = BufWriter::new(output);
+            output.write_all(b"cpu,host=a usage=1i 0\n").unwrap();
+        }
+
+        ToCsv::try_parse_from([
+            "to-csv",
+            "-i",
+            input_dir.path().to_str().unwrap(),
+            "-o",
+            output_dir.path().to_str().unwrap(),
+            "--recursive",
+            "--threads",
+            "2",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+
+        let expect =
+            "measurement,host,usage,timestamp\ncpu,a,1,1970-01-01T00:00:00.000000000+00:00\n";
+        for name in ["cpu.csv", "sub/mem.csv", "sub/disk.csv"] {
+            let csv = fs::read_to_string(output_dir.path().join(name)).unwrap();
+            assert_eq!(expect, csv);
+        }
+        assert_eq!(2, fs::read_dir(output_dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn test_to_csv_output_dir() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"cpu,host=a usage=1i 1\nmem,host=a used=2i,free=3i 1\ncpu,host=b,dc=x usage=4i 2\n")
+            .unwrap();
+        let output_dir = Builder::new().tempdir().unwrap();
+        ToCsv::try_parse_from([
+            "to-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "--output-dir",
+            output_dir.path().join("out").to_str().unwrap(),
+            "--time-format",
+            "ns",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+
+        let read = |name| fs::read_to_string(output_dir.path().join("out").join(name)).unwrap();
+        assert_eq!(
+            "measurement,host,dc,usage,timestamp\ncpu,a,,1,1\ncpu,b,x,4,2\n",
+            read("cpu.csv")
+        );
+        assert_eq!(
+            "measurement,host,used,free,timestamp\nmem,a,2,3,1\n",
+            read("mem.csv")
+        );
+    }
+
+    #[test]
+    fn test_to_csv_conflicting_types() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"cpu value=1i 1\nlog value=\"x\" 2\n")
+            .unwrap();
+        let output_file = NamedTempFile::new().unwrap();
+        ToCsv::try_parse_from([
+            "to-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--time-format",
+            "ns",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+
+        // CSV has no column types, so the field is an integer and a string in rows.
+        assert_eq!(
+            "measurement,value,timestamp\ncpu,1,1\nlog,x,2\n",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_csv_columns() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(
+                b"cpu,region=eu,host=a usage=1i 1
+cpu,host=b idle=2i,usage=3i 2
+",
+            )
+            .unwrap();
+        let output_file = NamedTempFile::new().unwrap();
+        let (input, output) = (
+            input_file.path().to_str().unwrap(),
+            output_file.path().to_str().unwrap(),
+        );
+        let to_csv = |fields: &[&str]| {
+            let mut args = vec!["to-csv", "-i", input, "-o", output, "--time-format", "ns"];
+            args.extend(["--tag", "host", "--tag", "region"]);
+            for &field in fields {
+                args.extend(["--field", field]);
+            }
+            ToCsv::try_parse_from(args).unwrap()
+        };
+        to_csv(&["usage", "idle"]).run().unwrap();
+        let expect = "measurement,host,region,usage,idle,timestamp\ncpu,a,eu,1,,1\ncpu,b,,3,2,2\n";
+        assert_eq!(expect, fs::read_to_string(output_file.path()).unwrap());
+
+        let err = to_csv(&["usage"]).run().unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+
+    #[test]
+    fn test_select_and_rename() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"cpu,host=a,id=1 usage=1i,idle=2i 1\ncpu,host=b usage=3i 2\n")
+            .unwrap();
+        let output_file = NamedTempFile::new().unwrap();
+        let (input, output) = (
+            input_file.path().to_str().unwrap(),
+            output_file.path().to_str().unwrap(),
+        );
+        let args = format!(
+            "to-csv -i {input} -o {output} --time-format ns \
+             --select host,usage --rename host=hostname"
+        );
+        let to_csv = ToCsv::try_parse_from(args.split(' ')).unwrap();
+        to_csv.run().unwrap();
+        assert_eq!(
+            "measurement,hostname,usage,timestamp\ncpu,a,1,1\ncpu,b,3,2\n",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+
+        fs::write(input_file.path(), "host,dc,usage,idle,ts\na,x,1,2,1\n").unwrap();
+        let args = format!(
+            "from-csv -i {input} -o {output} --measurement cpu --timestamp ts --precision ns \
+             --tag host --tag dc --select host,usage --rename usage=usage_user"
+        );
+        let from_csv = FromCsv::try_parse_from(args.split(' ')).unwrap();
+        from_csv.run().unwrap();
+        assert_eq!(
+            "cpu,host=a usage_user=1 1\n",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
     }
 
     #[test]
@@ -273,28 +2576,558 @@ host_2,sa-east-1,sa-east-1a,89,Ubuntu16.04LTS,x86,LON,13,0,staging,29,48,5,63,17
         let input_dir_path = input_dir.path().to_str().unwrap();
         {
             let mut csv1 = File::create(format!("{input_dir_path}/metric1.csv")).unwrap();
-            csv1.write(b"hostname,timestamp,usage_user,usage_system\nhost_0,1451606400000,58,2\n")
-                .unwrap();
+            csv1.write_all(
+                b"hostname,timestamp,usage_user,usage_system\nhost_0,1451606400000,58,2\n",
+            )
+            .unwrap();
         }
         {
             let mut csv2 = File::create(format!("{input_dir_path}/metric2.csv")).unwrap();
-            csv2.write(
+            csv2.write_all(
                 b"region,usage_user,usage_system,timestamp\neu-central-1,52,13,1451606400000\n",
             )
             .unwrap();
         }
 
-        let from_csv = FromCsv {
-            input: input_dir_path.to_string(),
-            output: output_file.path().to_str().unwrap().to_string(),
-            timestamp: "timestamp".to_string(),
-            tag: vec!["hostname".to_string(), "region".to_string()],
-            table_prefix: "test_".to_string(),
-        };
-        from_csv.run();
+        FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_dir_path,
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--precision",
+            "ms",
+            "--tag",
+            "hostname",
+            "--tag",
+            "region",
+            "--table-prefix",
+            "test_",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
 
         let mut lines = String::new();
         output_file.as_file().read_to_string(&mut lines).unwrap();
         assert_eq!(lines, "test_metric1,hostname=host_0 usage_user=58,usage_system=2 1451606400000000000\ntest_metric2,region=eu-central-1 usage_user=52,usage_system=13 1451606400000000000\n");
     }
+
+    #[test]
+    fn test_from_csv_precision() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        fs::write(input_file.path(), "host,usage,timestamp\na,1,86400000\n").unwrap();
+        let (input, output) = (
+            input_file.path().to_str().unwrap(),
+            output_file.path().to_str().unwrap(),
+        );
+        let from_csv = |args: &[&str]| {
+            let mut from_csv_args = vec!["from-csv", "-i", input, "-o", output];
+            from_csv_args.extend(["--measurement", "cpu", "--tag", "host"]);
+            from_csv_args.extend(args);
+            FromCsv::try_parse_from(from_csv_args)
+                .unwrap()
+                .run()
+                .unwrap();
+            fs::read_to_string(output_file.path()).unwrap()
+        };
+
+        // Timestamps are milliseconds by default, even if they look like seconds.
+        assert_eq!("cpu,host=a usage=1 86400000000000\n", from_csv(&[]));
+        assert_eq!(
+            "cpu,host=a usage=1 86400000000000000\n",
+            from_csv(&["--precision", "auto"])
+        );
+    }
+
+    #[test]
+    fn test_from_csv_watch() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_dir = Builder::new().tempdir().unwrap();
+        let from_csv = FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_dir.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--precision",
+            "ns",
+            "--tag",
+            "host",
+            "--watch",
+            "--poll-interval",
+            "10",
+        ])
+        .unwrap();
+        let schema = CsvSchema::default();
+        let output = create_output(&from_csv.output, Compression::None).unwrap();
+        let mut writer = from_csv.line_writer(output).unwrap();
+        let mut watcher = DirWatcher::new(input_dir.path(), from_csv.watch.interval()).unwrap();
+        let mut convert_new_files = |expect: &str| {
+            // Files are ready at the second poll, after their sizes are unchanged.
+            assert!(watcher.poll().unwrap().is_empty());
+            let files = watcher.poll().unwrap();
+            from_csv
+                .convert_files(&mut writer, &schema, &files)
+                .unwrap();
+            assert_eq!(expect, fs::read_to_string(output_file.path()).unwrap());
+        };
+
+        fs::write(
+            input_dir.path().join("cpu.csv"),
+            "host,usage,timestamp\na,1,1\n",
+        )
+        .unwrap();
+        convert_new_files("cpu,host=a usage=1 1\n");
+        fs::write(
+            input_dir.path().join("mem.csv"),
+            "host,used,timestamp\nb,2,2\n",
+        )
+        .unwrap();
+        convert_new_files("cpu,host=a usage=1 1\nmem,host=b used=2 2\n");
+    }
+
+    #[test]
+    fn test_from_csv_dry_run() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"host,usage,name\na,1,x\nb,,\n")
+            .unwrap();
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let tags = HashSet::from(["host".to_string(), "region".to_string()]);
+        let mut writer = LineWriter::new(
+            Box::new(StatsWriter::new(stats.clone())),
+            "ts".to_string(),
+            TimestampParser::new(Precision::Ns, None),
+            tags,
+            String::new(),
+        )
+        .unwrap();
+        writer.types = HashMap::from([("usage".to_string(), FieldType::Int64)]);
+        writer.mappings = Some(Vec::new());
+        writer
+            .convert_csv_file(input_file.path(), Compression::None, "cpu")
+            .unwrap();
+        writer.flush().unwrap();
+
+        let mapping = &writer.mappings.as_ref().unwrap()[0];
+        assert_eq!(
+            format!(
+                "File: {}
+  Measurement: cpu
+  Timestamp: missing, lines have no timestamps
+  Tags: host
+  Missing tags: region
+  Fields: usage (integer), name (auto)
+",
+                input_file.path().display()
+            ),
+            mapping.to_string()
+        );
+        assert_eq!(1, stats.lock().unwrap().points());
+        assert_eq!(1, writer.skipped_rows);
+    }
+
+    #[test]
+    fn test_from_csv_types() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(
+                b"host,usage,count,ratio,enabled,name,ts\n\
+                1,58,3,1,true,a,1\n\
+                2,,4,0.5,FALSE,2,2\n",
+            )
+            .unwrap();
+
+        let (input, output) = (
+            input_file.path().to_str().unwrap(),
+            output_file.path().to_str().unwrap(),
+        );
+        let from_csv = |args: &[&str]| {
+            let mut from_csv_args = vec!["from-csv", "-i", input, "-o", output];
+            from_csv_args.extend(["--measurement", "cpu", "--timestamp", "ts"]);
+            from_csv_args.extend(["--precision", "ns", "--tag", "host"]);
+            from_csv_args.extend(args);
+            FromCsv::try_parse_from(from_csv_args)
+                .unwrap()
+                .run()
+                .map(|_| fs::read_to_string(output_file.path()).unwrap())
+        };
+
+        assert_eq!(
+            "cpu,host=1 usage=58i,count=3u,ratio=1,enabled=true,name=\"a\" 1
+cpu,host=2 count=4u,ratio=0.5,enabled=false,name=\"2\" 2
+",
+            from_csv(&["--types", "count=u64", "--infer-types"]).unwrap()
+        );
+
+        let err = from_csv(&["--types", "name=i64"]).unwrap_err();
+        assert_eq!(
+            format!(
+                "Invalid integer value a in column name at line 2 of {}",
+                input_file.path().display()
+            ),
+            err.to_string()
+        );
+        assert!(parse_column_type("count=i32").is_err());
+    }
+
+    #[test]
+    fn test_column_value_not_finite() {
+        assert!(matches!(
+            ColumnValue::parse("1.5", FieldType::Float64),
+            Some(ColumnValue::Float64(1.5))
+        ));
+        assert!(ColumnValue::parse("NaN", FieldType::Float64).is_none());
+        assert!(ColumnValue::parse("-inf", FieldType::Float64).is_none());
+
+        // Untyped values that are not finite are strings.
+        let mut line = String::new();
+        ColumnValue::Text("NaN").write_field(&mut line);
+        assert_eq!("\"NaN\"", line);
+    }
+
+    #[test]
+    fn test_from_csv_schema() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_dir = Builder::new().tempdir().unwrap();
+        fs::write(
+            input_dir.path().join("cpu_usage.csv"),
+            "hostname,time,usage_user,note\nhost_0,2016-01-01 00:00:00,58,x\n",
+        )
+        .unwrap();
+        fs::write(
+            input_dir.path().join("mem.csv"),
+            "host,timestamp,used\nhost_1,1451606400000,2\n",
+        )
+        .unwrap();
+        let schema_file = input_dir.path().join("schema.toml");
+        fs::write(
+            &schema_file,
+            r#"
+[measurements.cpu_usage]
+name = "cpu"
+tags = ["hostname"]
+timestamp = "time"
+timestamp_format = "%Y-%m-%d %H:%M:%S"
+fields = { usage_user = "i64" }
+rename = { hostname = "host" }
+"#,
+        )
+        .unwrap();
+
+        FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_dir.path().join("*.csv").to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--precision",
+            "ms",
+            "--tag",
+            "host",
+            "--schema",
+            schema_file.to_str().unwrap(),
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+        assert_eq!(
+            "cpu,host=host_0 usage_user=58i 1451606400000000000
+mem,host=host_1 used=2 1451606400000000000
+",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_escape() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(
+                b"host name,region,usage user,note,ts\n\
+                \"a,b=c d\",,1,\"say \"\"hi\"\" \\\",1\n\
+                h\\x,eu,2,NaN,2\n",
+            )
+            .unwrap();
+
+        FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--measurement",
+            "my cpu,1",
+            "--timestamp",
+            "ts",
+            "--precision",
+            "ns",
+            "--tag",
+            "host name",
+            "--tag",
+            "region",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+
+        let lines = fs::read_to_string(output_file.path()).unwrap();
+        assert_eq!(
+            r#"my\ cpu\,1,host\ name=a\,b\=c\ d usage\ user=1,note="say \"hi\" \\" 1
+my\ cpu\,1,host\ name=h\\x,region=eu usage\ user=2,note="NaN" 2
+"#,
+            lines
+        );
+        let parsed: Vec<_> = influxdb_line_protocol::parse_lines(&lines)
+            .map(|line| line.unwrap())
+            .collect();
+        assert_eq!("my cpu,1", parsed[0].series.measurement.as_str());
+        let tags = parsed[0].series.tag_set.as_ref().unwrap();
+        assert_eq!("a,b=c d", tags[0].1.as_str());
+        assert_eq!(
+            FieldValue::String("say \"hi\" \\".into()),
+            parsed[0].field_set[1].1
+        );
+        assert_eq!(
+            "h\\x",
+            parsed[1].series.tag_set.as_ref().unwrap()[0].1.as_str()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_null_policy() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"host,usage,count,ts\na,1,,1\nb,,,2\n")
+            .unwrap();
+
+        let from_csv = |null_policy: &str| {
+            FromCsv::try_parse_from([
+                "from-csv",
+                "-i",
+                input_file.path().to_str().unwrap(),
+                "-o",
+                output_file.path().to_str().unwrap(),
+                "--measurement",
+                "cpu",
+                "--timestamp",
+                "ts",
+                "--precision",
+                "ns",
+                "--tag",
+                "host",
+                "--types",
+                "count=i64",
+                "--null-policy",
+                null_policy,
+            ])
+            .unwrap()
+            .run()
+            .unwrap();
+            fs::read_to_string(output_file.path()).unwrap()
+        };
+
+        // The second row has no fields.
+        assert_eq!("cpu,host=a usage=1 1\n", from_csv("skip-field"));
+        assert_eq!("", from_csv("skip-row"));
+        assert_eq!(
+            "cpu,host=a usage=1,count=\"\" 1\ncpu,host=b usage=\"\",count=\"\" 2\n",
+            from_csv("empty-string")
+        );
+        assert_eq!(
+            "cpu,host=a usage=1,count=0i 1\ncpu,host=b usage=0,count=0i 2\n",
+            from_csv("zero")
+        );
+    }
+
+    #[test]
+    fn test_csv_dialect() {
+        let schema = Schema::infer(Cursor::new("cpu,host=a usage=1 0\n")).unwrap();
+        let dialect = CsvDialect {
+            delimiter: parse_csv_char("\\t").unwrap(),
+            quote: b'\'',
+            no_header: true,
+        };
+        let output = line_protocol_to_csv(
+            &schema,
+            Cursor::new("cpu,host=a\\ b usage=1 0\n"),
+            Vec::new(),
+            &dialect,
+            TimeFormat::Rfc3339,
+            1,
+            DEFAULT_BLOCK_SIZE,
+        )
+        .unwrap();
+        assert_eq!(
+            "cpu\ta b\t1.0\t1970-01-01T00:00:00.000000000+00:00\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"'host a'\t1\t1451606400000\nhost_b\t2\t1451606400000\n")
+            .unwrap();
+        FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--measurement",
+            "cpu",
+            "--precision",
+            "ms",
+            "--columns",
+            "host:tag,usage:field,ts:time",
+            "--delimiter",
+            "\\t",
+            "--quote",
+            "'",
+            "--no-header",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+        assert_eq!(
+            "cpu,host=host\\ a usage=1 1451606400000000000
+cpu,host=host_b usage=2 1451606400000000000
+",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_measurement_column() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_dir = Builder::new().tempdir().unwrap();
+        let input_path = input_dir.path().join("metrics.csv");
+        fs::write(
+            &input_path,
+            "metric,host,value,ts\ncpu,a,1,1\nmem,a,2,2\n,b,3,3\n",
+        )
+        .unwrap();
+
+        let from_csv = |measurement_column: &str| {
+            FromCsv::try_parse_from([
+                "from-csv",
+                "-i",
+                input_path.to_str().unwrap(),
+                "-o",
+                output_file.path().to_str().unwrap(),
+                "--measurement-column",
+                measurement_column,
+                "--timestamp",
+                "ts",
+                "--precision",
+                "ns",
+                "--tag",
+                "host",
+            ])
+            .unwrap()
+        };
+        from_csv("metric").run().unwrap();
+        assert_eq!(
+            "cpu,host=a value=1 1\nmem,host=a value=2 2\nmetrics,host=b value=3 3\n",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+
+        let err = from_csv("name").run().unwrap_err();
+        assert_eq!(
+            format!("Column name is not in {}", input_path.display()),
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_from_csv_long_format() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(
+                b"timestamp,host,metric,value\n\
+                1,a,usage,1\n\
+                1,a,count,2\n\
+                1,b,usage,3\n\
+                2,b,usage,\n",
+            )
+            .unwrap();
+
+        FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--measurement",
+            "cpu",
+            "--precision",
+            "ns",
+            "--tag",
+            "host",
+            "--types",
+            "count=i64",
+            "--long-format",
+        ])
+        .unwrap()
+        .run()
+        .unwrap();
+        assert_eq!(
+            "cpu,host=a usage=1,count=2i 1\ncpu,host=b usage=3 1\n",
+            fs::read_to_string(output_file.path()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table_name() {
+        assert_eq!("cpu", table_name(Path::new("a/cpu.csv"), None).unwrap());
+        assert_eq!("cpu", table_name(Path::new("a/cpu.csv.gz"), None).unwrap());
+        assert_eq!("cpu", table_name(Path::new("cpu.csv.zst"), None).unwrap());
+        assert_eq!("mem", table_name(Path::new("-"), Some("mem")).unwrap());
+        assert!(table_name(Path::new("-"), None).is_err());
+    }
+
+    #[test]
+    fn test_from_csv_invalid_timestamp() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"hostname,ts,usage\nhost_0,1451606400000,1\nhost_1,yesterday,2\n")
+            .unwrap();
+
+        let from_csv = FromCsv::try_parse_from([
+            "from-csv",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            output_file.path().to_str().unwrap(),
+            "--timestamp",
+            "ts",
+            "--precision",
+            "ms",
+            "--tag",
+            "hostname",
+        ])
+        .unwrap();
+        let err = from_csv.run().unwrap_err();
+        assert_eq!(
+            format!(
+                "Invalid timestamp yesterday in column ts at line 3 of {}",
+                input_file.path().display()
+            ),
+            err.to_string()
+        );
+    }
 }
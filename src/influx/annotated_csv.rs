@@ -0,0 +1,428 @@
+//! Convert between line protocol and InfluxDB annotated CSV.
+//!
+//! Annotated CSV is the CSV of Flux results, each table has annotation rows like `#datatype`
+//! followed by the header, and each row has a value of a field in the `_measurement`, `_field`
+//! and `_value` columns.
+
+use crate::error::{ReadCsvSnafu, Result, WriteCsvSnafu, WriteOutputSnafu};
+use crate::influx::timestamp::{Precision, TimestampParser};
+use crate::influx::{read_lines, series_key, ColumnValue, LineWriter};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::reject;
+use chrono::{SecondsFormat, TimeZone, Utc};
+use clap::Parser;
+use csv::{ReaderBuilder, StringRecord, WriterBuilder};
+use influxdb_line_protocol::FieldValue;
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// Convert line protocol file to annotated CSV file.
+///
+/// Each field of each series is a table. Tables with the same tags and field type share
+/// the annotations and the header.
+#[derive(Debug, Parser)]
+pub(crate) struct ToAnnotatedCsv {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output annotated CSV file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToAnnotatedCsv {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = line_protocol_to_annotated_csv(input, output, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert annotated CSV file to line protocol file.
+///
+/// Each row is written as a line with the `_field` and `_value` columns as the field.
+/// Columns not starting with `_` other than `result` and `table` are tags.
+#[derive(Debug, Parser)]
+pub(crate) struct FromAnnotatedCsv {
+    /// Input annotated CSV file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromAnnotatedCsv {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut writer = LineWriter::new(
+            output,
+            String::new(),
+            TimestampParser::new(Precision::Ns, None),
+            HashSet::new(),
+            String::new(),
+        )?;
+        writer.convert_flux_csv(input, Path::new(&self.input))?;
+        writer.finish()
+    }
+}
+
+impl<W: Write> LineWriter<W> {
+    /// Convert Flux CSV with annotations to lines.
+    ///
+    /// Values of the `#datatype` annotation decide the field types, and empty values are
+    /// replaced by the `#default` annotation.
+    pub(crate) fn convert_flux_csv<R: Read>(&mut self, source: R, path: &Path) -> Result<()> {
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(source);
+        let mut datatypes = StringRecord::new();
+        let mut defaults = StringRecord::new();
+        let mut headers: Option<StringRecord> = None;
+
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            // Tables are separated by empty lines.
+            if record.iter().all(|v| v.is_empty()) {
+                datatypes.clear();
+                defaults.clear();
+                headers = None;
+                continue;
+            }
+            if let Some(annotation) = record.get(0).filter(|v| v.starts_with('#')) {
+                match annotation {
+                    "#datatype" => datatypes = record,
+                    "#default" => defaults = record,
+                    _ => (),
+                }
+                headers = None;
+                continue;
+            }
+            let Some(headers) = &headers else {
+                self.tags = record
+                    .iter()
+                    .filter(|name| !name.is_empty() && !name.starts_with('_'))
+                    .filter(|name| *name != "result" && *name != "table")
+                    .map(|name| name.to_string())
+                    .collect();
+                headers = Some(record);
+                continue;
+            };
+
+            let mut measurement = "";
+            let mut field = "";
+            let mut value = ColumnValue::Null;
+            let mut timestamp = None;
+            let mut columns = Vec::with_capacity(headers.len());
+            for (i, (name, v)) in headers.iter().zip(record.iter()).enumerate() {
+                let v = match v {
+                    "" => defaults.get(i).unwrap_or_default(),
+                    v => v,
+                };
+                match name {
+                    "_measurement" => measurement = v,
+                    "_field" => field = v,
+                    "_value" => value = flux_value(datatypes.get(i).unwrap_or_default(), v),
+                    "_time" => timestamp = self.timestamp_parser.parse(v),
+                    _ if self.tags.contains(name) => columns.push((name, ColumnValue::Text(v))),
+                    _ => (),
+                }
+            }
+            if value.is_empty() {
+                continue;
+            }
+            columns.push((field, value));
+
+            let result = self.write_row(measurement, &columns, timestamp);
+            reject::check(result, || self.record_text(&record))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the value of the Flux datatype.
+fn flux_value<'a>(datatype: &str, value: &'a str) -> ColumnValue<'a> {
+    if value.is_empty() {
+        return ColumnValue::Null;
+    }
+
+    let parsed = match datatype {
+        "long" => value.parse().ok().map(ColumnValue::Int64),
+        "unsignedLong" => value.parse().ok().map(ColumnValue::UInt64),
+        "double" => value.parse().ok().map(ColumnValue::Float64),
+        "boolean" => value.parse().ok().map(ColumnValue::Boolean),
+        "string" => Some(ColumnValue::String(value)),
+        _ => None,
+    };
+    parsed.unwrap_or(ColumnValue::Text(value))
+}
+
+/// Returns the Flux datatype of the field value.
+fn flux_datatype(value: &FieldValue<'_>) -> &'static str {
+    match value {
+        FieldValue::I64(_) => "long",
+        FieldValue::U64(_) => "unsignedLong",
+        FieldValue::F64(_) => "double",
+        FieldValue::String(_) => "string",
+        FieldValue::Boolean(_) => "boolean",
+    }
+}
+
+/// Values of a field of a series.
+struct Table {
+    measurement: String,
+    /// Sorted tags.
+    tags: Vec<(String, String)>,
+    field: String,
+    datatype: &'static str,
+    /// Timestamps and values.
+    rows: Vec<(i64, String)>,
+}
+
+/// Group lines into tables and write them as annotated CSV.
+///
+/// Lines without timestamp are at `now`.
+fn line_protocol_to_annotated_csv<R: Read, W: Write>(source: R, dest: W, now: i64) -> Result<W> {
+    let mut tables: Vec<Table> = Vec::new();
+    // Index of tables by the series key, field name and datatype.
+    let mut index: HashMap<(String, String, &'static str), usize> = HashMap::new();
+    read_lines(source, |line| {
+        let key = series_key(&line);
+        let timestamp = line.timestamp.unwrap_or(now);
+        for (field, value) in &line.field_set {
+            let datatype = flux_datatype(value);
+            let i = *index
+                .entry((key.clone(), field.to_string(), datatype))
+                .or_insert_with(|| {
+                    let mut tags: Vec<_> = line
+                        .series
+                        .tag_set
+                        .iter()
+                        .flatten()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect();
+                    tags.sort_unstable();
+                    tables.push(Table {
+                        measurement: line.series.measurement.to_string(),
+                        tags,
+                        field: field.to_string(),
+                        datatype,
+                        rows: Vec::new(),
+                    });
+                    tables.len() - 1
+                });
+            let value = match value {
+                FieldValue::I64(v) => v.to_string(),
+                FieldValue::U64(v) => v.to_string(),
+                FieldValue::F64(v) => v.to_string(),
+                FieldValue::String(v) => v.to_string(),
+                FieldValue::Boolean(v) => v.to_string(),
+            };
+            tables[i].rows.push((timestamp, value));
+        }
+        Ok(())
+    })?;
+
+    let mut dest = dest;
+    let mut table_id = 0;
+    // Tables with the same header are written in a block, blocks are separated by empty lines.
+    for (i, block) in tables.chunk_by_mut(same_header).enumerate() {
+        if i > 0 {
+            dest.write_all(b"\n").context(WriteOutputSnafu)?;
+        }
+        let mut writer = WriterBuilder::new().from_writer(&mut dest);
+        write_annotations(&mut writer, &block[0])?;
+        for table in block {
+            table.rows.sort_by_key(|(ts, _)| *ts);
+            let id = table_id.to_string();
+            table_id += 1;
+            for (ts, value) in &table.rows {
+                let time = Utc
+                    .timestamp_nanos(*ts)
+                    .to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                let columns = ["", "", &id, &time, value, &table.field, &table.measurement];
+                writer
+                    .write_record(
+                        columns
+                            .into_iter()
+                            .chain(table.tags.iter().map(|(_, v)| v.as_str())),
+                    )
+                    .context(WriteCsvSnafu)?;
+            }
+        }
+        writer.flush().context(WriteOutputSnafu)?;
+    }
+
+    Ok(dest)
+}
+
+/// Returns true if the tables have the same columns and types.
+fn same_header(a: &Table, b: &Table) -> bool {
+    a.datatype == b.datatype
+        && a.tags
+            .iter()
+            .map(|(k, _)| k)
+            .eq(b.tags.iter().map(|(k, _)| k))
+}
+
+/// Write the annotations and the header of the table.
+fn write_annotations<W: Write>(writer: &mut csv::Writer<W>, table: &Table) -> Result<()> {
+    let num_tags = table.tags.len();
+    let datatypes = [
+        "#datatype",
+        "string",
+        "long",
+        "dateTime:RFC3339",
+        table.datatype,
+    ]
+    .into_iter()
+    .chain(std::iter::repeat_n("string", 2 + num_tags));
+    let groups = ["#group", "false", "false", "false", "false"]
+        .into_iter()
+        .chain(std::iter::repeat_n("true", 2 + num_tags));
+    let defaults = ["#default", "_result"]
+        .into_iter()
+        .chain(std::iter::repeat_n("", 5 + num_tags));
+    let headers = [
+        "",
+        "result",
+        "table",
+        "_time",
+        "_value",
+        "_field",
+        "_measurement",
+    ]
+    .into_iter()
+    .chain(table.tags.iter().map(|(k, _)| k.as_str()));
+
+    writer.write_record(groups).context(WriteCsvSnafu)?;
+    writer.write_record(datatypes).context(WriteCsvSnafu)?;
+    writer.write_record(defaults).context(WriteCsvSnafu)?;
+    writer.write_record(headers).context(WriteCsvSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use tempfile::NamedTempFile;
+
+    fn flux_csv_to_lines(input: &str) -> String {
+        let output = NamedTempFile::new().unwrap();
+        let mut writer = LineWriter::new(
+            Box::new(output.reopen().unwrap()),
+            String::new(),
+            TimestampParser::new(Precision::Ns, None),
+            HashSet::new(),
+            String::new(),
+        )
+        .unwrap();
+        writer
+            .convert_flux_csv(Cursor::new(input), Path::new("test.csv"))
+            .unwrap();
+        writer.flush().unwrap();
+
+        let mut lines = String::new();
+        output.reopen().unwrap().read_to_string(&mut lines).unwrap();
+        lines
+    }
+
+    #[test]
+    fn test_convert_flux_csv() {
+        let input = "#datatype,string,long,dateTime:RFC3339,dateTime:RFC3339,dateTime:RFC3339,long,string,string,string
+,result,table,_start,_stop,_time,_value,_field,_measurement,host
+,_result,0,2016-01-01T00:00:00Z,2016-01-02T00:00:00Z,2016-01-01T00:00:00Z,58,usage_user,cpu,host_0
+,_result,0,2016-01-01T00:00:00Z,2016-01-02T00:00:00Z,2016-01-01T00:00:10Z,,usage_user,cpu,host_0
+
+#group,false,false,true,false,false,true
+#datatype,string,long,string,dateTime:RFC3339,double,string
+#default,_result,,mem,,,
+,result,table,_measurement,_time,_value,_field
+,,1,,2016-01-01T00:00:00Z,1.5,used
+";
+        assert_eq!(
+            "cpu,host=host_0 usage_user=58i 1451606400000000000\nmem used=1.5 1451606400000000000\n",
+            flux_csv_to_lines(input)
+        );
+    }
+
+    #[test]
+    fn test_convert_flux_csv_not_finite() {
+        let input = "#datatype,string,long,dateTime:RFC3339,double,string,string
+,result,table,_time,_value,_field,_measurement
+,_result,0,2016-01-01T00:00:00Z,NaN,used,mem
+";
+        let mut writer = LineWriter::new(
+            Vec::new(),
+            String::new(),
+            TimestampParser::new(Precision::Ns, None),
+            HashSet::new(),
+            String::new(),
+        )
+        .unwrap();
+        let e = writer
+            .convert_flux_csv(Cursor::new(input), Path::new("test.csv"))
+            .unwrap_err();
+        assert_eq!("Invalid point: field used is NaN", e.to_string());
+    }
+
+    #[test]
+    fn test_line_protocol_to_annotated_csv() {
+        let input = "cpu,host=a usage=1.5,count=2i 1451606400000000000
+cpu,host=b usage=3 1451606400000000000
+cpu,host=a usage=2.5 1451606400000000001
+mem used=\"x,y\"
+";
+        let output = line_protocol_to_annotated_csv(input.as_bytes(), Vec::new(), 0).unwrap();
+        let csv = String::from_utf8(output).unwrap();
+        assert_eq!(
+            "#group,false,false,false,false,true,true,true
+#datatype,string,long,dateTime:RFC3339,double,string,string,string
+#default,_result,,,,,,
+,result,table,_time,_value,_field,_measurement,host
+,,0,2016-01-01T00:00:00Z,1.5,usage,cpu,a
+,,0,2016-01-01T00:00:00.000000001Z,2.5,usage,cpu,a
+
+#group,false,false,false,false,true,true,true
+#datatype,string,long,dateTime:RFC3339,long,string,string,string
+#default,_result,,,,,,
+,result,table,_time,_value,_field,_measurement,host
+,,1,2016-01-01T00:00:00Z,2,count,cpu,a
+
+#group,false,false,false,false,true,true,true
+#datatype,string,long,dateTime:RFC3339,double,string,string,string
+#default,_result,,,,,,
+,result,table,_time,_value,_field,_measurement,host
+,,2,2016-01-01T00:00:00Z,3,usage,cpu,b
+
+#group,false,false,false,false,true,true
+#datatype,string,long,dateTime:RFC3339,string,string,string
+#default,_result,,,,,
+,result,table,_time,_value,_field,_measurement
+,,3,1970-01-01T00:00:00Z,\"x,y\",used,mem
+",
+            csv
+        );
+
+        assert_eq!(
+            "cpu,host=a usage=1.5 1451606400000000000
+cpu,host=a usage=2.5 1451606400000000001
+cpu,host=a count=2i 1451606400000000000
+cpu,host=b usage=3 1451606400000000000
+mem used=\"x,y\" 0
+",
+            flux_csv_to_lines(&csv)
+        );
+    }
+}
@@ -0,0 +1,178 @@
+//! Anonymize tag values of line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::{fnv1a, push_line, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::EscapedStr;
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::io::{BufWriter, Read, Write};
+
+/// How to replace tag values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Method {
+    /// Hex of the 64-bit FNV-1a hash of the salt and the value.
+    Hash,
+    /// The tag key and the order the value first appears, e.g. `host_0`.
+    Sequential,
+    /// Fake names like `amber-falcon`, in the order values first appear.
+    Faker,
+}
+
+/// Replace values of tags of line protocol file consistently.
+///
+/// The same value of a tag is always replaced by the same value, so the number of
+/// series and their points are kept.
+#[derive(Debug, Parser)]
+pub(crate) struct Anonymize {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Tags to anonymize.
+    #[arg(long, required = true)]
+    tag: Vec<String>,
+    /// How to replace tag values.
+    #[arg(long, value_enum, default_value_t = Method::Hash)]
+    method: Method,
+    /// Salt of hashes, which makes hashes of different exports unrelated.
+    #[arg(long, default_value = "")]
+    salt: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Anonymize {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut anonymizer = Anonymizer::new(&self.tag, self.method, self.salt);
+        let mut output = anonymizer.anonymize_lines(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Replacer of tag values, which remembers values of sequential and fake names.
+struct Anonymizer {
+    method: Method,
+    salt: String,
+    /// Replaced values by the original value of each tag.
+    values: HashMap<String, HashMap<String, String>>,
+}
+
+impl Anonymizer {
+    fn new(tags: &[String], method: Method, salt: String) -> Anonymizer {
+        Anonymizer {
+            method,
+            salt,
+            values: tags
+                .iter()
+                .map(|tag| (tag.clone(), HashMap::new()))
+                .collect(),
+        }
+    }
+
+    /// Write lines with values of the tags replaced.
+    fn anonymize_lines<R: Read, W: Write>(&mut self, source: R, dest: W) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        let mut buffer = String::new();
+        read_lines(source, |mut line| {
+            for (key, value) in line.series.tag_set.iter_mut().flatten() {
+                if let Some(replaced) = self.replace(key.as_str(), value.as_str()) {
+                    *value = EscapedStr::CopiedValue(replaced);
+                }
+            }
+            buffer.clear();
+            push_line(&mut buffer, &line);
+            writer
+                .write_all(buffer.as_bytes())
+                .context(WriteOutputSnafu)
+        })?;
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+
+    /// Returns the replaced value, or `None` if the tag is not anonymized.
+    fn replace(&mut self, tag: &str, value: &str) -> Option<String> {
+        let values = self.values.get_mut(tag)?;
+        if self.method == Method::Hash {
+            return Some(format!("{:016x}", fnv1a(&[&self.salt, value])));
+        }
+        if let Some(replaced) = values.get(value) {
+            return Some(replaced.clone());
+        }
+        let idx = values.len();
+        let replaced = match self.method {
+            Method::Sequential => format!("{tag}_{idx}"),
+            _ => fake_name(idx),
+        };
+        values.insert(value.to_string(), replaced.clone());
+        Some(replaced)
+    }
+}
+
+const ADJECTIVES: &[&str] = &[
+    "amber", "brave", "calm", "dusty", "eager", "fuzzy", "gentle", "hollow", "icy", "jolly",
+    "keen", "lucky", "misty", "noble", "olive", "proud",
+];
+const NOUNS: &[&str] = &[
+    "falcon", "badger", "cedar", "dune", "ember", "fjord", "glacier", "harbor", "island", "jaguar",
+    "kestrel", "lagoon", "meadow", "nebula", "otter", "pine",
+];
+
+/// Returns the fake name of the index, names are unique for different indices.
+fn fake_name(idx: usize) -> String {
+    let adjective = ADJECTIVES[idx % ADJECTIVES.len()];
+    let noun = NOUNS[idx / ADJECTIVES.len() % NOUNS.len()];
+    match idx / (ADJECTIVES.len() * NOUNS.len()) {
+        0 => format!("{adjective}-{noun}"),
+        round => format!("{adjective}-{noun}-{round}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anonymize_lines() {
+        let input = "cpu,host=web,region=eu usage=1 1
+cpu,host=db,region=eu usage=2 1
+mem,host=web used=3 2
+";
+        let anonymize = |method| {
+            let mut anonymizer = Anonymizer::new(&["host".to_string()], method, String::new());
+            let output = anonymizer
+                .anonymize_lines(input.as_bytes(), Vec::new())
+                .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            "cpu,host=host_0,region=eu usage=1 1
+cpu,host=host_1,region=eu usage=2 1
+mem,host=host_0 used=3 2
+",
+            anonymize(Method::Sequential)
+        );
+        assert_eq!(
+            "cpu,host=amber-falcon,region=eu usage=1 1
+cpu,host=brave-falcon,region=eu usage=2 1
+mem,host=amber-falcon used=3 2
+",
+            anonymize(Method::Faker)
+        );
+        let hashed = anonymize(Method::Hash);
+        let hash = format!("{:016x}", fnv1a(&["", "web"]));
+        assert!(hashed.starts_with(&format!("cpu,host={hash},region=eu")));
+        assert!(hashed.ends_with(&format!("mem,host={hash} used=3 2\n")));
+
+        assert_eq!("amber-falcon-1", fake_name(256));
+    }
+}
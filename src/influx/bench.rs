@@ -0,0 +1,281 @@
+//! Benchmark writing line protocol to InfluxDB.
+
+use crate::error::{ReadInputSnafu, Result};
+use crate::generate::{Generator, UseCase};
+use crate::influx::timestamp::parse_duration;
+use crate::influx::write::WriteTarget;
+use crate::io::{open_input, Compression};
+use crate::rate_limit::RateLimit;
+use chrono::Utc;
+use clap::Parser;
+use snafu::ResultExt;
+use std::io::{self, BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Benchmark writing line protocol to InfluxDB by concurrent workers, like `tsbs_load`.
+///
+/// Lines are generated by the `cpu-only` use case of `generate`, or read from the input
+/// file and written repeatedly. Failed requests are counted as errors after retries, so
+/// `--max-retries 0` counts every failure. Throughput, latency percentiles of successful
+/// requests and the error rate are reported at the end.
+#[derive(Debug, Parser)]
+pub(crate) struct Bench {
+    /// Input line protocol file path to write repeatedly, `-` for stdin. Lines are generated
+    /// if it is absent.
+    #[arg(short, long)]
+    input: Option<String>,
+    #[command(flatten)]
+    target: WriteTarget,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Number of concurrent workers.
+    #[arg(long, default_value_t = 4)]
+    workers: usize,
+    /// Number of lines in each request.
+    #[arg(long, default_value_t = 5000)]
+    batch_size: usize,
+    /// Duration of the benchmark, e.g. 60s.
+    #[arg(long, default_value = "60s", value_parser = parse_duration)]
+    duration: i64,
+    /// Number of hosts of generated lines.
+    #[arg(long, default_value_t = 100)]
+    scale: usize,
+    /// Interval between generated points of a host, e.g. 10s.
+    #[arg(long, default_value = "10s", value_parser = parse_duration)]
+    interval: i64,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Bench {
+    pub(crate) fn run(self) -> Result<()> {
+        let source = match &self.input {
+            Some(input) => Source::File {
+                batches: self.read_batches(input)?,
+                next: AtomicUsize::new(0),
+            },
+            None => Source::Generated(Mutex::new(Generated {
+                generator: Generator::new(UseCase::CpuOnly, self.scale.max(1), 123),
+                timestamp: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+                steps: (self.batch_size / self.scale.max(1)).max(1),
+                interval: self.interval,
+            })),
+        };
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_nanos(self.duration as u64);
+        let stats = thread::scope(|scope| -> Result<Stats> {
+            let workers: Vec<_> = (0..self.workers.max(1))
+                .map(|_| scope.spawn(|| self.work(&source, deadline)))
+                .collect();
+            let mut stats = Stats::default();
+            for worker in workers {
+                stats.merge(worker.join().unwrap()?);
+            }
+            Ok(stats)
+        })?;
+        print!("{}", stats.report(start.elapsed()));
+        Ok(())
+    }
+
+    /// Read the input into batches.
+    fn read_batches(&self, input: &str) -> Result<Vec<Batch>> {
+        let mut reader = BufReader::new(open_input(input, self.input_compression)?);
+        let mut line = String::new();
+        let mut batches = Vec::new();
+        let mut batch = Batch::default();
+        while reader.read_line(&mut line).context(ReadInputSnafu)? > 0 {
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                batch.body.push_str(trimmed);
+                batch.body.push('\n');
+                batch.num_lines += 1;
+                if batch.num_lines >= self.batch_size {
+                    batches.push(std::mem::take(&mut batch));
+                }
+            }
+            line.clear();
+        }
+        if batch.num_lines > 0 {
+            batches.push(batch);
+        }
+        Ok(batches)
+    }
+
+    /// Write batches until the deadline, returns an error if lines can't be generated.
+    fn work(&self, source: &Source, deadline: Instant) -> Result<Stats> {
+        let mut stats = Stats::default();
+        while Instant::now() < deadline {
+            let generated;
+            let batch = match source {
+                Source::File { batches, next } => {
+                    if batches.is_empty() {
+                        break;
+                    }
+                    &batches[next.fetch_add(1, Ordering::Relaxed) % batches.len()]
+                }
+                Source::Generated(generated_source) => {
+                    generated = generated_source.lock().unwrap().next_batch()?;
+                    &generated
+                }
+            };
+
+            // Waiting for the rate limit is not counted in the latency.
+            self.rate_limit.acquire(batch.num_lines, batch.body.len());
+            if Instant::now() >= deadline {
+                break;
+            }
+            let begin = Instant::now();
+            match self.target.send(&batch.body) {
+                Ok(()) => {
+                    stats.latencies.push(begin.elapsed());
+                    stats.lines += batch.num_lines;
+                    stats.bytes += batch.body.len();
+                }
+                Err(e) => {
+                    stats.errors += 1;
+                    stats.first_error.get_or_insert_with(|| e.to_string());
+                }
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Lines of a request.
+#[derive(Debug, Default)]
+struct Batch {
+    body: String,
+    num_lines: usize,
+}
+
+/// Source of batches shared by workers.
+enum Source {
+    /// Batches of the input that are written round-robin.
+    File {
+        batches: Vec<Batch>,
+        next: AtomicUsize,
+    },
+    Generated(Mutex<Generated>),
+}
+
+/// Generated lines, each batch has lines of all hosts at a few timestamps.
+struct Generated {
+    generator: Generator,
+    timestamp: i64,
+    steps: usize,
+    interval: i64,
+}
+
+impl Generated {
+    fn next_batch(&mut self) -> Result<Batch> {
+        let end = self
+            .timestamp
+            .saturating_add(self.interval.saturating_mul(self.steps as i64));
+        let body = self
+            .generator
+            .write_lines(self.timestamp, end, self.interval, Vec::new())?;
+        let body = String::from_utf8(body)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .context(ReadInputSnafu)?;
+        self.timestamp = end;
+        Ok(Batch {
+            num_lines: body.lines().count(),
+            body,
+        })
+    }
+}
+
+/// Statistics of workers.
+#[derive(Debug, Default)]
+struct Stats {
+    /// Latencies of successful requests.
+    latencies: Vec<Duration>,
+    lines: usize,
+    bytes: usize,
+    errors: usize,
+    first_error: Option<String>,
+}
+
+impl Stats {
+    fn merge(&mut self, other: Stats) {
+        self.latencies.extend(other.latencies);
+        self.lines += other.lines;
+        self.bytes += other.bytes;
+        self.errors += other.errors;
+        if self.first_error.is_none() {
+            self.first_error = other.first_error;
+        }
+    }
+
+    fn report(mut self, elapsed: Duration) -> String {
+        self.latencies.sort_unstable();
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let requests = self.latencies.len() + self.errors;
+        let error_rate = if requests == 0 {
+            0.0
+        } else {
+            self.errors as f64 * 100.0 / requests as f64
+        };
+        let mut report = format!(
+            "Elapsed: {secs:.2}s
+Requests: {requests}, errors: {} ({error_rate:.2}%)
+Throughput: {:.1} points/s, {:.2} MB/s
+",
+            self.errors,
+            self.lines as f64 / secs,
+            self.bytes as f64 / secs / 1e6,
+        );
+        if let (Some(min), Some(max)) = (self.latencies.first(), self.latencies.last()) {
+            let percentile = |p: usize| self.latencies[(self.latencies.len() - 1) * p / 100];
+            report.push_str(&format!(
+                "Latency: min {:?}, p50 {:?}, p90 {:?}, p99 {:?}, max {:?}\n",
+                min,
+                percentile(50),
+                percentile(90),
+                percentile(99),
+                max
+            ));
+        }
+        if let Some(error) = &self.first_error {
+            report.push_str(&format!("First error: {error}\n"));
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_report() {
+        let mut stats = Stats::default();
+        for (i, latency) in [3, 1, 2, 4].into_iter().enumerate() {
+            let mut worker = Stats {
+                latencies: vec![Duration::from_millis(latency)],
+                lines: 100,
+                bytes: 1_000_000,
+                ..Default::default()
+            };
+            if i == 0 {
+                worker.errors = 1;
+                worker.first_error = Some("status 500".to_string());
+            }
+            stats.merge(worker);
+        }
+        assert_eq!(
+            "Elapsed: 2.00s
+Requests: 5, errors: 1 (20.00%)
+Throughput: 200.0 points/s, 2.00 MB/s
+Latency: min 1ms, p50 2ms, p90 3ms, p99 3ms, max 4ms
+First error: status 500
+",
+            stats.report(Duration::from_secs(2))
+        );
+    }
+}
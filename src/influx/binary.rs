@@ -0,0 +1,335 @@
+//! Convert line protocol to streams of MessagePack or CBOR points.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::ResultExt;
+use std::io::{BufWriter, Read, Write};
+
+/// Binary format of points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryFormat {
+    Msgpack,
+    Cbor,
+}
+
+/// Convert line protocol file to a stream of MessagePack points.
+///
+/// Each point is an array of the measurement, a map of tags, a map of fields and the
+/// timestamp in nanosecond, which is nil if the line has no timestamp. Points follow one
+/// another without delimiters.
+#[derive(Debug, Parser)]
+pub(crate) struct ToMsgpack {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output MessagePack file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToMsgpack {
+    pub(crate) fn run(self) -> Result<()> {
+        convert(
+            &self.input,
+            &self.output,
+            self.compression,
+            BinaryFormat::Msgpack,
+        )
+    }
+}
+
+/// Convert line protocol file to a CBOR sequence of points (RFC 8742).
+///
+/// Each point is an array like `to-msgpack`, with null timestamps for lines without
+/// timestamp.
+#[derive(Debug, Parser)]
+pub(crate) struct ToCbor {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output CBOR file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToCbor {
+    pub(crate) fn run(self) -> Result<()> {
+        convert(
+            &self.input,
+            &self.output,
+            self.compression,
+            BinaryFormat::Cbor,
+        )
+    }
+}
+
+fn convert(
+    input: &str,
+    output: &str,
+    compression: CompressionArgs,
+    format: BinaryFormat,
+) -> Result<()> {
+    let input = open_input(input, compression.input_compression)?;
+    let output = create_output(output, compression.output_compression)?;
+    let mut output = line_protocol_to_binary(input, output, format)?;
+    output.finish().context(WriteOutputSnafu)
+}
+
+fn line_protocol_to_binary<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    format: BinaryFormat,
+) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut encoder = Encoder {
+        format,
+        buf: Vec::new(),
+    };
+    read_lines(source, |line| {
+        encoder.buf.clear();
+        encoder.point(&line);
+        writer.write_all(&encoder.buf).context(WriteOutputSnafu)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Encoder of values in the format.
+struct Encoder {
+    format: BinaryFormat,
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn point(&mut self, line: &ParsedLine<'_>) {
+        self.array(4);
+        self.str(line.series.measurement.as_str());
+        let tags = line.series.tag_set.as_deref().unwrap_or_default();
+        self.map(tags.len());
+        for (key, value) in tags {
+            self.str(key.as_str());
+            self.str(value.as_str());
+        }
+        self.map(line.field_set.len());
+        for (key, value) in &line.field_set {
+            self.str(key.as_str());
+            match value {
+                FieldValue::I64(v) => self.int(*v),
+                FieldValue::U64(v) => self.uint(*v),
+                FieldValue::F64(v) => self.float(*v),
+                FieldValue::String(v) => self.str(v.as_str()),
+                FieldValue::Boolean(v) => self.bool(*v),
+            }
+        }
+        match line.timestamp {
+            Some(ts) => self.int(ts),
+            None => self.nil(),
+        }
+    }
+
+    /// Push the head of CBOR with the major type and the argument.
+    fn cbor_head(&mut self, major: u8, n: u64) {
+        let major = major << 5;
+        match n {
+            0..=23 => self.buf.push(major | n as u8),
+            24..=0xff => self.buf.extend([major | 24, n as u8]),
+            0x100..=0xffff => {
+                self.buf.push(major | 25);
+                self.buf.extend((n as u16).to_be_bytes());
+            }
+            0x10000..=0xffff_ffff => {
+                self.buf.push(major | 26);
+                self.buf.extend((n as u32).to_be_bytes());
+            }
+            _ => {
+                self.buf.push(major | 27);
+                self.buf.extend(n.to_be_bytes());
+            }
+        }
+    }
+
+    /// Push the MessagePack length of array, map or str, in the fix type if it is less
+    /// than `fix_max`, otherwise in the type of the smallest width from `markers`.
+    fn msgpack_len(&mut self, len: usize, fix: u8, fix_max: usize, markers: [u8; 3]) {
+        if len < fix_max {
+            self.buf.push(fix | len as u8);
+        } else if markers[0] != 0 && len <= 0xff {
+            self.buf.extend([markers[0], len as u8]);
+        } else if len <= 0xffff {
+            self.buf.push(markers[1]);
+            self.buf.extend((len as u16).to_be_bytes());
+        } else {
+            self.buf.push(markers[2]);
+            self.buf.extend((len as u32).to_be_bytes());
+        }
+    }
+
+    fn array(&mut self, len: usize) {
+        match self.format {
+            BinaryFormat::Msgpack => self.msgpack_len(len, 0x90, 16, [0, 0xdc, 0xdd]),
+            BinaryFormat::Cbor => self.cbor_head(4, len as u64),
+        }
+    }
+
+    fn map(&mut self, len: usize) {
+        match self.format {
+            BinaryFormat::Msgpack => self.msgpack_len(len, 0x80, 16, [0, 0xde, 0xdf]),
+            BinaryFormat::Cbor => self.cbor_head(5, len as u64),
+        }
+    }
+
+    fn str(&mut self, s: &str) {
+        match self.format {
+            BinaryFormat::Msgpack => self.msgpack_len(s.len(), 0xa0, 32, [0xd9, 0xda, 0xdb]),
+            BinaryFormat::Cbor => self.cbor_head(3, s.len() as u64),
+        }
+        self.buf.extend(s.as_bytes());
+    }
+
+    fn uint(&mut self, v: u64) {
+        match self.format {
+            BinaryFormat::Msgpack => match v {
+                0..=0x7f => self.buf.push(v as u8),
+                0x80..=0xff => self.buf.extend([0xcc, v as u8]),
+                0x100..=0xffff => {
+                    self.buf.push(0xcd);
+                    self.buf.extend((v as u16).to_be_bytes());
+                }
+                0x10000..=0xffff_ffff => {
+                    self.buf.push(0xce);
+                    self.buf.extend((v as u32).to_be_bytes());
+                }
+                _ => {
+                    self.buf.push(0xcf);
+                    self.buf.extend(v.to_be_bytes());
+                }
+            },
+            BinaryFormat::Cbor => self.cbor_head(0, v),
+        }
+    }
+
+    fn int(&mut self, v: i64) {
+        if v >= 0 {
+            return self.uint(v as u64);
+        }
+        match self.format {
+            BinaryFormat::Msgpack => match v {
+                -32..=-1 => self.buf.push(v as u8),
+                -0x80..=-33 => self.buf.extend([0xd0, v as u8]),
+                -0x8000..=-0x81 => {
+                    self.buf.push(0xd1);
+                    self.buf.extend((v as i16).to_be_bytes());
+                }
+                -0x8000_0000..=-0x8001 => {
+                    self.buf.push(0xd2);
+                    self.buf.extend((v as i32).to_be_bytes());
+                }
+                _ => {
+                    self.buf.push(0xd3);
+                    self.buf.extend(v.to_be_bytes());
+                }
+            },
+            // Negative integers are `-1 - n`.
+            BinaryFormat::Cbor => self.cbor_head(1, !v as u64),
+        }
+    }
+
+    fn float(&mut self, v: f64) {
+        self.buf.push(match self.format {
+            BinaryFormat::Msgpack => 0xcb,
+            BinaryFormat::Cbor => 0xfb,
+        });
+        self.buf.extend(v.to_be_bytes());
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.buf.push(match (self.format, v) {
+            (BinaryFormat::Msgpack, false) => 0xc2,
+            (BinaryFormat::Msgpack, true) => 0xc3,
+            (BinaryFormat::Cbor, false) => 0xf4,
+            (BinaryFormat::Cbor, true) => 0xf5,
+        });
+    }
+
+    fn nil(&mut self) {
+        self.buf.push(match self.format {
+            BinaryFormat::Msgpack => 0xc0,
+            BinaryFormat::Cbor => 0xf6,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_protocol_to_binary() {
+        let input = "cpu,host=a usage=1.5,n=-2i,big=300u,up=true,s=\"x\" 1\nm v=-200i\n";
+        let msgpack =
+            line_protocol_to_binary(input.as_bytes(), Vec::new(), BinaryFormat::Msgpack).unwrap();
+        let mut expect = vec![0x94, 0xa3];
+        expect.extend(b"cpu");
+        expect.extend([0x81, 0xa4]);
+        expect.extend(b"host");
+        expect.extend([0xa1, b'a', 0x85, 0xa5]);
+        expect.extend(b"usage");
+        expect.push(0xcb);
+        expect.extend(1.5f64.to_be_bytes());
+        expect.extend([0xa1, b'n', 0xfe, 0xa3]);
+        expect.extend(b"big");
+        expect.extend([0xcd, 0x01, 0x2c, 0xa2]);
+        expect.extend(b"up");
+        expect.extend([0xc3, 0xa1, b's', 0xa1, b'x', 0x01]);
+        expect.extend([
+            0x94, 0xa1, b'm', 0x80, 0x81, 0xa1, b'v', 0xd1, 0xff, 0x38, 0xc0,
+        ]);
+        assert_eq!(expect, msgpack);
+
+        let cbor =
+            line_protocol_to_binary(input.as_bytes(), Vec::new(), BinaryFormat::Cbor).unwrap();
+        let mut expect = vec![0x84, 0x63];
+        expect.extend(b"cpu");
+        expect.extend([0xa1, 0x64]);
+        expect.extend(b"host");
+        expect.extend([0x61, b'a', 0xa5, 0x65]);
+        expect.extend(b"usage");
+        expect.push(0xfb);
+        expect.extend(1.5f64.to_be_bytes());
+        expect.extend([0x61, b'n', 0x21, 0x63]);
+        expect.extend(b"big");
+        expect.extend([0x19, 0x01, 0x2c, 0x62]);
+        expect.extend(b"up");
+        expect.extend([0xf5, 0x61, b's', 0x61, b'x', 0x01]);
+        expect.extend([0x84, 0x61, b'm', 0xa0, 0xa1, 0x61, b'v', 0x38, 0xc7, 0xf6]);
+        assert_eq!(expect, cbor);
+
+        let mut encoder = Encoder {
+            format: BinaryFormat::Msgpack,
+            buf: Vec::new(),
+        };
+        encoder.str(&"a".repeat(40));
+        encoder.int(i64::MIN);
+        encoder.uint(u64::MAX);
+        assert_eq!([0xd9, 40], encoder.buf[..2]);
+        assert_eq!(0xd3, encoder.buf[42]);
+        assert_eq!(0xcf, encoder.buf[51]);
+        encoder.format = BinaryFormat::Cbor;
+        encoder.buf.clear();
+        encoder.int(i64::MIN);
+        assert_eq!(
+            vec![0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            encoder.buf
+        );
+    }
+}
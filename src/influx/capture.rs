@@ -0,0 +1,128 @@
+//! Capture line protocol written to an emulated InfluxDB.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::io::{append_file, is_stdio};
+use crate::server::{self, Request, Response};
+use clap::Parser;
+use snafu::ResultExt;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use tracing::error;
+
+/// Emulate the write endpoints of InfluxDB and append the received line protocol to a
+/// file, e.g. to record what Telegraf sends.
+///
+/// Bodies of `/write` and `/api/v2/write` are appended as they are, so timestamps keep the
+/// precision of the request. `/ping`, `/health` and `/query` are answered to satisfy clients
+/// that check the server or create the database before writing.
+#[derive(Debug, Parser)]
+pub(crate) struct Capture {
+    /// Address to listen on.
+    #[arg(long, default_value = "0.0.0.0:8086")]
+    listen: String,
+    /// Output line protocol file path to append to, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+}
+
+impl Capture {
+    pub(crate) fn run(self) -> Result<()> {
+        let output: Box<dyn Write + Send> = if is_stdio(&self.output) {
+            Box::new(io::stdout())
+        } else {
+            Box::new(append_file(&self.output)?)
+        };
+        let output = Mutex::new(output);
+        let write = |body: &[u8]| {
+            let mut output = output.lock().unwrap();
+            write_body(&mut *output, body)
+        };
+        server::serve(&self.listen, |request| handle(request, &write))
+    }
+}
+
+/// Handle a request to InfluxDB.
+fn handle(request: &Request, write: &impl Fn(&[u8]) -> Result<()>) -> Response {
+    match request.path.as_str() {
+        "/write" | "/api/v2/write" => {}
+        "/ping" => return Response::text(204, ""),
+        "/health" => return json(200, r#"{"name":"influxdb","status":"pass"}"#),
+        "/query" => return json(200, r#"{"results":[{"statement_id":0}]}"#),
+        _ => return Response::text(404, "not found"),
+    }
+    if request.method != "POST" {
+        return Response::text(405, "method not allowed");
+    }
+    let body = match request.decoded_body() {
+        Ok(body) => body,
+        Err(e) => return json(400, &error_body(&e.to_string())),
+    };
+    if let Err(e) = write(&body) {
+        error!("Failed to write line protocol: {e}");
+        return json(500, &error_body(&e.to_string()));
+    }
+    Response::text(204, "")
+}
+
+fn json(status: u16, body: &str) -> Response {
+    Response {
+        status,
+        content_type: "application/json",
+        body: body.as_bytes().to_vec(),
+    }
+}
+
+/// Body of an error response, which is accepted by both v1 and v2 clients.
+fn error_body(message: &str) -> String {
+    serde_json::json!({ "code": "invalid", "error": message, "message": message }).to_string()
+}
+
+/// Write the body and a trailing newline if it is missing, then flush the output.
+fn write_body(output: &mut impl Write, body: &[u8]) -> Result<()> {
+    if body.is_empty() {
+        return Ok(());
+    }
+    output.write_all(body).context(WriteOutputSnafu)?;
+    if !body.ends_with(b"\n") {
+        output.write_all(b"\n").context(WriteOutputSnafu)?;
+    }
+    output.flush().context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_handle() {
+        let output = RefCell::new(Vec::new());
+        let write = |body: &[u8]| write_body(&mut *output.borrow_mut(), body);
+        let post = |path: &str, headers: Vec<(String, String)>, body: &[u8]| {
+            let request = Request {
+                method: "POST".to_string(),
+                path: path.to_string(),
+                headers,
+                body: body.to_vec(),
+            };
+            handle(&request, &write).status
+        };
+
+        assert_eq!(204, post("/write", vec![], b"cpu,host=a usage=1 1"));
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"mem,host=a used=2 2\n").unwrap();
+        let gzip = vec![("Content-Encoding".to_string(), "gzip".to_string())];
+        assert_eq!(
+            204,
+            post("/api/v2/write", gzip.clone(), &encoder.finish().unwrap())
+        );
+        assert_eq!(400, post("/api/v2/write", gzip, b"invalid"));
+        assert_eq!(404, post("/api/v1/write", vec![], b"disk free=3 3"));
+        assert_eq!(200, post("/query", vec![], b"q=CREATE DATABASE telegraf"));
+        assert_eq!(
+            "cpu,host=a usage=1 1\nmem,host=a used=2 2\n",
+            String::from_utf8(output.take()).unwrap()
+        );
+    }
+}
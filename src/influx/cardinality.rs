@@ -0,0 +1,341 @@
+//! Series cardinality of line protocol.
+
+use crate::error::{CardinalityExceededSnafu, Result, WriteOutputSnafu};
+use crate::influx::{read_lines, series_key};
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::{ensure, ResultExt};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+
+/// Report series cardinality of line protocol file overall, of each measurement and the
+/// number of values of each tag key.
+///
+/// Exact cardinality keeps all series keys in memory, `--approximate` estimates it with
+/// HyperLogLog in constant memory per counter, whose error is about 1%.
+#[derive(Debug, Parser)]
+pub(crate) struct Cardinality {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Estimate cardinality with HyperLogLog instead of counting distinct keys.
+    #[arg(long)]
+    approximate: bool,
+    /// Report the values of each tag key in the most series.
+    #[arg(long, conflicts_with = "approximate")]
+    top: Option<usize>,
+    /// Fail if the series cardinality exceeds the number.
+    #[arg(long)]
+    max_series: Option<u64>,
+    /// Fail if the number of values of any tag key exceeds the number.
+    #[arg(long)]
+    max_tag_values: Option<u64>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Cardinality {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut counter = CardinalityCounter::new(self.approximate, self.top.is_some());
+        read_lines(input, |line| {
+            counter.update(&line);
+            Ok(())
+        })?;
+
+        let report = counter.report(self.top.unwrap_or_default());
+        let mut output = create_output(&self.output, Compression::None)?;
+        write!(output, "{report}").context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)?;
+
+        if let Some(max) = self.max_series {
+            ensure!(
+                report.series <= max,
+                CardinalityExceededSnafu {
+                    name: "series",
+                    cardinality: report.series,
+                    max
+                }
+            );
+        }
+        if let Some(max) = self.max_tag_values {
+            for (key, values) in &report.tag_keys {
+                ensure!(
+                    *values <= max,
+                    CardinalityExceededSnafu {
+                        name: format!("values of tag {key}"),
+                        cardinality: *values,
+                        max
+                    }
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counter of distinct keys, exact or approximate.
+#[derive(Debug)]
+enum DistinctCounter {
+    Exact(HashSet<String>),
+    Approximate(Box<HyperLogLog>),
+}
+
+impl DistinctCounter {
+    fn new(approximate: bool) -> DistinctCounter {
+        if approximate {
+            DistinctCounter::Approximate(Box::default())
+        } else {
+            DistinctCounter::Exact(HashSet::new())
+        }
+    }
+
+    /// Insert the key, returns whether it is new, which is always false if approximate.
+    fn insert(&mut self, key: &str) -> bool {
+        match self {
+            DistinctCounter::Exact(keys) => !keys.contains(key) && keys.insert(key.to_string()),
+            DistinctCounter::Approximate(hll) => {
+                hll.insert(key);
+                false
+            }
+        }
+    }
+
+    fn count(&self) -> u64 {
+        match self {
+            DistinctCounter::Exact(keys) => keys.len() as u64,
+            DistinctCounter::Approximate(hll) => hll.count(),
+        }
+    }
+}
+
+/// Counters of series and tag values of lines.
+#[derive(Debug)]
+struct CardinalityCounter {
+    approximate: bool,
+    /// Series of each measurement.
+    measurements: HashMap<String, DistinctCounter>,
+    /// Values of each tag key.
+    tag_keys: HashMap<String, DistinctCounter>,
+    /// Number of series of each tag value by tag key, if top values are reported.
+    tag_value_series: Option<HashMap<String, HashMap<String, u64>>>,
+}
+
+impl CardinalityCounter {
+    fn new(approximate: bool, count_tag_value_series: bool) -> CardinalityCounter {
+        CardinalityCounter {
+            approximate,
+            measurements: HashMap::new(),
+            tag_keys: HashMap::new(),
+            tag_value_series: count_tag_value_series.then(HashMap::new),
+        }
+    }
+
+    fn update(&mut self, line: &ParsedLine<'_>) {
+        let measurement = line.series.measurement.as_str();
+        if !self.measurements.contains_key(measurement) {
+            self.measurements.insert(
+                measurement.to_string(),
+                DistinctCounter::new(self.approximate),
+            );
+        }
+        let is_new = self
+            .measurements
+            .get_mut(measurement)
+            .unwrap()
+            .insert(&series_key(line));
+
+        for (key, value) in line.series.tag_set.iter().flatten() {
+            if !self.tag_keys.contains_key(key.as_str()) {
+                self.tag_keys
+                    .insert(key.to_string(), DistinctCounter::new(self.approximate));
+            }
+            let values = self.tag_keys.get_mut(key.as_str()).unwrap();
+            values.insert(value.as_str());
+
+            if let Some(series) = self.tag_value_series.as_mut().filter(|_| is_new) {
+                let counts = series.entry(key.to_string()).or_default();
+                *counts.entry(value.to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    /// Returns the report with at most `top` values of each tag key.
+    fn report(&self, top: usize) -> Report {
+        let sorted = |counters: &HashMap<String, DistinctCounter>| {
+            let mut counts: Vec<_> = counters
+                .iter()
+                .map(|(name, counter)| (name.clone(), counter.count()))
+                .collect();
+            counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            counts
+        };
+        let measurements = sorted(&self.measurements);
+        let tag_keys = sorted(&self.tag_keys);
+
+        let mut top_values = Vec::new();
+        if let Some(series) = &self.tag_value_series {
+            for (key, _) in &tag_keys {
+                let mut values: Vec<_> = series[key]
+                    .iter()
+                    .map(|(value, count)| (value.clone(), *count))
+                    .collect();
+                values.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                values.truncate(top);
+                top_values.push((key.clone(), values));
+            }
+        }
+
+        Report {
+            approximate: self.approximate,
+            series: measurements.iter().map(|(_, series)| series).sum(),
+            measurements,
+            tag_keys,
+            top_values,
+        }
+    }
+}
+
+/// Cardinality of lines, names are sorted by cardinality in descending order.
+#[derive(Debug, PartialEq, Eq)]
+struct Report {
+    approximate: bool,
+    series: u64,
+    measurements: Vec<(String, u64)>,
+    tag_keys: Vec<(String, u64)>,
+    /// Values in the most series and their numbers of series of each tag key.
+    top_values: Vec<(String, Vec<(String, u64)>)>,
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = if self.approximate { "~" } else { "" };
+        writeln!(f, "Series: {prefix}{}", self.series)?;
+        writeln!(f)?;
+        writeln!(f, "Series of measurements:")?;
+        for (name, series) in &self.measurements {
+            writeln!(f, "  {name}: {prefix}{series}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Values of tag keys:")?;
+        for (key, values) in &self.tag_keys {
+            writeln!(f, "  {key}: {prefix}{values}")?;
+        }
+        for (key, values) in &self.top_values {
+            writeln!(f)?;
+            writeln!(f, "Top values of {key} by series:")?;
+            for (value, series) in values {
+                writeln!(f, "  {value}: {series}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Number of bits of the index of registers.
+const HLL_PRECISION: u32 = 14;
+const HLL_REGISTERS: usize = 1 << HLL_PRECISION;
+
+/// HyperLogLog estimator of the number of distinct keys.
+#[derive(Debug)]
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> HyperLogLog {
+        HyperLogLog {
+            registers: vec![0; HLL_REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    fn insert(&mut self, key: &str) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let idx = (hash >> (64 - HLL_PRECISION)) as usize;
+        // Position of the first 1 bit of the rest bits, which are never all 0.
+        let rank = ((hash << HLL_PRECISION) | (1 << (HLL_PRECISION - 1))).leading_zeros() + 1;
+        self.registers[idx] = self.registers[idx].max(rank as u8);
+    }
+
+    fn count(&self) -> u64 {
+        let m = HLL_REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|r| 2f64.powi(-i32::from(*r)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate for small cardinality.
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+        estimate.round() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cardinality() {
+        let input = "cpu,host=a,region=eu usage=1 1
+cpu,region=eu,host=a usage=2 2
+cpu,host=b,region=eu usage=3 1
+cpu,host=b,region=us usage=3 1
+mem,host=a used=1 1
+";
+        let mut counter = CardinalityCounter::new(false, true);
+        read_lines(input.as_bytes(), |line| {
+            counter.update(&line);
+            Ok(())
+        })
+        .unwrap();
+        let report = counter.report(1);
+        assert_eq!(4, report.series);
+        assert_eq!(
+            vec![("cpu".to_string(), 3), ("mem".to_string(), 1)],
+            report.measurements
+        );
+        assert_eq!(
+            vec![("host".to_string(), 2), ("region".to_string(), 2)],
+            report.tag_keys
+        );
+        assert_eq!(
+            vec![
+                ("host".to_string(), vec![("a".to_string(), 2)]),
+                ("region".to_string(), vec![("eu".to_string(), 2)]),
+            ],
+            report.top_values
+        );
+
+        let mut counter = CardinalityCounter::new(true, false);
+        let input: String = (0..20_000)
+            .map(|i| format!("cpu,host=host_{i},region=eu usage=1 1\n"))
+            .collect();
+        read_lines(input.as_bytes(), |line| {
+            counter.update(&line);
+            Ok(())
+        })
+        .unwrap();
+        let report = counter.report(0);
+        assert!((19_600..=20_400).contains(&report.series), "{report}");
+        assert_eq!(("region".to_string(), 1), report.tag_keys[1]);
+        assert!(report.to_string().starts_with("Series: ~"));
+    }
+}
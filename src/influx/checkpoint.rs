@@ -0,0 +1,227 @@
+//! Checkpoints to resume interrupted conversions of line protocol to CSV.
+
+use crate::error::{
+    CreateFileSnafu, InvalidCheckpointSnafu, OpenFileSnafu, ParseJsonSnafu, RemoveFileSnafu,
+    Result, WriteCsvSnafu, WriteJsonSnafu, WriteOutputSnafu,
+};
+use crate::influx::schema::{FieldType, Schema};
+use crate::influx::timestamp::TimeFormat;
+use crate::influx::{csv_headers, read_block_lines, write_csv_row, CsvDialect, CsvRow};
+use crate::io::ChunkReader;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, ResultExt};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Default bytes of the input converted between checkpoints.
+pub(crate) const DEFAULT_CHECKPOINT_INTERVAL: u64 = 256 << 20;
+
+/// Progress of a conversion, which is saved to a JSON file periodically.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Checkpoint {
+    /// Paths of the input and the output, which must be the same to resume.
+    pub(crate) input: PathBuf,
+    pub(crate) output: PathBuf,
+    /// Bytes and lines of the decompressed input converted.
+    pub(crate) input_offset: u64,
+    pub(crate) lines: usize,
+    /// Bytes of the output written, later bytes are discarded when resuming.
+    pub(crate) output_len: u64,
+    /// Tag and field columns before the selection, so the resumed conversion writes the
+    /// same columns without collecting them again.
+    pub(crate) tags: Vec<String>,
+    pub(crate) fields: Vec<(String, FieldType)>,
+}
+
+impl Checkpoint {
+    /// Create the checkpoint at the start of the input.
+    pub(crate) fn new(input: &Path, output: &Path, schema: &Schema) -> Checkpoint {
+        Checkpoint {
+            input: input.to_path_buf(),
+            output: output.to_path_buf(),
+            input_offset: 0,
+            lines: 0,
+            output_len: 0,
+            tags: schema.tags.clone(),
+            fields: schema.fields.clone(),
+        }
+    }
+
+    /// Read the checkpoint file, returns `None` if it doesn't exist.
+    pub(crate) fn load(path: &Path) -> Result<Option<Checkpoint>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).context(OpenFileSnafu { path }),
+        };
+        serde_json::from_reader(BufReader::new(file))
+            .map(Some)
+            .context(ParseJsonSnafu)
+    }
+
+    /// Write the checkpoint file by renaming a temporary file, so the previous checkpoint
+    /// is kept if the conversion dies while saving.
+    pub(crate) fn save(&self, path: &Path) -> Result<()> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(dir).context(CreateFileSnafu { path })?;
+        serde_json::to_writer(&mut file, self).context(WriteJsonSnafu)?;
+        file.as_file().sync_all().context(WriteOutputSnafu)?;
+        file.persist(path)
+            .map_err(|e| e.error)
+            .context(CreateFileSnafu { path })?;
+        Ok(())
+    }
+
+    /// Returns the schema of the columns before the selection.
+    pub(crate) fn schema(&self) -> Schema {
+        let names: Vec<_> = self.fields.iter().map(|(name, _)| name.clone()).collect();
+        let mut schema = Schema::with_columns(&self.tags, &names);
+        for (field, (_, field_type)) in schema.fields.iter_mut().zip(&self.fields) {
+            field.1 = *field_type;
+        }
+        schema
+    }
+}
+
+/// Options of a checkpointed conversion.
+#[derive(Debug)]
+pub(crate) struct CheckpointOptions<'a> {
+    /// Path of the checkpoint file.
+    pub(crate) path: &'a Path,
+    pub(crate) dialect: &'a CsvDialect,
+    pub(crate) time_format: TimeFormat,
+    pub(crate) block_size: usize,
+    /// Bytes of the input converted between checkpoints.
+    pub(crate) interval: u64,
+}
+
+/// Convert line protocol from the source, which starts at the offset of the checkpoint,
+/// to CSV of the schema like `line_protocol_to_csv`.
+///
+/// The output file is truncated to the bytes written at the checkpoint and rows are
+/// appended to it. The checkpoint is saved after each interval of the input and removed
+/// after all lines are converted.
+pub(crate) fn convert<R: Read>(
+    source: R,
+    checkpoint: &mut Checkpoint,
+    schema: &Schema,
+    options: &CheckpointOptions<'_>,
+) -> Result<()> {
+    let path = options.path;
+    let output = checkpoint.output.clone();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&output)
+        .context(CreateFileSnafu { path: &output })?;
+    let len = file.metadata().context(WriteOutputSnafu)?.len();
+    ensure!(
+        len >= checkpoint.output_len,
+        InvalidCheckpointSnafu {
+            path,
+            reason: format!(
+                "{} has {len} bytes, less than {} bytes written",
+                output.display(),
+                checkpoint.output_len
+            ),
+        }
+    );
+    file.set_len(checkpoint.output_len)
+        .context(WriteOutputSnafu)?;
+    file.seek(SeekFrom::End(0)).context(WriteOutputSnafu)?;
+
+    let mut writer = options.dialect.writer_builder().from_writer(file);
+    if checkpoint.output_len == 0 && !options.dialect.no_header {
+        writer
+            .write_record(csv_headers(schema))
+            .context(WriteCsvSnafu)?;
+    }
+
+    let mut reader = ChunkReader::new(source, options.block_size);
+    let mut row = CsvRow::default();
+    let mut converted = 0;
+    while let Some(block) = reader.next_chunk()? {
+        read_block_lines(block, &mut checkpoint.lines, |_, line| {
+            write_csv_row(schema, line, options.time_format, &mut row, &mut writer).map(|()| true)
+        })?;
+        checkpoint.input_offset += block.len() as u64;
+        converted += block.len() as u64;
+        if converted >= options.interval {
+            // Rows must be durable before the checkpoint refers to them.
+            writer.flush().context(WriteOutputSnafu)?;
+            let file = writer.get_ref();
+            file.sync_data().context(WriteOutputSnafu)?;
+            checkpoint.output_len = file.metadata().context(WriteOutputSnafu)?.len();
+            checkpoint.save(path)?;
+            debug!(
+                "Saved checkpoint at byte {} of {}",
+                checkpoint.input_offset,
+                checkpoint.input.display()
+            );
+            converted = 0;
+        }
+    }
+
+    let mut file = writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)?;
+    file.flush().context(WriteOutputSnafu)?;
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e).context(RemoveFileSnafu { path }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{open_input_at, Compression};
+
+    #[test]
+    fn test_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("cpu.lp");
+        let output = dir.path().join("cpu.csv");
+        let path = dir.path().join("cpu.ckpt");
+        let lines = "cpu,host=a usage=1.5 1\ncpu,host=b usage=2.5 2\n";
+        let dialect = CsvDialect::default();
+        let options = CheckpointOptions {
+            path: &path,
+            dialect: &dialect,
+            time_format: TimeFormat::Ns,
+            block_size: 8,
+            interval: 1,
+        };
+        let schema = Schema::infer(format!("{lines}cpu,host=c usage=3.5 3\n").as_bytes()).unwrap();
+        assert!(Checkpoint::load(&path).unwrap().is_none());
+
+        // The conversion dies at the invalid last line.
+        fs::write(&input, format!("{lines}cpu usage=\n")).unwrap();
+        let mut checkpoint = Checkpoint::new(&input, &output, &schema);
+        let source = File::open(&input).unwrap();
+        let err = convert(source, &mut checkpoint, &schema, &options).unwrap_err();
+        assert_eq!("Error at line 3", err.to_string());
+        let checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+        assert_eq!(lines.len() as u64, checkpoint.input_offset);
+        assert_eq!(2, checkpoint.lines);
+        assert_eq!(schema.fields, checkpoint.schema().fields);
+
+        // Resume the conversion after the last line is fixed.
+        fs::write(&input, format!("{lines}cpu,host=c usage=3.5 3\n")).unwrap();
+        let mut checkpoint = Checkpoint::load(&path).unwrap().unwrap();
+        let source = open_input_at(&input, Compression::Auto, checkpoint.input_offset).unwrap();
+        convert(source, &mut checkpoint, &schema, &options).unwrap();
+        assert_eq!(
+            "measurement,host,usage,timestamp\ncpu,a,1.5,1\ncpu,b,2.5,2\ncpu,c,3.5,3\n",
+            fs::read_to_string(&output).unwrap()
+        );
+        assert!(!path.exists());
+    }
+}
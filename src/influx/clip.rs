@@ -0,0 +1,100 @@
+//! Clip line protocol to a time range.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::read_lines_while;
+use crate::influx::timestamp::parse_rfc3339;
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use snafu::ResultExt;
+use std::io::{BufWriter, Read, Write};
+
+/// Write points of line protocol file in the time range.
+///
+/// Lines are copied as they are, lines without timestamp are dropped. If the input is
+/// sorted by timestamp, `--sorted` stops reading at the first point after the range.
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("range").required(true).multiple(true))]
+pub(crate) struct Clip {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Start time in RFC3339, inclusive.
+    #[arg(long, value_parser = parse_rfc3339, group = "range")]
+    start: Option<i64>,
+    /// End time in RFC3339, exclusive.
+    #[arg(long, value_parser = parse_rfc3339, group = "range")]
+    end: Option<i64>,
+    /// The input is sorted by timestamp, e.g. written by `influx sort`.
+    #[arg(long)]
+    sorted: bool,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Clip {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = self.clip_lines(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    fn clip_lines<R: Read, W: Write>(&self, source: R, dest: W) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        read_lines_while(source, |text, line| {
+            let Some(ts) = line.timestamp else {
+                return Ok(true);
+            };
+            if self.end.is_some_and(|end| ts >= end) {
+                return Ok(!self.sorted);
+            }
+            if self.start.is_none_or(|start| ts >= start) {
+                writer
+                    .write_all(text.as_bytes())
+                    .context(WriteOutputSnafu)?;
+                if !text.ends_with('\n') {
+                    writer.write_all(b"\n").context(WriteOutputSnafu)?;
+                }
+            }
+            Ok(true)
+        })?;
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_lines() {
+        let input = "cpu usage=1 1451606340000000000
+cpu usage=2 1451606400000000000
+cpu usage=3
+cpu usage=4 1451606460000000000
+cpu usage=5 1451606400000000000";
+        let clip = |args: &str| {
+            let clip = Clip::try_parse_from(
+                format!("clip -i - --start 2016-01-01T00:00:00Z --end 2016-01-01T00:01:00Z{args}")
+                    .split(' '),
+            )
+            .unwrap();
+            let output = clip.clip_lines(input.as_bytes(), Vec::new()).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            "cpu usage=2 1451606400000000000\ncpu usage=5 1451606400000000000\n",
+            clip("")
+        );
+        assert_eq!("cpu usage=2 1451606400000000000\n", clip(" --sorted"));
+        assert!(Clip::try_parse_from(["clip", "-i", "-"]).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+//! Schema file to map CSV files to line protocol.
+
+use crate::error::{OpenFileSnafu, ParseSchemaSnafu, Result};
+use crate::influx::schema::FieldType;
+use crate::influx::timestamp::Precision;
+use serde::Deserialize;
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Schema of CSV files in TOML.
+///
+/// ```toml
+/// [measurements.cpu_usage]
+/// name = "cpu"
+/// tags = ["hostname"]
+/// timestamp = "time"
+/// precision = "s"
+///
+/// [measurements.cpu_usage.fields]
+/// usage_user = "i64"
+/// usage_system = "f64"
+///
+/// [measurements.cpu_usage.rename]
+/// hostname = "host"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CsvSchema {
+    /// Schemas by the table name, which is the file name without extension.
+    #[serde(default)]
+    pub(crate) measurements: HashMap<String, MeasurementSchema>,
+}
+
+/// Schema of CSV files of a measurement.
+///
+/// Options that are not set fall back to the command line.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MeasurementSchema {
+    /// Measurement name, defaults to the table name.
+    pub(crate) name: Option<String>,
+    /// Tag columns.
+    pub(crate) tags: Option<Vec<String>>,
+    /// Field columns with their types, other columns are skipped if set.
+    pub(crate) fields: Option<HashMap<String, FieldType>>,
+    /// Timestamp column.
+    pub(crate) timestamp: Option<String>,
+    /// strptime format of the timestamp column.
+    pub(crate) timestamp_format: Option<String>,
+    /// Precision of the timestamp column if timestamps are epoch.
+    pub(crate) precision: Option<Precision>,
+    /// New names of tag and field columns.
+    #[serde(default)]
+    pub(crate) rename: HashMap<String, String>,
+}
+
+impl CsvSchema {
+    /// Load the schema file.
+    pub(crate) fn load(path: &Path) -> Result<CsvSchema> {
+        let content = fs::read_to_string(path).context(OpenFileSnafu { path })?;
+        toml::from_str(&content).context(ParseSchemaSnafu { path })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_load_schema() {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(
+            br#"
+[measurements.cpu_usage]
+name = "cpu"
+tags = ["hostname"]
+timestamp = "time"
+precision = "s"
+fields = { usage_user = "i64", enabled = "bool" }
+rename = { hostname = "host" }
+
+[measurements.mem]
+"#,
+        )
+        .unwrap();
+        let schema = CsvSchema::load(file.path()).unwrap();
+        let cpu = &schema.measurements["cpu_usage"];
+        assert_eq!(Some("cpu"), cpu.name.as_deref());
+        assert_eq!(Some(vec!["hostname".to_string()]), cpu.tags);
+        assert_eq!(Some(Precision::S), cpu.precision);
+        let fields = cpu.fields.as_ref().unwrap();
+        assert_eq!(FieldType::Int64, fields["usage_user"]);
+        assert_eq!(FieldType::Boolean, fields["enabled"]);
+        assert_eq!("host", cpu.rename["hostname"]);
+        assert!(schema.measurements["mem"].fields.is_none());
+
+        file.write_all(b"fields = { usage = \"i32\" }\n").unwrap();
+        let err = CsvSchema::load(file.path()).unwrap_err();
+        assert!(err.to_string().starts_with("Failed to parse schema file"));
+    }
+}
@@ -0,0 +1,231 @@
+//! Remove duplicate points of line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::{push_line, read_lines, series_key};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+use tracing::info;
+
+/// Which one of duplicate points to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Keep {
+    /// The first point in the input.
+    First,
+    /// The last point in the input.
+    Last,
+}
+
+/// Remove points of the same series and timestamp from line protocol file.
+///
+/// Lines without timestamp are never duplicates. Without a window, all keys are kept in
+/// memory, and all lines are also kept in memory to keep the last point.
+#[derive(Debug, Parser)]
+pub(crate) struct Dedup {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Which one of duplicate points to keep.
+    #[arg(long, value_enum, default_value_t = Keep::First)]
+    keep: Keep,
+    /// Only detect duplicates within this number of lines to bound memory.
+    #[arg(long)]
+    window: Option<usize>,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Dedup {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut dedup = Deduplicator::new(self.keep, self.window, output);
+        read_lines(input, |line| dedup.push(&line))?;
+        let removed = dedup.removed;
+        dedup.finish()?.finish().context(WriteOutputSnafu)?;
+
+        info!("Removed {removed} duplicate lines");
+        Ok(())
+    }
+}
+
+/// A line in the window.
+#[derive(Debug)]
+struct Entry {
+    /// Series key and timestamp of the line.
+    key: Option<String>,
+    /// Text of the line to write when it leaves the window.
+    text: Option<String>,
+}
+
+/// Deduplicator of lines in a window.
+#[derive(Debug)]
+struct Deduplicator<W: Write> {
+    keep: Keep,
+    window: Option<usize>,
+    writer: BufWriter<W>,
+    entries: VecDeque<Entry>,
+    /// Number of lines that have left the window.
+    offset: usize,
+    /// Position of the kept line of each key.
+    index: HashMap<String, usize>,
+    removed: u64,
+}
+
+impl<W: Write> Deduplicator<W> {
+    fn new(keep: Keep, window: Option<usize>, dest: W) -> Deduplicator<W> {
+        Deduplicator {
+            keep,
+            window,
+            writer: BufWriter::new(dest),
+            entries: VecDeque::new(),
+            offset: 0,
+            index: HashMap::new(),
+            removed: 0,
+        }
+    }
+
+    fn push(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        let key = line.timestamp.map(|ts| {
+            let mut key = series_key(line);
+            write!(key, " {ts}").unwrap();
+            key
+        });
+        let mut text = String::new();
+        push_line(&mut text, line);
+
+        let position = self.offset + self.entries.len();
+        match key.as_ref().and_then(|key| self.index.get(key)) {
+            Some(kept) => {
+                self.removed += 1;
+                if self.keep == Keep::First {
+                    return Ok(());
+                }
+                self.entries[kept - self.offset].text = None;
+            }
+            None if self.keep == Keep::First => {
+                // The first line is written at once, only its key is kept.
+                self.writer
+                    .write_all(text.as_bytes())
+                    .context(WriteOutputSnafu)?;
+                text.clear();
+            }
+            None => (),
+        }
+        if let Some(key) = &key {
+            self.index.insert(key.clone(), position);
+        }
+        self.entries.push_back(Entry {
+            key,
+            text: Some(text).filter(|text| !text.is_empty()),
+        });
+
+        while self
+            .window
+            .is_some_and(|window| self.entries.len() > window)
+        {
+            self.pop()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the oldest line from the window.
+    fn pop(&mut self) -> Result<()> {
+        let Some(entry) = self.entries.pop_front() else {
+            return Ok(());
+        };
+        if let Some(key) = entry.key {
+            if self.index.get(&key) == Some(&self.offset) {
+                self.index.remove(&key);
+            }
+        }
+        self.offset += 1;
+        match entry.text {
+            Some(text) => self
+                .writer
+                .write_all(text.as_bytes())
+                .context(WriteOutputSnafu),
+            None => Ok(()),
+        }
+    }
+
+    /// Write remaining lines and returns the output.
+    fn finish(mut self) -> Result<W> {
+        while !self.entries.is_empty() {
+            self.pop()?;
+        }
+        self.writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn dedup(keep: Keep, window: Option<usize>) -> (String, u64) {
+        let input = "cpu,host=a,region=eu usage=1 1
+cpu,host=b usage=2 1
+cpu,region=eu,host=a usage=3 1
+cpu usage=4
+cpu usage=5
+cpu,host=a,region=eu usage=6 1
+";
+        let mut dedup = Deduplicator::new(keep, window, Vec::new());
+        read_lines(Cursor::new(input), |line| dedup.push(&line)).unwrap();
+        let removed = dedup.removed;
+        let output = dedup.finish().unwrap();
+        (String::from_utf8(output).unwrap(), removed)
+    }
+
+    #[test]
+    fn test_dedup() {
+        assert_eq!(
+            (
+                "cpu,host=a,region=eu usage=1 1
+cpu,host=b usage=2 1
+cpu usage=4
+cpu usage=5
+"
+                .to_string(),
+                2
+            ),
+            dedup(Keep::First, None)
+        );
+        assert_eq!(
+            (
+                "cpu,host=b usage=2 1
+cpu usage=4
+cpu usage=5
+cpu,host=a,region=eu usage=6 1
+"
+                .to_string(),
+                2
+            ),
+            dedup(Keep::Last, None)
+        );
+        assert_eq!(
+            (
+                "cpu,host=b usage=2 1
+cpu,region=eu,host=a usage=3 1
+cpu usage=4
+cpu usage=5
+cpu,host=a,region=eu usage=6 1
+"
+                .to_string(),
+                1
+            ),
+            dedup(Keep::Last, Some(2))
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Summary statistics of field values of line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::influx::schema::{FieldType, Schema};
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as FmtWrite};
+use std::io::Write;
+
+/// Width of the longest bar of histograms.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Report summary statistics of values of each field of each measurement in line
+/// protocol file.
+///
+/// Numeric fields have min, max, mean, standard deviation and percentiles, boolean
+/// fields have the number of true values. Nulls are points of the measurement without the
+/// field. Numeric values are kept in memory to compute exact percentiles.
+#[derive(Debug, Parser)]
+pub(crate) struct Describe {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Print a histogram of values of each numeric field with the number of bins.
+    #[arg(long)]
+    histogram: Option<usize>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Describe {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut measurements: BTreeMap<String, MeasurementValues> = BTreeMap::new();
+        read_lines(input, |line| {
+            let name = line.series.measurement.as_str();
+            if !measurements.contains_key(name) {
+                measurements.insert(name.to_string(), MeasurementValues::default());
+            }
+            measurements.get_mut(name).unwrap().update(&line)
+        })?;
+
+        let mut report = String::new();
+        for (name, measurement) in &mut measurements {
+            measurement
+                .describe(name, self.histogram, &mut report)
+                .unwrap();
+        }
+        let mut output = create_output(&self.output, Compression::None)?;
+        output
+            .write_all(report.as_bytes())
+            .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Values of fields of a measurement.
+#[derive(Debug, Default)]
+struct MeasurementValues {
+    schema: Schema,
+    /// Values of each field in the schema.
+    fields: Vec<FieldValues>,
+    points: u64,
+}
+
+/// Values of a field.
+#[derive(Debug, Default)]
+struct FieldValues {
+    count: u64,
+    /// Numeric values as float.
+    numbers: Vec<f64>,
+    trues: u64,
+}
+
+impl MeasurementValues {
+    fn update(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        self.schema.update(line)?;
+        self.fields
+            .resize_with(self.schema.fields.len(), FieldValues::default);
+        self.points += 1;
+
+        for (key, value) in &line.field_set {
+            let idx = self.schema.field_index(key.as_str()).unwrap_or_default();
+            let field = &mut self.fields[idx];
+            field.count += 1;
+            match value {
+                FieldValue::I64(v) => field.numbers.push(*v as f64),
+                FieldValue::U64(v) => field.numbers.push(*v as f64),
+                FieldValue::F64(v) => field.numbers.push(*v),
+                FieldValue::Boolean(v) => field.trues += u64::from(*v),
+                FieldValue::String(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Write statistics of fields, numeric values are sorted.
+    fn describe(&mut self, name: &str, bins: Option<usize>, f: &mut String) -> fmt::Result {
+        writeln!(f, "Measurement: {name}")?;
+        writeln!(f, "  Points: {}", self.points)?;
+        for ((key, field_type), field) in self.schema.fields.iter().zip(&mut self.fields) {
+            writeln!(f)?;
+            writeln!(f, "  Field: {key} ({field_type})")?;
+            writeln!(f, "    Count: {}", field.count)?;
+            writeln!(f, "    Nulls: {}", self.points - field.count)?;
+            match field_type {
+                FieldType::Boolean => writeln!(f, "    True: {}", field.trues)?,
+                FieldType::String => {}
+                _ => field.describe_numbers(bins, f)?,
+            }
+        }
+        writeln!(f)
+    }
+}
+
+impl FieldValues {
+    fn describe_numbers(&mut self, bins: Option<usize>, f: &mut String) -> fmt::Result {
+        let values = &mut self.numbers;
+        values.sort_unstable_by(f64::total_cmp);
+        let (Some(min), Some(max)) = (values.first().copied(), values.last().copied()) else {
+            return Ok(());
+        };
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+        writeln!(f, "    Min: {}", fmt_number(min))?;
+        writeln!(f, "    Max: {}", fmt_number(max))?;
+        writeln!(f, "    Mean: {}", fmt_number(mean))?;
+        writeln!(f, "    Stddev: {}", fmt_number(variance.sqrt()))?;
+        for p in [50, 90, 99] {
+            writeln!(f, "    P{p}: {}", fmt_number(percentile(values, p)))?;
+        }
+
+        let Some(bins) = bins.filter(|bins| *bins > 0) else {
+            return Ok(());
+        };
+        let width = (max - min) / bins as f64;
+        let mut counts = vec![0u64; bins];
+        for v in values.iter() {
+            let bin = if width > 0.0 {
+                (((v - min) / width) as usize).min(bins - 1)
+            } else {
+                0
+            };
+            counts[bin] += 1;
+        }
+        let most = counts.iter().copied().max().unwrap_or_default();
+        writeln!(f, "    Histogram:")?;
+        for (i, count) in counts.iter().enumerate() {
+            let bar = (count * HISTOGRAM_WIDTH as u64).div_ceil(most) as usize;
+            writeln!(
+                f,
+                "      {:>12} | {:<HISTOGRAM_WIDTH$} {count}",
+                fmt_number(min + width * i as f64),
+                "#".repeat(bar)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the nearest-rank percentile of sorted values.
+fn percentile(values: &[f64], p: usize) -> f64 {
+    let rank = (values.len() * p).div_ceil(100);
+    values[rank.saturating_sub(1)]
+}
+
+/// Format the number with at most 6 decimal places.
+fn fmt_number(v: f64) -> String {
+    let s = format!("{v:.6}");
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let input = "cpu,host=a usage=1,up=true,state=\"ok\" 1
+cpu,host=b usage=2i,up=false 2
+cpu,host=c usage=3,up=true 3
+cpu,host=d up=true 4
+";
+        let mut measurement = MeasurementValues::default();
+        read_lines(input.as_bytes(), |line| measurement.update(&line)).unwrap();
+        let mut report = String::new();
+        measurement.describe("cpu", Some(2), &mut report).unwrap();
+        assert_eq!(
+            "Measurement: cpu
+  Points: 4
+
+  Field: usage (float)
+    Count: 3
+    Nulls: 1
+    Min: 1
+    Max: 3
+    Mean: 2
+    Stddev: 0.816497
+    P50: 2
+    P90: 3
+    P99: 3
+    Histogram:
+                 1 | ####################                     1
+                 2 | ######################################## 2
+
+  Field: up (boolean)
+    Count: 4
+    Nulls: 0
+    True: 3
+
+  Field: state (string)
+    Count: 1
+    Nulls: 3
+
+",
+            report
+        );
+    }
+}
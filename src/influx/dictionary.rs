@@ -0,0 +1,204 @@
+//! Dictionary encoding of string fields of line protocol.
+
+use crate::error::{ParseJsonSnafu, Result, UnknownCodeSnafu, WriteJsonSnafu, WriteOutputSnafu};
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, open_input, Compression, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::{EscapedStr, FieldValue, ParsedLine};
+use snafu::{OptionExt, ResultExt};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufWriter, Read, Write};
+
+/// Distinct values of each field, the code of a value is its index.
+type Dictionary = BTreeMap<String, Vec<String>>;
+
+/// Replace string field values of line protocol file by integer codes, and write the
+/// distinct values to a JSON dictionary file for `influx dict-decode`.
+///
+/// Codes of each field start from 0 in the order values first appear. The dictionary is
+/// kept in memory.
+#[derive(Debug, Parser)]
+pub(crate) struct DictEncode {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Output dictionary file path.
+    #[arg(short, long)]
+    dictionary: String,
+    /// String fields to encode, e.g. message,level, all string fields by default.
+    #[arg(long, value_delimiter = ',')]
+    field: Vec<String>,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl DictEncode {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let (mut output, dictionary) = self.encode_lines(input, output)?;
+        output.finish().context(WriteOutputSnafu)?;
+
+        let mut output = create_output(&self.dictionary, Compression::Auto)?;
+        serde_json::to_writer_pretty(&mut output, &dictionary).context(WriteJsonSnafu)?;
+        writeln!(output).context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Write lines with encoded values, returns the dictionary.
+    fn encode_lines<R: Read, W: Write>(&self, source: R, dest: W) -> Result<(W, Dictionary)> {
+        let mut dictionary = Dictionary::new();
+        // Codes of values of each field.
+        let mut codes: HashMap<String, HashMap<String, i64>> = HashMap::new();
+        let mut writer = BufWriter::new(dest);
+        let mut buffer = String::new();
+        read_lines(source, |mut line| {
+            for (key, value) in &mut line.field_set {
+                let FieldValue::String(v) = value else {
+                    continue;
+                };
+                if !self.field.is_empty() && !self.field.iter().any(|f| f == key.as_str()) {
+                    continue;
+                }
+                let values = dictionary.entry(key.to_string()).or_default();
+                let codes = codes.entry(key.to_string()).or_default();
+                let code = match codes.get(v.as_str()) {
+                    Some(code) => *code,
+                    None => {
+                        codes.insert(v.to_string(), values.len() as i64);
+                        values.push(v.to_string());
+                        values.len() as i64 - 1
+                    }
+                };
+                *value = FieldValue::I64(code);
+            }
+            buffer.clear();
+            push_line(&mut buffer, &line);
+            writer
+                .write_all(buffer.as_bytes())
+                .context(WriteOutputSnafu)
+        })?;
+
+        let writer = writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)?;
+        Ok((writer, dictionary))
+    }
+}
+
+/// Replace integer codes of line protocol file by string values in the dictionary
+/// written by `influx dict-encode`.
+///
+/// Only integer values of fields in the dictionary are decoded, fails on codes not in
+/// the dictionary.
+#[derive(Debug, Parser)]
+pub(crate) struct DictDecode {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Dictionary file path.
+    #[arg(short, long)]
+    dictionary: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl DictDecode {
+    pub(crate) fn run(self) -> Result<()> {
+        let dictionary = open_input(&self.dictionary, Compression::Auto)?;
+        let dictionary: Dictionary = serde_json::from_reader(dictionary).context(ParseJsonSnafu)?;
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = decode_lines(&dictionary, input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Write lines with decoded values.
+fn decode_lines<R: Read, W: Write>(dictionary: &Dictionary, source: R, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut buffer = String::new();
+    read_lines(source, |mut line| {
+        decode(dictionary, &mut line)?;
+        buffer.clear();
+        push_line(&mut buffer, &line);
+        writer
+            .write_all(buffer.as_bytes())
+            .context(WriteOutputSnafu)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn decode(dictionary: &Dictionary, line: &mut ParsedLine) -> Result<()> {
+    for (key, value) in &mut line.field_set {
+        let (FieldValue::I64(code), Some(values)) = (&value, dictionary.get(key.as_str())) else {
+            continue;
+        };
+        let decoded = usize::try_from(*code)
+            .ok()
+            .and_then(|i| values.get(i))
+            .context(UnknownCodeSnafu {
+                field: key.as_str(),
+                code: *code,
+            })?;
+        *value = FieldValue::String(EscapedStr::CopiedValue(decoded.clone()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dictionary() {
+        let input = "log,host=a level=\"info\",message=\"started\",code=1i 1
+log,host=b level=\"warn\",message=\"slow \\\"disk\\\"\",code=2i 2
+log,host=a level=\"info\",message=\"started\",code=3i 3
+";
+        let encoded = |args: &str| {
+            let command = DictEncode::try_parse_from(
+                format!("dict-encode -i - -d dict.json {args}")
+                    .split(' ')
+                    .filter(|arg| !arg.is_empty()),
+            )
+            .unwrap();
+            let (output, dictionary) = command.encode_lines(input.as_bytes(), Vec::new()).unwrap();
+            (String::from_utf8(output).unwrap(), dictionary)
+        };
+
+        let (output, dictionary) = encoded("");
+        assert_eq!(
+            "log,host=a level=0i,message=0i,code=1i 1
+log,host=b level=1i,message=1i,code=2i 2
+log,host=a level=0i,message=0i,code=3i 3
+",
+            output
+        );
+        assert_eq!(vec!["info", "warn"], dictionary["level"]);
+        assert_eq!(vec!["started", "slow \"disk\""], dictionary["message"]);
+        assert!(!dictionary.contains_key("code"));
+
+        // Codes of other fields are not decoded.
+        let decoded = decode_lines(&dictionary, output.as_bytes(), Vec::new()).unwrap();
+        assert_eq!(input, String::from_utf8(decoded).unwrap());
+
+        let (output, dictionary) = encoded("--field level");
+        assert!(output.starts_with("log,host=a level=0i,message=\"started\",code=1i 1\n"));
+        assert_eq!(1, dictionary.len());
+
+        let err = decode_lines(&dictionary, "log level=2i".as_bytes(), Vec::new()).unwrap_err();
+        assert!(err.is_invalid_line(), "{err}");
+    }
+}
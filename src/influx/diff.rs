@@ -0,0 +1,245 @@
+//! Compare line protocol files.
+
+use crate::error::{InputsDifferSnafu, Result, WriteOutputSnafu};
+use crate::influx::downsample::to_static;
+use crate::influx::{push_fields, read_lines, series_key};
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use snafu::{ensure, ResultExt};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{Read, Write};
+
+/// Fields of points by series key and timestamp.
+type Points = BTreeMap<(String, Option<i64>), BTreeMap<String, FieldValue<'static>>>;
+
+/// Compare two line protocol files point by point and report missing, extra and
+/// mismatched points.
+///
+/// Points are matched by series and timestamp regardless of the order of lines and tags.
+/// Fields of points with the same series and timestamp are merged, later values win,
+/// like writing the file to InfluxDB. Both files are loaded in memory.
+///
+/// Fails if the files differ.
+#[derive(Debug, Parser)]
+pub(crate) struct Diff {
+    /// Expected line protocol file path, e.g. the export of the old system.
+    expected: String,
+    /// Actual line protocol file path, `-` for stdin.
+    actual: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Max absolute difference of equal float values.
+    #[arg(long, default_value_t = 0.0)]
+    tolerance: f64,
+    /// Max difference of equal float values relative to the larger value.
+    #[arg(long, default_value_t = 0.0)]
+    relative_tolerance: f64,
+    /// Compression of the inputs, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Diff {
+    pub(crate) fn run(self) -> Result<()> {
+        let expected = read_points(open_input(&self.expected, self.input_compression)?)?;
+        let actual = read_points(open_input(&self.actual, self.input_compression)?)?;
+        let mut output = create_output(&self.output, Compression::None)?;
+        let summary = self.diff_points(&expected, &actual, &mut output)?;
+        writeln!(
+            output,
+            "{} missing, {} extra and {} mismatched of {} points",
+            summary.missing,
+            summary.extra,
+            summary.mismatched,
+            expected.len()
+        )
+        .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)?;
+
+        let differences = summary.missing + summary.extra + summary.mismatched;
+        ensure!(differences == 0, InputsDifferSnafu { differences });
+        Ok(())
+    }
+
+    /// Write differences of the points in the order of series and timestamps.
+    ///
+    /// Points only in the expected file are written with `-`, points only in the actual
+    /// file with `+` and each different field of other points with `~`.
+    fn diff_points<W: Write>(
+        &self,
+        expected: &Points,
+        actual: &Points,
+        mut dest: W,
+    ) -> Result<Summary> {
+        let mut summary = Summary::default();
+        for (key, fields) in expected {
+            let Some(actual_fields) = actual.get(key) else {
+                summary.missing += 1;
+                writeln!(dest, "- {}", format_point(key, fields)).context(WriteOutputSnafu)?;
+                continue;
+            };
+
+            let mut mismatched = false;
+            let names = fields.keys().chain(
+                actual_fields
+                    .keys()
+                    .filter(|name| !fields.contains_key(*name)),
+            );
+            for name in names {
+                let (left, right) = (fields.get(name), actual_fields.get(name));
+                if let (Some(left), Some(right)) = (left, right) {
+                    if self.is_equal(left, right) {
+                        continue;
+                    }
+                }
+                mismatched = true;
+                let (series, timestamp) = key;
+                let timestamp = timestamp.map(|ts| format!(" {ts}")).unwrap_or_default();
+                writeln!(
+                    dest,
+                    "~ {series}{timestamp} {name}: {} != {}",
+                    format_value(left),
+                    format_value(right)
+                )
+                .context(WriteOutputSnafu)?;
+            }
+            if mismatched {
+                summary.mismatched += 1;
+            }
+        }
+        for (key, fields) in actual {
+            if !expected.contains_key(key) {
+                summary.extra += 1;
+                writeln!(dest, "+ {}", format_point(key, fields)).context(WriteOutputSnafu)?;
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Returns whether the values are equal, floats are equal within the tolerances.
+    fn is_equal(&self, left: &FieldValue<'_>, right: &FieldValue<'_>) -> bool {
+        match (left, right) {
+            (FieldValue::F64(a), FieldValue::F64(b)) => {
+                let diff = (a - b).abs();
+                a == b
+                    || (a.is_nan() && b.is_nan())
+                    || diff <= self.tolerance
+                    || diff <= self.relative_tolerance * a.abs().max(b.abs())
+            }
+            _ => left == right,
+        }
+    }
+}
+
+/// Numbers of different points.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Summary {
+    missing: usize,
+    extra: usize,
+    mismatched: usize,
+}
+
+/// Read points of the lines.
+fn read_points<R: Read>(source: R) -> Result<Points> {
+    let mut points = Points::new();
+    read_lines(source, |line| {
+        let fields = points
+            .entry((series_key(&line), line.timestamp))
+            .or_default();
+        for (key, value) in &line.field_set {
+            fields.insert(key.to_string(), to_static(value));
+        }
+        Ok(())
+    })?;
+    Ok(points)
+}
+
+/// Format the point as a line.
+fn format_point(
+    (series, timestamp): &(String, Option<i64>),
+    fields: &BTreeMap<String, FieldValue<'_>>,
+) -> String {
+    let mut line = series.clone();
+    push_fields(&mut line, fields.iter().map(|(k, v)| (k.as_str(), v)));
+    if let Some(ts) = timestamp {
+        write!(line, " {ts}").unwrap();
+    }
+    line
+}
+
+/// Format the value of a field as in line protocol, `-` if the field is absent.
+fn format_value(value: Option<&FieldValue<'_>>) -> String {
+    let Some(value) = value else {
+        return "-".to_string();
+    };
+    let mut line = String::new();
+    push_fields(&mut line, [("v", value)]);
+    line.split_off(" v=".len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_points() {
+        let expected = "cpu,host=a,region=eu usage=1.0,idle=2i 1
+cpu,host=a,region=eu usage=1.5 2
+cpu,host=b usage=3 1
+mem,host=a used=4i 1
+";
+        let actual = "mem,host=a used=5i 1
+cpu,region=eu,host=a usage=1.5000001 2
+cpu,host=a,region=eu usage=1.0 1
+cpu,host=a,region=eu idle=2i 1
+disk,host=a used=6 1
+";
+        let diff =
+            Diff::try_parse_from(["diff", "expected.lp", "actual.lp", "--tolerance", "0.001"])
+                .unwrap();
+        let mut output = Vec::new();
+        let summary = diff
+            .diff_points(
+                &read_points(expected.as_bytes()).unwrap(),
+                &read_points(actual.as_bytes()).unwrap(),
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(
+            Summary {
+                missing: 1,
+                extra: 1,
+                mismatched: 1
+            },
+            summary
+        );
+        assert_eq!(
+            "- cpu,host=b usage=3 1
+~ mem,host=a 1 used: 4i != 5i
++ disk,host=a used=6 1
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let diff = Diff::try_parse_from(["diff", "expected.lp", "actual.lp"]).unwrap();
+        let mut output = Vec::new();
+        let summary = diff
+            .diff_points(
+                &read_points("cpu usage=1,idle=2 1\n".as_bytes()).unwrap(),
+                &read_points("cpu usage=1.0001,state=\"ok\" 1\n".as_bytes()).unwrap(),
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(1, summary.mismatched);
+        assert_eq!(
+            "~ cpu 1 idle: 2 != -
+~ cpu 1 usage: 1 != 1.0001
+~ cpu 1 state: - != \"ok\"
+",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
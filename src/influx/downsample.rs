@@ -0,0 +1,329 @@
+//! Downsample line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::timestamp::parse_duration;
+use crate::influx::{push_fields, read_lines, series_key};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{EscapedStr, FieldValue, ParsedLine};
+use snafu::ResultExt;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+
+/// Function to aggregate values of a field in a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Aggregate {
+    /// Mean of values, as float.
+    Mean,
+    /// Min value.
+    Min,
+    /// Max value.
+    Max,
+    /// Value with the max timestamp, including strings and booleans.
+    Last,
+    /// Sum of values.
+    Sum,
+}
+
+/// Downsample line protocol file by aggregating points of each series in time buckets.
+///
+/// Points are grouped in memory, each aggregated point has the start time of the bucket.
+/// String and boolean fields are dropped unless the aggregate is `last`.
+#[derive(Debug, Parser)]
+pub(crate) struct Downsample {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Width of time buckets, e.g. 1m.
+    #[arg(long, value_parser = parse_duration)]
+    every: i64,
+    /// Function to aggregate values in a bucket.
+    #[arg(long, value_enum, default_value_t = Aggregate::Mean)]
+    aggregate: Aggregate,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Downsample {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let mut downsampler = Downsampler::new(self.every, vec![self.aggregate]);
+        read_lines(input, |line| {
+            downsampler.update(&line);
+            Ok(())
+        })?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = downsampler.write_lines(output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Aggregator of points in buckets.
+#[derive(Debug)]
+pub(crate) struct Downsampler {
+    every: i64,
+    /// Aggregates of each field, fields are suffixed by names of aggregates if there
+    /// are more than one.
+    aggregates: Vec<Aggregate>,
+    series: Vec<SeriesBuckets>,
+    /// Index of series by the series key.
+    series_index: HashMap<String, usize>,
+    now: i64,
+}
+
+/// Fields and their states of each aggregate in a bucket.
+type BucketFields = Vec<(String, Vec<Option<FieldState>>)>;
+
+/// Buckets of a series.
+#[derive(Debug)]
+struct SeriesBuckets {
+    /// Measurement and sorted tags in line protocol.
+    key: String,
+    /// Fields by the start time of buckets.
+    buckets: BTreeMap<i64, BucketFields>,
+}
+
+/// Aggregate state of a field in a bucket.
+#[derive(Debug, Clone)]
+struct FieldState {
+    value: FieldValue<'static>,
+    /// Timestamp of the value, for `last`.
+    timestamp: i64,
+    /// Number and sum of values, for `mean`.
+    count: u64,
+    sum: f64,
+}
+
+impl Downsampler {
+    pub(crate) fn new(every: i64, aggregates: Vec<Aggregate>) -> Downsampler {
+        Downsampler {
+            every,
+            aggregates,
+            series: Vec::new(),
+            series_index: HashMap::new(),
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, line: &ParsedLine<'_>) {
+        let key = series_key(line);
+        let idx = match self.series_index.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                self.series_index.insert(key.clone(), self.series.len());
+                self.series.push(SeriesBuckets {
+                    key,
+                    buckets: BTreeMap::new(),
+                });
+                self.series.len() - 1
+            }
+        };
+
+        let ts = line.timestamp.unwrap_or(self.now);
+        let bucket = ts.div_euclid(self.every) * self.every;
+        let fields = self.series[idx].buckets.entry(bucket).or_default();
+        for (name, value) in &line.field_set {
+            let states = match fields.iter().position(|(n, _)| n == name.as_str()) {
+                Some(i) => &mut fields[i].1,
+                None => {
+                    fields.push((name.to_string(), vec![None; self.aggregates.len()]));
+                    &mut fields.last_mut().unwrap().1
+                }
+            };
+            for (aggregate, state) in self.aggregates.iter().zip(states) {
+                if *aggregate != Aggregate::Last && as_f64(value).is_none() {
+                    continue;
+                }
+                match state {
+                    Some(state) => state.update(*aggregate, value, ts),
+                    None => *state = Some(FieldState::new(value, ts)),
+                }
+            }
+        }
+    }
+
+    /// Write aggregated points, series are in the order they first appear.
+    pub(crate) fn write_lines<W: Write>(&self, dest: W) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        let mut line = String::new();
+        for series in &self.series {
+            for (bucket, fields) in &series.buckets {
+                let mut values = Vec::new();
+                for (name, states) in fields {
+                    for (aggregate, state) in self.aggregates.iter().zip(states) {
+                        let Some(state) = state else {
+                            continue;
+                        };
+                        let name = if self.aggregates.len() > 1 {
+                            format!("{name}_{}", aggregate.name())
+                        } else {
+                            name.clone()
+                        };
+                        values.push((name, state.finish(*aggregate)));
+                    }
+                }
+                if values.is_empty() {
+                    continue;
+                }
+
+                line.clear();
+                line.push_str(&series.key);
+                push_fields(&mut line, values.iter().map(|(name, v)| (name.as_str(), v)));
+                writeln!(line, " {bucket}").unwrap();
+                writer
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+impl Aggregate {
+    /// Returns the name of the aggregate, which suffixes fields.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Aggregate::Mean => "mean",
+            Aggregate::Min => "min",
+            Aggregate::Max => "max",
+            Aggregate::Last => "last",
+            Aggregate::Sum => "sum",
+        }
+    }
+}
+
+impl FieldState {
+    fn new(value: &FieldValue<'_>, timestamp: i64) -> FieldState {
+        FieldState {
+            value: to_static(value),
+            timestamp,
+            count: 1,
+            sum: as_f64(value).unwrap_or_default(),
+        }
+    }
+
+    fn update(&mut self, aggregate: Aggregate, value: &FieldValue<'_>, timestamp: i64) {
+        match aggregate {
+            Aggregate::Mean => {
+                self.count += 1;
+                self.sum += as_f64(value).unwrap_or_default();
+            }
+            Aggregate::Min => self.combine(value, i64::min, u64::min, f64::min),
+            Aggregate::Max => self.combine(value, i64::max, u64::max, f64::max),
+            Aggregate::Sum => {
+                self.combine(value, i64::saturating_add, u64::saturating_add, |a, b| {
+                    a + b
+                })
+            }
+            Aggregate::Last => {
+                if timestamp >= self.timestamp {
+                    self.value = to_static(value);
+                    self.timestamp = timestamp;
+                }
+            }
+        }
+    }
+
+    /// Combine numeric values, values of different types are combined as float.
+    fn combine(
+        &mut self,
+        value: &FieldValue<'_>,
+        i: impl Fn(i64, i64) -> i64,
+        u: impl Fn(u64, u64) -> u64,
+        f: impl Fn(f64, f64) -> f64,
+    ) {
+        self.value = match (&self.value, value) {
+            (FieldValue::I64(a), FieldValue::I64(b)) => FieldValue::I64(i(*a, *b)),
+            (FieldValue::U64(a), FieldValue::U64(b)) => FieldValue::U64(u(*a, *b)),
+            (a, b) => FieldValue::F64(f(
+                as_f64(a).unwrap_or_default(),
+                as_f64(b).unwrap_or_default(),
+            )),
+        };
+    }
+
+    fn finish(&self, aggregate: Aggregate) -> FieldValue<'static> {
+        match aggregate {
+            Aggregate::Mean => FieldValue::F64(self.sum / self.count as f64),
+            _ => to_static(&self.value),
+        }
+    }
+}
+
+/// Returns the numeric value as float.
+pub(crate) fn as_f64(value: &FieldValue<'_>) -> Option<f64> {
+    match value {
+        FieldValue::I64(v) => Some(*v as f64),
+        FieldValue::U64(v) => Some(*v as f64),
+        FieldValue::F64(v) => Some(*v),
+        FieldValue::String(_) | FieldValue::Boolean(_) => None,
+    }
+}
+
+pub(crate) fn to_static(value: &FieldValue<'_>) -> FieldValue<'static> {
+    match value {
+        FieldValue::I64(v) => FieldValue::I64(*v),
+        FieldValue::U64(v) => FieldValue::U64(*v),
+        FieldValue::F64(v) => FieldValue::F64(*v),
+        FieldValue::String(v) => FieldValue::String(EscapedStr::CopiedValue(v.to_string())),
+        FieldValue::Boolean(v) => FieldValue::Boolean(*v),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const INPUT: &str = r#"cpu,host=a,region=eu usage=1i,idle=2.5,state="up" 1451606400000000000
+cpu,region=eu,host=a usage=3i,idle=0.5,state="down" 1451606430000000000
+cpu,host=b usage=5i 1451606410000000000
+cpu,host=a,region=eu usage=7i,idle=1.5 1451606460000000000
+cpu,host=a,region=eu usage=2.5 1451606470000000000
+"#;
+
+    fn downsample(aggregate: Aggregate) -> String {
+        let mut downsampler = Downsampler::new(60_000_000_000, vec![aggregate]);
+        read_lines(Cursor::new(INPUT), |line| {
+            downsampler.update(&line);
+            Ok(())
+        })
+        .unwrap();
+        String::from_utf8(downsampler.write_lines(Vec::new()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_downsample() {
+        assert_eq!(
+            "cpu,host=a,region=eu usage=2,idle=1.5 1451606400000000000
+cpu,host=a,region=eu usage=4.75,idle=1.5 1451606460000000000
+cpu,host=b usage=5 1451606400000000000
+",
+            downsample(Aggregate::Mean)
+        );
+        assert_eq!(
+            "cpu,host=a,region=eu usage=4i,idle=3 1451606400000000000
+cpu,host=a,region=eu usage=9.5,idle=1.5 1451606460000000000
+cpu,host=b usage=5i 1451606400000000000
+",
+            downsample(Aggregate::Sum)
+        );
+        assert_eq!(
+            "cpu,host=a,region=eu usage=3i,idle=0.5,state=\"down\" 1451606400000000000
+cpu,host=a,region=eu usage=2.5,idle=1.5 1451606460000000000
+cpu,host=b usage=5i 1451606400000000000
+",
+            downsample(Aggregate::Last)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! Filter lines of line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::timestamp::parse_rfc3339;
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::io::{BufWriter, Write};
+
+/// Write lines of the line protocol file that match the measurement, tags and time range.
+#[derive(Debug, Parser)]
+pub(crate) struct Filter {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    filter: LineFilter,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Filter {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let mut output = BufWriter::new(create_output(
+            &self.output,
+            self.compression.output_compression,
+        )?);
+        let mut buffer = String::new();
+        read_lines(input, |line| {
+            if self.filter.matches(&line) {
+                buffer.clear();
+                push_line(&mut buffer, &line);
+                output
+                    .write_all(buffer.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+            Ok(())
+        })?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Conditions of lines to keep.
+#[derive(Debug, Default, clap::Args)]
+pub(crate) struct LineFilter {
+    /// Measurement to keep, can be repeated, all measurements by default.
+    #[arg(long)]
+    measurement: Vec<String>,
+    /// Tag `key=value` the line must have, can be repeated.
+    #[arg(long, value_parser = parse_tag_match)]
+    tag_match: Vec<(String, String)>,
+    /// Start time in RFC3339, inclusive.
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<i64>,
+    /// End time in RFC3339, exclusive.
+    #[arg(long, value_parser = parse_rfc3339)]
+    end: Option<i64>,
+}
+
+impl LineFilter {
+    /// Returns whether the line matches all conditions.
+    ///
+    /// Lines without timestamp don't match if there is a time range.
+    pub(crate) fn matches(&self, line: &ParsedLine<'_>) -> bool {
+        let measurement = line.series.measurement.as_str();
+        if !self.measurement.is_empty() && !self.measurement.iter().any(|m| m == measurement) {
+            return false;
+        }
+
+        let tags = &line.series.tag_set;
+        let has_tag = |key: &str, value: &str| {
+            tags.iter()
+                .flatten()
+                .any(|(k, v)| k.as_str() == key && v.as_str() == value)
+        };
+        if !self.tag_match.iter().all(|(k, v)| has_tag(k, v)) {
+            return false;
+        }
+
+        if self.start.is_some() || self.end.is_some() {
+            let Some(ts) = line.timestamp else {
+                return false;
+            };
+            if self.start.is_some_and(|start| ts < start) || self.end.is_some_and(|end| ts >= end) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn parse_tag_match(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{s} is not in the form of key=value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_line_filter() {
+        let input = r#"cpu,host=host_1,note=a\,b usage=1,s="a\"b" 1451606400000000000
+cpu,host=host_2 usage=2 1451606400000000000
+cpu,host=host_1 usage=3 1451606460000000000
+cpu,host=host_1 usage=4
+mem,host=host_1 used=5 1451606400000000000
+"#;
+        let filter = LineFilter {
+            measurement: vec!["cpu".to_string()],
+            tag_match: vec![parse_tag_match("host=host_1").unwrap()],
+            start: Some(parse_rfc3339("2016-01-01T00:00:00Z").unwrap()),
+            end: Some(parse_rfc3339("2016-01-01T00:01:00Z").unwrap()),
+        };
+        let mut output = String::new();
+        read_lines(Cursor::new(input), |line| {
+            if filter.matches(&line) {
+                push_line(&mut output, &line);
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            "cpu,host=host_1,note=a\\,b usage=1,s=\"a\\\"b\" 1451606400000000000\n",
+            output
+        );
+
+        let mut count = 0;
+        read_lines(Cursor::new(input), |line| {
+            count += usize::from(LineFilter::default().matches(&line));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(5, count);
+        assert!(parse_tag_match("host").is_err());
+    }
+}
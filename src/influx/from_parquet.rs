@@ -0,0 +1,298 @@
+//! Convert Apache Parquet to line protocol.
+
+use crate::error::{
+    ArrowSnafu, InvalidColumnTimestampSnafu, ReadParquetSnafu, Result,
+    UnsupportedTimestampTypeSnafu,
+};
+use crate::influx::timestamp::{Precision, TimestampParser};
+use crate::influx::{input_files, table_name, ColumnValue, LineWriter};
+use crate::io::{create_output, open_file, CompressionArgs};
+use crate::reject;
+use arrow::array::{
+    Array, ArrayRef, AsArray, BooleanArray, Float64Array, Int64Array, StringArray,
+    TimestampNanosecondArray, UInt64Array,
+};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, Int64Type, TimeUnit, UInt64Type};
+use arrow::record_batch::RecordBatch;
+use clap::Parser;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use snafu::{OptionExt, ResultExt};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::Path;
+
+/// Convert Parquet file to line protocol file.
+#[derive(Debug, Parser)]
+pub(crate) struct FromParquet {
+    /// Input Parquet file path (file or directory).
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    /// Measurement name, defaults to the file name without extension.
+    #[arg(long)]
+    measurement: Option<String>,
+    /// Timestamp column name.
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+    /// Precision of the timestamp column if it is an integer column.
+    #[arg(long, value_enum, default_value_t = Precision::Ms)]
+    precision: Precision,
+    /// Tag names.
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Table name prefix.
+    #[arg(long, default_value = "")]
+    table_prefix: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromParquet {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut writer = LineWriter::new(
+            create_output(&self.output, self.compression.output_compression)?,
+            self.timestamp,
+            TimestampParser::new(self.precision, None),
+            HashSet::from_iter(self.tag),
+            self.table_prefix,
+        )?;
+
+        for path in input_files(Path::new(&self.input))? {
+            let table_name = table_name(&path, self.measurement.as_deref())?;
+            writer.convert_parquet_file(&path, table_name)?;
+        }
+        writer.finish()
+    }
+}
+
+impl<W: Write> LineWriter<W> {
+    fn convert_parquet_file(&mut self, path: &Path, table_name: &str) -> Result<()> {
+        let input_file = open_file(path)?;
+        let reader = ParquetRecordBatchReaderBuilder::try_new(input_file)
+            .and_then(|builder| builder.build())
+            .context(ReadParquetSnafu { path })?;
+
+        for batch in reader {
+            let batch = batch.context(ArrowSnafu)?;
+            self.write_batch(path, table_name, &batch)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_batch(&mut self, path: &Path, table_name: &str, batch: &RecordBatch) -> Result<()> {
+        let schema = batch.schema();
+        let mut names = Vec::with_capacity(batch.num_columns());
+        let mut columns = Vec::with_capacity(batch.num_columns());
+        let mut timestamps = None;
+        for (field, array) in schema.fields().iter().zip(batch.columns()) {
+            if timestamps.is_none() && *field.name() == self.timestamp {
+                let array = timestamp_array(path, field.name(), array, &self.timestamp_parser)?;
+                timestamps = Some(array);
+            }
+            names.push(field.name().as_str());
+            columns.push(Column::new(array)?);
+        }
+
+        let mut row = Vec::with_capacity(columns.len());
+        for i in 0..batch.num_rows() {
+            row.clear();
+            for (name, column) in names.iter().zip(&columns) {
+                row.push((*name, column.value(i)));
+            }
+            let timestamp = timestamps
+                .as_ref()
+                .and_then(|array| (!array.is_null(i)).then(|| array.value(i)));
+
+            // Rows are rejected without text, e.g. rows with NaN fields.
+            let result = self.write_row(table_name, &row, timestamp);
+            reject::check(result, String::new)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the timestamp column in nanosecond.
+///
+/// Integer and string columns are parsed by the parser.
+fn timestamp_array(
+    path: &Path,
+    column: &str,
+    array: &ArrayRef,
+    parser: &TimestampParser,
+) -> Result<TimestampNanosecondArray> {
+    let parse = |value: &dyn Fn() -> String, ts: Option<i64>| {
+        ts.with_context(|| InvalidColumnTimestampSnafu {
+            path,
+            column,
+            value: value(),
+        })
+    };
+
+    match array.data_type() {
+        DataType::Timestamp(_, _) => {
+            let array = cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None))
+                .context(ArrowSnafu)?;
+            Ok(array.as_primitive().clone())
+        }
+        DataType::Int64 | DataType::Int32 | DataType::UInt32 => {
+            let array = cast(array, &DataType::Int64).context(ArrowSnafu)?;
+            array
+                .as_primitive::<Int64Type>()
+                .iter()
+                .map(|value| {
+                    value
+                        .map(|v| parse(&|| v.to_string(), parser.parse_epoch(v)))
+                        .transpose()
+                })
+                .collect()
+        }
+        DataType::Utf8 => array
+            .as_string::<i32>()
+            .iter()
+            .map(|value| {
+                value
+                    .map(|v| parse(&|| v.to_string(), parser.parse(v)))
+                    .transpose()
+            })
+            .collect(),
+        other => UnsupportedTimestampTypeSnafu {
+            path,
+            column,
+            data_type: other.to_string(),
+        }
+        .fail(),
+    }
+}
+
+/// A column of the record batch, casted to one of the line protocol types.
+enum Column {
+    Int64(Int64Array),
+    UInt64(UInt64Array),
+    Float64(Float64Array),
+    String(StringArray),
+    Boolean(BooleanArray),
+}
+
+impl Column {
+    fn new(array: &ArrayRef) -> Result<Column> {
+        let column = match array.data_type() {
+            DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => {
+                let array = cast(array, &DataType::Int64).context(ArrowSnafu)?;
+                Column::Int64(array.as_primitive::<Int64Type>().clone())
+            }
+            DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+                let array = cast(array, &DataType::UInt64).context(ArrowSnafu)?;
+                Column::UInt64(array.as_primitive::<UInt64Type>().clone())
+            }
+            DataType::Float16 | DataType::Float32 | DataType::Float64 => {
+                let array = cast(array, &DataType::Float64).context(ArrowSnafu)?;
+                Column::Float64(array.as_primitive::<Float64Type>().clone())
+            }
+            DataType::Boolean => Column::Boolean(array.as_boolean().clone()),
+            // Other types, including timestamps and dictionaries, are written as strings.
+            _ => {
+                let array = cast(array, &DataType::Utf8).context(ArrowSnafu)?;
+                Column::String(array.as_string::<i32>().clone())
+            }
+        };
+
+        Ok(column)
+    }
+
+    fn value(&self, i: usize) -> ColumnValue<'_> {
+        match self {
+            Column::Int64(array) if array.is_valid(i) => ColumnValue::Int64(array.value(i)),
+            Column::UInt64(array) if array.is_valid(i) => ColumnValue::UInt64(array.value(i)),
+            Column::Float64(array) if array.is_valid(i) => ColumnValue::Float64(array.value(i)),
+            Column::String(array) if array.is_valid(i) => ColumnValue::String(array.value(i)),
+            Column::Boolean(array) if array.is_valid(i) => ColumnValue::Boolean(array.value(i)),
+            _ => ColumnValue::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{StringDictionaryBuilder, TimestampMillisecondArray};
+    use arrow::datatypes::{Field, Int32Type, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::fs::File;
+    use std::io::Read;
+    use std::sync::Arc;
+    use tempfile::{Builder, NamedTempFile};
+
+    #[test]
+    fn test_from_parquet() {
+        let output_file = NamedTempFile::new().unwrap();
+        let input_dir = Builder::new().tempdir().unwrap();
+        let input_path = input_dir.path().join("metric1.parquet");
+        {
+            let mut hostname = StringDictionaryBuilder::<Int32Type>::new();
+            hostname.append_value("host_0");
+            hostname.append_null();
+            let columns: Vec<ArrayRef> = vec![
+                Arc::new(hostname.finish()),
+                Arc::new(Int64Array::from(vec![Some(58), None])),
+                Arc::new(Float64Array::from(vec![2.5, 3.0])),
+                Arc::new(StringArray::from(vec!["ok", "fail"])),
+                Arc::new(TimestampMillisecondArray::from(vec![
+                    1451606400000,
+                    1451606410000,
+                ])),
+            ];
+            let schema = Schema::new(vec![
+                Field::new("hostname", columns[0].data_type().clone(), true),
+                Field::new("usage_user", DataType::Int64, true),
+                Field::new("usage_system", DataType::Float64, false),
+                Field::new("status", DataType::Utf8, false),
+                Field::new("timestamp", columns[4].data_type().clone(), false),
+            ]);
+            let batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+            let file = File::create(&input_path).unwrap();
+            let mut writer = ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        }
+
+        let from_parquet = FromParquet {
+            input: input_dir.path().to_str().unwrap().to_string(),
+            output: output_file.path().to_str().unwrap().to_string(),
+            measurement: None,
+            timestamp: "timestamp".to_string(),
+            precision: Precision::Ms,
+            tag: vec!["hostname".to_string()],
+            table_prefix: "test_".to_string(),
+            compression: CompressionArgs::default(),
+        };
+        from_parquet.run().unwrap();
+
+        let mut lines = String::new();
+        output_file.as_file().read_to_string(&mut lines).unwrap();
+        assert_eq!(lines, "test_metric1,hostname=host_0 usage_user=58i,usage_system=2.5,status=\"ok\" 1451606400000000000\ntest_metric1 usage_system=3,status=\"fail\" 1451606410000000000\n");
+    }
+
+    #[test]
+    fn test_write_batch_not_finite() {
+        let schema = Schema::new(vec![Field::new("usage", DataType::Float64, false)]);
+        let columns: Vec<ArrayRef> = vec![Arc::new(Float64Array::from(vec![f64::NAN]))];
+        let batch = RecordBatch::try_new(Arc::new(schema), columns).unwrap();
+        let mut writer = LineWriter::new(
+            Vec::new(),
+            "timestamp".to_string(),
+            TimestampParser::new(Precision::Ns, None),
+            HashSet::new(),
+            String::new(),
+        )
+        .unwrap();
+        let e = writer
+            .write_batch(Path::new("cpu.parquet"), "cpu", &batch)
+            .unwrap_err();
+        assert_eq!("Invalid point: field usage is NaN", e.to_string());
+    }
+}
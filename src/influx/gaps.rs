@@ -0,0 +1,181 @@
+//! Detect gaps of series in line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::timestamp::{parse_duration, parse_rfc3339};
+use crate::influx::{read_lines, series_key};
+use crate::io::{create_output, open_input, Compression, Output};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Report gaps of each series of line protocol file, where the spacing between
+/// consecutive points exceeds the expected interval.
+///
+/// Each gap is reported with the times of the first and the last missing points and the
+/// number of missing points. Timestamps of series are kept in memory and lines don't
+/// need to be sorted. Lines without timestamp or out of the time range are ignored.
+#[derive(Debug, Parser)]
+pub(crate) struct Gaps {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Expected interval between points of a series, e.g. 10s.
+    #[arg(long, value_parser = parse_duration)]
+    expected_interval: i64,
+    /// Spacing larger than the expected interval multiplied by the threshold is a gap.
+    #[arg(long, default_value_t = 1.5)]
+    threshold: f64,
+    /// Start of the time range series are expected to cover in RFC3339, which reports
+    /// gaps before the first points.
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<i64>,
+    /// Exclusive end of the time range series are expected to cover in RFC3339, which
+    /// reports gaps after the last points.
+    #[arg(long, value_parser = parse_rfc3339)]
+    end: Option<i64>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Gaps {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut series: BTreeMap<String, Vec<i64>> = BTreeMap::new();
+        read_lines(input, |line| {
+            if let Some(ts) = line.timestamp {
+                series.entry(series_key(&line)).or_default().push(ts);
+            }
+            Ok(())
+        })?;
+
+        let mut output = create_output(&self.output, Compression::None)?;
+        let (mut gaps, mut series_with_gaps) = (0, 0);
+        for (key, timestamps) in &mut series {
+            let series_gaps = self.find_gaps(timestamps);
+            if !series_gaps.is_empty() {
+                series_with_gaps += 1;
+            }
+            gaps += series_gaps.len();
+            for gap in series_gaps {
+                writeln!(
+                    output,
+                    "{key}: {} - {}, {} missing points",
+                    Utc.timestamp_nanos(gap.first).to_rfc3339(),
+                    Utc.timestamp_nanos(gap.last).to_rfc3339(),
+                    gap.missing
+                )
+                .context(WriteOutputSnafu)?;
+            }
+        }
+        writeln!(
+            output,
+            "{gaps} gaps in {series_with_gaps} of {} series",
+            series.len()
+        )
+        .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Returns gaps of the timestamps of a series, timestamps are sorted.
+    fn find_gaps(&self, timestamps: &mut Vec<i64>) -> Vec<Gap> {
+        timestamps.sort_unstable();
+        timestamps.dedup();
+
+        let interval = self.expected_interval;
+        let in_range = |ts: &i64| {
+            self.start.is_none_or(|start| *ts >= start) && self.end.is_none_or(|end| *ts < end)
+        };
+        // The range is covered if there are points at the start and before the end.
+        let before = self.start.map(|start| start - interval);
+        let timestamps = before
+            .into_iter()
+            .chain(timestamps.iter().copied().filter(in_range))
+            .chain(self.end);
+
+        let max_spacing = interval as f64 * self.threshold;
+        let mut gaps = Vec::new();
+        let mut prev = None;
+        for ts in timestamps {
+            if let Some(prev) = prev.filter(|prev| (ts - prev) as f64 > max_spacing) {
+                let missing = ((ts - prev) as f64 / interval as f64).round() as i64 - 1;
+                gaps.push(Gap {
+                    first: prev + interval,
+                    last: ts - interval,
+                    missing: missing.max(1),
+                });
+            }
+            prev = Some(ts);
+        }
+        gaps
+    }
+}
+
+/// Missing points between two points of a series.
+#[derive(Debug, PartialEq, Eq)]
+struct Gap {
+    /// Expected time of the first missing point.
+    first: i64,
+    /// Expected time of the last missing point.
+    last: i64,
+    missing: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_gaps() {
+        let gaps =
+            Gaps::try_parse_from(["gaps", "-i", "-", "--expected-interval", "10ns"]).unwrap();
+        let mut timestamps = vec![50, 0, 10, 20, 20, 90, 104];
+        assert_eq!(
+            vec![
+                Gap {
+                    first: 30,
+                    last: 40,
+                    missing: 2
+                },
+                Gap {
+                    first: 60,
+                    last: 80,
+                    missing: 3
+                },
+            ],
+            gaps.find_gaps(&mut timestamps)
+        );
+
+        let gaps = Gaps::try_parse_from(
+            "gaps -i - --expected-interval 10s --start 2016-01-01T00:00:00Z \
+             --end 2016-01-01T00:01:00Z"
+                .split(' '),
+        )
+        .unwrap();
+        let start = gaps.start.unwrap();
+        let second = 1_000_000_000;
+        let mut timestamps = vec![start - second, start + 20 * second, start + 30 * second];
+        assert_eq!(
+            vec![
+                Gap {
+                    first: start,
+                    last: start + 10 * second,
+                    missing: 2
+                },
+                Gap {
+                    first: start + 40 * second,
+                    last: start + 50 * second,
+                    missing: 2
+                },
+            ],
+            gaps.find_gaps(&mut timestamps)
+        );
+        let mut timestamps: Vec<_> = (0..6).map(|i| start + i * 10 * second).collect();
+        assert!(gaps.find_gaps(&mut timestamps).is_empty());
+    }
+}
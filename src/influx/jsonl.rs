@@ -0,0 +1,245 @@
+//! Convert between line protocol and JSON Lines.
+
+use crate::error::{
+    InvalidPointSnafu, LineSnafu, ParseJsonSnafu, ReadInputSnafu, Result, WriteJsonSnafu,
+    WriteOutputSnafu,
+};
+use crate::influx::timestamp::{Precision, TimestampParser};
+use crate::influx::{push_fields, push_series, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// A point in JSON.
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonPoint {
+    measurement: String,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    fields: Map<String, Value>,
+    /// Timestamp in nanosecond or RFC3339.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timestamp: Option<Value>,
+}
+
+impl JsonPoint {
+    fn from_line(line: &ParsedLine<'_>) -> JsonPoint {
+        let tags = line.series.tag_set.iter().flatten();
+        let fields = line.field_set.iter().map(|(key, value)| {
+            let value = match value {
+                FieldValue::I64(v) => Value::from(*v),
+                FieldValue::U64(v) => Value::from(*v),
+                // Line protocol has no NaN or infinity.
+                FieldValue::F64(v) => Number::from_f64(*v).map_or(Value::Null, Value::Number),
+                FieldValue::String(v) => Value::from(v.as_str()),
+                FieldValue::Boolean(v) => Value::from(*v),
+            };
+            (key.to_string(), value)
+        });
+        JsonPoint {
+            measurement: line.series.measurement.to_string(),
+            tags: tags.map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            fields: fields.collect(),
+            timestamp: line.timestamp.map(Value::from),
+        }
+    }
+
+    /// Push the point as a line of line protocol.
+    fn push_line(&self, line: &mut String, parser: &TimestampParser) -> Result<()> {
+        let fields = self
+            .fields
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::Bool(v) => FieldValue::Boolean(*v),
+                    Value::Number(n) => {
+                        if let Some(v) = n.as_i64() {
+                            FieldValue::I64(v)
+                        } else if let Some(v) = n.as_u64() {
+                            FieldValue::U64(v)
+                        } else {
+                            FieldValue::F64(n.as_f64().unwrap_or_default())
+                        }
+                    }
+                    Value::String(v) => FieldValue::String(v.as_str().into()),
+                    _ => {
+                        return InvalidPointSnafu {
+                            reason: format!("field {key} is not a number, string or boolean"),
+                        }
+                        .fail()
+                    }
+                };
+                Ok((key.as_str(), value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ensure!(
+            !fields.is_empty(),
+            InvalidPointSnafu {
+                reason: "point has no fields"
+            }
+        );
+        let timestamp = match &self.timestamp {
+            None | Some(Value::Null) => None,
+            Some(value) => {
+                let timestamp = match value {
+                    Value::Number(n) => n.as_i64().and_then(|ts| parser.parse_epoch(ts)),
+                    Value::String(s) => parser.parse(s),
+                    _ => None,
+                };
+                Some(timestamp.with_context(|| InvalidPointSnafu {
+                    reason: format!("invalid timestamp {value}"),
+                })?)
+            }
+        };
+
+        push_series(
+            line,
+            &self.measurement,
+            self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+        );
+        push_fields(line, fields.iter().map(|(key, value)| (*key, value)));
+        if let Some(ts) = timestamp {
+            write!(line, " {ts}").unwrap();
+        }
+        line.push('\n');
+        Ok(())
+    }
+}
+
+/// Convert line protocol file to JSON Lines file.
+///
+/// Each line is a JSON object with the `measurement`, `tags`, `fields` and `timestamp` in
+/// nanosecond, the timestamp is absent if the line has no timestamp.
+#[derive(Debug, Parser)]
+pub(crate) struct ToJsonl {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output JSON Lines file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToJsonl {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = line_protocol_to_jsonl(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert JSON Lines file to line protocol file.
+///
+/// Each line is a JSON object with the `measurement`, `tags`, `fields` and optional
+/// `timestamp`. Integers are written as integer fields, and `null` fields are skipped.
+#[derive(Debug, Parser)]
+pub(crate) struct FromJsonl {
+    /// Input JSON Lines file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Precision of the timestamp if timestamps are epoch, timestamps may also be RFC3339.
+    #[arg(long, value_enum, default_value_t = Precision::Ns)]
+    precision: Precision,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromJsonl {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let parser = TimestampParser::new(self.precision, None);
+        let mut output = jsonl_to_line_protocol(input, output, &parser)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn line_protocol_to_jsonl<R: Read, W: Write>(source: R, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    read_lines(source, |line| {
+        serde_json::to_writer(&mut writer, &JsonPoint::from_line(&line)).context(WriteJsonSnafu)?;
+        writer.write_all(b"\n").context(WriteOutputSnafu)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn jsonl_to_line_protocol<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    parser: &TimestampParser,
+) -> Result<W> {
+    let reader = BufReader::new(source);
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    for (i, text) in reader.lines().enumerate() {
+        let text = text.context(ReadInputSnafu)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        line.clear();
+        serde_json::from_str::<JsonPoint>(&text)
+            .context(ParseJsonSnafu)
+            .and_then(|point| point.push_line(&mut line, parser))
+            .context(LineSnafu { line: i + 1 })?;
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonl_round_trip() {
+        let input = "cpu,host=a,region=eu usage=1.5,count=2i,ok=true,note=\"say \\\"hi\\\"\" 1
+mem used=3u
+";
+        let output = line_protocol_to_jsonl(input.as_bytes(), Vec::new()).unwrap();
+        let jsonl = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"{"measurement":"cpu","tags":{"host":"a","region":"eu"},"fields":{"count":2,"note":"say \"hi\"","ok":true,"usage":1.5},"timestamp":1}
+{"measurement":"mem","tags":{},"fields":{"used":3}}
+"#,
+            jsonl
+        );
+
+        let parser = TimestampParser::new(Precision::Ns, None);
+        let output = jsonl_to_line_protocol(jsonl.as_bytes(), Vec::new(), &parser).unwrap();
+        assert_eq!(
+            "cpu,host=a,region=eu count=2i,note=\"say \\\"hi\\\"\",ok=true,usage=1.5 1
+mem used=3i
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let input = r#"{"measurement":"cpu","fields":{"v":1},"timestamp":"2016-01-01T00:00:00Z"}
+{"measurement":"cpu","fields":{"v":null}}
+"#;
+        let err = jsonl_to_line_protocol(input.as_bytes(), Vec::new(), &parser).unwrap_err();
+        assert_eq!("Error at line 2", err.to_string());
+    }
+}
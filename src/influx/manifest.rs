@@ -0,0 +1,269 @@
+//! Manifests of line protocol files to verify datasets after transfer.
+
+use crate::error::{
+    ParseJsonSnafu, ReadFileSnafu, Result, VerificationFailedSnafu, WriteJsonSnafu,
+    WriteOutputSnafu,
+};
+use crate::influx::{input_files, read_lines};
+use crate::io::{create_output, decompress, open_file, open_input, Compression, Output};
+use crate::progress;
+use crate::reject;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use snafu::{ensure, ResultExt};
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Record line counts, time ranges and SHA-256 checksums of line protocol files in a
+/// JSON manifest.
+///
+/// Checksums are of the files as stored, the same as `sha256sum`, while lines and
+/// timestamps are read after decompression.
+#[derive(Debug, Parser)]
+pub(crate) struct Manifest {
+    /// Input line protocol files, directories or glob patterns.
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /// Output manifest file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Compression of the inputs, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Manifest {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut files = Vec::new();
+        for input in &self.inputs {
+            for path in input_files(Path::new(input))? {
+                files.push(FileEntry::scan(&path, self.input_compression)?);
+            }
+        }
+
+        let mut output = create_output(&self.output, Compression::None)?;
+        serde_json::to_writer_pretty(&mut output, &Dataset { files }).context(WriteJsonSnafu)?;
+        writeln!(output).context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Verify line protocol files against a manifest written by `influx manifest`.
+///
+/// Each file is read again and problems are reported per file, including invalid lines.
+/// Fails if any file is missing or differs.
+#[derive(Debug, Parser)]
+pub(crate) struct Verify {
+    /// Manifest file path.
+    #[arg(short, long)]
+    manifest: String,
+    /// Directory of relative paths in the manifest, the current directory by default.
+    #[arg(long)]
+    dir: Option<PathBuf>,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Compression of the inputs, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Verify {
+    pub(crate) fn run(self) -> Result<()> {
+        let manifest = open_input(&self.manifest, Compression::Auto)?;
+        let dataset: Dataset = serde_json::from_reader(manifest).context(ParseJsonSnafu)?;
+        let mut output = create_output(&self.output, Compression::None)?;
+        let failed = self.verify_files(&dataset, &mut output)?;
+        writeln!(
+            output,
+            "{} of {} files verified",
+            dataset.files.len() - failed,
+            dataset.files.len()
+        )
+        .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)?;
+
+        ensure!(failed == 0, VerificationFailedSnafu { files: failed });
+        Ok(())
+    }
+
+    /// Write problems of files and returns the number of failed files.
+    fn verify_files<W: Write>(&self, dataset: &Dataset, mut dest: W) -> Result<usize> {
+        let mut failed = 0;
+        for expected in &dataset.files {
+            let path = match &self.dir {
+                Some(dir) => dir.join(&expected.path),
+                None => PathBuf::from(&expected.path),
+            };
+            if !path.is_file() {
+                failed += 1;
+                writeln!(dest, "{}: missing", expected.path).context(WriteOutputSnafu)?;
+                continue;
+            }
+
+            let actual = match FileEntry::scan(&path, self.input_compression) {
+                Ok(actual) => actual,
+                Err(e) if e.is_invalid_line() => {
+                    failed += 1;
+                    writeln!(dest, "{}: {}", expected.path, reject::message(&e))
+                        .context(WriteOutputSnafu)?;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let problems = expected.problems(&actual);
+            if !problems.is_empty() {
+                failed += 1;
+                writeln!(dest, "{}: {}", expected.path, problems.join(", "))
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+        Ok(failed)
+    }
+}
+
+/// Files of a manifest.
+#[derive(Debug, Serialize, Deserialize)]
+struct Dataset {
+    files: Vec<FileEntry>,
+}
+
+/// Summary of a line protocol file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileEntry {
+    path: String,
+    /// Size in bytes as stored.
+    size: u64,
+    /// Hex of the SHA-256 of the file as stored.
+    sha256: String,
+    lines: usize,
+    /// Min timestamp in nanosecond, absent if no line has timestamp.
+    min_time: Option<i64>,
+    /// Max timestamp in nanosecond.
+    max_time: Option<i64>,
+}
+
+impl FileEntry {
+    /// Read the file, the checksum is updated while lines are parsed.
+    fn scan(path: &Path, compression: Compression) -> Result<FileEntry> {
+        let file = open_file(path)?;
+        let size = file.metadata().context(ReadFileSnafu { path })?.len();
+        progress::add_input_size(Some(size));
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let reader = HashReader {
+            inner: progress::track_bytes(file),
+            hasher: hasher.clone(),
+        };
+
+        let mut entry = FileEntry {
+            path: path.display().to_string(),
+            size,
+            sha256: String::new(),
+            lines: 0,
+            min_time: None,
+            max_time: None,
+        };
+        read_lines(decompress(reader, compression.of(path))?, |line| {
+            entry.lines += 1;
+            if let Some(ts) = line.timestamp {
+                entry.min_time = Some(entry.min_time.map_or(ts, |min| min.min(ts)));
+                entry.max_time = Some(entry.max_time.map_or(ts, |max| max.max(ts)));
+            }
+            Ok(())
+        })?;
+
+        let digest = hasher.lock().unwrap().clone().finalize();
+        for byte in digest {
+            write!(entry.sha256, "{byte:02x}").unwrap();
+        }
+        Ok(entry)
+    }
+
+    /// Returns descriptions of values of the other entry different from this one.
+    fn problems(&self, other: &FileEntry) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.size != other.size {
+            problems.push(format!("size {} != {}", self.size, other.size));
+        }
+        if self.sha256 != other.sha256 {
+            problems.push(format!("sha256 {} != {}", self.sha256, other.sha256));
+        }
+        if self.lines != other.lines {
+            problems.push(format!("lines {} != {}", self.lines, other.lines));
+        }
+        if (self.min_time, self.max_time) != (other.min_time, other.max_time) {
+            problems.push(format!(
+                "time range {:?}..{:?} != {:?}..{:?}",
+                self.min_time, self.max_time, other.min_time, other.max_time
+            ));
+        }
+        problems
+    }
+}
+
+/// Reader that updates the hasher with bytes read.
+struct HashReader<R> {
+    inner: R,
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.lock().unwrap().update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_manifest_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.lp"), "cpu usage=1 20\ncpu usage=2 10\n").unwrap();
+        fs::write(dir.path().join("b.lp"), "mem used=1\n").unwrap();
+
+        let a = FileEntry::scan(&dir.path().join("a.lp"), Compression::Auto).unwrap();
+        assert_eq!(30, a.size);
+        assert_eq!(2, a.lines);
+        assert_eq!((Some(10), Some(20)), (a.min_time, a.max_time));
+        let digest = Sha256::digest(b"cpu usage=1 20\ncpu usage=2 10\n");
+        assert_eq!(format!("{digest:x}"), a.sha256);
+        let b = FileEntry::scan(&dir.path().join("b.lp"), Compression::Auto).unwrap();
+        assert_eq!((1, None), (b.lines, b.max_time));
+
+        let dataset = Dataset {
+            files: vec![
+                FileEntry {
+                    path: "a.lp".to_string(),
+                    ..a
+                },
+                FileEntry {
+                    path: "c.lp".to_string(),
+                    ..b
+                },
+            ],
+        };
+        fs::write(dir.path().join("a.lp"), "cpu usage=1 20\ncpu usage=3 10\n").unwrap();
+        let verify = Verify::try_parse_from([
+            "verify",
+            "--manifest",
+            "manifest.json",
+            "--dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .unwrap();
+        let mut output = Vec::new();
+        assert_eq!(2, verify.verify_files(&dataset, &mut output).unwrap());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("a.lp: sha256 "), "{output}");
+        assert!(!output.contains("lines"), "{output}");
+        assert!(output.ends_with("c.lp: missing\n"), "{output}");
+    }
+}
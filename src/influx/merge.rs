@@ -0,0 +1,167 @@
+//! Merge sorted line protocol files.
+
+use crate::error::{
+    LineSnafu, ParseLineSnafu, ReadInputSnafu, Result, UnsortedInputSnafu, WriteOutputSnafu,
+};
+use crate::influx::push_line;
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::reject;
+use chrono::Utc;
+use clap::Parser;
+use snafu::{ensure, ResultExt};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Merge line protocol files sorted by timestamp into a sorted file.
+///
+/// Lines with the same timestamp are in the order of inputs, lines without timestamp are
+/// merged as they have the current time.
+#[derive(Debug, Parser)]
+pub(crate) struct Merge {
+    /// Input line protocol file paths sorted by timestamp, `-` for stdin.
+    #[arg(required = true)]
+    inputs: Vec<String>,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Merge {
+    pub(crate) fn run(self) -> Result<()> {
+        let sources = self
+            .inputs
+            .iter()
+            .map(|path| {
+                let input = open_input(path, self.compression.input_compression)?;
+                Ok(LineSource::new(path, input))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = merge_lines(sources, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Source of lines of a sorted input.
+struct LineSource<R> {
+    path: String,
+    reader: BufReader<R>,
+    buffer: String,
+    line_number: usize,
+    /// Timestamp of the last line.
+    last: i64,
+    now: i64,
+}
+
+impl<R: Read> LineSource<R> {
+    fn new(path: &str, source: R) -> LineSource<R> {
+        LineSource {
+            path: path.to_string(),
+            reader: BufReader::new(source),
+            buffer: String::new(),
+            line_number: 0,
+            last: i64::MIN,
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+        }
+    }
+
+    /// Returns the timestamp and text of the next line.
+    fn next_line(&mut self) -> Result<Option<(i64, String)>> {
+        loop {
+            self.buffer.clear();
+            if self
+                .reader
+                .read_line(&mut self.buffer)
+                .context(ReadInputSnafu)?
+                == 0
+            {
+                return Ok(None);
+            }
+            self.line_number += 1;
+
+            if let Some(line) = influxdb_line_protocol::parse_lines(&self.buffer).next() {
+                let result = line.context(ParseLineSnafu).context(LineSnafu {
+                    line: self.line_number,
+                });
+                let Some(line) = reject::check(result, || &self.buffer)? else {
+                    continue;
+                };
+                let timestamp = line.timestamp.unwrap_or(self.now);
+                ensure!(
+                    timestamp >= self.last,
+                    UnsortedInputSnafu {
+                        path: &self.path,
+                        line: self.line_number,
+                    }
+                );
+                self.last = timestamp;
+
+                let mut text = String::new();
+                push_line(&mut text, &line);
+                return Ok(Some((timestamp, text)));
+            }
+        }
+    }
+}
+
+/// Merge lines of sources in time order.
+fn merge_lines<R: Read, W: Write>(mut sources: Vec<LineSource<R>>, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut heap = BinaryHeap::with_capacity(sources.len());
+    for (idx, source) in sources.iter_mut().enumerate() {
+        if let Some((timestamp, text)) = source.next_line()? {
+            heap.push(Reverse((timestamp, idx, text)));
+        }
+    }
+    while let Some(Reverse((_, idx, text))) = heap.pop() {
+        writer
+            .write_all(text.as_bytes())
+            .context(WriteOutputSnafu)?;
+        if let Some((timestamp, text)) = sources[idx].next_line()? {
+            heap.push(Reverse((timestamp, idx, text)));
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_lines() {
+        let a = "cpu,host=a usage=1 10\n\ncpu,host=a usage=2 30\n";
+        let b = "# comment\ncpu,host=b usage=3 10\ncpu,host=b usage=4 20\n";
+        let sources = vec![
+            LineSource::new("a.lp", a.as_bytes()),
+            LineSource::new("b.lp", b.as_bytes()),
+        ];
+        let output = merge_lines(sources, Vec::new()).unwrap();
+        assert_eq!(
+            "cpu,host=a usage=1 10
+cpu,host=b usage=3 10
+cpu,host=b usage=4 20
+cpu,host=a usage=2 30
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let sources = vec![LineSource::new(
+            "c.lp",
+            "cpu v=1 20\ncpu v=1 10\n".as_bytes(),
+        )];
+        let err = merge_lines(sources, Vec::new()).unwrap_err();
+        assert_eq!(
+            "Input c.lp is not sorted by timestamp at line 2",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Add noise to values and timestamps of line protocol.
+
+use crate::error::{Result, TimestampOutOfRangeSnafu, WriteOutputSnafu};
+use crate::generate::Rng;
+use crate::influx::timestamp::parse_duration;
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::{OptionExt, ResultExt};
+use std::io::{BufWriter, Read, Write};
+
+/// Distribution of the relative noise of field values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Distribution {
+    /// Values are multiplied by `1 + N(0, scale)`.
+    Gaussian(f64),
+    /// Values are multiplied by `1 + U(-scale, scale)`.
+    Uniform(f64),
+}
+
+/// Add random noise to numeric field values and timestamps of line protocol file, so
+/// the same source can produce distinct but statistically similar datasets.
+///
+/// The noise is relative to each value, integer values are rounded and unsigned values
+/// are clamped at 0. Strings and booleans are kept. The same seed always writes the
+/// same output.
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("perturbation").required(true).multiple(true))]
+pub(crate) struct Noise {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Noise of field values, `gaussian:<stddev>` or `uniform:<max>`, e.g. gaussian:0.05.
+    #[arg(long, value_parser = parse_noise, group = "perturbation")]
+    noise: Option<Distribution>,
+    /// Fields to add noise to, all numeric fields by default.
+    #[arg(long, requires = "noise")]
+    field: Vec<String>,
+    /// Max offset of timestamps, each timestamp is moved uniformly within the offset,
+    /// e.g. 500ms.
+    #[arg(long, value_parser = parse_duration, group = "perturbation")]
+    time_jitter: Option<i64>,
+    /// Seed of the random generator.
+    #[arg(long, default_value_t = 123)]
+    seed: u64,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Noise {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = self.noise_lines(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Write lines with noise added.
+    fn noise_lines<R: Read, W: Write>(&self, source: R, dest: W) -> Result<W> {
+        let mut rng = Rng(self.seed);
+        let mut writer = BufWriter::new(dest);
+        let mut buffer = String::new();
+        read_lines(source, |mut line| {
+            self.perturb(&mut rng, &mut line)?;
+            buffer.clear();
+            push_line(&mut buffer, &line);
+            writer
+                .write_all(buffer.as_bytes())
+                .context(WriteOutputSnafu)
+        })?;
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+
+    fn perturb(&self, rng: &mut Rng, line: &mut ParsedLine) -> Result<()> {
+        if let Some(noise) = self.noise {
+            for (key, value) in &mut line.field_set {
+                if !self.field.is_empty() && !self.field.iter().any(|f| f == key.as_str()) {
+                    continue;
+                }
+                match value {
+                    FieldValue::F64(v) => *v *= noise.factor(rng),
+                    FieldValue::I64(v) => *v = (*v as f64 * noise.factor(rng)).round() as i64,
+                    FieldValue::U64(v) => {
+                        *v = (*v as f64 * noise.factor(rng)).round().max(0.0) as u64
+                    }
+                    FieldValue::String(_) | FieldValue::Boolean(_) => {}
+                }
+            }
+        }
+        if let (Some(jitter), Some(timestamp)) = (self.time_jitter, line.timestamp.as_mut()) {
+            let offset = rng.below(2 * jitter as u64 + 1) as i64 - jitter;
+            *timestamp = timestamp
+                .checked_add(offset)
+                .context(TimestampOutOfRangeSnafu {
+                    timestamp: *timestamp,
+                    offset,
+                })?;
+        }
+        Ok(())
+    }
+}
+
+impl Distribution {
+    /// Returns a random factor to multiply a value by.
+    fn factor(self, rng: &mut Rng) -> f64 {
+        match self {
+            Distribution::Gaussian(stddev) => 1.0 + stddev * rng.gaussian(),
+            Distribution::Uniform(max) => 1.0 + max * (2.0 * rng.next_f64() - 1.0),
+        }
+    }
+}
+
+fn parse_noise(s: &str) -> Result<Distribution, String> {
+    let (kind, scale) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{s} is not in the form of distribution:scale"))?;
+    let scale: f64 = match scale.parse() {
+        Ok(scale) if scale >= 0.0 && f64::is_finite(scale) => scale,
+        _ => return Err(format!("invalid scale of noise {scale:?}")),
+    };
+    match kind {
+        "gaussian" => Ok(Distribution::Gaussian(scale)),
+        "uniform" => Ok(Distribution::Uniform(scale)),
+        _ => Err(format!(
+            "unknown distribution {kind:?}, expect gaussian or uniform"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use influxdb_line_protocol::parse_lines;
+
+    #[test]
+    fn test_noise_lines() {
+        let input = "cpu,host=a usage=100,count=100i,total=100u,up=true,state=\"ok\" 1000
+cpu,host=a usage=0 2000
+";
+        let noise = |args: &str| {
+            let command = Noise::try_parse_from(format!("noise -i - {args}").split(' ')).unwrap();
+            let output = command.noise_lines(input.as_bytes(), Vec::new()).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let output = noise("--noise uniform:0.1 --time-jitter 10ns");
+        assert_eq!(output, noise("--noise uniform:0.1 --time-jitter 10ns"));
+        assert_ne!(
+            output,
+            noise("--noise uniform:0.1 --time-jitter 10ns --seed 1")
+        );
+        let lines: Vec<_> = output.lines().collect();
+        let first = parse_lines(lines[0]).next().unwrap().unwrap();
+        for (_, value) in &first.field_set {
+            match value {
+                FieldValue::F64(v) => assert!((90.0..=110.0).contains(v), "{v}"),
+                FieldValue::I64(v) => assert!((90..=110).contains(v), "{v}"),
+                FieldValue::U64(v) => assert!((90..=110).contains(v), "{v}"),
+                FieldValue::Boolean(v) => assert!(*v),
+                FieldValue::String(v) => assert_eq!("ok", v.as_str()),
+            }
+        }
+        assert!((990..=1010).contains(&first.timestamp.unwrap()));
+        assert!(lines[1].starts_with("cpu,host=a usage=0 "));
+
+        assert_eq!(
+            "cpu,host=a usage=100,count=100i,total=100u,up=true,state=\"ok\" 1000
+cpu,host=a usage=0 2000
+",
+            noise("--noise gaussian:0.5 --field other")
+        );
+
+        assert!(Noise::try_parse_from(["noise", "-i", "-"]).is_err());
+        assert!(parse_noise("gaussian").is_err());
+        assert!(parse_noise("poisson:1").is_err());
+        assert!(parse_noise("uniform:-1").is_err());
+    }
+}
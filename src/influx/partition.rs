@@ -0,0 +1,159 @@
+//! Partition line protocol into files by the hash of series.
+
+use crate::error::{CreateDirSnafu, Result, WriteOutputSnafu};
+use crate::influx::{fnv1a, push_line, read_lines, series_key};
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// Key to hash lines by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PartitionBy {
+    /// The measurement and tags, regardless of the order of tags.
+    Series,
+    /// The measurement.
+    Measurement,
+}
+
+/// Partition line protocol file into a fixed number of files in a directory.
+///
+/// All points of a series are in the same file and keep their order, so files can be
+/// loaded in parallel without ordering issues across files.
+#[derive(Debug, Parser)]
+pub(crate) struct Partition {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files, named like `part-00000.lp`.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Number of files.
+    #[arg(long, default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    partitions: u32,
+    /// Key to hash lines by.
+    #[arg(long, value_enum, default_value_t = PartitionBy::Series)]
+    by: PartitionBy,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+    /// Compression of the output files, which adds the extension to file names.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    output_compression: Compression,
+}
+
+impl Partition {
+    pub(crate) fn run(self) -> Result<()> {
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        // Files of all partitions are created, even if they are empty.
+        let mut outputs = (0..self.partitions)
+            .map(|i| {
+                let path = self.output_dir.join(format!(
+                    "part-{i:05}.lp{}",
+                    self.output_compression.extension()
+                ));
+                Ok(BufWriter::new(create_output(
+                    path,
+                    self.output_compression,
+                )?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut buffer = String::new();
+        read_lines(input, |line| {
+            buffer.clear();
+            push_line(&mut buffer, &line);
+            let partition = self.partition_of(&line);
+            outputs[partition]
+                .write_all(buffer.as_bytes())
+                .context(WriteOutputSnafu)
+        })?;
+
+        for mut output in outputs {
+            output.finish().context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the partition of the line.
+    fn partition_of(&self, line: &ParsedLine<'_>) -> usize {
+        let hash = match self.by {
+            PartitionBy::Series => fnv1a(&[&series_key(line)]),
+            PartitionBy::Measurement => fnv1a(&[line.series.measurement.as_str()]),
+        };
+        (hash % u64::from(self.partitions)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_partition() {
+        let mut input = String::new();
+        for ts in 0..10 {
+            for host in 0..20 {
+                // Tags of a series may be in different orders.
+                if ts % 2 == 0 {
+                    input.push_str(&format!("cpu,host=h{host},dc=a usage={ts} {ts}\n"));
+                } else {
+                    input.push_str(&format!("cpu,dc=a,host=h{host} usage={ts} {ts}\n"));
+                }
+            }
+        }
+        let input_file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(input_file.path(), &input).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let partition = Partition::try_parse_from([
+            "partition",
+            "-i",
+            input_file.path().to_str().unwrap(),
+            "-o",
+            dir.path().to_str().unwrap(),
+            "--partitions",
+            "4",
+        ])
+        .unwrap();
+        partition.run().unwrap();
+
+        let mut files_of_host: HashMap<String, usize> = HashMap::new();
+        let mut num_lines = 0;
+        let mut non_empty = 0;
+        for i in 0..4 {
+            let content = fs::read_to_string(dir.path().join(format!("part-{i:05}.lp"))).unwrap();
+            non_empty += usize::from(!content.is_empty());
+            let mut last_ts: HashMap<String, i64> = HashMap::new();
+            read_lines(content.as_bytes(), |line| {
+                num_lines += 1;
+                let host = line
+                    .series
+                    .tag_set
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|(k, _)| k.as_str() == "host")
+                    .unwrap()
+                    .1
+                    .to_string();
+                assert_eq!(i, *files_of_host.entry(host.clone()).or_insert(i));
+                // Points of a series keep their order.
+                let ts = line.timestamp.unwrap();
+                assert!(ts >= *last_ts.get(&host).unwrap_or(&0));
+                last_ts.insert(host, ts);
+                Ok(())
+            })
+            .unwrap();
+        }
+        assert_eq!(200, num_lines);
+        assert_eq!(20, files_of_host.len());
+        assert!(non_empty > 1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Query InfluxDB and export the result.
+
+use crate::error::{ReadCsvSnafu, RequestStatusSnafu, Result, SendRequestSnafu, WriteOutputSnafu};
+use crate::influx::timestamp::{Precision, TimestampParser};
+use crate::influx::{ColumnValue, LineWriter};
+use crate::io::{create_output, Compression, Output};
+use clap::{Parser, ValueEnum};
+use csv::{ReaderBuilder, StringRecord};
+use snafu::ResultExt;
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Language of the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum QueryLanguage {
+    /// Flux, sent to the v2 query API.
+    Flux,
+    /// InfluxQL, sent to the v1 compatible query API.
+    Influxql,
+}
+
+/// Format of the exported result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum OutputFormat {
+    /// CSV returned by the server.
+    Csv,
+    /// Line protocol.
+    Lp,
+}
+
+/// Query InfluxDB and write the result to CSV or line protocol file.
+#[derive(Debug, Parser)]
+pub(crate) struct Query {
+    /// Query to run.
+    #[arg(short, long)]
+    query: String,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    /// URL of the InfluxDB server, e.g. http://localhost:8086.
+    #[arg(long)]
+    url: String,
+    /// Organization name of Flux queries.
+    #[arg(long)]
+    org: Option<String>,
+    /// Database name of InfluxQL queries.
+    #[arg(long, required_if_eq("language", "influxql"))]
+    db: Option<String>,
+    /// API token.
+    #[arg(long)]
+    token: Option<String>,
+    /// Language of the query.
+    #[arg(long, value_enum, default_value_t = QueryLanguage::Flux)]
+    language: QueryLanguage,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl Query {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut source = self.send()?;
+        let mut output = create_output(&self.output, self.output_compression)?;
+        match self.format {
+            OutputFormat::Csv => {
+                io::copy(&mut source, &mut output).context(WriteOutputSnafu)?;
+                output.finish().context(WriteOutputSnafu)
+            }
+            OutputFormat::Lp => {
+                let mut writer = LineWriter::new(
+                    output,
+                    String::new(),
+                    TimestampParser::new(Precision::Ns, None),
+                    HashSet::new(),
+                    String::new(),
+                )?;
+                match self.language {
+                    QueryLanguage::Flux => {
+                        writer.convert_flux_csv(source, Path::new(QUERY_RESULT))?
+                    }
+                    QueryLanguage::Influxql => writer.convert_influxql_csv(source)?,
+                }
+                writer.finish()
+            }
+        }
+    }
+
+    /// Send the query and returns the CSV response.
+    fn send(&self) -> Result<Box<dyn Read + Send + Sync>> {
+        let base = self.url.trim_end_matches('/');
+        let (url, request, body) = match self.language {
+            QueryLanguage::Flux => {
+                let url = format!("{base}/api/v2/query");
+                let mut request = ureq::post(&url).set("Content-Type", "application/json");
+                if let Some(org) = &self.org {
+                    request = request.query("org", org);
+                }
+                let body = serde_json::json!({
+                    "query": self.query,
+                    "type": "flux",
+                    "dialect": { "header": true, "annotations": ["datatype"] },
+                });
+                (url, request, body.to_string())
+            }
+            QueryLanguage::Influxql => {
+                let url = format!("{base}/query");
+                let request = ureq::post(&url)
+                    .query("db", self.db.as_deref().unwrap_or_default())
+                    .query("q", &self.query)
+                    .query("epoch", "ns");
+                (url, request, String::new())
+            }
+        };
+        let mut request = request.set("Accept", "application/csv");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Token {token}"));
+        }
+
+        match request.send_string(&body) {
+            Ok(response) => Ok(response.into_reader()),
+            Err(ureq::Error::Status(status, response)) => RequestStatusSnafu {
+                url,
+                status,
+                body: response.into_string().unwrap_or_default(),
+            }
+            .fail(),
+            Err(ureq::Error::Transport(e)) => Err(Box::new(e)).context(SendRequestSnafu { url }),
+        }
+    }
+}
+
+/// Path of query results in errors.
+const QUERY_RESULT: &str = "<query result>";
+
+impl<W: Write> LineWriter<W> {
+    /// Convert InfluxQL CSV to lines.
+    ///
+    /// The CSV has the `name`, `tags` and `time` columns followed by fields, tags are
+    /// formatted as `key=value` pairs separated by commas.
+    fn convert_influxql_csv<R: Read>(&mut self, source: R) -> Result<()> {
+        let path = Path::new(QUERY_RESULT);
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(source);
+        let mut headers: Option<StringRecord> = None;
+
+        for result in reader.records() {
+            let record = result.context(ReadCsvSnafu { path })?;
+            // Each statement starts with a header.
+            if record.get(0) == Some("name") && record.get(1) == Some("tags") {
+                headers = Some(record);
+                continue;
+            }
+            let Some(headers) = &headers else {
+                continue;
+            };
+
+            let tags: Vec<_> = record
+                .get(1)
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .collect();
+            self.tags.clear();
+            self.tags.extend(tags.iter().map(|(k, _)| k.to_string()));
+            let mut columns: Vec<_> = tags
+                .iter()
+                .map(|(k, v)| (*k, ColumnValue::Text(v)))
+                .collect();
+            columns.extend(
+                headers
+                    .iter()
+                    .zip(record.iter())
+                    .skip(3)
+                    .map(|(name, v)| (name, ColumnValue::Text(v))),
+            );
+            if columns
+                .iter()
+                .all(|(name, v)| self.tags.contains(*name) || v.is_empty())
+            {
+                continue;
+            }
+            let timestamp = record.get(2).and_then(|v| self.timestamp_parser.parse(v));
+
+            self.write_row(record.get(0).unwrap_or_default(), &columns, timestamp)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Cursor;
+    use tempfile::NamedTempFile;
+
+    fn line_writer(output: &NamedTempFile) -> LineWriter<File> {
+        LineWriter::new(
+            output.reopen().unwrap(),
+            String::new(),
+            TimestampParser::new(Precision::Ns, None),
+            HashSet::new(),
+            String::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_convert_influxql_csv() {
+        let input = "name,tags,time,usage_user,usage_system
+cpu,\"host=host_0,region=eu\",1451606400000000000,58,
+cpu,\"host=host_1,region=eu\",1451606400000000000,,
+name,tags,time,used
+mem,,1451606400000000000,1.5
+";
+        let output = NamedTempFile::new().unwrap();
+        let mut writer = line_writer(&output);
+        writer.convert_influxql_csv(Cursor::new(input)).unwrap();
+        writer.flush().unwrap();
+
+        let mut lines = String::new();
+        output.reopen().unwrap().read_to_string(&mut lines).unwrap();
+        assert_eq!(
+            "cpu,host=host_0,region=eu usage_user=58 1451606400000000000\nmem used=1.5 1451606400000000000\n",
+            lines
+        );
+    }
+}
@@ -0,0 +1,286 @@
+//! Convert counters of line protocol to rates.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::downsample::{as_f64, to_static};
+use crate::influx::timestamp::parse_duration;
+use crate::influx::{push_fields, read_lines, series_key};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+
+/// Max share of steps a detected counter can decrease in, as resets.
+const MAX_RESET_RATIO: f64 = 0.01;
+
+/// Convert counter fields of line protocol file to rates between points of each series.
+///
+/// The rate at a point is the increase of the counter since the previous point of the
+/// series per unit, a decrease is a reset of the counter to zero. Counters are dropped
+/// from the first point of each series and other fields are kept as they are. Points are
+/// grouped in memory and lines without timestamp are dropped.
+///
+/// Without `--field`, counters are numeric fields of a measurement that are never
+/// negative, increase in some series and decrease in at most 1% of the steps.
+#[derive(Debug, Parser)]
+pub(crate) struct Rate {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Counter fields to convert, e.g. requests,errors, detected by default.
+    #[arg(long, value_delimiter = ',')]
+    field: Vec<String>,
+    /// Unit of rates, e.g. 1m for increases per minute.
+    #[arg(long, value_parser = parse_duration, default_value = "1s")]
+    unit: i64,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Rate {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let mut counters = Counters::default();
+        read_lines(input, |line| {
+            counters.update(&line);
+            Ok(())
+        })?;
+        let fields = if self.field.is_empty() {
+            counters.detect()
+        } else {
+            counters.with_fields(&self.field)
+        };
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = counters.write_rates(&fields, self.unit, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Points of series with counters.
+#[derive(Debug, Default)]
+struct Counters {
+    series: Vec<SeriesPoints>,
+    /// Index of series by the series key.
+    series_index: HashMap<String, usize>,
+}
+
+/// Points of a series.
+#[derive(Debug)]
+struct SeriesPoints {
+    measurement: String,
+    /// Measurement and sorted tags in line protocol.
+    key: String,
+    /// Timestamps and fields of points.
+    points: Vec<(i64, Vec<(String, FieldValue<'static>)>)>,
+}
+
+/// Steps between values of a field in series.
+#[derive(Debug, Default)]
+struct Steps {
+    steps: u64,
+    increases: u64,
+    decreases: u64,
+    /// Whether there is a value that can't be a counter.
+    invalid: bool,
+}
+
+impl Counters {
+    fn update(&mut self, line: &ParsedLine<'_>) {
+        let Some(ts) = line.timestamp else {
+            return;
+        };
+        let key = series_key(line);
+        let idx = match self.series_index.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                self.series_index.insert(key.clone(), self.series.len());
+                self.series.push(SeriesPoints {
+                    measurement: line.series.measurement.to_string(),
+                    key,
+                    points: Vec::new(),
+                });
+                self.series.len() - 1
+            }
+        };
+        let fields = line
+            .field_set
+            .iter()
+            .map(|(name, value)| (name.to_string(), to_static(value)))
+            .collect();
+        self.series[idx].points.push((ts, fields));
+    }
+
+    /// Sort points of each series by timestamp, keeping the order of the same timestamp.
+    fn sort(&mut self) {
+        for series in &mut self.series {
+            series.points.sort_by_key(|(ts, _)| *ts);
+        }
+    }
+
+    /// Returns the fields as counters of all measurements.
+    fn with_fields(&mut self, fields: &[String]) -> HashMap<String, HashSet<String>> {
+        self.sort();
+        let fields: HashSet<_> = fields.iter().cloned().collect();
+        self.series
+            .iter()
+            .map(|series| (series.measurement.clone(), fields.clone()))
+            .collect()
+    }
+
+    /// Returns fields of each measurement that look like counters.
+    fn detect(&mut self) -> HashMap<String, HashSet<String>> {
+        self.sort();
+        let mut steps: HashMap<(&str, &str), Steps> = HashMap::new();
+        for series in &self.series {
+            let mut last: HashMap<&str, f64> = HashMap::new();
+            for (_, fields) in &series.points {
+                for (name, value) in fields {
+                    let steps = steps
+                        .entry((series.measurement.as_str(), name.as_str()))
+                        .or_default();
+                    let Some(v) = as_f64(value).filter(|v| *v >= 0.0) else {
+                        steps.invalid = true;
+                        continue;
+                    };
+                    if let Some(prev) = last.insert(name, v) {
+                        steps.steps += 1;
+                        steps.increases += u64::from(v > prev);
+                        steps.decreases += u64::from(v < prev);
+                    }
+                }
+            }
+        }
+
+        let mut counters: HashMap<String, HashSet<String>> = HashMap::new();
+        for ((measurement, name), steps) in steps {
+            if !steps.invalid
+                && steps.increases > 0
+                && steps.decreases as f64 <= steps.steps as f64 * MAX_RESET_RATIO
+            {
+                counters
+                    .entry(measurement.to_string())
+                    .or_default()
+                    .insert(name.to_string());
+            }
+        }
+        counters
+    }
+
+    /// Write points with rates of the counters, series are in the order they first
+    /// appear and points are sorted.
+    fn write_rates<W: Write>(
+        &self,
+        counters: &HashMap<String, HashSet<String>>,
+        unit: i64,
+        dest: W,
+    ) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        let mut line = String::new();
+        for series in &self.series {
+            let counters = counters.get(&series.measurement);
+            let is_counter = |name: &str| counters.is_some_and(|c| c.contains(name));
+            // The last timestamp and value of each counter.
+            let mut last: HashMap<&str, (i64, f64)> = HashMap::new();
+            for (ts, fields) in &series.points {
+                let mut values = Vec::with_capacity(fields.len());
+                for (name, value) in fields {
+                    let counter = as_f64(value).filter(|_| is_counter(name));
+                    let Some(v) = counter else {
+                        values.push((name.as_str(), value.clone()));
+                        continue;
+                    };
+                    // A value of the same timestamp as the previous value has no rate.
+                    if let Some((prev_ts, prev)) = last.get(name.as_str()).filter(|(t, _)| t < ts) {
+                        let increase = if v >= *prev { v - prev } else { v };
+                        let rate = increase * unit as f64 / (ts - prev_ts) as f64;
+                        values.push((name.as_str(), FieldValue::F64(rate)));
+                    }
+                    last.insert(name, (*ts, v));
+                }
+                if values.is_empty() {
+                    continue;
+                }
+
+                line.clear();
+                line.push_str(&series.key);
+                push_fields(&mut line, values.iter().map(|(name, v)| (*name, v)));
+                writeln!(line, " {ts}").unwrap();
+                writer
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate() {
+        let counters = |input: &str| {
+            let mut counters = Counters::default();
+            read_lines(input.as_bytes(), |line| {
+                counters.update(&line);
+                Ok(())
+            })
+            .unwrap();
+            counters
+        };
+        let rates = |counters: &Counters, fields, unit| {
+            let output = counters.write_rates(fields, unit, Vec::new()).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        let input = "http,host=a requests=100i,errors=5i,latency=20 0
+http,host=b requests=10i,errors=0i,latency=30 0
+http,host=a requests=160i,errors=5i,latency=10 10000000000
+http,host=a requests=190i,errors=6i,latency=40 20000000000
+http,host=b requests=40i,errors=1i,latency=50 20000000000
+";
+        let mut http = counters(input);
+        // Latency decreases in a step.
+        let fields = http.detect();
+        assert_eq!(
+            HashSet::from(["requests".to_string(), "errors".to_string()]),
+            fields["http"]
+        );
+        assert_eq!(
+            "http,host=a latency=20 0
+http,host=a requests=6,errors=0,latency=10 10000000000
+http,host=a requests=3,errors=0.1,latency=40 20000000000
+http,host=b latency=30 0
+http,host=b requests=1.5,errors=0.05,latency=50 20000000000
+",
+            rates(&http, &fields, 1_000_000_000)
+        );
+
+        // The counter resets at 20s.
+        let input = "http requests=160i 10000000000
+http requests=100i,up=true 0
+http requests=30i 20000000000
+http requests=60i 20000000000
+";
+        let mut http = counters(input);
+        let fields = http.with_fields(&["requests".to_string()]);
+        assert_eq!(
+            "http up=true 0
+http requests=360 10000000000
+http requests=180 20000000000
+",
+            rates(&http, &fields, 60_000_000_000)
+        );
+    }
+}
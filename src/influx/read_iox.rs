@@ -0,0 +1,410 @@
+//! Read Parquet files of InfluxDB 3.x and IOx to line protocol.
+//!
+//! InfluxDB 3.x persists tables to Parquet files under
+//! `{node}/dbs/{database}-{id}/{table}-{id}/{date}/{time}/{file}.parquet` of the object
+//! store, and the type of each column is in the `iox::column::type` metadata of the Arrow
+//! schema of the file.
+
+use crate::error::{
+    ArrowSnafu, InvalidIoxFileSnafu, OpenFileSnafu, ReadDirSnafu, ReadParquetSnafu, Result,
+    WriteOutputSnafu,
+};
+use crate::influx::{push_fields, push_series};
+use crate::io::{create_output, open_file, Compression, Output};
+use arrow::array::{Array, ArrayRef, AsArray};
+use arrow::compute::cast;
+use arrow::datatypes::{DataType, Float64Type, Int64Type, TimeUnit, UInt64Type};
+use arrow::record_batch::RecordBatch;
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use snafu::{OptionExt, ResultExt};
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Key of the column type in the metadata of fields.
+const COLUMN_TYPE_KEY: &str = "iox::column::type";
+const TAG_TYPE: &str = "iox::column_type::tag";
+const TIMESTAMP_TYPE: &str = "iox::column_type::timestamp";
+/// Name of the timestamp column if the column type is unknown.
+const TIME_COLUMN: &str = "time";
+/// Directory of databases in the object store.
+const DBS_DIR: &str = "dbs";
+
+/// Convert Parquet files of InfluxDB 3.x and IOx to line protocol, to migrate data offline.
+///
+/// The database and the table of each file are read from its path, and the table is the
+/// measurement. Columns are tags, fields and the timestamp by their `iox::column::type`
+/// metadata, or dictionary columns are tags and the `time` column is the timestamp if the
+/// metadata is missing. Files are read as is, rows of files not yet persisted from the WAL
+/// are missing and rows in several files are written again.
+#[derive(Debug, Parser)]
+pub(crate) struct ReadIox {
+    /// Parquet file, or directory to read all `.parquet` files under, e.g. the data
+    /// directory `~/.influxdb3` or `{data-dir}/{node}/dbs/{database}-{id}`.
+    #[arg(long)]
+    path: PathBuf,
+    /// Only read tables of the database, can be repeated.
+    #[arg(long)]
+    database: Vec<String>,
+    /// Only read the table, can be repeated.
+    #[arg(long)]
+    table: Vec<String>,
+    /// Measurement of files not under the directory of databases.
+    #[arg(long)]
+    measurement: Option<String>,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl ReadIox {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut files = Vec::new();
+        parquet_files(&self.path, &mut files)?;
+        files.sort_unstable();
+
+        let mut output = create_output(&self.output, self.output_compression)?;
+        for path in files {
+            let (database, table) = match table_of(&path) {
+                Some((database, table)) => (Some(database), table),
+                None => (
+                    None,
+                    self.measurement.as_deref().context(InvalidIoxFileSnafu {
+                        path: &path,
+                        reason: "not under a database directory, --measurement is required",
+                    })?,
+                ),
+            };
+            if !self.database.is_empty()
+                && !database.is_some_and(|database| self.database.iter().any(|d| d == database))
+            {
+                continue;
+            }
+            if !self.table.is_empty() && !self.table.iter().any(|t| t == table) {
+                continue;
+            }
+            output = parquet_to_line_protocol(&path, table, output)?;
+        }
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Append the path if it is a file, or all `.parquet` files under the path if it is a
+/// directory.
+fn parquet_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let metadata = fs::metadata(path).context(OpenFileSnafu { path })?;
+    if !metadata.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in path.read_dir().context(ReadDirSnafu { path })? {
+        let entry = entry.context(ReadDirSnafu { path })?;
+        let path = entry.path();
+        if path.is_dir() {
+            parquet_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "parquet") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the database and the table of the file under `dbs/{database}-{id}/{table}-{id}`.
+fn table_of(path: &Path) -> Option<(&str, &str)> {
+    let mut components = path.components().filter_map(|c| match c {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    });
+    components.find(|name| *name == DBS_DIR)?;
+    let database = components.next()?;
+    let table = components.next()?;
+    // The file is under directories of the date and the time.
+    components.next()?;
+    Some((strip_id(database), strip_id(table)))
+}
+
+/// Returns the name without the `-{id}` suffix.
+fn strip_id(name: &str) -> &str {
+    match name.rsplit_once('-') {
+        Some((name, id)) if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) => name,
+        _ => name,
+    }
+}
+
+/// Role of a column in lines.
+enum Column {
+    Tag(ArrayRef),
+    Field(ArrayRef),
+    Timestamp(ArrayRef),
+}
+
+/// Convert rows of the Parquet file to lines of the measurement.
+fn parquet_to_line_protocol<W: Write>(path: &Path, measurement: &str, dest: W) -> Result<W> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(open_file(path)?)
+        .and_then(|builder| builder.build())
+        .context(ReadParquetSnafu { path })?;
+
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    for batch in reader {
+        let batch = batch.context(ArrowSnafu)?;
+        write_batch(path, measurement, &batch, &mut line, &mut writer)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn write_batch<W: Write>(
+    path: &Path,
+    measurement: &str,
+    batch: &RecordBatch,
+    line: &mut String,
+    writer: &mut W,
+) -> Result<()> {
+    let schema = batch.schema();
+    let mut names = Vec::with_capacity(batch.num_columns());
+    let mut columns = Vec::with_capacity(batch.num_columns());
+    for (field, array) in schema.fields().iter().zip(batch.columns()) {
+        let column_type = field.metadata().get(COLUMN_TYPE_KEY).map(String::as_str);
+        let column = match column_type {
+            Some(TAG_TYPE) => Column::Tag(cast(array, &DataType::Utf8).context(ArrowSnafu)?),
+            Some(TIMESTAMP_TYPE) => Column::Timestamp(timestamp_array(path, field.name(), array)?),
+            Some(_) => Column::Field(field_array(path, field.name(), array)?),
+            None if matches!(field.data_type(), DataType::Dictionary(_, _)) => {
+                Column::Tag(cast(array, &DataType::Utf8).context(ArrowSnafu)?)
+            }
+            None if field.name() == TIME_COLUMN => {
+                Column::Timestamp(timestamp_array(path, field.name(), array)?)
+            }
+            None => Column::Field(field_array(path, field.name(), array)?),
+        };
+        names.push(field.name().as_str());
+        columns.push(column);
+    }
+
+    let mut tags = Vec::new();
+    let mut fields = Vec::new();
+    for i in 0..batch.num_rows() {
+        tags.clear();
+        fields.clear();
+        let mut timestamp = None;
+        for (name, column) in names.iter().zip(&columns) {
+            match column {
+                Column::Tag(array) if array.is_valid(i) => {
+                    let value = array.as_string::<i32>().value(i);
+                    // Empty tag values are invalid in line protocol.
+                    if !value.is_empty() {
+                        tags.push((*name, value));
+                    }
+                }
+                Column::Field(array) if array.is_valid(i) => {
+                    fields.push((*name, field_value(array, i)));
+                }
+                Column::Timestamp(array) if array.is_valid(i) => {
+                    timestamp = Some(array.as_primitive::<Int64Type>().value(i));
+                }
+                _ => {}
+            }
+        }
+        // Rows without fields are not valid lines.
+        if fields.is_empty() {
+            continue;
+        }
+
+        line.clear();
+        push_series(line, measurement, tags.iter().copied());
+        push_fields(line, fields.iter().map(|(k, v)| (*k, v)));
+        if let Some(ts) = timestamp {
+            write!(line, " {ts}").unwrap();
+        }
+        line.push('\n');
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+    Ok(())
+}
+
+/// Returns the timestamp column as nanoseconds in `Int64`.
+fn timestamp_array(path: &Path, column: &str, array: &ArrayRef) -> Result<ArrayRef> {
+    let DataType::Timestamp(_, _) = array.data_type() else {
+        return InvalidIoxFileSnafu {
+            path,
+            reason: format!("timestamp column {column} has type {}", array.data_type()),
+        }
+        .fail();
+    };
+    let array =
+        cast(array, &DataType::Timestamp(TimeUnit::Nanosecond, None)).context(ArrowSnafu)?;
+    cast(&array, &DataType::Int64).context(ArrowSnafu)
+}
+
+/// Returns the field column casted to one of the line protocol types.
+fn field_array(path: &Path, column: &str, array: &ArrayRef) -> Result<ArrayRef> {
+    let data_type = match array.data_type() {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => DataType::Int64,
+        DataType::UInt8 | DataType::UInt16 | DataType::UInt32 | DataType::UInt64 => {
+            DataType::UInt64
+        }
+        DataType::Float16 | DataType::Float32 | DataType::Float64 => DataType::Float64,
+        DataType::Boolean => DataType::Boolean,
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View | DataType::Dictionary(_, _) => {
+            DataType::Utf8
+        }
+        other => {
+            return InvalidIoxFileSnafu {
+                path,
+                reason: format!("field column {column} has type {other}"),
+            }
+            .fail()
+        }
+    };
+    cast(array, &data_type).context(ArrowSnafu)
+}
+
+/// Returns the value of the field column casted by [field_array].
+fn field_value(array: &ArrayRef, i: usize) -> FieldValue<'_> {
+    match array.data_type() {
+        DataType::Int64 => FieldValue::I64(array.as_primitive::<Int64Type>().value(i)),
+        DataType::UInt64 => FieldValue::U64(array.as_primitive::<UInt64Type>().value(i)),
+        DataType::Float64 => FieldValue::F64(array.as_primitive::<Float64Type>().value(i)),
+        DataType::Boolean => FieldValue::Boolean(array.as_boolean().value(i)),
+        _ => FieldValue::String(array.as_string::<i32>().value(i).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{
+        BooleanArray, Float64Array, Int64Array, StringArray, StringDictionaryBuilder,
+        TimestampNanosecondArray,
+    };
+    use arrow::datatypes::{Field, Int32Type, Schema};
+    use parquet::arrow::ArrowWriter;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::sync::Arc;
+    use tempfile::{Builder, NamedTempFile};
+
+    fn write_parquet(path: &Path, fields: Vec<Field>, columns: Vec<ArrayRef>) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn typed(field: Field, column_type: &str) -> Field {
+        field.with_metadata(HashMap::from([(
+            COLUMN_TYPE_KEY.to_string(),
+            column_type.to_string(),
+        )]))
+    }
+
+    #[test]
+    fn test_read_iox() {
+        let dir = Builder::new().tempdir().unwrap();
+        let dbs = dir.path().join("node0").join(DBS_DIR);
+        let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+        let time = DataType::Timestamp(TimeUnit::Nanosecond, None);
+
+        let mut host = StringDictionaryBuilder::<Int32Type>::new();
+        host.append_value("a b");
+        host.append_null();
+        host.append_value("c");
+        write_parquet(
+            &dbs.join("db-1/cpu-0/2024-01-01/00-00/0000000001.parquet"),
+            vec![
+                typed(Field::new("host", dictionary.clone(), true), TAG_TYPE),
+                typed(
+                    Field::new("usage", DataType::Float64, true),
+                    "iox::column_type::field::float",
+                ),
+                typed(
+                    Field::new("count", DataType::Int64, true),
+                    "iox::column_type::field::integer",
+                ),
+                typed(
+                    Field::new("note", DataType::Utf8, true),
+                    "iox::column_type::field::string",
+                ),
+                typed(Field::new("time", time.clone(), false), TIMESTAMP_TYPE),
+            ],
+            vec![
+                Arc::new(host.finish()),
+                Arc::new(Float64Array::from(vec![Some(1.5), Some(2.0), None])),
+                Arc::new(Int64Array::from(vec![Some(3), None, None])),
+                Arc::new(StringArray::from(vec![None, Some("x\"y"), None])),
+                Arc::new(TimestampNanosecondArray::from(vec![1, 2, 3])),
+            ],
+        );
+        // Columns without metadata.
+        let mut region = StringDictionaryBuilder::<Int32Type>::new();
+        region.append_value("us");
+        write_parquet(
+            &dbs.join("db-1/disk-io-12/2024-01-01/00-10/0000000002.parquet"),
+            vec![
+                Field::new("region", dictionary, true),
+                Field::new("up", DataType::Boolean, true),
+                Field::new("time", time.clone(), false),
+            ],
+            vec![
+                Arc::new(region.finish()),
+                Arc::new(BooleanArray::from(vec![true])),
+                Arc::new(TimestampNanosecondArray::from(vec![4])),
+            ],
+        );
+        write_parquet(
+            &dbs.join("other-2/mem-3/2024-01-01/00-00/0000000003.parquet"),
+            vec![
+                Field::new("used", DataType::Int64, true),
+                Field::new("time", time, false),
+            ],
+            vec![
+                Arc::new(Int64Array::from(vec![5])),
+                Arc::new(TimestampNanosecondArray::from(vec![5])),
+            ],
+        );
+
+        let read = |database: Vec<String>, table: Vec<String>| {
+            let output = NamedTempFile::new().unwrap();
+            ReadIox {
+                path: dir.path().to_path_buf(),
+                database,
+                table,
+                measurement: None,
+                output: output.path().to_str().unwrap().to_string(),
+                output_compression: Compression::None,
+            }
+            .run()
+            .unwrap();
+            fs::read_to_string(output.path()).unwrap()
+        };
+        assert_eq!(
+            "cpu,host=a\\ b usage=1.5,count=3i 1
+cpu usage=2,note=\"x\\\"y\" 2
+disk-io,region=us up=true 4
+",
+            read(vec!["db".to_string()], Vec::new())
+        );
+        assert_eq!("mem used=5i 5\n", read(Vec::new(), vec!["mem".to_string()]));
+
+        assert_eq!(
+            Some(("db", "disk-io")),
+            table_of(&dbs.join("db-1/disk-io-12/d/t/f.parquet"))
+        );
+        assert_eq!(None, table_of(Path::new("/data/cpu/f.parquet")));
+    }
+}
@@ -0,0 +1,687 @@
+//! Read InfluxDB TSM files to line protocol.
+//!
+//! A TSM file has blocks of compressed points and an index of the blocks of each key at the
+//! end, see <https://docs.influxdata.com/influxdb/v1/concepts/storage_engine/#tsm-files>.
+
+use crate::decode::{zigzag, BitReader, Decbuf};
+use crate::error::{
+    InvalidTsmSnafu, OpenFileSnafu, ReadDirSnafu, ReadFileSnafu, Result, WriteOutputSnafu,
+};
+use crate::influx::{push_fields, push_series};
+use crate::io::{create_output, Compression, Output};
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Magic number of TSM files.
+const MAGIC: u32 = 0x16D1_16D1;
+/// Version of TSM files.
+const VERSION: u8 = 1;
+/// Separator of the series key and the field key in keys of the index.
+const FIELD_SEPARATOR: &[u8] = b"#!~#";
+/// Tag key of the measurement in series keys of InfluxDB 2.x.
+const MEASUREMENT_TAG_KEY: &[u8] = b"\x00";
+/// Tag key of the field in series keys of InfluxDB 2.x.
+const FIELD_TAG_KEY: &[u8] = b"\xff";
+/// Size of the checksum at the start of blocks.
+const CHECKSUM_SIZE: usize = 4;
+/// Maximum number of points decoded from a block, far more than the 1000 points InfluxDB
+/// writes to a block by default.
+const MAX_BLOCK_POINTS: usize = 1 << 20;
+/// The NaN marking the end of float values.
+const FLOAT_END: u64 = 0x7FF8_0000_0000_0001;
+
+/// Types of blocks.
+const BLOCK_FLOAT: u8 = 0;
+const BLOCK_INTEGER: u8 = 1;
+const BLOCK_BOOLEAN: u8 = 2;
+const BLOCK_STRING: u8 = 3;
+const BLOCK_UNSIGNED: u8 = 4;
+
+/// Encodings of timestamps and integers, in the upper 4 bits of the first byte.
+const ENCODING_UNCOMPRESSED: u8 = 0;
+const ENCODING_SIMPLE8B: u8 = 1;
+const ENCODING_RLE: u8 = 2;
+
+/// Number and width in bits of values in simple8b words of each selector.
+const SIMPLE8B_SELECTORS: [(usize, u32); 16] = [
+    (240, 0),
+    (120, 0),
+    (60, 1),
+    (30, 2),
+    (20, 3),
+    (15, 4),
+    (12, 5),
+    (10, 6),
+    (8, 7),
+    (7, 8),
+    (6, 10),
+    (5, 12),
+    (4, 15),
+    (3, 20),
+    (2, 30),
+    (1, 60),
+];
+
+/// Convert points of TSM files to line protocol.
+///
+/// Fields of the same series and timestamp in a file are written as a line. Tombstones are
+/// not applied, points overwritten in other files are written again and checksums of blocks
+/// are not verified. Series keys of InfluxDB 2.x are written with the measurement of the
+/// `\x00` tag, without the organization and bucket.
+#[derive(Debug, Parser)]
+pub(crate) struct ReadTsm {
+    /// TSM file, or directory to read all `.tsm` files under, e.g.
+    /// `/var/lib/influxdb/data/db/autogen/1`.
+    #[arg(long)]
+    path: PathBuf,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl ReadTsm {
+    pub(crate) fn run(self) -> Result<()> {
+        let mut files = Vec::new();
+        tsm_files(&self.path, &mut files)?;
+        files.sort_unstable();
+
+        let mut output = create_output(&self.output, self.output_compression)?;
+        for path in files {
+            let data = fs::read(&path).context(ReadFileSnafu { path: &path })?;
+            tsm_to_line_protocol(&path, &data, &mut output)?;
+        }
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Append the path if it is a file, or all `.tsm` files under the path if it is a directory.
+fn tsm_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let metadata = fs::metadata(path).context(OpenFileSnafu { path })?;
+    if !metadata.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    for entry in path.read_dir().context(ReadDirSnafu { path })? {
+        let entry = entry.context(ReadDirSnafu { path })?;
+        let path = entry.path();
+        if path.is_dir() {
+            tsm_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "tsm") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Convert points of the TSM file to line protocol.
+fn tsm_to_line_protocol<W: Write>(path: &Path, data: &[u8], dest: W) -> Result<W> {
+    let invalid = |reason| InvalidTsmSnafu { path, reason }.build();
+    let entries = parse_index(data).map_err(invalid)?;
+
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    let mut series: &[u8] = &[];
+    let mut points: BTreeMap<i64, Vec<(String, Value)>> = BTreeMap::new();
+    let mut values = Vec::new();
+    for entry in entries {
+        let (series_key, field) = split_key(entry.key).map_err(invalid)?;
+        if series_key != series {
+            write_series(path, series, &points, &mut line, &mut writer)?;
+            series = series_key;
+            points.clear();
+        }
+
+        let field = String::from_utf8_lossy(field);
+        for (offset, size) in entry.blocks {
+            let block = usize::try_from(offset)
+                .ok()
+                .and_then(|offset| data.get(offset..offset.checked_add(size as usize)?))
+                .ok_or_else(|| format!("block at offset {offset} is out of range"))
+                .map_err(invalid)?;
+            values.clear();
+            read_block(block, &mut values)
+                .map_err(|reason| invalid(format!("block at offset {offset}: {reason}")))?;
+            for (ts, value) in values.drain(..) {
+                let fields = points.entry(ts).or_default();
+                // Points of later blocks overwrite points of the same timestamp.
+                match fields.last_mut() {
+                    Some((last, v)) if *last == field => *v = value,
+                    _ => fields.push((field.to_string(), value)),
+                }
+            }
+        }
+    }
+    write_series(path, series, &points, &mut line, &mut writer)?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Write lines of points of the series.
+fn write_series<W: Write>(
+    path: &Path,
+    series: &[u8],
+    points: &BTreeMap<i64, Vec<(String, Value)>>,
+    line: &mut String,
+    writer: &mut W,
+) -> Result<()> {
+    if points.is_empty() {
+        return Ok(());
+    }
+    let mut prefix = String::new();
+    push_series_key(&mut prefix, series)
+        .map_err(|reason| InvalidTsmSnafu { path, reason }.build())?;
+    for (ts, fields) in points {
+        line.clear();
+        line.push_str(&prefix);
+        let values: Vec<_> = fields.iter().map(|(_, v)| v.as_field()).collect();
+        push_fields(line, fields.iter().map(|(k, _)| k.as_str()).zip(&values));
+        writeln!(line, " {ts}").unwrap();
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+    Ok(())
+}
+
+/// Value of a point.
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Float(f64),
+    Integer(i64),
+    Unsigned(u64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Value {
+    fn as_field(&self) -> FieldValue<'_> {
+        match self {
+            Value::Float(v) => FieldValue::F64(*v),
+            Value::Integer(v) => FieldValue::I64(*v),
+            Value::Unsigned(v) => FieldValue::U64(*v),
+            Value::Boolean(v) => FieldValue::Boolean(*v),
+            Value::String(v) => FieldValue::String(v.as_str().into()),
+        }
+    }
+}
+
+/// Blocks of a key in the index.
+struct IndexEntry<'a> {
+    key: &'a [u8],
+    /// Offsets and sizes of blocks.
+    blocks: Vec<(u64, u32)>,
+}
+
+/// Parse the index at the end of the file, the last 8 bytes are the offset of the index.
+fn parse_index(data: &[u8]) -> Result<Vec<IndexEntry<'_>>, String> {
+    let mut header = Decbuf::new(data);
+    if header.be_u32()? != MAGIC {
+        return Err("invalid magic number".to_string());
+    }
+    let version = header.byte()?;
+    if version != VERSION {
+        return Err(format!("unsupported version {version}"));
+    }
+
+    let footer = data.len().checked_sub(8).ok_or("file is too short")?;
+    let index_start = Decbuf::at(data, footer).be_u64()?;
+    let index_start = usize::try_from(index_start)
+        .ok()
+        .filter(|start| *start <= footer)
+        .ok_or_else(|| format!("invalid index offset {index_start}"))?;
+
+    let mut buf = Decbuf::at(&data[..footer], index_start);
+    let mut entries = Vec::new();
+    while !buf.is_empty() {
+        let key_len = buf.be_u16()?;
+        let key = buf.bytes(key_len as usize)?;
+        // The type is also in the blocks.
+        buf.byte()?;
+        let count = buf.be_u16()?;
+        let blocks = (0..count)
+            .map(|_| {
+                // Skip the min and max time of the block.
+                buf.bytes(16)?;
+                Ok((buf.be_u64()?, buf.be_u32()?))
+            })
+            .collect::<Result<_, String>>()?;
+        entries.push(IndexEntry { key, blocks });
+    }
+    Ok(entries)
+}
+
+/// Split the key into the series key and the field key.
+fn split_key(key: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let pos = key
+        .windows(FIELD_SEPARATOR.len())
+        .position(|w| w == FIELD_SEPARATOR)
+        .ok_or_else(|| format!("key {} has no field", String::from_utf8_lossy(key)))?;
+    Ok((&key[..pos], &key[pos + FIELD_SEPARATOR.len()..]))
+}
+
+/// Push the measurement and tags of the escaped series key.
+fn push_series_key(line: &mut String, series: &[u8]) -> Result<(), String> {
+    let mut parts = split_unescaped(series, b',').into_iter();
+    let mut measurement = unescape(parts.next().unwrap_or_default());
+    let mut tags = Vec::new();
+    for part in parts {
+        let [key, value] = split_unescaped(part, b'=')[..] else {
+            return Err(format!(
+                "invalid tag in series key {}",
+                String::from_utf8_lossy(series)
+            ));
+        };
+        match key {
+            MEASUREMENT_TAG_KEY => measurement = unescape(value),
+            FIELD_TAG_KEY => {}
+            _ => tags.push((unescape(key), unescape(value))),
+        }
+    }
+    push_series(
+        line,
+        &measurement,
+        tags.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+    );
+    Ok(())
+}
+
+/// Split bytes by the separator that is not escaped by `\`.
+fn split_unescaped(bytes: &[u8], separator: u8) -> Vec<&[u8]> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, b) in bytes.iter().enumerate() {
+        if escaped {
+            escaped = false;
+        } else if *b == b'\\' {
+            escaped = true;
+        } else if *b == separator {
+            parts.push(&bytes[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&bytes[start..]);
+    parts
+}
+
+/// Remove `\` before escaped commas, equal signs and spaces.
+fn unescape(bytes: &[u8]) -> String {
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    for (i, b) in bytes.iter().enumerate() {
+        let next = bytes.get(i + 1);
+        if *b != b'\\' || !matches!(next, Some(b',' | b'=' | b' ')) {
+            unescaped.push(*b);
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// Append points of the block.
+///
+/// A block is the checksum, the type, the length of timestamps in uvarint, the timestamps
+/// and the values.
+fn read_block(block: &[u8], points: &mut Vec<(i64, Value)>) -> Result<(), String> {
+    let mut buf = Decbuf::at(block, CHECKSUM_SIZE);
+    let block_type = buf.byte()?;
+    let len = buf.uvarint()?;
+    let timestamps = decode_timestamps(buf.bytes(len as usize)?)?;
+    let data = buf.rest();
+    let values: Vec<_> = match block_type {
+        BLOCK_FLOAT => decode_floats(data)?.into_iter().map(Value::Float).collect(),
+        BLOCK_INTEGER => decode_integers(data)?
+            .into_iter()
+            .map(Value::Integer)
+            .collect(),
+        BLOCK_BOOLEAN => decode_booleans(data)?
+            .into_iter()
+            .map(Value::Boolean)
+            .collect(),
+        BLOCK_STRING => decode_strings(data)?
+            .into_iter()
+            .map(Value::String)
+            .collect(),
+        BLOCK_UNSIGNED => decode_integers(data)?
+            .into_iter()
+            .map(|v| Value::Unsigned(v as u64))
+            .collect(),
+        _ => return Err(format!("unknown block type {block_type}")),
+    };
+    if values.len() != timestamps.len() {
+        return Err(format!(
+            "block has {} timestamps and {} values",
+            timestamps.len(),
+            values.len()
+        ));
+    }
+    points.extend(timestamps.into_iter().zip(values));
+    Ok(())
+}
+
+/// Decode timestamps, the lower 4 bits of the first byte are the log10 of the divisor of
+/// deltas.
+fn decode_timestamps(data: &[u8]) -> Result<Vec<i64>, String> {
+    let mut buf = Decbuf::new(data);
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    let header = buf.byte()?;
+    let scale = 10u64.pow(u32::from(header & 0xF));
+    let deltas = match header >> 4 {
+        ENCODING_UNCOMPRESSED => decode_uncompressed(buf.rest())?,
+        ENCODING_SIMPLE8B => {
+            let first = buf.be_u64()?;
+            let mut deltas = decode_simple8b(buf.rest())?;
+            deltas.iter_mut().for_each(|d| *d = d.wrapping_mul(scale));
+            deltas.insert(0, first);
+            deltas
+        }
+        ENCODING_RLE => {
+            let first = buf.be_u64()?;
+            let delta = buf.uvarint()?.wrapping_mul(scale);
+            let count = rle_count(&mut buf)?;
+            return Ok((0..count)
+                .map(|i| first.wrapping_add(delta.wrapping_mul(i as u64)) as i64)
+                .collect());
+        }
+        encoding => return Err(format!("unknown timestamp encoding {encoding}")),
+    };
+    Ok(deltas
+        .into_iter()
+        .scan(0u64, |t, delta| {
+            *t = t.wrapping_add(delta);
+            Some(*t as i64)
+        })
+        .collect())
+}
+
+/// Decode integers of zigzag encoded deltas.
+fn decode_integers(data: &[u8]) -> Result<Vec<i64>, String> {
+    let mut buf = Decbuf::new(data);
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut deltas = match buf.byte()? >> 4 {
+        ENCODING_UNCOMPRESSED => decode_uncompressed(buf.rest())?,
+        ENCODING_SIMPLE8B => {
+            let first = buf.be_u64()?;
+            let mut deltas = decode_simple8b(buf.rest())?;
+            deltas.insert(0, first);
+            deltas
+        }
+        ENCODING_RLE => {
+            let first = zigzag(buf.be_u64()?);
+            let delta = zigzag(buf.uvarint()?);
+            let count = rle_count(&mut buf)?;
+            return Ok((0..=count)
+                .map(|i| first.wrapping_add(delta.wrapping_mul(i as i64)))
+                .collect());
+        }
+        encoding => return Err(format!("unknown integer encoding {encoding}")),
+    }
+    .into_iter();
+    Ok(std::iter::successors(deltas.next().map(zigzag), |prev| {
+        Some(prev.wrapping_add(zigzag(deltas.next()?)))
+    })
+    .collect())
+}
+
+/// Read the number of run-length encoded values, which is not bounded by the data.
+fn rle_count(buf: &mut Decbuf) -> Result<usize, String> {
+    let count = buf.uvarint()?;
+    if count >= MAX_BLOCK_POINTS as u64 {
+        return Err(format!("RLE count {count} exceeds the block"));
+    }
+    Ok(count as usize)
+}
+
+/// Decode values of 8 bytes.
+fn decode_uncompressed(data: &[u8]) -> Result<Vec<u64>, String> {
+    if !data.len().is_multiple_of(8) {
+        return Err("invalid length of uncompressed values".to_string());
+    }
+    Ok(data
+        .chunks_exact(8)
+        .map(|chunk| u64::from_be_bytes(chunk.try_into().unwrap()))
+        .collect())
+}
+
+/// Decode simple8b words, the upper 4 bits of each word select the number and width of
+/// values packed from the lower bits.
+fn decode_simple8b(data: &[u8]) -> Result<Vec<u64>, String> {
+    let mut values = Vec::new();
+    for word in decode_uncompressed(data)? {
+        let (n, bits) = SIMPLE8B_SELECTORS[(word >> 60) as usize];
+        if bits == 0 {
+            values.extend(std::iter::repeat_n(1, n));
+            continue;
+        }
+        let mask = (1 << bits) - 1;
+        values.extend((0..n as u32).map(|i| (word >> (i * bits)) & mask));
+    }
+    Ok(values)
+}
+
+/// Decode floats XOR encoded like Gorilla, ended by a NaN.
+fn decode_floats(data: &[u8]) -> Result<Vec<f64>, String> {
+    let mut buf = Decbuf::new(data);
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    buf.byte()?;
+    let mut reader = BitReader::new(buf.rest());
+    let mut values = Vec::new();
+    let mut v = reader.bits(64)?;
+    let (mut leading, mut trailing) = (0, 0);
+    while v != FLOAT_END {
+        values.push(f64::from_bits(v));
+        v = reader.xor(v, &mut leading, &mut trailing)?;
+    }
+    Ok(values)
+}
+
+/// Decode booleans of the count in uvarint and bits.
+fn decode_booleans(data: &[u8]) -> Result<Vec<bool>, String> {
+    let mut buf = Decbuf::new(data);
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    buf.byte()?;
+    let count = buf.uvarint()? as usize;
+    let bits = buf.rest();
+    if bits.len() < count.div_ceil(8) {
+        return Err("unexpected end of booleans".to_string());
+    }
+    Ok((0..count)
+        .map(|i| bits[i / 8] & (0x80 >> (i % 8)) != 0)
+        .collect())
+}
+
+/// Decode snappy compressed strings, each is the length in uvarint and the bytes.
+fn decode_strings(data: &[u8]) -> Result<Vec<String>, String> {
+    let mut buf = Decbuf::new(data);
+    if buf.is_empty() {
+        return Ok(Vec::new());
+    }
+    buf.byte()?;
+    let data = snap::raw::Decoder::new()
+        .decompress_vec(buf.rest())
+        .map_err(|e| e.to_string())?;
+    let mut buf = Decbuf::new(&data);
+    let mut values = Vec::new();
+    while !buf.is_empty() {
+        let len = buf.uvarint()?;
+        let bytes = buf.bytes(len as usize)?;
+        values.push(String::from_utf8_lossy(bytes).into_owned());
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_uvarint(data: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            data.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        data.push(value as u8);
+    }
+
+    /// Encode floats with new leading and trailing zeros of each value.
+    fn encode_floats(values: &[f64]) -> Vec<u8> {
+        let mut bits = Vec::new();
+        let mut push = |value: u64, n: usize| bits.extend((0..n).rev().map(|i| (value >> i) & 1));
+        let mut prev = values[0].to_bits();
+        push(prev, 64);
+        for v in values[1..].iter().map(|v| v.to_bits()).chain([FLOAT_END]) {
+            let xor = v ^ prev;
+            prev = v;
+            if xor == 0 {
+                push(0, 1);
+                continue;
+            }
+            let leading = (xor.leading_zeros() as usize).min(31);
+            let trailing = xor.trailing_zeros() as usize;
+            let significant = 64 - leading - trailing;
+            push(0b11, 2);
+            push(leading as u64, 5);
+            push(significant as u64 % 64, 6);
+            push(xor >> trailing, significant);
+        }
+        let mut data = vec![1 << 4];
+        for chunk in bits.chunks(8) {
+            let byte = chunk.iter().fold(0, |b, bit| (b << 1) | *bit as u8);
+            data.push(byte << (8 - chunk.len()));
+        }
+        data
+    }
+
+    fn block(block_type: u8, timestamps: &[u8], values: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; CHECKSUM_SIZE];
+        data.push(block_type);
+        encode_uvarint(&mut data, timestamps.len() as u64);
+        data.extend(timestamps);
+        data.extend(values);
+        data
+    }
+
+    fn tsm_file(blocks: &[(&[u8], Vec<u8>)]) -> Vec<u8> {
+        let mut data = MAGIC.to_be_bytes().to_vec();
+        data.push(VERSION);
+        let mut index = Vec::new();
+        for (key, block) in blocks {
+            index.extend((key.len() as u16).to_be_bytes());
+            index.extend(*key);
+            index.push(block[CHECKSUM_SIZE]);
+            index.extend(1u16.to_be_bytes());
+            index.extend([0; 16]);
+            index.extend((data.len() as u64).to_be_bytes());
+            index.extend((block.len() as u32).to_be_bytes());
+            data.extend(block);
+        }
+        let index_start = data.len() as u64;
+        data.extend(index);
+        data.extend(index_start.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_tsm_to_line_protocol() {
+        let start: u64 = 1451606400000000000;
+        // RLE of 3 timestamps with delta 10s, i.e. 1 with the divisor of 10^10.
+        let mut rle = vec![(ENCODING_RLE << 4) | 10];
+        rle.extend(start.to_be_bytes());
+        encode_uvarint(&mut rle, 1);
+        encode_uvarint(&mut rle, 3);
+        // Simple8b of deltas 10s and 5s with the divisor of 10^9.
+        let mut simple8b = vec![(ENCODING_SIMPLE8B << 4) | 9];
+        simple8b.extend(start.to_be_bytes());
+        simple8b.extend(((15u64 << 60) | 10).to_be_bytes());
+        simple8b.extend(((15u64 << 60) | 5).to_be_bytes());
+        let mut uncompressed = vec![ENCODING_UNCOMPRESSED << 4];
+        uncompressed.extend(start.to_be_bytes());
+
+        // Integers 1, -2 and 3.
+        let mut integers = vec![ENCODING_UNCOMPRESSED << 4];
+        for delta in [2u64, 5, 10] {
+            integers.extend(delta.to_be_bytes());
+        }
+        let mut unsigned = vec![ENCODING_SIMPLE8B << 4];
+        unsigned.extend(14u64.to_be_bytes());
+        let mut strings = Vec::new();
+        for s in ["a b", "\"c\""] {
+            encode_uvarint(&mut strings, s.len() as u64);
+            strings.extend(s.as_bytes());
+        }
+        let mut string_values = vec![1 << 4];
+        string_values.extend(snap::raw::Encoder::new().compress_vec(&strings).unwrap());
+
+        let data = tsm_file(&[
+            (
+                b"cpu,host=a\\ b#!~#count",
+                block(BLOCK_INTEGER, &rle, &integers),
+            ),
+            (
+                b"cpu,host=a\\ b#!~#up",
+                block(BLOCK_BOOLEAN, &rle, &[16, 3, 0xA0]),
+            ),
+            (
+                b"cpu,host=a\\ b#!~#usage",
+                block(BLOCK_FLOAT, &simple8b, &encode_floats(&[1.5, 1.5, -0.25])),
+            ),
+            (
+                b"\x01\x02,\x00=mem,host=b,\xff=used#!~#used",
+                block(BLOCK_UNSIGNED, &uncompressed, &unsigned),
+            ),
+            (
+                b"note#!~#msg",
+                block(BLOCK_STRING, &simple8b[..17], &string_values),
+            ),
+        ]);
+        let output = tsm_to_line_protocol(Path::new("test.tsm"), &data, Vec::new()).unwrap();
+        assert_eq!(
+            r#"cpu,host=a\ b count=1i,up=true,usage=1.5 1451606400000000000
+cpu,host=a\ b count=-2i,up=false,usage=1.5 1451606410000000000
+cpu,host=a\ b usage=-0.25 1451606415000000000
+cpu,host=a\ b count=3i,up=true 1451606420000000000
+mem,host=b used=7u 1451606400000000000
+note msg="a b" 1451606400000000000
+note msg="\"c\"" 1451606410000000000
+"#,
+            String::from_utf8(output).unwrap()
+        );
+
+        let err = tsm_to_line_protocol(Path::new("test.tsm"), &data[..100], Vec::new());
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_rle_count_too_large() {
+        let mut rle = vec![ENCODING_RLE << 4];
+        rle.extend(0u64.to_be_bytes());
+        encode_uvarint(&mut rle, 1);
+        encode_uvarint(&mut rle, u64::MAX);
+        let e = decode_timestamps(&rle).unwrap_err();
+        assert_eq!(format!("RLE count {} exceeds the block", u64::MAX), e);
+        let e = decode_integers(&rle).unwrap_err();
+        assert_eq!(format!("RLE count {} exceeds the block", u64::MAX), e);
+
+        let data = tsm_file(&[(b"cpu#!~#count", block(BLOCK_INTEGER, &rle, &rle))]);
+        let e = tsm_to_line_protocol(Path::new("test.tsm"), &data, Vec::new()).unwrap_err();
+        assert!(e.to_string().contains("RLE count"), "{e}");
+    }
+}
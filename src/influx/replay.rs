@@ -0,0 +1,131 @@
+//! Replay line protocol to InfluxDB with the original pacing.
+
+use crate::error::Result;
+use crate::influx::write::WriteTarget;
+use crate::influx::{push_line, read_lines};
+use crate::io::{open_input, Compression};
+use crate::rate_limit::RateLimit;
+use chrono::Utc;
+use clap::Parser;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Replay line protocol file to InfluxDB by the HTTP API, for realistic load reproduction.
+///
+/// A line is due when the time since the replay starts reaches the gap between its
+/// timestamp and the first timestamp, divided by the speed. Lines are expected to be sorted
+/// by timestamp, lines that are already due are sent in batches without waiting.
+#[derive(Debug, Parser)]
+pub(crate) struct Replay {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    #[command(flatten)]
+    target: WriteTarget,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Speed of the replay, e.g. 2.0 replays an hour of data in 30 minutes.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_speed)]
+    speed: f64,
+    /// Wait until lines are due, otherwise lines are sent as fast as possible.
+    #[arg(long)]
+    respect_timestamps: bool,
+    /// Rewrite timestamps to the time lines are due, counted from now.
+    #[arg(long)]
+    to_now: bool,
+    /// Maximum number of lines in each request.
+    #[arg(long, default_value_t = 5000)]
+    batch_size: usize,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Replay {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let start = Instant::now();
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut schedule = Schedule::new(self.speed);
+        let mut batch = String::new();
+        let mut num_lines = 0;
+
+        read_lines(input, |mut line| {
+            if let Some(timestamp) = line.timestamp {
+                let offset = schedule.offset(timestamp);
+                if self.respect_timestamps {
+                    if let Some(wait) = (start + offset).checked_duration_since(Instant::now()) {
+                        // Send lines that are due before waiting for this one.
+                        if num_lines > 0 {
+                            self.rate_limit.acquire(num_lines, batch.len());
+                            self.target.send(&batch)?;
+                            batch.clear();
+                            num_lines = 0;
+                        }
+                        thread::sleep(wait);
+                    }
+                }
+                if self.to_now {
+                    line.timestamp = Some(now.saturating_add(offset.as_nanos() as i64));
+                }
+            }
+            push_line(&mut batch, &line);
+            num_lines += 1;
+            if num_lines >= self.batch_size {
+                self.rate_limit.acquire(num_lines, batch.len());
+                self.target.send(&batch)?;
+                batch.clear();
+                num_lines = 0;
+            }
+            Ok(())
+        })?;
+        if num_lines > 0 {
+            self.rate_limit.acquire(num_lines, batch.len());
+            self.target.send(&batch)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Offsets of timestamps from the first timestamp, scaled by the speed.
+struct Schedule {
+    speed: f64,
+    first: Option<i64>,
+}
+
+impl Schedule {
+    fn new(speed: f64) -> Schedule {
+        Schedule { speed, first: None }
+    }
+
+    /// Returns the offset of the timestamp, timestamps before the first one are due at once.
+    fn offset(&mut self, timestamp: i64) -> Duration {
+        let first = *self.first.get_or_insert(timestamp);
+        let gap = timestamp.saturating_sub(first).max(0) as f64 / self.speed;
+        Duration::from_nanos(gap as u64)
+    }
+}
+
+/// Parse a positive speed.
+fn parse_speed(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(format!("invalid speed {value}, expect a positive number")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let mut schedule = Schedule::new(parse_speed("2.0").unwrap());
+        assert_eq!(Duration::ZERO, schedule.offset(1451606400000000000));
+        assert_eq!(Duration::from_secs(5), schedule.offset(1451606410000000000));
+        assert_eq!(Duration::ZERO, schedule.offset(1451606390000000000));
+        assert!(parse_speed("0").is_err());
+        assert!(parse_speed("-1").is_err());
+    }
+}
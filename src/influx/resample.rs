@@ -0,0 +1,260 @@
+//! Resample line protocol to a fixed interval.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::downsample::{as_f64, to_static};
+use crate::influx::timestamp::parse_duration;
+use crate::influx::{push_fields, read_lines, series_key};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+
+/// How to get values of fields at times of the grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Fill {
+    /// The last value at or before the time.
+    Previous,
+    /// Interpolate numeric values between the values around the time, other values
+    /// are the previous values.
+    Linear,
+    /// The last value in the interval ending at the time, fields without values are
+    /// left out.
+    Null,
+    /// Like `null`, but drop points of the grid that miss any field of the series.
+    Drop,
+}
+
+/// Resample each series of line protocol file to points at times of a fixed grid.
+///
+/// Times of the grid are multiples of the interval, from the first to the last point of
+/// each series. Points are grouped in memory and lines without timestamp are dropped.
+#[derive(Debug, Parser)]
+pub(crate) struct Resample {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Interval of the grid, e.g. 15s.
+    #[arg(long, value_parser = parse_duration)]
+    every: i64,
+    /// How to get values of fields at times of the grid.
+    #[arg(long, value_enum, default_value_t = Fill::Previous)]
+    fill: Fill,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Resample {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let mut resampler = Resampler::new(self.every, self.fill);
+        read_lines(input, |line| {
+            resampler.update(&line);
+            Ok(())
+        })?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = resampler.write_lines(output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Values of series to resample.
+#[derive(Debug)]
+struct Resampler {
+    every: i64,
+    fill: Fill,
+    series: Vec<SeriesValues>,
+    /// Index of series by the series key.
+    series_index: HashMap<String, usize>,
+}
+
+/// Values of fields of a series.
+#[derive(Debug)]
+struct SeriesValues {
+    /// Measurement and sorted tags in line protocol.
+    key: String,
+    /// Timestamps and values of fields, in the order fields first appear.
+    fields: Vec<(String, Vec<(i64, FieldValue<'static>)>)>,
+}
+
+impl Resampler {
+    fn new(every: i64, fill: Fill) -> Resampler {
+        Resampler {
+            every,
+            fill,
+            series: Vec::new(),
+            series_index: HashMap::new(),
+        }
+    }
+
+    fn update(&mut self, line: &ParsedLine<'_>) {
+        let Some(ts) = line.timestamp else {
+            return;
+        };
+        let key = series_key(line);
+        let idx = match self.series_index.get(&key) {
+            Some(idx) => *idx,
+            None => {
+                self.series_index.insert(key.clone(), self.series.len());
+                self.series.push(SeriesValues {
+                    key,
+                    fields: Vec::new(),
+                });
+                self.series.len() - 1
+            }
+        };
+
+        let fields = &mut self.series[idx].fields;
+        for (name, value) in &line.field_set {
+            let values = match fields.iter_mut().position(|(n, _)| n == name.as_str()) {
+                Some(i) => &mut fields[i].1,
+                None => {
+                    fields.push((name.to_string(), Vec::new()));
+                    &mut fields.last_mut().unwrap().1
+                }
+            };
+            values.push((ts, to_static(value)));
+        }
+    }
+
+    /// Write resampled points, series are in the order they first appear.
+    fn write_lines<W: Write>(&mut self, dest: W) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        let mut line = String::new();
+        for series in &mut self.series {
+            let mut first = i64::MAX;
+            let mut last = i64::MIN;
+            for (_, values) in &mut series.fields {
+                // Later values of the same timestamp win.
+                values.sort_by_key(|(ts, _)| *ts);
+                first = first.min(values[0].0);
+                last = last.max(values[values.len() - 1].0);
+            }
+            let mut cursors = vec![0; series.fields.len()];
+
+            let mut time = first.div_euclid(self.every) * self.every;
+            if time < first {
+                time += self.every;
+            }
+            while time <= last {
+                let mut fields = Vec::with_capacity(series.fields.len());
+                for ((name, values), cursor) in series.fields.iter().zip(&mut cursors) {
+                    // Move the cursor to the last value at or before the time.
+                    while *cursor + 1 < values.len() && values[*cursor + 1].0 <= time {
+                        *cursor += 1;
+                    }
+                    if let Some(value) = value_at(self.fill, self.every, time, values, *cursor) {
+                        fields.push((name.as_str(), value));
+                    }
+                }
+
+                let complete = fields.len() == series.fields.len();
+                if !fields.is_empty() && (complete || self.fill != Fill::Drop) {
+                    line.clear();
+                    line.push_str(&series.key);
+                    push_fields(&mut line, fields.iter().map(|(name, v)| (*name, v)));
+                    writeln!(line, " {time}").unwrap();
+                    writer
+                        .write_all(line.as_bytes())
+                        .context(WriteOutputSnafu)?;
+                }
+                time += self.every;
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+/// Returns the value at the time, `cursor` is the index of the last value at or before
+/// the time if there is one.
+fn value_at(
+    fill: Fill,
+    every: i64,
+    time: i64,
+    values: &[(i64, FieldValue<'static>)],
+    cursor: usize,
+) -> Option<FieldValue<'static>> {
+    let (prev_ts, prev) = &values[cursor];
+    if *prev_ts > time {
+        return None;
+    }
+    match fill {
+        Fill::Previous => Some(prev.clone()),
+        Fill::Null | Fill::Drop => (*prev_ts > time - every).then(|| prev.clone()),
+        Fill::Linear => {
+            let Some((next_ts, next)) = values.get(cursor + 1).filter(|_| *prev_ts < time) else {
+                return Some(prev.clone());
+            };
+            let (Some(a), Some(b)) = (as_f64(prev), as_f64(next)) else {
+                return Some(prev.clone());
+            };
+            let ratio = (time - prev_ts) as f64 / (next_ts - prev_ts) as f64;
+            let v = a + (b - a) * ratio;
+            Some(match prev {
+                FieldValue::I64(_) => FieldValue::I64(v.round() as i64),
+                FieldValue::U64(_) => FieldValue::U64(v.round() as u64),
+                _ => FieldValue::F64(v),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample() {
+        let input = "cpu,host=a usage=1,count=10i 9
+cpu,host=a usage=3,state=\"ok\" 31
+cpu,host=a usage=5,count=40i 52
+mem used=1
+";
+        let resample = |fill| {
+            let mut resampler = Resampler::new(10, fill);
+            read_lines(input.as_bytes(), |line| {
+                resampler.update(&line);
+                Ok(())
+            })
+            .unwrap();
+            let output = resampler.write_lines(Vec::new()).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            "cpu,host=a usage=1,count=10i 10
+cpu,host=a usage=1,count=10i 20
+cpu,host=a usage=1,count=10i 30
+cpu,host=a usage=3,count=10i,state=\"ok\" 40
+cpu,host=a usage=3,count=10i,state=\"ok\" 50
+",
+            resample(Fill::Previous)
+        );
+        assert_eq!(
+            "cpu,host=a usage=1.0909090909090908,count=11i 10
+cpu,host=a usage=2,count=18i 20
+cpu,host=a usage=2.909090909090909,count=25i 30
+cpu,host=a usage=3.857142857142857,count=32i,state=\"ok\" 40
+cpu,host=a usage=4.809523809523809,count=39i,state=\"ok\" 50
+",
+            resample(Fill::Linear)
+        );
+        assert_eq!(
+            "cpu,host=a usage=1,count=10i 10
+cpu,host=a usage=3,state=\"ok\" 40
+",
+            resample(Fill::Null)
+        );
+        assert_eq!("", resample(Fill::Drop));
+    }
+}
@@ -0,0 +1,116 @@
+//! Downsample line protocol to multiple resolutions.
+
+use crate::error::{CreateDirSnafu, Result, WriteOutputSnafu};
+use crate::influx::downsample::{Aggregate, Downsampler};
+use crate::influx::read_lines;
+use crate::influx::timestamp::parse_duration;
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::Parser;
+use snafu::ResultExt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Downsample line protocol file to a file of each level in one scan of the input.
+///
+/// Files are named by the levels, e.g. `5m.lp`. Fields are suffixed by the aggregates
+/// if there are more than one, e.g. `usage_max`. Points of all levels are grouped in
+/// memory.
+#[derive(Debug, Parser)]
+pub(crate) struct Rollup {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Widths of time buckets of each level, e.g. 1m,5m,1h.
+    #[arg(long, required = true, value_delimiter = ',', value_parser = parse_level)]
+    levels: Vec<(String, i64)>,
+    /// Functions to aggregate values in a bucket.
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "mean")]
+    aggregates: Vec<Aggregate>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+    /// Compression of the output files, which adds the extension to file names.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    output_compression: Compression,
+}
+
+impl Rollup {
+    pub(crate) fn run(self) -> Result<()> {
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut downsamplers: Vec<_> = self
+            .levels
+            .iter()
+            .map(|(_, every)| Downsampler::new(*every, self.aggregates.clone()))
+            .collect();
+        read_lines(input, |line| {
+            for downsampler in &mut downsamplers {
+                downsampler.update(&line);
+            }
+            Ok(())
+        })?;
+
+        let extension = self.output_compression.extension();
+        for ((name, _), downsampler) in self.levels.iter().zip(&downsamplers) {
+            let path = self.output_dir.join(format!("{name}.lp{extension}"));
+            let output = create_output(path, self.output_compression)?;
+            let mut output = downsampler.write_lines(output)?;
+            output.finish().context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the level as its name and the width of buckets.
+fn parse_level(s: &str) -> Result<(String, i64), String> {
+    Ok((s.to_string(), parse_duration(s)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rollup() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.lp");
+        let points: String = (0..4)
+            .map(|i| {
+                format!(
+                    "cpu,host=a usage={i},state=\"s{i}\" {}\n",
+                    i * 30_000_000_000i64
+                )
+            })
+            .collect();
+        fs::write(&input, points).unwrap();
+
+        let output_dir = dir.path().join("out");
+        let args = format!(
+            "rollup -i {} -o {} --levels 1m,2m --aggregates mean,last",
+            input.display(),
+            output_dir.display()
+        );
+        Rollup::try_parse_from(args.split(' '))
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(
+            "cpu,host=a usage_mean=0.5,usage_last=1,state_last=\"s1\" 0
+cpu,host=a usage_mean=2.5,usage_last=3,state_last=\"s3\" 60000000000
+",
+            fs::read_to_string(output_dir.join("1m.lp")).unwrap()
+        );
+        assert_eq!(
+            "cpu,host=a usage_mean=1.5,usage_last=3,state_last=\"s3\" 0
+",
+            fs::read_to_string(output_dir.join("2m.lp")).unwrap()
+        );
+        assert!(Rollup::try_parse_from(["rollup", "-i", "-", "-o", "out"]).is_err());
+    }
+}
@@ -0,0 +1,139 @@
+//! Sample lines of line protocol.
+
+use crate::error::{ReadInputSnafu, Result, WriteOutputSnafu};
+use crate::generate::Rng;
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use snafu::ResultExt;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Write a smaller file of lines sampled from line protocol file.
+///
+/// Lines are copied as they are without parsing, empty lines and comments are ignored.
+/// Reading stops once the head is read.
+#[derive(Debug, Parser)]
+#[command(group = clap::ArgGroup::new("mode").required(true))]
+pub(crate) struct Sample {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Keep the first lines.
+    #[arg(long, group = "mode")]
+    head: Option<usize>,
+    /// Keep the last lines.
+    #[arg(long, group = "mode")]
+    tail: Option<usize>,
+    /// Keep each line with the probability, e.g. 0.01.
+    #[arg(long, group = "mode", value_parser = parse_fraction)]
+    fraction: Option<f64>,
+    /// Keep every N-th line, starting from the first line.
+    #[arg(long, group = "mode", value_parser = clap::value_parser!(u64).range(1..))]
+    every: Option<u64>,
+    /// Seed of the random generator of `--fraction`.
+    #[arg(long, default_value_t = 42)]
+    seed: u64,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Sample {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = self.sample_lines(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Write the sampled lines, lines without a trailing newline get one.
+    fn sample_lines<R: Read, W: Write>(&self, source: R, dest: W) -> Result<W> {
+        let mut reader = BufReader::new(source);
+        let mut writer = BufWriter::new(dest);
+        let mut rng = Rng(self.seed);
+        let mut tail = VecDeque::new();
+        let mut buffer = String::new();
+        let mut lines = 0;
+        while self.head.is_none_or(|head| lines < head as u64) {
+            buffer.clear();
+            if reader.read_line(&mut buffer).context(ReadInputSnafu)? == 0 {
+                break;
+            }
+            let line = buffer.trim_end_matches(['\r', '\n']);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            lines += 1;
+
+            let keep = if let Some(fraction) = self.fraction {
+                rng.next_f64() < fraction
+            } else if let Some(every) = self.every {
+                (lines - 1) % every == 0
+            } else if let Some(size) = self.tail {
+                if size > 0 {
+                    if tail.len() == size {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line.to_string());
+                }
+                false
+            } else {
+                true
+            };
+            if keep {
+                writeln!(writer, "{line}").context(WriteOutputSnafu)?;
+            }
+        }
+        for line in tail {
+            writeln!(writer, "{line}").context(WriteOutputSnafu)?;
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+fn parse_fraction(s: &str) -> Result<f64, String> {
+    match s.parse() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(fraction),
+        _ => Err(format!("invalid fraction {s:?}, expect a number in [0, 1]")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_lines() {
+        let input: String = (0..10)
+            .map(|i| format!("cpu usage={i} {i}\n"))
+            .chain(["# comment\n\n".to_string()])
+            .collect();
+        let sample = |args: &str| {
+            let sample = Sample::try_parse_from(format!("sample -i - {args}").split(' ')).unwrap();
+            let output = sample.sample_lines(input.as_bytes(), Vec::new()).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            output
+                .lines()
+                .map(|line| line.split(' ').nth(2).unwrap().parse().unwrap())
+                .collect::<Vec<u32>>()
+        };
+
+        assert_eq!(vec![0, 1, 2], sample("--head 3"));
+        assert_eq!(vec![7, 8, 9], sample("--tail 3"));
+        assert_eq!(vec![0, 4, 8], sample("--every 4"));
+        let sampled = sample("--fraction 0.5");
+        assert!(!sampled.is_empty() && sampled.len() < 10, "{sampled:?}");
+        assert_eq!(sampled, sample("--fraction 0.5"));
+        assert_eq!((0..10).collect::<Vec<_>>(), sample("--fraction 1"));
+
+        assert!(Sample::try_parse_from(["sample", "-i", "-"]).is_err());
+        assert!(Sample::try_parse_from("sample -i - --head 1 --every 2".split(' ')).is_err());
+        assert!(parse_fraction("1.5").is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! Scale line protocol by multiplying series.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use clap::Parser;
+use influxdb_line_protocol::EscapedStr;
+use snafu::ResultExt;
+use std::io::{BufWriter, Read, Write};
+
+/// Multiply series of line protocol file by writing copies of each point with a different
+/// value of a tag.
+///
+/// The value of the tag in the i-th copy is `{value}_copy{i}`, or `copy{i}` if the line
+/// doesn't have the tag.
+#[derive(Debug, Parser)]
+pub(crate) struct Scale {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Tag to multiply and the number of copies, e.g. host=10.
+    #[arg(long, value_parser = parse_multiply_tag)]
+    multiply_tag: (String, usize),
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Scale {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let (tag, copies) = &self.multiply_tag;
+        let mut output = scale_lines(input, tag, *copies, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Write copies of each line with the tag value rewritten.
+fn scale_lines<R: Read, W: Write>(source: R, tag: &str, copies: usize, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut buffer = String::new();
+    read_lines(source, |mut line| {
+        let tags = line.series.tag_set.get_or_insert_with(Default::default);
+        let idx = match tags.iter().position(|(key, _)| key.as_str() == tag) {
+            Some(idx) => idx,
+            None => {
+                tags.push((
+                    EscapedStr::CopiedValue(tag.to_string()),
+                    EscapedStr::CopiedValue(String::new()),
+                ));
+                tags.len() - 1
+            }
+        };
+        let value = tags[idx].1.to_string();
+
+        buffer.clear();
+        for i in 0..copies {
+            let copy = if value.is_empty() {
+                format!("copy{i}")
+            } else {
+                format!("{value}_copy{i}")
+            };
+            line.series.tag_set.as_mut().unwrap()[idx].1 = EscapedStr::CopiedValue(copy);
+            push_line(&mut buffer, &line);
+        }
+        writer
+            .write_all(buffer.as_bytes())
+            .context(WriteOutputSnafu)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+fn parse_multiply_tag(s: &str) -> Result<(String, usize), String> {
+    let (tag, copies) = s
+        .split_once('=')
+        .filter(|(tag, _)| !tag.is_empty())
+        .ok_or_else(|| format!("{s} is not in the form of tag=copies"))?;
+    match copies.parse() {
+        Ok(copies) if copies > 0 => Ok((tag.to_string(), copies)),
+        _ => Err(format!("invalid number of copies {copies:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_scale_lines() {
+        let input = "cpu,host=host_0,region=eu usage=1 1451606400000000000
+mem used=2 1451606400000000000
+";
+        let (tag, copies) = parse_multiply_tag("host=2").unwrap();
+        let output = scale_lines(Cursor::new(input), &tag, copies, Vec::new()).unwrap();
+        assert_eq!(
+            "cpu,host=host_0_copy0,region=eu usage=1 1451606400000000000
+cpu,host=host_0_copy1,region=eu usage=1 1451606400000000000
+mem,host=copy0 used=2 1451606400000000000
+mem,host=copy1 used=2 1451606400000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        assert!(parse_multiply_tag("host").is_err());
+        assert!(parse_multiply_tag("host=0").is_err());
+    }
+}
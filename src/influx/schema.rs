@@ -0,0 +1,552 @@
+//! Schema of line protocol data.
+
+use crate::error::{FieldTypeConflictSnafu, Result, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use serde::{Deserialize, Serialize, Serializer};
+use snafu::{OptionExt, ResultExt};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write as FmtWrite};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+/// Print `CREATE TABLE` statements of measurements in line protocol file.
+#[derive(Debug, Parser)]
+pub(crate) struct SchemaDdl {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output SQL file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// SQL dialect of the statements.
+    #[arg(long, value_enum)]
+    dialect: Dialect,
+    /// Timestamp column name, defaults to the conventional name of the dialect.
+    #[arg(long)]
+    timestamp: Option<String>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl SchemaDdl {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let schemas = Schema::infer_measurements(input)?;
+        let timestamp = self
+            .timestamp
+            .as_deref()
+            .unwrap_or(self.dialect.timestamp_column());
+
+        let mut output = create_output(&self.output, Compression::None)?;
+        for (measurement, schema) in &schemas {
+            let sql = schema.create_table(measurement, timestamp, self.dialect);
+            writeln!(output, "{sql}").context(WriteOutputSnafu)?;
+        }
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// SQL dialect of the DDL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Dialect {
+    /// GreptimeDB, tags are the primary key.
+    Greptime,
+    /// TimescaleDB, tables are hypertables.
+    Timescale,
+    /// ClickHouse, tables are MergeTree ordered by tags and timestamp.
+    Clickhouse,
+}
+
+impl Dialect {
+    /// Returns the default name of the timestamp column.
+    fn timestamp_column(self) -> &'static str {
+        match self {
+            Dialect::Greptime => "greptime_timestamp",
+            Dialect::Timescale => "time",
+            Dialect::Clickhouse => "timestamp",
+        }
+    }
+
+    /// Returns the quoted identifier.
+    pub(crate) fn quote(self, ident: &str) -> String {
+        match self {
+            Dialect::Greptime | Dialect::Timescale => format!("\"{}\"", ident.replace('"', "\"\"")),
+            // Backslashes are escaped first, so they don't escape the backtick.
+            Dialect::Clickhouse => format!("`{}`", ident.replace('\\', "\\\\").replace('`', "\\`")),
+        }
+    }
+
+    /// Returns the SQL type of the field type.
+    fn field_type(self, field_type: FieldType) -> &'static str {
+        match (self, field_type) {
+            (Dialect::Greptime, FieldType::Int64) => "BIGINT",
+            (Dialect::Greptime, FieldType::UInt64) => "BIGINT UNSIGNED",
+            (Dialect::Greptime, FieldType::Float64) => "DOUBLE",
+            (Dialect::Greptime, FieldType::String) => "STRING",
+            (Dialect::Greptime, FieldType::Boolean) => "BOOLEAN",
+            (Dialect::Timescale, FieldType::Int64) => "BIGINT",
+            // PostgreSQL has no unsigned types.
+            (Dialect::Timescale, FieldType::UInt64) => "NUMERIC(20)",
+            (Dialect::Timescale, FieldType::Float64) => "DOUBLE PRECISION",
+            (Dialect::Timescale, FieldType::String) => "TEXT",
+            (Dialect::Timescale, FieldType::Boolean) => "BOOLEAN",
+            (Dialect::Clickhouse, FieldType::Int64) => "Nullable(Int64)",
+            (Dialect::Clickhouse, FieldType::UInt64) => "Nullable(UInt64)",
+            (Dialect::Clickhouse, FieldType::Float64) => "Nullable(Float64)",
+            (Dialect::Clickhouse, FieldType::String) => "Nullable(String)",
+            (Dialect::Clickhouse, FieldType::Boolean) => "Nullable(Bool)",
+        }
+    }
+}
+
+/// Type of a field value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum FieldType {
+    /// Integer, the `i` suffix in line protocol.
+    Int64,
+    /// Unsigned integer, the `u` suffix in line protocol.
+    UInt64,
+    Float64,
+    String,
+    Boolean,
+}
+
+impl FieldType {
+    /// Returns the type of the field value.
+    pub(crate) fn of(value: &FieldValue) -> FieldType {
+        match value {
+            FieldValue::I64(_) => FieldType::Int64,
+            FieldValue::U64(_) => FieldType::UInt64,
+            FieldValue::F64(_) => FieldType::Float64,
+            FieldValue::String(_) => FieldType::String,
+            FieldValue::Boolean(_) => FieldType::Boolean,
+        }
+    }
+
+    /// Returns true if a column of the type holds values of the other type, e.g. integers
+    /// are widened in a float column.
+    pub(crate) fn holds(self, other: FieldType) -> bool {
+        self.merge(other) == Some(self)
+    }
+
+    /// Returns the type that is able to hold values of both types.
+    ///
+    /// Numeric types are widened to float, other conflicts have no common type.
+    pub(crate) fn merge(self, other: FieldType) -> Option<FieldType> {
+        use FieldType::*;
+
+        match (self, other) {
+            (a, b) if a == b => Some(a),
+            (Int64 | UInt64 | Float64, Int64 | UInt64 | Float64) => Some(Float64),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for FieldType {
+    type Err = String;
+
+    /// Parse a type name like `i64`, `u64`, `f64`, `bool` or `string`.
+    fn from_str(s: &str) -> Result<FieldType, String> {
+        match s {
+            "i64" | "int" | "integer" => Ok(FieldType::Int64),
+            "u64" | "uint" | "unsigned" => Ok(FieldType::UInt64),
+            "f64" | "float" => Ok(FieldType::Float64),
+            "bool" | "boolean" => Ok(FieldType::Boolean),
+            "string" => Ok(FieldType::String),
+            _ => Err(format!(
+                "unknown type {s}, expect i64, u64, f64, bool or string"
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for FieldType {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<FieldType, String> {
+        s.parse()
+    }
+}
+
+/// Serializes the name of the type, which is parsed back by `FromStr`.
+impl Serialize for FieldType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldType::Int64 => "integer",
+            FieldType::UInt64 => "unsigned",
+            FieldType::Float64 => "float",
+            FieldType::String => "string",
+            FieldType::Boolean => "boolean",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Tag and field columns of line protocol data, in the order they first appear.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Schema {
+    pub(crate) tags: Vec<String>,
+    pub(crate) fields: Vec<(String, FieldType)>,
+    tag_index: HashMap<String, usize>,
+    field_index: HashMap<String, usize>,
+    /// Keys of tags and fields that are not selected.
+    dropped: HashSet<String>,
+}
+
+impl Schema {
+    /// Collect the schema of all lines in the source.
+    pub(crate) fn infer<R: Read>(source: R) -> Result<Schema> {
+        let mut schema = Schema::default();
+        read_lines(source, |line| schema.update(&line))?;
+
+        Ok(schema)
+    }
+
+    /// Returns the schema of the tag and field columns.
+    ///
+    /// Types of the fields are unknown, they are strings in the schema.
+    pub(crate) fn with_columns(tags: &[String], fields: &[String]) -> Schema {
+        let mut schema = Schema::default();
+        for tag in tags {
+            if !schema.tag_index.contains_key(tag) {
+                schema.tag_index.insert(tag.clone(), schema.tags.len());
+                schema.tags.push(tag.clone());
+            }
+        }
+        for field in fields {
+            if !schema.field_index.contains_key(field) {
+                schema
+                    .field_index
+                    .insert(field.clone(), schema.fields.len());
+                schema.fields.push((field.clone(), FieldType::String));
+            }
+        }
+        schema
+    }
+
+    /// Collect the tag and field columns of all lines in the source like
+    /// `with_columns`, types of fields are not checked, e.g. for CSV that has no types.
+    pub(crate) fn collect_columns<R: Read>(source: R) -> Result<Schema> {
+        let mut schema = Schema::default();
+        read_lines(source, |line| {
+            schema.add_columns(&line);
+            Ok(())
+        })?;
+
+        Ok(schema)
+    }
+
+    /// Collect the schema of each measurement in the source, in the order they first
+    /// appear.
+    pub(crate) fn infer_measurements<R: Read>(source: R) -> Result<Vec<(String, Schema)>> {
+        Schema::read_measurements(source, Schema::update)
+    }
+
+    /// Collect the columns of each measurement in the source like `collect_columns`, in
+    /// the order they first appear.
+    pub(crate) fn collect_measurement_columns<R: Read>(source: R) -> Result<Vec<(String, Schema)>> {
+        Schema::read_measurements(source, |schema, line| {
+            schema.add_columns(line);
+            Ok(())
+        })
+    }
+
+    /// Update the schema of the measurement of each line by `f`.
+    fn read_measurements<R: Read>(
+        source: R,
+        mut f: impl FnMut(&mut Schema, &ParsedLine) -> Result<()>,
+    ) -> Result<Vec<(String, Schema)>> {
+        let mut schemas: Vec<(String, Schema)> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        read_lines(source, |line| {
+            let measurement = line.series.measurement.as_str();
+            let idx = match index.get(measurement) {
+                Some(idx) => *idx,
+                None => {
+                    index.insert(measurement.to_string(), schemas.len());
+                    schemas.push((measurement.to_string(), Schema::default()));
+                    schemas.len() - 1
+                }
+            };
+            f(&mut schemas[idx].1, &line)
+        })?;
+
+        Ok(schemas)
+    }
+
+    /// Returns the `CREATE TABLE` statement of the table in the dialect.
+    ///
+    /// Tags are strings, fields are nullable columns of their types and the timestamp
+    /// is in nanosecond.
+    pub(crate) fn create_table(&self, table: &str, timestamp: &str, dialect: Dialect) -> String {
+        let quote = |ident: &str| dialect.quote(ident);
+        let mut columns = Vec::with_capacity(self.tags.len() + self.fields.len() + 1);
+        if dialect == Dialect::Timescale {
+            columns.push(format!("{} TIMESTAMPTZ NOT NULL", quote(timestamp)));
+        }
+        for tag in &self.tags {
+            let tag_type = match dialect {
+                Dialect::Greptime => "STRING NULL",
+                Dialect::Timescale => "TEXT",
+                Dialect::Clickhouse => "LowCardinality(String)",
+            };
+            columns.push(format!("{} {tag_type}", quote(tag)));
+        }
+        for (field, field_type) in &self.fields {
+            columns.push(format!(
+                "{} {}",
+                quote(field),
+                dialect.field_type(*field_type)
+            ));
+        }
+        match dialect {
+            Dialect::Greptime => {
+                columns.push(format!("{} TIMESTAMP(9) NOT NULL", quote(timestamp)));
+                columns.push(format!("TIME INDEX ({})", quote(timestamp)));
+                if !self.tags.is_empty() {
+                    let keys: Vec<_> = self.tags.iter().map(|tag| quote(tag)).collect();
+                    columns.push(format!("PRIMARY KEY ({})", keys.join(", ")));
+                }
+            }
+            Dialect::Timescale => (),
+            Dialect::Clickhouse => columns.push(format!("{} DateTime64(9)", quote(timestamp))),
+        }
+
+        let mut sql = format!("CREATE TABLE IF NOT EXISTS {} (\n", quote(table));
+        sql += &columns
+            .iter()
+            .map(|column| format!("  {column}"))
+            .collect::<Vec<_>>()
+            .join(",\n");
+        sql += "\n)";
+        match dialect {
+            Dialect::Greptime => sql += ";",
+            Dialect::Timescale => write!(
+                sql,
+                ";\nSELECT create_hypertable('{}', '{}', if_not_exists => TRUE);",
+                quote(table).replace('\'', "''"),
+                timestamp.replace('\'', "''")
+            )
+            .unwrap(),
+            Dialect::Clickhouse => {
+                let keys: Vec<_> = self
+                    .tags
+                    .iter()
+                    .map(|tag| quote(tag))
+                    .chain([quote(timestamp)])
+                    .collect();
+                write!(sql, "\nENGINE = MergeTree\nORDER BY ({});", keys.join(", ")).unwrap();
+            }
+        }
+        sql
+    }
+
+    /// Add columns of the line to the schema.
+    pub(crate) fn update(&mut self, line: &ParsedLine) -> Result<()> {
+        self.add_tags(line);
+        for (key, value) in &line.field_set {
+            let field_type = FieldType::of(value);
+            match self.field_index.get(key.as_str()) {
+                Some(&idx) => {
+                    let (name, current) = &mut self.fields[idx];
+                    *current =
+                        current
+                            .merge(field_type)
+                            .with_context(|| FieldTypeConflictSnafu {
+                                field: name.clone(),
+                                current: current.to_string(),
+                                other: field_type.to_string(),
+                            })?;
+                }
+                None => {
+                    self.field_index.insert(key.to_string(), self.fields.len());
+                    self.fields.push((key.to_string(), field_type));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Add columns of the line to the schema without merging types of fields, new fields
+    /// are strings like `with_columns`.
+    pub(crate) fn add_columns(&mut self, line: &ParsedLine) {
+        self.add_tags(line);
+        for (key, _) in &line.field_set {
+            if !self.field_index.contains_key(key.as_str()) {
+                self.field_index.insert(key.to_string(), self.fields.len());
+                self.fields.push((key.to_string(), FieldType::String));
+            }
+        }
+    }
+
+    fn add_tags(&mut self, line: &ParsedLine) {
+        if let Some(tag_set) = &line.series.tag_set {
+            for (key, _) in tag_set {
+                if !self.tag_index.contains_key(key.as_str()) {
+                    self.tag_index.insert(key.to_string(), self.tags.len());
+                    self.tags.push(key.to_string());
+                }
+            }
+        }
+    }
+
+    /// Keep the tag and field columns of the keys and drop the others.
+    pub(crate) fn select(&mut self, keys: &HashSet<String>) {
+        let tags = std::mem::take(&mut self.tags);
+        let fields = std::mem::take(&mut self.fields);
+        self.tag_index.clear();
+        self.field_index.clear();
+        for tag in tags {
+            if keys.contains(&tag) {
+                self.tag_index.insert(tag.clone(), self.tags.len());
+                self.tags.push(tag);
+            } else {
+                self.dropped.insert(tag);
+            }
+        }
+        for (field, field_type) in fields {
+            if keys.contains(&field) {
+                self.field_index.insert(field.clone(), self.fields.len());
+                self.fields.push((field, field_type));
+            } else {
+                self.dropped.insert(field);
+            }
+        }
+    }
+
+    /// Rename the column of the key, which is still indexed by the key.
+    pub(crate) fn rename(&mut self, key: &str, name: &str) {
+        if let Some(idx) = self.tag_index(key) {
+            self.tags[idx] = name.to_string();
+        }
+        if let Some(idx) = self.field_index(key) {
+            self.fields[idx].0 = name.to_string();
+        }
+    }
+
+    /// Returns true if the tag or field is dropped by the selection.
+    pub(crate) fn is_dropped(&self, key: &str) -> bool {
+        self.dropped.contains(key)
+    }
+
+    /// Returns the index of the tag column.
+    pub(crate) fn tag_index(&self, key: &str) -> Option<usize> {
+        self.tag_index.get(key).copied()
+    }
+
+    /// Returns the index of the field column.
+    pub(crate) fn field_index(&self, key: &str) -> Option<usize> {
+        self.field_index.get(key).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_update() {
+        let input = "cpu,host=a usage=1i,idle=2i 1\ncpu,region=b,host=c usage=1.5,up=true 2\n";
+        let schema = Schema::infer(input.as_bytes()).unwrap();
+
+        assert_eq!(schema.tags, vec!["host", "region"]);
+        assert_eq!(
+            schema.fields,
+            vec![
+                ("usage".to_string(), FieldType::Float64),
+                ("idle".to_string(), FieldType::Int64),
+                ("up".to_string(), FieldType::Boolean),
+            ]
+        );
+        assert_eq!(Some(1), schema.tag_index("region"));
+        assert_eq!(None, schema.field_index("host"));
+
+        let input = "cpu usage=1i\ncpu usage=true\n";
+        let err = Schema::infer(input.as_bytes()).unwrap_err();
+        assert_eq!("Error at line 2", err.to_string());
+    }
+
+    #[test]
+    fn test_collect_columns() {
+        let input = "cpu,host=a value=1i 1\nlog,host=b value=\"x\",level=\"info\" 2\n";
+        let schema = Schema::collect_columns(input.as_bytes()).unwrap();
+        assert_eq!(schema.tags, vec!["host"]);
+        let names: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(vec!["value", "level"], names);
+
+        let schemas = Schema::collect_measurement_columns(input.as_bytes()).unwrap();
+        assert_eq!(2, schemas.len());
+        assert!(Schema::infer(input.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_create_table() {
+        let input =
+            "cpu,host=a usage=1i 1\nmem used=1.5,ok=true 1\ncpu,host=b,region=c usage=2i,id=1u 2\n";
+        let schemas = Schema::infer_measurements(input.as_bytes()).unwrap();
+        let names: Vec<_> = schemas.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["cpu", "mem"], names);
+
+        let (_, cpu) = &schemas[0];
+        assert_eq!(
+            r#"CREATE TABLE IF NOT EXISTS "cpu" (
+  "host" STRING NULL,
+  "region" STRING NULL,
+  "usage" BIGINT,
+  "id" BIGINT UNSIGNED,
+  "greptime_timestamp" TIMESTAMP(9) NOT NULL,
+  TIME INDEX ("greptime_timestamp"),
+  PRIMARY KEY ("host", "region")
+);"#,
+            cpu.create_table("cpu", "greptime_timestamp", Dialect::Greptime)
+        );
+        assert_eq!(
+            r#"CREATE TABLE IF NOT EXISTS "cpu" (
+  "time" TIMESTAMPTZ NOT NULL,
+  "host" TEXT,
+  "region" TEXT,
+  "usage" BIGINT,
+  "id" NUMERIC(20)
+);
+SELECT create_hypertable('"cpu"', 'time', if_not_exists => TRUE);"#,
+            cpu.create_table("cpu", "time", Dialect::Timescale)
+        );
+
+        let (_, mem) = &schemas[1];
+        assert_eq!(
+            "CREATE TABLE IF NOT EXISTS `mem` (
+  `used` Nullable(Float64),
+  `ok` Nullable(Bool),
+  `timestamp` DateTime64(9)
+)
+ENGINE = MergeTree
+ORDER BY (`timestamp`);",
+            mem.create_table("mem", "timestamp", Dialect::Clickhouse)
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(r#""a""b""#, Dialect::Greptime.quote(r#"a"b"#));
+        assert_eq!(r"`a\`b`", Dialect::Clickhouse.quote("a`b"));
+        assert_eq!(r"`a\\`", Dialect::Clickhouse.quote(r"a\"));
+        assert_eq!(r"`a\\\`b`", Dialect::Clickhouse.quote(r"a\`b"));
+    }
+}
@@ -0,0 +1,136 @@
+//! Shift timestamps of line protocol.
+
+use crate::error::{Result, TimestampOutOfRangeSnafu, WriteOutputSnafu};
+use crate::influx::timestamp::{parse_duration, parse_rfc3339};
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, CompressionArgs, Output, ReplayableInput};
+use crate::reject;
+use chrono::Utc;
+use clap::{ArgGroup, Parser};
+use snafu::{OptionExt, ResultExt};
+use std::io::{BufWriter, Read, Write};
+
+/// Shift timestamps of line protocol file by the same offset, so the relative spacing of
+/// points is preserved.
+///
+/// Lines without timestamp are written unchanged.
+#[derive(Debug, Parser)]
+#[command(group(ArgGroup::new("shift").required(true).args(["to_now", "offset", "start"])))]
+pub(crate) struct Shift {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Shift the max timestamp to now.
+    #[arg(long)]
+    to_now: bool,
+    /// Offset to add to timestamps, e.g. 90d or -1h.
+    #[arg(long, allow_hyphen_values = true, value_parser = parse_offset)]
+    offset: Option<i64>,
+    /// Shift the min timestamp to this time in RFC3339.
+    #[arg(long, value_parser = parse_rfc3339)]
+    start: Option<i64>,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Shift {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = ReplayableInput::open(&self.input, self.compression.input_compression)?;
+        let offset = match (self.offset, self.start) {
+            (Some(offset), _) => offset,
+            // The first pass finds the time range to compute the offset.
+            (None, Some(start)) => {
+                let range = reject::silently(|| time_range(input.reader()?))?;
+                range.map_or(0, |(min, _)| start.saturating_sub(min))
+            }
+            (None, None) => {
+                let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+                let range = reject::silently(|| time_range(input.reader()?))?;
+                range.map_or(0, |(_, max)| now.saturating_sub(max))
+            }
+        };
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = shift_lines(input.reader()?, offset, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Returns the min and max timestamps of lines.
+fn time_range<R: Read>(source: R) -> Result<Option<(i64, i64)>> {
+    let mut range: Option<(i64, i64)> = None;
+    read_lines(source, |line| {
+        if let Some(ts) = line.timestamp {
+            range = Some(range.map_or((ts, ts), |(min, max)| (min.min(ts), max.max(ts))));
+        }
+        Ok(())
+    })?;
+    Ok(range)
+}
+
+/// Write lines with timestamps shifted by the offset.
+fn shift_lines<R: Read, W: Write>(source: R, offset: i64, dest: W) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut buffer = String::new();
+    read_lines(source, |mut line| {
+        if let Some(timestamp) = line.timestamp {
+            let shifted = timestamp
+                .checked_add(offset)
+                .context(TimestampOutOfRangeSnafu { timestamp, offset })?;
+            line.timestamp = Some(shifted);
+        }
+        buffer.clear();
+        push_line(&mut buffer, &line);
+        writer
+            .write_all(buffer.as_bytes())
+            .context(WriteOutputSnafu)
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Parse a duration with an optional `-` sign.
+fn parse_offset(value: &str) -> Result<i64, String> {
+    match value.strip_prefix('-') {
+        Some(duration) => parse_duration(duration).map(|nanos| -nanos),
+        None => parse_duration(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_shift_lines() {
+        let input = "cpu usage=1 1451606400000000000
+cpu usage=2
+cpu usage=3 1451606410000000000
+";
+        assert_eq!(
+            Some((1451606400000000000, 1451606410000000000)),
+            time_range(Cursor::new(input)).unwrap()
+        );
+
+        let offset = parse_offset("-1h").unwrap();
+        assert_eq!(-3_600_000_000_000, offset);
+        let output = shift_lines(Cursor::new(input), offset, Vec::new()).unwrap();
+        assert_eq!(
+            "cpu usage=1 1451602800000000000
+cpu usage=2
+cpu usage=3 1451602810000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let err = shift_lines(Cursor::new(input), i64::MAX, Vec::new()).unwrap_err();
+        assert_eq!("Error at line 1", err.to_string());
+    }
+}
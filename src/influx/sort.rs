@@ -0,0 +1,262 @@
+//! Sort line protocol by external merge sort.
+
+use crate::error::{Result, SpillSnafu, WriteOutputSnafu};
+use crate::influx::{push_line, read_lines, series_key};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use chrono::Utc;
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// Sort line protocol file by timestamp.
+///
+/// Lines are sorted in memory until the buffer is full, then sorted runs are spilled to
+/// temporary files and merged. The sort is stable and lines without timestamp are sorted
+/// as they have the current time.
+#[derive(Debug, Parser)]
+pub(crate) struct Sort {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Sort by the series key, which is the measurement and sorted tags, then the timestamp.
+    #[arg(long)]
+    series_key: bool,
+    /// Size of lines to sort in memory in MiB.
+    #[arg(long, default_value_t = 256)]
+    buffer_size: usize,
+    /// Directory of temporary files, the system temporary directory by default.
+    #[arg(long)]
+    temp_dir: Option<PathBuf>,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl Sort {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let mut sorter = Sorter::new(self.series_key, self.buffer_size << 20, self.temp_dir);
+        read_lines(input, |line| sorter.push(&line))?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = sorter.finish(output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// A line with its sort key.
+///
+/// The sequence number keeps the order of lines with the same key.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Record {
+    series: String,
+    timestamp: i64,
+    seq: u64,
+    text: String,
+}
+
+impl Record {
+    /// Approximate memory size of the record.
+    fn size(&self) -> usize {
+        std::mem::size_of::<Record>() + self.series.len() + self.text.len()
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.timestamp.to_le_bytes())?;
+        writer.write_all(&self.seq.to_le_bytes())?;
+        for s in [&self.series, &self.text] {
+            writer.write_all(&(s.len() as u64).to_le_bytes())?;
+            writer.write_all(s.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read a record, returns `None` at the end of the reader.
+    fn read_from<R: Read>(reader: &mut R) -> io::Result<Option<Record>> {
+        let mut buf = [0; 8];
+        match reader.read_exact(&mut buf) {
+            Ok(()) => (),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let timestamp = i64::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let seq = u64::from_le_bytes(buf);
+        let mut read_string = || {
+            reader.read_exact(&mut buf)?;
+            let mut bytes = vec![0; u64::from_le_bytes(buf) as usize];
+            reader.read_exact(&mut bytes)?;
+            String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        };
+        let series = read_string()?;
+        let text = read_string()?;
+
+        Ok(Some(Record {
+            series,
+            timestamp,
+            seq,
+            text,
+        }))
+    }
+}
+
+/// External sorter of lines.
+#[derive(Debug)]
+struct Sorter {
+    by_series: bool,
+    buffer_size: usize,
+    temp_dir: Option<PathBuf>,
+    now: i64,
+    /// Sequence number of the next line.
+    seq: u64,
+    records: Vec<Record>,
+    /// Size of records in memory.
+    size: usize,
+    /// Spilled sorted runs.
+    runs: Vec<File>,
+}
+
+impl Sorter {
+    fn new(by_series: bool, buffer_size: usize, temp_dir: Option<PathBuf>) -> Sorter {
+        Sorter {
+            by_series,
+            buffer_size,
+            temp_dir,
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            seq: 0,
+            records: Vec::new(),
+            size: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        let mut text = String::new();
+        push_line(&mut text, line);
+        let record = Record {
+            series: if self.by_series {
+                series_key(line)
+            } else {
+                String::new()
+            },
+            timestamp: line.timestamp.unwrap_or(self.now),
+            seq: self.seq,
+            text,
+        };
+        self.seq += 1;
+        self.size += record.size();
+        self.records.push(record);
+
+        if self.size >= self.buffer_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sort records in memory and write them to a temporary file.
+    fn spill(&mut self) -> Result<()> {
+        self.records.sort_unstable();
+        let file = match &self.temp_dir {
+            Some(dir) => tempfile::tempfile_in(dir),
+            None => tempfile::tempfile(),
+        }
+        .context(SpillSnafu)?;
+        let mut writer = BufWriter::new(file);
+        for record in self.records.drain(..) {
+            record.write_to(&mut writer).context(SpillSnafu)?;
+        }
+        let mut file = writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(SpillSnafu)?;
+        file.seek(SeekFrom::Start(0)).context(SpillSnafu)?;
+
+        self.runs.push(file);
+        self.size = 0;
+        Ok(())
+    }
+
+    /// Write sorted lines to the dest.
+    fn finish<W: Write>(mut self, dest: W) -> Result<W> {
+        let mut writer = BufWriter::new(dest);
+        if self.runs.is_empty() {
+            self.records.sort_unstable();
+            for record in &self.records {
+                writer
+                    .write_all(record.text.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        } else {
+            if !self.records.is_empty() {
+                self.spill()?;
+            }
+            let mut readers: Vec<_> = self.runs.into_iter().map(BufReader::new).collect();
+            let mut heap = BinaryHeap::with_capacity(readers.len());
+            for (idx, reader) in readers.iter_mut().enumerate() {
+                if let Some(record) = Record::read_from(reader).context(SpillSnafu)? {
+                    heap.push(Reverse((record, idx)));
+                }
+            }
+            while let Some(Reverse((record, idx))) = heap.pop() {
+                writer
+                    .write_all(record.text.as_bytes())
+                    .context(WriteOutputSnafu)?;
+                if let Some(record) = Record::read_from(&mut readers[idx]).context(SpillSnafu)? {
+                    heap.push(Reverse((record, idx)));
+                }
+            }
+        }
+
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .context(WriteOutputSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn sort(by_series: bool, buffer_size: usize) -> String {
+        let input = "cpu,host=b usage=1 30
+cpu,host=a usage=2 10
+mem,host=a used=3 20
+cpu,host=a usage=4 30
+cpu,host=b usage=5 10
+";
+        let mut sorter = Sorter::new(by_series, buffer_size, None);
+        read_lines(Cursor::new(input), |line| sorter.push(&line)).unwrap();
+        String::from_utf8(sorter.finish(Vec::new()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sort() {
+        let by_time = "cpu,host=a usage=2 10
+cpu,host=b usage=5 10
+mem,host=a used=3 20
+cpu,host=b usage=1 30
+cpu,host=a usage=4 30
+";
+        assert_eq!(by_time, sort(false, 1 << 20));
+        // Spill each line to a run.
+        assert_eq!(by_time, sort(false, 1));
+
+        let by_series = "cpu,host=a usage=2 10
+cpu,host=a usage=4 30
+cpu,host=b usage=5 10
+cpu,host=b usage=1 30
+mem,host=a used=3 20
+";
+        assert_eq!(by_series, sort(true, 1 << 20));
+        assert_eq!(by_series, sort(true, 100));
+    }
+}
@@ -0,0 +1,246 @@
+//! Split line protocol into files.
+
+use crate::error::{CreateDirSnafu, Result, WriteOutputSnafu};
+use crate::influx::{push_line, read_lines};
+use crate::io::{create_output, open_input, Compression, Output};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+/// How to split lines into files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SplitBy {
+    /// A file of each day in UTC, named by the date, e.g. `2016-01-01.lp`.
+    Day,
+    /// A file of each measurement, named by the measurement, e.g. `cpu.lp`.
+    Measurement,
+    /// Files of at most this number of bytes, named by the part, e.g. `part-00000.lp`.
+    Size(u64),
+}
+
+/// Split line protocol file into files in a directory.
+#[derive(Debug, Parser)]
+pub(crate) struct Split {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// How to split lines: `day`, `measurement` or `size=<SIZE>`, e.g. `size=1GB`.
+    #[arg(long, value_parser = parse_split_by)]
+    by: SplitBy,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+    /// Compression of the output files, which adds the extension to file names.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    output_compression: Compression,
+}
+
+impl Split {
+    pub(crate) fn run(self) -> Result<()> {
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut splitter = Splitter::new(self.output_dir, self.by, self.output_compression);
+        read_lines(input, |line| splitter.write_line(&line))?;
+        splitter.finish()?;
+        Ok(())
+    }
+}
+
+/// Writer of lines into files.
+struct Splitter {
+    dir: PathBuf,
+    by: SplitBy,
+    compression: Compression,
+    /// Opened files by the name without extension.
+    outputs: HashMap<String, BufWriter<Box<dyn Output + Send>>>,
+    /// Part number and the written bytes of the current part.
+    part: usize,
+    part_size: u64,
+    now: i64,
+    buffer: String,
+}
+
+impl Splitter {
+    fn new(dir: PathBuf, by: SplitBy, compression: Compression) -> Splitter {
+        Splitter {
+            dir,
+            by,
+            compression,
+            outputs: HashMap::new(),
+            part: 0,
+            part_size: 0,
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default(),
+            buffer: String::new(),
+        }
+    }
+
+    fn write_line(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        self.buffer.clear();
+        push_line(&mut self.buffer, line);
+
+        let name = match self.by {
+            SplitBy::Day => Utc
+                .timestamp_nanos(line.timestamp.unwrap_or(self.now))
+                .format("%Y-%m-%d")
+                .to_string(),
+            SplitBy::Measurement => file_name(line.series.measurement.as_str()),
+            SplitBy::Size(size) => {
+                let len = self.buffer.len() as u64;
+                if self.part_size > 0 && self.part_size + len > size {
+                    // Finish the current part before starting the next one.
+                    let name = self.part_name();
+                    if let Some(mut output) = self.outputs.remove(&name) {
+                        output.finish().context(WriteOutputSnafu)?;
+                    }
+                    self.part += 1;
+                    self.part_size = 0;
+                }
+                self.part_size += len;
+                self.part_name()
+            }
+        };
+
+        let output = match self.outputs.get_mut(&name) {
+            Some(output) => output,
+            None => {
+                let path = self
+                    .dir
+                    .join(format!("{name}.lp{}", self.compression.extension()));
+                let output = BufWriter::new(create_output(path, self.compression)?);
+                self.outputs.entry(name).or_insert(output)
+            }
+        };
+        output
+            .write_all(self.buffer.as_bytes())
+            .context(WriteOutputSnafu)
+    }
+
+    fn part_name(&self) -> String {
+        format!("part-{:05}", self.part)
+    }
+
+    /// Finish all files.
+    fn finish(self) -> Result<()> {
+        for (_, mut output) in self.outputs {
+            output.finish().context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the file name of the measurement, chars that may be invalid are replaced by `_`.
+pub(crate) fn file_name(measurement: &str) -> String {
+    measurement
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn parse_split_by(s: &str) -> Result<SplitBy, String> {
+    match s {
+        "day" => return Ok(SplitBy::Day),
+        "measurement" => return Ok(SplitBy::Measurement),
+        _ => (),
+    }
+    let size = s
+        .strip_prefix("size=")
+        .ok_or_else(|| format!("{s} is not one of day, measurement and size=<SIZE>"))?;
+    let split = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let factor = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" | "K" => 1 << 10,
+        "MB" | "M" => 1 << 20,
+        "GB" | "G" => 1 << 30,
+        _ => return Err(format!("invalid unit of size {size:?}")),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .filter(|size| *size > 0)
+        .map(SplitBy::Size)
+        .ok_or_else(|| format!("invalid size {size:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn split(by: &str) -> Vec<(String, String)> {
+        let input = "cpu,host=a usage=1 1451606400000000000
+mem,host=a used=2 1451606400000000000
+cpu,host=a usage=3 1451692800000000000
+";
+        let dir = tempfile::tempdir().unwrap();
+        let mut splitter = Splitter::new(
+            dir.path().to_path_buf(),
+            parse_split_by(by).unwrap(),
+            Compression::None,
+        );
+        read_lines(Cursor::new(input), |line| splitter.write_line(&line)).unwrap();
+        splitter.finish().unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_str().unwrap().to_string();
+                (name, fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        files.sort_unstable();
+        files
+    }
+
+    #[test]
+    fn test_split() {
+        let cpu = "cpu,host=a usage=1 1451606400000000000\n";
+        let mem = "mem,host=a used=2 1451606400000000000\n";
+        let cpu_next_day = "cpu,host=a usage=3 1451692800000000000\n";
+        assert_eq!(
+            vec![
+                ("2016-01-01.lp".to_string(), format!("{cpu}{mem}")),
+                ("2016-01-02.lp".to_string(), cpu_next_day.to_string()),
+            ],
+            split("day")
+        );
+        assert_eq!(
+            vec![
+                ("cpu.lp".to_string(), format!("{cpu}{cpu_next_day}")),
+                ("mem.lp".to_string(), mem.to_string()),
+            ],
+            split("measurement")
+        );
+        assert_eq!(
+            vec![
+                ("part-00000.lp".to_string(), format!("{cpu}{mem}")),
+                ("part-00001.lp".to_string(), cpu_next_day.to_string()),
+            ],
+            split("size=80")
+        );
+
+        assert_eq!(Ok(SplitBy::Size(1 << 30)), parse_split_by("size=1GB"));
+        assert!(parse_split_by("size=1PB").is_err());
+        assert_eq!("a_b", file_name("a/b"));
+    }
+}
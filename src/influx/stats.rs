@@ -0,0 +1,275 @@
+//! Statistics of line protocol data.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::influx::schema::Schema;
+use crate::io::{create_output, open_input, Compression, Output};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+/// Report statistics of line protocol file.
+#[derive(Debug, Parser)]
+pub(crate) struct ShowStats {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl ShowStats {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let stats = Stats::collect(input)?;
+
+        let mut output = create_output(&self.output, Compression::None)?;
+        write!(output, "{stats}").context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Statistics of lines.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    measurements: Vec<MeasurementStats>,
+    measurement_index: HashMap<String, usize>,
+}
+
+/// Statistics of lines in a measurement.
+#[derive(Debug, Default)]
+struct MeasurementStats {
+    name: String,
+    schema: Schema,
+    /// Keys of series, which are sorted tags.
+    series: HashSet<String>,
+    /// Distinct values of each tag in the schema.
+    tag_values: Vec<HashSet<String>>,
+    points: u64,
+    /// Min and max timestamps.
+    time_range: Option<(i64, i64)>,
+}
+
+impl Stats {
+    pub(crate) fn collect<R: Read>(source: R) -> Result<Stats> {
+        let mut stats = Stats::default();
+        read_lines(source, |line| stats.update(&line))?;
+
+        Ok(stats)
+    }
+
+    pub(crate) fn update(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        let name = line.series.measurement.as_str();
+        let idx = match self.measurement_index.get(name) {
+            Some(idx) => *idx,
+            None => {
+                self.measurement_index
+                    .insert(name.to_string(), self.measurements.len());
+                self.measurements.push(MeasurementStats {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                self.measurements.len() - 1
+            }
+        };
+        self.measurements[idx].update(line)
+    }
+
+    pub(crate) fn points(&self) -> u64 {
+        self.measurements.iter().map(|m| m.points).sum()
+    }
+
+    fn time_range(&self) -> Option<(i64, i64)> {
+        self.measurements
+            .iter()
+            .filter_map(|m| m.time_range)
+            .reduce(|(min, max), (lo, hi)| (min.min(lo), max.max(hi)))
+    }
+}
+
+impl MeasurementStats {
+    fn update(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        self.schema.update(line)?;
+        self.tag_values
+            .resize_with(self.schema.tags.len(), HashSet::new);
+
+        let mut tags: Vec<_> = line.series.tag_set.iter().flatten().collect();
+        tags.sort_unstable_by_key(|(key, _)| key.as_str());
+        let mut series_key = String::new();
+        for (key, value) in tags {
+            let idx = self.schema.tag_index(key.as_str()).unwrap_or_default();
+            if !self.tag_values[idx].contains(value.as_str()) {
+                self.tag_values[idx].insert(value.to_string());
+            }
+            if !series_key.is_empty() {
+                series_key.push(',');
+            }
+            series_key.push_str(key.as_str());
+            series_key.push('=');
+            series_key.push_str(value.as_str());
+        }
+        self.series.insert(series_key);
+
+        self.points += 1;
+        if let Some(ts) = line.timestamp {
+            self.time_range = Some(match self.time_range {
+                Some((min, max)) => (min.min(ts), max.max(ts)),
+                None => (ts, ts),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Writer that collects statistics of line protocol written to it, e.g. by dry runs.
+pub(crate) struct StatsWriter {
+    stats: Arc<Mutex<Stats>>,
+    /// Bytes of the last line that is not complete.
+    buffer: Vec<u8>,
+}
+
+impl StatsWriter {
+    pub(crate) fn new(stats: Arc<Mutex<Stats>>) -> StatsWriter {
+        StatsWriter {
+            stats,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl Write for StatsWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if let Some(end) = self.buffer.iter().rposition(|&b| b == b'\n') {
+            let mut stats = self.stats.lock().unwrap();
+            read_lines(&self.buffer[..=end], |line| stats.update(&line))
+                .map_err(io::Error::other)?;
+            self.buffer.drain(..=end);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output for StatsWriter {
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write the time range in RFC3339.
+fn fmt_time_range(f: &mut fmt::Formatter<'_>, time_range: Option<(i64, i64)>) -> fmt::Result {
+    match time_range {
+        Some((min, max)) => writeln!(
+            f,
+            "{} - {}",
+            Utc.timestamp_nanos(min).to_rfc3339(),
+            Utc.timestamp_nanos(max).to_rfc3339()
+        ),
+        None => writeln!(f, "-"),
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Measurements: {}", self.measurements.len())?;
+        writeln!(
+            f,
+            "Series: {}",
+            self.measurements
+                .iter()
+                .map(|m| m.series.len())
+                .sum::<usize>()
+        )?;
+        writeln!(f, "Points: {}", self.points())?;
+        write!(f, "Time range: ")?;
+        fmt_time_range(f, self.time_range())?;
+
+        for measurement in &self.measurements {
+            writeln!(f)?;
+            writeln!(f, "Measurement: {}", measurement.name)?;
+            writeln!(f, "  Series: {}", measurement.series.len())?;
+            writeln!(f, "  Points: {}", measurement.points)?;
+            write!(f, "  Time range: ")?;
+            fmt_time_range(f, measurement.time_range)?;
+            writeln!(f, "  Tags:")?;
+            for (tag, values) in measurement.schema.tags.iter().zip(&measurement.tag_values) {
+                writeln!(f, "    {tag}: {}", values.len())?;
+            }
+            writeln!(f, "  Fields:")?;
+            for (field, field_type) in &measurement.schema.fields {
+                writeln!(f, "    {field}: {field_type}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let input = "cpu,host=a,region=eu usage=1i 1451606400000000000
+cpu,region=eu,host=a usage=2i 1451606410000000000
+cpu,host=b,region=eu usage=1.5 1451606420000000000
+mem,host=a used=1u,ok=true
+";
+        let stats = Stats::collect(input.as_bytes()).unwrap();
+        assert_eq!(
+            "Measurements: 2
+Series: 3
+Points: 4
+Time range: 2016-01-01T00:00:00+00:00 - 2016-01-01T00:00:20+00:00
+
+Measurement: cpu
+  Series: 2
+  Points: 3
+  Time range: 2016-01-01T00:00:00+00:00 - 2016-01-01T00:00:20+00:00
+  Tags:
+    host: 2
+    region: 1
+  Fields:
+    usage: float
+
+Measurement: mem
+  Series: 1
+  Points: 1
+  Time range: -
+  Tags:
+    host: 1
+  Fields:
+    used: unsigned
+    ok: boolean
+",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn test_stats_writer() {
+        let stats = Arc::new(Mutex::new(Stats::default()));
+        let mut writer = StatsWriter::new(stats.clone());
+        writer.write_all(b"cpu,host=a usage=1 1\ncpu,ho").unwrap();
+        assert_eq!(1, stats.lock().unwrap().points());
+        writer.write_all(b"st=b usage=2 2\n").unwrap();
+        let stats = stats.lock().unwrap();
+        assert_eq!(2, stats.points());
+        assert_eq!(2, stats.measurements[0].series.len());
+    }
+}
@@ -0,0 +1,265 @@
+//! Timestamp utilities.
+
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Timelike, Utc};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::fmt::Write;
+
+/// Precision of epoch timestamps in the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision {
+    /// Detect the precision by the magnitude of the timestamp.
+    Auto,
+    /// Second.
+    S,
+    /// Millisecond.
+    Ms,
+    /// Microsecond.
+    Us,
+    /// Nanosecond.
+    Ns,
+}
+
+impl Precision {
+    /// Returns the precision of the timestamp, assuming it is close to the current
+    /// epoch (between 1973 and 5138).
+    fn detect(ts: i64) -> Precision {
+        match ts.unsigned_abs() {
+            v if v < 100_000_000_000 => Precision::S,
+            v if v < 100_000_000_000_000 => Precision::Ms,
+            v if v < 100_000_000_000_000_000 => Precision::Us,
+            _ => Precision::Ns,
+        }
+    }
+
+    /// Convert the timestamp in this precision to nanosecond.
+    ///
+    /// Returns `None` if the timestamp is out of range.
+    pub(crate) fn to_nanos(self, ts: i64) -> Option<i64> {
+        let factor = match self {
+            Precision::Auto => return Precision::detect(ts).to_nanos(ts),
+            Precision::S => 1_000_000_000,
+            Precision::Ms => 1_000_000,
+            Precision::Us => 1_000,
+            Precision::Ns => 1,
+        };
+        ts.checked_mul(factor)
+    }
+}
+
+/// Format of timestamps in CSV output, both keep nanoseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum TimeFormat {
+    /// RFC3339 with 9 fractional digits, e.g. `2016-01-01T00:00:00.000000001+00:00`.
+    #[default]
+    Rfc3339,
+    /// Epoch nanoseconds.
+    Ns,
+}
+
+impl TimeFormat {
+    /// Format the timestamp in nanosecond.
+    pub(crate) fn format(self, ts: i64) -> String {
+        let mut out = String::new();
+        self.write(ts, &mut out);
+        out
+    }
+
+    /// Append the formatted timestamp in nanosecond to `out`, so a buffer is reused
+    /// across rows.
+    pub(crate) fn write(self, ts: i64, out: &mut String) {
+        match self {
+            TimeFormat::Rfc3339 => {
+                // Nanosecond timestamps are always in years 1677 to 2262.
+                let dt = Utc.timestamp_nanos(ts);
+                write!(
+                    out,
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}+00:00",
+                    dt.year(),
+                    dt.month(),
+                    dt.day(),
+                    dt.hour(),
+                    dt.minute(),
+                    dt.second(),
+                    dt.nanosecond()
+                )
+            }
+            TimeFormat::Ns => write!(out, "{ts}"),
+        }
+        .expect("writing to a string never fails");
+    }
+}
+
+/// Parser of timestamps in text.
+#[derive(Debug, Clone)]
+pub(crate) struct TimestampParser {
+    /// Precision of epoch timestamps.
+    precision: Precision,
+    /// strptime format of timestamps, timestamps are epoch or RFC3339 if not set.
+    format: Option<String>,
+}
+
+impl TimestampParser {
+    pub(crate) fn new(precision: Precision, format: Option<String>) -> TimestampParser {
+        TimestampParser { precision, format }
+    }
+
+    /// Convert the epoch timestamp to nanosecond.
+    pub(crate) fn parse_epoch(&self, ts: i64) -> Option<i64> {
+        self.precision.to_nanos(ts)
+    }
+
+    /// Parse the timestamp and returns it in nanosecond.
+    ///
+    /// Timestamps without time zone are in UTC.
+    pub(crate) fn parse(&self, value: &str) -> Option<i64> {
+        if let Some(format) = &self.format {
+            let dt = match DateTime::parse_from_str(value, format) {
+                Ok(dt) => dt.with_timezone(&Utc),
+                Err(_) => NaiveDateTime::parse_from_str(value, format).ok()?.and_utc(),
+            };
+            return dt.timestamp_nanos_opt();
+        }
+
+        if let Ok(ts) = value.parse::<i64>() {
+            return self.parse_epoch(ts);
+        }
+        DateTime::parse_from_rfc3339(value)
+            .ok()?
+            .timestamp_nanos_opt()
+    }
+}
+
+/// Parse a duration like `10s` to nanosecond.
+///
+/// Units are `ns`, `us`, `ms`, `s`, `m`, `h` and `d`.
+pub(crate) fn parse_duration(value: &str) -> Result<i64, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration {value:?}"))?;
+    let factor = match unit {
+        "ns" => 1,
+        "us" => 1_000,
+        "ms" => 1_000_000,
+        "s" => 1_000_000_000,
+        "m" => 60_000_000_000,
+        "h" => 3_600_000_000_000,
+        "d" => 86_400_000_000_000,
+        _ => return Err(format!("invalid unit of duration {value:?}")),
+    };
+    number
+        .checked_mul(factor)
+        .filter(|nanos| *nanos > 0)
+        .ok_or_else(|| format!("duration {value:?} is out of range"))
+}
+
+/// Parse a RFC3339 timestamp to nanosecond.
+pub(crate) fn parse_rfc3339(value: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .and_then(|dt| dt.timestamp_nanos_opt())
+        .ok_or_else(|| format!("invalid RFC3339 timestamp {value:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precision_to_nanos() {
+        assert_eq!(Some(1451606400000000000), Precision::S.to_nanos(1451606400));
+        assert_eq!(
+            Some(1451606400000000000),
+            Precision::Ms.to_nanos(1451606400000)
+        );
+        assert_eq!(
+            Some(1451606400000000000),
+            Precision::Us.to_nanos(1451606400000000)
+        );
+        assert_eq!(
+            Some(1451606400000000000),
+            Precision::Ns.to_nanos(1451606400000000000)
+        );
+
+        for ts in [
+            1451606400,
+            1451606400000,
+            1451606400000000,
+            1451606400000000000,
+        ] {
+            assert_eq!(Some(1451606400000000000), Precision::Auto.to_nanos(ts));
+        }
+        assert_eq!(None, Precision::S.to_nanos(i64::MAX / 10));
+    }
+
+    #[test]
+    fn test_timestamp_parser() {
+        let parser = TimestampParser::new(Precision::Ms, None);
+        assert_eq!(Some(1451606400000000000), parser.parse("1451606400000"));
+        assert_eq!(
+            Some(1451606400000000000),
+            parser.parse("2016-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            Some(1451606400123456789),
+            parser.parse("2016-01-01T08:00:00.123456789+08:00")
+        );
+        assert_eq!(None, parser.parse("2016/01/01 00:00:00"));
+
+        let parser = TimestampParser::new(Precision::Ms, Some("%Y/%m/%d %H:%M:%S".to_string()));
+        assert_eq!(
+            Some(1451606400000000000),
+            parser.parse("2016/01/01 00:00:00")
+        );
+        let parser = TimestampParser::new(Precision::Ms, Some("%Y/%m/%d %H:%M:%S %z".to_string()));
+        assert_eq!(
+            Some(1451606400000000000),
+            parser.parse("2016/01/01 09:00:00 +0900")
+        );
+        assert_eq!(None, parser.parse("1451606400000"));
+    }
+
+    #[test]
+    fn test_time_format() {
+        let parser = TimestampParser::new(Precision::Auto, None);
+        for ts in [1451606400123456789, 1451606400000000001] {
+            for format in [TimeFormat::Rfc3339, TimeFormat::Ns] {
+                assert_eq!(Some(ts), parser.parse(&format.format(ts)), "{format:?}");
+            }
+        }
+        assert_eq!(
+            "2016-01-01T00:00:00.000000001+00:00",
+            TimeFormat::Rfc3339.format(1451606400000000001)
+        );
+        for ts in [i64::MIN, -1, 0, i64::MAX] {
+            assert_eq!(
+                Utc.timestamp_nanos(ts)
+                    .to_rfc3339_opts(chrono::SecondsFormat::Nanos, false),
+                TimeFormat::Rfc3339.format(ts)
+            );
+        }
+        let mut out = "a,".to_string();
+        TimeFormat::Ns.write(-1, &mut out);
+        assert_eq!("a,-1", out);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Ok(10_000_000_000), parse_duration("10s"));
+        assert_eq!(Ok(90_000_000_000), parse_duration("90s"));
+        assert_eq!(Ok(300_000_000_000), parse_duration("5m"));
+        assert_eq!(Ok(1), parse_duration("1ns"));
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("s").is_err());
+        assert_eq!(
+            Ok(1451606400000000000),
+            parse_rfc3339("2016-01-01T00:00:00Z")
+        );
+    }
+}
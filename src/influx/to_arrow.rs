@@ -0,0 +1,235 @@
+//! Convert line protocol to Apache Arrow IPC files.
+
+use crate::error::{ArrowSnafu, CreateDirSnafu, Result, WriteOutputSnafu};
+use crate::influx::read_lines;
+use crate::influx::schema::Schema;
+use crate::influx::split::file_name;
+use crate::influx::to_parquet::BatchBuilder;
+use crate::io::{create_binary_output, Compression, Output, ReplayableInput};
+use crate::reject;
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use arrow::record_batch::RecordBatch;
+use clap::{Parser, ValueEnum};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::debug;
+
+/// Format of the Arrow IPC files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum IpcFormat {
+    /// IPC streaming format, files are named `<measurement>.arrows`.
+    Stream,
+    /// IPC file format (Feather V2), files are named `<measurement>.arrow`.
+    File,
+}
+
+impl IpcFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            IpcFormat::Stream => "arrows",
+            IpcFormat::File => "arrow",
+        }
+    }
+}
+
+/// Convert line protocol file to Arrow IPC files, a file of each measurement.
+#[derive(Debug, Parser)]
+pub(crate) struct ToArrow {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Format of the output files.
+    #[arg(long, value_enum, default_value_t = IpcFormat::Stream)]
+    format: IpcFormat,
+    /// Number of rows in each record batch.
+    #[arg(long, default_value_t = 8192)]
+    batch_size: usize,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl ToArrow {
+    pub(crate) fn run(self) -> Result<()> {
+        // The first pass collects the schema of each measurement, the second pass writes the rows.
+        let start = Instant::now();
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let schemas = reject::silently(|| Schema::infer_measurements(input.reader()?))?;
+        debug!(
+            "Inferred schemas of {} measurements in {:?}",
+            schemas.len(),
+            start.elapsed()
+        );
+
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        line_protocol_to_arrow(
+            &schemas,
+            input.reader()?,
+            &self.output_dir,
+            self.format,
+            self.batch_size,
+        )
+    }
+}
+
+type IpcOutput = BufWriter<Box<dyn Output + Send>>;
+
+/// Writer of record batches in the IPC format.
+enum IpcWriter {
+    Stream(StreamWriter<IpcOutput>),
+    File(FileWriter<IpcOutput>),
+}
+
+impl IpcWriter {
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        match self {
+            IpcWriter::Stream(writer) => writer.write(batch),
+            IpcWriter::File(writer) => writer.write(batch),
+        }
+        .context(ArrowSnafu)
+    }
+
+    /// Write the end of the file and finish the output.
+    fn finish(self) -> Result<()> {
+        let mut output = match self {
+            IpcWriter::Stream(writer) => writer.into_inner(),
+            IpcWriter::File(writer) => writer.into_inner(),
+        }
+        .context(ArrowSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Rows of a measurement being written.
+struct MeasurementWriter<'a> {
+    schema: &'a Schema,
+    builder: BatchBuilder,
+    writer: IpcWriter,
+}
+
+fn line_protocol_to_arrow<R: Read>(
+    schemas: &[(String, Schema)],
+    source: R,
+    dir: &Path,
+    format: IpcFormat,
+    batch_size: usize,
+) -> Result<()> {
+    let mut index = HashMap::with_capacity(schemas.len());
+    let mut writers = Vec::with_capacity(schemas.len());
+    for (measurement, schema) in schemas {
+        let builder = BatchBuilder::new(schema);
+        let path = dir.join(format!("{}.{}", file_name(measurement), format.extension()));
+        let output = BufWriter::new(create_binary_output(path)?);
+        let writer = match format {
+            IpcFormat::Stream => IpcWriter::Stream(
+                StreamWriter::try_new(output, &builder.arrow_schema).context(ArrowSnafu)?,
+            ),
+            IpcFormat::File => IpcWriter::File(
+                FileWriter::try_new(output, &builder.arrow_schema).context(ArrowSnafu)?,
+            ),
+        };
+        index.insert(measurement.as_str(), writers.len());
+        writers.push(MeasurementWriter {
+            schema,
+            builder,
+            writer,
+        });
+    }
+
+    read_lines(source, |line| {
+        // All measurements are collected by the first pass.
+        let writer = &mut writers[index[line.series.measurement.as_str()]];
+        writer.builder.append_line(writer.schema, &line)?;
+        if writer.builder.len >= batch_size {
+            writer.writer.write(&writer.builder.finish()?)?;
+        }
+        Ok(())
+    })?;
+
+    for mut writer in writers {
+        if writer.builder.len > 0 {
+            writer.writer.write(&writer.builder.finish()?)?;
+        }
+        writer.writer.finish()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::AsArray;
+    use arrow::datatypes::{Float64Type, Int64Type};
+    use arrow::ipc::reader::{FileReader, StreamReader};
+    use std::fs::File;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_line_protocol_to_arrow() {
+        let input = "cpu,hostname=host_0 usage_user=58i,usage_system=2.5 1451606400000000000
+mem,region=us-west-1 used=1024i
+cpu,hostname=host_1 usage_user=84i 1451606410000000000
+";
+        let schemas = Schema::infer_measurements(Cursor::new(input)).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        line_protocol_to_arrow(
+            &schemas,
+            Cursor::new(input),
+            dir.path(),
+            IpcFormat::Stream,
+            1,
+        )
+        .unwrap();
+
+        let file = File::open(dir.path().join("cpu.arrows")).unwrap();
+        let batches: Vec<_> = StreamReader::try_new(file, None)
+            .unwrap()
+            .map(|batch| batch.unwrap())
+            .collect();
+        assert_eq!(2, batches.len());
+        let batch_schema = batches[0].schema();
+        let names: Vec<_> = batch_schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "measurement",
+                "hostname",
+                "usage_user",
+                "usage_system",
+                "timestamp"
+            ],
+            names
+        );
+        let usage_user = batches[1].column(2).as_primitive::<Int64Type>();
+        assert_eq!(84, usage_user.value(0));
+        let usage_system = batches[0].column(3).as_primitive::<Float64Type>();
+        assert_eq!(2.5, usage_system.value(0));
+
+        line_protocol_to_arrow(
+            &schemas,
+            Cursor::new(input),
+            dir.path(),
+            IpcFormat::File,
+            8192,
+        )
+        .unwrap();
+        let file = File::open(dir.path().join("mem.arrow")).unwrap();
+        let reader = FileReader::try_new(file, None).unwrap();
+        assert_eq!(4, reader.schema().fields().len());
+        let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(1, batches.len());
+        assert_eq!(1, batches[0].num_rows());
+    }
+}
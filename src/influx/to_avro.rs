@@ -0,0 +1,448 @@
+//! Convert line protocol to Apache Avro object container files.
+
+use crate::error::{
+    CreateDirSnafu, FieldTypeConflictSnafu, InvalidPointSnafu, Result, WriteOutputSnafu,
+};
+use crate::influx::read_lines;
+use crate::influx::schema::{FieldType, Schema};
+use crate::influx::split::file_name;
+use crate::io::{create_binary_output, Compression, Output, ReplayableInput};
+use crate::reject;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use flate2::write::DeflateEncoder;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use serde_json::{json, Value};
+use snafu::{OptionExt, ResultExt};
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::debug;
+
+/// Magic bytes at the start of object container files.
+const MAGIC: &[u8] = b"Obj\x01";
+
+/// Codec of blocks of the files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum AvroCodec {
+    /// Not compressed.
+    Null,
+    /// Raw deflate of RFC 1951.
+    Deflate,
+}
+
+impl AvroCodec {
+    fn name(self) -> &'static str {
+        match self {
+            AvroCodec::Null => "null",
+            AvroCodec::Deflate => "deflate",
+        }
+    }
+}
+
+/// Convert line protocol file to an Avro object container file of each measurement.
+///
+/// Files are named by the measurements, e.g. `cpu.avro`, and have a record schema of the
+/// measurement inferred by the first pass of the input. Tags and fields are nullable,
+/// unsigned integers are `long` and lines with values out of its range are invalid. The
+/// timestamp is `long` nanoseconds of the `timestamp-nanos` logical type. Names are
+/// sanitized to Avro names.
+#[derive(Debug, Parser)]
+pub(crate) struct ToAvro {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output directory of the files.
+    #[arg(short, long)]
+    output_dir: PathBuf,
+    /// Timestamp column name.
+    #[arg(long, default_value = "timestamp")]
+    timestamp: String,
+    /// Codec of blocks.
+    #[arg(long, value_enum, default_value_t = AvroCodec::Deflate)]
+    codec: AvroCodec,
+    /// Number of rows in each block.
+    #[arg(long, default_value_t = 4096, value_parser = clap::value_parser!(u64).range(1..))]
+    block_size: u64,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl ToAvro {
+    pub(crate) fn run(self) -> Result<()> {
+        // The first pass collects the schema of each measurement, the second pass writes the rows.
+        let start = Instant::now();
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let schemas = reject::silently(|| Schema::infer_measurements(input.reader()?))?;
+        debug!(
+            "Inferred schemas of {} measurements in {:?}",
+            schemas.len(),
+            start.elapsed()
+        );
+
+        fs::create_dir_all(&self.output_dir).context(CreateDirSnafu {
+            path: &self.output_dir,
+        })?;
+        let index: HashMap<_, _> = schemas
+            .iter()
+            .enumerate()
+            .map(|(i, (measurement, _))| (measurement.clone(), i))
+            .collect();
+
+        let mut writers = Vec::with_capacity(schemas.len());
+        for (measurement, schema) in &schemas {
+            let path = self
+                .output_dir
+                .join(format!("{}.avro", file_name(measurement)));
+            let output = BufWriter::new(create_binary_output(&path)?);
+            let schema = avro_schema(measurement, schema, &self.timestamp);
+            writers.push(AvroWriter::new(output, &schema, self.codec)?);
+        }
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut row = Vec::new();
+        read_lines(input.reader()?, |line| {
+            // Schemas have all measurements of the input.
+            let idx = index[line.series.measurement.as_str()];
+            row.clear();
+            push_row(&mut row, &schemas[idx].1, &line, now)?;
+            let writer = &mut writers[idx];
+            writer.push_row(&row);
+            if writer.rows >= self.block_size {
+                writer.write_block()?;
+            }
+            Ok(())
+        })?;
+        for writer in writers {
+            writer.finish()?.finish().context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the name with chars other than letters, digits and `_` replaced by `_`, which
+/// doesn't start with a digit.
+fn avro_name(name: &str) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if !sanitized.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+/// Returns the record schema of the measurement.
+///
+/// Columns are tags, fields and the timestamp, names that are the same after sanitizing
+/// get a suffix like `_2`.
+fn avro_schema(measurement: &str, schema: &Schema, timestamp: &str) -> Value {
+    let mut names = HashSet::new();
+    let mut unique_name = |name: &str| {
+        let name = avro_name(name);
+        let mut unique = name.clone();
+        let mut n = 1;
+        while !names.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{name}_{n}");
+        }
+        unique
+    };
+
+    let mut fields = Vec::with_capacity(schema.tags.len() + schema.fields.len() + 1);
+    for tag in &schema.tags {
+        fields.push(json!({
+            "name": unique_name(tag),
+            "type": ["null", "string"],
+            "default": null,
+        }));
+    }
+    for (field, field_type) in &schema.fields {
+        let avro_type = match field_type {
+            FieldType::Int64 | FieldType::UInt64 => "long",
+            FieldType::Float64 => "double",
+            FieldType::String => "string",
+            FieldType::Boolean => "boolean",
+        };
+        fields.push(json!({
+            "name": unique_name(field),
+            "type": ["null", avro_type],
+            "default": null,
+        }));
+    }
+    fields.push(json!({
+        "name": unique_name(timestamp),
+        "type": {"type": "long", "logicalType": "timestamp-nanos"},
+    }));
+    json!({
+        "type": "record",
+        "name": avro_name(measurement),
+        "fields": fields,
+    })
+}
+
+/// Append the zigzag varint of the long.
+fn push_long(buf: &mut Vec<u8>, v: i64) {
+    let mut n = ((v << 1) ^ (v >> 63)) as u64;
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+/// Append the bytes with the length.
+fn push_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    push_long(buf, bytes.len() as i64);
+    buf.extend(bytes);
+}
+
+/// Append the line as a record of the schema, lines without timestamp have `now`.
+fn push_row(buf: &mut Vec<u8>, schema: &Schema, line: &ParsedLine<'_>, now: i64) -> Result<()> {
+    let mut tags = vec![None; schema.tags.len()];
+    for (key, value) in line.series.tag_set.iter().flatten() {
+        if let Some(i) = schema.tag_index(key.as_str()) {
+            tags[i] = Some(value.as_str());
+        }
+    }
+    let mut fields = vec![None; schema.fields.len()];
+    for (key, value) in &line.field_set {
+        if let Some(i) = schema.field_index(key.as_str()) {
+            fields[i] = Some(value);
+        }
+    }
+
+    // Values of nullable columns are unions of `null` and the type.
+    for tag in tags {
+        match tag {
+            Some(tag) => {
+                push_long(buf, 1);
+                push_bytes(buf, tag.as_bytes());
+            }
+            None => push_long(buf, 0),
+        }
+    }
+    for ((key, field_type), field) in schema.fields.iter().zip(fields) {
+        let Some(value) = field else {
+            push_long(buf, 0);
+            continue;
+        };
+        push_long(buf, 1);
+        match (field_type, value) {
+            (FieldType::Float64, FieldValue::F64(v)) => buf.extend(v.to_le_bytes()),
+            (FieldType::Float64, FieldValue::I64(v)) => buf.extend((*v as f64).to_le_bytes()),
+            (FieldType::Float64, FieldValue::U64(v)) => buf.extend((*v as f64).to_le_bytes()),
+            (FieldType::Int64 | FieldType::UInt64, FieldValue::I64(v)) => push_long(buf, *v),
+            (FieldType::Int64 | FieldType::UInt64, FieldValue::U64(v)) => {
+                let v = i64::try_from(*v).ok().context(InvalidPointSnafu {
+                    reason: format!("unsigned value {v} of {key} is out of range"),
+                })?;
+                push_long(buf, v);
+            }
+            (FieldType::Boolean, FieldValue::Boolean(v)) => buf.push(u8::from(*v)),
+            (FieldType::String, FieldValue::String(v)) => push_bytes(buf, v.as_bytes()),
+            // The line is skipped by the inference pass, e.g. with `--on-error skip`.
+            _ => {
+                return FieldTypeConflictSnafu {
+                    field: key,
+                    current: field_type.to_string(),
+                    other: FieldType::of(value).to_string(),
+                }
+                .fail()
+            }
+        }
+    }
+    push_long(buf, line.timestamp.unwrap_or(now));
+    Ok(())
+}
+
+/// Writer of an object container file.
+struct AvroWriter<W: Write> {
+    output: W,
+    codec: AvroCodec,
+    /// Marker after each block.
+    sync: [u8; 16],
+    /// Records of the current block.
+    block: Vec<u8>,
+    rows: u64,
+}
+
+impl<W: Write> AvroWriter<W> {
+    /// Write the header with the schema.
+    fn new(mut output: W, schema: &Value, codec: AvroCodec) -> Result<AvroWriter<W>> {
+        let mut sync = [0; 16];
+        for chunk in sync.chunks_mut(8) {
+            let hasher = RandomState::new().build_hasher();
+            chunk.copy_from_slice(&hasher.finish().to_le_bytes());
+        }
+
+        let mut header = MAGIC.to_vec();
+        // The metadata is a map of bytes with a block of 2 entries.
+        push_long(&mut header, 2);
+        push_bytes(&mut header, b"avro.schema");
+        push_bytes(&mut header, schema.to_string().as_bytes());
+        push_bytes(&mut header, b"avro.codec");
+        push_bytes(&mut header, codec.name().as_bytes());
+        push_long(&mut header, 0);
+        header.extend(sync);
+        output.write_all(&header).context(WriteOutputSnafu)?;
+
+        Ok(AvroWriter {
+            output,
+            codec,
+            sync,
+            block: Vec::new(),
+            rows: 0,
+        })
+    }
+
+    fn push_row(&mut self, row: &[u8]) {
+        self.block.extend(row);
+        self.rows += 1;
+    }
+
+    /// Write the current block if it has rows.
+    fn write_block(&mut self) -> Result<()> {
+        if self.rows == 0 {
+            return Ok(());
+        }
+        let data = match self.codec {
+            AvroCodec::Null => std::mem::take(&mut self.block),
+            AvroCodec::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&self.block).context(WriteOutputSnafu)?;
+                self.block.clear();
+                encoder.finish().context(WriteOutputSnafu)?
+            }
+        };
+        let mut header = Vec::new();
+        push_long(&mut header, self.rows as i64);
+        push_long(&mut header, data.len() as i64);
+        self.output.write_all(&header).context(WriteOutputSnafu)?;
+        self.output.write_all(&data).context(WriteOutputSnafu)?;
+        self.output
+            .write_all(&self.sync)
+            .context(WriteOutputSnafu)?;
+        self.rows = 0;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.write_block()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use apache_avro::types::Value as AvroValue;
+    use apache_avro::Reader;
+
+    #[test]
+    fn test_to_avro() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.lp");
+        fs::write(
+            &input,
+            "cpu,host=a usage=1.5,count=2i 1
+cpu,my-host=b usage=2i,up=true,note=\"x\" 2
+disk,host=a free=3u 3
+",
+        )
+        .unwrap();
+        let output_dir = dir.path().join("out");
+        let args = format!(
+            "to-avro -i {} -o {} --codec deflate --block-size 1",
+            input.display(),
+            output_dir.display()
+        );
+        ToAvro::try_parse_from(args.split(' '))
+            .unwrap()
+            .run()
+            .unwrap();
+
+        // Rows are decoded by an independent reader.
+        let data = fs::read(output_dir.join("cpu.avro")).unwrap();
+        let reader = Reader::new(&data[..]).unwrap();
+        let rows: Vec<_> = reader.map(|row| row.unwrap()).collect();
+        let null = || AvroValue::Union(0, Box::new(AvroValue::Null));
+        let some = |v| AvroValue::Union(1, Box::new(v));
+        let row = |values: Vec<AvroValue>| {
+            let names = [
+                "host",
+                "my_host",
+                "usage",
+                "count",
+                "up",
+                "note",
+                "timestamp",
+            ];
+            AvroValue::Record(names.into_iter().map(String::from).zip(values).collect())
+        };
+        assert_eq!(
+            vec![
+                row(vec![
+                    some(AvroValue::String("a".to_string())),
+                    null(),
+                    some(AvroValue::Double(1.5)),
+                    some(AvroValue::Long(2)),
+                    null(),
+                    null(),
+                    AvroValue::TimestampNanos(1),
+                ]),
+                row(vec![
+                    null(),
+                    some(AvroValue::String("b".to_string())),
+                    some(AvroValue::Double(2.0)),
+                    null(),
+                    some(AvroValue::Boolean(true)),
+                    some(AvroValue::String("x".to_string())),
+                    AvroValue::TimestampNanos(2),
+                ]),
+            ],
+            rows
+        );
+
+        // Unsigned values are longs.
+        let data = fs::read(output_dir.join("disk.avro")).unwrap();
+        let rows: Vec<_> = Reader::new(&data[..])
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect();
+        assert_eq!(
+            vec![AvroValue::Record(vec![
+                ("host".to_string(), some(AvroValue::String("a".to_string()))),
+                ("free".to_string(), some(AvroValue::Long(3))),
+                ("timestamp".to_string(), AvroValue::TimestampNanos(3)),
+            ])],
+            rows
+        );
+
+        let schemas = Schema::infer_measurements("m v=18446744073709551615u".as_bytes()).unwrap();
+        let line = influxdb_line_protocol::parse_lines("m v=18446744073709551615u")
+            .next()
+            .unwrap()
+            .unwrap();
+        let err = push_row(&mut Vec::new(), &schemas[0].1, &line, 0).unwrap_err();
+        assert!(err.is_invalid_line());
+
+        // Values must match types of columns, e.g. the line is missing from the schema.
+        let schemas = Schema::infer_measurements("m v=1i".as_bytes()).unwrap();
+        let line = influxdb_line_protocol::parse_lines("m v=\"x\"")
+            .next()
+            .unwrap()
+            .unwrap();
+        let err = push_row(&mut Vec::new(), &schemas[0].1, &line, 0).unwrap_err();
+        assert_eq!(
+            "Field v has conflicting types integer and string",
+            err.to_string()
+        );
+        assert_eq!("_1m", avro_name("1m"));
+    }
+}
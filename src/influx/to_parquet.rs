@@ -0,0 +1,430 @@
+//! Convert line protocol to Apache Parquet.
+
+use crate::error::{
+    ArrowSnafu, ColumnNotInSchemaSnafu, FieldTypeConflictSnafu, Result, WriteOutputSnafu,
+    WriteParquetSnafu,
+};
+use crate::influx::read_lines;
+use crate::influx::schema::{FieldType, Schema};
+use crate::io::{create_binary_output, Compression, Output, ReplayableInput};
+use crate::reject;
+use arrow::array::{
+    ArrayRef, BooleanBuilder, Float64Builder, Int64Builder, StringBuilder, StringDictionaryBuilder,
+    TimestampNanosecondBuilder, UInt64Builder,
+};
+use arrow::datatypes::{DataType, Field, Int32Type, Schema as ArrowSchema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use clap::Parser;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use parquet::arrow::ArrowWriter;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::debug;
+
+/// Name of the measurement column.
+const MEASUREMENT_COLUMN: &str = "measurement";
+/// Name of the timestamp column.
+const TIMESTAMP_COLUMN: &str = "timestamp";
+
+/// Convert line protocol file to Parquet file.
+#[derive(Debug, Parser)]
+pub(crate) struct ToParquet {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output Parquet file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    /// Number of rows in each record batch.
+    #[arg(long, default_value_t = 8192)]
+    batch_size: usize,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl ToParquet {
+    pub(crate) fn run(self) -> Result<()> {
+        // The first pass collects the schema, the second pass writes the rows.
+        let start = Instant::now();
+        let input = ReplayableInput::open(&self.input, self.input_compression)?;
+        let schema = reject::silently(|| Schema::infer(input.reader()?))?;
+        debug!(
+            "Inferred {} tags and {} fields in {:?}",
+            schema.tags.len(),
+            schema.fields.len(),
+            start.elapsed()
+        );
+
+        let output = create_binary_output(&self.output)?;
+        let mut output =
+            line_protocol_to_parquet(&schema, input.reader()?, output, self.batch_size)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn line_protocol_to_parquet<R: Read, W: Write + Send>(
+    schema: &Schema,
+    source: R,
+    dest: W,
+    batch_size: usize,
+) -> Result<W> {
+    let mut builder = BatchBuilder::new(schema);
+    let mut writer = ArrowWriter::try_new(dest, builder.arrow_schema.clone(), None)
+        .context(WriteParquetSnafu)?;
+
+    read_lines(source, |line| {
+        builder.append_line(schema, &line)?;
+        if builder.len >= batch_size {
+            writer
+                .write(&builder.finish()?)
+                .context(WriteParquetSnafu)?;
+        }
+        Ok(())
+    })?;
+    if builder.len > 0 {
+        writer
+            .write(&builder.finish()?)
+            .context(WriteParquetSnafu)?;
+    }
+
+    writer.into_inner().context(WriteParquetSnafu)
+}
+
+/// Returns the arrow type of the field type.
+fn arrow_type(field_type: FieldType) -> DataType {
+    match field_type {
+        FieldType::Int64 => DataType::Int64,
+        FieldType::UInt64 => DataType::UInt64,
+        FieldType::Float64 => DataType::Float64,
+        FieldType::String => DataType::Utf8,
+        FieldType::Boolean => DataType::Boolean,
+    }
+}
+
+/// Returns the arrow schema of the line protocol schema.
+///
+/// Columns are the measurement, tags, fields and the timestamp.
+fn arrow_schema(schema: &Schema) -> SchemaRef {
+    let dictionary = DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8));
+    let mut fields = vec![Field::new(MEASUREMENT_COLUMN, dictionary.clone(), false)];
+    fields.extend(
+        schema
+            .tags
+            .iter()
+            .map(|name| Field::new(name, dictionary.clone(), true)),
+    );
+    fields.extend(
+        schema
+            .fields
+            .iter()
+            .map(|(name, field_type)| Field::new(name, arrow_type(*field_type), true)),
+    );
+    fields.push(Field::new(
+        TIMESTAMP_COLUMN,
+        DataType::Timestamp(TimeUnit::Nanosecond, None),
+        true,
+    ));
+
+    Arc::new(ArrowSchema::new(fields))
+}
+
+/// Builder of a field column.
+enum FieldBuilder {
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float64(Float64Builder),
+    String(StringBuilder),
+    Boolean(BooleanBuilder),
+}
+
+impl FieldBuilder {
+    fn new(field_type: FieldType) -> FieldBuilder {
+        match field_type {
+            FieldType::Int64 => FieldBuilder::Int64(Int64Builder::new()),
+            FieldType::UInt64 => FieldBuilder::UInt64(UInt64Builder::new()),
+            FieldType::Float64 => FieldBuilder::Float64(Float64Builder::new()),
+            FieldType::String => FieldBuilder::String(StringBuilder::new()),
+            FieldType::Boolean => FieldBuilder::Boolean(BooleanBuilder::new()),
+        }
+    }
+
+    /// Returns true if the value can be appended to the column.
+    fn accepts(&self, value: &FieldValue) -> bool {
+        // Integers are widened to float if the field has mixed numeric types.
+        matches!(
+            (self, value),
+            (FieldBuilder::Int64(_), FieldValue::I64(_))
+                | (FieldBuilder::UInt64(_), FieldValue::U64(_))
+                | (
+                    FieldBuilder::Float64(_),
+                    FieldValue::F64(_) | FieldValue::I64(_) | FieldValue::U64(_)
+                )
+                | (FieldBuilder::String(_), FieldValue::String(_))
+                | (FieldBuilder::Boolean(_), FieldValue::Boolean(_))
+        )
+    }
+
+    /// Append the value, which must be accepted by the column.
+    fn append_value(&mut self, value: &FieldValue) {
+        match (self, value) {
+            (FieldBuilder::Int64(b), FieldValue::I64(v)) => b.append_value(*v),
+            (FieldBuilder::UInt64(b), FieldValue::U64(v)) => b.append_value(*v),
+            (FieldBuilder::Float64(b), FieldValue::F64(v)) => b.append_value(*v),
+            (FieldBuilder::Float64(b), FieldValue::I64(v)) => b.append_value(*v as f64),
+            (FieldBuilder::Float64(b), FieldValue::U64(v)) => b.append_value(*v as f64),
+            (FieldBuilder::String(b), FieldValue::String(v)) => b.append_value(v.as_str()),
+            (FieldBuilder::Boolean(b), FieldValue::Boolean(v)) => b.append_value(*v),
+            _ => unreachable!("the value is checked by accepts"),
+        }
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            FieldBuilder::Int64(b) => b.append_null(),
+            FieldBuilder::UInt64(b) => b.append_null(),
+            FieldBuilder::Float64(b) => b.append_null(),
+            FieldBuilder::String(b) => b.append_null(),
+            FieldBuilder::Boolean(b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            FieldBuilder::Int64(b) => Arc::new(b.finish()),
+            FieldBuilder::UInt64(b) => Arc::new(b.finish()),
+            FieldBuilder::Float64(b) => Arc::new(b.finish()),
+            FieldBuilder::String(b) => Arc::new(b.finish()),
+            FieldBuilder::Boolean(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Builds record batches from parsed lines.
+pub(crate) struct BatchBuilder {
+    pub(crate) arrow_schema: SchemaRef,
+    measurement: StringDictionaryBuilder<Int32Type>,
+    tags: Vec<StringDictionaryBuilder<Int32Type>>,
+    fields: Vec<FieldBuilder>,
+    timestamp: TimestampNanosecondBuilder,
+    /// Whether the tag is set in current line.
+    tag_filled: Vec<bool>,
+    /// Whether the field is set in current line.
+    field_filled: Vec<bool>,
+    /// Number of rows in the builder.
+    pub(crate) len: usize,
+}
+
+impl BatchBuilder {
+    pub(crate) fn new(schema: &Schema) -> BatchBuilder {
+        BatchBuilder {
+            arrow_schema: arrow_schema(schema),
+            measurement: StringDictionaryBuilder::new(),
+            tags: schema
+                .tags
+                .iter()
+                .map(|_| StringDictionaryBuilder::new())
+                .collect(),
+            fields: schema
+                .fields
+                .iter()
+                .map(|(_, field_type)| FieldBuilder::new(*field_type))
+                .collect(),
+            timestamp: TimestampNanosecondBuilder::new(),
+            tag_filled: vec![false; schema.tags.len()],
+            field_filled: vec![false; schema.fields.len()],
+            len: 0,
+        }
+    }
+
+    pub(crate) fn append_line(&mut self, schema: &Schema, line: &ParsedLine) -> Result<()> {
+        // All columns are checked before appending, so a rejected line leaves no partial
+        // row behind.
+        self.check_line(schema, line)?;
+        self.tag_filled.fill(false);
+        self.field_filled.fill(false);
+
+        self.measurement
+            .append_value(line.series.measurement.as_str());
+        if let Some(tag_set) = &line.series.tag_set {
+            for (key, value) in tag_set {
+                let idx = schema.tag_index(key.as_str()).expect("tags are checked");
+                // Only keep the first value of duplicate keys.
+                if !self.tag_filled[idx] {
+                    self.tags[idx].append_value(value.as_str());
+                    self.tag_filled[idx] = true;
+                }
+            }
+        }
+        for (key, value) in &line.field_set {
+            let idx = schema
+                .field_index(key.as_str())
+                .expect("fields are checked");
+            if !self.field_filled[idx] {
+                self.fields[idx].append_value(value);
+                self.field_filled[idx] = true;
+            }
+        }
+        for (builder, filled) in self.tags.iter_mut().zip(&self.tag_filled) {
+            if !filled {
+                builder.append_null();
+            }
+        }
+        for (builder, filled) in self.fields.iter_mut().zip(&self.field_filled) {
+            if !filled {
+                builder.append_null();
+            }
+        }
+        self.timestamp.append_option(line.timestamp);
+        self.len += 1;
+
+        Ok(())
+    }
+
+    /// Returns an error if a tag or field of the line is not in the schema, or the first
+    /// value of a field doesn't match the type of its column.
+    fn check_line(&mut self, schema: &Schema, line: &ParsedLine) -> Result<()> {
+        if let Some(tag_set) = &line.series.tag_set {
+            for (key, _) in tag_set {
+                schema
+                    .tag_index(key.as_str())
+                    .context(ColumnNotInSchemaSnafu {
+                        column: key.as_str(),
+                    })?;
+            }
+        }
+        self.field_filled.fill(false);
+        for (key, value) in &line.field_set {
+            let idx = schema
+                .field_index(key.as_str())
+                .context(ColumnNotInSchemaSnafu {
+                    column: key.as_str(),
+                })?;
+            if !self.field_filled[idx] {
+                let (name, field_type) = &schema.fields[idx];
+                ensure!(
+                    self.fields[idx].accepts(value),
+                    FieldTypeConflictSnafu {
+                        field: name,
+                        current: field_type.to_string(),
+                        other: FieldType::of(value).to_string(),
+                    }
+                );
+                self.field_filled[idx] = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build a record batch from rows in the builder and reset the builder.
+    pub(crate) fn finish(&mut self) -> Result<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(self.measurement.finish())];
+        columns.extend(
+            self.tags
+                .iter_mut()
+                .map(|builder| Arc::new(builder.finish()) as ArrayRef),
+        );
+        columns.extend(self.fields.iter_mut().map(|builder| builder.finish()));
+        columns.push(Arc::new(self.timestamp.finish()));
+        self.len = 0;
+
+        RecordBatch::try_new(self.arrow_schema.clone(), columns).context(ArrowSnafu)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray};
+    use arrow::datatypes::{Float64Type, Int64Type, TimestampNanosecondType};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::io::Cursor;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_line_protocol_to_parquet() {
+        let input = "cpu,hostname=host_0,region=eu-central-1 usage_user=58i,usage_system=2.5 1451606400000000000
+cpu,hostname=host_1 usage_user=84i,usage_idle=53i 1451606410000000000
+mem,region=us-west-1 used=1024i,active=true
+";
+        let schema = Schema::infer(Cursor::new(input)).unwrap();
+        let output_file = NamedTempFile::new().unwrap();
+        line_protocol_to_parquet(
+            &schema,
+            Cursor::new(input),
+            output_file.reopen().unwrap(),
+            2,
+        )
+        .unwrap();
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(output_file.reopen().unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+        assert_eq!(1, batches.len());
+        let batch = &batches[0];
+        assert_eq!(3, batch.num_rows());
+
+        let batch_schema = batch.schema();
+        let names: Vec<_> = batch_schema
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "measurement",
+                "hostname",
+                "region",
+                "usage_user",
+                "usage_system",
+                "usage_idle",
+                "used",
+                "active",
+                "timestamp"
+            ]
+        );
+        assert!(matches!(
+            batch_schema.field(1).data_type(),
+            DataType::Dictionary(_, _)
+        ));
+
+        let usage_user = batch.column(3).as_primitive::<Int64Type>();
+        assert_eq!((58, 84), (usage_user.value(0), usage_user.value(1)));
+        let usage_system = batch.column(4).as_primitive::<Float64Type>();
+        assert_eq!(2.5, usage_system.value(0));
+        assert!(usage_system.is_null(1));
+        let timestamp = batch.column(8).as_primitive::<TimestampNanosecondType>();
+        assert_eq!(1451606410000000000, timestamp.value(1));
+        assert!(timestamp.is_null(2));
+        assert!(batch.column(1).is_null(2));
+        assert!(batch.column(7).as_boolean().value(2));
+    }
+
+    #[test]
+    fn test_skip_conflicting_line() {
+        // The schema misses the conflicting line, which is skipped by the inference pass.
+        let schema = Schema::infer(Cursor::new("cpu,host=a v=1i 1\n")).unwrap();
+        let input = "cpu,host=a v=1i 1\ncpu,host=b v=\"x\" 2\ncpu,host=c v=3i 3\n";
+        let mut builder = BatchBuilder::new(&schema);
+        let mut errors = Vec::new();
+        read_lines(Cursor::new(input), |line| {
+            // Like `--on-error skip`, the rejected line is not appended.
+            if let Err(e) = builder.append_line(&schema, &line) {
+                errors.push(e.to_string());
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(1, errors.len());
+
+        let batch = builder.finish().unwrap();
+        assert_eq!(2, batch.num_rows());
+        let v = batch.column(2).as_primitive::<Int64Type>();
+        assert_eq!((1, 3), (v.value(0), v.value(1)));
+    }
+}
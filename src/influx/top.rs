@@ -0,0 +1,151 @@
+//! Heaviest series of line protocol.
+
+use crate::error::{Result, WriteOutputSnafu};
+use crate::influx::{read_lines_with_text, series_key};
+use crate::io::{create_output, open_input, Compression, Output};
+use clap::{Parser, ValueEnum};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{Read, Write};
+
+/// What to rank series by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum RankBy {
+    /// Number of points.
+    Points,
+    /// Bytes of lines, including newlines.
+    Bytes,
+}
+
+/// Report the series with the most points or bytes in line protocol file.
+///
+/// Counts of all series are kept in memory.
+#[derive(Debug, Parser)]
+pub(crate) struct Top {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// What to rank series by.
+    #[arg(long, value_enum, default_value_t = RankBy::Points)]
+    by: RankBy,
+    /// Number of series to report.
+    #[arg(short, default_value_t = 20)]
+    n: usize,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Top {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let series = count_series(input)?;
+
+        let mut output = create_output(&self.output, Compression::None)?;
+        write!(output, "{}", self.report(series)).context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+
+    /// Returns the report of the top series, with their shares of the total.
+    fn report(&self, series: HashMap<String, SeriesSize>) -> String {
+        let total = SeriesSize::sum(series.values());
+        let num_series = series.len();
+        let mut series: Vec<_> = series.into_iter().collect();
+        let rank = |size: &SeriesSize| match self.by {
+            RankBy::Points => size.points,
+            RankBy::Bytes => size.bytes,
+        };
+        series.sort_unstable_by(|a, b| rank(&b.1).cmp(&rank(&a.1)).then_with(|| a.0.cmp(&b.0)));
+        series.truncate(self.n);
+
+        let share = |value: u64, total: u64| value as f64 * 100.0 / total.max(1) as f64;
+        let mut report = format!(
+            "{:>12} {:>7} {:>14} {:>7}  Series\n",
+            "Points", "%", "Bytes", "%"
+        );
+        for (key, size) in &series {
+            writeln!(
+                report,
+                "{:>12} {:>6.2}% {:>14} {:>6.2}%  {key}",
+                size.points,
+                share(size.points, total.points),
+                size.bytes,
+                share(size.bytes, total.bytes)
+            )
+            .unwrap();
+        }
+        let top = rank(&SeriesSize::sum(series.iter().map(|(_, size)| size)));
+        let by = match self.by {
+            RankBy::Points => "points",
+            RankBy::Bytes => "bytes",
+        };
+        writeln!(
+            report,
+            "Top {} of {num_series} series have {:.2}% of {by}",
+            series.len(),
+            share(top, rank(&total))
+        )
+        .unwrap();
+        report
+    }
+}
+
+/// Points and bytes of a series.
+#[derive(Debug, Default, Clone, Copy)]
+struct SeriesSize {
+    points: u64,
+    bytes: u64,
+}
+
+impl SeriesSize {
+    fn sum<'a>(sizes: impl Iterator<Item = &'a SeriesSize>) -> SeriesSize {
+        sizes.fold(SeriesSize::default(), |sum, size| SeriesSize {
+            points: sum.points + size.points,
+            bytes: sum.bytes + size.bytes,
+        })
+    }
+}
+
+/// Returns points and bytes of each series of the lines.
+fn count_series<R: Read>(source: R) -> Result<HashMap<String, SeriesSize>> {
+    let mut series: HashMap<String, SeriesSize> = HashMap::new();
+    read_lines_with_text(source, |text, line| {
+        let size = series.entry(series_key(&line)).or_default();
+        size.points += 1;
+        size.bytes += text.len() as u64;
+        Ok(())
+    })?;
+    Ok(series)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top() {
+        let input = "cpu,host=a usage=1 1
+cpu,host=a usage=2 2
+cpu,host=a usage=3 3
+mem,host=a used=1,free=1000000,cached=1000000,buffered=1000000 1
+";
+        let series = count_series(input.as_bytes()).unwrap();
+
+        let top = Top::try_parse_from(["top", "-i", "-", "-n", "1"]).unwrap();
+        assert_eq!(
+            "      Points       %          Bytes       %  Series
+           3  75.00%             63  49.22%  cpu,host=a
+Top 1 of 2 series have 75.00% of points
+",
+            top.report(series.clone())
+        );
+        let top = Top::try_parse_from(["top", "-i", "-", "--by", "bytes"]).unwrap();
+        let report = top.report(series);
+        assert!(report.contains("50.78%  mem,host=a\n"), "{report}");
+        assert!(report.ends_with("Top 2 of 2 series have 100.00% of bytes\n"));
+    }
+}
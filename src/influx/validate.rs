@@ -0,0 +1,186 @@
+//! Validate line protocol data.
+
+use crate::error::{ReadInputSnafu, Result, ValidationFailedSnafu, WriteOutputSnafu};
+use crate::influx::schema::FieldType;
+use crate::influx::timestamp::parse_rfc3339;
+use crate::io::{create_output, open_input, Compression, Output};
+use chrono::{TimeZone, Utc};
+use clap::Parser;
+use influxdb_line_protocol::ParsedLine;
+use snafu::{ensure, ResultExt};
+use std::collections::{HashMap, HashSet};
+use std::io::{BufRead, BufReader, Write};
+
+/// Validate line protocol file and report problems with line numbers.
+///
+/// Fails if there is any problem.
+#[derive(Debug, Parser)]
+pub(crate) struct Validate {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output report file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Minimum valid timestamp in RFC3339.
+    #[arg(long, default_value = "1970-01-01T00:00:00Z", value_parser = parse_rfc3339)]
+    min_timestamp: i64,
+    /// Maximum valid timestamp in RFC3339.
+    #[arg(long, default_value = "2262-04-11T23:47:16Z", value_parser = parse_rfc3339)]
+    max_timestamp: i64,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Validate {
+    pub(crate) fn run(self) -> Result<()> {
+        let input = BufReader::new(open_input(&self.input, self.input_compression)?);
+        let mut output = create_output(&self.output, Compression::None)?;
+        let mut validator = Validator::new(self.min_timestamp, self.max_timestamp);
+        validator.validate(input, &mut output)?;
+        writeln!(
+            output,
+            "{} problems in {} lines",
+            validator.problems, validator.lines
+        )
+        .context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)?;
+
+        ensure!(
+            validator.problems == 0,
+            ValidationFailedSnafu {
+                problems: validator.problems
+            }
+        );
+        Ok(())
+    }
+}
+
+/// Validator of lines.
+#[derive(Debug)]
+struct Validator {
+    min_timestamp: i64,
+    max_timestamp: i64,
+    /// Type of each field and the line it first appears, by measurement and field.
+    field_types: HashMap<(String, String), (FieldType, usize)>,
+    lines: usize,
+    problems: usize,
+}
+
+impl Validator {
+    fn new(min_timestamp: i64, max_timestamp: i64) -> Validator {
+        Validator {
+            min_timestamp,
+            max_timestamp,
+            field_types: HashMap::new(),
+            lines: 0,
+            problems: 0,
+        }
+    }
+
+    /// Validate lines in the source and write problems to the dest.
+    fn validate<R: BufRead, W: Write>(&mut self, mut source: R, mut dest: W) -> Result<()> {
+        let mut buffer = Vec::new();
+        let mut problems = Vec::new();
+        while source
+            .read_until(b'\n', &mut buffer)
+            .context(ReadInputSnafu)?
+            > 0
+        {
+            self.lines += 1;
+            match std::str::from_utf8(&buffer) {
+                Ok(text) => {
+                    for line in influxdb_line_protocol::parse_lines(text) {
+                        match line {
+                            Ok(line) => self.check_line(&line, &mut problems),
+                            Err(e) => problems.push(format!("parse error: {e}")),
+                        }
+                    }
+                }
+                Err(e) => problems.push(format!("invalid UTF-8: {e}")),
+            }
+
+            for problem in problems.drain(..) {
+                self.problems += 1;
+                writeln!(dest, "line {}: {problem}", self.lines).context(WriteOutputSnafu)?;
+            }
+            buffer.clear();
+        }
+
+        Ok(())
+    }
+
+    fn check_line(&mut self, line: &ParsedLine<'_>, problems: &mut Vec<String>) {
+        let measurement = line.series.measurement.as_str();
+        let mut keys = HashSet::new();
+        for (key, _) in line.series.tag_set.iter().flatten() {
+            if !keys.insert(key.as_str()) {
+                problems.push(format!("duplicate tag key {key}"));
+            }
+        }
+
+        keys.clear();
+        for (key, value) in &line.field_set {
+            if !keys.insert(key.as_str()) {
+                problems.push(format!("duplicate field key {key}"));
+            }
+
+            let field_type = FieldType::of(value);
+            let (first_type, first_line) = *self
+                .field_types
+                .entry((measurement.to_string(), key.to_string()))
+                .or_insert((field_type, self.lines));
+            if first_type != field_type {
+                problems.push(format!(
+                    "field {key} of measurement {measurement} is {field_type}, but it is {first_type} at line {first_line}"
+                ));
+            }
+        }
+
+        if let Some(ts) = line.timestamp {
+            if ts < self.min_timestamp || ts > self.max_timestamp {
+                problems.push(format!(
+                    "timestamp {ts} ({}) is out of range",
+                    Utc.timestamp_nanos(ts).to_rfc3339()
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let input = b"cpu,host=a usage=1i 1451606400000000000
+cpu,host=a,host=b usage=1.5,usage=2i 1451606400000000000
+cpu usage=
+mem,host=\xff used=1
+
+mem used=1 -1
+";
+        let mut validator = Validator::new(0, i64::MAX);
+        let mut output = Vec::new();
+        validator.validate(&input[..], &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+
+        assert_eq!(6, validator.lines);
+        assert_eq!(6, validator.problems);
+        assert_eq!("line 2: duplicate tag key host", lines[0]);
+        assert_eq!(
+            "line 2: field usage of measurement cpu is float, but it is integer at line 1",
+            lines[1]
+        );
+        assert_eq!("line 2: duplicate field key usage", lines[2]);
+        assert!(lines[3].starts_with("line 3: parse error: "));
+        assert!(lines[4].starts_with("line 4: invalid UTF-8: "));
+        assert_eq!(
+            "line 6: timestamp -1 (1969-12-31T23:59:59.999999999+00:00) is out of range",
+            lines[5]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Write line protocol to InfluxDB.
+
+use crate::error::{RequestStatusSnafu, Result, SendRequestSnafu, WriteOutputSnafu};
+use crate::influx::stats::Stats;
+use crate::influx::DEFAULT_BLOCK_SIZE;
+use crate::io::{open_input, ChunkReader, Compression, TailReader, WatchArgs};
+use crate::progress;
+use crate::rate_limit::RateLimit;
+use clap::{Args, Parser};
+use snafu::ResultExt;
+use std::io::{self, Read, Write as _};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// Write line protocol file to InfluxDB by the HTTP API.
+#[derive(Debug, Parser)]
+pub(crate) struct Write {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    #[command(flatten)]
+    target: WriteTarget,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Number of lines in each request.
+    #[arg(long, default_value_t = 5000)]
+    batch_size: usize,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+    /// With `--watch`, lines are sent as they are appended to the input file or FIFO
+    /// without waiting for a full batch, the file is reopened if it is rotated.
+    #[command(flatten)]
+    watch: WatchArgs,
+    /// Parse the input and print a summary of it without sending any request.
+    #[arg(long, conflicts_with = "watch")]
+    dry_run: bool,
+}
+
+impl Write {
+    pub(crate) fn run(self) -> Result<()> {
+        if self.dry_run {
+            let stats = Stats::collect(open_input(&self.input, self.input_compression)?)?;
+            let requests = stats.points().div_ceil(self.batch_size.max(1) as u64);
+            let mut output = io::stdout().lock();
+            return writeln!(output, "{stats}\nRequests: {requests}").context(WriteOutputSnafu);
+        }
+
+        let mut reader: ChunkReader<Box<dyn Read + Send>> = if self.watch.watch {
+            let path = Path::new(&self.input);
+            ChunkReader::follow(
+                Box::new(TailReader::open(
+                    path,
+                    self.input_compression,
+                    self.watch.interval(),
+                )?),
+                DEFAULT_BLOCK_SIZE,
+            )
+        } else {
+            ChunkReader::new(
+                open_input(&self.input, self.input_compression)?,
+                DEFAULT_BLOCK_SIZE,
+            )
+        };
+        let mut batch = String::new();
+        let mut num_lines = 0;
+
+        while let Some(block) = reader.next_chunk()? {
+            for line in block.split_inclusive('\n') {
+                // Skip empty lines and comments.
+                let trimmed = line.trim();
+                if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                    batch.push_str(trimmed);
+                    batch.push('\n');
+                    num_lines += 1;
+                    if num_lines >= self.batch_size {
+                        self.send(&mut batch, &mut num_lines)?;
+                    }
+                } else {
+                    progress::add_lines_skipped(1);
+                }
+            }
+            // Lines of a watched file are sent as they arrive.
+            if self.watch.watch && num_lines > 0 {
+                self.send(&mut batch, &mut num_lines)?;
+            }
+        }
+        if num_lines > 0 {
+            self.send(&mut batch, &mut num_lines)?;
+        }
+
+        Ok(())
+    }
+
+    /// Send the batch of lines and clear it.
+    fn send(&self, batch: &mut String, num_lines: &mut usize) -> Result<()> {
+        self.rate_limit.acquire(*num_lines, batch.len());
+        self.target.send(batch)?;
+        batch.clear();
+        *num_lines = 0;
+        Ok(())
+    }
+}
+
+/// InfluxDB to write line protocol to.
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub(crate) struct WriteTarget {
+    /// URL of the InfluxDB server, e.g. http://localhost:8086.
+    #[arg(long)]
+    url: String,
+    /// Organization name.
+    #[arg(long)]
+    org: Option<String>,
+    /// Bucket name.
+    #[arg(long)]
+    bucket: String,
+    /// API token.
+    #[arg(long)]
+    token: Option<String>,
+    /// Maximum number of retries of a failed request.
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+    /// Interval in milliseconds before the first retry, doubled after each retry.
+    #[arg(long, default_value_t = 1000)]
+    retry_interval: u64,
+}
+
+impl WriteTarget {
+    /// Returns the target of the bucket with the default retries.
+    pub(crate) fn new(
+        url: String,
+        org: Option<String>,
+        bucket: String,
+        token: Option<String>,
+    ) -> WriteTarget {
+        WriteTarget {
+            url,
+            org,
+            bucket,
+            token,
+            max_retries: 3,
+            retry_interval: 1000,
+        }
+    }
+
+    /// Send the lines to the write endpoint.
+    ///
+    /// Requests that are throttled, failed by server errors or failed to send are
+    /// retried with exponential backoff.
+    pub(crate) fn send(&self, body: &str) -> Result<()> {
+        let url = format!("{}/api/v2/write", self.url.trim_end_matches('/'));
+        let mut request = ureq::post(&url)
+            .query("bucket", &self.bucket)
+            .query("precision", "ns")
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(org) = &self.org {
+            request = request.query("org", org);
+        }
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Token {token}"));
+        }
+
+        let mut interval = Duration::from_millis(self.retry_interval);
+        let mut retries = 0;
+        loop {
+            let retry_after = match request.clone().send_string(body) {
+                Ok(_) => {
+                    progress::add_lines_written(body.matches('\n').count());
+                    return Ok(());
+                }
+                Err(ureq::Error::Status(status, response))
+                    if retries < self.max_retries && (status == 429 || status >= 500) =>
+                {
+                    response
+                        .header("Retry-After")
+                        .and_then(|v| v.parse().ok())
+                        .map(Duration::from_secs)
+                }
+                Err(ureq::Error::Transport(_)) if retries < self.max_retries => None,
+                Err(ureq::Error::Status(status, response)) => {
+                    return RequestStatusSnafu {
+                        url,
+                        status,
+                        body: response.into_string().unwrap_or_default(),
+                    }
+                    .fail();
+                }
+                Err(ureq::Error::Transport(e)) => {
+                    return Err(Box::new(e)).context(SendRequestSnafu { url });
+                }
+            };
+
+            thread::sleep(retry_after.unwrap_or(interval));
+            interval *= 2;
+            retries += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve_statuses;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_write() {
+        let input_file = NamedTempFile::new().unwrap();
+        input_file
+            .as_file()
+            .write_all(b"cpu usage=1 1\n\n# comment\ncpu usage=2 2\ncpu usage=3 3\n")
+            .unwrap();
+        let (url, handle) = serve_statuses(vec![204, 503, 204]);
+
+        let write = Write {
+            input: input_file.path().to_str().unwrap().to_string(),
+            target: WriteTarget {
+                url,
+                org: Some("org".to_string()),
+                bucket: "bucket".to_string(),
+                token: Some("token".to_string()),
+                max_retries: 1,
+                retry_interval: 1,
+            },
+            rate_limit: RateLimit::default(),
+            batch_size: 2,
+            input_compression: Compression::Auto,
+            watch: WatchArgs::default(),
+            dry_run: false,
+        };
+        write.run().unwrap();
+
+        let bodies: Vec<_> = handle
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|(_, body)| String::from_utf8(body).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "cpu usage=1 1\ncpu usage=2 2\n",
+                "cpu usage=3 3\n",
+                "cpu usage=3 3\n"
+            ],
+            bodies
+        );
+    }
+}
@@ -0,0 +1,669 @@
+//! Input and output of the tools.
+
+#[cfg(not(feature = "object-store"))]
+use crate::error::ObjectStoreDisabledSnafu;
+use crate::error::{
+    CreateFileSnafu, GlobSnafu, InvalidGlobSnafu, NoMatchedFileSnafu, OpenFileSnafu,
+    ReadInputSnafu, Result, WriteOutputSnafu,
+};
+use crate::progress;
+use clap::{Args, ValueEnum};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use snafu::{ensure, ResultExt};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::{debug, error};
+
+mod chunk;
+#[cfg(feature = "object-store")]
+mod cloud;
+mod http;
+mod mmap;
+mod watch;
+
+pub(crate) use chunk::ChunkReader;
+pub(crate) use mmap::Mmap;
+pub(crate) use watch::{DirWatcher, TailReader, WatchArgs};
+
+/// Path that refers to stdin for inputs and stdout for outputs.
+pub(crate) const STDIO: &str = "-";
+
+/// Schemes of object store URLs.
+const OBJECT_STORE_SCHEMES: &[&str] = &["s3", "s3a", "gs", "az", "azblob", "abfs", "abfss"];
+
+/// Returns true if the path refers to stdin or stdout.
+pub(crate) fn is_stdio(path: impl AsRef<Path>) -> bool {
+    path.as_ref() == Path::new(STDIO)
+}
+
+/// Returns true if the path is a URL of a remote input or output, instead of a local file.
+pub(crate) fn is_remote(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    object_store_url(path).is_some() || http_url(path).is_some()
+}
+
+/// Returns the URL if the path is an HTTP(S) URL.
+fn http_url(path: &Path) -> Option<&str> {
+    let url = path.to_str()?;
+    (url.starts_with("http://") || url.starts_with("https://")).then_some(url)
+}
+
+/// Returns the URL if the path is an object store URL, e.g. `s3://bucket/key`.
+fn object_store_url(path: &Path) -> Option<&str> {
+    let url = path.to_str()?;
+    let (scheme, _) = url.split_once("://")?;
+    OBJECT_STORE_SCHEMES.contains(&scheme).then_some(url)
+}
+
+/// Decode `%XX` escapes of a component of a URL, returns `None` if an escape is invalid
+/// or the decoded bytes are not UTF-8.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'%' {
+            bytes.push(b);
+            continue;
+        }
+        let hex: Vec<_> = iter.by_ref().take(2).collect();
+        let decoded = std::str::from_utf8(&hex)
+            .ok()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .filter(|_| hex.len() == 2)?;
+        bytes.push(decoded);
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Compression of inputs and outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Compression {
+    /// Detect the compression by the extension of the path.
+    Auto,
+    /// Not compressed.
+    None,
+    /// Gzip (`.gz`).
+    Gzip,
+    /// Zstandard (`.zst`).
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the compression of the path.
+    ///
+    /// The compression is detected by the extension of the path if it is `Auto`.
+    pub(crate) fn of(self, path: impl AsRef<Path>) -> Compression {
+        if self != Compression::Auto {
+            return self;
+        }
+
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Returns the extension of files in this compression, including the dot.
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Compression::Auto | Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// Compression options of commands.
+#[derive(Debug, Clone, Copy, Args)]
+pub(crate) struct CompressionArgs {
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    pub(crate) input_compression: Compression,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    pub(crate) output_compression: Compression,
+}
+
+impl Default for CompressionArgs {
+    fn default() -> CompressionArgs {
+        CompressionArgs {
+            input_compression: Compression::Auto,
+            output_compression: Compression::Auto,
+        }
+    }
+}
+
+/// Open the file to read.
+pub(crate) fn open_file(path: impl AsRef<Path>) -> Result<File> {
+    let path = path.as_ref();
+    File::open(path).context(OpenFileSnafu { path })
+}
+
+/// Create the file to write.
+pub(crate) fn create_file(path: impl AsRef<Path>) -> Result<File> {
+    let path = path.as_ref();
+    File::create(path).context(CreateFileSnafu { path })
+}
+
+/// Open the file to append, the file is created if it doesn't exist.
+pub(crate) fn append_file(path: impl AsRef<Path>) -> Result<File> {
+    let path = path.as_ref();
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(CreateFileSnafu { path })
+}
+
+/// Returns true if the path is a glob pattern, e.g. `data/2023-*/cpu_*.lp`.
+///
+/// An existing path is read literally even if it has glob characters, e.g. `cpu[1].lp`.
+pub(crate) fn is_glob(path: impl AsRef<Path>) -> bool {
+    let path = path.as_ref();
+    path.to_str()
+        .is_some_and(|path| path.contains(['*', '?', '[']))
+        && !path.exists()
+}
+
+/// Returns files matching the glob pattern in sorted order.
+pub(crate) fn expand_glob(pattern: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let pattern = pattern.as_ref().to_string_lossy();
+    let mut paths = Vec::new();
+    for entry in glob::glob(&pattern).context(InvalidGlobSnafu { pattern: &*pattern })? {
+        let path = entry.context(GlobSnafu)?;
+        if path.is_file() {
+            paths.push(path);
+        }
+    }
+    ensure!(!paths.is_empty(), NoMatchedFileSnafu { pattern });
+    paths.sort_unstable();
+    Ok(paths)
+}
+
+/// Open the input to read, `-` is stdin.
+///
+/// The input is decompressed by the compression of the path. If the path is a glob
+/// pattern, matched files are read one by one. Object store and HTTP(S) URLs are streamed.
+pub(crate) fn open_input(
+    path: impl AsRef<Path>,
+    compression: Compression,
+) -> Result<Box<dyn Read + Send>> {
+    open_tracked(path.as_ref(), compression, true)
+}
+
+/// Open the input like `open_input` from the offset of the decompressed input.
+///
+/// Uncompressed local files are seeked to the offset, other inputs are read until the
+/// offset. Returns an error if the input is shorter than the offset.
+pub(crate) fn open_input_at(
+    path: impl AsRef<Path>,
+    compression: Compression,
+    offset: u64,
+) -> Result<Box<dyn Read + Send>> {
+    let path = path.as_ref();
+    let too_short = || {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("{} is shorter than {offset} bytes", path.display()),
+        )
+    };
+    if compression.of(path) == Compression::None
+        && !is_stdio(path)
+        && !is_glob(path)
+        && !is_remote(path)
+    {
+        let mut file = open_file(path)?;
+        let len = file.metadata().context(ReadInputSnafu)?.len();
+        if len < offset {
+            return Err(too_short()).context(ReadInputSnafu);
+        }
+        file.seek(SeekFrom::Start(offset)).context(ReadInputSnafu)?;
+        progress::add_input_size(Some(len - offset));
+        return decompress_tracked(file, Compression::None, true);
+    }
+
+    let mut reader = open_input(path, compression)?;
+    let skipped =
+        io::copy(&mut (&mut reader).take(offset), &mut io::sink()).context(ReadInputSnafu)?;
+    if skipped < offset {
+        return Err(too_short()).context(ReadInputSnafu);
+    }
+    Ok(reader)
+}
+
+/// Open the input like `open_input`, lines read are counted by the progress if
+/// `count_lines` is true.
+fn open_tracked(
+    path: &Path,
+    compression: Compression,
+    count_lines: bool,
+) -> Result<Box<dyn Read + Send>> {
+    if is_glob(path) {
+        let paths = expand_glob(path)?;
+        for path in &paths {
+            progress::add_input_size(path.metadata().ok().map(|m| m.len()));
+        }
+        return Ok(Box::new(MultiFileReader {
+            paths: paths.into(),
+            compression,
+            count_lines,
+            current: None,
+        }));
+    }
+
+    debug!("Reading {}", path.display());
+    let reader: Box<dyn Read + Send> = if let Some(url) = object_store_url(path) {
+        progress::add_input_size(None);
+        open_object(url)?
+    } else if let Some(url) = http_url(path) {
+        let reader = http::open(url)?;
+        progress::add_input_size(reader.len());
+        Box::new(reader)
+    } else if is_stdio(path) {
+        progress::add_input_size(None);
+        Box::new(io::stdin())
+    } else {
+        let file = open_file(path)?;
+        progress::add_input_size(file.metadata().ok().map(|m| m.len()));
+        Box::new(file)
+    };
+
+    decompress_tracked(reader, compression.of(path), count_lines)
+}
+
+/// Reader of files in order.
+///
+/// A newline is read after each file, so the last line of a file is not joined with the
+/// first line of the next file.
+struct MultiFileReader {
+    paths: VecDeque<PathBuf>,
+    compression: Compression,
+    count_lines: bool,
+    current: Option<Box<dyn Read + Send>>,
+}
+
+impl Read for MultiFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let Some(current) = &mut self.current else {
+                let Some(path) = self.paths.pop_front() else {
+                    return Ok(0);
+                };
+                debug!("Reading {}", path.display());
+                let file = File::open(&path).map_err(|e| {
+                    io::Error::new(e.kind(), format!("failed to open {}: {e}", path.display()))
+                })?;
+                let reader = decompress_tracked(file, self.compression.of(&path), self.count_lines)
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                self.current = Some(Box::new(reader.chain(&b"\n"[..])));
+                continue;
+            };
+
+            let n = current.read(buf)?;
+            if n > 0 || buf.is_empty() {
+                return Ok(n);
+            }
+            self.current = None;
+        }
+    }
+}
+
+#[cfg(feature = "object-store")]
+fn open_object(url: &str) -> Result<Box<dyn Read + Send>> {
+    Ok(Box::new(cloud::open(url)?))
+}
+
+#[cfg(not(feature = "object-store"))]
+fn open_object(url: &str) -> Result<Box<dyn Read + Send>> {
+    ObjectStoreDisabledSnafu { url }.fail()
+}
+
+#[cfg(feature = "object-store")]
+fn create_object(url: &str) -> Result<Box<dyn Output + Send>> {
+    Ok(Box::new(cloud::create(url)?))
+}
+
+#[cfg(not(feature = "object-store"))]
+fn create_object(url: &str) -> Result<Box<dyn Output + Send>> {
+    ObjectStoreDisabledSnafu { url }.fail()
+}
+
+/// Decompress the input, bytes read are tracked by the progress.
+fn decompress_tracked<R: Read + Send + 'static>(
+    reader: R,
+    compression: Compression,
+    count_lines: bool,
+) -> Result<Box<dyn Read + Send>> {
+    let reader = decompress(progress::track_bytes(reader), compression)?;
+    if count_lines {
+        Ok(progress::track_lines_read(reader))
+    } else {
+        Ok(reader)
+    }
+}
+
+/// Decompress the input.
+pub(crate) fn decompress<R: Read + Send + 'static>(
+    reader: R,
+    compression: Compression,
+) -> Result<Box<dyn Read + Send>> {
+    let reader: Box<dyn Read + Send> = match compression {
+        Compression::Auto | Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(MultiGzDecoder::new(reader)),
+        Compression::Zstd => Box::new(zstd::Decoder::new(reader).context(ReadInputSnafu)?),
+    };
+    Ok(reader)
+}
+
+/// Create the output to write, `-` is stdout.
+///
+/// The output is compressed by the compression of the path. Outputs should be
+/// finished after writing, which writes the trailer of compressed outputs, errors of
+/// outputs finished when they are dropped are only logged.
+/// Object store URLs are uploaded in parts, the upload is completed when finished.
+pub(crate) fn create_output(
+    path: impl AsRef<Path>,
+    compression: Compression,
+) -> Result<Box<dyn Output + Send>> {
+    create_writer(path.as_ref(), compression).map(progress::track_lines_written)
+}
+
+/// Create the output of a binary format like Parquet, whose lines are not counted.
+pub(crate) fn create_binary_output(path: impl AsRef<Path>) -> Result<Box<dyn Output + Send>> {
+    create_writer(path.as_ref(), Compression::None)
+}
+
+/// Create the output like `create_output`, whose lines are not counted by the progress.
+pub(crate) fn create_writer(
+    path: &Path,
+    compression: Compression,
+) -> Result<Box<dyn Output + Send>> {
+    let writer: Box<dyn Output + Send> = if is_stdio(path) {
+        Box::new(BufWriter::new(io::stdout()))
+    } else if let Some(url) = object_store_url(path) {
+        create_object(url)?
+    } else {
+        Box::new(create_file(path)?)
+    };
+
+    let writer: Box<dyn Output + Send> = match compression.of(path) {
+        Compression::Auto | Compression::None => writer,
+        Compression::Gzip => Box::new(GzEncoder::new(writer, flate2::Compression::default())),
+        Compression::Zstd => Box::new(
+            zstd::Encoder::new(writer, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .context(WriteOutputSnafu)?,
+        ),
+    };
+    Ok(Box::new(FinishOnDrop {
+        inner: writer,
+        path: path.to_path_buf(),
+        finished: false,
+    }))
+}
+
+/// Output that is completed after all data is written.
+pub(crate) trait Output: Write {
+    /// Flush the output and complete it, e.g. write the trailer of a compressed output.
+    /// Nothing should be written after the output is finished.
+    fn finish(&mut self) -> io::Result<()>;
+}
+
+impl<W: Output + ?Sized> Output for Box<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        (**self).finish()
+    }
+}
+
+impl<W: Output> Output for BufWriter<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.get_mut().finish()
+    }
+}
+
+impl Output for File {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl Output for io::Stdout {
+    fn finish(&mut self) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Output> Output for GzEncoder<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.try_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+impl<W: Output> Output for zstd::Encoder<'_, W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.do_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+/// Output that is finished when it's dropped without being finished.
+struct FinishOnDrop<W: Output> {
+    inner: W,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl<W: Output> Write for FinishOnDrop<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Output> Output for FinishOnDrop<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.inner.finish()
+    }
+}
+
+impl<W: Output> Drop for FinishOnDrop<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to finish {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Input that can be read more than once.
+///
+/// Stdin is spooled into a temporary file so it can be read again. Lines are counted
+/// by the progress only once, by the first reader.
+#[derive(Debug)]
+pub(crate) struct ReplayableInput {
+    source: ReplayableSource,
+    compression: Compression,
+    opened: AtomicBool,
+}
+
+#[derive(Debug)]
+enum ReplayableSource {
+    Path(PathBuf),
+    Spool(File),
+}
+
+impl ReplayableInput {
+    pub(crate) fn open(
+        path: impl AsRef<Path>,
+        compression: Compression,
+    ) -> Result<ReplayableInput> {
+        let path = path.as_ref();
+        let compression = compression.of(path);
+        if !is_stdio(path) {
+            return Ok(ReplayableInput {
+                source: ReplayableSource::Path(path.to_path_buf()),
+                compression,
+                opened: AtomicBool::new(false),
+            });
+        }
+
+        // Spool the raw stdin, which is decompressed by each reader.
+        let mut spool = tempfile::tempfile().context(ReadInputSnafu)?;
+        io::copy(&mut io::stdin(), &mut spool).context(ReadInputSnafu)?;
+        Ok(ReplayableInput {
+            source: ReplayableSource::Spool(spool),
+            compression,
+            opened: AtomicBool::new(false),
+        })
+    }
+
+    /// Returns a reader from the start of the input.
+    pub(crate) fn reader(&self) -> Result<Box<dyn Read + Send>> {
+        let count_lines = !self.opened.swap(true, Ordering::Relaxed);
+        match &self.source {
+            ReplayableSource::Path(path) => open_tracked(path, self.compression, count_lines),
+            ReplayableSource::Spool(spool) => {
+                let mut file = spool.try_clone().context(ReadInputSnafu)?;
+                file.seek(SeekFrom::Start(0)).context(ReadInputSnafu)?;
+                progress::add_input_size(file.metadata().ok().map(|m| m.len()));
+                decompress_tracked(file, self.compression, count_lines)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(Some("a b/c".to_string()), percent_decode("a%20b%2Fc"));
+        assert_eq!(None, percent_decode("a%2"));
+        assert_eq!(None, percent_decode("a%zz"));
+        assert_eq!(None, percent_decode("%ff"));
+    }
+
+    #[test]
+    fn test_finish_output() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.lp.gz", "a.lp.zst"] {
+            let path = dir.path().join(name);
+            let mut output = create_output(&path, Compression::Auto).unwrap();
+            output.write_all(b"cpu usage=1 1\n").unwrap();
+            output.finish().unwrap();
+
+            // The output is complete before it's dropped.
+            let mut text = String::new();
+            open_input(&path, Compression::Auto)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            assert_eq!("cpu usage=1 1\n", text);
+            drop(output);
+        }
+    }
+
+    #[test]
+    fn test_replayable_input() {
+        assert!(is_stdio("-"));
+        assert!(!is_stdio("./-"));
+
+        let file = NamedTempFile::new().unwrap();
+        file.as_file().write_all(b"cpu value=1\n").unwrap();
+        let input = ReplayableInput::open(file.path(), Compression::Auto).unwrap();
+        for _ in 0..2 {
+            let mut content = String::new();
+            input
+                .reader()
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!("cpu value=1\n", content);
+        }
+    }
+
+    #[test]
+    fn test_compression() {
+        assert_eq!(Compression::Gzip, Compression::Auto.of("a.lp.gz"));
+        assert_eq!(Compression::Zstd, Compression::Auto.of("a.csv.zst"));
+        assert_eq!(Compression::None, Compression::Auto.of("-"));
+        assert_eq!(Compression::Gzip, Compression::Gzip.of("a.lp"));
+
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.lp", "a.lp.gz", "a.lp.zst"] {
+            let path = dir.path().join(name);
+            {
+                let mut output = create_output(&path, Compression::Auto).unwrap();
+                output.write_all(b"cpu value=1\n").unwrap();
+                output.flush().unwrap();
+            }
+
+            let mut content = String::new();
+            open_input(&path, Compression::Auto)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            assert_eq!("cpu value=1\n", content);
+        }
+
+        let mut raw = Vec::new();
+        open_file(dir.path().join("a.lp.gz"))
+            .unwrap()
+            .read_to_end(&mut raw)
+            .unwrap();
+        assert_eq!([0x1f, 0x8b], raw[..2]);
+    }
+
+    #[test]
+    fn test_glob() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("2023-01")).unwrap();
+        std::fs::create_dir(dir.path().join("2023-02")).unwrap();
+        for (name, content) in [
+            ("2023-02/cpu_1.lp.gz", "cpu value=2\n"),
+            ("2023-01/cpu_1.lp", "cpu value=1"),
+            ("2023-01/mem_1.lp", "mem value=3\n"),
+        ] {
+            let mut output = create_output(dir.path().join(name), Compression::Auto).unwrap();
+            output.write_all(content.as_bytes()).unwrap();
+            output.flush().unwrap();
+        }
+
+        let pattern = dir.path().join("2023-*/cpu_*.lp*");
+        assert!(is_glob(&pattern));
+        let mut content = String::new();
+        open_input(&pattern, Compression::Auto)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!("cpu value=1\ncpu value=2\n\n", content);
+
+        let err = expand_glob(dir.path().join("*.csv")).unwrap_err();
+        assert!(err.to_string().starts_with("No file matches "));
+
+        // Existing paths with glob characters are not patterns.
+        let path = dir.path().join("cpu[1].lp");
+        std::fs::write(&path, "cpu value=4\n").unwrap();
+        assert!(!is_glob(&path));
+        let mut content = String::new();
+        open_input(&path, Compression::Auto)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!("cpu value=4\n", content);
+    }
+}
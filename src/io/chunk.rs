@@ -0,0 +1,159 @@
+//! Reader of blocks of whole lines.
+
+use crate::error::{ReadInputSnafu, Result};
+use snafu::ResultExt;
+use std::io::{self, Read};
+
+/// Reader of text in blocks of about `block_size` bytes, which are split on newline
+/// boundaries so that lines are parsed from slices of a block instead of being read one
+/// by one.
+///
+/// A line longer than the block size is read into a larger block. The last line of the
+/// source may have no newline.
+///
+/// A block is filled until it is full or the end of the source, unless the reader follows
+/// a source that grows, see [`ChunkReader::follow`].
+#[derive(Debug)]
+pub(crate) struct ChunkReader<R> {
+    source: R,
+    block_size: usize,
+    buffer: Vec<u8>,
+    /// Bytes of the last chunk, which are removed before reading the next chunk.
+    consumed: usize,
+    eof: bool,
+    /// Whether a short read ends the block.
+    follow: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    pub(crate) fn new(source: R, block_size: usize) -> ChunkReader<R> {
+        ChunkReader {
+            source,
+            block_size: block_size.max(1),
+            buffer: Vec::new(),
+            consumed: 0,
+            eof: false,
+            follow: false,
+        }
+    }
+
+    /// Like [`ChunkReader::new`], but lines are returned before the block is full if a
+    /// read is short, so that lines of a watched file or a FIFO are not held back until
+    /// more lines arrive.
+    pub(crate) fn follow(source: R, block_size: usize) -> ChunkReader<R> {
+        ChunkReader {
+            follow: true,
+            ..ChunkReader::new(source, block_size)
+        }
+    }
+
+    /// Returns the text of the next chunk of whole lines, or `None` at the end of the
+    /// source. The text must be valid UTF-8.
+    pub(crate) fn next_chunk(&mut self) -> Result<Option<&str>> {
+        self.buffer.drain(..self.consumed);
+        self.consumed = 0;
+
+        let end = loop {
+            let short = self.read()?;
+            if self.eof {
+                break self.buffer.len();
+            }
+            if let Some(i) = self.buffer.iter().rposition(|&b| b == b'\n') {
+                if (self.follow && short) || self.buffer.len() >= self.block_size {
+                    break i + 1;
+                }
+            }
+        };
+        if end == 0 {
+            return Ok(None);
+        }
+
+        self.consumed = end;
+        std::str::from_utf8(&self.buffer[..end])
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            .context(ReadInputSnafu)
+    }
+
+    /// Read the source once into the rest of the block, or another block if the buffer
+    /// has a line longer than the block. Returns true if the read is short.
+    fn read(&mut self) -> Result<bool> {
+        let len = self.buffer.len();
+        let size = match self.block_size.checked_sub(len) {
+            Some(size) if size > 0 => size,
+            _ => self.block_size,
+        };
+        self.buffer.resize(len + size, 0);
+        let n = loop {
+            match self.source.read(&mut self.buffer[len..]) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => {
+                    self.buffer.truncate(len);
+                    return Err(e).context(ReadInputSnafu);
+                }
+            }
+        };
+        self.buffer.truncate(len + n);
+        self.eof = n == 0;
+        Ok(n < size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning at most 4 bytes per read, like a pipe.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(4);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    fn chunks(input: &[u8], block_size: usize) -> Result<Vec<String>> {
+        collect(ChunkReader::new(input, block_size))
+    }
+
+    fn collect<R: Read>(mut reader: ChunkReader<R>) -> Result<Vec<String>> {
+        let mut chunks = Vec::new();
+        while let Some(text) = reader.next_chunk()? {
+            chunks.push(text.to_string());
+        }
+        Ok(chunks)
+    }
+
+    #[test]
+    fn test_chunk_reader() {
+        let input = b"a=1\nb=22\nc=333\nd";
+        assert_eq!(vec!["a=1\nb=22\nc=333\nd"], chunks(input, 1 << 20).unwrap());
+        assert_eq!(vec!["a=1\nb=22\n", "c=333\nd"], chunks(input, 10).unwrap());
+        // Lines longer than the block are read whole.
+        assert_eq!(
+            vec!["a=1\n", "b=22\n", "c=333\n", "d"],
+            chunks(input, 2).unwrap()
+        );
+        assert!(chunks(b"", 10).unwrap().is_empty());
+
+        let err = chunks(b"a=1\nb=\xff\n", 10).unwrap_err();
+        assert_eq!("Failed to read input", err.to_string());
+    }
+
+    #[test]
+    fn test_chunk_reader_short_reads() {
+        let input = b"a=1\nb=22\nc=333\nd";
+        // Short reads fill the block.
+        assert_eq!(
+            vec!["a=1\nb=22\n", "c=333\nd"],
+            collect(ChunkReader::new(Trickle(input), 10)).unwrap()
+        );
+        // Short reads end the block when following the source.
+        assert_eq!(
+            vec!["a=1\n", "b=22\n", "c=333\n", "d"],
+            collect(ChunkReader::follow(Trickle(input), 1 << 20)).unwrap()
+        );
+    }
+}
@@ -0,0 +1,263 @@
+//! Inputs and outputs on object stores like S3, GCS and Azure Blob Storage.
+//!
+//! Requests run on a runtime in a background thread, so readers and writers can be used
+//! in both sync and async contexts.
+
+use crate::error::{CreateRuntimeSnafu, InvalidUrlSnafu, ObjectStoreSnafu, Result};
+use crate::io::Output;
+use futures::StreamExt;
+use object_store::path::Path as ObjectPath;
+use object_store::{ObjectStore, WriteMultipart};
+use snafu::ResultExt;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use tokio::runtime::{Builder, Runtime};
+use tracing::error;
+use url::Url;
+
+/// Size of chunks sent to the uploader.
+const CHUNK_SIZE: usize = 1 << 20;
+/// Maximum number of parts uploaded concurrently.
+const MAX_CONCURRENT_PARTS: usize = 4;
+
+/// Open the object of the URL to read, the object is streamed while reading.
+pub(super) fn open(url: &str) -> Result<ObjectReader> {
+    let (store, path) = parse_url(url)?;
+    ObjectReader::new(store, path, url)
+}
+
+/// Create the object of the URL to write by a multipart upload, which is completed when
+/// the writer is finished.
+pub(super) fn create(url: &str) -> Result<ObjectWriter> {
+    let (store, path) = parse_url(url)?;
+    ObjectWriter::new(store, path, url)
+}
+
+/// Returns the store and the path of the URL, e.g. `s3://bucket/key`.
+///
+/// Options of stores are read from environment variables, e.g. `AWS_ACCESS_KEY_ID`.
+/// `azblob://container/path` is an alias of `az://container/path`.
+fn parse_url(url: &str) -> Result<(Arc<dyn ObjectStore>, ObjectPath)> {
+    let normalized = match url.strip_prefix("azblob://") {
+        Some(rest) => format!("az://{rest}"),
+        None => url.to_string(),
+    };
+    let parsed = Url::parse(&normalized).context(InvalidUrlSnafu { url })?;
+    let options = std::env::vars().map(|(k, v)| (k.to_ascii_lowercase(), v));
+    let (store, path) = object_store::parse_url_opts(&parsed, options)
+        .map_err(Box::new)
+        .context(ObjectStoreSnafu { url })?;
+    Ok((Arc::from(store), path))
+}
+
+fn runtime() -> Result<Runtime> {
+    Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .context(CreateRuntimeSnafu)
+}
+
+/// Reader of an object streamed by a background thread.
+pub(super) struct ObjectReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ObjectReader {
+    fn new(store: Arc<dyn ObjectStore>, path: ObjectPath, url: &str) -> Result<ObjectReader> {
+        let runtime = runtime()?;
+        let (sender, receiver) = sync_channel(16);
+        let url = url.to_string();
+        thread::spawn(move || {
+            let result: object_store::Result<()> = runtime.block_on(async {
+                let mut stream = store.get(&path).await?.into_stream();
+                while let Some(bytes) = stream.next().await {
+                    if sender.send(Ok(bytes?.to_vec())).is_err() {
+                        // The reader is dropped.
+                        break;
+                    }
+                }
+                Ok(())
+            });
+            if let Err(e) = result {
+                let _ = sender.send(Err(io::Error::other(format!("failed to read {url}: {e}"))));
+            }
+        });
+        Ok(ObjectReader {
+            receiver,
+            chunk: Vec::new(),
+            pos: 0,
+        })
+    }
+}
+
+impl Read for ObjectReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.receiver.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                // The object is read to the end.
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Writer of an object uploaded in parts by a background thread.
+pub(super) struct ObjectWriter {
+    url: String,
+    buffer: Vec<u8>,
+    sender: Option<SyncSender<Vec<u8>>>,
+    uploader: Option<JoinHandle<object_store::Result<()>>>,
+}
+
+impl ObjectWriter {
+    fn new(store: Arc<dyn ObjectStore>, path: ObjectPath, url: &str) -> Result<ObjectWriter> {
+        let runtime = runtime()?;
+        let upload = runtime
+            .block_on(store.put_multipart(&path))
+            .map_err(Box::new)
+            .context(ObjectStoreSnafu { url })?;
+        let (sender, receiver) = sync_channel::<Vec<u8>>(MAX_CONCURRENT_PARTS);
+        let uploader = thread::spawn(move || {
+            runtime.block_on(async {
+                let mut upload = WriteMultipart::new(upload);
+                // The channel is closed when the writer is dropped.
+                while let Ok(chunk) = receiver.recv() {
+                    upload.write(&chunk);
+                    if let Err(e) = upload.wait_for_capacity(MAX_CONCURRENT_PARTS).await {
+                        let _ = upload.abort().await;
+                        return Err(e);
+                    }
+                }
+                upload.finish().await.map(|_| ())
+            })
+        });
+        Ok(ObjectWriter {
+            url: url.to_string(),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            sender: Some(sender),
+            uploader: Some(uploader),
+        })
+    }
+
+    /// Send the buffer to the uploader.
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        let sent = self.sender.as_ref().is_some_and(|s| s.send(chunk).is_ok());
+        if sent {
+            return Ok(());
+        }
+        // The uploader stops on errors.
+        let message = match self.complete() {
+            Err(e) => e.to_string(),
+            Ok(()) => "upload is closed".to_string(),
+        };
+        Err(io::Error::other(format!(
+            "failed to write {}: {message}",
+            self.url
+        )))
+    }
+
+    /// Close the channel and wait for the upload to complete.
+    fn complete(&mut self) -> object_store::Result<()> {
+        self.sender = None;
+        match self.uploader.take() {
+            Some(uploader) => uploader.join().unwrap(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for ObjectWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Send buffered data to the uploader, which uploads it when a part is full.
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+impl Output for ObjectWriter {
+    /// Send the remaining data and wait for the upload to complete, errors of the
+    /// uploader are returned.
+    fn finish(&mut self) -> io::Result<()> {
+        self.send_buffer()?;
+        self.complete().map_err(|e| {
+            io::Error::other(format!(
+                "failed to complete the upload of {}: {e}",
+                self.url
+            ))
+        })
+    }
+}
+
+/// Complete the upload if the writer is not finished.
+impl Drop for ObjectWriter {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            error!("Failed to upload {}: {e}", self.url);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object_store::local::LocalFileSystem;
+
+    #[test]
+    fn test_write_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let store: Arc<dyn ObjectStore> =
+            Arc::new(LocalFileSystem::new_with_prefix(dir.path()).unwrap());
+        let url = "file:///data/a.lp";
+        let content: String = (0..100_000)
+            .map(|i| format!("cpu usage={i} {i}\n"))
+            .collect();
+        let path = ObjectPath::from("data/a.lp");
+        let mut writer = ObjectWriter::new(store.clone(), path, url).unwrap();
+        writer.write_all(content.as_bytes()).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(
+            content,
+            std::fs::read_to_string(dir.path().join("data/a.lp")).unwrap()
+        );
+        // Nothing can be written after the upload is completed.
+        writer.write_all(&[b'\n'; CHUNK_SIZE]).unwrap_err();
+        drop(writer);
+
+        let path = ObjectPath::from("data/a.lp");
+        let mut read = String::new();
+        ObjectReader::new(store.clone(), path, url)
+            .unwrap()
+            .read_to_string(&mut read)
+            .unwrap();
+        assert_eq!(content, read);
+
+        let path = ObjectPath::from("data/missing.lp");
+        let mut reader = ObjectReader::new(store, path, url).unwrap();
+        assert!(reader.read_to_end(&mut Vec::new()).is_err());
+        assert!(parse_url("s3://bucket/a.lp").is_ok());
+    }
+}
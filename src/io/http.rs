@@ -0,0 +1,167 @@
+//! Inputs downloaded over HTTP(S).
+
+use crate::error::{RequestStatusSnafu, Result, SendRequestSnafu};
+use snafu::ResultExt;
+use std::io::{self, Read};
+use tracing::warn;
+
+/// Maximum number of times to resume a download.
+const MAX_RESUMES: u32 = 5;
+
+/// Open the URL to read, the body is streamed while reading.
+pub(super) fn open(url: &str) -> Result<HttpReader> {
+    let response = match request(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, response)) => {
+            return RequestStatusSnafu {
+                url,
+                status,
+                body: response.into_string().unwrap_or_default(),
+            }
+            .fail();
+        }
+        Err(ureq::Error::Transport(e)) => {
+            return Err(Box::new(e)).context(SendRequestSnafu { url });
+        }
+    };
+
+    let resumable = response
+        .header("Accept-Ranges")
+        .is_some_and(|v| v.eq_ignore_ascii_case("bytes"));
+    // Weak entity tags can't be used in `If-Range`.
+    let validator = response
+        .header("ETag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| response.header("Last-Modified"))
+        .map(str::to_string);
+    let len = response
+        .header("Content-Length")
+        .and_then(|v| v.parse().ok());
+    Ok(HttpReader {
+        url: url.to_string(),
+        reader: response.into_reader(),
+        len,
+        pos: 0,
+        resumable,
+        validator,
+        resumes: 0,
+    })
+}
+
+fn request(url: &str) -> ureq::Request {
+    // Offsets of range requests are positions in the body as it is sent.
+    ureq::get(url).set("Accept-Encoding", "identity")
+}
+
+/// Reader of a download, which is resumed by range requests if it fails while reading.
+pub(super) struct HttpReader {
+    url: String,
+    reader: Box<dyn Read + Send + Sync>,
+    /// Length of the body if it is known.
+    len: Option<u64>,
+    /// Number of bytes read.
+    pos: u64,
+    /// Whether the server accepts range requests.
+    resumable: bool,
+    /// Entity tag or last modified time to resume only if the resource is not changed.
+    validator: Option<String>,
+    resumes: u32,
+}
+
+impl HttpReader {
+    /// Returns the length of the body if it is known.
+    pub(super) fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Request the rest of the resource from the current position.
+    fn resume(&mut self) -> io::Result<()> {
+        let mut request = request(&self.url).set("Range", &format!("bytes={}-", self.pos));
+        if let Some(validator) = &self.validator {
+            request = request.set("If-Range", validator);
+        }
+        let response = request.call().map_err(io::Error::other)?;
+        if response.status() != 206 {
+            return Err(io::Error::other(format!(
+                "failed to resume {}: the resource is changed or ranges are not supported",
+                self.url
+            )));
+        }
+        self.reader = response.into_reader();
+        Ok(())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.reader.read(buf) {
+                Ok(n) => {
+                    self.pos += n as u64;
+                    return Ok(n);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) if self.resumable && self.resumes < MAX_RESUMES => {
+                    self.resumes += 1;
+                    warn!("Resuming {} from byte {}: {e}", self.url, self.pos);
+                    self.resume()?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_resume() {
+        let body = "cpu usage=1 1\ncpu usage=2 2\n";
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/data.lp", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut ranges = Vec::new();
+            for i in 0..2 {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    if let Some(v) = header.to_lowercase().strip_prefix("range:") {
+                        ranges.push(v.trim().to_string());
+                    }
+                    header.clear();
+                }
+                let stream = reader.get_mut();
+                if i == 0 {
+                    // Close the connection in the middle of the body.
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        &body[..10]
+                    )
+                    .unwrap();
+                } else {
+                    write!(
+                        stream,
+                        "HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len() - 10,
+                        &body[10..]
+                    )
+                    .unwrap();
+                }
+            }
+            ranges
+        });
+
+        let mut content = String::new();
+        open(&url).unwrap().read_to_string(&mut content).unwrap();
+        assert_eq!(body, content);
+        assert_eq!(vec!["bytes=10-"], server.join().unwrap());
+    }
+}
@@ -0,0 +1,77 @@
+//! Read-only memory maps of files.
+
+use crate::error::{InvalidMappedInputSnafu, MapFileSnafu, OpenFileSnafu, Result};
+use crate::io::{is_remote, is_stdio, Compression};
+use crate::progress;
+use snafu::{ensure, ResultExt};
+use std::fs::File;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Read-only memory map of a file.
+///
+/// The file must not be truncated while it is mapped, or reading the map fails with
+/// `SIGBUS`.
+#[derive(Debug)]
+pub(crate) struct Mmap {
+    map: memmap2::Mmap,
+}
+
+impl Mmap {
+    /// Map the uncompressed local file.
+    pub(crate) fn open(path: &Path, compression: Compression) -> Result<Mmap> {
+        let invalid = |reason: &str| InvalidMappedInputSnafu {
+            path,
+            reason: reason.to_string(),
+        };
+        ensure!(
+            !is_stdio(path) && !is_remote(path),
+            invalid("not a local file")
+        );
+        ensure!(
+            compression.of(path) == Compression::None,
+            invalid("compressed files can't be mapped")
+        );
+        let file = File::open(path).context(OpenFileSnafu { path })?;
+        // SAFETY: the map is read-only, the file must not be truncated while it is mapped.
+        let map = unsafe { memmap2::Mmap::map(&file) }.context(MapFileSnafu { path })?;
+        // The advice only affects the performance.
+        #[cfg(unix)]
+        let _ = map.advise(memmap2::Advice::Sequential);
+        progress::add_input_size(Some(map.len() as u64));
+        Ok(Mmap { map })
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_mmap() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"cpu usage=1 1\n").unwrap();
+        let map = Mmap::open(file.path(), Compression::Auto).unwrap();
+        assert_eq!(b"cpu usage=1 1\n", &map[..]);
+
+        let empty = tempfile::NamedTempFile::new().unwrap();
+        assert!(Mmap::open(empty.path(), Compression::Auto)
+            .unwrap()
+            .is_empty());
+
+        let err = Mmap::open(Path::new("cpu.lp.gz"), Compression::Auto).unwrap_err();
+        assert_eq!(
+            "Cannot map cpu.lp.gz: compressed files can't be mapped",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! Watch inputs for data appended to files and new files in directories.
+
+use crate::error::{InvalidWatchInputSnafu, OpenFileSnafu, ReadDirSnafu, Result};
+use crate::io::{is_glob, is_remote, is_stdio, open_file, Compression};
+use clap::Args;
+use snafu::{ensure, ResultExt};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+/// Options to keep reading the input as new data arrives.
+#[derive(Debug, Clone, Copy, Default, Args)]
+pub(crate) struct WatchArgs {
+    /// Keep reading lines appended to the input file, or new files in the input
+    /// directory, until the process is killed.
+    #[arg(long)]
+    pub(crate) watch: bool,
+    /// Interval in milliseconds to check the input for new data with `--watch`.
+    #[arg(long, default_value_t = 1000)]
+    pub(crate) poll_interval: u64,
+}
+
+impl WatchArgs {
+    pub(crate) fn interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval)
+    }
+}
+
+/// Returns an error unless the path is an uncompressed local file or directory.
+fn ensure_local(path: &Path, compression: Compression) -> Result<()> {
+    let invalid = |reason: &str| InvalidWatchInputSnafu {
+        path,
+        reason: reason.to_string(),
+    };
+    ensure!(
+        !is_stdio(path) && !is_glob(path) && !is_remote(path),
+        invalid("not a local file or directory")
+    );
+    ensure!(
+        compression.of(path) == Compression::None,
+        invalid("compressed files can't be watched")
+    );
+    Ok(())
+}
+
+/// Bytes at the start of a tailed file that are compared to detect rewrites, which are
+/// enough to hold the timestamps of the first lines.
+const HEAD_SIZE: usize = 4096;
+
+/// Reader of a file like `tail -F`, which waits for data appended to the file instead of
+/// reaching the end of it.
+///
+/// Only complete lines are returned, a partially written last line is held back until its
+/// newline is written. The file is read again from the start if it is truncated or its
+/// first bytes are rewritten, and the path is reopened if it refers to a new file, e.g.
+/// after log rotation. A FIFO is reopened after all writers close it, which waits for the
+/// next writer.
+#[derive(Debug)]
+pub(crate) struct TailReader {
+    path: PathBuf,
+    file: File,
+    fifo: bool,
+    interval: Duration,
+    /// Bytes read from the current file.
+    position: u64,
+    /// First bytes of the current file, up to `HEAD_SIZE`.
+    head: Vec<u8>,
+    /// Bytes read but not returned, lines before `complete` are returned first.
+    buffer: Vec<u8>,
+    consumed: usize,
+    complete: usize,
+}
+
+impl TailReader {
+    /// Open the uncompressed local file or FIFO to read from the start, opening a FIFO
+    /// waits for a writer.
+    pub(crate) fn open(
+        path: &Path,
+        compression: Compression,
+        interval: Duration,
+    ) -> Result<TailReader> {
+        ensure_local(path, compression)?;
+        let file = open_file(path)?;
+        let metadata = file.metadata().context(OpenFileSnafu { path })?;
+        Ok(TailReader {
+            path: path.to_path_buf(),
+            file,
+            fifo: is_fifo(&metadata),
+            interval,
+            position: 0,
+            head: Vec::new(),
+            buffer: Vec::new(),
+            consumed: 0,
+            complete: 0,
+        })
+    }
+
+    /// Read complete lines into `buf` without waiting, returns `None` if there are no
+    /// new lines.
+    fn read_available(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        loop {
+            if self.consumed < self.complete {
+                let n = buf.len().min(self.complete - self.consumed);
+                buf[..n].copy_from_slice(&self.buffer[self.consumed..self.consumed + n]);
+                self.consumed += n;
+                return Ok(Some(n));
+            }
+            if buf.is_empty() {
+                return Ok(Some(0));
+            }
+            if !self.fifo && self.is_truncated()? {
+                self.restart()?;
+            }
+
+            // Only the partial line is left in the buffer.
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+            self.complete = 0;
+            let len = self.buffer.len();
+            self.buffer.resize(len + buf.len(), 0);
+            let result = self.file.read(&mut self.buffer[len..]);
+            self.buffer
+                .truncate(len + result.as_ref().map_or(0, |n| *n));
+            let n = result?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.position += n as u64;
+            let missing = HEAD_SIZE.saturating_sub(self.head.len()).min(n);
+            self.head
+                .extend_from_slice(&self.buffer[len..len + missing]);
+            self.complete = self
+                .buffer
+                .iter()
+                .rposition(|b| *b == b'\n')
+                .map_or(0, |i| i + 1);
+        }
+    }
+
+    /// Wait for new data after the end of the file is reached.
+    fn wait(&mut self) -> io::Result<()> {
+        if self.fifo {
+            // The unterminated line is the last line of the writer.
+            self.finish_line();
+            debug!("Writers of {} closed, reopening", self.path.display());
+            self.file = File::open(&self.path)?;
+            return Ok(());
+        }
+        thread::sleep(self.interval);
+        self.reopen_if_rotated()
+    }
+
+    /// Returns true if the file is shorter than the bytes read, or its first bytes are
+    /// different from those read, e.g. it is truncated and written again.
+    fn is_truncated(&mut self) -> io::Result<bool> {
+        if self.file.metadata()?.len() < self.position {
+            return Ok(true);
+        }
+        if self.head.is_empty() {
+            return Ok(false);
+        }
+        let mut head = vec![0; self.head.len()];
+        self.file.seek(SeekFrom::Start(0))?;
+        let result = self.file.read_exact(&mut head);
+        self.file.seek(SeekFrom::Start(self.position))?;
+        match result {
+            Ok(()) => Ok(head != self.head),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(true),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Read the truncated file again from the start.
+    fn restart(&mut self) -> io::Result<()> {
+        // The rest of the partial line is lost with the truncated data.
+        if !self.buffer.is_empty() {
+            warn!(
+                "Dropped partial line of {} bytes in {}",
+                self.buffer.len(),
+                self.path.display()
+            );
+            self.buffer.clear();
+        }
+        info!(
+            "{} is truncated, reading from the start",
+            self.path.display()
+        );
+        self.file.seek(SeekFrom::Start(0))?;
+        self.position = 0;
+        self.head.clear();
+        Ok(())
+    }
+
+    /// Open the path again if it refers to a new file and the current file has no new
+    /// data.
+    fn reopen_if_rotated(&mut self) -> io::Result<()> {
+        // The renamed file is read until it has no new data for an interval, as the writer
+        // may append lines to it before reopening the path. Waits while the path is missing.
+        let current = self.file.metadata()?;
+        if current.len() > self.position {
+            return Ok(());
+        }
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return Ok(());
+        };
+        if same_file(&current, &metadata) {
+            return Ok(());
+        }
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        self.finish_line();
+        info!("{} is rotated, reading the new file", self.path.display());
+        self.file = file;
+        self.position = 0;
+        self.head.clear();
+        Ok(())
+    }
+
+    /// Terminate the partial line at the end of the current file by a newline, so it is
+    /// not joined with the first line of the next file.
+    fn finish_line(&mut self) {
+        if !self.buffer.is_empty() {
+            self.buffer.push(b'\n');
+            self.complete = self.buffer.len();
+        }
+    }
+}
+
+impl Read for TailReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(n) = self.read_available(buf)? {
+                return Ok(n);
+            }
+            self.wait()?;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(metadata: &Metadata) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    metadata.file_type().is_fifo()
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Returns true if both are metadata of the same file, by the device and inode.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Returns true if both are metadata of the same file, by the creation time if the
+/// platform has it.
+#[cfg(not(unix))]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    match (a.created(), b.created()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => true,
+    }
+}
+
+/// Watcher of new files in a directory.
+///
+/// A new file is ready once its size is unchanged for an interval, so files still being
+/// written are not read early. Hidden files, e.g. temporary files of copies, are skipped.
+#[derive(Debug)]
+pub(crate) struct DirWatcher {
+    dir: PathBuf,
+    interval: Duration,
+    /// Sizes of new files at the last poll.
+    pending: HashMap<PathBuf, u64>,
+    /// Files returned before.
+    seen: HashSet<PathBuf>,
+}
+
+impl DirWatcher {
+    /// Watch the local directory, files already in it are also new files.
+    pub(crate) fn new(dir: &Path, interval: Duration) -> Result<DirWatcher> {
+        ensure_local(dir, Compression::None)?;
+        let metadata = fs::metadata(dir).context(OpenFileSnafu { path: dir })?;
+        ensure!(
+            metadata.is_dir(),
+            InvalidWatchInputSnafu {
+                path: dir,
+                reason: "not a directory",
+            }
+        );
+        Ok(DirWatcher {
+            dir: dir.to_path_buf(),
+            interval,
+            pending: HashMap::new(),
+            seen: HashSet::new(),
+        })
+    }
+
+    /// Wait until there are new files that are ready, returns them in sorted order.
+    pub(crate) fn next_files(&mut self) -> Result<Vec<PathBuf>> {
+        loop {
+            let files = self.poll()?;
+            if !files.is_empty() {
+                return Ok(files);
+            }
+            thread::sleep(self.interval);
+        }
+    }
+
+    /// Returns new files whose sizes are unchanged since the last poll, without waiting.
+    pub(crate) fn poll(&mut self) -> Result<Vec<PathBuf>> {
+        let dir = &self.dir;
+        let mut ready = Vec::new();
+        for entry in dir.read_dir().context(ReadDirSnafu { path: dir })? {
+            let entry = entry.context(ReadDirSnafu { path: dir })?;
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden || self.seen.contains(&path) {
+                continue;
+            }
+            // The file may be removed or replaced after it is listed.
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            if self.pending.insert(path.clone(), metadata.len()) == Some(metadata.len()) {
+                self.pending.remove(&path);
+                self.seen.insert(path.clone());
+                ready.push(path);
+            }
+        }
+        ready.sort_unstable();
+        Ok(ready)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// Returns the lines read without waiting.
+    fn available(reader: &mut TailReader) -> String {
+        let mut buf = [0; 1024];
+        let n = reader.read_available(&mut buf).unwrap().unwrap_or(0);
+        String::from_utf8(buf[..n].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_tail_reader() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"cpu usage=1 1\ncpu usa").unwrap();
+        let interval = Duration::from_millis(10);
+        let mut reader = TailReader::open(file.path(), Compression::Auto, interval).unwrap();
+        assert_eq!("cpu usage=1 1\n", available(&mut reader));
+
+        // The partial line is returned after it is complete.
+        assert_eq!("", available(&mut reader));
+        file.write_all(b"ge=2 2\n").unwrap();
+        assert_eq!("cpu usage=2 2\n", available(&mut reader));
+
+        let err =
+            TailReader::open(Path::new("cpu.lp.gz"), Compression::Auto, interval).unwrap_err();
+        assert_eq!(
+            "Cannot watch cpu.lp.gz: compressed files can't be watched",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_tail_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.lp");
+        fs::write(&path, "cpu v=1 1\ncpu v=2 2").unwrap();
+        let interval = Duration::from_millis(10);
+        let mut reader = TailReader::open(&path, Compression::Auto, interval).unwrap();
+        assert_eq!("cpu v=1 1\n", available(&mut reader));
+
+        // The partial last line of the rotated file is terminated.
+        fs::rename(&path, dir.path().join("metrics.lp.1")).unwrap();
+        fs::write(&path, "cpu v=3 3\ncpu v=").unwrap();
+        assert_eq!("", available(&mut reader));
+        reader.reopen_if_rotated().unwrap();
+        assert_eq!("cpu v=2 2\n", available(&mut reader));
+        assert_eq!("cpu v=3 3\n", available(&mut reader));
+
+        // The partial line is dropped if the file is rewritten past the bytes read, or
+        // truncated.
+        fs::write(&path, "cpu v=4 4\ncpu v=5 5\n").unwrap();
+        assert_eq!("cpu v=4 4\ncpu v=5 5\n", available(&mut reader));
+        fs::write(&path, "cpu v=6 6\n").unwrap();
+        assert_eq!("cpu v=6 6\n", available(&mut reader));
+
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"cpu v=7 7\n").unwrap();
+        reader.reopen_if_rotated().unwrap();
+        assert_eq!("cpu v=7 7\n", available(&mut reader));
+    }
+
+    #[test]
+    fn test_dir_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("b.csv"), "a,b\n").unwrap();
+        fs::write(dir.path().join(".a.csv.tmp"), "a,b\n").unwrap();
+        let mut watcher = DirWatcher::new(dir.path(), Duration::from_millis(10)).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+
+        fs::write(dir.path().join("a.csv"), "a,b\n").unwrap();
+        assert_eq!(vec![dir.path().join("b.csv")], watcher.poll().unwrap());
+        // Growing files are not ready.
+        fs::write(dir.path().join("a.csv"), "a,b\n1,2\n").unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        assert_eq!(vec![dir.path().join("a.csv")], watcher.poll().unwrap());
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+}
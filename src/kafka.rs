@@ -0,0 +1,311 @@
+//! Tools for Kafka.
+
+use crate::error::{CreateRuntimeSnafu, KafkaSnafu, ReadInputSnafu, Result, WriteOutputSnafu};
+use crate::io::{create_output, open_input, Compression, Output};
+use crate::progress;
+use crate::rate_limit::RateLimit;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use rskafka::client::partition::{self, OffsetAt, PartitionClient, UnknownTopicHandling};
+use rskafka::client::ClientBuilder;
+use rskafka::record::{Record, RecordAndOffset};
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use tokio::runtime::Runtime;
+
+/// Maximum bytes of records in a fetch.
+const MAX_FETCH_BYTES: i32 = 16 << 20;
+/// Maximum time in milliseconds a fetch waits for new records.
+const MAX_FETCH_WAIT_MS: i32 = 1000;
+
+/// Kafka command.
+#[derive(Debug, Parser)]
+pub struct KafkaCommand {
+    #[clap(subcommand)]
+    subcmd: KafkaSubcommand,
+}
+
+impl KafkaCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            KafkaSubcommand::Produce(c) => c.run(),
+            KafkaSubcommand::Consume(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Kafka.
+#[derive(Debug, Parser)]
+enum KafkaSubcommand {
+    /// Publish line protocol to a topic.
+    Produce(Produce),
+    /// Read line protocol messages of a topic.
+    Consume(Consume),
+}
+
+/// Compression of messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum MessageCompression {
+    None,
+    Gzip,
+    Lz4,
+    Snappy,
+    Zstd,
+}
+
+impl From<MessageCompression> for partition::Compression {
+    fn from(compression: MessageCompression) -> partition::Compression {
+        match compression {
+            MessageCompression::None => partition::Compression::NoCompression,
+            MessageCompression::Gzip => partition::Compression::Gzip,
+            MessageCompression::Lz4 => partition::Compression::Lz4,
+            MessageCompression::Snappy => partition::Compression::Snappy,
+            MessageCompression::Zstd => partition::Compression::Zstd,
+        }
+    }
+}
+
+/// Where to start consuming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StartOffset {
+    /// The earliest message that is kept.
+    Earliest,
+    /// Messages produced after the start.
+    Latest,
+}
+
+/// Publish line protocol file to a Kafka topic, each message is a batch of lines.
+#[derive(Debug, Parser)]
+struct Produce {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Bootstrap brokers, e.g. `localhost:9092`.
+    #[arg(long, value_delimiter = ',', required = true)]
+    brokers: Vec<String>,
+    /// Topic to publish to.
+    #[arg(long)]
+    topic: String,
+    /// Partition of the topic.
+    #[arg(long, default_value_t = 0)]
+    partition: i32,
+    /// Number of lines in each message.
+    #[arg(long, default_value_t = 1000)]
+    batch_size: usize,
+    /// Compression of messages.
+    #[arg(long, value_enum, default_value_t = MessageCompression::None)]
+    compression: MessageCompression,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl Produce {
+    fn run(self) -> Result<()> {
+        let producer =
+            Producer::connect(&self.brokers, &self.topic, self.partition, self.compression)?;
+
+        let mut reader = BufReader::new(open_input(&self.input, self.input_compression)?);
+        let mut line = String::new();
+        let mut batch = String::new();
+        let mut num_lines = 0;
+        while reader.read_line(&mut line).context(ReadInputSnafu)? > 0 {
+            // Skip empty lines and comments.
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                batch.push_str(trimmed);
+                batch.push('\n');
+                num_lines += 1;
+                if num_lines >= self.batch_size {
+                    self.rate_limit.acquire(num_lines, batch.len());
+                    producer.produce(std::mem::take(&mut batch), num_lines)?;
+                    num_lines = 0;
+                }
+            } else {
+                progress::add_lines_skipped(1);
+            }
+            line.clear();
+        }
+        if num_lines > 0 {
+            self.rate_limit.acquire(num_lines, batch.len());
+            producer.produce(batch, num_lines)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Producer of line protocol messages to a partition of a topic.
+pub(crate) struct Producer {
+    runtime: Runtime,
+    client: PartitionClient,
+    compression: MessageCompression,
+}
+
+impl Producer {
+    /// Connect to the partition of the topic.
+    pub(crate) fn connect(
+        brokers: &[String],
+        topic: &str,
+        partition: i32,
+        compression: MessageCompression,
+    ) -> Result<Producer> {
+        let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+        let client = runtime.block_on(connect(brokers, topic, partition))?;
+        Ok(Producer {
+            runtime,
+            client,
+            compression,
+        })
+    }
+
+    /// Publish the lines as a message.
+    pub(crate) fn produce(&self, lines: String, num_lines: usize) -> Result<()> {
+        let record = Record {
+            key: None,
+            value: Some(lines.into_bytes()),
+            headers: BTreeMap::new(),
+            timestamp: Utc::now(),
+        };
+        self.runtime
+            .block_on(self.client.produce(vec![record], self.compression.into()))
+            .map_err(Box::new)
+            .context(KafkaSnafu)?;
+        progress::add_lines_written(num_lines);
+        Ok(())
+    }
+}
+
+/// Read line protocol messages of a Kafka topic to a file.
+///
+/// Messages between the start and the latest offset at the start are read, or messages
+/// are read until the process is killed with `--follow`.
+#[derive(Debug, Parser)]
+struct Consume {
+    /// Bootstrap brokers, e.g. `localhost:9092`.
+    #[arg(long, value_delimiter = ',', required = true)]
+    brokers: Vec<String>,
+    /// Topic to read from.
+    #[arg(long)]
+    topic: String,
+    /// Partition of the topic.
+    #[arg(long, default_value_t = 0)]
+    partition: i32,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Where to start reading.
+    #[arg(long, value_enum, default_value_t = StartOffset::Earliest, conflicts_with = "offset")]
+    start: StartOffset,
+    /// Offset of the first message to read.
+    #[arg(long)]
+    offset: Option<i64>,
+    /// Keep waiting for new messages.
+    #[arg(long)]
+    follow: bool,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl Consume {
+    fn run(self) -> Result<()> {
+        let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+        let mut output = create_output(&self.output, self.output_compression)?;
+        runtime.block_on(async {
+            let client = connect(&self.brokers, &self.topic, self.partition).await?;
+            let mut offset = match (self.offset, self.start) {
+                (Some(offset), _) => offset,
+                (None, StartOffset::Earliest) => get_offset(&client, OffsetAt::Earliest).await?,
+                (None, StartOffset::Latest) => get_offset(&client, OffsetAt::Latest).await?,
+            };
+            let end = get_offset(&client, OffsetAt::Latest).await?;
+
+            while self.follow || offset < end {
+                let (records, _) = client
+                    .fetch_records(offset, 1..MAX_FETCH_BYTES, MAX_FETCH_WAIT_MS)
+                    .await
+                    .map_err(Box::new)
+                    .context(KafkaSnafu)?;
+                if let Some(last) = records.last() {
+                    offset = last.offset + 1;
+                }
+                write_records(&records, &mut output)?;
+            }
+            Ok(())
+        })?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Connect to the partition of the topic.
+async fn connect(brokers: &[String], topic: &str, partition: i32) -> Result<PartitionClient> {
+    let client = ClientBuilder::new(brokers.to_vec())
+        .build()
+        .await
+        .map_err(Box::new)
+        .context(KafkaSnafu)?;
+    client
+        .partition_client(topic, partition, UnknownTopicHandling::Error)
+        .await
+        .map_err(Box::new)
+        .context(KafkaSnafu)
+}
+
+async fn get_offset(client: &PartitionClient, at: OffsetAt) -> Result<i64> {
+    client
+        .get_offset(at)
+        .await
+        .map_err(Box::new)
+        .context(KafkaSnafu)
+}
+
+/// Write values of records with a trailing newline if it is missing, then flush the output.
+fn write_records<W: Write>(records: &[RecordAndOffset], output: &mut W) -> Result<()> {
+    for value in records.iter().filter_map(|r| r.record.value.as_deref()) {
+        if value.is_empty() {
+            continue;
+        }
+        output.write_all(value).context(WriteOutputSnafu)?;
+        if !value.ends_with(b"\n") {
+            output.write_all(b"\n").context(WriteOutputSnafu)?;
+        }
+    }
+    output.flush().context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_records() {
+        let records: Vec<_> = [
+            Some("cpu usage=1 1\ncpu usage=2 2\n"),
+            None,
+            Some("mem used=3 3"),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(offset, value)| RecordAndOffset {
+            record: Record {
+                key: None,
+                value: value.map(|v| v.as_bytes().to_vec()),
+                headers: BTreeMap::new(),
+                timestamp: Utc::now(),
+            },
+            offset: offset as i64,
+        })
+        .collect();
+        let mut output = Vec::new();
+        write_records(&records, &mut output).unwrap();
+        assert_eq!(
+            "cpu usage=1 1\ncpu usage=2 2\nmem used=3 3\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
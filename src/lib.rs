@@ -1,3 +1,35 @@
 //! Tools for Time Series Databases (TSDB)
 
+pub mod clickhouse;
+pub mod config;
+pub mod convert;
+pub mod csv_tools;
+pub mod datadog;
+mod decode;
+mod error;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod generate;
+pub mod graphite;
+#[cfg(feature = "greptime")]
+pub mod greptime;
 pub mod influx;
+mod io;
+#[cfg(feature = "kafka")]
+pub mod kafka;
+pub mod opentsdb;
+pub mod otel;
+mod pipeline;
+pub mod progress;
+pub mod prom;
+pub mod questdb;
+mod rate_limit;
+pub mod reject;
+mod server;
+#[cfg(feature = "tokio")]
+pub mod sink;
+pub mod timescale;
+pub mod vm;
+pub mod wavefront;
+
+pub use error::{Error, Result};
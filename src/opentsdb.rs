@@ -0,0 +1,443 @@
+//! Tools for OpenTSDB target.
+
+use crate::error::{
+    InvalidPointSnafu, LineSnafu, ParseJsonSnafu, ReadInputSnafu, Result, WriteJsonSnafu,
+    WriteOutputSnafu,
+};
+use crate::influx::timestamp::Precision;
+use crate::influx::{push_fields, push_series, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::reject;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use serde::{Deserialize, Serialize};
+use serde_json::Number;
+use snafu::{OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// OpenTSDB command.
+#[derive(Debug, Parser)]
+pub struct OpenTsdbCommand {
+    #[clap(subcommand)]
+    subcmd: OpenTsdbSubcommand,
+}
+
+impl OpenTsdbCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            OpenTsdbSubcommand::FromLp(c) => c.run(),
+            OpenTsdbSubcommand::ToLp(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for OpenTSDB.
+#[derive(Debug, Parser)]
+enum OpenTsdbSubcommand {
+    /// Line protocol to OpenTSDB data points.
+    FromLp(FromLp),
+    /// OpenTSDB data points to line protocol.
+    ToLp(ToLp),
+}
+
+/// Format of OpenTSDB data points.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    /// Telnet `put <metric> <timestamp> <value> <tagk=tagv>...` lines.
+    Telnet,
+    /// JSON array of data points of the `/api/put` HTTP API.
+    Json,
+}
+
+/// Precision of OpenTSDB timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimestampPrecision {
+    /// Second.
+    S,
+    /// Millisecond.
+    Ms,
+}
+
+/// A data point of OpenTSDB.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct DataPoint {
+    metric: String,
+    timestamp: i64,
+    value: Number,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+}
+
+impl DataPoint {
+    /// Parse a telnet `put` line.
+    fn parse_telnet(text: &str) -> Result<DataPoint> {
+        let mut parts = text.split_ascii_whitespace();
+        let invalid = || InvalidPointSnafu {
+            reason: format!("invalid put line {text:?}"),
+        };
+        if parts.next() != Some("put") {
+            return invalid().fail();
+        }
+        let metric = parts.next().with_context(invalid)?;
+        let timestamp = parts
+            .next()
+            .and_then(|ts| ts.parse().ok())
+            .with_context(invalid)?;
+        let value = parts
+            .next()
+            .and_then(|v| v.parse::<Number>().ok())
+            .with_context(invalid)?;
+        let tags = parts
+            .map(|pair| {
+                pair.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .with_context(invalid)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(DataPoint {
+            metric: metric.to_string(),
+            timestamp,
+            value,
+            tags,
+        })
+    }
+
+    /// Push the data point as a telnet `put` line.
+    fn push_telnet(&self, line: &mut String) {
+        write!(
+            line,
+            "put {} {} {}",
+            self.metric, self.timestamp, self.value
+        )
+        .unwrap();
+        for (k, v) in &self.tags {
+            write!(line, " {k}={v}").unwrap();
+        }
+        line.push('\n');
+    }
+
+    /// Push the data point as a line of line protocol.
+    ///
+    /// The metric is the measurement and the value is the `value` field, or the metric is
+    /// split at the last `.` into the measurement and the field if `split_metric` is set.
+    /// Timestamps are in second or millisecond, detected by the magnitude.
+    fn push_line(&self, line: &mut String, split_metric: bool) -> Result<()> {
+        let (measurement, field) = match self.metric.rsplit_once('.') {
+            Some(split) if split_metric => split,
+            _ => (self.metric.as_str(), "value"),
+        };
+        let value = self.value.as_f64().unwrap_or_default();
+        let timestamp =
+            Precision::Auto
+                .to_nanos(self.timestamp)
+                .with_context(|| InvalidPointSnafu {
+                    reason: format!("timestamp {} is out of range", self.timestamp),
+                })?;
+
+        push_series(
+            line,
+            measurement,
+            self.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+        );
+        push_fields(line, [(field, &FieldValue::F64(value))]);
+        writeln!(line, " {timestamp}").unwrap();
+        Ok(())
+    }
+}
+
+/// Returns data points of numeric fields in the line, named `<measurement>.<field>`.
+///
+/// Booleans are converted to 0 and 1, string fields are skipped. Characters not allowed
+/// by OpenTSDB in names and tag values are replaced by `_`.
+fn line_data_points(
+    line: &ParsedLine<'_>,
+    precision: TimestampPrecision,
+    now: i64,
+) -> Vec<DataPoint> {
+    let timestamp = line.timestamp.unwrap_or(now);
+    let timestamp = match precision {
+        TimestampPrecision::S => timestamp.div_euclid(1_000_000_000),
+        TimestampPrecision::Ms => timestamp.div_euclid(1_000_000),
+    };
+    let tags: BTreeMap<_, _> = line
+        .series
+        .tag_set
+        .iter()
+        .flatten()
+        .map(|(k, v)| (sanitize(k), sanitize(v)))
+        .collect();
+
+    line.field_set
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                FieldValue::I64(v) => Number::from(*v),
+                FieldValue::U64(v) => Number::from(*v),
+                FieldValue::F64(v) => Number::from_f64(*v)?,
+                FieldValue::Boolean(v) => Number::from(u8::from(*v)),
+                FieldValue::String(_) => return None,
+            };
+            Some(DataPoint {
+                metric: sanitize(&format!("{}.{}", line.series.measurement, key)),
+                timestamp,
+                value,
+                tags: tags.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Returns a valid name or tag value, invalid characters are replaced by `_`.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Convert line protocol file to OpenTSDB data points.
+///
+/// Each numeric field is a data point of the metric `<measurement>.<field>`, tags are
+/// kept. Lines without timestamp use the current time.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = Format::Telnet)]
+    format: Format,
+    /// Precision of the output timestamps.
+    #[arg(long, value_enum, default_value_t = TimestampPrecision::Ms)]
+    precision: TimestampPrecision,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = line_protocol_to_opentsdb(input, output, self.format, self.precision)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert OpenTSDB data points to line protocol file.
+#[derive(Debug, Parser)]
+struct ToLp {
+    /// Input file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format of the input.
+    #[arg(long, value_enum, default_value_t = Format::Telnet)]
+    format: Format,
+    /// Split metrics at the last `.` into the measurement and the field, e.g. `cpu.usage`
+    /// to the `usage` field of `cpu`, otherwise the field is `value`.
+    #[arg(long)]
+    split_metric: bool,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = opentsdb_to_line_protocol(input, output, self.format, self.split_metric)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn line_protocol_to_opentsdb<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    format: Format,
+    precision: TimestampPrecision,
+) -> Result<W> {
+    let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    let mut first = true;
+    if format == Format::Json {
+        writer.write_all(b"[").context(WriteOutputSnafu)?;
+    }
+    read_lines(source, |parsed| {
+        for point in line_data_points(&parsed, precision, now) {
+            match format {
+                Format::Telnet => {
+                    line.clear();
+                    point.push_telnet(&mut line);
+                    writer
+                        .write_all(line.as_bytes())
+                        .context(WriteOutputSnafu)?;
+                }
+                Format::Json => {
+                    if !first {
+                        writer.write_all(b",\n").context(WriteOutputSnafu)?;
+                    }
+                    serde_json::to_writer(&mut writer, &point).context(WriteJsonSnafu)?;
+                }
+            }
+            first = false;
+        }
+        Ok(())
+    })?;
+    if format == Format::Json {
+        writer.write_all(b"]\n").context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Data points of the `/api/put` body.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DataPoints {
+    One(DataPoint),
+    Many(Vec<DataPoint>),
+}
+
+fn opentsdb_to_line_protocol<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    format: Format,
+    split_metric: bool,
+) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    match format {
+        Format::Telnet => {
+            let reader = BufReader::new(source);
+            for (i, text) in reader.lines().enumerate() {
+                let text = text.context(ReadInputSnafu)?;
+                if text.trim().is_empty() {
+                    continue;
+                }
+                line.clear();
+                let result = DataPoint::parse_telnet(&text)
+                    .and_then(|point| point.push_line(&mut line, split_metric))
+                    .context(LineSnafu { line: i + 1 });
+                if reject::check(result, || &text)?.is_none() {
+                    continue;
+                }
+                writer
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+        Format::Json => {
+            let points = match serde_json::from_reader(source).context(ParseJsonSnafu)? {
+                DataPoints::One(point) => vec![point],
+                DataPoints::Many(points) => points,
+            };
+            for point in points {
+                line.clear();
+                point.push_line(&mut line, split_metric)?;
+                writer
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+            }
+        }
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_protocol_to_opentsdb() {
+        let input =
+            "cpu,host=a,dc=eu\\ 1 usage=1.5,count=2i,ok=true,note=\"x\" 1451606400123456789\n";
+        let output = line_protocol_to_opentsdb(
+            input.as_bytes(),
+            Vec::new(),
+            Format::Telnet,
+            TimestampPrecision::Ms,
+        )
+        .unwrap();
+        assert_eq!(
+            "put cpu.usage 1451606400123 1.5 dc=eu_1 host=a
+put cpu.count 1451606400123 2 dc=eu_1 host=a
+put cpu.ok 1451606400123 1 dc=eu_1 host=a
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let output = line_protocol_to_opentsdb(
+            input.as_bytes(),
+            Vec::new(),
+            Format::Json,
+            TimestampPrecision::S,
+        )
+        .unwrap();
+        let json = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"[{"metric":"cpu.usage","timestamp":1451606400,"value":1.5,"tags":{"dc":"eu_1","host":"a"}},
+{"metric":"cpu.count","timestamp":1451606400,"value":2,"tags":{"dc":"eu_1","host":"a"}},
+{"metric":"cpu.ok","timestamp":1451606400,"value":1,"tags":{"dc":"eu_1","host":"a"}}]
+"#,
+            json
+        );
+
+        let output =
+            opentsdb_to_line_protocol(json.as_bytes(), Vec::new(), Format::Json, true).unwrap();
+        assert_eq!(
+            "cpu,dc=eu_1,host=a usage=1.5 1451606400000000000
+cpu,dc=eu_1,host=a count=2 1451606400000000000
+cpu,dc=eu_1,host=a ok=1 1451606400000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_opentsdb_to_line_protocol() {
+        let input = "put sys.cpu.user 1356998400 42.5 host=webserver01 cpu=0
+
+put sys.cpu.user 1356998400500 7 host=webserver01
+";
+        let output =
+            opentsdb_to_line_protocol(input.as_bytes(), Vec::new(), Format::Telnet, false).unwrap();
+        assert_eq!(
+            "sys.cpu.user,cpu=0,host=webserver01 value=42.5 1356998400000000000
+sys.cpu.user,host=webserver01 value=7 1356998400500000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let err = opentsdb_to_line_protocol(
+            "put sys.cpu.user 1356998400 x\n".as_bytes(),
+            Vec::new(),
+            Format::Telnet,
+            false,
+        )
+        .unwrap_err();
+        assert_eq!("Error at line 1", err.to_string());
+    }
+}
@@ -0,0 +1,389 @@
+//! Tools for OpenTelemetry target.
+
+#[cfg(feature = "otel-grpc")]
+pub(crate) mod grpc;
+pub(crate) mod proto;
+mod receive;
+
+#[cfg(not(feature = "otel-grpc"))]
+use crate::error::GrpcDisabledSnafu;
+use crate::error::{
+    InvalidPointSnafu, ReadInputSnafu, RequestStatusSnafu, Result, SendRequestSnafu,
+    WriteOutputSnafu,
+};
+use crate::influx::read_lines;
+use crate::io::{create_binary_output, open_input, Compression, Output};
+use crate::progress;
+use crate::rate_limit::RateLimit;
+use chrono::Utc;
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use prost::Message;
+use proto::{
+    any_value, metric, number_data_point, AnyValue, ExportMetricsServiceRequest,
+    ExportMetricsServiceResponse, Gauge, InstrumentationScope, KeyValue, Metric, NumberDataPoint,
+    Resource, ResourceMetrics, ScopeMetrics,
+};
+use receive::Receive;
+use snafu::{OptionExt, ResultExt};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use tracing::warn;
+
+/// OpenTelemetry command.
+#[derive(Debug, Parser)]
+pub struct OtelCommand {
+    #[clap(subcommand)]
+    subcmd: OtelSubcommand,
+}
+
+impl OtelCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            OtelSubcommand::FromLp(c) => c.run(),
+            OtelSubcommand::Receive(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for OpenTelemetry.
+#[derive(Debug, Parser)]
+enum OtelSubcommand {
+    /// Line protocol to OTLP metrics.
+    FromLp(FromLp),
+    /// Receive OTLP metrics to line protocol or CSV.
+    Receive(Receive),
+}
+
+/// Name of the instrumentation scope of metrics.
+const SCOPE_NAME: &str = "tsdb-tools";
+
+/// Protocol of OTLP.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Protocol {
+    /// Protobuf over HTTP, e.g. `http://localhost:4318/v1/metrics`.
+    Http,
+    /// gRPC, e.g. `http://localhost:4317`, requires the `otel-grpc` feature.
+    Grpc,
+}
+
+/// Convert line protocol file to an OTLP `ExportMetricsServiceRequest` file, or push it to
+/// a collector.
+///
+/// Each field is a gauge named `{measurement}_{field}`, tags are attributes of data points
+/// except resource tags, which are attributes of the resource. Boolean fields are converted
+/// to integer and string fields are skipped. Lines without timestamp use the current time.
+/// Input in CSV can be converted by `influx from-csv` first.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output protobuf file path, `-` for stdout.
+    #[arg(
+        short,
+        long,
+        required_unless_present = "endpoint",
+        conflicts_with = "endpoint"
+    )]
+    output: Option<String>,
+    /// Endpoint of the collector to push metrics to.
+    #[arg(long)]
+    endpoint: Option<String>,
+    /// Protocol to push metrics.
+    #[arg(long, value_enum, default_value_t = Protocol::Http)]
+    protocol: Protocol,
+    /// Tags as attributes of the resource, e.g. `host,region`.
+    #[arg(long, value_delimiter = ',')]
+    resource_tag: Vec<String>,
+    /// Number of lines in each request to push.
+    #[arg(long, default_value_t = 1000)]
+    batch_size: usize,
+    #[command(flatten)]
+    rate_limit: RateLimit,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let mut builder = RequestBuilder::new(&self.resource_tag);
+        let Some(endpoint) = &self.endpoint else {
+            read_lines(input, |line| builder.append_line(&line))?;
+            let output = self.output.as_deref().unwrap_or("-");
+            let mut output = create_binary_output(output)?;
+            output
+                .write_all(&builder.finish().encode_to_vec())
+                .context(WriteOutputSnafu)?;
+            return output.finish().context(WriteOutputSnafu);
+        };
+
+        let mut exporter = Exporter::new(endpoint, self.protocol)?;
+        read_lines(input, |line| {
+            builder.append_line(&line)?;
+            if builder.num_lines >= self.batch_size {
+                self.export(&mut exporter, &mut builder)?;
+            }
+            Ok(())
+        })?;
+        if builder.num_lines > 0 {
+            self.export(&mut exporter, &mut builder)?;
+        }
+        Ok(())
+    }
+
+    /// Export lines in the builder after waiting for the rate limit.
+    fn export(&self, exporter: &mut Exporter, builder: &mut RequestBuilder<'_>) -> Result<()> {
+        let num_lines = builder.num_lines;
+        let request = builder.finish();
+        self.rate_limit.acquire(num_lines, request.encoded_len());
+        exporter.export(request)?;
+        progress::add_lines_written(num_lines);
+        Ok(())
+    }
+}
+
+/// Exporter of requests to a collector.
+enum Exporter {
+    Http(String),
+    #[cfg(feature = "otel-grpc")]
+    Grpc(Box<grpc::MetricsClient>),
+}
+
+impl Exporter {
+    fn new(endpoint: &str, protocol: Protocol) -> Result<Exporter> {
+        match protocol {
+            Protocol::Http => Ok(Exporter::Http(endpoint.to_string())),
+            #[cfg(feature = "otel-grpc")]
+            Protocol::Grpc => Ok(Exporter::Grpc(Box::new(grpc::MetricsClient::connect(
+                endpoint,
+            )?))),
+            #[cfg(not(feature = "otel-grpc"))]
+            Protocol::Grpc => GrpcDisabledSnafu.fail(),
+        }
+    }
+
+    /// Export the request, data points rejected by the collector are printed to stderr.
+    fn export(&mut self, request: ExportMetricsServiceRequest) -> Result<()> {
+        let response = match self {
+            Exporter::Http(url) => {
+                let response = match ureq::post(url)
+                    .set("Content-Type", "application/x-protobuf")
+                    .send_bytes(&request.encode_to_vec())
+                {
+                    Ok(response) => response,
+                    Err(ureq::Error::Status(status, response)) => {
+                        return RequestStatusSnafu {
+                            url: url.as_str(),
+                            status,
+                            body: response.into_string().unwrap_or_default(),
+                        }
+                        .fail();
+                    }
+                    Err(ureq::Error::Transport(e)) => {
+                        return Err(Box::new(e)).context(SendRequestSnafu { url: url.as_str() });
+                    }
+                };
+                let mut body = Vec::new();
+                response
+                    .into_reader()
+                    .read_to_end(&mut body)
+                    .context(ReadInputSnafu)?;
+                // Collectors may respond in JSON or with an empty body.
+                ExportMetricsServiceResponse::decode(body.as_slice()).unwrap_or_default()
+            }
+            #[cfg(feature = "otel-grpc")]
+            Exporter::Grpc(client) => client.export(request)?,
+        };
+        warn_rejected(&response);
+        Ok(())
+    }
+}
+
+/// Log data points rejected by the collector in the response.
+pub(crate) fn warn_rejected(response: &ExportMetricsServiceResponse) {
+    if let Some(partial) = &response.partial_success {
+        if partial.rejected_data_points > 0 {
+            warn!(
+                "Rejected {} data points: {}",
+                partial.rejected_data_points, partial.error_message
+            );
+        }
+    }
+}
+
+/// Builder of the export request of lines.
+///
+/// Data points with the same resource tags are in a resource, and data points of the same
+/// metric name in a resource are in a gauge.
+#[derive(Debug)]
+pub(crate) struct RequestBuilder<'a> {
+    resource_tags: &'a [String],
+    request: ExportMetricsServiceRequest,
+    /// Index of each resource by its attributes.
+    resource_index: HashMap<Vec<(String, String)>, usize>,
+    /// Index of each metric by the index of its resource and its name.
+    metric_index: HashMap<(usize, String), usize>,
+    /// Timestamp of lines without timestamp.
+    now: u64,
+    pub(crate) num_lines: usize,
+}
+
+impl<'a> RequestBuilder<'a> {
+    pub(crate) fn new(resource_tags: &'a [String]) -> RequestBuilder<'a> {
+        RequestBuilder {
+            resource_tags,
+            request: ExportMetricsServiceRequest::default(),
+            resource_index: HashMap::new(),
+            metric_index: HashMap::new(),
+            now: Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64,
+            num_lines: 0,
+        }
+    }
+
+    pub(crate) fn append_line(&mut self, line: &ParsedLine<'_>) -> Result<()> {
+        let time_unix_nano = match line.timestamp {
+            Some(ts) => u64::try_from(ts).ok().context(InvalidPointSnafu {
+                reason: format!("timestamp {ts} is before 1970"),
+            })?,
+            None => self.now,
+        };
+        let mut resource = Vec::new();
+        let mut attributes = Vec::new();
+        for (key, value) in line.series.tag_set.iter().flatten() {
+            if self.resource_tags.iter().any(|tag| tag == key.as_str()) {
+                resource.push((key.to_string(), value.to_string()));
+            } else {
+                attributes.push(string_attribute(key, value));
+            }
+        }
+
+        let resource_idx = *self
+            .resource_index
+            .entry(resource)
+            .or_insert_with_key(|resource| {
+                let attributes = resource.iter().map(|(k, v)| string_attribute(k, v));
+                self.request.resource_metrics.push(ResourceMetrics {
+                    resource: Some(Resource {
+                        attributes: attributes.collect(),
+                    }),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: Some(InstrumentationScope {
+                            name: SCOPE_NAME.to_string(),
+                            version: env!("CARGO_PKG_VERSION").to_string(),
+                        }),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                });
+                self.request.resource_metrics.len() - 1
+            });
+        let metrics = &mut self.request.resource_metrics[resource_idx].scope_metrics[0].metrics;
+
+        for (fieldk, fieldv) in &line.field_set {
+            let value = match fieldv {
+                FieldValue::I64(v) => number_data_point::Value::AsInt(*v),
+                FieldValue::U64(v) => match i64::try_from(*v) {
+                    Ok(v) => number_data_point::Value::AsInt(v),
+                    Err(_) => number_data_point::Value::AsDouble(*v as f64),
+                },
+                FieldValue::F64(v) => number_data_point::Value::AsDouble(*v),
+                FieldValue::Boolean(v) => number_data_point::Value::AsInt(i64::from(*v)),
+                FieldValue::String(_) => continue,
+            };
+            let name = format!("{}_{}", line.series.measurement, fieldk);
+            let idx = *self
+                .metric_index
+                .entry((resource_idx, name))
+                .or_insert_with_key(|(_, name)| {
+                    metrics.push(Metric {
+                        name: name.clone(),
+                        data: Some(metric::Data::Gauge(Gauge::default())),
+                        ..Default::default()
+                    });
+                    metrics.len() - 1
+                });
+            if let Some(metric::Data::Gauge(gauge)) = &mut metrics[idx].data {
+                gauge.data_points.push(NumberDataPoint {
+                    attributes: attributes.clone(),
+                    start_time_unix_nano: 0,
+                    time_unix_nano,
+                    value: Some(value),
+                });
+            }
+        }
+        self.num_lines += 1;
+        Ok(())
+    }
+
+    /// Returns the request of appended lines and resets the builder.
+    pub(crate) fn finish(&mut self) -> ExportMetricsServiceRequest {
+        self.resource_index.clear();
+        self.metric_index.clear();
+        self.num_lines = 0;
+        std::mem::take(&mut self.request)
+    }
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::String(value.to_string())),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_protocol_to_request() {
+        let input = "cpu,host=a,region=us usage=1.5,count=2i,up=true,note=\"x\" 1451606400000000000
+cpu,host=a,region=us usage=2.5 1451606410000000000
+cpu,host=b,region=us usage=3.5 1451606400000000000
+";
+        let resource_tags = vec!["host".to_string()];
+        let mut builder = RequestBuilder::new(&resource_tags);
+        read_lines(input.as_bytes(), |line| builder.append_line(&line)).unwrap();
+        assert_eq!(3, builder.num_lines);
+        let request = builder.finish();
+        let request = ExportMetricsServiceRequest::decode(&*request.encode_to_vec()).unwrap();
+
+        assert_eq!(2, request.resource_metrics.len());
+        let resource = &request.resource_metrics[0];
+        assert_eq!(
+            vec![string_attribute("host", "a")],
+            resource.resource.as_ref().unwrap().attributes
+        );
+        let metrics = &resource.scope_metrics[0].metrics;
+        let names: Vec<_> = metrics.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(vec!["cpu_usage", "cpu_count", "cpu_up"], names);
+
+        let Some(metric::Data::Gauge(gauge)) = &metrics[0].data else {
+            panic!("unexpected data {:?}", metrics[0].data);
+        };
+        assert_eq!(2, gauge.data_points.len());
+        let point = &gauge.data_points[1];
+        assert_eq!(vec![string_attribute("region", "us")], point.attributes);
+        assert_eq!(1451606410000000000, point.time_unix_nano);
+        assert_eq!(Some(number_data_point::Value::AsDouble(2.5)), point.value);
+        let Some(metric::Data::Gauge(gauge)) = &metrics[2].data else {
+            panic!("unexpected data {:?}", metrics[2].data);
+        };
+        assert_eq!(
+            Some(number_data_point::Value::AsInt(1)),
+            gauge.data_points[0].value
+        );
+
+        assert!(builder.finish().resource_metrics.is_empty());
+        let err = read_lines("cpu usage=1 -1".as_bytes(), |line| {
+            builder.append_line(&line)
+        });
+        assert!(err.is_err());
+    }
+}
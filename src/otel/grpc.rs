@@ -0,0 +1,164 @@
+//! Client and server of the OTLP metrics service by gRPC.
+
+use crate::error::{
+    BindSnafu, ConnectGrpcSnafu, CreateRuntimeSnafu, GrpcSnafu, Result, ServeGrpcSnafu,
+};
+use crate::otel::proto::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+use snafu::ResultExt;
+use std::convert::Infallible;
+use std::io;
+use std::net::ToSocketAddrs;
+use tokio::runtime::Runtime;
+use tonic::client::Grpc;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::codegen::{http, Arc, Body, BoxFuture, Context, Poll, Service, StdError};
+use tonic::server::{NamedService, UnaryService};
+use tonic::transport::{Channel, Endpoint, Server};
+use tonic::{Request, Response, Status};
+use tonic_prost::ProstCodec;
+use tracing::{error, info};
+
+/// Name of the metrics service.
+const SERVICE_NAME: &str = "opentelemetry.proto.collector.metrics.v1.MetricsService";
+/// Path of the export method of the metrics service.
+const EXPORT_PATH: &str = "/opentelemetry.proto.collector.metrics.v1.MetricsService/Export";
+
+/// Client of the metrics service.
+pub(crate) struct MetricsClient {
+    runtime: Runtime,
+    grpc: Grpc<Channel>,
+}
+
+impl MetricsClient {
+    /// Connect to the endpoint, e.g. `http://localhost:4317`.
+    pub(crate) fn connect(endpoint: &str) -> Result<MetricsClient> {
+        let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+        let grpc = runtime.block_on(connect(endpoint))?;
+        Ok(MetricsClient { runtime, grpc })
+    }
+
+    pub(crate) fn export(
+        &mut self,
+        request: ExportMetricsServiceRequest,
+    ) -> Result<ExportMetricsServiceResponse> {
+        self.runtime.block_on(export(&mut self.grpc, request))
+    }
+}
+
+/// Connect to the metrics service of the endpoint.
+pub(crate) async fn connect(endpoint: &str) -> Result<Grpc<Channel>> {
+    let channel = async { Endpoint::from_shared(endpoint.to_string())?.connect().await }
+        .await
+        .map_err(Box::new)
+        .context(ConnectGrpcSnafu { endpoint })?;
+    Ok(Grpc::new(channel))
+}
+
+/// Export the request to the metrics service.
+pub(crate) async fn export(
+    grpc: &mut Grpc<Channel>,
+    request: ExportMetricsServiceRequest,
+) -> Result<ExportMetricsServiceResponse> {
+    let response = async {
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(e.to_string()))?;
+        let codec = ProstCodec::default();
+        let path = PathAndQuery::from_static(EXPORT_PATH);
+        grpc.unary(Request::new(request), path, codec).await
+    }
+    .await
+    .map_err(Box::new)
+    .context(GrpcSnafu)?;
+    Ok(response.into_inner())
+}
+
+/// Serve the metrics service on the address until the process is killed, `write` is called
+/// with each export request.
+pub(crate) fn serve<F>(addr: &str, write: F) -> Result<()>
+where
+    F: Fn(&ExportMetricsServiceRequest) -> Result<()> + Send + Sync + 'static,
+{
+    let socket_addr = addr
+        .to_socket_addrs()
+        .and_then(|mut addrs| {
+            addrs
+                .next()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address"))
+        })
+        .context(BindSnafu { addr })?;
+    let runtime = Runtime::new().context(CreateRuntimeSnafu)?;
+    let service = MetricsService {
+        write: Arc::new(write),
+    };
+    info!("Listening on {addr}");
+    runtime
+        .block_on(Server::builder().add_service(service).serve(socket_addr))
+        .map_err(Box::new)
+        .context(ServeGrpcSnafu { addr })
+}
+
+/// Server of the metrics service, like the code generated by `tonic-build`.
+struct MetricsService<F> {
+    write: Arc<F>,
+}
+
+impl<F> Clone for MetricsService<F> {
+    fn clone(&self) -> Self {
+        MetricsService {
+            write: self.write.clone(),
+        }
+    }
+}
+
+impl<F> NamedService for MetricsService<F> {
+    const NAME: &'static str = SERVICE_NAME;
+}
+
+impl<F, B> Service<http::Request<B>> for MetricsService<F>
+where
+    F: Fn(&ExportMetricsServiceRequest) -> Result<()> + Send + Sync + 'static,
+    B: Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::Body>;
+    type Error = Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if request.uri().path() != EXPORT_PATH {
+            return Box::pin(async { Ok(Status::unimplemented("unknown method").into_http()) });
+        }
+        let export = Export(self.write.clone());
+        Box::pin(async move {
+            let mut grpc = tonic::server::Grpc::new(ProstCodec::default());
+            Ok(grpc.unary(export, request).await)
+        })
+    }
+}
+
+/// The export method.
+struct Export<F>(Arc<F>);
+
+impl<F> UnaryService<ExportMetricsServiceRequest> for Export<F>
+where
+    F: Fn(&ExportMetricsServiceRequest) -> Result<()> + Send + Sync + 'static,
+{
+    type Response = ExportMetricsServiceResponse;
+    type Future = BoxFuture<Response<Self::Response>, Status>;
+
+    fn call(&mut self, request: Request<ExportMetricsServiceRequest>) -> Self::Future {
+        let result = match (self.0)(request.get_ref()) {
+            Ok(()) => Ok(Response::new(ExportMetricsServiceResponse::default())),
+            Err(e) => {
+                error!("Failed to write metrics: {e}");
+                Err(Status::internal(e.to_string()))
+            }
+        };
+        Box::pin(async move { result })
+    }
+}
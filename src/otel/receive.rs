@@ -0,0 +1,370 @@
+//! Receive OTLP metrics to line protocol or CSV.
+
+#[cfg(not(feature = "otel-grpc"))]
+use crate::error::GrpcDisabledSnafu;
+use crate::error::{Result, WriteCsvSnafu, WriteOutputSnafu};
+use crate::influx::{push_fields, push_series};
+use crate::io::{create_output, Compression};
+use crate::otel::proto::{
+    any_value, metric, number_data_point, AnyValue, ExportMetricsServiceRequest,
+    ExportMetricsServiceResponse, HistogramDataPoint, KeyValue,
+};
+use crate::otel::Protocol;
+use crate::prom::SampleFormat;
+use crate::server::{self, Request, Response};
+use chrono::Utc;
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use prost::Message;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::Write;
+use std::sync::Mutex;
+use tracing::error;
+
+/// Path of metrics over HTTP.
+const METRICS_PATH: &str = "/v1/metrics";
+
+/// Receive OTLP metrics from agents and write them to line protocol or CSV file.
+///
+/// Each data point is a line with the metric name as the measurement, attributes of the
+/// resource and the data point as tags. Gauges and sums have the `value` field. Histograms
+/// have the `count`, `sum`, `min`, `max` fields and a field of the cumulative count of each
+/// bucket named by its upper bound, e.g. `0.5` and `+Inf`. Summaries have the `count`, `sum`
+/// fields and a field of each quantile. Exponential histograms are skipped.
+///
+/// Each CSV row is a field with the `timestamp`, `measurement`, `field`, `value` and `tags`
+/// columns, in which tags are a JSON object. The output is flushed after each request.
+#[derive(Debug, Parser)]
+pub(crate) struct Receive {
+    /// Address to listen on, e.g. `0.0.0.0:4317` for gRPC.
+    #[arg(long, default_value = "0.0.0.0:4318")]
+    listen: String,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = SampleFormat::Lp)]
+    format: SampleFormat,
+    /// Protocol to receive metrics, HTTP receives protobuf on `/v1/metrics`.
+    #[arg(long, value_enum, default_value_t = Protocol::Http)]
+    protocol: Protocol,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl Receive {
+    pub(crate) fn run(self) -> Result<()> {
+        let output = create_output(&self.output, self.output_compression)?;
+        let writer = Mutex::new(PointWriter::new(output, self.format)?);
+        let write = move |request: &ExportMetricsServiceRequest| {
+            let mut writer = writer.lock().unwrap();
+            writer.write_request(request)
+        };
+        match self.protocol {
+            Protocol::Http => server::serve(&self.listen, |request| handle(request, &write)),
+            #[cfg(feature = "otel-grpc")]
+            Protocol::Grpc => super::grpc::serve(&self.listen, write),
+            #[cfg(not(feature = "otel-grpc"))]
+            Protocol::Grpc => GrpcDisabledSnafu.fail(),
+        }
+    }
+}
+
+/// Handle a request over HTTP.
+fn handle(
+    request: &Request,
+    write: &impl Fn(&ExportMetricsServiceRequest) -> Result<()>,
+) -> Response {
+    if request.path != METRICS_PATH {
+        return Response::text(404, "not found");
+    }
+    if request.method != "POST" {
+        return Response::text(405, "method not allowed");
+    }
+    if request
+        .header("Content-Type")
+        .is_some_and(|v| !v.starts_with("application/x-protobuf"))
+    {
+        return Response::text(415, "only protobuf is supported");
+    }
+    let request = match request.decoded_body() {
+        Ok(body) => match ExportMetricsServiceRequest::decode(&*body) {
+            Ok(request) => request,
+            Err(e) => return Response::text(400, e.to_string()),
+        },
+        Err(e) => return Response::text(400, e.to_string()),
+    };
+    if let Err(e) = write(&request) {
+        error!("Failed to write metrics: {e}");
+        return Response::text(500, e.to_string());
+    }
+    Response {
+        status: 200,
+        content_type: "application/x-protobuf",
+        body: ExportMetricsServiceResponse::default().encode_to_vec(),
+    }
+}
+
+/// A point of a data point.
+#[derive(Debug, PartialEq)]
+struct Point<'a> {
+    measurement: &'a str,
+    tags: BTreeMap<&'a str, String>,
+    fields: Vec<(String, FieldValue<'static>)>,
+    timestamp: i64,
+}
+
+/// Returns points of all data points of the request.
+///
+/// Data points without time use `now`.
+fn request_points<'a>(request: &'a ExportMetricsServiceRequest, now: i64) -> Vec<Point<'a>> {
+    let mut points = Vec::new();
+    for resource_metrics in &request.resource_metrics {
+        let resource = resource_metrics.resource.iter();
+        let resource_tags = attribute_tags(resource.flat_map(|r| &r.attributes));
+        let metrics = resource_metrics
+            .scope_metrics
+            .iter()
+            .flat_map(|s| &s.metrics);
+        for metric in metrics {
+            let mut push = |attributes: &'a [KeyValue], time: u64, fields| {
+                let mut tags = resource_tags.clone();
+                tags.extend(attribute_tags(attributes));
+                points.push(Point {
+                    measurement: &metric.name,
+                    tags,
+                    fields,
+                    timestamp: if time == 0 { now } else { time as i64 },
+                });
+            };
+            let number_points = match &metric.data {
+                Some(metric::Data::Gauge(gauge)) => &gauge.data_points,
+                Some(metric::Data::Sum(sum)) => &sum.data_points,
+                Some(metric::Data::Histogram(histogram)) => {
+                    for point in &histogram.data_points {
+                        push(
+                            &point.attributes,
+                            point.time_unix_nano,
+                            histogram_fields(point),
+                        );
+                    }
+                    continue;
+                }
+                Some(metric::Data::Summary(summary)) => {
+                    for point in &summary.data_points {
+                        let mut fields = vec![
+                            ("count".to_string(), FieldValue::U64(point.count)),
+                            ("sum".to_string(), FieldValue::F64(point.sum)),
+                        ];
+                        fields.extend(
+                            point
+                                .quantile_values
+                                .iter()
+                                .map(|q| (q.quantile.to_string(), FieldValue::F64(q.value))),
+                        );
+                        push(&point.attributes, point.time_unix_nano, fields);
+                    }
+                    continue;
+                }
+                None => continue,
+            };
+            for point in number_points {
+                let value = match point.value {
+                    Some(number_data_point::Value::AsDouble(v)) => FieldValue::F64(v),
+                    Some(number_data_point::Value::AsInt(v)) => FieldValue::I64(v),
+                    None => continue,
+                };
+                push(
+                    &point.attributes,
+                    point.time_unix_nano,
+                    vec![("value".to_string(), value)],
+                );
+            }
+        }
+    }
+    points
+}
+
+fn histogram_fields(point: &HistogramDataPoint) -> Vec<(String, FieldValue<'static>)> {
+    let mut fields = vec![("count".to_string(), FieldValue::U64(point.count))];
+    for (name, value) in [("sum", point.sum), ("min", point.min), ("max", point.max)] {
+        if let Some(value) = value {
+            fields.push((name.to_string(), FieldValue::F64(value)));
+        }
+    }
+    let mut count = 0;
+    for (i, bucket_count) in point.bucket_counts.iter().enumerate() {
+        count += bucket_count;
+        let bound = match point.explicit_bounds.get(i) {
+            Some(bound) => bound.to_string(),
+            None => "+Inf".to_string(),
+        };
+        fields.push((bound, FieldValue::U64(count)));
+    }
+    fields
+}
+
+/// Returns attributes as tags, values that are not strings are formatted.
+fn attribute_tags<'a>(
+    attributes: impl IntoIterator<Item = &'a KeyValue>,
+) -> BTreeMap<&'a str, String> {
+    attributes
+        .into_iter()
+        .map(|kv| {
+            let value = match kv.value.as_ref().and_then(|v: &AnyValue| v.value.as_ref()) {
+                Some(any_value::Value::String(v)) => v.clone(),
+                Some(any_value::Value::Bool(v)) => v.to_string(),
+                Some(any_value::Value::Int(v)) => v.to_string(),
+                Some(any_value::Value::Double(v)) => v.to_string(),
+                None => String::new(),
+            };
+            (kv.key.as_str(), value)
+        })
+        .collect()
+}
+
+/// Writer of points to line protocol or CSV.
+enum PointWriter<W: Write> {
+    Lp(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+impl<W: Write> PointWriter<W> {
+    fn new(output: W, format: SampleFormat) -> Result<PointWriter<W>> {
+        match format {
+            SampleFormat::Lp => Ok(PointWriter::Lp(output)),
+            SampleFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(output);
+                writer
+                    .write_record(["timestamp", "measurement", "field", "value", "tags"])
+                    .context(WriteCsvSnafu)?;
+                Ok(PointWriter::Csv(Box::new(writer)))
+            }
+        }
+    }
+
+    /// Write points of the request and flush the output.
+    fn write_request(&mut self, request: &ExportMetricsServiceRequest) -> Result<()> {
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let points = request_points(request, now);
+        match self {
+            PointWriter::Lp(output) => {
+                let mut line = String::new();
+                for point in &points {
+                    let tags = point.tags.iter().map(|(k, v)| (*k, v.as_str()));
+                    push_series(&mut line, point.measurement, tags);
+                    push_fields(&mut line, point.fields.iter().map(|(k, v)| (k.as_str(), v)));
+                    writeln!(line, " {}", point.timestamp).unwrap();
+                }
+                output
+                    .write_all(line.as_bytes())
+                    .context(WriteOutputSnafu)?;
+                output.flush().context(WriteOutputSnafu)
+            }
+            PointWriter::Csv(writer) => {
+                for point in &points {
+                    let tags = serde_json::to_string(&point.tags).unwrap();
+                    let timestamp = point.timestamp.to_string();
+                    for (field, value) in &point.fields {
+                        let value = match value {
+                            FieldValue::I64(v) => v.to_string(),
+                            FieldValue::U64(v) => v.to_string(),
+                            FieldValue::F64(v) => v.to_string(),
+                            FieldValue::Boolean(v) => v.to_string(),
+                            FieldValue::String(v) => v.to_string(),
+                        };
+                        writer
+                            .write_record([&timestamp, point.measurement, field, &value, &tags])
+                            .context(WriteCsvSnafu)?;
+                    }
+                }
+                writer.flush().context(WriteOutputSnafu)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::otel::proto::{
+        Gauge, Histogram, Metric, NumberDataPoint, Resource, ResourceMetrics, ScopeMetrics,
+    };
+
+    fn attribute(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    #[test]
+    fn test_write_request() {
+        let gauge = Metric {
+            name: "cpu usage".to_string(),
+            data: Some(metric::Data::Gauge(Gauge {
+                data_points: vec![NumberDataPoint {
+                    attributes: vec![attribute("core", any_value::Value::Int(0))],
+                    time_unix_nano: 1451606400000000000,
+                    value: Some(number_data_point::Value::AsDouble(1.5)),
+                    ..Default::default()
+                }],
+            })),
+            ..Default::default()
+        };
+        let histogram = Metric {
+            name: "latency".to_string(),
+            data: Some(metric::Data::Histogram(Histogram {
+                data_points: vec![HistogramDataPoint {
+                    time_unix_nano: 1451606410000000000,
+                    count: 3,
+                    sum: Some(1.5),
+                    bucket_counts: vec![1, 0, 2],
+                    explicit_bounds: vec![0.1, 0.5],
+                    ..Default::default()
+                }],
+                aggregation_temporality: 2,
+            })),
+            ..Default::default()
+        };
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: vec![attribute("host", any_value::Value::String("a".to_string()))],
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![gauge, histogram, Metric::default()],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+        };
+
+        let mut writer = PointWriter::new(Vec::new(), SampleFormat::Lp).unwrap();
+        writer.write_request(&request).unwrap();
+        let PointWriter::Lp(output) = writer else {
+            unreachable!()
+        };
+        assert_eq!(
+            "cpu\\ usage,core=0,host=a value=1.5 1451606400000000000
+latency,host=a count=3u,sum=1.5,0.1=1u,0.5=1u,+Inf=3u 1451606410000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let mut writer = PointWriter::new(Vec::new(), SampleFormat::Csv).unwrap();
+        writer.write_request(&request).unwrap();
+        let PointWriter::Csv(writer) = writer else {
+            unreachable!()
+        };
+        let output = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            Some(r#"1451606400000000000,cpu usage,value,1.5,"{""core"":""0"",""host"":""a""}""#),
+            output.lines().nth(1)
+        );
+        assert_eq!(7, output.lines().count());
+    }
+}
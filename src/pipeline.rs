@@ -0,0 +1,213 @@
+//! Parallel pipeline to convert lines.
+
+use crate::error::{LineSnafu, ParseLineSnafu, Result, WriteOutputSnafu};
+use crate::io::ChunkReader;
+use crate::reject;
+use influxdb_line_protocol::ParsedLine;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+use tracing::debug;
+
+/// Chunk of lines with the sequence number and the line number of its first line.
+#[derive(Debug)]
+struct Chunk {
+    seq: usize,
+    first_line: usize,
+    text: String,
+}
+
+/// Lines of a chunk.
+#[derive(Debug)]
+pub(crate) struct Lines<'a> {
+    text: &'a str,
+    first_line: usize,
+}
+
+impl Lines<'_> {
+    /// Parse lines and call `f` for each line, like `read_lines`.
+    pub(crate) fn for_each(self, mut f: impl FnMut(ParsedLine<'_>) -> Result<()>) -> Result<()> {
+        for (i, text) in self.text.split_inclusive('\n').enumerate() {
+            for line in influxdb_line_protocol::parse_lines(text) {
+                let result = line
+                    .context(ParseLineSnafu)
+                    .and_then(&mut f)
+                    .context(LineSnafu {
+                        line: self.first_line + i,
+                    });
+                reject::check(result, || text)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Convert lines of line protocol with a pool of threads.
+///
+/// The reader sends chunks of lines of about `block_size` bytes to workers, each worker
+/// converts lines of a chunk into a buffer by `f`, and buffers are written to the dest in
+/// the order of the input.
+pub(crate) fn convert_lines<R, W, F>(
+    source: R,
+    mut dest: W,
+    threads: usize,
+    block_size: usize,
+    f: F,
+) -> Result<W>
+where
+    R: Read + Send,
+    W: Write,
+    F: Fn(Lines<'_>, &mut Vec<u8>) -> Result<()> + Sync,
+{
+    let threads = threads.max(1);
+    let start = Instant::now();
+    thread::scope(|scope| {
+        let (chunk_tx, chunk_rx) = sync_channel::<Chunk>(threads * 2);
+        let (result_tx, result_rx) = sync_channel::<(usize, Result<Vec<u8>>)>(threads * 2);
+
+        let reader = scope
+            .spawn(move || read_chunks(source, block_size, |chunk| chunk_tx.send(chunk).is_ok()));
+        let chunk_rx = Arc::new(Mutex::new(chunk_rx));
+        for _ in 0..threads {
+            let chunk_rx = chunk_rx.clone();
+            let result_tx = result_tx.clone();
+            let f = &f;
+            scope.spawn(move || {
+                while let Some(chunk) = next_chunk(&chunk_rx) {
+                    let result = convert_chunk(&chunk, f);
+                    if result_tx.send((chunk.seq, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+
+        // Results may arrive out of order, so they are buffered until their turn.
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (seq, result) in result_rx.iter() {
+            pending.insert(seq, result);
+            while let Some(result) = pending.remove(&next) {
+                dest.write_all(&result?).context(WriteOutputSnafu)?;
+                next += 1;
+            }
+        }
+
+        reader.join().expect("reader thread panicked")?;
+        debug!(
+            "Converted {next} chunks by {threads} threads in {:?}",
+            start.elapsed()
+        );
+        Ok(dest)
+    })
+}
+
+/// Call `f` for each item with a pool of threads, returns the first error.
+pub(crate) fn for_each<T, F>(items: Vec<T>, threads: usize, f: F) -> Result<()>
+where
+    T: Send,
+    F: Fn(T) -> Result<()> + Sync,
+{
+    let items = Mutex::new(items.into_iter());
+    let error = Mutex::new(None);
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| loop {
+                // Stop taking items after an error.
+                if error.lock().unwrap().is_some() {
+                    return;
+                }
+                let Some(item) = items.lock().unwrap().next() else {
+                    return;
+                };
+                if let Err(e) = f(item) {
+                    error.lock().unwrap().get_or_insert(e);
+                }
+            });
+        }
+    });
+
+    match error.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn next_chunk(chunk_rx: &Mutex<Receiver<Chunk>>) -> Option<Chunk> {
+    chunk_rx.lock().unwrap().recv().ok()
+}
+
+/// Read chunks of whole lines until `send` returns false.
+fn read_chunks<R: Read>(
+    source: R,
+    block_size: usize,
+    mut send: impl FnMut(Chunk) -> bool,
+) -> Result<()> {
+    let mut reader = ChunkReader::new(source, block_size);
+    let mut seq = 0;
+    let mut line_number = 1;
+    while let Some(text) = reader.next_chunk()? {
+        let lines = text.split_inclusive('\n').count();
+        let chunk = Chunk {
+            seq,
+            first_line: line_number,
+            text: text.to_string(),
+        };
+        if !send(chunk) {
+            return Ok(());
+        }
+        seq += 1;
+        line_number += lines;
+    }
+    Ok(())
+}
+
+fn convert_chunk<F>(chunk: &Chunk, f: &F) -> Result<Vec<u8>>
+where
+    F: Fn(Lines<'_>, &mut Vec<u8>) -> Result<()>,
+{
+    let mut buffer = Vec::new();
+    let lines = Lines {
+        text: &chunk.text,
+        first_line: chunk.first_line,
+    };
+    f(lines, &mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_convert_lines() {
+        let input: String = (0..100_000).map(|i| format!("cpu v={i}i {i}\n")).collect();
+        let output = convert_lines(
+            Cursor::new(&input),
+            Vec::new(),
+            4,
+            1 << 10,
+            |lines, buffer| {
+                lines.for_each(|line| {
+                    writeln!(buffer, "{}", line.timestamp.unwrap()).context(WriteOutputSnafu)
+                })
+            },
+        )
+        .unwrap();
+        let expect: String = (0..100_000).map(|i| format!("{i}\n")).collect();
+        assert_eq!(expect, String::from_utf8(output).unwrap());
+
+        let input = format!("{input}cpu v=\n");
+        let err = convert_lines(Cursor::new(&input), Vec::new(), 4, 1 << 10, |lines, _| {
+            lines.for_each(|_| Ok(()))
+        })
+        .unwrap_err();
+        assert_eq!("Error at line 100001", err.to_string());
+    }
+}
@@ -0,0 +1,286 @@
+//! Progress of streaming commands.
+//!
+//! The progress bar is driven by bytes read from inputs against their sizes. Lines are
+//! counted by newlines of decompressed inputs and outputs, and by writers of remote
+//! endpoints.
+
+use crate::io::Output;
+use indicatif::{HumanBytes, ProgressBar, ProgressDrawTarget, ProgressState, ProgressStyle};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Template of the bar if sizes of all inputs are known.
+const SIZED_TEMPLATE: &str = "{spinner} [{elapsed_precise}] [{wide_bar}] {binary_bytes}/{binary_total_bytes} ({binary_bytes_per_sec}, ETA {eta}) {lines}";
+/// Template of the bar if the size of an input is unknown, e.g. stdin.
+const UNSIZED_TEMPLATE: &str =
+    "{spinner} [{elapsed_precise}] {binary_bytes} ({binary_bytes_per_sec}) {lines}";
+
+static PROGRESS: OnceLock<Progress> = OnceLock::new();
+
+struct Progress {
+    bar: ProgressBar,
+    start: Instant,
+    counters: Counters,
+    /// Total size of inputs, `None` if the size of an input is unknown.
+    total: Mutex<Option<u64>>,
+}
+
+/// Counters of a command.
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_read: AtomicU64,
+    lines_read: AtomicU64,
+    lines_written: AtomicU64,
+    lines_skipped: AtomicU64,
+}
+
+impl Counters {
+    fn add(counter: &AtomicU64, n: usize) {
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    fn get(counter: &AtomicU64) -> u64 {
+        counter.load(Ordering::Relaxed)
+    }
+
+    /// Returns the summary of the command that ran for the elapsed time.
+    fn summary(&self, elapsed: Duration) -> String {
+        let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+        let bytes_read = Counters::get(&self.bytes_read);
+        let lines_read = Counters::get(&self.lines_read);
+        let lines_written = Counters::get(&self.lines_written);
+        // Commands like `generate` only write lines.
+        let lines = lines_read.max(lines_written);
+        format!(
+            "Read {lines_read} lines ({}), wrote {lines_written} lines, skipped {} lines in {secs:.2}s ({:.0} lines/s, {}/s)",
+            HumanBytes(bytes_read),
+            Counters::get(&self.lines_skipped),
+            lines as f64 / secs,
+            HumanBytes((bytes_read as f64 / secs) as u64),
+        )
+    }
+}
+
+/// Show a progress bar on stderr if it is a terminal, and count lines for the summary
+/// printed by [finish].
+pub fn enable() {
+    PROGRESS.get_or_init(|| {
+        let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stderr());
+        bar.set_style(style(UNSIZED_TEMPLATE));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        Progress {
+            bar,
+            start: Instant::now(),
+            counters: Counters::default(),
+            total: Mutex::new(Some(0)),
+        }
+    });
+}
+
+/// Clear the progress bar and print the summary to stderr if the progress is enabled.
+pub fn finish() {
+    if let Some(progress) = PROGRESS.get() {
+        progress.bar.finish_and_clear();
+        eprintln!("{}", progress.counters.summary(progress.start.elapsed()));
+    }
+}
+
+/// Writer of logs to stderr, which hides the progress bar while writing.
+#[derive(Debug, Default)]
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match PROGRESS.get() {
+            Some(progress) => progress.bar.suspend(|| io::stderr().write(buf)),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn style(template: &str) -> ProgressStyle {
+    ProgressStyle::with_template(template)
+        .expect("valid template")
+        .with_key("lines", |_: &ProgressState, w: &mut dyn fmt::Write| {
+            if let Some(progress) = PROGRESS.get() {
+                let counters = &progress.counters;
+                let _ = write!(
+                    w,
+                    "read {} lines, wrote {} lines",
+                    Counters::get(&counters.lines_read),
+                    Counters::get(&counters.lines_written)
+                );
+            }
+        })
+}
+
+/// Add the size of an input to read, `None` if the size is unknown.
+pub(crate) fn add_input_size(size: Option<u64>) {
+    let Some(progress) = PROGRESS.get() else {
+        return;
+    };
+    let mut total = progress.total.lock().unwrap();
+    match (*total, size) {
+        (Some(sum), Some(size)) => {
+            if sum == 0 {
+                progress.bar.set_style(style(SIZED_TEMPLATE));
+            }
+            progress.bar.set_length(sum + size);
+            *total = Some(sum + size);
+        }
+        (Some(_), None) => {
+            progress.bar.unset_length();
+            progress.bar.set_style(style(UNSIZED_TEMPLATE));
+            *total = None;
+        }
+        (None, _) => {}
+    }
+}
+
+/// Add lines written to a remote endpoint.
+pub(crate) fn add_lines_written(lines: usize) {
+    if let Some(progress) = PROGRESS.get() {
+        Counters::add(&progress.counters.lines_written, lines);
+    }
+}
+
+/// Add lines skipped, e.g. comments.
+pub(crate) fn add_lines_skipped(lines: usize) {
+    if let Some(progress) = PROGRESS.get() {
+        Counters::add(&progress.counters.lines_skipped, lines);
+    }
+}
+
+/// Add bytes and lines read from a mapped input, which is not read by a tracked reader.
+pub(crate) fn add_read(bytes: usize, lines: usize) {
+    if let Some(progress) = PROGRESS.get() {
+        Counters::add(&progress.counters.bytes_read, bytes);
+        Counters::add(&progress.counters.lines_read, lines);
+        progress.bar.inc(bytes as u64);
+    }
+}
+
+/// Returns the raw input whose bytes drive the progress bar.
+pub(crate) fn track_bytes<R: Read + Send + 'static>(reader: R) -> Box<dyn Read + Send> {
+    match PROGRESS.get() {
+        Some(progress) => Box::new(Counted {
+            inner: reader,
+            counter: &progress.counters.bytes_read,
+            bar: Some(&progress.bar),
+            newlines: false,
+        }),
+        None => Box::new(reader),
+    }
+}
+
+/// Returns the decompressed input whose lines are counted.
+pub(crate) fn track_lines_read(reader: Box<dyn Read + Send>) -> Box<dyn Read + Send> {
+    match PROGRESS.get() {
+        Some(progress) => Box::new(Counted {
+            inner: reader,
+            counter: &progress.counters.lines_read,
+            bar: None,
+            newlines: true,
+        }),
+        None => reader,
+    }
+}
+
+/// Returns the uncompressed output whose lines are counted.
+pub(crate) fn track_lines_written(writer: Box<dyn Output + Send>) -> Box<dyn Output + Send> {
+    match PROGRESS.get() {
+        Some(progress) => Box::new(Counted {
+            inner: writer,
+            counter: &progress.counters.lines_written,
+            bar: None,
+            newlines: true,
+        }),
+        None => writer,
+    }
+}
+
+/// Reader or writer that counts bytes or newlines passed through it.
+struct Counted<T> {
+    inner: T,
+    counter: &'static AtomicU64,
+    bar: Option<&'static ProgressBar>,
+    /// Whether to count newlines instead of bytes.
+    newlines: bool,
+}
+
+impl<T> Counted<T> {
+    fn count(&self, buf: &[u8]) {
+        let n = if self.newlines {
+            buf.iter().filter(|&&b| b == b'\n').count()
+        } else {
+            buf.len()
+        };
+        Counters::add(self.counter, n);
+        if let Some(bar) = self.bar {
+            bar.inc(n as u64);
+        }
+    }
+}
+
+impl<R: Read> Read for Counted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Output> Output for Counted<W> {
+    fn finish(&mut self) -> io::Result<()> {
+        self.inner.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counted() {
+        let counters = Box::leak(Box::new(Counters::default()));
+        let mut reader = Counted {
+            inner: &b"cpu usage=1 1\ncpu usage=2 2\n"[..],
+            counter: &counters.lines_read,
+            bar: None,
+            newlines: true,
+        };
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        let mut writer = Counted {
+            inner: Vec::new(),
+            counter: &counters.bytes_read,
+            bar: None,
+            newlines: false,
+        };
+        writer.write_all(&[b'\n'; 2048]).unwrap();
+        Counters::add(&counters.lines_written, 3);
+        Counters::add(&counters.lines_skipped, 1);
+        assert_eq!(
+            "Read 2 lines (2.00 KiB), wrote 3 lines, skipped 1 lines in 2.00s (2 lines/s, 1.00 KiB/s)",
+            counters.summary(Duration::from_secs(2))
+        );
+    }
+}
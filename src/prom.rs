@@ -0,0 +1,495 @@
+//! Tools for Prometheus target.
+
+pub(crate) mod block;
+pub(crate) mod exposition;
+pub(crate) mod remote;
+
+use crate::error::{ReadInputSnafu, Result, SnappyCompressSnafu, WriteCsvSnafu, WriteOutputSnafu};
+use crate::influx::{escape, read_lines, KEY_ESCAPES, MEASUREMENT_ESCAPES};
+use crate::io::{
+    create_binary_output, create_output, open_input, Compression, CompressionArgs, Output,
+};
+use chrono::{TimeZone, Utc};
+use clap::{Parser, ValueEnum};
+use csv::Writer;
+use exposition::Sample as ExpositionSample;
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use prost::Message;
+use remote::{Label, Sample, TimeSeries, WriteRequest};
+use snafu::ResultExt;
+use std::collections::HashMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Read, Write};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Prometheus command.
+#[derive(Debug, Parser)]
+pub struct PromCommand {
+    #[clap(subcommand)]
+    subcmd: PromSubcommand,
+}
+
+impl PromCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            PromSubcommand::FromLp(c) => c.run(),
+            PromSubcommand::ToLp(c) => c.run(),
+            PromSubcommand::ToCsv(c) => c.run(),
+            PromSubcommand::ReadBlock(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Prometheus.
+#[derive(Debug, Parser)]
+enum PromSubcommand {
+    /// Line protocol to remote write request.
+    FromLp(FromLp),
+    /// Exposition format to line protocol.
+    ToLp(ToLp),
+    /// Exposition format to CSV.
+    ToCsv(ToCsv),
+    /// TSDB block to line protocol or CSV.
+    ReadBlock(ReadBlock),
+}
+
+/// Format of converted samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum SampleFormat {
+    /// Line protocol.
+    Lp,
+    /// CSV.
+    Csv,
+}
+
+/// Convert line protocol file to snappy compressed remote write request.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output remote write request file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let request = line_protocol_to_write_request(input)?;
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .context(SnappyCompressSnafu)?;
+
+        let mut output = create_binary_output(&self.output)?;
+        output.write_all(&body).context(WriteOutputSnafu)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol to a remote write request.
+///
+/// Each field is a series named `{measurement}_{field}`, tags are labels.
+/// Integer and boolean fields are converted to float, string fields are skipped.
+/// Lines without timestamp use the current time.
+pub(crate) fn line_protocol_to_write_request<R: Read>(source: R) -> Result<WriteRequest> {
+    let now = Utc::now().timestamp_millis();
+    let mut request = WriteRequest::default();
+    // Index of each series in the request by its labels.
+    let mut series_index: HashMap<Vec<Label>, usize> = HashMap::new();
+
+    read_lines(source, |line| {
+        let timestamp = line
+            .timestamp
+            .map(|ts| ts.div_euclid(1_000_000))
+            .unwrap_or(now);
+        let labels = line_labels(&line);
+        for (fieldk, fieldv) in &line.field_set {
+            let value = match fieldv {
+                FieldValue::I64(v) => *v as f64,
+                FieldValue::U64(v) => *v as f64,
+                FieldValue::F64(v) => *v,
+                FieldValue::Boolean(v) => f64::from(u8::from(*v)),
+                FieldValue::String(_) => continue,
+            };
+
+            let name = metric_name(&format!("{}_{}", line.series.measurement, fieldk));
+            let mut series_labels = Vec::with_capacity(labels.len() + 1);
+            series_labels.push(Label {
+                name: "__name__".to_string(),
+                value: name,
+            });
+            series_labels.extend(labels.iter().cloned());
+            series_labels.sort_unstable();
+
+            let idx = *series_index
+                .entry(series_labels.clone())
+                .or_insert_with(|| {
+                    request.timeseries.push(TimeSeries {
+                        labels: series_labels,
+                        samples: Vec::new(),
+                    });
+                    request.timeseries.len() - 1
+                });
+            request.timeseries[idx]
+                .samples
+                .push(Sample { value, timestamp });
+        }
+        Ok(())
+    })?;
+
+    for series in &mut request.timeseries {
+        series.samples.sort_by_key(|sample| sample.timestamp);
+    }
+    Ok(request)
+}
+
+/// Convert exposition file to line protocol file.
+#[derive(Debug, Parser)]
+struct ToLp {
+    /// Input exposition file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToLp {
+    fn run(self) -> Result<()> {
+        let text = read_exposition(&self.input, self.compression.input_compression)?;
+        let samples = exposition::parse(&text)?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = samples_to_line_protocol(&samples, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert exposition file to CSV file.
+#[derive(Debug, Parser)]
+struct ToCsv {
+    /// Input exposition file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output CSV file path, `-` for stdout.
+    #[arg(short, long)]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToCsv {
+    fn run(self) -> Result<()> {
+        let text = read_exposition(&self.input, self.compression.input_compression)?;
+        let samples = exposition::parse(&text)?;
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = samples_to_csv(&samples, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert samples of an on-disk TSDB block to line protocol or CSV file.
+///
+/// Each sample is a line with the metric name as the measurement, labels as tags and the
+/// `value` field. Tombstones are not applied and native histograms are skipped.
+#[derive(Debug, Parser)]
+struct ReadBlock {
+    /// Block directory, e.g. `/prometheus/01BKGV7JBM69T2G1BGBGM6KB12`.
+    #[arg(long)]
+    dir: PathBuf,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = SampleFormat::Lp)]
+    format: SampleFormat,
+    /// Compression of the output, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    output_compression: Compression,
+}
+
+impl ReadBlock {
+    fn run(self) -> Result<()> {
+        let (index, mut chunks) = block::open(&self.dir)?;
+        let mut output = create_output(&self.output, self.output_compression)?;
+
+        // Lines are written by series, but CSV needs all label names for the header.
+        let mut csv_samples = Vec::new();
+        let mut values = Vec::new();
+        for series in index.series() {
+            values.clear();
+            chunks.read_samples(&series.chunks, &mut values)?;
+            let labels = index.labels(series);
+            let name = labels
+                .iter()
+                .find(|(name, _)| *name == "__name__")
+                .map(|(_, value)| *value)
+                .unwrap_or_default();
+            let labels: Vec<_> = labels
+                .iter()
+                .filter(|(name, _)| *name != "__name__")
+                .map(|(name, value)| (*name, value.to_string()))
+                .collect();
+            let samples = values.iter().map(|(ts, value)| ExpositionSample {
+                name,
+                labels: labels.clone(),
+                value: *value,
+                timestamp: Some(ts * 1_000_000),
+            });
+            match self.format {
+                SampleFormat::Lp => {
+                    let samples: Vec<_> = samples.collect();
+                    samples_to_line_protocol(&samples, &mut output)?;
+                }
+                SampleFormat::Csv => csv_samples.extend(samples),
+            }
+        }
+        if self.format == SampleFormat::Csv {
+            samples_to_csv(&csv_samples, &mut output)?;
+        }
+        if chunks.skipped_chunks > 0 {
+            warn!(
+                "Skipped {} chunks of native histograms",
+                chunks.skipped_chunks
+            );
+        }
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn read_exposition(path: &str, compression: Compression) -> Result<String> {
+    let mut text = String::new();
+    open_input(path, compression)?
+        .read_to_string(&mut text)
+        .context(ReadInputSnafu)?;
+    Ok(text)
+}
+
+/// Write samples as lines.
+///
+/// The metric name is the measurement, labels are tags and the value is the `value`
+/// field. Samples that are not finite are skipped as line protocol doesn't support
+/// them, samples without timestamp use the current time.
+pub(crate) fn samples_to_line_protocol<W: Write>(
+    samples: &[ExpositionSample<'_>],
+    dest: W,
+) -> Result<W> {
+    let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    for sample in samples {
+        if !sample.value.is_finite() {
+            continue;
+        }
+
+        line.clear();
+        escape(&mut line, sample.name, MEASUREMENT_ESCAPES);
+        for (name, value) in &sample.labels {
+            // Empty label values are the same as missing labels.
+            if value.is_empty() {
+                continue;
+            }
+            line.push(',');
+            escape(&mut line, name, KEY_ESCAPES);
+            line.push('=');
+            escape(&mut line, value, KEY_ESCAPES);
+        }
+        writeln!(
+            line,
+            " value={} {}",
+            sample.value,
+            sample.timestamp.unwrap_or(now)
+        )
+        .unwrap();
+
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Write samples as CSV.
+///
+/// The header contains the `measurement` column, all label names and the `value`
+/// and `timestamp` columns.
+pub(crate) fn samples_to_csv<W: Write>(samples: &[ExpositionSample<'_>], dest: W) -> Result<W> {
+    let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+    let mut label_index: HashMap<&str, usize> = HashMap::new();
+    let mut label_names = Vec::new();
+    for (name, _) in samples.iter().flat_map(|sample| &sample.labels) {
+        label_index.entry(name).or_insert_with(|| {
+            label_names.push(*name);
+            label_names.len() - 1
+        });
+    }
+
+    let mut writer = Writer::from_writer(dest);
+    let headers = ["measurement"]
+        .into_iter()
+        .chain(label_names.iter().copied())
+        .chain(["value", "timestamp"]);
+    writer.write_record(headers).context(WriteCsvSnafu)?;
+
+    let num_columns = label_names.len() + 3;
+    let mut row = vec![String::new(); num_columns];
+    for sample in samples {
+        row.iter_mut().for_each(String::clear);
+        row[0].push_str(sample.name);
+        for (name, value) in &sample.labels {
+            row[1 + label_index[name]].push_str(value);
+        }
+        row[num_columns - 2] = sample.value.to_string();
+        row[num_columns - 1] = Utc
+            .timestamp_nanos(sample.timestamp.unwrap_or(now))
+            .to_rfc3339();
+        writer.write_record(&row).context(WriteCsvSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Returns labels of tags in the line.
+fn line_labels(line: &ParsedLine<'_>) -> Vec<Label> {
+    line.series
+        .tag_set
+        .iter()
+        .flatten()
+        .map(|(tagk, tagv)| Label {
+            name: label_name(tagk.as_str()),
+            value: tagv.to_string(),
+        })
+        .collect()
+}
+
+/// Returns a valid metric name, invalid characters are replaced by `_`.
+fn metric_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+/// Returns a valid label name, invalid characters are replaced by `_`.
+fn label_name(name: &str) -> String {
+    sanitize(name, |c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn sanitize(name: &str, is_valid: impl Fn(char) -> bool) -> String {
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| if is_valid(c) { c } else { '_' })
+        .collect();
+    if sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+        sanitized.insert(0, '_');
+    }
+    sanitized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn label(name: &str, value: &str) -> Label {
+        Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_line_protocol_to_write_request() {
+        let input =
+            "cpu,host=host_0,k8s.pod=app usage_user=58i,ok=true,status=\"up\" 1451606410000000000
+cpu,host=host_0,k8s.pod=app usage_user=60i 1451606400000000000
+disk.io,host=host_1 read=1.5 1451606400000000000
+";
+        let request = line_protocol_to_write_request(Cursor::new(input)).unwrap();
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+        let decoded = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        let request = WriteRequest::decode(decoded.as_slice()).unwrap();
+
+        let expect = vec![
+            TimeSeries {
+                labels: vec![
+                    label("__name__", "cpu_usage_user"),
+                    label("host", "host_0"),
+                    label("k8s_pod", "app"),
+                ],
+                samples: vec![
+                    Sample {
+                        value: 60.0,
+                        timestamp: 1451606400000,
+                    },
+                    Sample {
+                        value: 58.0,
+                        timestamp: 1451606410000,
+                    },
+                ],
+            },
+            TimeSeries {
+                labels: vec![
+                    label("__name__", "cpu_ok"),
+                    label("host", "host_0"),
+                    label("k8s_pod", "app"),
+                ],
+                samples: vec![Sample {
+                    value: 1.0,
+                    timestamp: 1451606410000,
+                }],
+            },
+            TimeSeries {
+                labels: vec![label("__name__", "disk_io_read"), label("host", "host_1")],
+                samples: vec![Sample {
+                    value: 1.5,
+                    timestamp: 1451606400000,
+                }],
+            },
+        ];
+        assert_eq!(expect, request.timeseries);
+    }
+
+    #[test]
+    fn test_samples_to_line_protocol_and_csv() {
+        let text = r#"# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{code="400",path="/a b"} 3 1395066363000
+up NaN 1395066363000
+"#;
+        let samples = exposition::parse(text).unwrap();
+
+        let output = samples_to_line_protocol(&samples, Vec::new()).unwrap();
+        assert_eq!(
+            "http_requests_total,method=post,code=200 value=1027 1395066363000000000
+http_requests_total,code=400,path=/a\\ b value=3 1395066363000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let output = samples_to_csv(&samples, Vec::new()).unwrap();
+        assert_eq!(
+            "measurement,method,code,path,value,timestamp
+http_requests_total,post,200,,1027,2014-03-17T14:26:03+00:00
+http_requests_total,,400,/a b,3,2014-03-17T14:26:03+00:00
+up,,,,NaN,2014-03-17T14:26:03+00:00
+",
+            String::from_utf8(output).unwrap()
+        );
+    }
+}
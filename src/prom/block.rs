@@ -0,0 +1,554 @@
+//! Reader of Prometheus TSDB blocks.
+//!
+//! A block directory has an `index` file of series and `chunks/` segment files of samples,
+//! see <https://github.com/prometheus/prometheus/tree/main/tsdb/docs/format>.
+
+use crate::decode::{BitReader, Decbuf};
+use crate::error::{InvalidBlockSnafu, OpenFileSnafu, ReadFileSnafu, Result};
+use snafu::{ensure, ResultExt};
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Magic number of index files.
+const INDEX_MAGIC: u32 = 0xBAAD_D700;
+/// Magic number of chunk segment files.
+const CHUNKS_MAGIC: u32 = 0x85BD_40DD;
+/// Size of the table of contents at the end of index files.
+const TOC_SIZE: usize = 6 * 8 + 4;
+/// Encoding of float chunks.
+const ENCODING_XOR: u8 = 1;
+
+/// A series in the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Series {
+    /// References of label names and values in the symbol table.
+    labels: Vec<(u32, u32)>,
+    /// References of chunks, the upper 4 bytes are the segment and the lower 4 bytes are
+    /// the offset in the segment file.
+    pub(crate) chunks: Vec<u64>,
+}
+
+/// Symbols and series of an index file.
+#[derive(Debug)]
+pub(crate) struct Index {
+    symbols: Vec<String>,
+    series: Vec<Series>,
+}
+
+impl Index {
+    /// Read the index file of the block. Only the version 2 format is supported.
+    pub(crate) fn open(dir: &Path) -> Result<Index> {
+        let path = dir.join("index");
+        let data = fs::read(&path).context(ReadFileSnafu { path: &path })?;
+        Index::parse(&data).map_err(|reason| InvalidBlockSnafu { path, reason }.build())
+    }
+
+    fn parse(data: &[u8]) -> Result<Index, String> {
+        let mut header = Decbuf::new(data);
+        if header.be_u32()? != INDEX_MAGIC {
+            return Err("invalid magic number".to_string());
+        }
+        let version = header.byte()?;
+        if version != 2 {
+            return Err(format!("unsupported index version {version}"));
+        }
+
+        let toc_start = data
+            .len()
+            .checked_sub(TOC_SIZE)
+            .ok_or("index is too short")?;
+        let mut toc = Decbuf::at(data, toc_start);
+        let mut offsets = [0; 6];
+        for offset in &mut offsets {
+            *offset = usize::try_from(toc.be_u64()?).map_err(|e| e.to_string())?;
+        }
+        let [symbols_start, series_start, ..] = offsets;
+        // The series section ends at the next section.
+        let series_end = offsets[2..]
+            .iter()
+            .copied()
+            .filter(|offset| *offset > series_start)
+            .min()
+            .unwrap_or(toc_start);
+
+        let mut buf = Decbuf::at(data, symbols_start);
+        buf.be_u32()?;
+        let num_symbols = buf.be_u32()?;
+        let symbols = (0..num_symbols)
+            .map(|_| {
+                let len = buf.uvarint()? as usize;
+                let bytes = buf.bytes(len)?;
+                String::from_utf8(bytes.to_vec()).map_err(|e| e.to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut series = Vec::new();
+        let mut pos = series_start;
+        loop {
+            // Each series is aligned to 16 bytes.
+            pos = pos.next_multiple_of(16);
+            if pos >= series_end {
+                break;
+            }
+            let mut buf = Decbuf::at(data, pos);
+            let len = buf.uvarint()? as usize;
+            if len == 0 {
+                break;
+            }
+            let content_start = buf.position();
+            series.push(parse_series(&mut buf, symbols.len())?);
+            // Skip the CRC32 checksum.
+            pos = content_start
+                .checked_add(len)
+                .and_then(|end| end.checked_add(4))
+                .ok_or("series length overflows")?;
+        }
+
+        Ok(Index { symbols, series })
+    }
+
+    /// Returns all series in the order of the index, which is sorted by labels.
+    pub(crate) fn series(&self) -> &[Series] {
+        &self.series
+    }
+
+    /// Returns the labels of the series.
+    pub(crate) fn labels(&self, series: &Series) -> Vec<(&str, &str)> {
+        series
+            .labels
+            .iter()
+            .map(|(name, value)| {
+                (
+                    self.symbols[*name as usize].as_str(),
+                    self.symbols[*value as usize].as_str(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn parse_series(buf: &mut Decbuf<'_>, num_symbols: usize) -> Result<Series, String> {
+    let num_labels = buf.uvarint()?;
+    let labels = (0..num_labels)
+        .map(|_| Ok((symbol(buf, num_symbols)?, symbol(buf, num_symbols)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let num_chunks = buf.uvarint()?;
+    // Each chunk has at least 3 bytes.
+    if num_chunks > buf.remaining() as u64 / 3 {
+        return Err(format!("{num_chunks} chunks exceed the index"));
+    }
+    let mut chunks = Vec::with_capacity(num_chunks as usize);
+    let mut maxt = 0;
+    let mut chunk_ref = 0;
+    for i in 0..num_chunks {
+        // Chunks are delta encoded, only the references are needed.
+        if i == 0 {
+            let mint = buf.varint()?;
+            maxt = mint.wrapping_add(buf.uvarint()? as i64);
+            chunk_ref = buf.uvarint()?;
+        } else {
+            let mint = maxt.wrapping_add(buf.uvarint()? as i64);
+            maxt = mint.wrapping_add(buf.uvarint()? as i64);
+            chunk_ref = chunk_ref.wrapping_add_signed(buf.varint()?);
+        }
+        chunks.push(chunk_ref);
+    }
+
+    Ok(Series { labels, chunks })
+}
+
+/// Reader of chunks in segment files.
+pub(crate) struct ChunkReader {
+    dir: PathBuf,
+    /// Opened segment files by the segment number.
+    files: HashMap<u32, File>,
+    /// Number of chunks skipped for unsupported encodings, e.g. native histograms.
+    pub(crate) skipped_chunks: u64,
+    buffer: Vec<u8>,
+}
+
+impl ChunkReader {
+    pub(crate) fn new(dir: &Path) -> ChunkReader {
+        ChunkReader {
+            dir: dir.join("chunks"),
+            files: HashMap::new(),
+            skipped_chunks: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Append timestamps in millisecond and values of the chunks to `samples`.
+    pub(crate) fn read_samples(
+        &mut self,
+        chunks: &[u64],
+        samples: &mut Vec<(i64, f64)>,
+    ) -> Result<()> {
+        for chunk_ref in chunks {
+            let segment = (chunk_ref >> 32) as u32;
+            let offset = chunk_ref & 0xFFFF_FFFF;
+            let path = self.dir.join(format!("{:06}", segment + 1));
+            let encoding = self
+                .read_chunk(segment, offset, &path)
+                .context(ReadFileSnafu { path: &path })?;
+            if encoding != Some(ENCODING_XOR) {
+                ensure!(
+                    encoding.is_some(),
+                    InvalidBlockSnafu {
+                        path,
+                        reason: format!("invalid chunk at offset {offset}"),
+                    }
+                );
+                self.skipped_chunks += 1;
+                continue;
+            }
+            decode_xor(&self.buffer, samples).map_err(|reason| {
+                InvalidBlockSnafu {
+                    path,
+                    reason: format!("chunk at offset {offset}: {reason}"),
+                }
+                .build()
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Read the chunk data into the buffer and returns the encoding, or `None` if the
+    /// chunk header is invalid.
+    fn read_chunk(
+        &mut self,
+        segment: u32,
+        offset: u64,
+        path: &Path,
+    ) -> std::io::Result<Option<u8>> {
+        let file = match self.files.entry(segment) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let mut file = File::open(path)?;
+                let mut magic = [0; 4];
+                file.read_exact(&mut magic)?;
+                if u32::from_be_bytes(magic) != CHUNKS_MAGIC {
+                    return Ok(None);
+                }
+                entry.insert(file)
+            }
+        };
+
+        // The header is the length in uvarint and the encoding.
+        let mut header = Vec::with_capacity(11);
+        file.seek(SeekFrom::Start(offset))?;
+        file.by_ref().take(11).read_to_end(&mut header)?;
+        let mut buf = Decbuf::new(&header);
+        let (Ok(len), Ok(encoding)) = (buf.uvarint(), buf.byte()) else {
+            return Ok(None);
+        };
+        // The chunk can't be larger than the file.
+        let start = offset + buf.position() as u64;
+        let file_len = file.metadata()?.len();
+        if start.checked_add(len).is_none_or(|end| end > file_len) {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(start))?;
+        self.buffer.resize(len as usize, 0);
+        file.read_exact(&mut self.buffer)?;
+        Ok(Some(encoding))
+    }
+}
+
+/// Open the block directory, returns an error if it has no index.
+pub(crate) fn open(dir: &Path) -> Result<(Index, ChunkReader)> {
+    let meta = dir.join("meta.json");
+    File::open(&meta).context(OpenFileSnafu { path: meta })?;
+    Ok((Index::open(dir)?, ChunkReader::new(dir)))
+}
+
+/// Decode samples of a XOR chunk.
+///
+/// Timestamps are delta-of-delta encoded and values are XOR encoded like Gorilla.
+fn decode_xor(data: &[u8], samples: &mut Vec<(i64, f64)>) -> Result<(), String> {
+    let count = u16::from_be_bytes([
+        *data.first().ok_or("empty chunk")?,
+        *data.get(1).ok_or("empty chunk")?,
+    ]);
+    let mut reader = BitReader::new(&data[2..]);
+    let mut t = 0;
+    let mut delta = 0;
+    let mut v = 0u64;
+    let mut leading = 0;
+    let mut trailing = 0;
+    for i in 0..count {
+        match i {
+            0 => {
+                t = reader.varint()?;
+                v = reader.bits(64)?;
+            }
+            1 => {
+                delta = reader.uvarint()? as i64;
+                t += delta;
+                v = reader.xor(v, &mut leading, &mut trailing)?;
+            }
+            _ => {
+                let mut prefix = 0;
+                while prefix < 4 && reader.bit()? {
+                    prefix += 1;
+                }
+                let size = [0, 14, 17, 20, 64][prefix];
+                let dod = if size == 0 {
+                    0
+                } else {
+                    let bits = reader.bits(size)?;
+                    if size != 64 && bits > 1 << (size - 1) {
+                        bits as i64 - (1 << size)
+                    } else {
+                        bits as i64
+                    }
+                };
+                delta += dod;
+                t += delta;
+                v = reader.xor(v, &mut leading, &mut trailing)?;
+            }
+        }
+        samples.push((t, f64::from_bits(v)));
+    }
+    Ok(())
+}
+
+/// Read a reference of the symbol table with `num_symbols` symbols.
+fn symbol(buf: &mut Decbuf, num_symbols: usize) -> Result<u32, String> {
+    let symbol = buf.uvarint()?;
+    if symbol >= num_symbols as u64 {
+        return Err(format!("symbol {symbol} is out of range"));
+    }
+    Ok(symbol as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writer of bits for XOR chunks.
+    #[derive(Default)]
+    struct BitWriter {
+        data: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, value: u64, n: usize) {
+            for i in (0..n).rev() {
+                if self.len.is_multiple_of(8) {
+                    self.data.push(0);
+                }
+                if (value >> i) & 1 == 1 {
+                    *self.data.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+                }
+                self.len += 1;
+            }
+        }
+
+        fn uvarint(&mut self, value: u64) {
+            for byte in encode_uvarint(value) {
+                self.bits(u64::from(byte), 8);
+            }
+        }
+    }
+
+    fn encode_uvarint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    fn encode_varint(value: i64) -> Vec<u8> {
+        encode_uvarint(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    /// Encode samples like the XOR chunk of Prometheus.
+    fn encode_xor(samples: &[(i64, f64)]) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        let (mut t, mut delta, mut v) = (0, 0, 0u64);
+        let (mut leading, mut trailing) = (0xff, 0);
+        for (i, (ts, value)) in samples.iter().enumerate() {
+            match i {
+                0 => {
+                    for byte in encode_varint(*ts) {
+                        w.bits(u64::from(byte), 8);
+                    }
+                    w.bits(value.to_bits(), 64);
+                    t = *ts;
+                    v = value.to_bits();
+                    continue;
+                }
+                1 => {
+                    delta = ts - t;
+                    w.uvarint(delta as u64);
+                }
+                _ => {
+                    let dod = ts - t - delta;
+                    delta = ts - t;
+                    let fits = |n: u32| -((1 << (n - 1)) - 1) <= dod && dod <= 1 << (n - 1);
+                    match dod {
+                        0 => w.bits(0, 1),
+                        _ if fits(14) => {
+                            w.bits(0b10, 2);
+                            w.bits(dod as u64, 14);
+                        }
+                        _ if fits(17) => {
+                            w.bits(0b110, 3);
+                            w.bits(dod as u64, 17);
+                        }
+                        _ if fits(20) => {
+                            w.bits(0b1110, 4);
+                            w.bits(dod as u64, 20);
+                        }
+                        _ => {
+                            w.bits(0b1111, 4);
+                            w.bits(dod as u64, 64);
+                        }
+                    }
+                }
+            }
+            t = *ts;
+
+            let xor = value.to_bits() ^ v;
+            v = value.to_bits();
+            if xor == 0 {
+                w.bits(0, 1);
+                continue;
+            }
+            w.bits(1, 1);
+            let lz = (xor.leading_zeros() as usize).min(31);
+            let tz = xor.trailing_zeros() as usize;
+            if leading != 0xff && lz >= leading && tz >= trailing {
+                w.bits(0, 1);
+            } else {
+                (leading, trailing) = (lz, tz);
+                w.bits(1, 1);
+                w.bits(leading as u64, 5);
+                w.bits((64 - leading - trailing) as u64, 6);
+            }
+            w.bits(xor >> trailing, 64 - leading - trailing);
+        }
+
+        let mut data = (samples.len() as u16).to_be_bytes().to_vec();
+        data.extend(w.data);
+        data
+    }
+
+    #[test]
+    fn test_read_block() {
+        let samples = [
+            (1451606400000, 1.0),
+            (1451606401000, 1.0),
+            (1451606402000, 2.5),
+            (1451606403500, -3.0),
+            (1451606403501, 1e100),
+            (1451606500000, 1e100),
+            (1451706500000, 0.1),
+            (1451706500000, 0.2),
+        ];
+
+        // A chunk of floats and a chunk of native histograms.
+        let mut segment = Vec::new();
+        segment.extend(CHUNKS_MAGIC.to_be_bytes());
+        segment.extend([1, 0, 0, 0]);
+        let mut chunks = Vec::new();
+        for (encoding, data) in [(ENCODING_XOR, encode_xor(&samples)), (2, vec![0, 0])] {
+            chunks.push(segment.len() as u64);
+            segment.extend(encode_uvarint(data.len() as u64));
+            segment.push(encoding);
+            segment.extend(data);
+            segment.extend([0; 4]);
+        }
+
+        let symbols = ["__name__", "host", "a", "up"];
+        let mut index = INDEX_MAGIC.to_be_bytes().to_vec();
+        index.push(2);
+        let symbols_start = index.len() as u64;
+        index.extend([0; 4]);
+        index.extend((symbols.len() as u32).to_be_bytes());
+        for symbol in symbols {
+            index.extend(encode_uvarint(symbol.len() as u64));
+            index.extend(symbol.as_bytes());
+        }
+        index.extend([0; 4]);
+        index.resize(index.len().next_multiple_of(16), 0);
+        let series_start = index.len() as u64;
+        for labels in [vec![(0, 3)], vec![(0, 3), (1, 2)]] {
+            index.resize(index.len().next_multiple_of(16), 0);
+            let mut series = encode_uvarint(labels.len() as u64);
+            for (name, value) in labels {
+                series.extend([name, value]);
+            }
+            series.extend(encode_uvarint(2));
+            series.extend(encode_varint(1451606400000));
+            series.extend(encode_uvarint(100000000));
+            series.extend(encode_uvarint(chunks[0]));
+            series.extend(encode_uvarint(0));
+            series.extend(encode_uvarint(0));
+            series.extend(encode_varint((chunks[1] - chunks[0]) as i64));
+            index.extend(encode_uvarint(series.len() as u64));
+            index.extend(series);
+            index.extend([0; 4]);
+        }
+        index.resize(index.len().next_multiple_of(16), 0);
+        let postings_start = index.len() as u64;
+        for offset in [
+            symbols_start,
+            series_start,
+            0,
+            0,
+            postings_start,
+            postings_start,
+        ] {
+            index.extend(offset.to_be_bytes());
+        }
+        index.extend([0; 4]);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("meta.json"), "{}").unwrap();
+        fs::write(dir.path().join("index"), index).unwrap();
+        fs::create_dir(dir.path().join("chunks")).unwrap();
+        fs::write(dir.path().join("chunks/000001"), segment).unwrap();
+
+        let (index, mut reader) = open(dir.path()).unwrap();
+        assert_eq!(2, index.series().len());
+        let series = &index.series()[1];
+        assert_eq!(
+            vec![("__name__", "up"), ("host", "a")],
+            index.labels(series)
+        );
+        let mut decoded = Vec::new();
+        reader.read_samples(&series.chunks, &mut decoded).unwrap();
+        assert_eq!(samples.to_vec(), decoded);
+        assert_eq!(1, reader.skipped_chunks);
+
+        assert!(open(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_invalid_lengths() {
+        let mut series = encode_uvarint(0);
+        series.extend(encode_uvarint(u64::MAX));
+        let e = parse_series(&mut Decbuf::new(&series), 0).unwrap_err();
+        assert_eq!(format!("{} chunks exceed the index", u64::MAX), e);
+
+        // The chunk is larger than the segment file.
+        let mut segment = CHUNKS_MAGIC.to_be_bytes().to_vec();
+        segment.extend([1, 0, 0, 0]);
+        segment.extend(encode_uvarint(1 << 40));
+        segment.push(ENCODING_XOR);
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("chunks")).unwrap();
+        fs::write(dir.path().join("chunks/000001"), segment).unwrap();
+        let mut reader = ChunkReader::new(dir.path());
+        let e = reader.read_samples(&[8], &mut Vec::new()).unwrap_err();
+        assert!(e.to_string().contains("invalid chunk at offset 8"), "{e}");
+    }
+}
@@ -0,0 +1,271 @@
+//! Parser of the Prometheus text exposition format and OpenMetrics.
+
+use crate::error::{InvalidSampleSnafu, LineSnafu, Result};
+use crate::reject;
+use snafu::{ensure, OptionExt, ResultExt};
+
+/// A sample in the exposition.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Sample<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) labels: Vec<(&'a str, String)>,
+    pub(crate) value: f64,
+    /// Timestamp in nanosecond.
+    pub(crate) timestamp: Option<i64>,
+}
+
+/// Parse samples in the exposition.
+///
+/// The input is OpenMetrics if it ends with `# EOF`, where timestamps are in second,
+/// otherwise timestamps are in millisecond. `HELP`, `TYPE` and other comments are
+/// skipped, exemplars are validated and dropped.
+pub(crate) fn parse(text: &str) -> Result<Vec<Sample<'_>>> {
+    let openmetrics = text.trim_end().ends_with("# EOF");
+    let mut samples = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let result = parse_sample(line, openmetrics).context(LineSnafu { line: i + 1 });
+        if let Some(sample) = reject::check(result, || line)? {
+            samples.push(sample);
+        }
+    }
+
+    Ok(samples)
+}
+
+fn parse_sample(line: &str, openmetrics: bool) -> Result<Sample<'_>> {
+    let mut cursor = Cursor { rest: line };
+    let name = cursor.take_until(|c| c == '{' || c.is_whitespace());
+    ensure!(
+        !name.is_empty(),
+        InvalidSampleSnafu {
+            reason: "missing metric name"
+        }
+    );
+    let labels = if cursor.eat('{') {
+        cursor.labels()?
+    } else {
+        Vec::new()
+    };
+
+    let value = cursor.token();
+    let value = parse_value(value).with_context(|| InvalidSampleSnafu {
+        reason: format!("invalid value {value:?}"),
+    })?;
+    cursor.skip_whitespace();
+    let timestamp = match cursor.rest {
+        "" => None,
+        rest if rest.starts_with('#') => None,
+        _ => {
+            let ts = cursor.token();
+            let ts = parse_timestamp(ts, openmetrics).with_context(|| InvalidSampleSnafu {
+                reason: format!("invalid timestamp {ts:?}"),
+            })?;
+            Some(ts)
+        }
+    };
+
+    // Exemplar of OpenMetrics: `# {labels} value [timestamp]`.
+    cursor.skip_whitespace();
+    if cursor.eat('#') {
+        cursor.skip_whitespace();
+        ensure!(
+            cursor.eat('{'),
+            InvalidSampleSnafu {
+                reason: "invalid exemplar"
+            }
+        );
+        cursor.labels()?;
+        let value = cursor.token();
+        ensure!(
+            parse_value(value).is_some(),
+            InvalidSampleSnafu {
+                reason: format!("invalid exemplar value {value:?}")
+            }
+        );
+        cursor.token();
+    }
+    ensure!(
+        cursor.token().is_empty(),
+        InvalidSampleSnafu {
+            reason: "unexpected trailing content"
+        }
+    );
+
+    Ok(Sample {
+        name,
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+/// Parse a float value, including `NaN` and `+Inf`.
+fn parse_value(value: &str) -> Option<f64> {
+    match value {
+        "+Inf" | "Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        _ => value.parse().ok(),
+    }
+}
+
+/// Parse the timestamp to nanosecond.
+fn parse_timestamp(ts: &str, openmetrics: bool) -> Option<i64> {
+    if openmetrics {
+        // Parse the fraction as digits to avoid the precision loss of floats.
+        let (seconds, fraction) = ts.split_once('.').unwrap_or((ts, ""));
+        if fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+            let seconds: f64 = ts.parse().ok()?;
+            return Some((seconds * 1e9).round() as i64);
+        }
+        let nanos: i64 = format!("{fraction:0<9}").parse().ok()?;
+        let seconds: i64 = seconds.parse().ok()?;
+        let nanos = if ts.starts_with('-') { -nanos } else { nanos };
+        return seconds.checked_mul(1_000_000_000)?.checked_add(nanos);
+    }
+    ts.parse::<i64>().ok()?.checked_mul(1_000_000)
+}
+
+/// Cursor over the rest of a line.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    /// Consume the char if the rest starts with it.
+    fn eat(&mut self, c: char) -> bool {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn take_until(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let end = self.rest.find(f).unwrap_or(self.rest.len());
+        let (taken, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        taken
+    }
+
+    /// Returns the next token separated by whitespaces.
+    fn token(&mut self) -> &'a str {
+        self.skip_whitespace();
+        self.take_until(char::is_whitespace)
+    }
+
+    /// Parse labels after `{` until `}`.
+    fn labels(&mut self) -> Result<Vec<(&'a str, String)>> {
+        let mut labels = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(labels);
+            }
+            let name = self.take_until(|c| c == '=' || c.is_whitespace()).trim();
+            self.skip_whitespace();
+            ensure!(
+                !name.is_empty() && self.eat('=') && self.eat('"'),
+                InvalidSampleSnafu {
+                    reason: format!("invalid label {name:?}")
+                }
+            );
+            labels.push((name, self.label_value()?));
+            self.skip_whitespace();
+            self.eat(',');
+        }
+    }
+
+    /// Parse an escaped label value after `"` until the closing `"`.
+    fn label_value(&mut self) -> Result<String> {
+        let mut value = String::new();
+        let mut chars = self.rest.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &self.rest[i + 1..];
+                    return Ok(value);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => value.push('\n'),
+                    Some((_, c)) => value.push(c),
+                    None => break,
+                },
+                c => value.push(c),
+            }
+        }
+
+        InvalidSampleSnafu {
+            reason: "unterminated label value",
+        }
+        .fail()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let text = r#"# HELP http_requests_total The total number of HTTP requests.
+# TYPE http_requests_total counter
+http_requests_total{method="post",code="200"} 1027 1395066363000
+http_requests_total{method="post",code="400",} 3 1395066363000
+
+msdos_file_access_time_seconds{path="C:\\DIR\\FILE.TXT",error="Cannot find file:\n\"FILE.TXT\""} 1.458255915e9
+rpc_duration_seconds{quantile="0.5"} NaN
+up 1
+"#;
+        let samples = parse(text).unwrap();
+        assert_eq!(5, samples.len());
+        assert_eq!(
+            Sample {
+                name: "http_requests_total",
+                labels: vec![("method", "post".to_string()), ("code", "200".to_string())],
+                value: 1027.0,
+                timestamp: Some(1395066363000000000),
+            },
+            samples[0]
+        );
+        assert_eq!(2, samples[1].labels.len());
+        assert_eq!(
+            vec![
+                ("path", "C:\\DIR\\FILE.TXT".to_string()),
+                ("error", "Cannot find file:\n\"FILE.TXT\"".to_string())
+            ],
+            samples[2].labels
+        );
+        assert!(samples[3].value.is_nan());
+        assert_eq!(
+            Sample {
+                name: "up",
+                labels: Vec::new(),
+                value: 1.0,
+                timestamp: None,
+            },
+            samples[4]
+        );
+
+        let text = r#"# TYPE foo counter
+foo_total{a="b"} 17.0 1520879607.789 # {trace_id="KOO5S4vxi0o"} 0.67 1520879602.029
+# EOF
+"#;
+        let samples = parse(text).unwrap();
+        assert_eq!(Some(1520879607789000000), samples[0].timestamp);
+
+        let err = parse("up 1\nup{a=\"b} 1\n").unwrap_err();
+        assert_eq!("Error at line 2", err.to_string());
+    }
+}
@@ -0,0 +1,230 @@
+//! Tools for QuestDB target.
+
+use crate::error::{ConnectSnafu, InvalidPointSnafu, Result, SendSnafu};
+use crate::influx::{push_fields, push_series, read_lines};
+use crate::io::{open_input, Compression};
+use crate::progress;
+use clap::{Parser, ValueEnum};
+use influxdb_line_protocol::{FieldValue, ParsedLine};
+use snafu::{OptionExt, ResultExt};
+use std::fmt::Write as FmtWrite;
+use std::io::{BufWriter, Write};
+use std::net::TcpStream;
+
+/// QuestDB command.
+#[derive(Debug, Parser)]
+pub struct QuestdbCommand {
+    #[clap(subcommand)]
+    subcmd: QuestdbSubcommand,
+}
+
+impl QuestdbCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            QuestdbSubcommand::Send(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for QuestDB.
+#[derive(Debug, Parser)]
+enum QuestdbSubcommand {
+    /// Send line protocol to the ILP port over TCP.
+    Send(SendLines),
+}
+
+/// Where designated timestamps come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum TimestampSource {
+    /// Timestamps of lines, lines without timestamp get the time of the server.
+    Line,
+    /// The time of the server when it receives each line.
+    Server,
+}
+
+/// A tag or field of all tables or a table, `[table:]name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Column {
+    table: Option<String>,
+    name: String,
+}
+
+impl Column {
+    fn matches(&self, table: &str, name: &str) -> bool {
+        self.name == name && self.table.as_deref().is_none_or(|t| t == table)
+    }
+}
+
+fn parse_column(s: &str) -> Result<Column, String> {
+    let (table, name) = match s.split_once(':') {
+        Some((table, name)) => (Some(table.to_string()), name),
+        None => (None, s),
+    };
+    if name.is_empty() || table.as_deref() == Some("") {
+        return Err(format!("{s} is not in the form of [table:]name"));
+    }
+    Ok(Column {
+        table,
+        name: name.to_string(),
+    })
+}
+
+/// Send line protocol file to the InfluxDB Line Protocol port of QuestDB over TCP.
+///
+/// Each measurement is a table, tags are symbol columns and fields are columns. String
+/// fields can be written as symbols by `--symbol`, and tags as string columns by
+/// `--column`. Unsigned integers are written as integers as QuestDB has no unsigned
+/// types, and lines with values out of range are invalid. QuestDB doesn't respond to
+/// lines over TCP, check its logs for rejected lines.
+#[derive(Debug, Parser)]
+struct SendLines {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Address of the ILP port.
+    #[arg(long, default_value = "localhost:9009")]
+    addr: String,
+    /// Where designated timestamps come from.
+    #[arg(long, value_enum, default_value_t = TimestampSource::Line)]
+    timestamp: TimestampSource,
+    /// String field to write as a symbol column, `[table:]field`, e.g. cpu:state.
+    #[arg(long, value_parser = parse_column)]
+    symbol: Vec<Column>,
+    /// Tag to write as a string column, `[table:]tag`, e.g. request_id.
+    #[arg(long, value_parser = parse_column)]
+    column: Vec<Column>,
+    /// Compression of the input, detected by the extension by default.
+    #[arg(long, value_enum, default_value_t = Compression::Auto)]
+    input_compression: Compression,
+}
+
+impl SendLines {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.input_compression)?;
+        let stream = TcpStream::connect(&self.addr).context(ConnectSnafu { addr: &self.addr })?;
+        let mut writer = BufWriter::new(stream);
+        let mut buffer = String::new();
+        read_lines(input, |line| {
+            buffer.clear();
+            self.push_line(&mut buffer, &line)?;
+            writer
+                .write_all(buffer.as_bytes())
+                .context(SendSnafu { addr: &self.addr })?;
+            progress::add_lines_written(1);
+            Ok(())
+        })?;
+        writer.flush().context(SendSnafu { addr: &self.addr })
+    }
+
+    /// Push the line in the dialect of QuestDB.
+    fn push_line(&self, buf: &mut String, line: &ParsedLine<'_>) -> Result<()> {
+        let table = line.series.measurement.as_str();
+        let is_symbol = |name: &str| self.symbol.iter().any(|c| c.matches(table, name));
+        let is_column = |name: &str| self.column.iter().any(|c| c.matches(table, name));
+
+        let mut symbols = Vec::new();
+        let mut columns = Vec::new();
+        for (key, value) in line.series.tag_set.iter().flatten() {
+            if is_column(key.as_str()) {
+                columns.push((key.as_str(), FieldValue::String(value.clone())));
+            } else {
+                symbols.push((key.as_str(), value.as_str()));
+            }
+        }
+        let mut fields = Vec::with_capacity(line.field_set.len() + columns.len());
+        for (key, value) in &line.field_set {
+            match value {
+                FieldValue::String(v) if is_symbol(key.as_str()) => {
+                    symbols.push((key.as_str(), v.as_str()));
+                }
+                FieldValue::U64(v) => {
+                    let v = i64::try_from(*v).ok().context(InvalidPointSnafu {
+                        reason: format!("unsigned value {v} of {key} is out of range"),
+                    })?;
+                    fields.push((key.as_str(), FieldValue::I64(v)));
+                }
+                _ => fields.push((key.as_str(), value.clone())),
+            }
+        }
+        fields.extend(columns);
+        if fields.is_empty() {
+            // A line needs a field, which is a tag written as a column.
+            return InvalidPointSnafu {
+                reason: format!("line of {table} has no field"),
+            }
+            .fail();
+        }
+
+        push_series(buf, table, symbols);
+        push_fields(buf, fields.iter().map(|(key, value)| (*key, value)));
+        if let (TimestampSource::Line, Some(ts)) = (self.timestamp, line.timestamp) {
+            write!(buf, " {ts}").unwrap();
+        }
+        buf.push('\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use influxdb_line_protocol::parse_lines;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_send_lines() {
+        let input = "cpu,host=a,request=r1 usage=1,total=2u,state=\"ok\" 1
+mem,request=r2 state=\"ok\",free=18446744073709551615u
+";
+        let convert = |args: &str| {
+            let send = SendLines::try_parse_from(
+                format!("send -i - {args}")
+                    .split(' ')
+                    .filter(|arg| !arg.is_empty()),
+            )
+            .unwrap();
+            parse_lines(input)
+                .map(|line| {
+                    let mut buf = String::new();
+                    send.push_line(&mut buf, &line.unwrap()).map(|()| buf)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let lines = convert("--symbol cpu:state --column request");
+        assert_eq!(
+            "cpu,host=a,state=ok usage=1,total=2i,request=\"r1\" 1\n",
+            lines[0].as_ref().unwrap()
+        );
+        assert!(lines[1].as_ref().unwrap_err().is_invalid_line());
+        let lines = convert("--symbol state --timestamp server");
+        assert_eq!(
+            "cpu,host=a,request=r1,state=ok usage=1,total=2i\n",
+            lines[0].as_ref().unwrap()
+        );
+        assert!(parse_column("cpu:").is_err());
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = String::new();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "cpu,host=a usage=1 1\ncpu usage=2u 2\n").unwrap();
+        let args = format!("send -i {} --addr {addr}", file.path().display());
+        SendLines::try_parse_from(args.split(' '))
+            .unwrap()
+            .run()
+            .unwrap();
+        assert_eq!(
+            "cpu,host=a usage=1 1\ncpu usage=2i 2\n",
+            handle.join().unwrap()
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Rate limit of writes to remote endpoints.
+
+use clap::Args;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Options to limit the rate of writes by token buckets, shared by concurrent writers.
+///
+/// Each bucket holds tokens of a second, a write larger than the bucket waits until the
+/// tokens it borrowed are refilled.
+#[derive(Debug, Default, Args)]
+pub(crate) struct RateLimit {
+    /// Maximum number of points written per second.
+    #[arg(long, value_parser = parse_rate)]
+    rate: Option<f64>,
+    /// Maximum megabytes written per second.
+    #[arg(long, value_parser = parse_rate)]
+    bandwidth: Option<f64>,
+    #[arg(skip)]
+    buckets: Mutex<[TokenBucket; 2]>,
+}
+
+impl RateLimit {
+    /// Wait until the points and bytes can be written.
+    pub(crate) fn acquire(&self, points: usize, bytes: usize) {
+        if self.rate.is_none() && self.bandwidth.is_none() {
+            return;
+        }
+        let now = Instant::now();
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let [points_bucket, bytes_bucket] = &mut *buckets;
+            let points_wait = self
+                .rate
+                .map(|rate| points_bucket.take(rate, points as f64, now));
+            let bytes_wait = self
+                .bandwidth
+                .map(|bandwidth| bytes_bucket.take(bandwidth * 1e6, bytes as f64, now));
+            points_wait.max(bytes_wait).unwrap_or_default()
+        };
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct TokenBucket {
+    tokens: f64,
+    /// Time of the last refill, the bucket is full before the first take.
+    updated: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Take tokens and returns how long to wait until they are available.
+    fn take(&mut self, rate: f64, tokens: f64, now: Instant) -> Duration {
+        self.tokens = match self.updated {
+            Some(updated) => {
+                let elapsed = now.saturating_duration_since(updated).as_secs_f64();
+                (self.tokens + elapsed * rate).min(rate)
+            }
+            None => rate,
+        };
+        self.updated = Some(now);
+        self.tokens -= tokens;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+/// Parse a positive rate.
+fn parse_rate(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(rate),
+        _ => Err(format!("invalid rate {value}, expect a positive number")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::default();
+        assert_eq!(Duration::ZERO, bucket.take(100.0, 50.0, start));
+        assert_eq!(Duration::from_millis(500), bucket.take(100.0, 100.0, start));
+        // Refilled to 50 tokens after a second.
+        let later = start + Duration::from_secs(1);
+        assert_eq!(Duration::ZERO, bucket.take(100.0, 50.0, later));
+        // Never more than a second of tokens.
+        let idle = later + Duration::from_secs(10);
+        assert_eq!(Duration::from_secs(1), bucket.take(100.0, 200.0, idle));
+        assert!(parse_rate("0").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! Rejection of invalid lines.
+//!
+//! Invalid lines abort commands by default. They can be skipped by `--on-error`, and
+//! written to the `--errors` file with their errors as comments, so the file can be fixed
+//! and converted again.
+
+use crate::error::{Error, Result, WriteOutputSnafu};
+use crate::io::{create_writer, Compression, Output};
+use crate::progress;
+use clap::ValueEnum;
+use snafu::ResultExt;
+use std::cell::Cell;
+use std::error::Error as _;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use tracing::{debug, warn};
+
+static REJECTS: OnceLock<Rejects> = OnceLock::new();
+
+thread_local! {
+    /// Whether invalid lines are skipped without being rejected.
+    static SILENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// What to do with invalid lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnError {
+    /// Stop at the first invalid line.
+    Abort,
+    /// Skip invalid lines.
+    Skip,
+    /// Skip invalid lines and log them as warnings.
+    Log,
+}
+
+struct Rejects {
+    on_error: OnError,
+    /// Output of rejected lines.
+    errors: Mutex<Option<Box<dyn Output + Send>>>,
+}
+
+impl Rejects {
+    /// Write the line with its error to the output of rejected lines.
+    fn write(&self, message: &str, text: &str) -> Result<()> {
+        let mut errors = self.errors.lock().unwrap();
+        let Some(errors) = errors.as_mut() else {
+            return Ok(());
+        };
+        let mut rejected = format!("# {message}\n");
+        let text = text.trim_end_matches(['\r', '\n']);
+        if !text.is_empty() {
+            rejected.push_str(text);
+            rejected.push('\n');
+        }
+        errors
+            .write_all(rejected.as_bytes())
+            .context(WriteOutputSnafu)
+    }
+}
+
+/// Handle invalid lines by `on_error`, rejected lines are written to `errors` if it is set.
+pub fn init(on_error: OnError, errors: Option<&Path>) -> Result<()> {
+    let errors = errors
+        .map(|path| create_writer(path, Compression::Auto))
+        .transpose()?;
+    let _ = REJECTS.set(Rejects {
+        on_error,
+        errors: Mutex::new(errors),
+    });
+    Ok(())
+}
+
+/// Finish and close the output of rejected lines.
+pub fn finish() -> Result<()> {
+    let Some(rejects) = REJECTS.get() else {
+        return Ok(());
+    };
+    match rejects.errors.lock().unwrap().take() {
+        Some(mut errors) => errors.finish().context(WriteOutputSnafu),
+        None => Ok(()),
+    }
+}
+
+/// Returns the value converted from the line, or `None` if the line is invalid and
+/// skipped. `text` returns the text of the line to reject.
+///
+/// Errors not caused by the line, e.g. failures to write the output, are always returned.
+pub(crate) fn check<T, S: AsRef<str>>(
+    result: Result<T>,
+    text: impl FnOnce() -> S,
+) -> Result<Option<T>> {
+    let e = match result {
+        Ok(value) => return Ok(Some(value)),
+        Err(e) => e,
+    };
+    let Some(rejects) = REJECTS.get().filter(|_| e.is_invalid_line()) else {
+        return Err(e);
+    };
+
+    if SILENT.get() && rejects.on_error != OnError::Abort {
+        return Ok(None);
+    }
+    let message = message(&e);
+    rejects.write(&message, text().as_ref())?;
+    match rejects.on_error {
+        OnError::Abort => return Err(e),
+        OnError::Skip => debug!("Skipped invalid line: {message}"),
+        OnError::Log => warn!("Skipped invalid line: {message}"),
+    }
+    progress::add_lines_skipped(1);
+    Ok(None)
+}
+
+/// Run a pass over the input in which invalid lines are skipped without being rejected,
+/// e.g. the pass to infer the schema, as they are rejected by the pass converting them.
+pub(crate) fn silently<T>(f: impl FnOnce() -> T) -> T {
+    let silent = SILENT.replace(true);
+    let result = f();
+    SILENT.set(silent);
+    result
+}
+
+/// Returns the message of the error with all its causes.
+pub(crate) fn message(e: &Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{InvalidPointSnafu, LineSnafu};
+
+    #[test]
+    fn test_rejects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("errors.txt");
+        let rejects = Rejects {
+            on_error: OnError::Skip,
+            errors: Mutex::new(Some(create_writer(&path, Compression::Auto).unwrap())),
+        };
+        let e = InvalidPointSnafu {
+            reason: "missing value",
+        }
+        .fail::<()>()
+        .context(LineSnafu { line: 2usize })
+        .unwrap_err();
+        assert!(e.is_invalid_line());
+        rejects.write(&message(&e), "cpu.usage 1\n").unwrap();
+        rejects.write("Invalid field", "").unwrap();
+        drop(rejects);
+        assert_eq!(
+            "# Error at line 2: Invalid point: missing value\ncpu.usage 1\n# Invalid field\n",
+            std::fs::read_to_string(&path).unwrap()
+        );
+
+        // Errors are returned if rejection is not initialized.
+        assert!(check::<(), _>(Err(e), || "cpu.usage 1").is_err());
+    }
+}
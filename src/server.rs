@@ -0,0 +1,369 @@
+//! Minimal HTTP/1.1 server to receive requests of agents.
+
+use crate::error::{BindSnafu, Result};
+use flate2::read::MultiGzDecoder;
+use snafu::ResultExt;
+use std::borrow::Cow;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use tracing::{info, warn};
+
+/// Maximum size of request bodies.
+const MAX_BODY_SIZE: u64 = 256 << 20;
+
+/// Request with the body of the `Content-Length` or in chunks.
+#[derive(Debug, Default)]
+pub(crate) struct Request {
+    pub(crate) method: String,
+    /// Path without the query.
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+    /// Returns the value of the header, the name is case-insensitive.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Returns the body decompressed by the `Content-Encoding`, the decompressed body is
+    /// also limited to `MAX_BODY_SIZE`.
+    pub(crate) fn decoded_body(&self) -> io::Result<Cow<'_, [u8]>> {
+        match self.header("Content-Encoding") {
+            Some(encoding) if encoding.eq_ignore_ascii_case("gzip") => {
+                Ok(Cow::Owned(gunzip(&self.body, MAX_BODY_SIZE)?))
+            }
+            None => Ok(Cow::Borrowed(&self.body)),
+            Some(encoding) if encoding.eq_ignore_ascii_case("identity") => {
+                Ok(Cow::Borrowed(&self.body))
+            }
+            Some(encoding) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported content encoding {encoding}"),
+            )),
+        }
+    }
+}
+
+/// Decompress the gzip body, returns an error if it is larger than `limit` bytes.
+fn gunzip(body: &[u8], limit: u64) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    MultiGzDecoder::new(body)
+        .take(limit + 1)
+        .read_to_end(&mut decoded)?;
+    if decoded.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "decompressed request body is too large",
+        ));
+    }
+    Ok(decoded)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) content_type: &'static str,
+    pub(crate) body: Vec<u8>,
+}
+
+impl Response {
+    /// Response of the status with a plain text body.
+    pub(crate) fn text(status: u16, body: impl Into<String>) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body: body.into().into_bytes(),
+        }
+    }
+}
+
+/// Listen on the address and handle requests of each connection in a thread, until the
+/// process is killed.
+pub(crate) fn serve<F>(addr: &str, handle: F) -> Result<()>
+where
+    F: Fn(&Request) -> Response + Sync,
+{
+    let listener = TcpListener::bind(addr).context(BindSnafu { addr })?;
+    info!("Listening on {addr}");
+    thread::scope(|scope| {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Failed to accept connection: {e}");
+                    continue;
+                }
+            };
+            let handle = &handle;
+            scope.spawn(move || serve_stream(stream, handle));
+        }
+    });
+    Ok(())
+}
+
+fn serve_stream(stream: TcpStream, handle: &impl Fn(&Request) -> Response) {
+    let peer = stream
+        .peer_addr()
+        .map(|addr| addr.to_string())
+        .unwrap_or_default();
+    if let Err(e) = serve_connection(BufReader::new(&stream), &stream, handle) {
+        warn!("Failed to serve connection from {peer}: {e}");
+    }
+}
+
+/// Handle requests of a connection until it is closed.
+fn serve_connection<R: BufRead, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    handle: &impl Fn(&Request) -> Response,
+) -> io::Result<()> {
+    loop {
+        let (request, keep_alive) = match read_request(&mut reader, &mut writer) {
+            Ok(Some(request)) => request,
+            Ok(None) => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                write_response(&mut writer, &Response::text(400, e.to_string()), false)?;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        let response = handle(&request);
+        write_response(&mut writer, &response, keep_alive)?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// Read a request and whether the connection is kept alive, or `None` at the end.
+fn read_request<R: BufRead, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+) -> io::Result<Option<(Request, bool)>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("invalid request line"));
+    };
+    let mut request = Request {
+        method: method.to_string(),
+        path: target.split('?').next().unwrap_or_default().to_string(),
+        ..Default::default()
+    };
+    let mut keep_alive = version != "HTTP/1.0";
+
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("unexpected end of headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let (name, value) = header
+            .split_once(':')
+            .ok_or_else(|| invalid("invalid header"))?;
+        request
+            .headers
+            .push((name.trim().to_string(), value.trim().to_string()));
+    }
+    match request.header("Connection") {
+        Some(v) if v.eq_ignore_ascii_case("close") => keep_alive = false,
+        Some(v) if v.eq_ignore_ascii_case("keep-alive") => keep_alive = true,
+        _ => {}
+    }
+    if request
+        .header("Expect")
+        .is_some_and(|v| v.eq_ignore_ascii_case("100-continue"))
+    {
+        writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+        writer.flush()?;
+    }
+
+    let chunked = request
+        .header("Transfer-Encoding")
+        .is_some_and(|v| v.eq_ignore_ascii_case("chunked"));
+    if chunked {
+        request.body = read_chunks(reader)?;
+    } else if let Some(len) = request.header("Content-Length") {
+        let len: u64 = len.parse().map_err(|_| invalid("invalid content length"))?;
+        if len > MAX_BODY_SIZE {
+            return Err(invalid("request body is too large"));
+        }
+        request.body.resize(len as usize, 0);
+        reader.read_exact(&mut request.body)?;
+    }
+    Ok(Some((request, keep_alive)))
+}
+
+/// Read the body in chunks, trailers are ignored.
+fn read_chunks<R: BufRead>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidData, reason.to_string());
+    let mut body = Vec::new();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let size = line.trim_end().split(';').next().unwrap_or_default();
+        let size =
+            u64::from_str_radix(size.trim(), 16).map_err(|_| invalid("invalid chunk size"))?;
+        if body.len() as u64 + size > MAX_BODY_SIZE {
+            return Err(invalid("request body is too large"));
+        }
+        if size == 0 {
+            break;
+        }
+        let start = body.len();
+        body.resize(start + size as usize, 0);
+        reader.read_exact(&mut body[start..])?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+    // Skip trailers until the empty line.
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            return Ok(body);
+        }
+    }
+}
+
+fn write_response<W: Write>(
+    writer: &mut W,
+    response: &Response,
+    keep_alive: bool,
+) -> io::Result<()> {
+    let reason = match response.status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        _ => "Internal Server Error",
+    };
+    write!(
+        writer,
+        "HTTP/1.1 {} {reason}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: {}\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        if keep_alive { "keep-alive" } else { "close" },
+    )?;
+    writer.write_all(&response.body)?;
+    writer.flush()
+}
+
+/// Serve a request of each status in order on a local port for tests, returns the URL and
+/// the thread returning the request lines and bodies.
+#[cfg(test)]
+pub(crate) fn serve_statuses(
+    statuses: Vec<u16>,
+) -> (String, thread::JoinHandle<Vec<(String, Vec<u8>)>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for status in statuses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some(v) = header.to_lowercase().strip_prefix("content-length:") {
+                    content_length = v.trim().parse().unwrap();
+                }
+                header.clear();
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            requests.push((request_line, body));
+
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+        }
+        requests
+    });
+    (url, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serve_connection() {
+        let input = "POST /write?db=a HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello\
+POST /v1/metrics HTTP/1.1\r\nTransfer-Encoding: chunked\r\nExpect: 100-continue\r\n\r\n\
+3\r\nabc\r\n2;ext=1\r\nde\r\n0\r\n\r\n\
+GET /ping HTTP/1.1\r\nConnection: close\r\n\r\n\
+GET /ignored HTTP/1.1\r\n\r\n";
+        let mut output = Vec::new();
+        let handle = |request: &Request| {
+            let body = format!(
+                "{} {} {}",
+                request.method,
+                request.path,
+                String::from_utf8_lossy(&request.body)
+            );
+            Response::text(200, body)
+        };
+        serve_connection(input.as_bytes(), &mut output, &handle).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let bodies: Vec<_> = output
+            .split("HTTP/1.1 ")
+            .skip(1)
+            .map(|response| response.split("\r\n\r\n").nth(1).unwrap())
+            .collect();
+        assert_eq!(
+            vec![
+                "POST /write hello",
+                "",
+                "POST /v1/metrics abcde",
+                "GET /ping "
+            ],
+            bodies
+        );
+        assert!(output.ends_with("Connection: close\r\n\r\nGET /ping "));
+
+        let mut output = Vec::new();
+        serve_connection("invalid\r\n\r\n".as_bytes(), &mut output, &handle).unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("HTTP/1.1 400 Bad Request"));
+    }
+
+    #[test]
+    fn test_gunzip() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        encoder.write_all(&[0; 1000]).unwrap();
+        let body = encoder.finish().unwrap();
+        assert_eq!(vec![0; 1000], gunzip(&body, 1000).unwrap());
+        let err = gunzip(&body, 999).unwrap_err();
+        assert_eq!("decompressed request body is too large", err.to_string());
+
+        let request = Request {
+            headers: vec![("Content-Encoding".to_string(), "gzip".to_string())],
+            body,
+            ..Default::default()
+        };
+        assert_eq!(1000, request.decoded_body().unwrap().len());
+    }
+}
@@ -0,0 +1,279 @@
+//! Async sinks of line protocol for tokio services.
+//!
+//! The sinks send the same requests as `influx write` and `otel push`, without
+//! blocking the runtime.
+//!
+//! ```no_run
+//! use tsdb_tools::sink::{InfluxSink, OtlpSink};
+//!
+//! # async fn run() -> tsdb_tools::Result<()> {
+//! let influx = InfluxSink::new("http://localhost:8086", "bucket").token("token");
+//! influx.write_lines("cpu,host=a usage=1 1\n").await?;
+//!
+//! let mut otlp = OtlpSink::http("http://localhost:4318/v1/metrics");
+//! otlp.write_lines("cpu,host=a usage=1 1\n").await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{ReadInputSnafu, RequestStatusSnafu, Result, SendAsyncRequestSnafu};
+use crate::influx::read_lines;
+#[cfg(feature = "otel-grpc")]
+use crate::otel::grpc;
+use crate::otel::proto::{ExportMetricsServiceRequest, ExportMetricsServiceResponse};
+use crate::otel::{self, RequestBuilder};
+use crate::progress;
+use prost::Message;
+use reqwest::{Client, StatusCode};
+use snafu::ResultExt;
+use std::time::Duration;
+#[cfg(feature = "otel-grpc")]
+use tonic::{client::Grpc, transport::Channel};
+
+/// Sink of the InfluxDB v2 write API.
+#[derive(Debug, Clone)]
+pub struct InfluxSink {
+    client: Client,
+    url: String,
+    bucket: String,
+    org: Option<String>,
+    token: Option<String>,
+    max_retries: u32,
+    retry_interval: Duration,
+}
+
+impl InfluxSink {
+    /// Create a sink of the bucket of the server, e.g. `http://localhost:8086`.
+    pub fn new(url: &str, bucket: &str) -> InfluxSink {
+        InfluxSink {
+            client: Client::new(),
+            url: format!("{}/api/v2/write", url.trim_end_matches('/')),
+            bucket: bucket.to_string(),
+            org: None,
+            token: None,
+            max_retries: 3,
+            retry_interval: Duration::from_secs(1),
+        }
+    }
+
+    /// Set the organization name.
+    pub fn org(mut self, org: &str) -> InfluxSink {
+        self.org = Some(org.to_string());
+        self
+    }
+
+    /// Set the API token.
+    pub fn token(mut self, token: &str) -> InfluxSink {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    /// Set the maximum number of retries and the interval before the first retry,
+    /// which is doubled after each retry.
+    pub fn retries(mut self, max_retries: u32, interval: Duration) -> InfluxSink {
+        self.max_retries = max_retries;
+        self.retry_interval = interval;
+        self
+    }
+
+    /// Write the lines, each line ends with a newline.
+    ///
+    /// Requests that are throttled, failed by server errors or failed to send are
+    /// retried with exponential backoff.
+    pub async fn write_lines(&self, lines: &str) -> Result<()> {
+        let mut query = vec![("bucket", self.bucket.as_str()), ("precision", "ns")];
+        if let Some(org) = &self.org {
+            query.push(("org", org));
+        }
+        let mut request = self
+            .client
+            .post(&self.url)
+            .query(&query)
+            .header("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.token {
+            request = request.header("Authorization", format!("Token {token}"));
+        }
+
+        let mut interval = self.retry_interval;
+        let mut retries = 0;
+        loop {
+            let request = request
+                .try_clone()
+                .expect("body of the request is not a stream")
+                .body(lines.to_string());
+            let retry_after = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    progress::add_lines_written(lines.matches('\n').count());
+                    return Ok(());
+                }
+                Ok(response) if retries < self.max_retries && is_retryable(response.status()) => {
+                    response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|v| v.to_str().ok()?.parse().ok())
+                        .map(Duration::from_secs)
+                }
+                Err(_) if retries < self.max_retries => None,
+                Ok(response) => {
+                    return RequestStatusSnafu {
+                        url: self.url.as_str(),
+                        status: response.status().as_u16(),
+                        body: response.text().await.unwrap_or_default(),
+                    }
+                    .fail();
+                }
+                Err(e) => {
+                    return Err(e).context(SendAsyncRequestSnafu {
+                        url: self.url.as_str(),
+                    });
+                }
+            };
+
+            tokio::time::sleep(retry_after.unwrap_or(interval)).await;
+            interval *= 2;
+            retries += 1;
+        }
+    }
+}
+
+/// Returns whether requests failed with the status should be retried.
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Sink of an OTLP metrics collector, lines are pushed as gauges.
+#[derive(Debug)]
+pub struct OtlpSink {
+    exporter: Exporter,
+    resource_tags: Vec<String>,
+}
+
+/// Exporter of requests to a collector.
+#[derive(Debug)]
+enum Exporter {
+    Http {
+        client: Client,
+        url: String,
+    },
+    #[cfg(feature = "otel-grpc")]
+    Grpc(Box<Grpc<Channel>>),
+}
+
+impl OtlpSink {
+    /// Create a sink of the HTTP endpoint, e.g. `http://localhost:4318/v1/metrics`.
+    pub fn http(endpoint: &str) -> OtlpSink {
+        OtlpSink {
+            exporter: Exporter::Http {
+                client: Client::new(),
+                url: endpoint.to_string(),
+            },
+            resource_tags: Vec::new(),
+        }
+    }
+
+    /// Connect to the gRPC endpoint, e.g. `http://localhost:4317`.
+    #[cfg(feature = "otel-grpc")]
+    pub async fn grpc(endpoint: &str) -> Result<OtlpSink> {
+        Ok(OtlpSink {
+            exporter: Exporter::Grpc(Box::new(grpc::connect(endpoint).await?)),
+            resource_tags: Vec::new(),
+        })
+    }
+
+    /// Set the tags that become resource attributes instead of data point attributes.
+    pub fn resource_tags(mut self, tags: &[&str]) -> OtlpSink {
+        self.resource_tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    /// Write the lines in one export request.
+    ///
+    /// Data points rejected by the collector are logged as warnings.
+    pub async fn write_lines(&mut self, lines: &str) -> Result<()> {
+        let mut builder = RequestBuilder::new(&self.resource_tags);
+        read_lines(lines.as_bytes(), |line| builder.append_line(&line))?;
+        let num_lines = builder.num_lines;
+        if num_lines == 0 {
+            return Ok(());
+        }
+        let request = builder.finish();
+
+        let response = self.exporter.export(request).await?;
+        otel::warn_rejected(&response);
+        progress::add_lines_written(num_lines);
+        Ok(())
+    }
+}
+
+impl Exporter {
+    async fn export(
+        &mut self,
+        request: ExportMetricsServiceRequest,
+    ) -> Result<ExportMetricsServiceResponse> {
+        match self {
+            Exporter::Http { client, url } => {
+                let response = client
+                    .post(url.as_str())
+                    .header("Content-Type", "application/x-protobuf")
+                    .body(request.encode_to_vec())
+                    .send()
+                    .await
+                    .context(SendAsyncRequestSnafu { url: url.as_str() })?;
+                let status = response.status();
+                if !status.is_success() {
+                    return RequestStatusSnafu {
+                        url: url.as_str(),
+                        status: status.as_u16(),
+                        body: response.text().await.unwrap_or_default(),
+                    }
+                    .fail();
+                }
+                let body = response
+                    .bytes()
+                    .await
+                    .map_err(std::io::Error::other)
+                    .context(ReadInputSnafu)?;
+                // Collectors may respond in JSON or with an empty body.
+                Ok(ExportMetricsServiceResponse::decode(body).unwrap_or_default())
+            }
+            #[cfg(feature = "otel-grpc")]
+            Exporter::Grpc(grpc) => grpc::export(grpc, request).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve_statuses;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_write_lines() {
+        let runtime = Runtime::new().unwrap();
+        let lines = "cpu,host=a usage=1 1\ncpu,host=a usage=2 2\n";
+
+        let (url, handle) = serve_statuses(vec![503, 204]);
+        let sink = InfluxSink::new(&url, "bucket").retries(1, Duration::from_millis(1));
+        runtime.block_on(sink.write_lines(lines)).unwrap();
+        let bodies: Vec<_> = handle.join().unwrap().into_iter().map(|(_, b)| b).collect();
+        assert_eq!(vec![lines.as_bytes(); 2], bodies);
+
+        let (url, handle) = serve_statuses(vec![400]);
+        let sink = InfluxSink::new(&url, "bucket").retries(1, Duration::from_millis(1));
+        let err = runtime.block_on(sink.write_lines(lines)).unwrap_err();
+        assert!(err.to_string().contains("status 400"), "{err}");
+        handle.join().unwrap();
+
+        let (url, handle) = serve_statuses(vec![200]);
+        let mut sink = OtlpSink::http(&url).resource_tags(&["host"]);
+        runtime.block_on(sink.write_lines(lines)).unwrap();
+        let requests = handle.join().unwrap();
+        let request = ExportMetricsServiceRequest::decode(requests[0].1.as_slice()).unwrap();
+        let resource_metrics = &request.resource_metrics;
+        assert_eq!(1, resource_metrics.len());
+        let metrics = &resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(1, metrics.len());
+        assert_eq!("cpu_usage", metrics[0].name);
+    }
+}
@@ -0,0 +1,247 @@
+//! Tools for VictoriaMetrics target.
+
+use crate::error::{
+    InvalidSampleSnafu, LineSnafu, ParseJsonSnafu, ReadInputSnafu, Result, WriteJsonSnafu,
+    WriteOutputSnafu,
+};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::prom::exposition::Sample;
+use crate::prom::{
+    line_protocol_to_write_request, samples_to_csv, samples_to_line_protocol, SampleFormat,
+};
+use crate::reject;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// VictoriaMetrics command.
+#[derive(Debug, Parser)]
+pub struct VmCommand {
+    #[clap(subcommand)]
+    subcmd: VmSubcommand,
+}
+
+impl VmCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            VmSubcommand::FromExport(c) => c.run(),
+            VmSubcommand::ToImport(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for VictoriaMetrics.
+#[derive(Debug, Parser)]
+enum VmSubcommand {
+    /// JSON export to line protocol or CSV.
+    FromExport(FromExport),
+    /// Line protocol to JSON import.
+    ToImport(ToImport),
+}
+
+/// Name of the label of metric names.
+const NAME_LABEL: &str = "__name__";
+
+/// A series in the JSON line format of `/api/v1/export` and `/api/v1/import`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct JsonSeries {
+    metric: BTreeMap<String, String>,
+    values: Vec<Option<f64>>,
+    /// Timestamps in millisecond.
+    timestamps: Vec<i64>,
+}
+
+impl JsonSeries {
+    /// Append samples of the series, `null` values are skipped.
+    fn push_samples<'a>(&'a self, samples: &mut Vec<Sample<'a>>) -> Result<()> {
+        let name = self.metric.get(NAME_LABEL).context(InvalidSampleSnafu {
+            reason: "series has no __name__",
+        })?;
+        ensure!(
+            self.values.len() == self.timestamps.len(),
+            InvalidSampleSnafu {
+                reason: format!(
+                    "series has {} values and {} timestamps",
+                    self.values.len(),
+                    self.timestamps.len()
+                ),
+            }
+        );
+        let labels: Vec<_> = self
+            .metric
+            .iter()
+            .filter(|(k, _)| *k != NAME_LABEL)
+            .map(|(k, v)| (k.as_str(), v.clone()))
+            .collect();
+
+        for (value, ts) in self.values.iter().zip(&self.timestamps) {
+            let Some(value) = value else {
+                continue;
+            };
+            let timestamp = ts.checked_mul(1_000_000).context(InvalidSampleSnafu {
+                reason: format!("timestamp {ts} is out of range"),
+            })?;
+            samples.push(Sample {
+                name,
+                labels: labels.clone(),
+                value: *value,
+                timestamp: Some(timestamp),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Convert JSON lines exported by `/api/v1/export` to line protocol or CSV file.
+///
+/// Each sample is a line with the metric name as the measurement, labels as tags and the
+/// `value` field, or a CSV row with the `measurement`, label, `value` and `timestamp`
+/// columns.
+#[derive(Debug, Parser)]
+struct FromExport {
+    /// Input JSON lines file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Format of the output.
+    #[arg(long, value_enum, default_value_t = SampleFormat::Lp)]
+    format: SampleFormat,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromExport {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let series = read_export(input)?;
+        let mut samples = Vec::new();
+        for s in &series {
+            s.push_samples(&mut samples)?;
+        }
+
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = match self.format {
+            SampleFormat::Lp => samples_to_line_protocol(&samples, output)?,
+            SampleFormat::Csv => samples_to_csv(&samples, output)?,
+        };
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol file to JSON lines for `/api/v1/import`.
+///
+/// Each field is a series named `{measurement}_{field}` with tags as its labels, integer
+/// and boolean fields are converted to float and string fields are skipped.
+#[derive(Debug, Parser)]
+struct ToImport {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output JSON lines file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToImport {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let mut output = line_protocol_to_import(input, output)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+fn read_export<R: Read>(source: R) -> Result<Vec<JsonSeries>> {
+    let reader = BufReader::new(source);
+    let mut series = Vec::new();
+    for (i, text) in reader.lines().enumerate() {
+        let text = text.context(ReadInputSnafu)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        let result = serde_json::from_str(&text)
+            .context(ParseJsonSnafu)
+            .context(LineSnafu { line: i + 1 });
+        if let Some(s) = reject::check(result, || &text)? {
+            series.push(s);
+        }
+    }
+    Ok(series)
+}
+
+fn line_protocol_to_import<R: Read, W: Write>(source: R, dest: W) -> Result<W> {
+    let request = line_protocol_to_write_request(source)?;
+    let mut writer = BufWriter::new(dest);
+    for ts in request.timeseries {
+        let series = JsonSeries {
+            metric: ts
+                .labels
+                .into_iter()
+                .map(|label| (label.name, label.value))
+                .collect(),
+            values: ts.samples.iter().map(|s| Some(s.value)).collect(),
+            timestamps: ts.samples.iter().map(|s| s.timestamp).collect(),
+        };
+        serde_json::to_writer(&mut writer, &series).context(WriteJsonSnafu)?;
+        writer.write_all(b"\n").context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vm_export_import() {
+        let input = "cpu,host=a usage=1.5 1451606410000000000
+cpu,host=a usage=2.5 1451606400000000000
+mem,host=a used=3i,note=\"x\" 1451606400000000000
+";
+        let output = line_protocol_to_import(input.as_bytes(), Vec::new()).unwrap();
+        let jsonl = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"{"metric":{"__name__":"cpu_usage","host":"a"},"values":[2.5,1.5],"timestamps":[1451606400000,1451606410000]}
+{"metric":{"__name__":"mem_used","host":"a"},"values":[3.0],"timestamps":[1451606400000]}
+"#,
+            jsonl
+        );
+
+        let jsonl = format!(
+            "{jsonl}{}\n",
+            r#"{"metric":{"__name__":"up"},"values":[null,1],"timestamps":[1451606400000,1451606410000]}"#
+        );
+        let series = read_export(jsonl.as_bytes()).unwrap();
+        let mut samples = Vec::new();
+        for s in &series {
+            s.push_samples(&mut samples).unwrap();
+        }
+        let output = samples_to_line_protocol(&samples, Vec::new()).unwrap();
+        assert_eq!(
+            "cpu_usage,host=a value=2.5 1451606400000000000
+cpu_usage,host=a value=1.5 1451606410000000000
+mem_used,host=a value=3 1451606400000000000
+up value=1 1451606410000000000
+",
+            String::from_utf8(output).unwrap()
+        );
+
+        let series =
+            read_export(r#"{"metric":{"job":"a"},"values":[1],"timestamps":[1]}"#.as_bytes())
+                .unwrap();
+        assert!(series[0].push_samples(&mut Vec::new()).is_err());
+        let err = read_export("\n{}\n".as_bytes()).unwrap_err();
+        assert_eq!("Error at line 2", err.to_string());
+    }
+}
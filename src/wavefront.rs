@@ -0,0 +1,394 @@
+//! Tools for Wavefront data format.
+
+use crate::error::{InvalidPointSnafu, LineSnafu, ReadInputSnafu, Result, WriteOutputSnafu};
+use crate::influx::{push_fields, push_series, read_lines};
+use crate::io::{create_output, open_input, CompressionArgs, Output};
+use crate::reject;
+use chrono::Utc;
+use clap::Parser;
+use influxdb_line_protocol::FieldValue;
+use snafu::{ensure, OptionExt, ResultExt};
+use std::collections::BTreeMap;
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+/// Wavefront command.
+#[derive(Debug, Parser)]
+pub struct WavefrontCommand {
+    #[clap(subcommand)]
+    subcmd: WavefrontSubcommand,
+}
+
+impl WavefrontCommand {
+    /// Run this command.
+    pub fn run(self) -> Result<()> {
+        match self.subcmd {
+            WavefrontSubcommand::ToLp(c) => c.run(),
+            WavefrontSubcommand::FromLp(c) => c.run(),
+        }
+    }
+}
+
+/// Subcommands for Wavefront.
+#[derive(Debug, Parser)]
+enum WavefrontSubcommand {
+    /// Wavefront data format to line protocol.
+    ToLp(ToLp),
+    /// Line protocol to Wavefront data format.
+    FromLp(FromLp),
+}
+
+/// Name of the field of values.
+const VALUE_FIELD: &str = "value";
+
+/// Convert Wavefront data format file to line protocol file.
+///
+/// Each line is `<metric> <value> [<timestamp>] source=<source> [<tag>=<value>...]` with
+/// the timestamp in second, names and values may be quoted by `"`. The metric is the
+/// measurement with the `value` field, the source and point tags are tags.
+#[derive(Debug, Parser)]
+struct ToLp {
+    /// Input Wavefront data format file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output line protocol file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Tag of the source, `host` is also a source in Wavefront.
+    #[arg(long, default_value = "source")]
+    source_tag: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl ToLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = wavefront_to_line_protocol(input, output, &self.source_tag, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Convert line protocol file to Wavefront data format file.
+///
+/// Each numeric field is a metric named `{measurement}.{field}`, or the measurement for
+/// the `value` field. Booleans are converted to 0 and 1 and string fields are skipped.
+/// The source tag is the source and other tags are point tags, lines without the source
+/// tag have no source and get the one of the proxy.
+#[derive(Debug, Parser)]
+struct FromLp {
+    /// Input line protocol file path, `-` for stdin.
+    #[arg(short, long)]
+    input: String,
+    /// Output Wavefront data format file path, `-` for stdout.
+    #[arg(short, long, default_value = "-")]
+    output: String,
+    /// Tag of the source.
+    #[arg(long, default_value = "source")]
+    source_tag: String,
+    #[command(flatten)]
+    compression: CompressionArgs,
+}
+
+impl FromLp {
+    fn run(self) -> Result<()> {
+        let input = open_input(&self.input, self.compression.input_compression)?;
+        let output = create_output(&self.output, self.compression.output_compression)?;
+        let now = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let mut output = line_protocol_to_wavefront(input, output, &self.source_tag, now)?;
+        output.finish().context(WriteOutputSnafu)
+    }
+}
+
+/// Split the text into tokens by whitespaces outside quotes.
+fn tokens(text: &str) -> Result<Vec<&str>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if quoted {
+            match c {
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                tokens.push(&text[s..i]);
+            }
+        } else {
+            start.get_or_insert(i);
+            quoted = c == '"';
+        }
+    }
+    ensure!(
+        !quoted,
+        InvalidPointSnafu {
+            reason: format!("unclosed quote in {text:?}"),
+        }
+    );
+    tokens.extend(start.map(|s| &text[s..]));
+    Ok(tokens)
+}
+
+/// Returns the token without quotes and escapes.
+fn unquote(token: &str) -> String {
+    let Some(inner) = token.strip_prefix('"').and_then(|t| t.strip_suffix('"')) else {
+        return token.to_string();
+    };
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => value.extend(chars.next()),
+            _ => value.push(c),
+        }
+    }
+    value
+}
+
+/// Split the `key=value` token, the key may be quoted.
+fn split_tag(token: &str) -> Option<(String, String)> {
+    let eq = if token.starts_with('"') {
+        let mut escaped = false;
+        let end = token.char_indices().skip(1).find_map(|(i, c)| {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => return Some(i),
+                _ => {}
+            }
+            None
+        })?;
+        (token[end + 1..].starts_with('=')).then_some(end + 1)?
+    } else {
+        token.find('=')?
+    };
+    Some((unquote(&token[..eq]), unquote(&token[eq + 1..])))
+}
+
+/// Push the Wavefront line as a line of line protocol.
+///
+/// Non-finite values are skipped as line protocol doesn't support them, missing
+/// timestamps use `now`. The `host` tag is the source if there is no `source` tag.
+fn push_wavefront_line(line: &mut String, text: &str, source_tag: &str, now: i64) -> Result<()> {
+    let invalid = || InvalidPointSnafu {
+        reason: format!("invalid Wavefront line {text:?}"),
+    };
+    let tokens = tokens(text)?;
+    ensure!(
+        !tokens.first().is_some_and(|t| t.starts_with('!')),
+        InvalidPointSnafu {
+            reason: format!("histogram line {text:?} is not supported"),
+        }
+    );
+    let mut tokens = tokens.into_iter().peekable();
+    let metric = tokens.next().map(unquote).with_context(invalid)?;
+    let value: f64 = tokens
+        .next()
+        .and_then(|v| v.parse().ok())
+        .with_context(invalid)?;
+    let timestamp = match tokens.next_if(|t| !t.contains('=')) {
+        None => now,
+        Some(ts) => ts
+            .parse::<f64>()
+            .ok()
+            .map(|ts| (ts * 1e9) as i64)
+            .with_context(invalid)?,
+    };
+    if !value.is_finite() {
+        return Ok(());
+    }
+    ensure!(
+        !metric.is_empty(),
+        InvalidPointSnafu {
+            reason: format!("line {text:?} has no metric"),
+        }
+    );
+
+    let mut tags = BTreeMap::new();
+    let mut source = None;
+    let mut host = None;
+    for token in tokens {
+        let (k, v) = split_tag(token).with_context(invalid)?;
+        match k.as_str() {
+            "source" => source = Some(v),
+            "host" => host = Some(v),
+            _ => {
+                tags.insert(k, v);
+            }
+        }
+    }
+    match (source, host) {
+        (Some(source), host) => {
+            tags.extend(host.map(|host| ("host".to_string(), host)));
+            tags.insert(source_tag.to_string(), source);
+        }
+        (None, Some(host)) => {
+            tags.insert(source_tag.to_string(), host);
+        }
+        (None, None) => {}
+    }
+
+    push_series(
+        line,
+        &metric,
+        tags.iter().map(|(k, v)| (k.as_str(), v.as_str())),
+    );
+    push_fields(line, [(VALUE_FIELD, &FieldValue::F64(value))]);
+    writeln!(line, " {timestamp}").unwrap();
+    Ok(())
+}
+
+fn wavefront_to_line_protocol<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    source_tag: &str,
+    now: i64,
+) -> Result<W> {
+    let reader = BufReader::new(source);
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    for (i, text) in reader.lines().enumerate() {
+        let text = text.context(ReadInputSnafu)?;
+        if text.trim().is_empty() {
+            continue;
+        }
+        line.clear();
+        let result = push_wavefront_line(&mut line, &text, source_tag, now)
+            .context(LineSnafu { line: i + 1 });
+        if reject::check(result, || &text)?.is_none() {
+            continue;
+        }
+        writer
+            .write_all(line.as_bytes())
+            .context(WriteOutputSnafu)?;
+    }
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+/// Push the value in quotes.
+fn push_quoted(line: &mut String, value: &str) {
+    line.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            line.push('\\');
+        }
+        line.push(c);
+    }
+    line.push('"');
+}
+
+/// Push the tag key, chars other than letters, digits, `-`, `_` and `.` are replaced by
+/// `_`.
+fn push_key(line: &mut String, key: &str) {
+    line.extend(key.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+            c
+        } else {
+            '_'
+        }
+    }));
+}
+
+fn line_protocol_to_wavefront<R: Read, W: Write>(
+    source: R,
+    dest: W,
+    source_tag: &str,
+    now: i64,
+) -> Result<W> {
+    let mut writer = BufWriter::new(dest);
+    let mut line = String::new();
+    read_lines(source, |parsed| {
+        let timestamp = parsed.timestamp.unwrap_or(now).div_euclid(1_000_000_000);
+        let tags = parsed.series.tag_set.iter().flatten();
+        for (key, value) in &parsed.field_set {
+            let value = match value {
+                FieldValue::I64(v) => *v as f64,
+                FieldValue::U64(v) => *v as f64,
+                FieldValue::F64(v) => *v,
+                FieldValue::Boolean(v) => f64::from(u8::from(*v)),
+                FieldValue::String(_) => continue,
+            };
+
+            line.clear();
+            if key.as_str() == VALUE_FIELD {
+                push_quoted(&mut line, &parsed.series.measurement);
+            } else {
+                push_quoted(&mut line, &format!("{}.{}", parsed.series.measurement, key));
+            }
+            write!(line, " {value} {timestamp}").unwrap();
+            let source = tags.clone().find(|(k, _)| k.as_str() == source_tag);
+            if let Some((_, v)) = source {
+                line.push_str(" source=");
+                push_quoted(&mut line, v);
+            }
+            for (k, v) in tags.clone().filter(|(k, _)| k.as_str() != source_tag) {
+                line.push(' ');
+                push_key(&mut line, k);
+                line.push('=');
+                push_quoted(&mut line, v);
+            }
+            line.push('\n');
+            writer
+                .write_all(line.as_bytes())
+                .context(WriteOutputSnafu)?;
+        }
+        Ok(())
+    })?;
+
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())
+        .context(WriteOutputSnafu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wavefront_line_protocol() {
+        let input = r#"system.cpu.loadavg.1m 0.03 1382754475 source=test1.wavefront.com
+"disk used" 12 source="host 1" "mount point"="/data \"x\"" host=h
+request.count 3 host=a env=prod
+request.count NaN 1382754475 source=a
+"#;
+        let output = wavefront_to_line_protocol(input.as_bytes(), Vec::new(), "source", 7).unwrap();
+        let lp = String::from_utf8(output).unwrap();
+        assert_eq!(
+            r#"system.cpu.loadavg.1m,source=test1.wavefront.com value=0.03 1382754475000000000
+disk\ used,host=h,mount\ point=/data\ "x",source=host\ 1 value=12 7
+request.count,env=prod,source=a value=3 7
+"#,
+            lp
+        );
+        assert!(split_tag("\"a\"b=c").is_none());
+        assert!(tokens("a 1 source=\"b").is_err());
+        let input = "!M 1382754475 #10 1.5 request.latency source=a";
+        let err =
+            wavefront_to_line_protocol(input.as_bytes(), Vec::new(), "source", 0).unwrap_err();
+        assert_eq!("Error at line 1", err.to_string());
+
+        let input = format!("{lp}cpu,host=a usage=1i,idle=true,note=\"x\" 1382754476000000000\n");
+        let output = line_protocol_to_wavefront(input.as_bytes(), Vec::new(), "source", 0).unwrap();
+        assert_eq!(
+            r#""system.cpu.loadavg.1m" 0.03 1382754475 source="test1.wavefront.com"
+"disk used" 12 0 source="host 1" host="h" mount_point="/data \"x\""
+"request.count" 3 0 source="a" env="prod"
+"cpu.usage" 1 1382754476 host="a"
+"cpu.idle" 1 1382754476 host="a"
+"#,
+            String::from_utf8(output).unwrap()
+        );
+    }
+}